@@ -0,0 +1,89 @@
+use std::process::Command;
+
+/// Embeds what `kitsune-rendercore version` reports: the git revision, the
+/// build date, the enabled features and the versions of the graphics and
+/// Wayland crates the binary was linked against.
+fn main() {
+    let describe = Command::new("git")
+        .args(["describe", "--always", "--dirty", "--tags"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=KRC_GIT_DESCRIBE={describe}");
+
+    // SOURCE_DATE_EPOCH keeps packaged builds reproducible.
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let epoch = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|raw| raw.trim().parse::<u64>().ok())
+        .or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs())
+        });
+    let date = epoch.map_or_else(|| "unknown".to_string(), civil_date);
+    println!("cargo:rustc-env=KRC_BUILD_DATE={date}");
+
+    let mut features = std::env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|name| name.to_ascii_lowercase().replace('_', "-"))
+        })
+        .filter(|name| name != "default")
+        .collect::<Vec<_>>();
+    features.sort();
+    println!("cargo:rustc-env=KRC_BUILD_FEATURES={}", features.join(","));
+
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    for (crate_name, var) in [
+        ("wgpu", "KRC_WGPU_VERSION"),
+        ("wayland-client", "KRC_WAYLAND_CLIENT_VERSION"),
+    ] {
+        let version = locked_version(&lock, crate_name).unwrap_or_else(|| "unknown".to_string());
+        println!("cargo:rustc-env={var}={version}");
+    }
+
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    for path in [".git/HEAD", ".git/index", ".git/refs"] {
+        if std::path::Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+}
+
+/// The version of `name` in Cargo.lock: the `version` line after its
+/// `name = "..."` line.
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+    let wanted = format!("name = \"{name}\"");
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() == wanted {
+            return lines
+                .next()?
+                .trim()
+                .strip_prefix("version = \"")?
+                .strip_suffix('"')
+                .map(str::to_string);
+        }
+    }
+    None
+}
+
+/// `YYYY-MM-DD` (UTC) for seconds since the epoch.
+fn civil_date(secs: u64) -> String {
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
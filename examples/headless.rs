@@ -0,0 +1,104 @@
+//! Drives the renderer from a supervisor thread with a backend that draws
+//! nothing: pause, resume and shutdown through a `RenderHandle`, events
+//! through a callback.
+//!
+//!     cargo run --example headless
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use kitsune_rendercore::{
+    LayerBackend, LayerRole, MonitorInfo, MonitorSurfaceSpec, OutputRuntimeStatus, OutputTransform,
+    RenderCoreBuilder, RenderCoreConfig,
+};
+
+/// One virtual output that only counts the frames it is asked for.
+struct HeadlessBackend {
+    frames: Arc<AtomicU64>,
+}
+
+impl LayerBackend for HeadlessBackend {
+    fn name(&self) -> &'static str {
+        "headless-example"
+    }
+
+    fn bootstrap(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn discover_monitors(&mut self) -> Result<Vec<MonitorInfo>, String> {
+        Ok(vec![MonitorInfo {
+            name: "HEADLESS-1".to_string(),
+            width: 1920,
+            height: 1080,
+            refresh_hz: 60,
+            transform: OutputTransform::Normal,
+        }])
+    }
+
+    fn build_surfaces(
+        &mut self,
+        monitors: &[MonitorInfo],
+    ) -> Result<Vec<MonitorSurfaceSpec>, String> {
+        Ok(monitors
+            .iter()
+            .map(|monitor| MonitorSurfaceSpec {
+                monitor: monitor.clone(),
+                layer: LayerRole::Background,
+            })
+            .collect())
+    }
+
+    fn render_frame(&mut self, _surfaces: &[MonitorSurfaceSpec]) -> Result<(), String> {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn output_status(&self) -> Vec<OutputRuntimeStatus> {
+        Vec::new()
+    }
+}
+
+fn main() -> Result<(), String> {
+    let config = RenderCoreConfig {
+        target_fps: 30,
+        quiet_hours: None,
+        report_interval: None,
+        ..RenderCoreConfig::default()
+    };
+
+    let frames = Arc::new(AtomicU64::new(0));
+    let (core, handle) = RenderCoreBuilder::new(config)
+        .backend(Box::new(HeadlessBackend {
+            frames: Arc::clone(&frames),
+        }))
+        .on_event(|event| println!("event: {} on {}", event.name(), event.monitor()))
+        .build();
+
+    // The supervisor: any thread may hold a handle.
+    let supervisor = thread::spawn(move || -> Result<(), String> {
+        thread::sleep(Duration::from_secs(1));
+        println!("frames after 1s: {}", frames.load(Ordering::Relaxed));
+        handle.pause("supervisor")?;
+        let paused_at = frames.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_secs(1));
+        println!(
+            "frames drawn while paused: {}",
+            frames.load(Ordering::Relaxed) - paused_at
+        );
+        handle.resume()?;
+        // This backend has no video map, so the renderer refuses the switch.
+        if let Err(err) = handle.set_video("HEADLESS-1", "/srv/loops/rain.mp4") {
+            println!("set_video refused: {err}");
+        }
+        thread::sleep(Duration::from_millis(500));
+        handle.shutdown()
+    });
+
+    core.run()?;
+    supervisor
+        .join()
+        .map_err(|_| "supervisor thread panicked".to_string())?
+}
@@ -0,0 +1,108 @@
+//! A backend whose frames are computed instead of decoded: a plasma drawn on
+//! the CPU into an offscreen buffer, 90 frames, then the last one is written
+//! as a PPM next to the system temp files.
+//!
+//!     cargo run --example procedural
+
+use std::sync::{Arc, Mutex};
+
+use kitsune_rendercore::{
+    LayerBackend, LayerRole, MonitorInfo, MonitorSurfaceSpec, OutputRuntimeStatus, OutputTransform,
+    RenderCoreBuilder, RenderCoreConfig,
+};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 180;
+
+/// RGB rows of the last frame drawn.
+type Frame = Arc<Mutex<Vec<u8>>>;
+
+struct PlasmaBackend {
+    frame: Frame,
+    tick: u32,
+}
+
+impl PlasmaBackend {
+    fn draw(&self, pixels: &mut [u8]) {
+        let t = self.tick as f32 * 0.08;
+        for (i, rgb) in pixels.chunks_exact_mut(3).enumerate() {
+            let x = (i as u32 % WIDTH) as f32 / WIDTH as f32;
+            let y = (i as u32 / WIDTH) as f32 / HEIGHT as f32;
+            let v = (x * 10.0 + t).sin() + (y * 8.0 - t).sin() + ((x + y) * 6.0 + t).sin();
+            let channel = |phase: f32| (((v + phase).sin() * 0.5 + 0.5) * 255.0) as u8;
+            rgb.copy_from_slice(&[channel(0.0), channel(2.1), channel(4.2)]);
+        }
+    }
+}
+
+impl LayerBackend for PlasmaBackend {
+    fn name(&self) -> &'static str {
+        "plasma-example"
+    }
+
+    fn bootstrap(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn discover_monitors(&mut self) -> Result<Vec<MonitorInfo>, String> {
+        Ok(vec![MonitorInfo {
+            name: "OFFSCREEN-1".to_string(),
+            width: WIDTH,
+            height: HEIGHT,
+            refresh_hz: 30,
+            transform: OutputTransform::Normal,
+        }])
+    }
+
+    fn build_surfaces(
+        &mut self,
+        monitors: &[MonitorInfo],
+    ) -> Result<Vec<MonitorSurfaceSpec>, String> {
+        Ok(monitors
+            .iter()
+            .map(|monitor| MonitorSurfaceSpec {
+                monitor: monitor.clone(),
+                layer: LayerRole::Background,
+            })
+            .collect())
+    }
+
+    fn render_frame(&mut self, _surfaces: &[MonitorSurfaceSpec]) -> Result<(), String> {
+        let mut pixels = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+        self.draw(&mut pixels);
+        self.tick += 1;
+        *self.frame.lock().map_err(|_| "frame lock poisoned")? = pixels;
+        Ok(())
+    }
+
+    fn output_status(&self) -> Vec<OutputRuntimeStatus> {
+        Vec::new()
+    }
+}
+
+fn main() -> Result<(), String> {
+    let config = RenderCoreConfig {
+        target_fps: 30,
+        max_frames: Some(90),
+        quiet_hours: None,
+        report_interval: None,
+        ..RenderCoreConfig::default()
+    };
+
+    let frame = Frame::default();
+    let (core, _handle) = RenderCoreBuilder::new(config)
+        .backend(Box::new(PlasmaBackend {
+            frame: Arc::clone(&frame),
+            tick: 0,
+        }))
+        .build();
+    core.run()?;
+
+    let pixels = frame.lock().map_err(|_| "frame lock poisoned")?;
+    let path = std::env::temp_dir().join("krc-plasma.ppm");
+    let mut ppm = format!("P6\n{WIDTH} {HEIGHT}\n255\n").into_bytes();
+    ppm.extend_from_slice(&pixels);
+    std::fs::write(&path, ppm).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    println!("last frame written to {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,58 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
+cd "$ROOT_DIR"
+
+usage() {
+  cat <<'USAGE'
+Usage: ./scripts/check-features.sh [OPTIONS]
+
+Builds, lints and tests every supported feature combination, so code gated
+on one feature cannot break a build that leaves it out.
+
+Options:
+  --check-only        Only run 'cargo check' for each combination
+  -h, --help          Show this help
+USAGE
+}
+
+check_only=false
+while (($#)); do
+  case "$1" in
+    --check-only) check_only=true ;;
+    -h|--help)
+      usage
+      exit 0
+      ;;
+    *)
+      echo "Unknown option: $1" >&2
+      usage
+      exit 1
+      ;;
+  esac
+  shift
+done
+
+# Each line is one set of cargo flags; the empty line is the default build.
+matrix=(
+  ""
+  "--no-default-features"
+  "--no-default-features --features wayland-layer"
+  "--features wayland-layer"
+  "--features dbus,wayland-layer"
+  "--features http-control"
+  "--features update-check"
+)
+
+for flags in "${matrix[@]}"; do
+  echo "==> features: ${flags:-default}"
+  # shellcheck disable=SC2086 # flags are split on purpose
+  if $check_only; then
+    cargo check --all-targets $flags
+  else
+    cargo clippy --all-targets $flags -- -D warnings
+    cargo test $flags
+  fi
+done
+echo "[ok] ${#matrix[@]} feature combinations"
@@ -0,0 +1,796 @@
+//! Monitor aliases: `[aliases]` in the config file names each output once
+//! (`main = "desc:LG ULTRAGEAR"`, `side = "DP-3"`), and map keys,
+//! `--monitor` arguments and `KRC_OUTPUTS` entries can then use the alias
+//! while connector names shift between boots and docks.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::config_file::{ConfigFile, config_file_path_from_env, set_config_value};
+use crate::control;
+use crate::json::{JsonValue, parse_json};
+use crate::user_path::display_text;
+use crate::video_map::{
+    MapDocument, VideoMap, load_map_document, map_file_path_from_env, set_monitor_video,
+    unset_monitor_video,
+};
+
+const SECTION: &str = "aliases";
+
+/// What an alias finally points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// An output name (connector), matched exactly.
+    Name(String),
+    /// Text found in the output description, case-insensitive.
+    Desc(String),
+}
+
+impl Selector {
+    pub fn matches(&self, name: Option<&str>, description: Option<&str>) -> bool {
+        match self {
+            Self::Name(want) => name == Some(want.as_str()),
+            Self::Desc(want) => {
+                description.is_some_and(|d| d.to_lowercase().contains(&want.to_lowercase()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{name}"),
+            Self::Desc(text) => write!(f, "desc:{text}"),
+        }
+    }
+}
+
+/// The `[aliases]` table, alias name to target as written: `desc:TEXT`,
+/// `name:OUTPUT`, another alias, or a bare output name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aliases {
+    entries: BTreeMap<String, String>,
+}
+
+/// One alias against the outputs a renderer sees, for `status`.
+#[derive(Debug, Clone)]
+pub struct AliasReport {
+    pub alias: String,
+    pub target: String,
+    pub outputs: Vec<String>,
+    /// Why the alias names no output or several, or cannot be resolved.
+    pub problem: Option<String>,
+}
+
+impl Aliases {
+    pub fn load_from_env() -> Self {
+        Self::from_config(&ConfigFile::load_from_env())
+    }
+
+    pub fn from_config(config: &ConfigFile) -> Self {
+        let entries = config
+            .section(SECTION)
+            .map(|section| {
+                section
+                    .iter()
+                    .map(|(alias, target)| (alias.clone(), target.trim().to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &BTreeMap<String, String> {
+        &self.entries
+    }
+
+    pub fn is_alias(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Follows `alias` through other aliases to the output it names.
+    pub fn resolve(&self, alias: &str) -> Result<Selector, String> {
+        let mut chain = vec![alias];
+        let mut target = self
+            .entries
+            .get(alias)
+            .ok_or_else(|| format!("unknown alias '{alias}'"))?
+            .as_str();
+        loop {
+            if let Some(text) = target.strip_prefix("desc:") {
+                return match text.trim() {
+                    "" => Err(format!("alias '{alias}': desc: needs some text")),
+                    text => Ok(Selector::Desc(text.to_string())),
+                };
+            }
+            if let Some(name) = target.strip_prefix("name:") {
+                return match name.trim() {
+                    "" => Err(format!("alias '{alias}': name: needs an output name")),
+                    name => Ok(Selector::Name(name.to_string())),
+                };
+            }
+            if target.is_empty() {
+                return Err(format!("alias '{alias}' has an empty target"));
+            }
+            let Some(next) = self.entries.get(target) else {
+                return Ok(Selector::Name(target.to_string()));
+            };
+            if chain.contains(&target) {
+                chain.push(target);
+                return Err(format!("alias cycle: {}", chain.join(" -> ")));
+            }
+            chain.push(target);
+            target = next.as_str();
+        }
+    }
+
+    /// Every alias naming this output, sorted.
+    pub fn matching(&self, name: Option<&str>, description: Option<&str>) -> Vec<String> {
+        self.entries
+            .keys()
+            .filter(|alias| {
+                self.resolve(alias)
+                    .is_ok_and(|selector| selector.matches(name, description))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `key`, an alias or an output name, names this output. An alias
+    /// takes its name from an output called the same.
+    pub fn matches(&self, key: &str, name: Option<&str>, description: Option<&str>) -> bool {
+        if self.is_alias(key) {
+            return self
+                .resolve(key)
+                .is_ok_and(|selector| selector.matches(name, description));
+        }
+        name == Some(key)
+    }
+
+    /// The map key of an output its aliases are `matched`: the first of them
+    /// the map has, else its own name unless an alias took it. `None` leaves
+    /// the output to the default.
+    pub fn map_key(&self, map: &VideoMap, matched: &[String], name: &str) -> Option<String> {
+        if let Some(alias) = matched.iter().find(|alias| map.contains_key(*alias)) {
+            return Some(alias.clone());
+        }
+        (!self.is_alias(name) || matched.iter().any(|alias| alias == name))
+            .then(|| name.to_string())
+    }
+
+    /// The key `set-video --all` writes an output under: the one it is
+    /// looked up by when the map has it, else its first alias.
+    pub fn write_key(&self, map: &VideoMap, matched: &[String], name: &str) -> Option<String> {
+        let key = self.map_key(map, matched, name)?;
+        if map.contains_key(&key) {
+            return Some(key);
+        }
+        Some(matched.first().cloned().unwrap_or(key))
+    }
+
+    /// Each alias with the `outputs` (name, description) it matches.
+    pub fn report(&self, outputs: &[(String, Option<String>)]) -> Vec<AliasReport> {
+        self.entries
+            .iter()
+            .map(|(alias, target)| {
+                let (outputs, problem) = match self.resolve(alias) {
+                    Err(err) => (Vec::new(), Some(err)),
+                    Ok(selector) => {
+                        let matched = outputs
+                            .iter()
+                            .filter(|(name, description)| {
+                                selector.matches(Some(name), description.as_deref())
+                            })
+                            .map(|(name, _)| name.clone())
+                            .collect::<Vec<_>>();
+                        let problem = match matched.len() {
+                            0 => Some("matches no output".to_string()),
+                            1 => None,
+                            n => Some(format!("matches {n} outputs")),
+                        };
+                        (matched, problem)
+                    }
+                };
+                AliasReport {
+                    alias: alias.clone(),
+                    target: target.clone(),
+                    outputs,
+                    problem,
+                }
+            })
+            .collect()
+    }
+}
+
+/// An alias has to work as a map key, a `--monitor` value and a list item.
+pub fn check_alias_name(alias: &str) -> Result<(), String> {
+    if alias.is_empty() {
+        return Err("alias name is empty".to_string());
+    }
+    if !alias
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!(
+            "alias '{alias}': use letters, digits, '-', '_' and '.' only"
+        ));
+    }
+    if alias == "default" {
+        return Err("'default' is reserved for the map's default entry".to_string());
+    }
+    Ok(())
+}
+
+/// `alias list | set <ALIAS> <TARGET> | unset <ALIAS>`.
+pub fn run_alias(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        None | Some("list") => list_aliases(),
+        Some("set") => match &args[1..] {
+            [alias, target] => set_alias(alias, target.trim()),
+            _ => Err("usage: alias set <ALIAS> <TARGET>".to_string()),
+        },
+        Some("unset") => match &args[1..] {
+            [alias] => unset_alias(alias),
+            _ => Err("usage: alias unset <ALIAS>".to_string()),
+        },
+        Some("--help" | "-h" | "help") => {
+            print_alias_help();
+            Ok(())
+        }
+        Some(other) => Err(format!("unknown alias action: {other}")),
+    }
+}
+
+/// `monitors list | rename <OUTPUT|ALIAS> <NEW_ALIAS>`.
+pub fn run_monitors(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        None | Some("list") => list_monitors(),
+        Some("rename") => match &args[1..] {
+            [from, to] => rename_monitor(from, to),
+            _ => Err("usage: monitors rename <OUTPUT|ALIAS> <NEW_ALIAS>".to_string()),
+        },
+        Some("--help" | "-h" | "help") => {
+            print_monitors_help();
+            Ok(())
+        }
+        Some(other) => Err(format!("unknown monitors action: {other}")),
+    }
+}
+
+/// An output as the running renderer reports it.
+struct LiveOutput {
+    name: String,
+    description: Option<String>,
+}
+
+/// The running renderer's outputs and alias report; `None` when it does not
+/// answer.
+fn live_status() -> Option<JsonValue> {
+    control::request("status")
+        .ok()
+        .and_then(|raw| parse_json(&raw).ok())
+}
+
+fn live_outputs(status: &JsonValue) -> Vec<LiveOutput> {
+    let Some(JsonValue::Array(outputs)) = status.get("outputs") else {
+        return Vec::new();
+    };
+    outputs
+        .iter()
+        .filter_map(|out| {
+            Some(LiveOutput {
+                name: out.get("name")?.as_str()?.to_string(),
+                description: out
+                    .get("description")
+                    .and_then(JsonValue::as_str)
+                    .filter(|d| !d.is_empty())
+                    .map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+fn list_aliases() -> Result<(), String> {
+    let config_path = config_file_path_from_env()?;
+    let aliases = Aliases::from_config(&ConfigFile::load(&config_path));
+    if aliases.is_empty() {
+        println!(
+            "[info] no aliases in {} (add one with: kitsune-rendercore alias set <ALIAS> <TARGET>)",
+            config_path.display()
+        );
+        return Ok(());
+    }
+    println!("[info] aliases in {}", config_path.display());
+    let outputs = live_status().map(|status| {
+        live_outputs(&status)
+            .into_iter()
+            .map(|out| (out.name, out.description))
+            .collect::<Vec<_>>()
+    });
+    let Some(outputs) = outputs else {
+        for (alias, target) in aliases.entries() {
+            match aliases.resolve(alias) {
+                Ok(_) => println!("  {alias} = {}", display_text(target)),
+                Err(err) => println!("  {alias} = {} [warn] {err}", display_text(target)),
+            }
+        }
+        println!("[info] renderer not running: the outputs each alias matches are not known");
+        return Ok(());
+    };
+    for report in aliases.report(&outputs) {
+        let matched = if report.outputs.is_empty() {
+            "-".to_string()
+        } else {
+            report.outputs.join(", ")
+        };
+        match report.problem {
+            Some(problem) if report.outputs.len() != 1 => println!(
+                "  {} = {} -> {matched} [warn] {problem}",
+                report.alias,
+                display_text(&report.target)
+            ),
+            _ => println!(
+                "  {} = {} -> {matched}",
+                report.alias,
+                display_text(&report.target)
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn set_alias(alias: &str, target: &str) -> Result<(), String> {
+    check_alias_name(alias)?;
+    if target.is_empty() {
+        return Err("alias target is empty".to_string());
+    }
+    if target.contains(['\n', '\r']) {
+        return Err("alias target contains a newline".to_string());
+    }
+    let config_path = config_file_path_from_env()?;
+    let mut aliases = Aliases::from_config(&ConfigFile::load(&config_path));
+    aliases
+        .entries
+        .insert(alias.to_string(), target.to_string());
+    let selector = aliases.resolve(alias)?;
+    if set_config_value(&config_path, SECTION, alias, Some(target))? {
+        println!(
+            "[ok] alias {alias} = {} (config={})",
+            display_text(target),
+            config_path.display()
+        );
+    } else {
+        println!("[ok] alias {alias} = {} (unchanged)", display_text(target));
+    }
+    if let Selector::Name(name) = &selector
+        && !target.starts_with("name:")
+        && !aliases.is_alias(target)
+    {
+        println!(
+            "[info] {alias} follows the connector name {name}; desc:<TEXT> survives connector changes"
+        );
+    }
+    reload_and_show(&aliases, alias);
+    Ok(())
+}
+
+fn unset_alias(alias: &str) -> Result<(), String> {
+    let config_path = config_file_path_from_env()?;
+    let aliases = Aliases::from_config(&ConfigFile::load(&config_path));
+    if !aliases.is_alias(alias) {
+        return Err(format!("unknown alias '{alias}'"));
+    }
+    set_config_value(&config_path, SECTION, alias, None)?;
+    println!(
+        "[ok] removed alias {alias} (config={})",
+        config_path.display()
+    );
+    let dependents = aliases
+        .entries()
+        .iter()
+        .filter(|(_, target)| target.as_str() == alias)
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    if !dependents.is_empty() {
+        println!(
+            "[warn] {} pointed at {alias} and now name an output called {alias}",
+            dependents.join(", ")
+        );
+    }
+    if let Ok(map_path) = map_file_path_from_env()
+        && load_map_document(&map_path).entries.contains_key(alias)
+    {
+        println!(
+            "[warn] the map still has an entry for {alias}; it now applies only to an output named {alias}"
+        );
+    }
+    reload_renderer();
+    Ok(())
+}
+
+fn list_monitors() -> Result<(), String> {
+    let status = live_status().ok_or("renderer not running (control socket not reachable)")?;
+    let aliases = Aliases::load_from_env();
+    let outputs = live_outputs(&status);
+    if outputs.is_empty() {
+        println!("[info] the renderer reports no outputs");
+    }
+    for out in outputs {
+        let matched = aliases.matching(Some(&out.name), out.description.as_deref());
+        println!(
+            "  {}{} description=\"{}\"",
+            out.name,
+            if matched.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", matched.join(", "))
+            },
+            display_text(out.description.as_deref().unwrap_or(""))
+        );
+    }
+    Ok(())
+}
+
+/// Pins `to` to what `from` names now: an alias's target, or a live output's
+/// description (its connector name when the description is not known), and
+/// moves `from`'s map entry over.
+fn rename_monitor(from: &str, to: &str) -> Result<(), String> {
+    check_alias_name(to)?;
+    let config_path = config_file_path_from_env()?;
+    let aliases = Aliases::from_config(&ConfigFile::load(&config_path));
+    if aliases.is_alias(to) {
+        return Err(format!(
+            "alias '{to}' already exists (change it with: kitsune-rendercore alias set {to} <TARGET>)"
+        ));
+    }
+    let from_alias = aliases.entries().get(from).cloned();
+    let target = match &from_alias {
+        Some(target) => target.clone(),
+        None => pin_output(from)?,
+    };
+    set_config_value(&config_path, SECTION, to, Some(&target))?;
+    if from_alias.is_some() {
+        set_config_value(&config_path, SECTION, from, None)?;
+        println!(
+            "[ok] renamed alias {from} to {to} = {}",
+            display_text(&target)
+        );
+        for (alias, _) in aliases.entries().iter().filter(|(_, t)| t.as_str() == from) {
+            set_config_value(&config_path, SECTION, alias, Some(to))?;
+            println!("[ok] alias {alias} now points at {to}");
+        }
+    } else {
+        println!("[ok] alias {to} = {}", display_text(&target));
+    }
+    println!("[ok] config={}", config_path.display());
+    let map_path = map_file_path_from_env()?;
+    move_map_entry(&map_path, from, to)?;
+    let aliases = Aliases::load_from_env();
+    reload_and_show(&aliases, to);
+    Ok(())
+}
+
+/// The target that keeps naming the live output `name`: `desc:` with its
+/// description, else its connector name.
+fn pin_output(name: &str) -> Result<String, String> {
+    let Some(status) = live_status() else {
+        println!(
+            "[warn] renderer not running: {name} is pinned by its connector name, which can shift"
+        );
+        return Ok(name.to_string());
+    };
+    let outputs = live_outputs(&status);
+    let Some(output) = outputs.iter().find(|out| out.name == name) else {
+        let known = outputs
+            .iter()
+            .map(|out| out.name.as_str())
+            .collect::<Vec<_>>();
+        return Err(format!(
+            "no output named '{name}' (outputs: {})",
+            known.join(", ")
+        ));
+    };
+    let Some(description) = output.description.as_deref() else {
+        println!(
+            "[warn] the compositor gives {name} no description: it is pinned by its connector name"
+        );
+        return Ok(name.to_string());
+    };
+    // wlroots compositors end the description with the connector, which
+    // is exactly what must not be pinned.
+    let description = description
+        .strip_suffix(&format!("({name})"))
+        .unwrap_or(description)
+        .trim();
+    let selector = Selector::Desc(description.to_string());
+    let others = outputs
+        .iter()
+        .filter(|out| out.name != name && selector.matches(None, out.description.as_deref()))
+        .map(|out| out.name.as_str())
+        .collect::<Vec<_>>();
+    if !others.is_empty() {
+        println!(
+            "[warn] {} has the same description as {name}: pinned by connector name instead",
+            others.join(", ")
+        );
+        return Ok(name.to_string());
+    }
+    Ok(selector.to_string())
+}
+
+fn move_map_entry(map_path: &Path, from: &str, to: &str) -> Result<(), String> {
+    let MapDocument { entries, .. } = load_map_document(map_path);
+    let Some(entry) = entries.get(from) else {
+        return Ok(());
+    };
+    if entries.contains_key(to) {
+        println!(
+            "[warn] the map has entries for both {from} and {to}; {to}'s is kept and {from}'s left alone"
+        );
+        return Ok(());
+    }
+    set_monitor_video(map_path, to, entry)?;
+    unset_monitor_video(map_path, from)?;
+    println!(
+        "[ok] moved the map entry of {from} to {to} (map={})",
+        map_path.display()
+    );
+    Ok(())
+}
+
+/// Asks a running renderer to pick up the new aliases; quiet when none runs.
+fn reload_renderer() -> bool {
+    match control::request("reload") {
+        Ok(_) => {
+            println!("[ok] running renderer re-read the aliases");
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn reload_and_show(aliases: &Aliases, alias: &str) {
+    if !reload_renderer() {
+        return;
+    }
+    let Some(status) = live_status() else {
+        return;
+    };
+    let outputs = live_outputs(&status)
+        .into_iter()
+        .map(|out| (out.name, out.description))
+        .collect::<Vec<_>>();
+    let Some(report) = aliases
+        .report(&outputs)
+        .into_iter()
+        .find(|report| report.alias == alias)
+    else {
+        return;
+    };
+    match report.problem {
+        None => println!("[ok] {alias} -> {}", report.outputs.join(", ")),
+        Some(problem) if report.outputs.is_empty() => println!("[warn] {alias} {problem}"),
+        Some(problem) => println!("[warn] {alias} {problem}: {}", report.outputs.join(", ")),
+    }
+}
+
+fn print_alias_help() {
+    println!("kitsune-rendercore alias");
+    println!("Usage:");
+    println!("  kitsune-rendercore alias [list]");
+    println!("  kitsune-rendercore alias set <ALIAS> <TARGET>");
+    println!("  kitsune-rendercore alias unset <ALIAS>");
+    println!();
+    println!("Description:");
+    println!("  Stable names for outputs, kept under [aliases] in the config file.");
+    println!("  Map keys, --monitor values and KRC_OUTPUTS entries can use an alias;");
+    println!("  it is resolved before any output name is matched, and an alias wins");
+    println!("  over an output with the same name. status shows each output's aliases");
+    println!("  and any alias that matches no output or several.");
+    println!();
+    println!("Targets:");
+    println!("  desc:<TEXT>    Outputs whose description contains TEXT (any case).");
+    println!("  name:<OUTPUT>  The output with this connector name.");
+    println!("  <ALIAS>        Another alias.");
+    println!("  <OUTPUT>       A connector name, when no alias is called that.");
+    println!();
+    println!("Examples:");
+    println!("  kitsune-rendercore alias set main 'desc:LG ULTRAGEAR'");
+    println!("  kitsune-rendercore alias set side DP-3");
+    println!("  kitsune-rendercore set-video --monitor main --video ~/Videos/live/a.mp4");
+}
+
+fn print_monitors_help() {
+    println!("kitsune-rendercore monitors");
+    println!("Usage:");
+    println!("  kitsune-rendercore monitors [list]");
+    println!("  kitsune-rendercore monitors rename <OUTPUT|ALIAS> <NEW_ALIAS>");
+    println!();
+    println!("Description:");
+    println!("  list shows the running renderer's outputs with their descriptions and");
+    println!("  aliases. rename pins NEW_ALIAS to an output by its description (the");
+    println!("  running renderer's; the connector name without one), or renames an");
+    println!("  alias, and moves the map entry of the old name to the new one.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_map::VideoMapEntry;
+
+    fn aliases(pairs: &[(&str, &str)]) -> Aliases {
+        Aliases {
+            entries: pairs
+                .iter()
+                .map(|(alias, target)| (alias.to_string(), target.to_string()))
+                .collect(),
+        }
+    }
+
+    fn map(keys: &[&str]) -> VideoMap {
+        keys.iter()
+            .map(|key| (key.to_string(), VideoMapEntry::default()))
+            .collect()
+    }
+
+    const LG: &str = "LG Electronics LG ULTRAGEAR 27GP850 (DP-1)";
+    const DELL: &str = "Dell Inc. DELL U2720Q (HDMI-A-1)";
+
+    #[test]
+    fn alias_to_desc_matches_the_description_case_insensitively() {
+        let aliases = aliases(&[("main", "desc:lg ultragear")]);
+        assert_eq!(
+            aliases.resolve("main"),
+            Ok(Selector::Desc("lg ultragear".to_string()))
+        );
+        assert!(aliases.matches("main", Some("DP-1"), Some(LG)));
+        // Whatever connector it is on today.
+        assert!(aliases.matches("main", Some("DP-4"), Some(LG)));
+        assert!(!aliases.matches("main", Some("HDMI-A-1"), Some(DELL)));
+        assert!(!aliases.matches("main", Some("DP-1"), None));
+        assert_eq!(aliases.matching(Some("DP-4"), Some(LG)), ["main"]);
+    }
+
+    #[test]
+    fn alias_to_name_matches_the_connector() {
+        let aliases = aliases(&[("side", "DP-3"), ("pinned", "name:DP-3"), ("left", "side")]);
+        for alias in ["side", "pinned", "left"] {
+            assert_eq!(
+                aliases.resolve(alias),
+                Ok(Selector::Name("DP-3".to_string())),
+                "{alias}"
+            );
+        }
+        assert!(aliases.matches("left", Some("DP-3"), Some(DELL)));
+        assert!(!aliases.matches("left", Some("DP-30"), None));
+        assert_eq!(
+            aliases.matching(Some("DP-3"), None),
+            ["left", "pinned", "side"]
+        );
+        assert_eq!(Selector::Desc("LG".into()).to_string(), "desc:LG");
+    }
+
+    #[test]
+    fn unknown_and_broken_aliases_are_errors() {
+        let aliases = aliases(&[
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "a"),
+            ("blank", "desc:  "),
+            ("unnamed", "name:"),
+            ("empty", ""),
+        ]);
+        assert_eq!(
+            aliases.resolve("nope"),
+            Err("unknown alias 'nope'".to_string())
+        );
+        assert_eq!(
+            aliases.resolve("a"),
+            Err("alias cycle: a -> b -> c -> a".to_string())
+        );
+        assert!(aliases.resolve("blank").unwrap_err().contains("desc:"));
+        assert!(aliases.resolve("unnamed").unwrap_err().contains("name:"));
+        assert!(
+            aliases
+                .resolve("empty")
+                .unwrap_err()
+                .contains("empty target")
+        );
+        // A broken alias names no output and is reported, never matched.
+        assert!(!aliases.matches("a", Some("a"), None));
+        assert!(aliases.matching(Some("a"), None).is_empty());
+        let report = aliases.report(&[("DP-1".to_string(), None)]);
+        assert!(report.iter().all(|r| r.problem.is_some()));
+    }
+
+    #[test]
+    fn an_alias_takes_the_name_of_an_output_called_the_same() {
+        // `DP-1` is an alias for the LG, wherever it is; the output that is
+        // physically DP-1 today is not what the key means.
+        let aliases = aliases(&[("DP-1", "desc:ULTRAGEAR"), ("tv", "name:DP-1")]);
+        assert!(aliases.matches("DP-1", Some("DP-2"), Some(LG)));
+        assert!(!aliases.matches("DP-1", Some("DP-1"), Some(DELL)));
+        // `name:` still reaches the physical connector.
+        assert!(aliases.matches("tv", Some("DP-1"), Some(DELL)));
+
+        let map = map(&["DP-1"]);
+        let lg = aliases.matching(Some("DP-2"), Some(LG));
+        assert_eq!(lg, ["DP-1"]);
+        assert_eq!(aliases.map_key(&map, &lg, "DP-2"), Some("DP-1".into()));
+        // The physical DP-1 does not get the alias's entry.
+        let dell = aliases.matching(Some("DP-1"), Some(DELL));
+        assert_eq!(dell, ["tv"]);
+        assert_eq!(aliases.map_key(&map, &dell, "DP-1"), None);
+        // When the alias does match it, its own name is fine.
+        assert_eq!(aliases.map_key(&map, &lg, "DP-1"), Some("DP-1".into()));
+    }
+
+    #[test]
+    fn map_keys_prefer_an_alias_the_map_has() {
+        let aliases = aliases(&[("main", "desc:ULTRAGEAR"), ("desk", "DP-1")]);
+        let matched = aliases.matching(Some("DP-1"), Some(LG));
+        assert_eq!(matched, ["desk", "main"]);
+        assert_eq!(
+            aliases.map_key(&map(&["main", "DP-1"]), &matched, "DP-1"),
+            Some("main".into())
+        );
+        assert_eq!(
+            aliases.map_key(&map(&["DP-1"]), &matched, "DP-1"),
+            Some("DP-1".into())
+        );
+        // set-video --all writes a new entry under the first alias.
+        assert_eq!(
+            aliases.write_key(&map(&[]), &matched, "DP-1"),
+            Some("desk".into())
+        );
+        assert_eq!(
+            aliases.write_key(&map(&["DP-1"]), &matched, "DP-1"),
+            Some("DP-1".into())
+        );
+    }
+
+    #[test]
+    fn report_flags_aliases_matching_none_or_several_outputs() {
+        let aliases = aliases(&[("main", "desc:ULTRAGEAR"), ("any", "desc:(")]);
+        let outputs = [
+            ("DP-1".to_string(), Some(LG.to_string())),
+            ("HDMI-A-1".to_string(), Some(DELL.to_string())),
+            ("eDP-1".to_string(), None),
+        ];
+        let report = aliases.report(&outputs);
+        assert_eq!(report[0].alias, "any");
+        assert_eq!(report[0].outputs, ["DP-1", "HDMI-A-1"]);
+        assert_eq!(report[0].problem.as_deref(), Some("matches 2 outputs"));
+        assert_eq!(report[1].outputs, ["DP-1"]);
+        assert_eq!(report[1].problem, None);
+        let report = aliases.report(&outputs[2..]);
+        assert_eq!(report[1].problem.as_deref(), Some("matches no output"));
+    }
+
+    #[test]
+    fn config_section_and_alias_names() {
+        let dir = std::env::temp_dir().join(format!("krc-aliases-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            "[aliases]\nmain = \"desc:LG ULTRAGEAR\"\nside = \" DP-3 \"\n",
+        )
+        .unwrap();
+        let aliases = Aliases::from_config(&ConfigFile::load(&path));
+        assert_eq!(aliases.entries()["side"], "DP-3");
+        assert!(aliases.is_alias("main") && !aliases.is_alias("DP-3"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(check_alias_name("main-4k_v2.1").is_ok());
+        for bad in ["", "my tv", "a:b", "default", "x/y"] {
+            assert!(check_alias_name(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -1,22 +1,94 @@
+use crate::aliases::{Aliases, Selector, run_alias, run_monitors};
+use crate::backend::wayland_output_names;
+use crate::bench::{parse_duration, run_bench};
+use crate::clean::run_clean;
 use crate::config::RenderCoreConfig;
-use crate::runtime::RenderRuntime;
+use crate::config_bundle::{run_export_config, run_import_config};
+use crate::config_file::{ConfigFile, config_file_path_from_env};
+use crate::control;
+use crate::doctor::run_doctor;
+use crate::env_file::{self, EnvFileWatcher};
+use crate::event_file::{self, run_events};
+use crate::hooks::{self, HookConfig, HookContext, HookEvent, spawn_hook};
+use crate::instance::{
+    acquire_instance, daemonize, instance_id_from_env, running_pid, send_signal,
+};
+use crate::journal;
+use crate::json::{JsonValue, escape_json, parse_json};
+use crate::map_apply::{self, ApplyWait};
+use crate::map_migrate::run_migrate_map;
+use crate::map_watch::watch_map;
+use crate::monitor::OutputAllowlist;
+use crate::monitor_filter::{MonitorFilter, monitor_forms};
+use crate::paths::{migrate_legacy, run_paths, snapshot_file};
+use crate::pause_file::{create_pause_file, pause_file_path_from_env, remove_pause_file};
+use crate::quiet_hours::{QUIET_EXIT_CODE, QuietHours, schedule_service_start, sleep_through};
+use crate::reduce_motion::{self, run_reduce_motion};
+use crate::replay::replay_journal;
+use crate::runtime::{LoopExit, RenderRuntime};
+use crate::service_logs::run_service_logs;
+use crate::setup::run_setup;
+use crate::snapshot::now_ms;
+use crate::status_format::render_status_template;
+use crate::status_schema::{STATUS_JSON_SCHEMA, STATUS_SCHEMA_VERSION};
 use crate::steam::SteamGameDetector;
+use crate::supervisor::{SupervisorState, run_supervise};
+use crate::tools::{Tool, hyprland_ipc_socket};
+#[cfg(feature = "video-ffmpeg")]
+use crate::tools::{probe_duration_ms, probe_video_size};
+use crate::user_path::{
+    cli_base_dir, display_path, display_path_short, display_text, normalize_video_path,
+};
+use crate::version::{self, NO_VIDEO_SUPPORT, run_version};
 use crate::video_map::{
-    map_file_path_from_env, parse_video_map_env, parse_video_map_file, set_monitor_video,
-    unset_all_monitors, unset_monitor_video,
+    Crop, EntryOptions, Flip, Layout, QualityPreset, Rotation, Trim, UnsetAllOutcome,
+    VideoMapEntry, env_video_map, load_map_document, map_file_path_from_env, merge_maps,
+    resolve_output_video, set_entries_quality, set_monitor_video, split_still, unset_all_monitors,
+    unset_monitor_video,
 };
-use std::process::{Command, Stdio};
+use std::collections::BTreeMap;
+use std::process::Stdio;
 
 pub fn run() -> Result<(), String> {
     let args = std::env::args().collect::<Vec<_>>();
     match args.get(1).map(|s| s.as_str()) {
+        Some("setup") => return run_setup(&args[2..]),
         Some("set-video") => return run_set_video(&args[2..]),
         Some("unset-video") => return run_unset_video(&args[2..]),
         Some("status") => return run_status(&args[2..]),
         Some("install-deps") => return run_kitowall(&["live", "doctor", "--fix"]),
+        Some("doctor") => return run_doctor(),
+        Some("bench") => return run_bench(&args[2..]),
+        Some("clean") => return run_clean(&args[2..]),
+        Some("paths") => return run_paths(&args[2..]),
+        Some("version") => return run_version(&args[2..]),
+        Some("--version") | Some("-V") => return run_version(&[]),
         Some("check-deps") => return run_kitowall(&["live", "doctor"]),
         Some("install-service") => return run_kitowall(&["live", "service-autostart", "install"]),
         Some("service") => return run_service(&args[2..]),
+        Some("supervise") => return run_supervise(&args[2..]),
+        Some("restart") => return run_restart(&args[2..]),
+        Some("replay") => return run_replay(&args[2..]),
+        Some("events") => return run_events(&args[2..]),
+        Some("watch-map") => return run_watch_map(&args[2..]),
+        Some("migrate-map") => return run_migrate_map(&args[2..]),
+        Some("export-config") => return run_export_config(&args[2..]),
+        Some("import-config") => return run_import_config(&args[2..]),
+        Some("quality") => return run_quality(&args[2..]),
+        Some("alias") => return run_alias(&args[2..]),
+        Some("monitors") => return run_monitors(&args[2..]),
+        #[cfg(feature = "update-check")]
+        Some("update-check") => return crate::update_check::run_update_check(&args[2..]),
+        #[cfg(not(feature = "update-check"))]
+        Some("update-check") => {
+            return Err("built without the update-check feature: no network access".to_string());
+        }
+        Some("profile") => return run_profile(),
+        Some("reload") => return run_reload(&args[2..]),
+        Some("hooks") => return run_hooks(&args[2..]),
+        Some("reduce-motion") => return run_reduce_motion(&args[2..]),
+        Some("pause") => return run_pause(&args[2..], true),
+        Some("resume") => return run_pause(&args[2..], false),
         Some("--help") | Some("-h") | Some("help") => {
             print_help();
             return Ok(());
@@ -24,10 +96,70 @@ pub fn run() -> Result<(), String> {
         _ => {}
     }
 
+    let flags = &args[1..];
+    let has_flag = |flag: &str| flags.iter().any(|a| a == flag);
+    if has_flag("--foreground") && has_flag("--daemonize") {
+        return Err("--foreground and --daemonize are mutually exclusive".to_string());
+    }
+    let instance = instance_id_from_env();
+    let replace = has_flag("--replace");
+    if has_flag("--daemonize") {
+        if std::env::var_os("INVOCATION_ID").is_some() {
+            // systemd tracks the main PID; forking away from it would look like an exit.
+            eprintln!("[rendercore] --daemonize ignored under systemd, running in foreground");
+        } else {
+            if !replace {
+                // Fail in the calling shell rather than in the background log.
+                drop(acquire_instance(&instance, false)?);
+            }
+            let child_args = flags
+                .iter()
+                .filter(|a| *a != "--daemonize")
+                .cloned()
+                .collect::<Vec<_>>();
+            return daemonize(&instance, &child_args);
+        }
+    }
+    let _instance_lock = acquire_instance(&instance, replace)?;
+    migrate_legacy();
+
+    journal::init_from_env();
+    event_file::init_from_env();
+    hooks::init(!has_flag("--no-hooks"));
     let cfg = RenderCoreConfig::default();
-    let mut runtime = RenderRuntime::new(cfg);
-    runtime.bootstrap()?;
-    runtime.run()
+    loop {
+        if let Some(quiet) = cfg.quiet_hours.filter(QuietHours::is_active_now) {
+            wait_out_quiet_hours(quiet);
+        }
+        // Dropping the runtime at the end of an iteration closes the Wayland
+        // connection, the GPU device and every decoder.
+        let mut runtime = RenderRuntime::new(cfg.clone());
+        runtime.bootstrap()?;
+        match runtime.run()? {
+            LoopExit::Done => return Ok(()),
+            LoopExit::QuietHours => {}
+        }
+    }
+}
+
+/// Under systemd the process exits with [`QUIET_EXIT_CODE`] after arranging
+/// for the service to start again at the window's end; elsewhere (or if that
+/// fails) it sleeps here and the caller bootstraps a fresh renderer.
+fn wait_out_quiet_hours(quiet: QuietHours) {
+    if std::env::var_os("INVOCATION_ID").is_some() {
+        match schedule_service_start(quiet) {
+            Ok(at) => {
+                println!(
+                    "[rendercore] quiet hours {quiet}: exiting, the service starts again at {at}"
+                );
+                std::process::exit(QUIET_EXIT_CODE);
+            }
+            Err(err) => eprintln!(
+                "[rendercore] warning: cannot schedule the restart after quiet hours ({err}); sleeping in process instead"
+            ),
+        }
+    }
+    sleep_through(quiet);
 }
 
 fn run_set_video(args: &[String]) -> Result<(), String> {
@@ -36,6 +168,11 @@ fn run_set_video(args: &[String]) -> Result<(), String> {
     let mut map_file = None::<String>;
     let mut all = false;
     let mut except_raw = None::<String>;
+    let mut only_raw = None::<String>;
+    let mut options = EntryOptions::default();
+    let mut wait = true;
+    let mut wait_timeout = map_apply::DEFAULT_TIMEOUT;
+    let mut allow_paused = false;
 
     let mut i = 0usize;
     while i < args.len() {
@@ -47,14 +184,61 @@ fn run_set_video(args: &[String]) -> Result<(), String> {
                 i += 1;
                 monitor = args.get(i).cloned();
             }
+            "--no-wait" => {
+                wait = false;
+            }
+            "--allow-paused" => {
+                allow_paused = true;
+            }
+            "--wait-timeout" => {
+                i += 1;
+                let raw = args
+                    .get(i)
+                    .ok_or_else(|| "missing value for --wait-timeout".to_string())?;
+                wait_timeout = parse_duration("--wait-timeout", raw)?;
+            }
             "--video" => {
                 i += 1;
                 video = args.get(i).cloned();
             }
+            "--rotate" => {
+                i += 1;
+                let raw = args
+                    .get(i)
+                    .ok_or_else(|| "missing value for --rotate".to_string())?;
+                options.rotate = Rotation::parse(raw)?;
+            }
+            "--flip" => {
+                i += 1;
+                let raw = args
+                    .get(i)
+                    .ok_or_else(|| "missing value for --flip".to_string())?;
+                options.flip = Flip::parse(raw)?;
+            }
+            "--quality" => {
+                i += 1;
+                let raw = args
+                    .get(i)
+                    .ok_or_else(|| "missing value for --quality".to_string())?;
+                options.quality = QualityPreset::parse_override(raw)?;
+            }
+            flag @ ("--effect" | "--amplitude" | "--period" | "--drift" | "--overlay"
+            | "--density" | "--speed" | "--wind" | "--trim" | "--crop" | "--filter"
+            | "--fit" | "--gamut" | "--clock" | "--timebase") => {
+                i += 1;
+                let raw = args
+                    .get(i)
+                    .ok_or_else(|| format!("missing value for {flag}"))?;
+                options.set(&flag[2..], raw)?;
+            }
             "--except" => {
                 i += 1;
                 except_raw = args.get(i).cloned();
             }
+            "--only" => {
+                i += 1;
+                only_raw = args.get(i).cloned();
+            }
             "--map-file" => {
                 i += 1;
                 map_file = args.get(i).cloned();
@@ -70,58 +254,277 @@ fn run_set_video(args: &[String]) -> Result<(), String> {
         i += 1;
     }
 
-    let video = video.ok_or_else(|| "missing --video".to_string())?;
+    let raw_video = video.ok_or_else(|| "missing --video".to_string())?;
+    let mut entry = video_entry_from_arg(&raw_video, options)?;
+    // A layout's cells share the options, so they have to fit every cell's video.
+    let videos: Vec<String> = entry.videos().into_iter().map(str::to_string).collect();
+    for video in &videos {
+        if let Some(trim) = entry.options.trim {
+            entry.options.trim = Some(check_trim(video, trim)?);
+        }
+        if let Some(crop) = entry.options.crop {
+            entry.options.crop = Some(check_crop(video, crop)?);
+        }
+    }
+    let label = entry.display_label();
+    if !cfg!(feature = "video-ffmpeg") {
+        println!("[warn] {NO_VIDEO_SUPPORT}: this renderer shows the procedural fallback instead");
+    }
     let map_path = map_file
         .map(std::path::PathBuf::from)
-        .unwrap_or_else(map_file_path_from_env);
-    let except = except_raw
-        .as_deref()
-        .map(parse_csv_list)
-        .unwrap_or_default();
+        .map_or_else(map_file_path_from_env, Ok)?;
+    let filter = MonitorFilter::from_flags(except_raw.as_deref(), only_raw.as_deref())?;
+    // Before the map changes, so no event of the change is missed.
+    let apply_wait = wait.then(|| ApplyWait::begin(&map_path));
 
     if all {
-        let monitors = detect_monitor_names()?;
+        let list = monitors_for_all()?;
+        let monitors = &list.names;
         if monitors.is_empty() {
-            return Err("no monitors found via hyprctl".to_string());
+            return Err(format!("no monitors found ({})", list.source));
         }
+        println!("[info] monitors from the {}", list.source);
+        warn_unmatched(&filter, &list, &[]);
+        let aliases = Aliases::load_from_env();
+        let map = load_map_document(&map_path).entries;
         let mut applied = 0usize;
-        for m in &monitors {
-            if except.iter().any(|x| x == m) {
-                println!("[ok] skipped monitor by --except: {}", m);
+        for m in monitors {
+            let matched = list.aliases_of(m);
+            if !filter.selects(&list.forms(m)) {
+                println!("[ok] skipped monitor {}: {m}", skipped_by(&filter));
+                continue;
+            }
+            if list.excluded.contains(m) {
+                println!("[ok] skipped monitor excluded by the renderer (KRC_OUTPUTS): {m}");
+                continue;
+            }
+            let Some(key) = aliases.write_key(&map, matched, m) else {
+                println!("[warn] skipped {m}: alias {m} names another output");
                 continue;
+            };
+            set_monitor_video(&map_path, &key, &entry)?;
+            if key == *m {
+                println!("[ok] updated monitor mapping: {m} -> {label}");
+            } else {
+                println!("[ok] updated monitor mapping: {key} ({m}) -> {label}");
             }
-            set_monitor_video(&map_path, m, &video)?;
-            println!("[ok] updated monitor mapping: {} -> {}", m, video);
             applied += 1;
         }
         println!(
-            "[ok] updated {} monitors (detected={}, map={})",
+            "[ok] updated {} monitors (detected={} from the {}, map={})",
             applied,
             monitors.len(),
+            list.source,
             map_path.display()
         );
     } else {
-        if !except.is_empty() {
-            return Err("--except requires --all".to_string());
+        if !filter.is_all() {
+            return Err(format!("{} requires --all", filter.flag()));
         }
         let monitor = monitor.ok_or_else(|| "missing --monitor (or use --all)".to_string())?;
-        set_monitor_video(&map_path, &monitor, &video)?;
+        set_monitor_video(&map_path, &monitor, &entry)?;
         println!(
             "[ok] updated monitor mapping: {} -> {} (map={})",
             monitor,
-            video,
+            label,
+            map_path.display()
+        );
+    }
+    match apply_wait {
+        Some(Ok(apply_wait)) => return apply_wait.finish(&videos, wait_timeout, allow_paused),
+        Some(Err(why)) => println!("[info] not waiting for the renderer: {why}"),
+        None => {}
+    }
+    println!(
+        "[ok] if renderer is running, it will reload this mapping automatically (force it with: kitsune-rendercore reload)."
+    );
+    warn_if_renderer_map_differs(&map_path, None);
+    Ok(())
+}
+
+/// A `--video` value (a path, `still:PATH[@TIME]` or `layout=gridCxR:...`)
+/// as a map entry, with relative paths resolved against the shell's directory.
+pub fn video_entry_from_arg(
+    raw_video: &str,
+    mut options: EntryOptions,
+) -> Result<VideoMapEntry, String> {
+    if let Some(layout) = raw_video.trim().strip_prefix("layout=") {
+        return Ok(VideoMapEntry::from_layout(
+            Layout::parse(layout, &cli_base_dir())?,
+            options,
+        ));
+    }
+    let (raw_video, still) = split_still(raw_video.trim())?;
+    if raw_video.trim().is_empty() {
+        return Err("video path is empty".to_string());
+    }
+    if still.is_some() && !cfg!(feature = "video-ffmpeg") {
+        return Err(format!("still: needs video decoding: {NO_VIDEO_SUPPORT}"));
+    }
+    options.still = still;
+    let video = normalize_video_path(raw_video, &cli_base_dir())?;
+    if video != raw_video.trim() {
+        println!(
+            "[ok] video path {} -> {}",
+            display_path(raw_video),
+            display_path(&video)
+        );
+    }
+    Ok(VideoMapEntry {
+        video,
+        options,
+        layout: None,
+    })
+}
+
+/// `quality (--monitor M | --all [--except ..]) <PRESET|default>`: sets the
+/// per-entry `quality=` option through the normal map-writing path.
+fn run_quality(args: &[String]) -> Result<(), String> {
+    let mut monitor = None::<String>;
+    let mut map_file = None::<String>;
+    let mut all = false;
+    let mut except_raw = None::<String>;
+    let mut only_raw = None::<String>;
+    let mut preset = None::<String>;
+
+    let mut i = 0usize;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--all" => all = true,
+            "--monitor" => {
+                i += 1;
+                monitor = args.get(i).cloned();
+            }
+            "--except" => {
+                i += 1;
+                except_raw = args.get(i).cloned();
+            }
+            "--only" => {
+                i += 1;
+                only_raw = args.get(i).cloned();
+            }
+            "--map-file" => {
+                i += 1;
+                map_file = args.get(i).cloned();
+            }
+            "--help" | "-h" => {
+                print_quality_help();
+                return Ok(());
+            }
+            other if other.starts_with("--") => {
+                return Err(format!("unknown argument for quality: {other}"));
+            }
+            other => {
+                if preset.is_some() {
+                    return Err(format!("unexpected argument for quality: {other}"));
+                }
+                preset = Some(other.to_string());
+            }
+        }
+        i += 1;
+    }
+
+    let raw = preset.ok_or_else(|| {
+        "missing preset: low, medium, high, ultra, or default to follow KRC_QUALITY".to_string()
+    })?;
+    let quality = QualityPreset::parse_override(&raw)?;
+    let label = quality.map_or("default", QualityPreset::as_str);
+    let map_path = map_file
+        .map(std::path::PathBuf::from)
+        .map_or_else(map_file_path_from_env, Ok)?;
+    let filter = MonitorFilter::from_flags(except_raw.as_deref(), only_raw.as_deref())?;
+    if !all && !filter.is_all() {
+        return Err(format!("{} requires --all", filter.flag()));
+    }
+    let monitors = match (all, monitor) {
+        (true, Some(_)) => return Err("--monitor and --all are mutually exclusive".to_string()),
+        (true, None) => None,
+        (false, Some(monitor)) => Some(vec![monitor]),
+        (false, None) => return Err("missing --monitor (or use --all)".to_string()),
+    };
+    // Patterns are checked against the outputs when some source lists them;
+    // the map keys alone are enough to apply them.
+    let list = (!filter.is_all())
+        .then(monitors_for_all)
+        .and_then(Result::ok);
+    if let Some(list) = &list {
+        let mapped = load_map_document(&map_path)
+            .entries
+            .into_keys()
+            .collect::<Vec<_>>();
+        warn_unmatched(&filter, list, &mapped);
+    }
+    let key_forms = |key: &str| {
+        list.as_ref()
+            .map_or_else(|| vec![key.to_string()], |list| list.key_forms(key))
+    };
+
+    let changed = set_entries_quality(&map_path, monitors.as_deref(), quality, |key| {
+        filter.selects(&key_forms(key))
+    })?;
+    for monitor in &changed {
+        println!("[ok] quality {monitor} -> {label}");
+    }
+    if changed.is_empty() {
+        println!(
+            "[ok] nothing to change: quality is already {label} (map={})",
+            map_path.display()
+        );
+    } else {
+        println!(
+            "[ok] updated {} entr{} (map={})",
+            changed.len(),
+            if changed.len() == 1 { "y" } else { "ies" },
             map_path.display()
         );
+        println!(
+            "[ok] if renderer is running, it rebuilds only these outputs' textures and decoders."
+        );
+    }
+    if all {
+        println!(
+            "[info] --all covers the entries in the map file; outputs showing the default video follow KRC_QUALITY"
+        );
     }
-    println!("[ok] if renderer is running, it will reload this mapping automatically.");
+    warn_if_renderer_map_differs(&map_path, None);
     Ok(())
 }
 
+fn print_quality_help() {
+    println!("kitsune-rendercore quality");
+    println!("Usage:");
+    println!(
+        "  kitsune-rendercore quality (--monitor <MONITOR> | --all) <PRESET> [--except <LIST> | --only <LIST>] [--map-file <PATH>]"
+    );
+    println!();
+    println!("Description:");
+    println!("  Stores quality=<PRESET> on map entries, overriding KRC_QUALITY and");
+    println!("  KRC_SOURCE_WIDTH/HEIGHT for those monitors. A running renderer rebuilds");
+    println!("  only the affected outputs' textures and decoders. The monitor needs a map");
+    println!("  entry already; --all changes every entry in the map file.");
+    println!();
+    println!("  --except <LIST> leaves the entries its patterns match alone and --only");
+    println!("  changes just those. Patterns are comma-separated, match a connector, an");
+    println!("  alias or desc:<text>, and may use '*' and '?' (only with --all).");
+    println!();
+    println!("Presets:");
+    println!(
+        "  low (720p), medium (1080p), high (1440p), ultra (4k), default (follow KRC_QUALITY)"
+    );
+    println!();
+    println!("Example:");
+    println!("  kitsune-rendercore quality --monitor DP-1 ultra");
+    println!("  kitsune-rendercore quality --all low --except DP-1");
+}
+
 fn run_unset_video(args: &[String]) -> Result<(), String> {
     let mut monitor = None::<String>;
     let mut map_file = None::<String>;
     let mut all = false;
     let mut except_raw = None::<String>;
+    let mut only_raw = None::<String>;
+    let mut include_default = false;
+    let mut include_transient = false;
 
     let mut i = 0usize;
     while i < args.len() {
@@ -129,6 +532,8 @@ fn run_unset_video(args: &[String]) -> Result<(), String> {
             "--all" => {
                 all = true;
             }
+            "--include-default" => include_default = true,
+            "--include-transient" => include_transient = true,
             "--monitor" => {
                 i += 1;
                 monitor = args.get(i).cloned();
@@ -137,6 +542,10 @@ fn run_unset_video(args: &[String]) -> Result<(), String> {
                 i += 1;
                 except_raw = args.get(i).cloned();
             }
+            "--only" => {
+                i += 1;
+                only_raw = args.get(i).cloned();
+            }
             "--map-file" => {
                 i += 1;
                 map_file = args.get(i).cloned();
@@ -152,23 +561,62 @@ fn run_unset_video(args: &[String]) -> Result<(), String> {
 
     let map_path = map_file
         .map(std::path::PathBuf::from)
-        .unwrap_or_else(map_file_path_from_env);
-    let except = except_raw
-        .as_deref()
-        .map(parse_csv_list)
-        .unwrap_or_default();
+        .map_or_else(map_file_path_from_env, Ok)?;
+    let filter = MonitorFilter::from_flags(except_raw.as_deref(), only_raw.as_deref())?;
 
+    let mut live = None;
     if all {
-        let removed = unset_all_monitors(&map_path, &except)?;
-        println!(
-            "[ok] removed {} mappings via --all (kept {} via --except, map={})",
-            removed,
-            except.len(),
-            map_path.display()
-        );
+        let list = (!filter.is_all())
+            .then(monitors_for_all)
+            .and_then(Result::ok);
+        if let Some(list) = &list {
+            let mapped = load_map_document(&map_path)
+                .entries
+                .into_keys()
+                .collect::<Vec<_>>();
+            warn_unmatched(&filter, list, &mapped);
+        }
+        let key_forms = |key: &str| {
+            list.as_ref()
+                .map_or_else(|| vec![key.to_string()], |list| list.key_forms(key))
+        };
+        let outcome = unset_all_monitors(
+            &map_path,
+            |key| filter.selects(&key_forms(key)),
+            include_default,
+        )?;
+        match filter {
+            MonitorFilter::All => println!(
+                "[ok] removed {} mappings via --all (map={})",
+                outcome.removed,
+                map_path.display()
+            ),
+            _ => println!(
+                "[ok] removed {} mappings via --all (kept {} via {}, map={})",
+                outcome.removed,
+                outcome.kept,
+                filter.flag(),
+                map_path.display()
+            ),
+        }
+        if outcome.default_removed {
+            println!("[ok] removed the map file default ([default])");
+        } else if include_default {
+            println!("[ok] no default in the map file");
+        }
+        if include_transient {
+            clear_transient_overrides()?;
+        }
+        live = control::request("status")
+            .ok()
+            .and_then(|raw| parse_json(&raw).ok());
+        report_unset_leftovers(&outcome, live.as_ref());
     } else {
-        if !except.is_empty() {
-            return Err("--except requires --all".to_string());
+        if !filter.is_all() {
+            return Err(format!("{} requires --all", filter.flag()));
+        }
+        if include_default || include_transient {
+            return Err("--include-default and --include-transient require --all".to_string());
         }
         let monitor = monitor.ok_or_else(|| "missing --monitor (or use --all)".to_string())?;
         let removed = unset_monitor_video(&map_path, &monitor)?;
@@ -186,20 +634,98 @@ fn run_unset_video(args: &[String]) -> Result<(), String> {
             );
         }
     }
-    println!("[ok] if renderer is running, it will reload this mapping automatically.");
+    println!(
+        "[ok] if renderer is running, it will reload this mapping automatically (force it with: kitsune-rendercore reload)."
+    );
+    warn_if_renderer_map_differs(&map_path, live.as_ref());
+    Ok(())
+}
+
+/// `unset-video --all --include-transient`: asks a running renderer to drop
+/// its `set-video transient` overrides.
+fn clear_transient_overrides() -> Result<(), String> {
+    let Ok(reply) = control::request("clear-transient") else {
+        println!("[ok] renderer not reachable; no transient overrides to clear");
+        return Ok(());
+    };
+    let value = parse_json(&reply).map_err(|e| format!("unreadable clear-transient reply: {e}"))?;
+    if let Some(err) = value.get("error").and_then(JsonValue::as_str) {
+        return Err(format!(
+            "renderer could not clear transient overrides: {err}"
+        ));
+    }
+    let cleared = value
+        .get("cleared")
+        .and_then(JsonValue::as_u64)
+        .unwrap_or(0);
+    println!("[ok] cleared {cleared} transient override(s) in the running renderer");
     Ok(())
 }
 
+/// After `unset-video --all`: names whatever still puts a video on outputs
+/// without an entry, so a wallpaper that keeps playing is not a surprise.
+fn report_unset_leftovers(outcome: &UnsetAllOutcome, live: Option<&JsonValue>) {
+    let env_default = std::env::var("KRC_VIDEO_DEFAULT").ok();
+    let left = unset_leftovers(outcome, live, env_default.as_deref());
+    if left.is_empty() {
+        println!("[ok] no default or transient override left");
+    }
+    for line in left {
+        println!("[info] not removed: {line}");
+    }
+}
+
+fn unset_leftovers(
+    outcome: &UnsetAllOutcome,
+    live: Option<&JsonValue>,
+    env_default: Option<&str>,
+) -> Vec<String> {
+    let mut left = Vec::new();
+    if outcome.default_kept {
+        left.push("the map file default ([default]); add --include-default".to_string());
+    }
+    if let Some(default) = env_default.map(str::trim).filter(|d| !d.is_empty()) {
+        left.push(format!(
+            "KRC_VIDEO_DEFAULT={default} (environment, not the map file)"
+        ));
+    }
+    let transient = live
+        .and_then(|value| value.get("transient_overrides"))
+        .and_then(JsonValue::as_u64)
+        .unwrap_or(0);
+    if transient > 0 {
+        left.push(format!(
+            "{transient} transient override(s) in the running renderer; add --include-transient"
+        ));
+    }
+    left
+}
+
 fn run_status(args: &[String]) -> Result<(), String> {
     let mut as_json = false;
     let mut json_pretty = true;
     let mut out_file = None::<String>;
+    let mut template = None::<String>;
+    let mut watch = false;
     let mut i = 0usize;
     while i < args.len() {
         match args[i].as_str() {
             "--json" => as_json = true,
+            "--watch" => watch = true,
+            "--format" | "--output-format" => {
+                i += 1;
+                template = Some(
+                    args.get(i)
+                        .cloned()
+                        .ok_or_else(|| "--format requires a template".to_string())?,
+                );
+            }
             "--pretty" => json_pretty = true,
             "--compact" => json_pretty = false,
+            "--schema" => {
+                println!("{STATUS_JSON_SCHEMA}");
+                return Ok(());
+            }
             "--file" => {
                 i += 1;
                 out_file = args.get(i).cloned();
@@ -215,25 +741,44 @@ fn run_status(args: &[String]) -> Result<(), String> {
     if out_file.is_some() && !as_json {
         return Err("--file requires --json".to_string());
     }
+    if template.is_some() && as_json {
+        return Err("--format and --json are mutually exclusive".to_string());
+    }
+    if watch && (out_file.is_some() || template.is_some()) {
+        return Err("--watch cannot be combined with --file or --format".to_string());
+    }
+    if watch {
+        // One JSON document per line, like the events that follow it.
+        json_pretty = false;
+    }
 
-    let map_path = map_file_path_from_env();
-    let file_map = parse_video_map_file(&map_path);
-    let env_map = std::env::var("KRC_VIDEO_MAP")
-        .ok()
-        .map(|v| parse_video_map_env(&v))
-        .unwrap_or_default();
-    let default_video = std::env::var("KRC_VIDEO_DEFAULT")
-        .ok()
-        .or_else(|| std::env::var("KRC_VIDEO").ok());
+    // Report what the renderer sees, including KRC_ENV_FILE overrides.
+    let _ = EnvFileWatcher::from_env();
+    let map_path = map_file_path_from_env()?;
+    let map_doc = load_map_document(&map_path);
+    let env_map = env_video_map();
+    let default_video = map_doc.default_video();
+    let default_video_label = default_video
+        .as_ref()
+        .map(VideoMapEntry::to_string)
+        .unwrap_or_else(|| "<none>".to_string());
     let mut steam = SteamGameDetector::from_env();
     let steam_running = steam.steam_game_running();
-    let fps = std::env::var("KRC_VIDEO_FPS").unwrap_or_else(|_| "30".to_string());
-    let speed = std::env::var("KRC_VIDEO_SPEED").unwrap_or_else(|_| "1.0".to_string());
+    let pause_file = pause_file_path_from_env();
+    let pause_reason = if pause_file.exists() {
+        "manual-file"
+    } else if steam_running {
+        "steam-game"
+    } else {
+        "none"
+    };
+    let fps = env_file::var("KRC_VIDEO_FPS").unwrap_or_else(|| "30".to_string());
+    let speed = env_file::var("KRC_VIDEO_SPEED").unwrap_or_else(|| "1.0".to_string());
     let quality = std::env::var("KRC_QUALITY").unwrap_or_else(|_| "default".to_string());
-    let hwaccel = std::env::var("KRC_HWACCEL").unwrap_or_else(|_| "auto".to_string());
+    let hwaccel = env_file::var("KRC_HWACCEL").unwrap_or_else(|| "auto".to_string());
 
     let service_state = if let Ok(active) = run_cmd_capture(
-        "systemctl",
+        Tool::Systemctl,
         &["--user", "is-active", "kitsune-rendercore.service"],
     ) {
         active.trim().to_string()
@@ -241,43 +786,141 @@ fn run_status(args: &[String]) -> Result<(), String> {
         "<unknown>".to_string()
     };
 
-    let monitors = detect_monitor_names().unwrap_or_default();
-    let mut mapped = Vec::<(String, String)>::new();
+    // Live facts from a running renderer, if its control socket answers.
+    let live = control::request("status")
+        .ok()
+        .and_then(|raw| parse_json(&raw).ok().map(|value| (raw, value)));
+    if watch && live.is_none() {
+        return Err("--watch needs a running renderer (control socket not reachable)".to_string());
+    }
+    let live_outputs = live
+        .as_ref()
+        .and_then(|(_, value)| match value.get("outputs") {
+            Some(JsonValue::Array(items)) => Some(items.clone()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    warn_if_renderer_map_differs(&map_path, live.as_ref().map(|(_, value)| value));
+    // A renderer stopped for quiet hours is not reachable by design.
+    let quiet_hours = env_file::var("KRC_QUIET_HOURS")
+        .and_then(|raw| QuietHours::parse(&raw).ok())
+        .filter(|quiet| live.is_none() && quiet.is_active_now());
+    let pause_reason = live
+        .as_ref()
+        .and_then(|(_, value)| value.get("pause_reason").and_then(JsonValue::as_str))
+        .or(quiet_hours.map(|_| "quiet-hours"))
+        .unwrap_or(pause_reason)
+        .to_string();
+    let reduce_motion = live
+        .as_ref()
+        .and_then(|(_, value)| value.get("reduce_motion").and_then(JsonValue::as_bool))
+        .unwrap_or_else(reduce_motion::is_on);
+
+    let supervisor = SupervisorState::load();
+
+    let merged_map = merge_maps(env_map, map_doc.entries);
+    let monitors = detect_monitor_names()
+        .ok()
+        .filter(|names| !names.is_empty())
+        .unwrap_or_else(|| {
+            live_outputs
+                .iter()
+                .filter_map(|out| out.get("name").and_then(JsonValue::as_str))
+                .map(str::to_string)
+                .collect()
+        });
+    let allowlist = OutputAllowlist::from_env();
+    let aliases = Aliases::load_from_env();
+    // Only a running renderer knows descriptions, which `desc:` aliases need.
+    let description_of = |m: &str| {
+        live_outputs
+            .iter()
+            .find(|out| out.get("name").and_then(JsonValue::as_str) == Some(m))
+            .and_then(|out| out.get("description"))
+            .and_then(JsonValue::as_str)
+            .filter(|description| !description.is_empty())
+            .map(str::to_string)
+    };
+    let mut mapped = Vec::<(String, VideoMapEntry, bool)>::new();
     for m in &monitors {
-        let selected = file_map
-            .get(m)
-            .cloned()
-            .or_else(|| env_map.get(m).cloned())
-            .or_else(|| default_video.clone())
-            .unwrap_or_else(|| "<none>".to_string());
-        mapped.push((m.clone(), selected));
+        let matched = aliases.matching(Some(m), description_of(m).as_deref());
+        // A running renderer also matches output descriptions, so it has the
+        // final word on what is excluded.
+        let excluded = match &live {
+            Some(_) => live_outputs.iter().any(|out| {
+                out.get("name").and_then(JsonValue::as_str) == Some(m.as_str())
+                    && out.get("stream").and_then(JsonValue::as_str) == Some("excluded")
+            }),
+            None => !allowlist.allows(Some(m), None, &matched),
+        };
+        // An output whose name an alias took only gets the default.
+        let selected = match aliases.map_key(&merged_map, &matched, m) {
+            Some(key) => resolve_output_video(&merged_map, default_video.as_ref(), &key),
+            None => default_video.clone(),
+        }
+        .unwrap_or_else(|| VideoMapEntry {
+            video: "<none>".to_string(),
+            options: EntryOptions::default(),
+            layout: None,
+        });
+        mapped.push((m.clone(), selected, excluded));
+    }
+
+    if let Some(template) = template {
+        // Same document as --json, so templates work with and without a live renderer.
+        let doc = build_status_json(
+            &map_path.display().to_string(),
+            &default_video_label,
+            &fps,
+            &speed,
+            &quality,
+            &hwaccel,
+            steam.is_enabled(),
+            steam_running,
+            &pause_reason,
+            &pause_file.display().to_string(),
+            reduce_motion,
+            &service_state,
+            &mapped,
+            supervisor.as_ref().map(SupervisorState::to_json).as_deref(),
+            live.as_ref().map(|(raw, _)| raw.as_str()),
+            false,
+        );
+        let doc = parse_json(&doc).map_err(|e| format!("failed to read status document: {e}"))?;
+        println!("{}", render_status_template(&template, &doc)?);
+        return Ok(());
     }
 
     if as_json {
         let out = build_status_json(
             &map_path.display().to_string(),
-            default_video.as_deref().unwrap_or("<none>"),
+            &default_video_label,
             &fps,
             &speed,
             &quality,
             &hwaccel,
             steam.is_enabled(),
             steam_running,
+            &pause_reason,
+            &pause_file.display().to_string(),
+            reduce_motion,
             &service_state,
             &mapped,
+            supervisor.as_ref().map(SupervisorState::to_json).as_deref(),
+            live.as_ref().map(|(raw, _)| raw.as_str()),
             json_pretty,
         );
         if let Some(path) = out_file {
             let p = std::path::PathBuf::from(&path);
-            if let Some(parent) = p.parent() {
-                if !parent.as_os_str().is_empty() {
-                    std::fs::create_dir_all(parent).map_err(|e| {
-                        format!(
-                            "failed to create parent directory {}: {e}",
-                            parent.display()
-                        )
-                    })?;
-                }
+            if let Some(parent) = p.parent()
+                && !parent.as_os_str().is_empty()
+            {
+                std::fs::create_dir_all(parent).map_err(|e| {
+                    format!(
+                        "failed to create parent directory {}: {e}",
+                        parent.display()
+                    )
+                })?;
             }
             std::fs::write(&p, out)
                 .map_err(|e| format!("failed to write status file {}: {e}", p.display()))?;
@@ -285,110 +928,1115 @@ fn run_status(args: &[String]) -> Result<(), String> {
         } else {
             println!("{}", out);
         }
-        return Ok(());
+        return if watch {
+            watch_stream_events(true)
+        } else {
+            Ok(())
+        };
     }
 
     println!("kitsune-rendercore status");
+    if reduce_motion {
+        println!(
+            "[info] reduce motion is on: every output shows a still frame ('reduce-motion off' plays again)"
+        );
+    }
     println!("map_file={}", map_path.display());
+    println!("map_format={}", map_doc.format.label());
     println!(
         "default_video={}",
-        default_video.as_deref().unwrap_or("<none>")
+        default_video
+            .as_ref()
+            .map_or_else(|| "<none>".to_string(), VideoMapEntry::display_label)
     );
     println!(
-        "runtime_cfg: fps={} speed={} quality={} hwaccel={}",
-        fps, speed, quality, hwaccel
+        "runtime_cfg: fps={} speed={} quality={} hwaccel={} video={}",
+        fps,
+        speed,
+        quality,
+        hwaccel,
+        version::video_support()
     );
     println!("steam_pause_enabled={}", steam.is_enabled());
     println!("steam_game_running={}", steam_running);
+    println!("pause_file={}", pause_file.display());
+    println!("pause_reason={}", pause_reason);
+    println!("reduce_motion={}", if reduce_motion { "on" } else { "off" });
     println!("service_state={}", service_state);
+    if let Some(supervisor) = &supervisor {
+        print_supervisor(supervisor);
+    }
+    if allowlist.is_restricted() {
+        println!("output_allowlist={}", allowlist.entries().join(","));
+    } else {
+        println!("output_allowlist=<all>");
+    }
+    match &live {
+        Some((_, value)) => {
+            let num = |key: &str| value.get(key).and_then(JsonValue::as_u64).unwrap_or(0);
+            println!(
+                "renderer: uptime={:.1}s frames={} wakeups={} backend={}",
+                num("uptime_ms") as f64 / 1000.0,
+                num("frames"),
+                num("wakeups"),
+                value
+                    .get("backend")
+                    .and_then(JsonValue::as_str)
+                    .unwrap_or("?")
+            );
+            if let Some(bootstrap) = value.get("bootstrap")
+                && let Some(total) = bootstrap.get("total_ms").and_then(JsonValue::as_u64)
+            {
+                // In the order they run; the parsed object sorts its keys.
+                let steps = [
+                    "config",
+                    "compositor",
+                    "adapter",
+                    "device",
+                    "surfaces",
+                    "pipelines",
+                    "streams",
+                ]
+                .iter()
+                .filter_map(|step| {
+                    let ms = bootstrap.get("steps")?.get(step)?.as_u64()?;
+                    Some(format!("{step}={ms}ms"))
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+                println!(
+                    "renderer: bootstrap={:.1}s ({steps})",
+                    total as f64 / 1000.0
+                );
+            }
+            if let Some(gpu) = value.get("gpu").filter(|gpu| gpu.get("name").is_some()) {
+                let field = |key: &str| gpu.get(key).and_then(JsonValue::as_str).unwrap_or("?");
+                let software = match gpu.get("fps_cap").and_then(JsonValue::as_u64) {
+                    Some(cap) => format!(" software, capped at {cap}fps"),
+                    None if field("device_type") == "cpu" => " software".to_string(),
+                    None => String::new(),
+                };
+                println!(
+                    "renderer: gpu={} ({}, {}){software}",
+                    field("name"),
+                    field("backend"),
+                    field("device_type")
+                );
+            }
+            if let Some(shell) = value
+                .get("shell")
+                .filter(|shell| shell.get("protocol").is_some())
+            {
+                let field = |key: &str| shell.get(key).and_then(JsonValue::as_str).unwrap_or("?");
+                println!(
+                    "renderer: shell={} on_missing={} ({})",
+                    field("protocol"),
+                    field("on_missing"),
+                    field("reason")
+                );
+            }
+            if let Some(achieved) = value.get("achieved_fps").and_then(JsonValue::as_f64) {
+                println!(
+                    "renderer: fps={achieved:.2}/{} late_avg={:.2}ms late_max={:.2}ms",
+                    num("target_fps"),
+                    num("frame_late_avg_us") as f64 / 1000.0,
+                    num("frame_late_max_us") as f64 / 1000.0
+                );
+            }
+            if let Some(scan_ms) = value.get("steam_scan_ms").and_then(JsonValue::as_f64)
+                && num("steam_scan_pids") > 0
+            {
+                println!(
+                    "renderer: steam_scan={scan_ms:.2}ms pids={} inspected={} over_budget={}",
+                    num("steam_scan_pids"),
+                    num("steam_scan_inspected"),
+                    num("steam_scan_over_budget")
+                );
+            }
+            if let Some(thermal) = value
+                .get("thermal")
+                .filter(|t| t.get("enabled").and_then(JsonValue::as_bool) == Some(true))
+            {
+                let reading = |key: &str, limit: &str, unit: &str, digits: usize| match (
+                    thermal.get(key).and_then(JsonValue::as_f64),
+                    thermal.get(limit).and_then(JsonValue::as_f64),
+                ) {
+                    (_, None) => String::new(),
+                    (Some(now), Some(limit)) => {
+                        format!(" {key}={now:.digits$}{unit}/{limit}{unit}")
+                    }
+                    (None, Some(limit)) => format!(" {key}=?/{limit}{unit}"),
+                };
+                println!(
+                    "renderer: thermal_pause={}{}{}",
+                    if thermal.get("active").and_then(JsonValue::as_bool) == Some(true) {
+                        "active"
+                    } else {
+                        "armed"
+                    },
+                    reading("temp_c", "pause_c", "C", 1),
+                    reading("cpu_pressure", "cpu_pressure_pause", "", 2)
+                );
+            }
+            if let Some(headroom) = value
+                .get("headroom")
+                .filter(|h| matches!(h, JsonValue::Object(_)))
+            {
+                let verdict = headroom
+                    .get("verdict")
+                    .and_then(JsonValue::as_str)
+                    .unwrap_or("?");
+                let list = |key: &str| match headroom.get(key) {
+                    Some(JsonValue::Array(items)) => items
+                        .iter()
+                        .filter_map(JsonValue::as_str)
+                        .collect::<Vec<_>>(),
+                    _ => Vec::new(),
+                };
+                println!(
+                    "renderer: headroom={verdict} decoders need ~{:.1} of {} cores{}",
+                    headroom
+                        .get("need_cores")
+                        .and_then(JsonValue::as_f64)
+                        .unwrap_or(0.0),
+                    headroom
+                        .get("capacity_cores")
+                        .and_then(JsonValue::as_f64)
+                        .unwrap_or(0.0),
+                    if headroom.get("pending").and_then(JsonValue::as_bool) == Some(true) {
+                        " (hwaccel probe pending)"
+                    } else {
+                        ""
+                    }
+                );
+                if verdict != "ok" {
+                    println!("renderer: headroom heaviest={}", list("heaviest").join(","));
+                    for suggestion in list("suggestions") {
+                        println!("renderer: headroom try: {suggestion}");
+                    }
+                }
+            }
+            if num("transient_overrides") > 0 {
+                println!(
+                    "renderer: transient_overrides={} (until restart)",
+                    num("transient_overrides")
+                );
+            }
+            if let Some(events) = value.get("stream_events") {
+                let count = |key: &str| events.get(key).and_then(JsonValue::as_u64).unwrap_or(0);
+                println!(
+                    "renderer: stream_events started={} degraded={} recovered={} stopped={} presented={} dropped={}",
+                    count("stream_started"),
+                    count("stream_degraded"),
+                    count("stream_recovered"),
+                    count("stream_stopped"),
+                    count("stream_presented"),
+                    count("dropped")
+                );
+            }
+        }
+        None => match quiet_hours {
+            Some(quiet) => println!(
+                "renderer=<stopped for quiet hours {quiet}, starts again at {}>",
+                quiet.end_label()
+            ),
+            None => println!("renderer=<not reachable via control socket>"),
+        },
+    }
     if monitors.is_empty() {
         println!("monitors=<unavailable>");
     } else {
         println!("monitors:");
-        for (m, selected) in mapped {
-            println!("  {} -> {}", m, selected);
+        for (m, selected, excluded) in mapped {
+            let name = match aliases.matching(Some(&m), description_of(&m).as_deref()) {
+                matched if matched.is_empty() => m.clone(),
+                matched => format!("{m} ({})", matched.join(", ")),
+            };
+            if excluded {
+                println!("  {} -> excluded (output allowlist)", name);
+                continue;
+            }
+            println!("  {} -> {}", name, selected.status_label());
+            if live.is_none() {
+                continue;
+            }
+            match live_outputs
+                .iter()
+                .find(|out| out.get("name").and_then(JsonValue::as_str) == Some(m.as_str()))
+            {
+                Some(out) => print_live_output(out),
+                None => println!("    live: <no surface>"),
+            }
+        }
+    }
+    if !aliases.is_empty() {
+        let outputs = monitors
+            .iter()
+            .map(|m| (m.clone(), description_of(m)))
+            .collect::<Vec<_>>();
+        for report in aliases.report(&outputs) {
+            match report.problem {
+                // Without the renderer, `desc:` aliases cannot be checked.
+                Some(_)
+                    if live.is_none()
+                        && matches!(aliases.resolve(&report.alias), Ok(Selector::Desc(_))) => {}
+                Some(problem) if report.outputs.is_empty() => {
+                    println!("[warn] alias {} {problem}", report.alias)
+                }
+                Some(problem) => println!(
+                    "[warn] alias {} {problem}: {}",
+                    report.alias,
+                    report.outputs.join(", ")
+                ),
+                None => {}
+            }
         }
     }
+    if watch {
+        return watch_stream_events(false);
+    }
     Ok(())
 }
 
-fn build_status_json(
-    map_file: &str,
-    default_video: &str,
-    fps: &str,
-    speed: &str,
-    quality: &str,
-    hwaccel: &str,
-    steam_pause_enabled: bool,
-    steam_game_running: bool,
-    service_state: &str,
-    mapped: &[(String, String)],
-    pretty: bool,
-) -> String {
-    if pretty {
-        let mut out = String::new();
-        out.push_str("{\n");
-        out.push_str(&format!("  \"map_file\": \"{}\",\n", escape_json(map_file)));
-        out.push_str(&format!(
-            "  \"default_video\": \"{}\",\n",
-            escape_json(default_video)
-        ));
-        out.push_str("  \"runtime\": {\n");
-        out.push_str(&format!("    \"fps\": \"{}\",\n", escape_json(fps)));
-        out.push_str(&format!("    \"speed\": \"{}\",\n", escape_json(speed)));
-        out.push_str(&format!("    \"quality\": \"{}\",\n", escape_json(quality)));
-        out.push_str(&format!("    \"hwaccel\": \"{}\"\n", escape_json(hwaccel)));
-        out.push_str("  },\n");
-        out.push_str(&format!(
-            "  \"steam_pause_enabled\": {},\n",
-            steam_pause_enabled
-        ));
-        out.push_str(&format!(
+/// `status --watch`: follows the renderer's stream events until it stops
+/// answering. Events are polled by sequence number, so nothing published
+/// between two polls is lost unless more than the renderer keeps piled up.
+fn watch_stream_events(as_json: bool) -> Result<(), String> {
+    let poll = |after: Option<u64>| -> Result<JsonValue, String> {
+        let command = match after {
+            Some(after) => format!("events {after}"),
+            None => "events".to_string(),
+        };
+        let raw = control::request(&command)?;
+        let value = parse_json(&raw).map_err(|e| format!("bad events reply: {e}"))?;
+        if let Some(err) = value.get("error").and_then(JsonValue::as_str) {
+            return Err(format!("renderer rejected events: {err}"));
+        }
+        Ok(value)
+    };
+    let mut next = poll(None)?
+        .get("next")
+        .and_then(JsonValue::as_u64)
+        .unwrap_or(0);
+    if !as_json {
+        println!("watching stream events (Ctrl+C to stop)");
+    }
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        let reply = match poll(Some(next)) {
+            Ok(reply) => reply,
+            Err(err) => {
+                eprintln!("[info] renderer stopped answering: {err}");
+                return Ok(());
+            }
+        };
+        let missed = reply.get("missed").and_then(JsonValue::as_u64).unwrap_or(0);
+        if missed > 0 {
+            eprintln!("[warn] missed {missed} event(s); the renderer keeps only the recent ones");
+        }
+        if let Some(JsonValue::Array(events)) = reply.get("events") {
+            for event in events {
+                if as_json {
+                    println!("{}", event.to_json());
+                } else {
+                    println!("{}", format_stream_event(event));
+                }
+            }
+        }
+        next = reply
+            .get("next")
+            .and_then(JsonValue::as_u64)
+            .unwrap_or(next);
+    }
+}
+
+/// `+12.3s stream_degraded DP-1 consecutive_failures=2 reason=...`
+fn format_stream_event(event: &JsonValue) -> String {
+    let JsonValue::Object(fields) = event else {
+        return String::new();
+    };
+    let text = |key: &str| event.get(key).and_then(JsonValue::as_str).unwrap_or("?");
+    let t_ms = event.get("t_ms").and_then(JsonValue::as_u64).unwrap_or(0);
+    let mut line = format!(
+        "+{:.1}s {} {}",
+        t_ms as f64 / 1000.0,
+        text("event"),
+        display_text(text("monitor"))
+    );
+    for (key, value) in fields {
+        if matches!(key.as_str(), "seq" | "t_ms" | "event" | "monitor") {
+            continue;
+        }
+        match value {
+            JsonValue::String(s) if key == "video" => {
+                line.push_str(&format!(" {key}={}", display_path_short(s)))
+            }
+            JsonValue::String(s) => line.push_str(&format!(" {key}={}", display_text(s))),
+            other => line.push_str(&format!(" {key}={}", other.to_json())),
+        }
+    }
+    line
+}
+
+fn print_live_output(out: &JsonValue) {
+    let text = |key: &str| out.get(key).and_then(JsonValue::as_str).unwrap_or("");
+    let source = text("source");
+    println!(
+        "    stream={} source={}",
+        text("stream"),
+        if source.is_empty() {
+            "<none>".to_string()
+        } else {
+            display_path(source)
+        }
+    );
+    if let Some(layout) = out.get("layout").and_then(JsonValue::as_str)
+        && !layout.is_empty()
+    {
+        println!("    layout={layout}");
+    }
+    if let Some(JsonValue::Array(cells)) = out.get("cells") {
+        for cell in cells {
+            let text = |key: &str| cell.get(key).and_then(JsonValue::as_str).unwrap_or("");
+            let source = text("source");
+            let error = cell
+                .get("last_error")
+                .and_then(JsonValue::as_str)
+                .map(|err| format!(" last_error=\"{}\"", display_text(err)))
+                .unwrap_or_default();
+            println!(
+                "    cell {}: {} {}{error}",
+                cell.get("cell").and_then(JsonValue::as_u64).unwrap_or(0),
+                text("kind"),
+                if source.is_empty() {
+                    "<none>".to_string()
+                } else {
+                    display_path(source)
+                }
+            );
+        }
+    }
+    let first_frame = out
+        .get("first_frame_ms")
+        .and_then(JsonValue::as_u64)
+        .map(|ms| format!("+{:.3}s", ms as f64 / 1000.0))
+        .unwrap_or_else(|| "<never>".to_string());
+    println!(
+        "    first_frame={} presented_frames={} frame_callback_pending={}",
+        first_frame,
+        out.get("presented_frames")
+            .and_then(JsonValue::as_u64)
+            .unwrap_or(0),
+        out.get("frame_callback_pending")
+            .and_then(JsonValue::as_bool)
+            .unwrap_or(false)
+    );
+    match out.get("degraded").and_then(JsonValue::as_str) {
+        Some("ffmpeg-not-found") => {
+            println!("    degraded: ffmpeg-not-found (looked for again every 30s; see doctor)")
+        }
+        Some("none") | None => {}
+        Some(reason) => println!("    degraded: {reason}"),
+    }
+    match out.get("last_error").and_then(JsonValue::as_str) {
+        Some(err) => {
+            let repeats = match out.get("last_error_repeats").and_then(JsonValue::as_u64) {
+                Some(n) if n > 0 => format!(", repeated {n} more times"),
+                _ => String::new(),
+            };
+            println!(
+                "    last_error=\"{}\" ({:.1}s ago{repeats})",
+                display_text(err),
+                out.get("last_error_age_ms")
+                    .and_then(JsonValue::as_u64)
+                    .unwrap_or(0) as f64
+                    / 1000.0
+            )
+        }
+        None => println!("    last_error=<none>"),
+    }
+    if let Some(size) = out.get("source_size").and_then(JsonValue::as_str)
+        && !size.is_empty()
+    {
+        let preset = out
+            .get("quality_preset")
+            .and_then(JsonValue::as_str)
+            .filter(|p| !p.is_empty())
+            .unwrap_or("global");
+        println!("    source_size={size} (quality={preset})");
+    }
+    if let Some(nice) = out.get("decode_nice").and_then(JsonValue::as_f64) {
+        println!("    decode_nice={nice}");
+    }
+    if let Some(decode) = out.get("decode").and_then(JsonValue::as_str)
+        && !decode.is_empty()
+    {
+        match out.get("decode_detail").and_then(JsonValue::as_str) {
+            Some(detail) if !detail.is_empty() => {
+                println!("    decode={decode} ({})", display_text(detail))
+            }
+            _ => println!("    decode={decode}"),
+        }
+    }
+    if let Some(effect) = out.get("effect").and_then(JsonValue::as_str)
+        && !effect.is_empty()
+    {
+        println!("    effect={effect}");
+    }
+    if let Some(overlay) = out.get("overlay").and_then(JsonValue::as_str)
+        && !overlay.is_empty()
+    {
+        println!("    overlay={overlay}");
+    }
+    if let Some(trim) = out.get("trim").and_then(JsonValue::as_str)
+        && !trim.is_empty()
+    {
+        println!("    trim={trim}");
+    }
+    if let Some(crop) = out.get("crop").and_then(JsonValue::as_str)
+        && !crop.is_empty()
+    {
+        println!("    crop={crop}");
+    }
+    if let (Some(filter), Some(fit)) = (
+        out.get("filter").and_then(JsonValue::as_str),
+        out.get("fit").and_then(JsonValue::as_str),
+    ) {
+        println!("    filter={filter} fit={fit}");
+    }
+    if let Some(color) = out
+        .get("color")
+        .filter(|color| color.get("source").is_some())
+    {
+        let field = |key: &str| color.get(key).and_then(JsonValue::as_str).unwrap_or("");
+        let note = color.get("note").and_then(JsonValue::as_str);
+        // sRGB passed through untouched, as without gamut= or the protocol.
+        if field("source") != "none" || field("gamut") != "auto" || note.is_some() {
+            let mut line = format!(
+                "    color={}: {} {} {}",
+                field("gamut"),
+                field("source"),
+                field("primaries"),
+                field("transfer")
+            );
+            if matches!(color.get("matrix"), Some(JsonValue::Array(_))) {
+                line.push_str(", mapped");
+            }
+            if color.get("tagged").and_then(JsonValue::as_bool) == Some(true) {
+                line.push_str(", tagged");
+            }
+            if let Some(note) = note {
+                line.push_str(&format!(" ({note})"));
+            }
+            println!("{line}");
+        }
+    }
+    if let Some(leader) = out.get("decoder_shared_with").and_then(JsonValue::as_str)
+        && !leader.is_empty()
+    {
+        println!("    decoder shared with {leader}");
+    }
+    if let Some(region) = out.get("input_region").and_then(JsonValue::as_str)
+        && region != "empty"
+    {
+        println!("    input_region={region} (the wallpaper may take clicks)");
+    }
+    if out.get("mode").and_then(JsonValue::as_str) == Some("suspect") {
+        println!(
+            "    mode=suspect (compositor reported {}, keeping the last good size)",
+            text("suspect_mode")
+        );
+    }
+    if let Some(process) = out.get("decoder_process")
+        && let Some(pid) = process.get("pid").and_then(JsonValue::as_u64)
+    {
+        let number = |key: &str| process.get(key).and_then(JsonValue::as_f64).unwrap_or(0.0);
+        println!(
+            "    decoder pid={pid} cpu={:.1}% rss={:.1}MiB up={}s",
+            number("cpu_percent"),
+            number("rss_bytes") / (1024.0 * 1024.0),
+            (number("uptime_ms") / 1000.0) as u64
+        );
+    }
+    if let Some(callback) = out.get("callback_latency")
+        && let Some(samples) = callback.get("samples").and_then(JsonValue::as_u64)
+        && (samples > 0 || callback.get("stalled").and_then(JsonValue::as_bool) == Some(true))
+    {
+        let ms = |key: &str| callback.get(key).and_then(JsonValue::as_f64).unwrap_or(0.0);
+        let flag = |key: &str| callback.get(key).and_then(JsonValue::as_bool) == Some(true);
+        println!(
+            "    frame_callback p50={:.2}ms p99={:.2}ms n={samples}{}{}",
+            ms("p50_ms"),
+            ms("p99_ms"),
+            if flag("slow") {
+                " (slow: compositor delays)"
+            } else {
+                ""
+            },
+            if flag("stalled") { " (stalled)" } else { "" }
+        );
+    }
+    if let Some(arrival) = out.get("frame_arrival") {
+        let count = |key: &str| arrival.get(key).and_then(JsonValue::as_u64).unwrap_or(0);
+        let (decode, io) = (count("starved_decode"), count("starved_io"));
+        if decode + io > 0 {
+            println!(
+                "    frame_arrival p95={:.2}ms pipe={}/{}B starved decode={decode} io={io}",
+                arrival
+                    .get("p95_ms")
+                    .and_then(JsonValue::as_f64)
+                    .unwrap_or(0.0),
+                count("queued_bytes"),
+                count("capacity_bytes")
+            );
+        }
+    }
+    if let Some(damage) = out.get("damage")
+        && let Some(presents) = damage.get("presents").and_then(JsonValue::as_u64)
+        && presents > 0
+    {
+        println!(
+            "    damage avg={:.1}% presents={presents} skipped={}",
+            damage
+                .get("avg_percent")
+                .and_then(JsonValue::as_f64)
+                .unwrap_or(0.0),
+            damage
+                .get("skipped")
+                .and_then(JsonValue::as_u64)
+                .unwrap_or(0)
+        );
+    }
+    match out.get("flash_guard").and_then(JsonValue::as_str) {
+        Some("smoothing") => println!("    flash_guard=smoothing (luminance swings blended)"),
+        Some("flash-guard-triggered") => {
+            println!("    flash_guard=flash-guard-triggered (flashing, frame held)")
+        }
+        _ => {}
+    }
+    if out.get("pause_reason").and_then(JsonValue::as_str) == Some("manual-output") {
+        println!("    paused on its own (pause --monitor)");
+    }
+    let buffer_scale = out.get("buffer_scale").and_then(JsonValue::as_u64);
+    let buffer_transform = out.get("buffer_transform").and_then(JsonValue::as_str);
+    if buffer_scale.is_some_and(|scale| scale > 1)
+        || buffer_transform.is_some_and(|transform| transform != "normal")
+    {
+        println!(
+            "    buffer scale={} transform={}",
+            buffer_scale.unwrap_or(1),
+            buffer_transform.unwrap_or("normal")
+        );
+    }
+    if out.get("clamped").and_then(JsonValue::as_bool) == Some(true) {
+        println!("    clamped: buffer below the output's resolution (GPU texture limit)");
+    }
+    if let Some(level) = out.get("quality_level").and_then(JsonValue::as_u64)
+        && level > 0
+    {
+        println!(
+            "    auto_quality=level {} ({})",
+            level,
+            out.get("quality").and_then(JsonValue::as_str).unwrap_or("")
+        );
+    }
+}
+
+/// The `supervise` line of `status`; a supervisor that gave up is a warning.
+fn print_supervisor(supervisor: &SupervisorState) {
+    let last_exit = match (&supervisor.last_exit, supervisor.last_exit_at_ms) {
+        (Some(exit), Some(at)) => format!("{exit} ({}s ago)", now_ms().saturating_sub(at) / 1000),
+        (Some(exit), None) => exit.clone(),
+        _ => "<none>".to_string(),
+    };
+    if supervisor.state == "gave-up" {
+        println!(
+            "[warn] supervisor gave up after {} restarts within {} min; last exit: {last_exit}; start 'kitsune-rendercore supervise' again once fixed",
+            supervisor.recent_restarts,
+            supervisor.window_sec / 60
+        );
+        return;
+    }
+    println!(
+        "supervisor={} pid={} child={} restarts={} ({} in the last {} min, limit {}) last_exit={last_exit}",
+        supervisor.state,
+        supervisor.pid,
+        supervisor
+            .child_pid
+            .map_or_else(|| "<none>".to_string(), |pid| pid.to_string()),
+        supervisor.restarts,
+        supervisor.recent_restarts,
+        supervisor.window_sec / 60,
+        supervisor.max_restarts
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_status_json(
+    map_file: &str,
+    default_video: &str,
+    fps: &str,
+    speed: &str,
+    quality: &str,
+    hwaccel: &str,
+    steam_pause_enabled: bool,
+    steam_game_running: bool,
+    pause_reason: &str,
+    pause_file: &str,
+    reduce_motion: bool,
+    service_state: &str,
+    mapped: &[(String, VideoMapEntry, bool)],
+    supervisor: Option<&str>,
+    live: Option<&str>,
+    pretty: bool,
+) -> String {
+    if pretty {
+        let mut out = String::new();
+        out.push_str("{\n");
+        out.push_str(&format!(
+            "  \"schema_version\": {},\n",
+            STATUS_SCHEMA_VERSION
+        ));
+        out.push_str(&format!("  \"map_file\": \"{}\",\n", escape_json(map_file)));
+        out.push_str(&format!(
+            "  \"default_video\": \"{}\",\n",
+            escape_json(default_video)
+        ));
+        out.push_str("  \"runtime\": {\n");
+        out.push_str(&format!("    \"fps\": \"{}\",\n", escape_json(fps)));
+        out.push_str(&format!("    \"speed\": \"{}\",\n", escape_json(speed)));
+        out.push_str(&format!("    \"quality\": \"{}\",\n", escape_json(quality)));
+        out.push_str(&format!("    \"hwaccel\": \"{}\",\n", escape_json(hwaccel)));
+        out.push_str(&format!(
+            "    \"video\": \"{}\"\n",
+            version::video_support()
+        ));
+        out.push_str("  },\n");
+        out.push_str(&format!(
+            "  \"steam_pause_enabled\": {},\n",
+            steam_pause_enabled
+        ));
+        out.push_str(&format!(
             "  \"steam_game_running\": {},\n",
             steam_game_running
         ));
+        out.push_str(&format!(
+            "  \"pause_reason\": \"{}\",\n",
+            escape_json(pause_reason)
+        ));
+        out.push_str(&format!(
+            "  \"pause_file\": \"{}\",\n",
+            escape_json(pause_file)
+        ));
+        out.push_str(&format!("  \"reduce_motion\": {reduce_motion},\n"));
         out.push_str(&format!(
             "  \"service_state\": \"{}\",\n",
             escape_json(service_state)
         ));
         out.push_str("  \"monitors\": [\n");
-        for (idx, (m, v)) in mapped.iter().enumerate() {
+        for (idx, (m, v, excluded)) in mapped.iter().enumerate() {
             let comma = if idx + 1 == mapped.len() { "" } else { "," };
             out.push_str(&format!(
-                "    {{\"name\":\"{}\",\"video\":\"{}\"}}{}\n",
+                "    {{\"name\":\"{}\",\"video\":\"{}\",\"rotate\":{},\"flip\":\"{}\",\"quality\":\"{}\",\"excluded\":{}}}{}\n",
                 escape_json(m),
-                escape_json(v),
+                escape_json(&v.video),
+                v.options.rotate.degrees(),
+                v.options.flip.as_str(),
+                v.options.quality.map_or("default", QualityPreset::as_str),
+                excluded,
                 comma
             ));
         }
-        out.push_str("  ]\n");
+        out.push_str("  ],\n");
+        out.push_str(&format!(
+            "  \"supervisor\": {},\n",
+            supervisor.unwrap_or("null")
+        ));
+        out.push_str(&format!("  \"live\": {}\n", live.unwrap_or("null")));
         out.push('}');
         return out;
     }
 
     let monitors_json = mapped
         .iter()
-        .map(|(m, v)| {
+        .map(|(m, v, excluded)| {
             format!(
-                "{{\"name\":\"{}\",\"video\":\"{}\"}}",
+                "{{\"name\":\"{}\",\"video\":\"{}\",\"rotate\":{},\"flip\":\"{}\",\"quality\":\"{}\",\"excluded\":{}}}",
                 escape_json(m),
-                escape_json(v)
+                escape_json(&v.video),
+                v.options.rotate.degrees(),
+                v.options.flip.as_str(),
+                v.options.quality.map_or("default", QualityPreset::as_str),
+                excluded
             )
         })
         .collect::<Vec<_>>()
         .join(",");
     format!(
-        "{{\"map_file\":\"{}\",\"default_video\":\"{}\",\"runtime\":{{\"fps\":\"{}\",\"speed\":\"{}\",\"quality\":\"{}\",\"hwaccel\":\"{}\"}},\"steam_pause_enabled\":{},\"steam_game_running\":{},\"service_state\":\"{}\",\"monitors\":[{}]}}",
+        "{{\"schema_version\":{},\"map_file\":\"{}\",\"default_video\":\"{}\",\"runtime\":{{\"fps\":\"{}\",\"speed\":\"{}\",\"quality\":\"{}\",\"hwaccel\":\"{}\",\"video\":\"{}\"}},\"steam_pause_enabled\":{},\"steam_game_running\":{},\"pause_reason\":\"{}\",\"pause_file\":\"{}\",\"reduce_motion\":{},\"service_state\":\"{}\",\"monitors\":[{}],\"supervisor\":{},\"live\":{}}}",
+        STATUS_SCHEMA_VERSION,
         escape_json(map_file),
         escape_json(default_video),
         escape_json(fps),
         escape_json(speed),
         escape_json(quality),
         escape_json(hwaccel),
+        version::video_support(),
         steam_pause_enabled,
         steam_game_running,
+        escape_json(pause_reason),
+        escape_json(pause_file),
+        reduce_motion,
         escape_json(service_state),
-        monitors_json
+        monitors_json,
+        supervisor.unwrap_or("null"),
+        live.unwrap_or("null")
+    )
+}
+
+fn run_pause(args: &[String], pause: bool) -> Result<(), String> {
+    let name = if pause { "pause" } else { "resume" };
+    match args {
+        [] => {}
+        [flag, monitor] if flag == "--monitor" => return run_pause_output(monitor, pause),
+        [flag] if flag == "--monitor" => return Err("missing value for --monitor".to_string()),
+        [flag, rest @ ..] if flag == "--all" => return run_pause_all(rest, pause),
+        [flag, ..] if flag == "--except" || flag == "--only" => {
+            return Err(format!("{flag} requires --all"));
+        }
+        [arg, ..] if arg == "--help" || arg == "-h" => {
+            print_pause_help();
+            return Ok(());
+        }
+        [arg, ..] => return Err(format!("unknown argument for {name}: {arg}")),
+    }
+    let path = pause_file_path_from_env();
+    if pause {
+        create_pause_file(&path)?;
+        println!("[ok] pause file created: {}", path.display());
+        println!("[ok] renderer pauses within ~1s (reason=manual-file).");
+    } else if remove_pause_file(&path)? {
+        println!("[ok] pause file removed: {}", path.display());
+        println!("[ok] renderer resumes within ~1s.");
+    } else {
+        println!("[ok] no pause file at {} (already resumed)", path.display());
+    }
+    Ok(())
+}
+
+/// `pause --all` / `resume --all`: `pause --monitor` for every live output the
+/// filter selects. Unlike a plain `pause` the pause file is left alone, so the
+/// skipped outputs keep playing.
+fn run_pause_all(args: &[String], pause: bool) -> Result<(), String> {
+    let (except, only) = match args {
+        [] => (None, None),
+        [flag, list] if flag == "--except" => (Some(list.as_str()), None),
+        [flag, list] if flag == "--only" => (None, Some(list.as_str())),
+        [flag, _, other, _] if (flag == "--except" || flag == "--only") && flag != other => {
+            return Err("--except and --only are mutually exclusive".to_string());
+        }
+        [flag] if flag == "--except" || flag == "--only" => {
+            return Err(format!("missing value for {flag}"));
+        }
+        [arg, ..] => return Err(format!("unknown argument for --all: {arg}")),
+    };
+    let filter = MonitorFilter::from_flags(except, only)?;
+    let list = monitors_for_all()
+        .ok()
+        .filter(|list| list.source == "running renderer")
+        .ok_or("per-output pauses need the running renderer (no reply on the control socket)")?;
+    warn_unmatched(&filter, &list, &[]);
+    let mut changed = 0usize;
+    for monitor in &list.names {
+        if !filter.selects(&list.forms(monitor)) {
+            println!("[ok] skipped monitor {}: {monitor}", skipped_by(&filter));
+            continue;
+        }
+        run_pause_output(monitor, pause)?;
+        changed += 1;
+    }
+    println!(
+        "[ok] {} {changed} of {} outputs",
+        if pause { "paused" } else { "resumed" },
+        list.names.len()
+    );
+    Ok(())
+}
+
+/// `pause --monitor` / `resume --monitor`: only the running renderer knows
+/// per-output pauses, so this goes through the control socket.
+fn run_pause_output(monitor: &str, pause: bool) -> Result<(), String> {
+    let command = if pause {
+        "pause-output"
+    } else {
+        "resume-output"
+    };
+    let reply = control::request(&format!("{command} {}", monitor.trim()))?;
+    let value = parse_json(&reply).map_err(|e| format!("unreadable {command} reply: {e}"))?;
+    if let Some(err) = value.get("error").and_then(JsonValue::as_str) {
+        return Err(err.to_string());
+    }
+    let changed = value
+        .get("changed")
+        .and_then(JsonValue::as_bool)
+        .unwrap_or(false);
+    match (pause, changed) {
+        (true, true) => println!("[ok] {monitor} frozen on its last frame"),
+        (true, false) => println!("[ok] {monitor} was already paused"),
+        (false, true) => println!("[ok] {monitor} resumed"),
+        (false, false) => println!("[ok] {monitor} was not paused on its own"),
+    }
+    Ok(())
+}
+
+fn run_hooks(args: &[String]) -> Result<(), String> {
+    let action = args.first().map(|s| s.as_str()).unwrap_or("list");
+    if matches!(action, "--help" | "-h" | "help") {
+        print_hooks_help();
+        return Ok(());
+    }
+    let config_path = config_file_path_from_env()?;
+    let config = HookConfig::from_config(&ConfigFile::load(&config_path));
+    match action {
+        "list" => {
+            println!("config_file={}", config_path.display());
+            println!(
+                "timeout_ms={} debounce_ms={}",
+                config.timeout.as_millis(),
+                config.debounce.as_millis()
+            );
+            for event in HookEvent::ALL {
+                println!(
+                    "  {} = {}",
+                    event.config_key(),
+                    config.command(event).unwrap_or("<unset>")
+                );
+            }
+            Ok(())
+        }
+        "test" => {
+            let event_raw = args
+                .get(1)
+                .ok_or_else(|| "missing event (usage: hooks test <EVENT>)".to_string())?;
+            let event = HookEvent::parse(event_raw)
+                .ok_or_else(|| format!("unknown hook event: {event_raw}"))?;
+            let mut monitor = None::<String>;
+            let mut video = None::<String>;
+            let mut reason = None::<String>;
+            let mut i = 2usize;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--monitor" => {
+                        i += 1;
+                        monitor = args.get(i).cloned();
+                    }
+                    "--video" => {
+                        i += 1;
+                        video = args.get(i).cloned();
+                    }
+                    "--reason" => {
+                        i += 1;
+                        reason = args.get(i).cloned();
+                    }
+                    unknown => return Err(format!("unknown argument for hooks test: {unknown}")),
+                }
+                i += 1;
+            }
+            let command = config
+                .command(event)
+                .ok_or_else(|| format!("no {} hook configured", event.config_key()))?;
+            println!("[ok] firing {}: {}", event.config_key(), command);
+            let ctx = HookContext {
+                monitor: monitor.as_deref(),
+                video: video.as_deref(),
+                reason: reason.as_deref().or(Some("test")),
+                detail: None,
+            };
+            let handle = spawn_hook(command, event, ctx, config.timeout)?;
+            match handle.join().ok().flatten() {
+                Some(status) if status.success() => {
+                    println!("[ok] hook exited successfully");
+                    Ok(())
+                }
+                Some(status) => Err(format!("hook exited with {status}")),
+                None => Err("hook timed out or could not be waited on".to_string()),
+            }
+        }
+        other => Err(format!("unknown hooks action: {other}")),
+    }
+}
+
+fn run_replay(args: &[String]) -> Result<(), String> {
+    let mut file = None::<String>;
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "--verbose" | "-v" => verbose = true,
+            "--help" | "-h" => {
+                print_replay_help();
+                return Ok(());
+            }
+            other if other.starts_with('-') => {
+                return Err(format!("unknown argument for replay: {other}"));
+            }
+            other => file = Some(other.to_string()),
+        }
+    }
+    let file = file.ok_or_else(|| "missing journal file (usage: replay <FILE>)".to_string())?;
+    let divergences = replay_journal(std::path::Path::new(&file), verbose)?;
+    if divergences > 0 {
+        return Err(format!(
+            "replay diverged from the journal {divergences} time(s)"
+        ));
+    }
+    println!("[ok] replay matched the recorded decisions");
+    Ok(())
+}
+
+fn run_reload(args: &[String]) -> Result<(), String> {
+    let mut hard = false;
+    for arg in args {
+        match arg.as_str() {
+            "--hard" => hard = true,
+            "--help" | "-h" => {
+                print_reload_help();
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument for reload: {other}")),
+        }
+    }
+    let command = if hard { "reload hard" } else { "reload" };
+    let reply = match control::request(command) {
+        Ok(reply) => reply,
+        Err(err) => {
+            // No control socket (e.g. disabled): a plain SIGHUP still gets a soft reload.
+            let instance = instance_id_from_env();
+            let Some(pid) = running_pid(&instance) else {
+                return Err(format!(
+                    "no running renderer for instance '{instance}' ({err})"
+                ));
+            };
+            if hard {
+                return Err(format!(
+                    "--hard needs the control socket ({err}); renderer pid {pid} only takes SIGHUP"
+                ));
+            }
+            send_signal(pid, libc::SIGHUP)?;
+            println!("[ok] sent SIGHUP to renderer pid {pid} (soft reload, no per-monitor report)");
+            return Ok(());
+        }
+    };
+    let value = parse_json(&reply).map_err(|e| format!("unreadable reload reply: {e}"))?;
+    if let Some(err) = value.get("error").and_then(JsonValue::as_str) {
+        return Err(err.to_string());
+    }
+    println!("[ok] {} reload done", if hard { "hard" } else { "soft" });
+    if let Some(JsonValue::Array(outputs)) = value.get("outputs") {
+        for out in outputs {
+            let text = |key: &str| out.get(key).and_then(JsonValue::as_str).unwrap_or("");
+            let video = text("video");
+            println!(
+                "  {} -> {} ({})",
+                text("name"),
+                if video.is_empty() {
+                    "<none>".to_string()
+                } else {
+                    display_path(video)
+                },
+                text("action")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_profile() -> Result<(), String> {
+    let reply = control::request("profile")?;
+    if let Some(err) = parse_json(&reply)
+        .ok()
+        .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(str::to_string))
+    {
+        return Err(err);
+    }
+    println!("{reply}");
+    Ok(())
+}
+
+fn run_watch_map(args: &[String]) -> Result<(), String> {
+    let mut map_file = None::<String>;
+    let mut strict = false;
+    let mut once = false;
+    let mut i = 0usize;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--map-file" => {
+                i += 1;
+                map_file = args.get(i).cloned();
+            }
+            "--strict" => strict = true,
+            "--once" => once = true,
+            "--help" | "-h" => {
+                print_watch_map_help();
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument for watch-map: {other}")),
+        }
+        i += 1;
+    }
+    let path = map_file
+        .map(std::path::PathBuf::from)
+        .map_or_else(map_file_path_from_env, Ok)?;
+    watch_map(&path, strict, once, connected_output_names)
+}
+
+/// Warns on stderr when a running renderer resolved a different map file than
+/// this CLI, e.g. a service started without HOME or with another
+/// KRC_VIDEO_MAP_FILE. `live` is a status reply already at hand, if any.
+fn warn_if_renderer_map_differs(cli_map: &std::path::Path, live: Option<&JsonValue>) {
+    let queried;
+    let live = match live {
+        Some(value) => value,
+        None => {
+            let Some(value) = control::request("status")
+                .ok()
+                .and_then(|raw| parse_json(&raw).ok())
+            else {
+                return;
+            };
+            queried = value;
+            &queried
+        }
+    };
+    let Some(renderer_map) = live.get("map_file").and_then(JsonValue::as_str) else {
+        return;
+    };
+    if std::path::Path::new(renderer_map) != cli_map {
+        eprintln!(
+            "[warn] the running renderer uses map file {renderer_map}, but this command uses {}; set KRC_VIDEO_MAP_FILE (or HOME/XDG_CONFIG_HOME) the same way for both",
+            cli_map.display()
+        );
+    }
+}
+
+/// Output names from the compositor, or from a running renderer when neither
+/// Hyprland IPC nor the Wayland registry answers.
+fn connected_output_names() -> Option<Vec<String>> {
+    if let Ok(names) = detect_monitor_names()
+        && !names.is_empty()
+    {
+        return Some(names);
+    }
+    let raw = control::request("status").ok()?;
+    let live = parse_json(&raw).ok()?;
+    let Some(JsonValue::Array(outputs)) = live.get("outputs") else {
+        return None;
+    };
+    Some(
+        outputs
+            .iter()
+            .filter_map(|out| out.get("name").and_then(JsonValue::as_str))
+            .map(str::to_string)
+            .collect(),
     )
 }
 
@@ -396,33 +2044,27 @@ fn run_service(args: &[String]) -> Result<(), String> {
     let action = args.first().map(|s| s.as_str()).unwrap_or("status");
     match action {
         "enable" => run_cmd(
-            "systemctl",
+            Tool::Systemctl,
             &["--user", "enable", "--now", "kitsune-rendercore.service"],
         ),
         "disable" => run_cmd(
-            "systemctl",
+            Tool::Systemctl,
             &["--user", "disable", "--now", "kitsune-rendercore.service"],
         ),
         "start" => run_cmd(
-            "systemctl",
+            Tool::Systemctl,
             &["--user", "start", "kitsune-rendercore.service"],
         ),
         "stop" => run_cmd(
-            "systemctl",
+            Tool::Systemctl,
             &["--user", "stop", "kitsune-rendercore.service"],
         ),
-        "restart" => run_cmd(
-            "systemctl",
-            &["--user", "restart", "kitsune-rendercore.service"],
-        ),
+        "restart" => run_restart(&args[1..]),
         "status" => run_cmd(
-            "systemctl",
+            Tool::Systemctl,
             &["--user", "status", "kitsune-rendercore.service"],
         ),
-        "logs" => run_cmd(
-            "journalctl",
-            &["--user", "-u", "kitsune-rendercore.service", "-f"],
-        ),
+        "logs" => run_service_logs(&args[1..]),
         "install" => run_kitowall(&["live", "service-autostart", "install"]),
         "--help" | "-h" | "help" => {
             print_service_help();
@@ -432,19 +2074,70 @@ fn run_service(args: &[String]) -> Result<(), String> {
     }
 }
 
+/// `restart [--preserve-state]` (also `service restart`). With the flag the
+/// running renderer saves its runtime state first and the new one picks it
+/// up; without it the state is dropped and the new renderer starts from the
+/// map alone.
+fn run_restart(args: &[String]) -> Result<(), String> {
+    let mut preserve = false;
+    for arg in args {
+        match arg.as_str() {
+            "--preserve-state" => preserve = true,
+            "--help" | "-h" => {
+                print_restart_help();
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument for restart: {other}")),
+        }
+    }
+    let path = snapshot_file(&instance_id_from_env())?;
+    if preserve {
+        let saved = control::request("snapshot").and_then(|reply| {
+            let value =
+                parse_json(&reply).map_err(|e| format!("unreadable snapshot reply: {e}"))?;
+            match value.get("error").and_then(JsonValue::as_str) {
+                Some(err) => Err(err.to_string()),
+                None => Ok(value),
+            }
+        });
+        match saved {
+            Ok(value) => {
+                let count = |key: &str| value.get(key).and_then(JsonValue::as_u64).unwrap_or(0);
+                println!(
+                    "[ok] runtime state saved: {} paused output(s), {} transient override(s), {} playback position(s)",
+                    count("paused_outputs"),
+                    count("transient"),
+                    count("positions")
+                );
+            }
+            Err(err) if path.exists() => {
+                println!("[warn] could not save the runtime state now ({err})");
+                println!("[info] the last periodic snapshot is restored if still recent");
+            }
+            Err(err) => println!("[warn] no runtime state to preserve ({err})"),
+        }
+    } else {
+        match std::fs::remove_file(&path) {
+            Ok(()) => println!("[info] runtime state dropped; use --preserve-state to keep it"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("failed to remove {}: {e}", path.display())),
+        }
+    }
+    run_cmd(
+        Tool::Systemctl,
+        &["--user", "restart", "kitsune-rendercore.service"],
+    )
+}
+
 fn run_kitowall(args: &[&str]) -> Result<(), String> {
-    let status = Command::new("kitowall")
+    let status = Tool::Kitowall
+        .command()
         .args(args)
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
         .status()
-        .map_err(|e| {
-            format!(
-                "failed to execute kitowall {}: {e}. Install kitowall CLI first.",
-                args.join(" ")
-            )
-        })?;
+        .map_err(|e| Tool::Kitowall.spawn_error(e))?;
     if status.success() {
         Ok(())
     } else {
@@ -455,35 +2148,262 @@ fn run_kitowall(args: &[&str]) -> Result<(), String> {
     }
 }
 
-fn run_cmd(bin: &str, args: &[&str]) -> Result<(), String> {
-    let status = Command::new(bin)
+fn run_cmd(tool: Tool, args: &[&str]) -> Result<(), String> {
+    let status = tool
+        .command()
         .args(args)
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
         .status()
-        .map_err(|e| format!("failed to execute {bin}: {e}"))?;
+        .map_err(|e| tool.spawn_error(e))?;
     if status.success() {
         Ok(())
     } else {
-        Err(format!("{bin} exited with status: {status}"))
+        Err(format!("{} exited with status: {status}", tool.name()))
     }
 }
 
-fn run_cmd_capture(bin: &str, args: &[&str]) -> Result<String, String> {
-    let output = Command::new(bin)
+fn run_cmd_capture(tool: Tool, args: &[&str]) -> Result<String, String> {
+    let output = tool
+        .command()
         .args(args)
         .output()
-        .map_err(|e| format!("failed to execute {bin}: {e}"))?;
+        .map_err(|e| tool.spawn_error(e))?;
     if output.status.success() {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     } else {
-        Err(format!("{bin} exited with status: {}", output.status))
+        Err(format!(
+            "{} exited with status: {}",
+            tool.name(),
+            output.status
+        ))
     }
 }
 
+/// Monitor names from Hyprland when its IPC is reachable, otherwise from the
+/// Wayland registry, so sandboxed setups with only the Wayland socket still work.
 fn detect_monitor_names() -> Result<Vec<String>, String> {
-    let json = run_cmd_capture("hyprctl", &["-j", "monitors"])?;
+    let hyprland_err = match hyprland_monitor_names() {
+        Ok(names) => return Ok(names),
+        Err(err) => err,
+    };
+    wayland_output_names()
+        .map_err(|err| format!("no monitor source available: {hyprland_err}; {err}"))
+}
+
+/// Checks `--trim` against the video length from ffprobe: an in point at or
+/// past the end is rejected, an out point past it is clamped. Without ffprobe
+/// the window is kept as given (decoding then simply ends at the video's end).
+#[cfg(feature = "video-ffmpeg")]
+fn check_trim(video: &str, trim: Trim) -> Result<Trim, String> {
+    let duration_ms = match probe_duration_ms(video) {
+        Ok(duration_ms) => duration_ms,
+        Err(err) => {
+            println!("[warn] cannot check --trim against the video length: {err}");
+            return Ok(trim);
+        }
+    };
+    let seconds = |ms: u32| ms as f64 / 1000.0;
+    if trim.start_ms >= duration_ms {
+        return Err(format!(
+            "trim in {}s is not before the end of the video ({}s)",
+            seconds(trim.start_ms),
+            seconds(duration_ms)
+        ));
+    }
+    match trim.end_ms {
+        Some(end_ms) if end_ms > duration_ms => {
+            println!(
+                "[warn] trim out {}s is past the end of the video; using {}s",
+                seconds(end_ms),
+                seconds(duration_ms)
+            );
+            Ok(Trim {
+                end_ms: Some(duration_ms),
+                ..trim
+            })
+        }
+        _ => Ok(trim),
+    }
+}
+
+/// Checks `--crop` against the video size from ffprobe: a region reaching
+/// past the frame is cut to it, one with no area inside is rejected. Without
+/// ffprobe the region is kept as given (the renderer clamps it the same way).
+#[cfg(feature = "video-ffmpeg")]
+fn check_crop(video: &str, crop: Crop) -> Result<Crop, String> {
+    let (width, height) = match probe_video_size(video) {
+        Ok(size) => size,
+        Err(err) => {
+            println!("[warn] cannot check --crop against the video size: {err}");
+            return Ok(crop);
+        }
+    };
+    let Some(clamped) = crop.clamped((width, height)) else {
+        return Err(format!(
+            "crop {crop} has no area inside the {width}x{height} video"
+        ));
+    };
+    if clamped != crop {
+        println!("[warn] crop {crop} reaches past the {width}x{height} video; using {clamped}");
+    }
+    Ok(clamped)
+}
+
+#[cfg(not(feature = "video-ffmpeg"))]
+fn check_trim(_video: &str, _trim: Trim) -> Result<Trim, String> {
+    Err(format!("--trim needs video decoding: {NO_VIDEO_SUPPORT}"))
+}
+
+#[cfg(not(feature = "video-ffmpeg"))]
+fn check_crop(_video: &str, _crop: Crop) -> Result<Crop, String> {
+    Err(format!("--crop needs video decoding: {NO_VIDEO_SUPPORT}"))
+}
+
+/// Monitors `--all` works on, and where the list came from.
+pub struct MonitorList {
+    pub names: Vec<String>,
+    /// Outputs the renderer leaves alone (`KRC_OUTPUTS`); only known when the
+    /// list came from the renderer.
+    pub excluded: Vec<String>,
+    /// Each monitor's aliases (`[aliases]` in the config file).
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// Output descriptions; only known when the list came from the renderer.
+    pub descriptions: BTreeMap<String, String>,
+    pub source: &'static str,
+}
+
+impl MonitorList {
+    fn aliases_of(&self, name: &str) -> &[String] {
+        self.aliases.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every form `--except`/`--only` patterns are tried against.
+    fn forms(&self, name: &str) -> Vec<String> {
+        monitor_forms(
+            name,
+            self.aliases_of(name),
+            self.descriptions.get(name).map(String::as_str),
+        )
+    }
+
+    /// The forms of a map key: the key itself, plus those of the monitor it
+    /// names (directly or as one of its aliases).
+    fn key_forms(&self, key: &str) -> Vec<String> {
+        let mut forms = vec![key.to_string()];
+        for name in &self.names {
+            if name == key || self.aliases_of(name).iter().any(|alias| alias == key) {
+                forms.extend(self.forms(name));
+            }
+        }
+        forms
+    }
+}
+
+/// The running renderer's outputs when its control socket answers, since it
+/// knows what it excludes and sees outputs on any compositor; else Hyprland
+/// IPC, else the Wayland registry.
+pub fn monitors_for_all() -> Result<MonitorList, String> {
+    let live = control::request("status")
+        .ok()
+        .and_then(|raw| parse_json(&raw).ok());
+    if let Some(JsonValue::Array(outputs)) = live.as_ref().and_then(|v| v.get("outputs"))
+        && !outputs.is_empty()
+    {
+        let mut list = MonitorList {
+            names: Vec::new(),
+            excluded: Vec::new(),
+            aliases: BTreeMap::new(),
+            descriptions: BTreeMap::new(),
+            source: "running renderer",
+        };
+        for out in outputs {
+            let Some(name) = out.get("name").and_then(JsonValue::as_str) else {
+                continue;
+            };
+            if out.get("stream").and_then(JsonValue::as_str) == Some("excluded") {
+                list.excluded.push(name.to_string());
+            }
+            if let Some(description) = out
+                .get("description")
+                .and_then(JsonValue::as_str)
+                .filter(|d| !d.is_empty())
+            {
+                list.descriptions
+                    .insert(name.to_string(), description.to_string());
+            }
+            if let Some(JsonValue::Array(aliases)) = out.get("aliases") {
+                list.aliases.insert(
+                    name.to_string(),
+                    aliases
+                        .iter()
+                        .filter_map(JsonValue::as_str)
+                        .map(str::to_string)
+                        .collect(),
+                );
+            }
+            list.names.push(name.to_string());
+        }
+        list.names.sort();
+        list.names.dedup();
+        return Ok(list);
+    }
+    let (names, source) = match hyprland_monitor_names() {
+        Ok(names) => (names, "Hyprland IPC"),
+        Err(hyprland_err) => (
+            wayland_output_names()
+                .map_err(|err| format!("no monitor source available: {hyprland_err}; {err}"))?,
+            "Wayland outputs",
+        ),
+    };
+    // Without the renderer only name aliases can be matched.
+    let aliases = Aliases::load_from_env();
+    Ok(MonitorList {
+        aliases: names
+            .iter()
+            .map(|name| (name.clone(), aliases.matching(Some(name), None)))
+            .collect(),
+        names,
+        excluded: Vec::new(),
+        descriptions: BTreeMap::new(),
+        source,
+    })
+}
+
+/// A pattern that matches nothing is usually a typo that makes `--all` touch
+/// the monitor it meant to skip, or `--only` touch none. `mapped` are map
+/// keys, which count as monitors too.
+fn warn_unmatched(filter: &MonitorFilter, list: &MonitorList, mapped: &[String]) {
+    let mut monitors = list
+        .names
+        .iter()
+        .map(|name| list.forms(name))
+        .collect::<Vec<_>>();
+    monitors.extend(mapped.iter().map(|key| vec![key.clone()]));
+    for pattern in filter.unmatched(&monitors) {
+        println!(
+            "[warn] {} {}: matches no monitor in the {}",
+            filter.flag(),
+            display_text(pattern),
+            list.source
+        );
+    }
+}
+
+/// The `[ok] skipped ...` reason for a monitor `filter` leaves out.
+fn skipped_by(filter: &MonitorFilter) -> &'static str {
+    match filter {
+        MonitorFilter::Only(_) => "not matched by --only",
+        _ => "matched by --except",
+    }
+}
+
+fn hyprland_monitor_names() -> Result<Vec<String>, String> {
+    if hyprland_ipc_socket().is_none() {
+        return Err("Hyprland IPC socket not reachable".to_string());
+    }
+    let json = run_cmd_capture(Tool::Hyprctl, &["-j", "monitors"])?;
     let mut names = Vec::new();
     let mut rest = json.as_str();
     while let Some(idx) = rest.find("\"name\"") {
@@ -491,14 +2411,14 @@ fn detect_monitor_names() -> Result<Vec<String>, String> {
         if let Some(colon) = rest.find(':') {
             rest = &rest[colon + 1..];
             let trimmed = rest.trim_start();
-            if let Some(stripped) = trimmed.strip_prefix('"') {
-                if let Some(end) = stripped.find('"') {
-                    let name = &stripped[..end];
-                    if !name.is_empty() {
-                        names.push(name.to_string());
-                    }
-                    rest = &stripped[end + 1..];
+            if let Some(stripped) = trimmed.strip_prefix('"')
+                && let Some(end) = stripped.find('"')
+            {
+                let name = &stripped[..end];
+                if !name.is_empty() {
+                    names.push(name.to_string());
                 }
+                rest = &stripped[end + 1..];
             }
         }
     }
@@ -507,23 +2427,25 @@ fn detect_monitor_names() -> Result<Vec<String>, String> {
     Ok(names)
 }
 
-fn parse_csv_list(raw: &str) -> Vec<String> {
-    raw.split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect()
-}
-
-fn escape_json(s: &str) -> String {
-    s.replace('\\', "\\\\").replace('"', "\\\"")
-}
-
 fn print_help() {
     println!("kitsune-rendercore - Wayland live wallpaper renderer");
     println!();
     println!("Usage:");
-    println!("  kitsune-rendercore");
-    println!("    Run renderer using current environment/configuration.");
+    println!("  kitsune-rendercore [--foreground|--daemonize] [--replace] [--no-hooks]");
+    println!("    Run renderer using current environment/configuration (foreground by default).");
+    println!(
+        "    One renderer per KRC_INSTANCE; --replace asks the running one to quit and takes over."
+    );
+    println!("    --daemonize returns once the wallpaper is up; it is ignored under systemd.");
+    println!("  kitsune-rendercore supervise [--replace] [--no-hooks]");
+    println!(
+        "    Run the renderer as a child and restart it when it crashes (see supervise --help)."
+    );
+    println!();
+    println!(
+        "  kitsune-rendercore setup [--defaults] [--video <PATH>] [--service] [--start|--preview]"
+    );
+    println!("    First-run wizard: a video per monitor, quality, fps, the user service.");
     println!();
     println!("  kitsune-rendercore status");
     println!(
@@ -531,19 +2453,93 @@ fn print_help() {
     );
     println!();
     println!(
-        "  kitsune-rendercore set-video (--monitor <MONITOR> | --all) --video <VIDEO_PATH> [--except <MON1,MON2>] [--map-file <PATH>]"
+        "  kitsune-rendercore set-video (--monitor <MONITOR> | --all) --video <VIDEO_PATH> [--rotate <DEG>] [--flip <h|v|hv>] [--quality <PRESET>] [--effect ambient [--amplitude <A>] [--period <SEC>] [--drift <D>]] [--overlay <PRESET> [--density <D>] [--speed <S>] [--wind <W>]] [--trim <IN:OUT>] [--crop <X,Y,W,H>] [--filter <nearest|linear>] [--fit <cover|integer>] [--gamut <MODE>] [--clock <on|off|auto>] [--timebase <monotonic|utc|auto>] [--except <LIST> | --only <LIST>] [--map-file <PATH>]"
     );
     println!(
         "    Update one monitor (or all monitors) mapping for hot-reload without restarting the renderer."
     );
     println!();
     println!(
-        "  kitsune-rendercore unset-video (--monitor <MONITOR> | --all) [--except <MON1,MON2>] [--map-file <PATH>]"
+        "  kitsune-rendercore unset-video (--monitor <MONITOR> | --all) [--except <LIST> | --only <LIST>] [--map-file <PATH>]"
     );
     println!("    Remove one mapping, or all mappings with optional exclusions.");
     println!();
+    println!(
+        "  kitsune-rendercore quality (--monitor <MONITOR> | --all) <low|medium|high|ultra|default>"
+    );
+    println!("    Set a per-monitor source quality preset, overriding KRC_QUALITY for that entry.");
+    println!();
+    println!("  kitsune-rendercore alias [list | set <ALIAS> <TARGET> | unset <ALIAS>]");
+    println!("    Stable monitor names ('desc:LG ULTRAGEAR', DP-3) for the map and --monitor.");
+    println!("  kitsune-rendercore monitors [list | rename <OUTPUT|ALIAS> <NEW_ALIAS>]");
+    println!("    List outputs with their aliases, or pin an alias to an output's description.");
+    println!();
     println!("  kitsune-rendercore status [--json] [--pretty|--compact] [--file <PATH>]");
     println!("    Show current runtime/service/monitor mapping in text or JSON.");
+    println!("  kitsune-rendercore status --watch [--json]");
+    println!("    Then follow per-output stream events until the renderer exits.");
+    println!("  kitsune-rendercore status --format <TEMPLATE>");
+    println!("    One-line custom output for bars, e.g. '{{{{monitor:DP-1.video|basename}}}}'.");
+    println!();
+    println!("  kitsune-rendercore replay <FILE> [--verbose]");
+    println!("    Replay a decision journal recorded with KRC_RECORD and report divergences.");
+    println!();
+    println!("  kitsune-rendercore events [--tail <N>] [--type <TYPE>] [--json] [--file <PATH>]");
+    println!("    Print the last wallpaper changes, pauses and stream events from KRC_EVENT_LOG.");
+    println!();
+    println!("  kitsune-rendercore watch-map [--map-file <PATH>] [--strict] [--once]");
+    println!("    Validate the map file on every save and print what changed.");
+    println!();
+    println!("  kitsune-rendercore migrate-map [--map-file <PATH>] [--to v1|v2] [--check]");
+    println!("    Convert video-map.conf (v1) to video-map.toml (v2), keeping a backup.");
+    println!();
+    println!(
+        "  kitsune-rendercore export-config --out <FILE> [--include-videos] [--map-file <PATH>]"
+    );
+    println!("    Pack the map, config.toml and env file (and the videos) to move a setup.");
+    println!(
+        "  kitsune-rendercore import-config <FILE> [--videos-dir <PATH>] [--force] [--allow-missing]"
+    );
+    println!("    Install such a bundle here, re-resolving paths and checking every video.");
+    println!();
+    println!("  kitsune-rendercore reload [--hard]");
+    println!("    Make the running renderer re-read the map and env file now; --hard also");
+    println!("    restarts unchanged decoders (e.g. after replacing a video file in place).");
+    println!();
+    println!("  kitsune-rendercore update-check [--json]");
+    println!("    Ask GitHub whether a newer release exists and how to upgrade; never downloads.");
+    println!();
+    println!("  kitsune-rendercore profile");
+    println!("    Print the render timing window of a renderer started with KRC_PROFILE=1.");
+    println!();
+    println!("  kitsune-rendercore hooks [list | test <EVENT>]");
+    println!("    List the [hooks] from the config file or test-fire one (see 'hooks --help').");
+    println!();
+    println!("  kitsune-rendercore pause | resume [--monitor <MONITOR>]");
+    println!("    Pause or resume rendering through the pause file (reason=manual-file),");
+    println!("    or freeze/unfreeze one output only.");
+    println!("  kitsune-rendercore reduce-motion [on|off|toggle]");
+    println!("    Show every output as a still of its first frame, e.g. while presenting.");
+    println!();
+    println!("  kitsune-rendercore doctor");
+    println!("    Check the Wayland socket, helper tools and XDG dirs (sandbox-aware).");
+    println!();
+    println!(
+        "  kitsune-rendercore bench [--video <PATH>] [--size <WxH>] [--duration <10s>] [--streams <N>] [--json]"
+    );
+    println!("    Measure decode, copy and offscreen render throughput and whether N outputs");
+    println!("    at that size keep up at 30/60 fps (see 'bench --help').");
+    println!();
+    println!("  kitsune-rendercore clean [--dry-run] [--cache] [--all] [--older-than <30d>]");
+    println!("    List the renderer's caches with their size; remove the selected ones.");
+    println!();
+    println!("  kitsune-rendercore paths [--json]");
+    println!("    Show every config, state, cache and runtime location, resolved from XDG_*");
+    println!("    and KRC_* overrides, and whether each exists and is writable.");
+    println!();
+    println!("  kitsune-rendercore version [--json] | --version");
+    println!("    Show the release, git revision, build date, cargo features and the");
+    println!("    wgpu/wayland-client versions linked. Include it in bug reports.");
     println!();
     println!("  kitsune-rendercore check-deps");
     println!("    Validate runtime dependencies via: kitowall live doctor");
@@ -558,6 +2554,9 @@ fn print_help() {
         "  kitsune-rendercore service <install|enable|disable|start|stop|restart|status|logs>"
     );
     println!("    Manage the user systemd service.");
+    println!("  kitsune-rendercore restart [--preserve-state]");
+    println!("    Restart it, keeping transient overrides, frozen outputs and playback");
+    println!("    positions with --preserve-state (the upgrade path).");
     println!();
     println!(
         "Run 'kitsune-rendercore service --help' or 'kitsune-rendercore set-video --help' for details."
@@ -568,41 +2567,100 @@ fn print_set_video_help() {
     println!("kitsune-rendercore set-video");
     println!("Usage:");
     println!(
-        "  kitsune-rendercore set-video (--monitor <MONITOR> | --all) --video <VIDEO_PATH> [--except <MON1,MON2>] [--map-file <PATH>]"
+        "  kitsune-rendercore set-video (--monitor <MONITOR> | --all) --video <VIDEO_PATH> [--rotate <DEG>] [--flip <h|v|hv>] [--quality <PRESET>] [--effect ambient [--amplitude <A>] [--period <SEC>] [--drift <D>]] [--overlay <PRESET> [--density <D>] [--speed <S>] [--wind <W>]] [--trim <IN:OUT>] [--crop <X,Y,W,H>] [--filter <nearest|linear>] [--fit <cover|integer>] [--gamut <MODE>] [--clock <on|off|auto>] [--timebase <monotonic|utc|auto>] [--except <LIST> | --only <LIST>] [--map-file <PATH>] [--no-wait | --wait-timeout <DUR> [--allow-paused]]"
     );
     println!();
     println!("Description:");
     println!("  Updates one monitor->video mapping in the map file.");
-    println!("  If renderer is running, it reloads the changed mapping automatically.");
+    println!("  With a renderer on the control socket, it is reloaded at once and the");
+    println!("  command waits until every stream the change switched has presented a frame");
+    println!("  of its new video; a stream that fails or times out exits non-zero with its");
+    println!("  reason. Without one, the renderer reloads the mapping when it next runs.");
     println!();
     println!("Options:");
     println!("  --monitor <MONITOR>   Monitor name (e.g. DP-1, eDP-1, HDMI-A-1).");
-    println!("  --all                 Apply same video to all detected monitors.");
-    println!("  --except <LIST>       Comma-separated monitor names to skip (only with --all).");
-    println!("  --video <VIDEO_PATH>  Absolute path to the video file.");
+    println!(
+        "  --all                 Apply same video to all monitors: the running renderer's outputs"
+    );
+    println!(
+        "                        (skipping those KRC_OUTPUTS excludes), else Hyprland IPC, else"
+    );
+    println!("                        the Wayland outputs. The source is printed.");
+    println!("  --except <LIST>       Comma-separated monitors to skip (only with --all): a");
+    println!("                        connector, alias or desc:<text>; '*' and '?' match any");
+    println!("                        run of characters or one (e.g. 'DP-*').");
+    println!("  --only <LIST>         The inverse: apply only to the monitors the list matches.");
+    println!("  --video <VIDEO_PATH>  Absolute path to the video file. still:<PATH>@<HH:MM:SS>");
+    println!("                        shows that one frame instead (default: 10% in).");
+    println!("                        layout=grid<C>x<R>:<PATH>,<PATH>,... tiles several videos,");
+    println!("                        row by row; missing cells stay black.");
+    println!("  --rotate <DEG>        Rotate the video on this monitor: 0, 90, 180 or 270.");
+    println!("  --flip <MODE>         Mirror the video: h, v, hv or none.");
+    println!("  --quality <PRESET>    Source quality for this monitor: low, medium, high, ultra.");
+    println!("  --effect <NAME>       Motion over the video: ambient (slow zoom) or none.");
+    println!("  --amplitude <A>       Ambient peak zoom, 0.001-0.25 (default 0.05).");
+    println!("  --period <SEC>        Ambient zoom cycle in seconds, 10-86400 (default 300).");
+    println!("  --drift <D>           Ambient pan within the zoom margin, 0-1 (default 0).");
+    println!("  --overlay <PRESET>    Particles over the picture: snow, rain, fireflies or none.");
+    println!(
+        "  --density <D>         Share of the preset's particles drawn, 0.01-1 (default 0.5)."
+    );
+    println!("  --speed <S>           Particle speed multiplier, 0.1-4 (default 1).");
+    println!("  --wind <W>            Sideways push, -1 (left) to 1 (right) (default 0).");
+    println!("  --trim <IN:OUT>       Play and loop only this window, in seconds (e.g. 2.0:14.5).");
+    println!("  --crop <X,Y,W,H>      Show only this region, in video pixels or 0-1 fractions.");
+    println!("  --filter <MODE>       Pixel sampling: linear (default) or nearest (pixel art).");
+    println!("  --fit <MODE>          cover (default) or integer: whole-pixel scaling, centred.");
+    println!("  --gamut <MODE>        Color mapping to the panel: auto (default; the compositor's");
+    println!("                        description of the output), srgb (none), native (the");
+    println!("                        EDID's primaries) or matrix:M11,M12,...,M33.");
+    println!("  --clock <MODE>        Clock widget on this monitor: on, off or auto (default;");
+    println!("                        follows [widgets.clock] enabled in config.toml).");
+    println!("  --timebase <MODE>     What effects and playback follow: monotonic, utc (wall");
+    println!("                        clock, in sync across machines) or auto (default;");
+    println!("                        follows KRC_TIMEBASE).");
     println!("  --map-file <PATH>     Custom map file path.");
+    println!("  --no-wait             Write the map and return; the renderer reloads it on its");
+    println!("                        next map check.");
+    println!("  --wait-timeout <DUR>  How long to wait for the new video on screen, e.g. 10s");
+    println!("                        (default) or 500ms.");
+    println!("  --allow-paused        Succeed when an output is paused and shows the video only");
+    println!("                        on resume (default: fail naming the pause).");
     println!();
     println!("Example:");
     println!(
         "  kitsune-rendercore set-video --monitor DP-1 --video /home/user/Videos/live/new.mp4"
     );
     println!("  kitsune-rendercore set-video --all --video /home/user/Videos/live/new.mp4");
+    println!(
+        "  kitsune-rendercore set-video --all --only 'DP-*' --video /home/user/Videos/live/new.mp4"
+    );
+    println!(
+        "  kitsune-rendercore set-video --monitor HDMI-A-1 --video still:/home/user/Videos/live/new.mp4@00:01:23"
+    );
 }
 
 fn print_unset_video_help() {
     println!("kitsune-rendercore unset-video");
     println!("Usage:");
     println!(
-        "  kitsune-rendercore unset-video (--monitor <MONITOR> | --all) [--except <MON1,MON2>] [--map-file <PATH>]"
+        "  kitsune-rendercore unset-video (--monitor <MONITOR> | --all [--except <LIST> | --only <LIST>] [--include-default] [--include-transient]) [--map-file <PATH>]"
     );
     println!();
     println!("Description:");
-    println!("  Removes one monitor mapping, or all mappings with --all.");
+    println!("  Removes one monitor mapping, or all mappings with --all. --all then lists");
+    println!("  what still plays on outputs without an entry (default, transient overrides).");
     println!();
     println!("Options:");
     println!("  --monitor <MONITOR>   Remove one mapping.");
     println!("  --all                 Remove all mappings.");
-    println!("  --except <LIST>       Comma-separated monitor names to keep (only with --all).");
+    println!("  --except <LIST>       Comma-separated monitors to keep (only with --all): a");
+    println!("                        connector, alias or desc:<text>; '*' and '?' are wildcards.");
+    println!("  --only <LIST>         The inverse: remove only the mappings the list matches.");
+    println!("  --include-default     Also remove the map file's [default] (only with --all).");
+    println!(
+        "  --include-transient   Also drop the running renderer's transient overrides (only with --all)."
+    );
     println!("  --map-file <PATH>     Custom map file path.");
 }
 
@@ -610,16 +2668,177 @@ fn print_status_help() {
     println!("kitsune-rendercore status");
     println!("Usage:");
     println!("  kitsune-rendercore status [--json] [--pretty|--compact] [--file <PATH>]");
+    println!("  kitsune-rendercore status --watch [--json]");
+    println!("  kitsune-rendercore status --schema");
+    println!("  kitsune-rendercore status --format <TEMPLATE>");
     println!();
     println!("Description:");
     println!("  Shows runtime config, Steam pause state, user service state,");
     println!("  and effective monitor->video mapping.");
+    println!(
+        "  The JSON carries \"schema_version\" ({STATUS_SCHEMA_VERSION}); it is bumped whenever a key"
+    );
+    println!("  is renamed, removed, or changes type, or an enum gains a value. New keys may");
+    println!("  appear without a bump.");
     println!();
     println!("Options:");
     println!("  --json       Print status as JSON for automation/CLI integration.");
     println!("  --pretty     Pretty JSON output (default when using --json).");
     println!("  --compact    Compact single-line JSON output.");
     println!("  --file PATH  Write JSON output to file (requires --json).");
+    println!("  --schema     Print the JSON Schema of the --json output and exit.");
+    println!("  --watch      After the status, follow stream events (stream_started,");
+    println!("               stream_degraded, stream_recovered, stream_stopped,");
+    println!("               stream_presented) until the renderer exits. With --json, one");
+    println!("               compact JSON object per line.");
+    println!("  --format T   Print one line from template T (alias --output-format), e.g.");
+    println!("               '{{{{monitor:DP-1.video|basename}}}} {{{{fps}}}}'.");
+    println!("               {{{{path}}}} reads a JSON field (runtime.fps or just fps);");
+    println!(
+        "               {{{{#monitors:SEP}}}}{{{{name}}}}={{{{video|basename}}}}{{{{/monitors}}}} loops"
+    );
+    println!("               over monitors. Filters: basename, truncate:N, default:TEXT.");
+}
+
+fn print_reload_help() {
+    println!("kitsune-rendercore reload");
+    println!("Usage:");
+    println!("  kitsune-rendercore reload [--hard]");
+    println!();
+    println!("Description:");
+    println!("  Asks the running renderer over its control socket to re-read the env file");
+    println!("  and the map now, regardless of mtimes, and prints per monitor what happened:");
+    println!("  unchanged, options (rotate/flip), switched (new video) or restarted.");
+    println!("  Without a control socket it falls back to SIGHUP (soft reload only).");
+    println!();
+    println!("Options:");
+    println!("  --hard  Also restart decoders whose mapping did not change.");
+}
+
+fn print_restart_help() {
+    println!("kitsune-rendercore restart");
+    println!("Usage:");
+    println!("  kitsune-rendercore restart [--preserve-state]");
+    println!();
+    println!("Description:");
+    println!("  Restarts the user service, e.g. after upgrading the binary. With");
+    println!("  --preserve-state the running renderer first saves what only it knows:");
+    println!("  transient video overrides (set-video transient over the control socket or");
+    println!("  D-Bus), outputs frozen with pause --monitor and where each output's video");
+    println!("  was playing. The new renderer restores them when the snapshot is recent");
+    println!("  (KRC_STATE_SNAPSHOT_TTL_SEC, default 300) and the map file is unchanged,");
+    println!("  and logs why otherwise. Without the flag the saved state is dropped.");
+    println!("  Reduce-motion and the map survive restarts either way.");
+    println!();
+    println!("  The service also saves the state every 30s, so a crash or a plain");
+    println!("  'systemctl --user restart' restores what it had up to then.");
+}
+
+fn print_replay_help() {
+    println!("kitsune-rendercore replay");
+    println!("Usage:");
+    println!("  kitsune-rendercore replay <FILE> [--verbose]");
+    println!();
+    println!("Description:");
+    println!("  Feeds a journal recorded with KRC_RECORD=/path/file.jsonl through the");
+    println!("  scheduler, pause logic, and map resolution without any hardware, and");
+    println!("  prints every event where the replayed decision differs from the recording.");
+    println!("  Exits non-zero when at least one divergence is found.");
+    println!();
+    println!("Options:");
+    println!("  --verbose  Print every replayed event.");
+}
+
+fn print_watch_map_help() {
+    println!("kitsune-rendercore watch-map");
+    println!("Usage:");
+    println!("  kitsune-rendercore watch-map [--map-file <PATH>] [--strict] [--once]");
+    println!();
+    println!("Description:");
+    println!("  Watches the map file and, on every change, re-parses it with the renderer's");
+    println!("  own parser. Reports entries added (+), removed (-) and changed (~), lines the");
+    println!("  renderer skips, videos that do not exist, and monitor names that match no");
+    println!("  connected output.");
+    println!();
+    println!("Options:");
+    println!("  --map-file <PATH>  Map file to watch (default: KRC_VIDEO_MAP_FILE or");
+    println!("                     $XDG_CONFIG_HOME/kitsune-rendercore/video-map.conf).");
+    println!("  --strict           Exit non-zero on the first report with errors.");
+    println!("  --once             Check the file once and exit (e.g. as an editor lint hook).");
+}
+
+fn print_hooks_help() {
+    println!("kitsune-rendercore hooks");
+    println!("Usage:");
+    println!("  kitsune-rendercore hooks [list]");
+    println!(
+        "  kitsune-rendercore hooks test <EVENT> [--monitor <MONITOR>] [--video <PATH>] [--reason <TEXT>]"
+    );
+    println!();
+    println!("Description:");
+    println!("  Hooks are shell commands from the [hooks] section of the config file");
+    println!("  ($XDG_CONFIG_HOME/kitsune-rendercore/config.toml, override with KRC_CONFIG_FILE):");
+    println!();
+    println!("    [hooks]");
+    println!("    on_pause = \"ledctl dim\"");
+    println!("    on_resume = \"ledctl restore\"");
+    println!("    on_wallpaper_change = \"notify-send \\\"$KRC_MONITOR\\\" \\\"$KRC_VIDEO\\\"\"");
+    println!("    on_output_added = \"...\"");
+    println!("    on_stream_degraded = \"notify-send \\\"$KRC_MONITOR\\\" \\\"$KRC_REASON\\\"\"");
+    println!("    timeout_ms = 10000   # kill the hook after this long");
+    println!("    debounce_ms = 1000   # skip repeats of the same hook/monitor within this window");
+    println!();
+    println!("  The renderer runs them with 'sh -c' in the background; failures and");
+    println!("  timeouts are logged and never affect rendering. Start the renderer with");
+    println!("  --no-hooks to disable them.");
+    println!();
+    println!("Environment passed to hooks:");
+    println!("  KRC_EVENT    pause | resume | wallpaper-change | output-added | stream-started");
+    println!("               | stream-degraded | stream-recovered | stream-stopped");
+    println!("               | stream-presented");
+    println!("  KRC_MONITOR  output name (wallpaper-change, output-added, stream-*), else empty");
+    println!("  KRC_VIDEO    new video path (wallpaper-change, stream-started,");
+    println!("               stream-presented), closed video (stream-stopped), else empty");
+    println!("  KRC_REASON   steam-game | manual-file (pause/resume), map-reload | env-file");
+    println!("               (wallpaper-change), the error (stream-degraded), why the decoder");
+    println!("               closed (stream-stopped), test (hooks test)");
+    println!("  KRC_DETAIL   decoder and source size (stream-started), failures=N");
+    println!("               (stream-degraded, stream-recovered), else empty");
+    println!();
+    println!("Actions:");
+    println!("  list  Show the configured hooks (default).");
+    println!(
+        "  test  Fire one hook now and wait for it; EVENT is a KRC_EVENT value or config key."
+    );
+}
+
+fn print_pause_help() {
+    println!("kitsune-rendercore pause | resume");
+    println!("Usage:");
+    println!("  kitsune-rendercore pause");
+    println!("  kitsune-rendercore resume");
+    println!("  kitsune-rendercore pause --monitor <MONITOR>");
+    println!("  kitsune-rendercore resume --monitor <MONITOR>");
+    println!("  kitsune-rendercore pause --all [--except <LIST> | --only <LIST>]");
+    println!("  kitsune-rendercore resume --all [--except <LIST> | --only <LIST>]");
+    println!();
+    println!("Description:");
+    println!("  'pause' creates the pause file and 'resume' removes it. While the file");
+    println!("  exists the renderer stays paused with reason=manual-file; it is checked");
+    println!("  once per second, so scripts can also touch/rm it directly.");
+    println!();
+    println!("  With --monitor only that output freezes on its last frame (e.g. the one being");
+    println!("  screen-shared) while the others keep playing. This goes to the running");
+    println!("  renderer over the control socket, lasts until it exits, and is not undone");
+    println!("  by a plain 'resume'.");
+    println!();
+    println!("  With --all every live output is paused that way, one by one; --except skips");
+    println!("  the outputs its patterns match and --only keeps just those. Patterns are");
+    println!("  comma-separated, match a connector, an alias or desc:<text>, and may use");
+    println!("  '*' and '?' (e.g. --only 'DP-*').");
+    println!();
+    println!("Pause file:");
+    println!("  $XDG_RUNTIME_DIR/kitsune-rendercore/paused (override with KRC_PAUSE_FILE)");
 }
 
 fn print_service_help() {
@@ -635,7 +2854,107 @@ fn print_service_help() {
     println!("  disable  Disable and stop service now.");
     println!("  start    Start service.");
     println!("  stop     Stop service.");
-    println!("  restart  Restart service.");
+    println!("  restart  Restart service; --preserve-state keeps transient overrides,");
+    println!("           frozen outputs and playback positions (see 'restart --help').");
     println!("  status   Show service status.");
-    println!("  logs     Follow service logs (journalctl -f).");
+    println!("  logs     Follow service logs (journalctl -f); see 'service logs --help' for");
+    println!(
+        "           --since, --lines, --priority, --boot, --grep, --no-follow, --render-only."
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status_schema::schema_errors;
+    use crate::video_map::parse_entry_value;
+    use std::path::Path;
+
+    const SUPERVISOR: &str = r#"{"pid":4100,"state":"running","child_pid":4101,"started_at_ms":1760000000000,"restarts":1,"recent_restarts":1,"max_restarts":5,"window_sec":600,"last_exit":"killed by SIGSEGV, core dumped","last_exit_at_ms":1760000100000}"#;
+
+    fn status(pretty: bool) -> String {
+        let entry = |raw: &str| parse_entry_value(raw, Path::new("/")).unwrap();
+        let mapped = [
+            (
+                "DP-1".to_string(),
+                entry("/srv/loops/rain.mp4 rotate=90 flip=h quality=high"),
+                false,
+            ),
+            (
+                "HDMI-A-1".to_string(),
+                entry("/srv/loops/\"quoted\".mp4"),
+                true,
+            ),
+        ];
+        build_status_json(
+            "/home/user/.config/kitsune-rendercore/video-map.conf",
+            "<none>",
+            "30",
+            "1.0",
+            "auto",
+            "vaapi",
+            true,
+            false,
+            "manual-file",
+            "/run/user/1000/kitsune-rendercore/paused",
+            false,
+            "active",
+            &mapped,
+            Some(SUPERVISOR),
+            None,
+            pretty,
+        )
+    }
+
+    /// The golden file with this build's `runtime.video`.
+    fn golden() -> String {
+        include_str!("../tests/fixtures/status.golden.json").replace(
+            "\"video\": \"ffmpeg\"",
+            &format!("\"video\": \"{}\"", version::video_support()),
+        )
+    }
+
+    #[test]
+    fn status_json_matches_the_golden_file() {
+        assert_eq!(status(true), golden().trim_end());
+    }
+
+    #[test]
+    fn compact_and_pretty_status_json_agree() {
+        assert_eq!(parse_json(&status(false)), parse_json(&status(true)));
+    }
+
+    #[test]
+    fn status_json_follows_its_schema() {
+        let schema = parse_json(STATUS_JSON_SCHEMA).unwrap();
+        let value = parse_json(&status(false)).unwrap();
+        assert_eq!(
+            schema_errors(&schema, &value, "status"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            value.get("schema_version").and_then(JsonValue::as_u64),
+            Some(u64::from(STATUS_SCHEMA_VERSION))
+        );
+    }
+
+    #[test]
+    fn unset_all_names_what_it_left_in_place() {
+        let outcome = |default_kept| UnsetAllOutcome {
+            removed: 2,
+            kept: 0,
+            default_removed: false,
+            default_kept,
+        };
+        let live = parse_json(r#"{"transient_overrides":3}"#).unwrap();
+        let left = unset_leftovers(&outcome(true), Some(&live), Some(" /v/env.mp4 "));
+        assert_eq!(left.len(), 3, "{left:?}");
+        assert!(left[0].contains("--include-default"));
+        assert!(left[1].contains("KRC_VIDEO_DEFAULT=/v/env.mp4"));
+        assert!(left[2].starts_with("3 transient") && left[2].contains("--include-transient"));
+
+        let idle = parse_json(r#"{"transient_overrides":0}"#).unwrap();
+        assert!(unset_leftovers(&outcome(false), Some(&idle), Some("  ")).is_empty());
+        assert!(unset_leftovers(&outcome(false), None, None).is_empty());
+    }
 }
@@ -0,0 +1,496 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use wayland_client::protocol::wl_registry;
+use wayland_client::{Connection, Dispatch, QueueHandle};
+use wayland_protocols::wp::linux_dmabuf::zv1::client::{
+    zwp_linux_dmabuf_feedback_v1, zwp_linux_dmabuf_v1,
+};
+
+/// A DRM render node (`/dev/dri/renderD128`) and the PCI device behind it,
+/// read from sysfs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderNode {
+    pub path: PathBuf,
+    /// Canonical sysfs directory of the device; two nodes of one GPU share it.
+    pci: PathBuf,
+    pub vendor: u32,
+    pub device: u32,
+    boot_vga: bool,
+}
+
+impl RenderNode {
+    pub fn label(&self) -> String {
+        format!(
+            "{} ({:04x}:{:04x})",
+            self.path.display(),
+            self.vendor,
+            self.device
+        )
+    }
+}
+
+/// Every render node on this machine, in node order.
+pub fn render_nodes() -> Vec<RenderNode> {
+    let Ok(entries) = fs::read_dir("/sys/class/drm") else {
+        return Vec::new();
+    };
+    let mut nodes = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with("renderD") {
+                return None;
+            }
+            let pci = fs::canonicalize(entry.path().join("device")).ok()?;
+            Some(RenderNode {
+                path: Path::new("/dev/dri").join(&name),
+                vendor: read_hex(&pci.join("vendor"))?,
+                device: read_hex(&pci.join("device"))?,
+                boot_vga: read_hex(&pci.join("boot_vga")) == Some(1),
+                pci,
+            })
+        })
+        .collect::<Vec<_>>();
+    nodes.sort_by(|a, b| a.path.cmp(&b.path));
+    nodes
+}
+
+fn read_hex(path: &Path) -> Option<u32> {
+    let raw = fs::read_to_string(path).ok()?;
+    u32::from_str_radix(raw.trim().trim_start_matches("0x"), 16).ok()
+}
+
+/// The GPU the compositor composites and scans out on, and how it was found.
+#[derive(Debug, Clone)]
+pub struct CompositorDevice {
+    pub node: RenderNode,
+    pub source: &'static str,
+}
+
+/// Asks the compositor for its main device through linux-dmabuf default
+/// feedback (v4). Without it, the GPU firmware set up for display
+/// (`boot_vga`) is assumed, which is what compositors pick unless told
+/// otherwise. `None` when neither says anything.
+pub fn compositor_device(
+    connection: &Connection,
+    nodes: &[RenderNode],
+) -> Option<CompositorDevice> {
+    if let Some(node) = dmabuf_main_device(connection).and_then(|dev| node_for_dev(dev, nodes)) {
+        return Some(CompositorDevice {
+            node,
+            source: "linux-dmabuf feedback",
+        });
+    }
+    nodes
+        .iter()
+        .find(|node| node.boot_vga)
+        .map(|node| CompositorDevice {
+            node: node.clone(),
+            source: "boot_vga guess; compositor sent no dmabuf feedback",
+        })
+}
+
+/// The render node of the GPU that owns device number `dev`, which may be its
+/// primary node (`card0`) as well as its render node.
+fn node_for_dev(dev: u64, nodes: &[RenderNode]) -> Option<RenderNode> {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let pci = fs::canonicalize(format!("/sys/dev/char/{major}:{minor}/device")).ok()?;
+    nodes.iter().find(|node| node.pci == pci).cloned()
+}
+
+fn dmabuf_main_device(connection: &Connection) -> Option<u64> {
+    let mut event_queue = connection.new_event_queue();
+    let qh = event_queue.handle();
+    connection.display().get_registry(&qh, ());
+    let mut probe = DmabufProbe::default();
+    // First roundtrip binds linux-dmabuf and asks for feedback, the second
+    // delivers it.
+    for _ in 0..2 {
+        event_queue.roundtrip(&mut probe).ok()?;
+    }
+    if let Some(feedback) = probe.feedback.take() {
+        feedback.destroy();
+    }
+    if let Some(dmabuf) = probe.dmabuf.take() {
+        dmabuf.destroy();
+    }
+    let _ = connection.flush();
+    probe.main_device
+}
+
+#[derive(Default)]
+struct DmabufProbe {
+    dmabuf: Option<zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1>,
+    feedback: Option<zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1>,
+    main_device: Option<u64>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for DmabufProbe {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+            && interface == "zwp_linux_dmabuf_v1"
+            && version >= 4
+            && state.dmabuf.is_none()
+        {
+            let dmabuf: zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1 = registry.bind(name, 4, qh, ());
+            state.feedback = Some(dmabuf.get_default_feedback(qh, ()));
+            state.dmabuf = Some(dmabuf);
+        }
+    }
+}
+
+impl Dispatch<zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1, ()> for DmabufProbe {
+    fn event(
+        _: &mut Self,
+        _: &zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+        _: zwp_linux_dmabuf_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1, ()> for DmabufProbe {
+    fn event(
+        state: &mut Self,
+        _: &zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1,
+        event: zwp_linux_dmabuf_feedback_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // dev_t in host byte order; the format table fd is dropped unread.
+        if let zwp_linux_dmabuf_feedback_v1::Event::MainDevice { device } = event
+            && let Ok(bytes) = <[u8; 8]>::try_from(device.as_slice())
+        {
+            state.main_device = Some(u64::from_ne_bytes(bytes));
+        }
+    }
+}
+
+/// The render node behind a wgpu adapter, matched by PCI ids. `None` for
+/// software adapters, backends that report no ids, and two identical cards.
+pub fn adapter_node<'a>(
+    info: &wgpu::AdapterInfo,
+    nodes: &'a [RenderNode],
+) -> Option<&'a RenderNode> {
+    let mut matching = nodes
+        .iter()
+        .filter(|node| node.vendor == info.vendor && node.device == info.device);
+    let first = matching.next()?;
+    matching.next().is_none().then_some(first)
+}
+
+/// The adapter the renderer draws with, and why.
+pub struct GpuSelection {
+    pub adapter: wgpu::Adapter,
+    pub node: Option<RenderNode>,
+    pub reason: String,
+    /// Set when the adapter is not the compositor's GPU: every frame then
+    /// crosses the bus to be composited.
+    pub mismatch: Option<String>,
+    /// A CPU adapter (llvmpipe, lavapipe): it works, but every pixel costs CPU.
+    pub software: bool,
+}
+
+impl GpuSelection {
+    pub fn summary(&self) -> String {
+        let info = self.adapter.get_info();
+        format!(
+            "{} ({:?}, {:?}, {}) chosen by {}",
+            info.name,
+            info.backend,
+            info.device_type,
+            self.node
+                .as_ref()
+                .map_or("render node unknown".to_string(), RenderNode::label),
+            self.reason
+        )
+    }
+}
+
+/// `KRC_SOFTWARE_MAX_FPS` default.
+const SOFTWARE_MAX_FPS: u32 = 30;
+
+/// `KRC_FORCE_SOFTWARE`: render on a software adapter even when a GPU works,
+/// to tell driver bugs from renderer bugs.
+fn force_software() -> bool {
+    std::env::var("KRC_FORCE_SOFTWARE")
+        .ok()
+        .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+/// Frame rate outputs and decoders are held to on a software adapter
+/// (`KRC_SOFTWARE_MAX_FPS`, default 30); `None` when set to 0.
+pub fn software_fps_cap() -> Option<u32> {
+    match std::env::var("KRC_SOFTWARE_MAX_FPS") {
+        Ok(raw) => match raw.trim().parse::<u32>() {
+            Ok(0) => None,
+            Ok(cap) => Some(cap),
+            Err(_) => {
+                eprintln!(
+                    "[rendercore] ignoring KRC_SOFTWARE_MAX_FPS={raw}: expected whole fps, 0 for no cap"
+                );
+                Some(SOFTWARE_MAX_FPS)
+            }
+        },
+        Err(_) => Some(SOFTWARE_MAX_FPS),
+    }
+}
+
+/// Picks the adapter: a software one with `KRC_FORCE_SOFTWARE`, else
+/// `KRC_GPU` when set and it matches, else the adapter on the compositor's
+/// GPU, else wgpu's high-performance default, falling back per backend and
+/// finally to software when that finds nothing.
+pub fn select_adapter(
+    instance: &wgpu::Instance,
+    nodes: &[RenderNode],
+    compositor: Option<&CompositorDevice>,
+) -> Result<GpuSelection, String> {
+    let mut adapters = instance.enumerate_adapters(wgpu::Backends::all());
+    let infos = adapters.iter().map(|a| a.get_info()).collect::<Vec<_>>();
+    let explicit = std::env::var("KRC_GPU")
+        .ok()
+        .filter(|v| !v.trim().is_empty());
+
+    let mut pick = None::<(usize, String)>;
+    let mut note = String::new();
+    if force_software() {
+        let (adapter, how) = software_adapter(instance, &mut adapters, &infos).ok_or_else(|| {
+            "KRC_FORCE_SOFTWARE is set but no software adapter exists (install mesa's llvmpipe or lavapipe)".to_string()
+        })?;
+        return Ok(selection(
+            adapter,
+            nodes,
+            compositor,
+            format!("KRC_FORCE_SOFTWARE ({how})"),
+        ));
+    }
+    if let Some(spec) = &explicit {
+        match explicit_adapter(spec, &infos, nodes) {
+            Some(idx) => pick = Some((idx, format!("KRC_GPU={spec}"))),
+            None => note = format!(" (KRC_GPU={spec} matches no adapter)"),
+        }
+    }
+    if pick.is_none()
+        && let Some(compositor) = compositor
+    {
+        let candidates = (0..infos.len()).filter(|idx| {
+            adapter_node(&infos[*idx], nodes).is_some_and(|node| node.pci == compositor.node.pci)
+        });
+        pick = preferred(candidates, &infos).map(|idx| {
+            (
+                idx,
+                format!("matching the compositor ({}){note}", compositor.source),
+            )
+        });
+    }
+
+    let (adapter, reason) = match pick {
+        Some((idx, reason)) => (adapters.swap_remove(idx), reason),
+        None => {
+            let (adapter, reason) = default_adapter(instance, &mut adapters, &infos)?;
+            (adapter, format!("{reason}{note}"))
+        }
+    };
+    Ok(selection(adapter, nodes, compositor, reason))
+}
+
+fn selection(
+    adapter: wgpu::Adapter,
+    nodes: &[RenderNode],
+    compositor: Option<&CompositorDevice>,
+    reason: String,
+) -> GpuSelection {
+    let info = adapter.get_info();
+    let node = adapter_node(&info, nodes).cloned();
+    let mismatch = match (compositor, &node) {
+        (Some(compositor), Some(node)) if node.pci != compositor.node.pci => Some(format!(
+            "rendering on {} but the compositor uses {} ({}): every frame is copied between GPUs (PRIME), expect extra GPU load and possible flicker; set KRC_GPU={} to render on the compositor's GPU",
+            node.label(),
+            compositor.node.label(),
+            compositor.source,
+            compositor
+                .node
+                .path
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+        )),
+        _ => None,
+    };
+    GpuSelection {
+        adapter,
+        node,
+        reason,
+        mismatch,
+        software: info.device_type == wgpu::DeviceType::Cpu,
+    }
+}
+
+fn request_adapter(instance: &wgpu::Instance, software: bool) -> Option<wgpu::Adapter> {
+    pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        compatible_surface: None,
+        force_fallback_adapter: software,
+    }))
+}
+
+/// wgpu's high-performance default. With a broken driver that request can
+/// come back empty while adapters still enumerate, so the first hardware
+/// adapter is tried next (Vulkan, then GL), then a software one.
+fn default_adapter(
+    instance: &wgpu::Instance,
+    adapters: &mut Vec<wgpu::Adapter>,
+    infos: &[wgpu::AdapterInfo],
+) -> Result<(wgpu::Adapter, String), String> {
+    if let Some(adapter) = request_adapter(instance, false) {
+        return Ok((adapter, "wgpu default (high performance)".to_string()));
+    }
+    let hardware = (0..infos.len()).filter(|idx| infos[*idx].device_type != wgpu::DeviceType::Cpu);
+    if let Some(idx) = preferred(hardware, infos) {
+        return Ok((
+            adapters.swap_remove(idx),
+            format!(
+                "first {:?} adapter (wgpu found no default adapter)",
+                infos[idx].backend
+            ),
+        ));
+    }
+    let (adapter, how) = software_adapter(instance, adapters, infos).ok_or_else(|| {
+        "wgpu found no adapter on any backend, not even a software one (install mesa's llvmpipe or lavapipe)".to_string()
+    })?;
+    Ok((
+        adapter,
+        format!("software fallback ({how}; wgpu found no hardware adapter)"),
+    ))
+}
+
+/// wgpu's fallback adapter, else any enumerated CPU adapter.
+fn software_adapter(
+    instance: &wgpu::Instance,
+    adapters: &mut Vec<wgpu::Adapter>,
+    infos: &[wgpu::AdapterInfo],
+) -> Option<(wgpu::Adapter, &'static str)> {
+    if let Some(adapter) = request_adapter(instance, true) {
+        return Some((adapter, "wgpu fallback adapter"));
+    }
+    let cpu = (0..infos.len()).filter(|idx| infos[*idx].device_type == wgpu::DeviceType::Cpu);
+    let idx = preferred(cpu, infos)?;
+    Some((adapters.swap_remove(idx), "first CPU adapter"))
+}
+
+/// `KRC_GPU`: an adapter index as `doctor` lists it, a render node
+/// (`renderD129` or `/dev/dri/renderD129`), or part of the adapter name.
+fn explicit_adapter(
+    spec: &str,
+    infos: &[wgpu::AdapterInfo],
+    nodes: &[RenderNode],
+) -> Option<usize> {
+    let spec = spec.trim();
+    if let Ok(idx) = spec.parse::<usize>() {
+        return (idx < infos.len()).then_some(idx);
+    }
+    let node_name = spec.rsplit('/').next().unwrap_or(spec);
+    if node_name.starts_with("renderD") {
+        let candidates = (0..infos.len()).filter(|idx| {
+            adapter_node(&infos[*idx], nodes)
+                .is_some_and(|node| node.path.file_name().is_some_and(|n| n == node_name))
+        });
+        return preferred(candidates, infos);
+    }
+    let needle = spec.to_lowercase();
+    let candidates =
+        (0..infos.len()).filter(|idx| infos[*idx].name.to_lowercase().contains(&needle));
+    preferred(candidates, infos)
+}
+
+/// Among adapters for the same GPU, Vulkan first, then GL, then the rest.
+fn preferred(
+    candidates: impl Iterator<Item = usize>,
+    infos: &[wgpu::AdapterInfo],
+) -> Option<usize> {
+    candidates.min_by_key(|idx| match infos[*idx].backend {
+        wgpu::Backend::Vulkan => 0,
+        wgpu::Backend::Gl => 1,
+        _ => 2,
+    })
+}
+
+/// `doctor` lines: each adapter with its render node, the compositor's GPU,
+/// and the adapter the renderer would pick.
+pub fn gpu_report() -> Result<Vec<String>, String> {
+    let nodes = render_nodes();
+    let mut lines = Vec::new();
+    let compositor = match Connection::connect_to_env() {
+        Ok(connection) => compositor_device(&connection, &nodes),
+        Err(err) => {
+            lines.push(format!(
+                "[warn] gpu: cannot ask the compositor for its device: {err}"
+            ));
+            None
+        }
+    };
+    match &compositor {
+        Some(compositor) => lines.push(format!(
+            "[info] gpu: compositor uses {} ({})",
+            compositor.node.label(),
+            compositor.source
+        )),
+        None => lines.push("[info] gpu: compositor device unknown".to_string()),
+    }
+    let instance = wgpu::Instance::default();
+    for (idx, adapter) in instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .iter()
+        .enumerate()
+    {
+        let info = adapter.get_info();
+        let node = adapter_node(&info, &nodes);
+        let is_compositor = node
+            .zip(compositor.as_ref())
+            .is_some_and(|(node, compositor)| node.pci == compositor.node.pci);
+        lines.push(format!(
+            "[info] gpu adapter {idx}: {} ({:?}, {:?}) -> {}{}",
+            info.name,
+            info.backend,
+            info.device_type,
+            node.map_or("render node unknown".to_string(), RenderNode::label),
+            if is_compositor { " [compositor]" } else { "" }
+        ));
+    }
+    let selection = select_adapter(&instance, &nodes, compositor.as_ref())?;
+    lines.push(format!(
+        "[info] gpu: renderer would use {}",
+        selection.summary()
+    ));
+    if let Some(mismatch) = &selection.mismatch {
+        lines.push(format!("[warn] gpu: {mismatch}"));
+    }
+    if selection.software {
+        lines.push(format!(
+            "[warn] gpu: software rendering; the renderer caps every output at {} (KRC_SOFTWARE_MAX_FPS){}",
+            software_fps_cap().map_or_else(|| "no fps".to_string(), |cap| format!("{cap}fps")),
+            if force_software() {
+                ""
+            } else {
+                "; check the Vulkan/GL drivers"
+            }
+        ));
+    }
+    Ok(lines)
+}
@@ -1,9 +1,30 @@
 #[cfg(feature = "wayland-layer")]
+mod gpu_select;
+#[cfg(feature = "wayland-layer")]
+mod particles;
+#[cfg(feature = "wayland-layer")]
+mod shell_policy;
+#[cfg(not(feature = "wayland-layer"))]
+mod stub_scenario;
+#[cfg(feature = "wayland-layer")]
+mod text_layer;
+#[cfg(feature = "wayland-layer")]
 mod wayland_layer;
+#[cfg(feature = "wayland-layer")]
+mod wayland_outputs;
 #[cfg(not(feature = "wayland-layer"))]
 mod wayland_stub;
 
-use crate::monitor::{MonitorInfo, MonitorSurfaceSpec};
+use std::time::Duration;
+
+use crate::aliases::Aliases;
+use crate::bench::RenderBench;
+use crate::headroom::Estimate;
+use crate::monitor::{
+    GpuAdapterStatus, MonitorInfo, MonitorSurfaceSpec, OutputRuntimeStatus, ShellStatus,
+};
+use crate::snapshot::{PlaybackPosition, Restored};
+use crate::video_map::{VideoMap, VideoMapEntry};
 
 pub trait LayerBackend {
     fn name(&self) -> &'static str;
@@ -14,12 +35,103 @@ pub trait LayerBackend {
         monitors: &[MonitorInfo],
     ) -> Result<Vec<MonitorSurfaceSpec>, String>;
     fn render_frame(&mut self, surfaces: &[MonitorSurfaceSpec]) -> Result<(), String>;
+    fn output_status(&self) -> Vec<OutputRuntimeStatus>;
+    /// `reload` control command: re-resolve the map and env file now (`hard`
+    /// also restarts unchanged decoders). Returns the JSON reply, or `None` when
+    /// the backend has nothing to reload.
+    fn reload(&mut self, _hard: bool) -> Option<String> {
+        None
+    }
+    /// `set-video transient`: maps `monitor` to `entry` on top of the map file
+    /// until restart (`None` drops the override) and re-resolves outputs now.
+    /// Returns the JSON reply, or `None` when the backend has no video map.
+    fn set_transient_video(
+        &mut self,
+        _monitor: &str,
+        _entry: Option<VideoMapEntry>,
+    ) -> Option<String> {
+        None
+    }
+    /// `clear-transient`: drops every `set-video transient` override and
+    /// re-resolves outputs. `None` when the backend has no video map.
+    fn clear_transient_videos(&mut self) -> Option<String> {
+        None
+    }
+    /// Outputs frozen by name (`pause --monitor`): they stop decoding and keep
+    /// their last frame, redrawn only when the compositor needs a new buffer.
+    fn set_paused_outputs(&mut self, _monitors: &[String]) {}
+    /// A global pause with [`PauseBehavior::Freeze`](crate::pause::PauseBehavior):
+    /// every output is frozen as if paused by name, until called with `false`.
+    fn set_frozen(&mut self, _frozen: bool) {}
+    /// Polls the map file, the env file and the mapped videos for changes,
+    /// applied with the next frame. Returns when to poll again (sooner while
+    /// a change settles), or `None` when the backend has no video map.
+    fn check_video_map(&mut self) -> Option<Duration> {
+        None
+    }
+    /// Monitor aliases from the config file. Called before `bootstrap` and
+    /// again whenever they change; outputs whose aliases changed are
+    /// re-resolved against the map.
+    fn set_aliases(&mut self, _aliases: &Aliases) {}
+    /// `reduce-motion`: every resolved entry shows a still of its first frame
+    /// instead of playing. Called before `bootstrap` with the saved flag and
+    /// again on every change, which re-resolves outputs now. Returns the JSON
+    /// reply, or `None` when the backend has no video map.
+    fn set_reduce_motion(&mut self, _on: bool) -> Option<String> {
+        None
+    }
+    /// Number of `set-video transient` overrides in effect.
+    fn transient_overrides(&self) -> usize {
+        0
+    }
+    /// The `set-video transient` overrides in effect, for a runtime snapshot.
+    fn transient_videos(&self) -> VideoMap {
+        VideoMap::new()
+    }
+    /// Where each output's decoder is in its video, for a runtime snapshot.
+    fn playback_positions(&self) -> Vec<PlaybackPosition> {
+        Vec::new()
+    }
+    /// What a restart preserved: the overrides to start with, and where to
+    /// resume each output whose video is unchanged. Called before `bootstrap`.
+    fn restore_state(&mut self, _restored: Restored) {}
+    /// The render loop's rate. No output is drawn faster than this, however
+    /// fast its panel refreshes.
+    fn set_fps_ceiling(&mut self, _fps: u32) {}
+    /// Adapter the frames are drawn with; `None` for backends without a GPU.
+    fn gpu_status(&self) -> Option<GpuAdapterStatus> {
+        None
+    }
+    /// Which shell the wallpaper surfaces use and why; `None` for backends
+    /// without a compositor.
+    fn shell_status(&self) -> Option<ShellStatus> {
+        None
+    }
+    /// Backend half of the periodic report line (`key=value` pairs); `None`
+    /// when there is nothing to add.
+    fn report_summary(&self) -> Option<String> {
+        None
+    }
+    /// Current `KRC_PROFILE` window as JSON; `None` when the backend has no profiler.
+    fn profile_report(&self) -> Option<String> {
+        None
+    }
+    /// Whether the configured video decoders fit this machine; `None` until
+    /// estimated, and for backends that do not decode.
+    fn headroom(&self) -> Option<Estimate> {
+        None
+    }
+    /// Releases the surfaces, GPU and compositor objects in an order that
+    /// does not depend on struct field drop order. Called when the render loop
+    /// ends; backends also call it from `Drop`, so it must tolerate a second
+    /// call.
+    fn shutdown(&mut self) {}
 }
 
 pub fn create_default_backend() -> Box<dyn LayerBackend> {
     #[cfg(feature = "wayland-layer")]
     {
-        return Box::new(wayland_layer::WaylandLayerBackend::default());
+        Box::new(wayland_layer::WaylandLayerBackend::default())
     }
 
     #[cfg(not(feature = "wayland-layer"))]
@@ -27,3 +139,49 @@ pub fn create_default_backend() -> Box<dyn LayerBackend> {
         Box::new(wayland_stub::WaylandLayerStubBackend::default())
     }
 }
+
+/// Connected output names from the Wayland registry alone, without any
+/// compositor-specific IPC.
+pub fn wayland_output_names() -> Result<Vec<String>, String> {
+    #[cfg(feature = "wayland-layer")]
+    {
+        wayland_outputs::wayland_output_names()
+    }
+
+    #[cfg(not(feature = "wayland-layer"))]
+    {
+        Err("built without the wayland-layer feature".to_string())
+    }
+}
+
+/// `bench` offscreen upload and render rate with the real frame pipeline.
+pub fn bench_render(
+    size: (u32, u32),
+    streams: u32,
+    duration: std::time::Duration,
+) -> Result<RenderBench, String> {
+    #[cfg(feature = "wayland-layer")]
+    {
+        wayland_layer::bench_render(size, streams, duration)
+    }
+
+    #[cfg(not(feature = "wayland-layer"))]
+    {
+        let _ = (size, streams, duration);
+        Err("built without the wayland-layer feature".to_string())
+    }
+}
+
+/// `doctor` report of the GPUs: each adapter's render node, the one the
+/// compositor uses, and the adapter the renderer would pick.
+pub fn gpu_report() -> Result<Vec<String>, String> {
+    #[cfg(feature = "wayland-layer")]
+    {
+        gpu_select::gpu_report()
+    }
+
+    #[cfg(not(feature = "wayland-layer"))]
+    {
+        Err("built without the wayland-layer feature".to_string())
+    }
+}
@@ -0,0 +1,345 @@
+//! `overlay=snow|rain|fireflies`: particles drawn over an output's picture in
+//! the same render pass, as instanced quads. The vertex shader places each
+//! particle from a hash of its index and `time_sec`, so there is no particle
+//! state to keep, upload or reset on a reload; the entry's `density`, `speed`
+//! and `wind` arrive in the frame uniform like every other render-side option.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+use crate::profile;
+use crate::video_map::{EntryOptions, Overlay};
+
+/// Appended to the frame shader's common part, whose uniform and
+/// `display_uv` / `output_gamut` it uses. Sizes are in output heights, with
+/// x running over `0..aspect`, so particles stay round on any output and a
+/// logical pixel (`overlay_params.w`) keeps them the same size at any scale.
+pub const OVERLAY_SHADER_WGSL: &str = r#"
+struct OverlayOut {
+    @builtin(position) pos: vec4<f32>,
+    // Across the particle's quad, -1..1 on both axes; x runs along a rain
+    // streak, tail to head.
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+const TAU: f32 = 6.2831853;
+
+fn hash(n: u32) -> f32 {
+    var x = n * 747796405u + 2891336453u;
+    x = ((x >> ((x >> 28u) + 4u)) ^ x) * 277803737u;
+    x = (x >> 22u) ^ x;
+    return f32(x) * (1.0 / 4294967296.0);
+}
+
+// The particle's k-th random number, fixed for its lifetime.
+fn rand(particle: u32, k: u32) -> f32 {
+    return hash(particle * 8u + k);
+}
+
+// Phase (0..1) of something repeating `rate` times a second, rounded to whole
+// cycles per time_sec wrap (3600 s) so nothing jumps when it wraps. Whole
+// seconds go through integers, so the phase is as exact late in the hour as
+// at its start.
+fn cycle(rate: f32) -> f32 {
+    let k = i32(round(rate * 3600.0));
+    let whole = floor(uniforms.time_sec);
+    let turns = (i32(whole) * k) % 3600;
+    return fract(f32(turns) / 3600.0 + (uniforms.time_sec - whole) * f32(k) / 3600.0);
+}
+
+// Position along an axis `span` long that the particle crosses at `speed`
+// heights a second, starting from `start` (0..1) of the way, wrapping around.
+fn travel(start: f32, speed: f32, span: f32) -> f32 {
+    return fract(start + cycle(speed / span)) * span;
+}
+
+// Display UV to buffer UV, the inverse of display_uv: transforms 1 and 3
+// undo each other, the others undo themselves.
+fn buffer_uv(d: vec2<f32>) -> vec2<f32> {
+    switch uniforms.buffer_transform {
+        case 1u: { return vec2<f32>(d.y, 1.0 - d.x); }
+        case 3u: { return vec2<f32>(1.0 - d.y, d.x); }
+        default: { return display_uv(d); }
+    }
+}
+
+@vertex
+fn vs_overlay(
+    @builtin(vertex_index) vid: u32,
+    @builtin(instance_index) i: u32,
+) -> OverlayOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>( 1.0,  1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 1.0,  1.0),
+        vec2<f32>(-1.0,  1.0)
+    );
+    let corner = corners[vid];
+    let aspect = uniforms.aspect;
+    let speed = uniforms.overlay_params.y;
+    let wind = uniforms.overlay_params.z;
+    let px = uniforms.overlay_params.w;
+    // 0 far away (small, slow, faint) to 1 close by.
+    let depth = rand(i, 0u);
+    var center: vec2<f32>;
+    var along: vec2<f32>;
+    var across: vec2<f32>;
+    var color: vec4<f32>;
+    switch uniforms.overlay {
+        case 2u: {
+            let fall = mix(0.9, 1.6, depth) * speed;
+            let drift = wind * 0.6 * fall;
+            let half_len = mix(0.012, 0.035, depth);
+            let margin = 0.08;
+            center = vec2<f32>(
+                travel(rand(i, 1u), drift, aspect + 2.0 * margin),
+                travel(rand(i, 2u), fall, 1.0 + 2.0 * margin)
+            ) - vec2<f32>(margin);
+            let dir = normalize(vec2<f32>(drift, fall));
+            along = dir * half_len;
+            across = vec2<f32>(-dir.y, dir.x) * mix(0.5, 0.9, depth) * px;
+            color = vec4<f32>(0.75, 0.8, 0.9, mix(0.12, 0.35, depth));
+        }
+        case 3u: {
+            let radius = mix(6.0, 14.0, depth) * px;
+            let margin = 0.06;
+            let wander = vec2<f32>(
+                0.04 * sin(TAU * cycle(mix(0.03, 0.08, rand(i, 3u)) * speed) + TAU * rand(i, 4u)),
+                0.03 * sin(TAU * cycle(mix(0.04, 0.10, rand(i, 5u)) * speed) + TAU * rand(i, 6u))
+            );
+            center = vec2<f32>(
+                travel(rand(i, 1u), wind * 0.02 * speed, aspect + 2.0 * margin) - margin,
+                0.15 + 0.8 * rand(i, 2u)
+            ) + wander;
+            along = vec2<f32>(radius, 0.0);
+            across = vec2<f32>(0.0, radius);
+            let glow = 0.5 + 0.5 * sin(TAU * cycle(mix(0.15, 0.4, rand(i, 7u)) * speed) + TAU * rand(i, 3u));
+            color = vec4<f32>(1.0, 0.88, 0.4, glow * glow * glow * mix(0.5, 1.0, depth));
+        }
+        default: {
+            let fall = mix(0.035, 0.11, depth) * speed;
+            let drift = wind * 0.12 * speed * mix(0.6, 1.0, depth);
+            let radius = mix(1.2, 3.5, depth) * px;
+            let margin = 0.03;
+            let sway = 0.012 * mix(0.5, 1.0, depth)
+                * sin(TAU * cycle(mix(0.1, 0.25, rand(i, 3u)) * speed) + TAU * rand(i, 4u));
+            center = vec2<f32>(
+                travel(rand(i, 1u), drift, aspect + 2.0 * margin) + sway,
+                travel(rand(i, 2u), fall, 1.0 + 2.0 * margin)
+            ) - vec2<f32>(margin);
+            along = vec2<f32>(radius, 0.0);
+            across = vec2<f32>(0.0, radius);
+            color = vec4<f32>(1.0, 1.0, 1.0, mix(0.35, 0.85, depth));
+        }
+    }
+    let p = center + corner.x * along + corner.y * across;
+    let b = buffer_uv(vec2<f32>(p.x / aspect, p.y));
+    var out: OverlayOut;
+    out.pos = vec4<f32>(2.0 * b.x - 1.0, 1.0 - 2.0 * b.y, 0.0, 1.0);
+    out.local = corner;
+    out.color = color;
+    return out;
+}
+
+// Premultiplied, and faded with the picture underneath so a fade-in starts
+// from black.
+@fragment
+fn fs_overlay(in: OverlayOut) -> @location(0) vec4<f32> {
+    var shape: f32;
+    switch uniforms.overlay {
+        case 2u: {
+            shape = (1.0 - abs(in.local.y)) * (0.5 + 0.5 * in.local.x);
+        }
+        case 3u: {
+            let r2 = dot(in.local, in.local);
+            shape = min(exp(-6.0 * r2) + 0.6 * exp(-40.0 * r2), 1.0);
+        }
+        default: {
+            shape = 1.0 - smoothstep(0.4, 1.0, length(in.local));
+        }
+    }
+    let alpha = clamp(in.color.a * shape, 0.0, 1.0) * uniforms.brightness;
+    return vec4<f32>(output_gamut(in.color.rgb) * alpha, alpha);
+}
+"#;
+
+/// The uniform's `overlay`: 0 draws nothing, then the shader's cases.
+pub fn shader_kind(overlay: Overlay) -> u32 {
+    match overlay {
+        Overlay::None => 0,
+        Overlay::Snow => 1,
+        Overlay::Rain => 2,
+        Overlay::Fireflies => 3,
+    }
+}
+
+/// Particles at `density=1` over a 16:9 area.
+fn budget(overlay: Overlay) -> f32 {
+    match overlay {
+        Overlay::None => 0.0,
+        Overlay::Snow => 1600.0,
+        Overlay::Rain => 2400.0,
+        Overlay::Fireflies => 120.0,
+    }
+}
+
+/// Instances to draw over a `size` area; 0 (no draw at all) without an
+/// overlay. The count follows the area's aspect, so a wide output or a tall
+/// layout cell is as dense as a 16:9 one.
+pub fn instance_count(options: EntryOptions, size: (u32, u32)) -> u32 {
+    let aspect = size.0 as f32 / size.1.max(1) as f32;
+    let share = (aspect / (16.0 / 9.0)).clamp(0.25, 4.0);
+    let count = budget(options.overlay) * options.overlay_params.density() * share;
+    count.round() as u32
+}
+
+/// Most overlay passes timed in one frame; outputs past it go untimed.
+const TIMER_SLOTS: u64 = 8;
+
+/// Readback states, set by the map callback.
+const READBACK_WAITING: u8 = 0;
+const READBACK_READY: u8 = 1;
+const READBACK_FAILED: u8 = 2;
+
+/// `KRC_PROFILE` GPU time of the overlay draws. A timed overlay gets a
+/// render pass of its own with timestamps at both ends, so the time includes
+/// the pass switch and is an upper bound of what the shared pass costs. The
+/// timestamps are read back without blocking: frames drawn while a readback
+/// is in flight go untimed.
+pub struct OverlayTimer {
+    queries: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period_ns: f32,
+    /// The outputs timed this frame (or being read back), in query order.
+    timed: Vec<String>,
+    readback_state: Option<Arc<AtomicU8>>,
+}
+
+impl OverlayTimer {
+    /// With `KRC_PROFILE=1` on an adapter with timestamp queries.
+    pub fn from_env(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !profile::enabled_from_env() {
+            return None;
+        }
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            println!(
+                "[rendercore] profile: no overlay GPU time (the adapter has no timestamp queries)"
+            );
+            return None;
+        }
+        let size = TIMER_SLOTS * 2 * 8;
+        let buffer = |label, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        Some(Self {
+            queries: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("kitsune-rendercore-overlay-timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: (TIMER_SLOTS * 2) as u32,
+            }),
+            resolve: buffer(
+                "kitsune-rendercore-overlay-timestamps-resolve",
+                wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            ),
+            readback: buffer(
+                "kitsune-rendercore-overlay-timestamps-readback",
+                wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            ),
+            period_ns: queue.get_timestamp_period(),
+            timed: Vec::new(),
+            readback_state: None,
+        })
+    }
+
+    /// The previous frames' times once their readback is back, as `(output,
+    /// GPU time)`. Called before a frame's passes are recorded.
+    pub fn collect(&mut self, device: &wgpu::Device) -> Vec<(String, Duration)> {
+        let Some(state) = &self.readback_state else {
+            // Nothing in flight: a frame that failed before its submit
+            // leaves nothing worth reading.
+            self.timed.clear();
+            return Vec::new();
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let state = state.load(Ordering::Acquire);
+        if state == READBACK_WAITING {
+            return Vec::new();
+        }
+        // A failed map drops the frame's times.
+        let mut times = Vec::new();
+        if state == READBACK_READY {
+            let len = self.timed.len() as u64 * 16;
+            let ticks = self
+                .readback
+                .slice(..len)
+                .get_mapped_range()
+                .chunks_exact(8)
+                .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap_or_default()))
+                .collect::<Vec<_>>();
+            for (output, pair) in self.timed.iter().zip(ticks.chunks_exact(2)) {
+                let ns = pair[1].saturating_sub(pair[0]) as f64 * f64::from(self.period_ns);
+                times.push((output.clone(), Duration::from_nanos(ns as u64)));
+            }
+            self.readback.unmap();
+        }
+        self.readback_state = None;
+        self.timed.clear();
+        times
+    }
+
+    /// Timestamps for `output`'s overlay pass; `None` when it goes untimed
+    /// and the overlay stays in the frame's pass.
+    pub fn pass_writes(&mut self, output: &str) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        if self.readback_state.is_some() || self.timed.len() as u64 >= TIMER_SLOTS {
+            return None;
+        }
+        let index = self.timed.len() as u32 * 2;
+        self.timed.push(output.to_string());
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.queries,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    /// After the frame's passes: copies this frame's timestamps out.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.readback_state.is_some() || self.timed.is_empty() {
+            return;
+        }
+        let queries = self.timed.len() as u32 * 2;
+        encoder.resolve_query_set(&self.queries, 0..queries, &self.resolve, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve, 0, &self.readback, 0, u64::from(queries) * 8);
+    }
+
+    /// After the frame's submit: starts reading the timestamps back.
+    pub fn submitted(&mut self) {
+        if self.readback_state.is_some() || self.timed.is_empty() {
+            return;
+        }
+        let state = Arc::new(AtomicU8::new(READBACK_WAITING));
+        let done = Arc::clone(&state);
+        self.readback
+            .slice(..self.timed.len() as u64 * 16)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let state = if result.is_ok() {
+                    READBACK_READY
+                } else {
+                    READBACK_FAILED
+                };
+                done.store(state, Ordering::Release);
+            });
+        self.readback_state = Some(state);
+    }
+}
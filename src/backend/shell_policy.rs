@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use crate::monitor::ShellStatus;
+
+/// `KRC_LAYER_SHELL_WAIT_SEC` default: how long `wait` keeps polling for a
+/// layer-shell global the compositor registers late.
+const DEFAULT_LAYER_SHELL_WAIT: Duration = Duration::from_secs(60);
+
+/// `KRC_ON_NO_LAYER_SHELL`: what to do when the compositor has no
+/// `zwlr_layer_shell_v1` (GNOME, or one still starting up).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoLayerShell {
+    /// Fail the bootstrap once the usual compositor wait is over.
+    #[default]
+    Error,
+    /// Keep polling the registry up to `KRC_LAYER_SHELL_WAIT_SEC`.
+    Wait,
+    /// Show each output's wallpaper in a fullscreen xdg-shell window.
+    Windowed,
+}
+
+impl NoLayerShell {
+    pub fn from_env() -> Self {
+        let Ok(raw) = std::env::var("KRC_ON_NO_LAYER_SHELL") else {
+            return Self::default();
+        };
+        match raw.trim() {
+            "" | "error" => Self::Error,
+            "wait" => Self::Wait,
+            "windowed" => Self::Windowed,
+            _ => {
+                eprintln!(
+                    "[rendercore] ignoring KRC_ON_NO_LAYER_SHELL={raw}: expected error, wait or windowed"
+                );
+                Self::default()
+            }
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Wait => "wait",
+            Self::Windowed => "windowed",
+        }
+    }
+
+    /// How long to wait for layer-shell, given the compositor wait.
+    pub fn wait_limit(self, compositor_wait: Duration) -> Duration {
+        match self {
+            Self::Wait => compositor_wait.max(layer_shell_wait_from_env()),
+            Self::Error | Self::Windowed => compositor_wait,
+        }
+    }
+
+    /// The decision taken, for the bootstrap log and `status`.
+    pub fn status(self, protocol: &'static str, reason: String) -> ShellStatus {
+        ShellStatus {
+            protocol,
+            on_missing: self.as_str(),
+            reason,
+        }
+    }
+
+    /// Why the bootstrap failed without layer-shell, and what would help.
+    pub fn missing_error(self, waited: Duration) -> String {
+        let desktop = desktop_name();
+        let hint = match self {
+            Self::Error | Self::Wait if desktop.as_deref().is_some_and(is_gnome) => {
+                "GNOME has no layer-shell; set KRC_ON_NO_LAYER_SHELL=windowed to draw each output in a fullscreen window instead"
+            }
+            Self::Error => {
+                "set KRC_ON_NO_LAYER_SHELL=wait if the compositor registers it late, or =windowed to draw each output in a fullscreen window"
+            }
+            Self::Wait => {
+                "raise KRC_LAYER_SHELL_WAIT_SEC if the compositor registers it later still, or set KRC_ON_NO_LAYER_SHELL=windowed"
+            }
+            Self::Windowed => "the compositor has no xdg_wm_base either",
+        };
+        format!(
+            "zwlr_layer_shell_v1 is not available on {} after {}s (KRC_ON_NO_LAYER_SHELL={}): {hint}",
+            desktop.as_deref().unwrap_or("this compositor"),
+            waited.as_secs(),
+            self.as_str()
+        )
+    }
+}
+
+/// `XDG_CURRENT_DESKTOP`, e.g. `GNOME` or `ubuntu:GNOME`; `None` when unset.
+pub fn desktop_name() -> Option<String> {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .ok()
+        .map(|raw| raw.trim().to_string())
+        .filter(|raw| !raw.is_empty())
+}
+
+fn is_gnome(desktop: &str) -> bool {
+    desktop
+        .split(':')
+        .any(|part| part.eq_ignore_ascii_case("gnome"))
+}
+
+fn layer_shell_wait_from_env() -> Duration {
+    let Ok(raw) = std::env::var("KRC_LAYER_SHELL_WAIT_SEC") else {
+        return DEFAULT_LAYER_SHELL_WAIT;
+    };
+    raw.trim()
+        .parse::<u64>()
+        .map(Duration::from_secs)
+        .unwrap_or_else(|_| {
+            eprintln!(
+                "[rendercore] ignoring KRC_LAYER_SHELL_WAIT_SEC={raw}: expected whole seconds"
+            );
+            DEFAULT_LAYER_SHELL_WAIT
+        })
+}
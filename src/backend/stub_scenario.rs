@@ -0,0 +1,535 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config_file::parse_value;
+use crate::monitor::{MonitorInfo, OutputTransform};
+
+/// `KRC_STUB_SCENARIO`: the outputs the stub backend starts with and a
+/// timeline of hotplug and mode changes, so hotplug, scheduling and pause
+/// logic can be exercised without a compositor.
+///
+/// ```toml
+/// [[monitor]]
+/// name = "DP-1"
+/// size = "2560x1440"
+/// refresh = 144
+///
+/// [[event]]
+/// at = 5            # seconds after bootstrap
+/// action = "add"    # add | mode | remove
+/// name = "HDMI-A-1"
+/// size = "1920x1080"
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    pub monitors: Vec<MonitorInfo>,
+    /// In time order; events at the same time keep their file order.
+    pub events: Vec<ScenarioEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScenarioEvent {
+    /// Time since bootstrap.
+    pub at: Duration,
+    pub change: TopologyChange,
+}
+
+#[derive(Debug, Clone)]
+pub enum TopologyChange {
+    Add(MonitorInfo),
+    /// A new mode or transform; keys the event leaves out keep their value.
+    Mode {
+        name: String,
+        size: Option<(u32, u32)>,
+        refresh_hz: Option<u32>,
+        transform: Option<OutputTransform>,
+    },
+    Remove(String),
+}
+
+const TRANSFORMS: [OutputTransform; 8] = [
+    OutputTransform::Normal,
+    OutputTransform::Rotate90,
+    OutputTransform::Rotate180,
+    OutputTransform::Rotate270,
+    OutputTransform::Flipped,
+    OutputTransform::Flipped90,
+    OutputTransform::Flipped180,
+    OutputTransform::Flipped270,
+];
+
+/// One `[[monitor]]` or `[[event]]` table: its header line and `key = value`
+/// pairs with the line each came from.
+struct Table {
+    kind: &'static str,
+    line: usize,
+    values: BTreeMap<String, (usize, String)>,
+}
+
+impl Table {
+    fn take(&mut self, key: &str) -> Option<(usize, String)> {
+        self.values.remove(key)
+    }
+
+    fn require(&mut self, key: &str) -> Result<(usize, String), String> {
+        self.take(key)
+            .ok_or_else(|| format!("line {}: [[{}]] is missing '{key}'", self.line, self.kind))
+    }
+
+    /// Fails on keys no reader took.
+    fn finish(self) -> Result<(), String> {
+        match self.values.into_iter().next() {
+            Some((key, (line, _))) => Err(format!(
+                "line {line}: unknown key '{key}' in [[{}]]",
+                self.kind
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+        Self::parse(&contents).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// Parses a scenario and checks its timeline: every event must apply to
+    /// the outputs present at its time.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut scenario = Self::default();
+        for mut table in parse_tables(contents)? {
+            match table.kind {
+                "monitor" => {
+                    let line = table.line;
+                    let monitor = monitor_from(&mut table)?;
+                    table.finish()?;
+                    if scenario.monitors.iter().any(|m| m.name == monitor.name) {
+                        return Err(format!(
+                            "line {line}: monitor {} is listed twice",
+                            monitor.name
+                        ));
+                    }
+                    scenario.monitors.push(monitor);
+                }
+                _ => {
+                    let line = table.line;
+                    let (at_line, at) = table.require("at")?;
+                    let at = at
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                        .map(Duration::from_secs_f64)
+                        .ok_or_else(|| {
+                            format!("line {at_line}: invalid at '{at}' (expected seconds)")
+                        })?;
+                    let (action_line, action) = table.require("action")?;
+                    let change = match action.as_str() {
+                        "add" => TopologyChange::Add(monitor_from(&mut table)?),
+                        "mode" => {
+                            let (_, name) = table.require("name")?;
+                            let size = table.take("size").map(parse_size).transpose()?;
+                            let refresh_hz =
+                                table.take("refresh").map(parse_refresh).transpose()?;
+                            let transform =
+                                table.take("transform").map(parse_transform).transpose()?;
+                            if size.is_none() && refresh_hz.is_none() && transform.is_none() {
+                                return Err(format!(
+                                    "line {line}: a mode event needs size, refresh or transform"
+                                ));
+                            }
+                            TopologyChange::Mode {
+                                name,
+                                size,
+                                refresh_hz,
+                                transform,
+                            }
+                        }
+                        "remove" => TopologyChange::Remove(table.require("name")?.1),
+                        other => {
+                            return Err(format!(
+                                "line {action_line}: invalid action '{other}' (expected add|mode|remove)"
+                            ));
+                        }
+                    };
+                    table.finish()?;
+                    scenario.events.push(ScenarioEvent { at, change });
+                }
+            }
+        }
+        // A stable sort keeps file order among events at the same time.
+        scenario.events.sort_by_key(|event| event.at);
+        let mut topology = scenario.monitors.clone();
+        for event in &scenario.events {
+            apply(&mut topology, &event.change)
+                .map_err(|err| format!("event at {:.1}s: {err}", event.at.as_secs_f64()))?;
+        }
+        Ok(scenario)
+    }
+}
+
+/// Applies one change to the outputs present.
+pub fn apply(topology: &mut Vec<MonitorInfo>, change: &TopologyChange) -> Result<(), String> {
+    match change {
+        TopologyChange::Add(monitor) => {
+            if topology.iter().any(|m| m.name == monitor.name) {
+                return Err(format!("{} is added while already present", monitor.name));
+            }
+            topology.push(monitor.clone());
+        }
+        TopologyChange::Mode {
+            name,
+            size,
+            refresh_hz,
+            transform,
+        } => {
+            let monitor = topology
+                .iter_mut()
+                .find(|m| m.name == *name)
+                .ok_or_else(|| format!("{name} changes mode while not present"))?;
+            if let Some((width, height)) = size {
+                monitor.width = *width;
+                monitor.height = *height;
+            }
+            if let Some(refresh_hz) = refresh_hz {
+                monitor.refresh_hz = *refresh_hz;
+            }
+            if let Some(transform) = transform {
+                monitor.transform = *transform;
+            }
+        }
+        TopologyChange::Remove(name) => {
+            let before = topology.len();
+            topology.retain(|m| m.name != *name);
+            if topology.len() == before {
+                return Err(format!("{name} is removed while not present"));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for TopologyChange {
+    /// `add HDMI-A-1 1920x1080@60 transform=normal`, `mode DP-1 3840x2160`,
+    /// `remove DP-1`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add(monitor) => write!(
+                f,
+                "add {} {}x{}@{} transform={}",
+                monitor.name,
+                monitor.width,
+                monitor.height,
+                monitor.refresh_hz,
+                monitor.transform.as_str()
+            ),
+            Self::Mode {
+                name,
+                size,
+                refresh_hz,
+                transform,
+            } => {
+                write!(f, "mode {name}")?;
+                if let Some((width, height)) = size {
+                    write!(f, " {width}x{height}")?;
+                }
+                if let Some(refresh_hz) = refresh_hz {
+                    write!(f, " refresh={refresh_hz}")?;
+                }
+                if let Some(transform) = transform {
+                    write!(f, " transform={}", transform.as_str())?;
+                }
+                Ok(())
+            }
+            Self::Remove(name) => write!(f, "remove {name}"),
+        }
+    }
+}
+
+/// Plays a scenario's events against the time since bootstrap.
+pub struct ScenarioPlayer {
+    events: Vec<ScenarioEvent>,
+    next: usize,
+}
+
+impl ScenarioPlayer {
+    pub fn new(events: Vec<ScenarioEvent>) -> Self {
+        Self { events, next: 0 }
+    }
+
+    /// Events whose time has come since the last call, in order.
+    pub fn due(&mut self, elapsed: Duration) -> &[ScenarioEvent] {
+        let start = self.next;
+        while self
+            .events
+            .get(self.next)
+            .is_some_and(|event| event.at <= elapsed)
+        {
+            self.next += 1;
+        }
+        &self.events[start..self.next]
+    }
+}
+
+fn parse_tables(contents: &str) -> Result<Vec<Table>, String> {
+    let mut tables = Vec::<Table>::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            let header = line.split('#').next().unwrap_or("").trim();
+            let kind = match header {
+                "[[monitor]]" => "monitor",
+                "[[event]]" => "event",
+                other => {
+                    return Err(format!(
+                        "line {line_no}: unknown table {other} (expected [[monitor]] or [[event]])"
+                    ));
+                }
+            };
+            tables.push(Table {
+                kind,
+                line: line_no,
+                values: BTreeMap::new(),
+            });
+            continue;
+        }
+        let Some(table) = tables.last_mut() else {
+            return Err(format!(
+                "line {line_no}: expected [[monitor]] or [[event]] before any key"
+            ));
+        };
+        let (key, raw_value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {line_no}: expected key = value"))?;
+        let value =
+            parse_value(raw_value.trim()).map_err(|err| format!("line {line_no}: {err}"))?;
+        if table
+            .values
+            .insert(key.trim().to_string(), (line_no, value))
+            .is_some()
+        {
+            return Err(format!("line {line_no}: duplicate key '{}'", key.trim()));
+        }
+    }
+    Ok(tables)
+}
+
+/// A monitor from `name`, `size`, and optional `refresh` (60) and
+/// `transform` (normal).
+fn monitor_from(table: &mut Table) -> Result<MonitorInfo, String> {
+    let (_, name) = table.require("name")?;
+    let (width, height) = parse_size(table.require("size")?)?;
+    let refresh_hz = table
+        .take("refresh")
+        .map(parse_refresh)
+        .transpose()?
+        .unwrap_or(60);
+    let transform = table
+        .take("transform")
+        .map(parse_transform)
+        .transpose()?
+        .unwrap_or_default();
+    Ok(MonitorInfo {
+        name,
+        width,
+        height,
+        refresh_hz,
+        transform,
+    })
+}
+
+fn parse_size((line, raw): (usize, String)) -> Result<(u32, u32), String> {
+    raw.split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)))
+        .filter(|(w, h)| *w > 0 && *h > 0)
+        .ok_or_else(|| format!("line {line}: invalid size '{raw}' (expected WxH)"))
+}
+
+fn parse_refresh((line, raw): (usize, String)) -> Result<u32, String> {
+    raw.parse::<u32>()
+        .ok()
+        .filter(|hz| *hz > 0)
+        .ok_or_else(|| format!("line {line}: invalid refresh '{raw}' (expected Hz)"))
+}
+
+fn parse_transform((line, raw): (usize, String)) -> Result<OutputTransform, String> {
+    TRANSFORMS
+        .into_iter()
+        .find(|transform| transform.as_str() == raw)
+        .ok_or_else(|| {
+            format!(
+                "line {line}: invalid transform '{raw}' (expected {})",
+                TRANSFORMS.map(OutputTransform::as_str).join("|")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/stub-scenario.toml");
+
+    fn describe(topology: &[MonitorInfo]) -> Vec<String> {
+        topology
+            .iter()
+            .map(|m| {
+                format!(
+                    "{} {}x{}@{} {}",
+                    m.name,
+                    m.width,
+                    m.height,
+                    m.refresh_hz,
+                    m.transform.as_str()
+                )
+            })
+            .collect()
+    }
+
+    fn parse_err(contents: &str) -> String {
+        Scenario::parse(contents).unwrap_err()
+    }
+
+    #[test]
+    fn fixture_parses_with_defaults_and_sorted_events() {
+        let scenario = Scenario::parse(FIXTURE).unwrap();
+        assert_eq!(
+            describe(&scenario.monitors),
+            ["DP-1 2560x1440@144 normal", "eDP-1 1920x1200@60 90"]
+        );
+        let timeline = scenario
+            .events
+            .iter()
+            .map(|event| format!("{}: {}", event.at.as_secs(), event.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timeline,
+            [
+                "5: add HDMI-A-1 1920x1080@60 transform=normal",
+                "12: mode DP-1 3840x2160 refresh=60",
+                "12: mode DP-1 transform=flipped",
+                "20: remove DP-1",
+            ]
+        );
+    }
+
+    #[test]
+    fn player_hands_out_each_event_once_when_due() {
+        let scenario = Scenario::parse(FIXTURE).unwrap();
+        let mut player = ScenarioPlayer::new(scenario.events);
+        let secs = Duration::from_secs_f64;
+        assert!(player.due(secs(0.0)).is_empty());
+        assert!(player.due(secs(4.9)).is_empty());
+        assert_eq!(player.due(secs(5.0)).len(), 1);
+        assert!(player.due(secs(5.0)).is_empty());
+        // A late tick gets everything it missed, in order.
+        let due = player.due(secs(30.0));
+        assert_eq!(due.len(), 3);
+        assert!(matches!(&due[2].change, TopologyChange::Remove(name) if name == "DP-1"));
+        assert!(player.due(secs(60.0)).is_empty());
+    }
+
+    #[test]
+    fn applying_the_timeline_tracks_the_outputs() {
+        let scenario = Scenario::parse(FIXTURE).unwrap();
+        let mut topology = scenario.monitors.clone();
+        let mut player = ScenarioPlayer::new(scenario.events);
+        let mut at = |secs: u64, topology: &mut Vec<MonitorInfo>| {
+            for event in player.due(Duration::from_secs(secs)) {
+                apply(topology, &event.change).unwrap();
+            }
+            describe(topology)
+        };
+        assert_eq!(
+            at(5, &mut topology),
+            [
+                "DP-1 2560x1440@144 normal",
+                "eDP-1 1920x1200@60 90",
+                "HDMI-A-1 1920x1080@60 normal"
+            ]
+        );
+        assert_eq!(at(12, &mut topology)[0], "DP-1 3840x2160@60 flipped");
+        assert_eq!(
+            at(20, &mut topology),
+            ["eDP-1 1920x1200@60 90", "HDMI-A-1 1920x1080@60 normal"]
+        );
+    }
+
+    #[test]
+    fn apply_refuses_changes_the_outputs_cannot_take() {
+        let mut topology = Scenario::parse(FIXTURE).unwrap().monitors;
+        let dp1 = topology[0].clone();
+        assert!(apply(&mut topology, &TopologyChange::Add(dp1)).is_err());
+        assert!(apply(&mut topology, &TopologyChange::Remove("HDMI-A-1".into())).is_err());
+        let mode = TopologyChange::Mode {
+            name: "HDMI-A-1".into(),
+            size: None,
+            refresh_hz: Some(30),
+            transform: None,
+        };
+        assert!(apply(&mut topology, &mode).is_err());
+        assert_eq!(topology.len(), 2);
+    }
+
+    #[test]
+    fn timeline_must_apply_to_the_outputs_present_at_its_time() {
+        let err = parse_err(
+            "[[event]]\nat = 3\naction = \"remove\"\nname = \"DP-1\"\n\n\
+             [[event]]\nat = 1\naction = \"add\"\nname = \"DP-1\"\nsize = \"800x600\"\n\n\
+             [[event]]\nat = 4\naction = \"mode\"\nname = \"DP-1\"\nrefresh = 30\n",
+        );
+        assert_eq!(err, "event at 4.0s: DP-1 changes mode while not present");
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let monitor = "[[monitor]]\nname = \"DP-1\"\nsize = \"1920x1080\"\n";
+        assert_eq!(
+            parse_err("name = \"DP-1\"\n"),
+            "line 1: expected [[monitor]] or [[event]] before any key"
+        );
+        assert!(parse_err("[monitor]\n").starts_with("line 1: unknown table [monitor]"));
+        assert_eq!(
+            parse_err(&format!("{monitor}{monitor}")),
+            "line 4: monitor DP-1 is listed twice"
+        );
+        assert_eq!(
+            parse_err("[[monitor]]\nname = \"DP-1\"\n"),
+            "line 1: [[monitor]] is missing 'size'"
+        );
+        assert_eq!(
+            parse_err(&format!("{monitor}size = \"800x600\"\n")),
+            "line 4: duplicate key 'size'"
+        );
+        assert_eq!(
+            parse_err(&format!("{monitor}scale = 2\n")),
+            "line 4: unknown key 'scale' in [[monitor]]"
+        );
+        assert!(
+            parse_err("[[monitor]]\nname = \"DP-1\"\nsize = \"0x1080\"\n")
+                .starts_with("line 3: invalid size '0x1080'")
+        );
+        assert!(
+            parse_err(&format!("{monitor}transform = \"upside\"\n"))
+                .starts_with("line 4: invalid transform 'upside' (expected normal|90|")
+        );
+        assert!(
+            parse_err("[[event]]\nat = -1\naction = \"remove\"\nname = \"DP-1\"\n")
+                .starts_with("line 2: invalid at '-1'")
+        );
+        assert!(
+            parse_err("[[event]]\nat = 1\naction = \"swap\"\nname = \"DP-1\"\n")
+                .starts_with("line 3: invalid action 'swap'")
+        );
+        assert_eq!(
+            parse_err("[[event]]\nat = 1\naction = \"mode\"\nname = \"DP-1\"\n"),
+            "line 1: a mode event needs size, refresh or transform"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! The GPU side of [`crate::widgets`]: each text widget on each output is a
+//! small coverage texture, uploaded again only when its text changes, drawn
+//! as one quad at the end of the output's frame pass. The quad is placed in
+//! display pixels and mapped into the pre-transformed buffer here, so a
+//! widget reads the right way up under any buffer transform.
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::bitmap_font;
+use crate::monitor::OutputTransform;
+use crate::widgets::TextStyle;
+
+const TEXT_SHADER_WGSL: &str = r#"
+struct TextUniform {
+    // Display UV origin and size of the widget.
+    rect: vec4<f32>,
+    // Linear RGB and opacity.
+    color: vec4<f32>,
+    buffer_transform: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+struct TextOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0) var mask: texture_2d<f32>;
+@group(0) @binding(1) var mask_sampler: sampler;
+@group(0) @binding(2) var<uniform> text: TextUniform;
+
+// Display UV to buffer UV: the inverse of the frame shader's display_uv.
+fn buffer_uv(d: vec2<f32>) -> vec2<f32> {
+    switch text.buffer_transform {
+        case 1u: { return vec2<f32>(d.y, 1.0 - d.x); }
+        case 2u: { return vec2<f32>(1.0 - d.x, 1.0 - d.y); }
+        case 3u: { return vec2<f32>(1.0 - d.y, d.x); }
+        case 4u: { return vec2<f32>(1.0 - d.x, d.y); }
+        case 5u: { return vec2<f32>(d.y, d.x); }
+        case 6u: { return vec2<f32>(d.x, 1.0 - d.y); }
+        case 7u: { return vec2<f32>(1.0 - d.y, 1.0 - d.x); }
+        default: { return d; }
+    }
+}
+
+@vertex
+fn vs_text(@builtin(vertex_index) vid: u32) -> TextOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0)
+    );
+    let corner = corners[vid];
+    let b = buffer_uv(text.rect.xy + corner * text.rect.zw);
+    var out: TextOut;
+    out.pos = vec4<f32>(2.0 * b.x - 1.0, 1.0 - 2.0 * b.y, 0.0, 1.0);
+    out.uv = corner;
+    return out;
+}
+
+@fragment
+fn fs_text(in: TextOut) -> @location(0) vec4<f32> {
+    let alpha = textureSample(mask, mask_sampler, in.uv).r * text.color.a;
+    return vec4<f32>(text.color.rgb * alpha, alpha);
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct TextUniform {
+    rect: [f32; 4],
+    color: [f32; 4],
+    buffer_transform: u32,
+    _pad: [u32; 3],
+}
+
+/// Shader, layout and sampler every text layer shares; pipelines per
+/// surface format, built on first use like the frame pipelines.
+pub struct TextProgram {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    sampler: wgpu::Sampler,
+    pipelines: Vec<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+}
+
+impl TextProgram {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("kitsune-rendercore-text-shader"),
+            source: wgpu::ShaderSource::Wgsl(TEXT_SHADER_WGSL.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("kitsune-rendercore-text-bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("kitsune-rendercore-text-pipeline-layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        // Nearest: each font texel covers a whole square of output pixels.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("kitsune-rendercore-text-sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+            pipelines: Vec::new(),
+        }
+    }
+
+    pub fn pipeline(&self, format: wgpu::TextureFormat) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines
+            .iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, pipeline)| pipeline)
+    }
+
+    pub fn ensure_pipeline(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        if self.pipeline(format).is_some() {
+            return;
+        }
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("kitsune-rendercore-text-pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_text"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: Some("fs_text"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        self.pipelines.push((format, pipeline));
+        println!("[rendercore] text pipeline: {format:?}");
+    }
+}
+
+/// One text widget on one output.
+pub struct TextLayer {
+    uniform_buffer: wgpu::Buffer,
+    /// The mask texture and its bind group; `None` until there is text.
+    mask: Option<(wgpu::Texture, wgpu::BindGroup)>,
+    /// The uploaded text and its size in texels.
+    text: String,
+    size: (u32, u32),
+    /// Text and display rect as last presented; `None` while the widget is
+    /// not on screen.
+    pub shown: Option<(String, (u32, u32, u32, u32))>,
+}
+
+impl TextLayer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            uniform_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("kitsune-rendercore-text-uniform"),
+                size: std::mem::size_of::<TextUniform>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            mask: None,
+            text: String::new(),
+            size: (0, 0),
+            shown: None,
+        }
+    }
+
+    /// Rasterizes and uploads `text` when it differs from the last one; the
+    /// texture is only recreated when the size changes. Returns the size in
+    /// texels, `None` for empty text.
+    pub fn set_text(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        program: &TextProgram,
+        text: &str,
+    ) -> Option<(u32, u32)> {
+        if text == self.text {
+            return self.mask.is_some().then_some(self.size);
+        }
+        self.text = text.to_string();
+        let Some(mask) = bitmap_font::rasterize(text) else {
+            self.mask = None;
+            return None;
+        };
+        let size = (mask.width, mask.height);
+        if self.mask.is_none() || size != self.size {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("kitsune-rendercore-text-mask"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("kitsune-rendercore-text-bg"),
+                layout: &program.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&program.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+            self.mask = Some((texture, bind_group));
+            self.size = size;
+        }
+        if let Some((texture, _)) = &self.mask {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &mask.texels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size.0),
+                    rows_per_image: Some(size.1),
+                },
+                wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        Some(size)
+    }
+
+    /// Places the widget at display-pixel `rect` of a `display_size` output.
+    pub fn write_uniform(
+        &self,
+        queue: &wgpu::Queue,
+        (x0, y0, x1, y1): (u32, u32, u32, u32),
+        (width, height): (u32, u32),
+        buffer_transform: OutputTransform,
+        style: TextStyle,
+    ) {
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        let uniform = TextUniform {
+            rect: [
+                x0 as f32 / width,
+                y0 as f32 / height,
+                (x1 - x0) as f32 / width,
+                (y1 - y0) as f32 / height,
+            ],
+            color: style.linear_rgba(),
+            buffer_transform: buffer_transform.wire_value(),
+            _pad: [0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Draws the widget with the program's pipeline already set; the pass's
+    /// viewport must cover the whole buffer.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>) {
+        if let Some((_, bind_group)) = &self.mask {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..6, 0..1);
+        }
+    }
+}
@@ -1,24 +1,80 @@
+use crate::aliases::Aliases;
 use crate::backend::LayerBackend;
-use crate::frame_source::{FrameSource, VideoOptions};
-use crate::monitor::{LayerRole, MonitorInfo, MonitorSurfaceSpec};
+use crate::backend::gpu_select::{
+    compositor_device, render_nodes, select_adapter, software_fps_cap,
+};
+use crate::backend::particles::{self, OverlayTimer};
+use crate::backend::shell_policy::{self, NoLayerShell};
+use crate::backend::text_layer::{TextLayer, TextProgram};
+use crate::bench::RenderBench;
+use crate::callback_latency::{CallbackLatency, LatencyChange};
+use crate::color::{self, ColorPipeline, Gamut, OutputColorimetry, Primaries, Transfer};
+use crate::config_file::config_file_path_from_env;
+use crate::env_file::EnvFileWatcher;
+use crate::event_file;
+use crate::events::{self, RenderEvent};
+use crate::file_access;
+use crate::flash_guard::{FlashGuard, GuardState};
+use crate::frame_source::{self, FrameSource, StartAt, VideoOptions, WarmPoll, WarmSource};
+use crate::headroom::{self, DecodePath, Estimate, MachineFacts, StreamLoad, Verdict};
+use crate::hooks::{self, HookContext, HookEvent};
+use crate::hw_decode::{self, HwDecode};
+use crate::journal::{self, Value};
+use crate::json::escape_json;
+use crate::log_limit;
+use crate::monitor::{
+    CallbackStats, CellStatus, DamageStats, GpuAdapterStatus, LayerRole, MonitorInfo,
+    MonitorSurfaceSpec, OutputAllowlist, OutputRuntimeStatus, OutputTransform, RefreshSample,
+    ShellStatus, filter_refresh, plausible_mode,
+};
+use crate::path_probe::{self, Probe};
+use crate::paths::cache_dir;
+use crate::profile::{FrameProfiler, Phase, RollingStats};
+use crate::quality::{self, AutoQuality};
+use crate::snapshot::{PlaybackPosition, Restored};
+use crate::startup::{self, Step};
+use crate::timebase::{self, ClockReading, RenderClock, Timebase};
+use crate::tools::Tool;
+use crate::user_path::{display_path, display_text};
 use crate::video_map::{
-    map_file_path_from_env, merge_maps, parse_video_map_env, parse_video_map_file,
+    Effect, EntryOptions, Fit, Layout, MAP_CHECK_INTERVAL, MapDocument, Overlay, QualityPreset,
+    SampleFilter, StillFrame, Trim, VideoMap, VideoMapEntry, default_video_from_env, env_video_map,
+    format_video_map, load_map_document, map_file_path_from_env, merge_maps, read_map_document,
+    resolve_output_video,
 };
+use crate::widgets::{Clock, ClockConfig};
 use bytemuck::{Pod, Zeroable};
 use raw_window_handle::{
     RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
 };
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant, SystemTime};
 use wayland_client::protocol::{
-    wl_callback, wl_compositor, wl_output, wl_registry, wl_surface, wl_surface::WlSurface,
+    wl_callback, wl_compositor, wl_output, wl_region, wl_registry, wl_surface,
+    wl_surface::WlSurface,
 };
 use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum, delegate_noop};
+use wayland_protocols::wp::color_management::v1::client::{
+    wp_color_management_output_v1::{self, WpColorManagementOutputV1},
+    wp_color_management_surface_v1::WpColorManagementSurfaceV1,
+    wp_color_manager_v1::{self, WpColorManagerV1},
+    wp_image_description_info_v1::{self, WpImageDescriptionInfoV1},
+    wp_image_description_v1::{self, WpImageDescriptionV1},
+};
+use wayland_protocols::wp::viewporter::client::{
+    wp_viewport::WpViewport, wp_viewporter::WpViewporter,
+};
+use wayland_protocols::xdg::shell::client::{
+    xdg_surface::{self, XdgSurface},
+    xdg_toplevel::{self, XdgToplevel},
+    xdg_wm_base::{self, XdgWmBase},
+};
 use wayland_protocols_wlr::layer_shell::v1::client::{
     zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
-    zwlr_layer_surface_v1::{self, Anchor, ZwlrLayerSurfaceV1},
+    zwlr_layer_surface_v1::{self, Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
 };
 
 #[derive(Default)]
@@ -27,70 +83,189 @@ pub struct WaylandLayerBackend {
     connection: Option<Connection>,
     event_queue: Option<EventQueue<WaylandLayerState>>,
     wgpu_shared: Option<WgpuShared>,
-    frame_index: u64,
     state: WaylandLayerState,
+    profiler: FrameProfiler,
+    /// Which shell the surfaces use and why (`KRC_ON_NO_LAYER_SHELL`).
+    shell: Option<ShellStatus>,
+    /// Handed to the streams at bootstrap; see [`LayerBackend::restore_state`].
+    restored: Option<Restored>,
 }
 
 impl Drop for WaylandLayerBackend {
     fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl LayerBackend for WaylandLayerBackend {
+    /// Tears everything down in dependency order instead of field order: the
+    /// Vulkan swapchains reference the `wl_surface`s, and destroying those
+    /// under a live swapchain makes the WSI layer fail validation or crash.
+    /// So: the wgpu surfaces, a wait for the GPU to go idle, the device; then
+    /// each surface's role and `wl_surface`, the outputs and globals; and the
+    /// event queue and connection last. Safe to call more than once.
+    fn shutdown(&mut self) {
         self.bootstrapped = false;
 
-        // Drop GPU surfaces/resources before tearing down Wayland objects they reference.
-        self.wgpu_shared.take();
+        if let Some(mut shared) = self.wgpu_shared.take() {
+            shared.release_outputs(|_| true, |_| None);
+        }
 
-        // Drop protocol objects before the connection/event queue.
-        self.state.layer_surfaces.clear();
-        self.state.outputs.clear();
-        self.state.layer_shell = None;
+        for slot in self.state.layer_surfaces.drain(..) {
+            slot.destroy();
+        }
+        for out in std::mem::take(&mut self.state.outputs).into_values() {
+            out.release();
+        }
+        if let Some(layer_shell) = self.state.layer_shell.take()
+            && layer_shell.version() >= 3
+        {
+            layer_shell.destroy();
+        }
+        if let Some(xdg_wm_base) = self.state.xdg_wm_base.take() {
+            xdg_wm_base.destroy();
+        }
+        if let Some(viewporter) = self.state.viewporter.take() {
+            viewporter.destroy();
+        }
+        if let Some(color_manager) = self.state.color_manager.take() {
+            color_manager.destroy();
+        }
         self.state.compositor = None;
 
+        // The destroy requests are only queued; send them while the
+        // connection is still there.
+        if let Some(connection) = &self.connection {
+            let _ = connection.flush();
+        }
         self.event_queue = None;
         self.connection = None;
     }
-}
 
-impl LayerBackend for WaylandLayerBackend {
     fn name(&self) -> &'static str {
         "wayland-layer"
     }
 
     fn bootstrap(&mut self) -> Result<(), String> {
-        let connection = Connection::connect_to_env()
-            .map_err(|err| format!("failed to connect wayland display: {err}"))?;
+        startup::begin(Step::Compositor)?;
+        // A user service may start before the compositor has its socket up or
+        // has announced its outputs; wait instead of failing into a restart loop.
+        let wait = compositor_wait_from_env();
+        let connection = wait_for("the wayland compositor", wait, || {
+            Ok(Connection::connect_to_env()
+                .map_err(|err| format!("failed to connect wayland display: {err}")))
+        })?;
         let mut event_queue = connection.new_event_queue();
         let qh = event_queue.handle();
-
-        connection.display().get_registry(&qh, ());
-        event_queue
-            .roundtrip(&mut self.state)
-            .map_err(|err| format!("wayland roundtrip failed: {err}"))?;
-
-        if self.state.compositor.is_none() {
-            return Err("wl_compositor is not available".to_string());
-        }
-        if self.state.layer_shell.is_none() {
-            return Err(
-                "zwlr_layer_shell_v1 is not available (compositor may not support layer-shell)"
-                    .to_string(),
+        self.state.allowlist = OutputAllowlist::from_env();
+        self.profiler = FrameProfiler::from_env();
+        if self.state.allowlist.is_restricted() {
+            println!(
+                "[backend:{}] output allowlist: {}",
+                self.name(),
+                self.state.allowlist.entries().join(",")
             );
         }
-        if self.state.outputs.is_empty() {
-            return Err("no wl_output globals discovered".to_string());
-        }
+
+        connection.display().get_registry(&qh, ());
+        let on_missing = NoLayerShell::from_env();
+        let state = &mut self.state;
+        let started = Instant::now();
+        // Whether the first registry seen had a compositor but no layer-shell.
+        let mut missing_at_first = None::<bool>;
+        wait_for(
+            "layer-shell and outputs",
+            on_missing.wait_limit(wait),
+            || {
+                event_queue
+                    .roundtrip(state)
+                    .map_err(|err| format!("wayland roundtrip failed: {err}"))?;
+                if state.compositor.is_some() {
+                    missing_at_first.get_or_insert(state.layer_shell.is_none());
+                }
+                let windowed = on_missing == NoLayerShell::Windowed && state.xdg_wm_base.is_some();
+                Ok(if state.compositor.is_none() {
+                    Err("wl_compositor is not available".to_string())
+                } else if state.layer_shell.is_none() && !windowed {
+                    Err(NO_LAYER_SHELL.to_string())
+                } else if state.outputs.is_empty() {
+                    Err("no wl_output globals discovered".to_string())
+                } else {
+                    Ok(())
+                })
+            },
+        )
+        .map_err(|err| {
+            if err == NO_LAYER_SHELL {
+                on_missing.missing_error(started.elapsed())
+            } else {
+                err
+            }
+        })?;
+        let shell = if self.state.layer_shell.is_some() {
+            let reason = if missing_at_first == Some(true) {
+                format!(
+                    "zwlr_layer_shell_v1 registered after {}ms",
+                    started.elapsed().as_millis()
+                )
+            } else {
+                "zwlr_layer_shell_v1 available".to_string()
+            };
+            on_missing.status("layer-shell", reason)
+        } else {
+            on_missing.status(
+                "xdg-shell",
+                format!(
+                    "zwlr_layer_shell_v1 missing on {}, KRC_ON_NO_LAYER_SHELL=windowed: one fullscreen window per output",
+                    shell_policy::desktop_name().as_deref().unwrap_or("this compositor")
+                ),
+            )
+        };
+        println!(
+            "[backend:{}] shell: {} ({})",
+            self.name(),
+            shell.protocol,
+            shell.reason
+        );
+        journal::record(
+            "shell",
+            &[
+                ("protocol", Value::Str(shell.protocol)),
+                ("on_missing", Value::Str(shell.on_missing)),
+                ("reason", Value::Str(&shell.reason)),
+            ],
+        );
+        self.shell = Some(shell);
 
         self.state.create_layer_surfaces(&qh)?;
         event_queue
             .roundtrip(&mut self.state)
             .map_err(|err| format!("wayland post-surface roundtrip failed: {err}"))?;
+        // Output names usually arrive only now; drop the surfaces the allowlist
+        // rejects before any GPU surface or decoder exists for them.
+        self.state.drop_retired_surfaces();
+        if self.state.layer_surfaces.is_empty() {
+            return Err(format!(
+                "no output matches KRC_OUTPUTS ({})",
+                self.state.allowlist.entries().join(",")
+            ));
+        }
 
-        let wgpu_shared =
-            init_wgpu_shared(&connection, &self.state.outputs, &self.state.layer_surfaces)?;
+        let wgpu_shared = init_wgpu_shared(
+            &connection,
+            &self.state.outputs,
+            &self.state.layer_surfaces,
+            self.state.reduce_motion,
+            self.restored.take().unwrap_or_default(),
+        )?;
+        // Streams were just resolved with the names known so far.
+        self.state.take_remaps();
+        self.state.software_fps_cap = wgpu_shared.gpu.fps_cap;
 
         self.bootstrapped = true;
         self.connection = Some(connection);
         self.event_queue = Some(event_queue);
         self.wgpu_shared = Some(wgpu_shared);
-        self.frame_index = 0;
 
         println!(
             "[backend:{}] wayland connected outputs={} layer-surfaces={}",
@@ -110,14 +285,13 @@ impl LayerBackend for WaylandLayerBackend {
             .state
             .outputs
             .values()
+            .filter(|out| !out.excluded)
             .map(|out| MonitorInfo {
-                name: out
-                    .name
-                    .clone()
-                    .unwrap_or_else(|| format!("wl-output-{}", out.global_name)),
-                width: out.width.unwrap_or(1920),
-                height: out.height.unwrap_or(1080),
+                name: out.display_name(),
+                width: out.logical_size().0,
+                height: out.logical_size().1,
                 refresh_hz: out.refresh_hz.unwrap_or(60),
+                transform: out.transform,
             })
             .collect::<Vec<_>>();
 
@@ -145,7 +319,7 @@ impl LayerBackend for WaylandLayerBackend {
             .collect())
     }
 
-    fn render_frame(&mut self, surfaces: &[MonitorSurfaceSpec]) -> Result<(), String> {
+    fn render_frame(&mut self, _surfaces: &[MonitorSurfaceSpec]) -> Result<(), String> {
         if !self.bootstrapped {
             return Err("backend not bootstrapped".to_string());
         }
@@ -154,16 +328,367 @@ impl LayerBackend for WaylandLayerBackend {
             .event_queue
             .as_mut()
             .ok_or_else(|| "missing wayland event queue".to_string())?;
+        let t = self.profiler.start();
         queue
             .dispatch_pending(&mut self.state)
             .map_err(|err| format!("wayland dispatch_pending failed: {err}"))?;
+        self.profiler.end(Phase::Dispatch, t);
         let qh = queue.handle();
-        if self.state.ready_output_ids().is_empty() {
+        // A paced output waiting for its own slot is left to a later tick:
+        // no frame callback is coming to wake a blocking dispatch.
+        if !self.state.has_pending_redraw() {
+            let t = self.profiler.start();
             queue
                 .blocking_dispatch(&mut self.state)
                 .map_err(|err| format!("wayland blocking_dispatch failed: {err}"))?;
+            self.profiler.end(Phase::DispatchWait, t);
+        }
+        if self.state.has_retired_outputs() {
+            // Same order as `shutdown`, for just these outputs: GPU surfaces
+            // and decoders first, since they reference the wl_surface.
+            if let Some(shared) = self.wgpu_shared.as_mut() {
+                let outputs = &self.state.outputs;
+                shared.release_outputs(
+                    |id| outputs.get(&id).is_some_and(OutputSlot::retired),
+                    |id| match outputs.get(&id) {
+                        Some(out) if out.gone => Some("output-removed"),
+                        _ => Some("output-excluded"),
+                    },
+                );
+            }
+            self.state.drop_retired_surfaces();
+        }
+
+        let shared = self.wgpu_shared.as_ref();
+        self.state.check_callback_stalls(Instant::now());
+        let mut ready_outputs = self.state.ready_output_ids(Instant::now(), |output_id| {
+            shared.is_some_and(|shared| shared.shows_still(output_id))
+        });
+        let remapped = self.state.take_remaps();
+        if let Some(shared) = self.wgpu_shared.as_mut() {
+            shared.freeze_streams(|output_id| self.state.is_output_paused(output_id));
+            let (presented, unchanged) = shared.render_textured(
+                &remapped,
+                &self.state.outputs,
+                &self.state.layer_surfaces,
+                &ready_outputs,
+                &mut self.profiler,
+            )?;
+            // Nothing changed on these: no present and no frame callback,
+            // like an idle still; they are due again next tick.
+            ready_outputs.retain(|output_id| !unchanged.contains(output_id));
+            let now = Instant::now();
+            for slot in &mut self.state.layer_surfaces {
+                if presented.contains(&slot.output_global_name) {
+                    slot.presented_frames += 1;
+                    slot.first_presented_at.get_or_insert(now);
+                }
+            }
         }
+        if !ready_outputs.is_empty() {
+            self.state
+                .mark_presented_and_request_frames(&qh, &ready_outputs, Instant::now());
+            if let Some(conn) = self.connection.as_ref() {
+                let t = self.profiler.start();
+                conn.flush()
+                    .map_err(|err| format!("wayland connection flush failed: {err}"))?;
+                self.profiler.end(Phase::Flush, t);
+            }
+            let (state, shared) = (&self.state, self.wgpu_shared.as_ref());
+            self.profiler.frame_done(|| rolling_stats(state, shared));
+        }
+
+        Ok(())
+    }
+
+    fn shell_status(&self) -> Option<ShellStatus> {
+        self.shell.clone()
+    }
+
+    fn output_status(&self) -> Vec<OutputRuntimeStatus> {
+        self.state
+            .layer_surfaces
+            .iter()
+            .map(|slot| {
+                let output_id = slot.output_global_name;
+                let stream = self
+                    .wgpu_shared
+                    .as_ref()
+                    .and_then(|shared| shared.video_streams.get(&output_id));
+                let render_surface = self.wgpu_shared.as_ref().and_then(|shared| {
+                    shared
+                        .render_surfaces
+                        .iter()
+                        .find(|rs| rs.output_global_name == output_id)
+                });
+                // A follower shows its leader's decoder.
+                let decoder = self.wgpu_shared.as_ref().and_then(|shared| {
+                    shared.video_streams.get(&shared.decoding_output(output_id))
+                });
+                let (quality_level, quality) = self
+                    .wgpu_shared
+                    .as_ref()
+                    .map(|shared| shared.quality_status(output_id))
+                    .unwrap_or((0, "full".to_string()));
+                let layout = self
+                    .wgpu_shared
+                    .as_ref()
+                    .and_then(|shared| shared.layouts.get(&output_id));
+                OutputRuntimeStatus {
+                    name: output_label(&self.state.outputs, output_id),
+                    description: self
+                        .state
+                        .outputs
+                        .get(&output_id)
+                        .and_then(|out| out.identity.description.clone()),
+                    stream_kind: match decoder.map(|s| &s.frame_source) {
+                        _ if layout.is_some() => "layout",
+                        Some(source) if source.is_video() => "video",
+                        Some(source) if source.is_still() => "still",
+                        _ => "procedural",
+                    },
+                    source: stream
+                        .and_then(|s| s.current_entry.as_ref())
+                        .map(|entry| entry.video.clone()),
+                    first_presented_at: slot.first_presented_at,
+                    presented_frames: slot.presented_frames,
+                    frame_callback_pending: slot.frame_callback_pending,
+                    last_error: stream.and_then(|s| s.last_error.clone()),
+                    last_error_repeats: stream.map_or(0, |s| s.last_error_repeats),
+                    degraded: decoder.and_then(VideoStream::degraded),
+                    source_size: stream.map(|s| (s.source_width, s.source_height)),
+                    quality_preset: stream
+                        .and_then(|s| s.current_entry.as_ref())
+                        .and_then(|entry| entry.options.quality)
+                        .map(QualityPreset::as_str),
+                    quality_level,
+                    quality,
+                    decode_nice: decoder.and_then(|s| s.frame_source.decoder_nice()),
+                    decoder_pid: stream.and_then(|s| s.frame_source.decoder_pid()),
+                    hw_decode: decoder.and_then(|s| s.frame_source.hw_decode()),
+                    effect: stream
+                        .and_then(|s| s.current_entry.as_ref())
+                        .filter(|entry| entry.options.effect != Effect::None)
+                        .map(|entry| {
+                            format!(
+                                "{} {}",
+                                entry.options.effect.as_str(),
+                                entry.options.ambient
+                            )
+                        }),
+                    overlay: stream
+                        .and_then(|s| s.current_entry.as_ref())
+                        .filter(|entry| entry.options.overlay != Overlay::None)
+                        .map(|entry| {
+                            format!(
+                                "{} {}",
+                                entry.options.overlay.as_str(),
+                                entry.options.overlay_params
+                            )
+                            .trim_end()
+                            .to_string()
+                        }),
+                    trim: stream
+                        .and_then(|s| s.current_entry.as_ref())
+                        .and_then(|entry| entry.options.trim)
+                        .map(|trim| trim.to_string()),
+                    crop: stream.and_then(|s| {
+                        let crop = s.current_entry.as_ref()?.options.crop?;
+                        Some(match s.crop_rect() {
+                            Some(((x, y, w, h), _)) => format!("{x},{y},{w},{h}"),
+                            None => format!("{crop} (pending)"),
+                        })
+                    }),
+                    filter: stream
+                        .and_then(|s| s.current_entry.as_ref())
+                        .map_or(SampleFilter::Linear, |entry| entry.options.filter)
+                        .as_str(),
+                    fit: stream
+                        .and_then(|s| s.current_entry.as_ref())
+                        .map_or(Fit::Cover, |entry| entry.options.fit)
+                        .as_str(),
+                    decoder_shared_with: stream
+                        .and_then(|s| s.shared_from.as_ref())
+                        .map(|shared| shared.leader_monitor.clone()),
+                    buffer_scale: render_surface.map_or(1, |rs| rs.buffer_scale),
+                    buffer_transform: render_surface
+                        .map_or(OutputTransform::Normal, |rs| rs.buffer_transform)
+                        .as_str(),
+                    clamped: render_surface.is_some_and(|rs| rs.clamped),
+                    color: render_surface.and_then(|rs| rs.color.clone()),
+                    input_region: Some(slot.input_region),
+                    callback_latency: Some(slot.callback_latency.stats()),
+                    frame_arrival: decoder.and_then(|s| s.frame_source.frame_arrival()),
+                    damage: render_surface
+                        .filter(|_| {
+                            self.wgpu_shared
+                                .as_ref()
+                                .is_some_and(|shared| shared.damage_tracking)
+                        })
+                        .map(|rs| rs.damage.stats()),
+                    flash_guard: decoder
+                        .and_then(|s| s.flash_guard.as_ref())
+                        .map(|guard| guard.state().as_str()),
+                    suspect_mode: self
+                        .state
+                        .outputs
+                        .get(&output_id)
+                        .and_then(|out| out.suspect_mode),
+                    layout: layout.map(Layout::grid),
+                    cells: self
+                        .wgpu_shared
+                        .as_ref()
+                        .zip(layout)
+                        .map(|(shared, layout)| shared.cell_status(output_id, layout))
+                        .unwrap_or_default(),
+                }
+            })
+            .chain(
+                self.state
+                    .outputs
+                    .values()
+                    .filter(|out| out.excluded)
+                    .map(|out| OutputRuntimeStatus {
+                        name: out.display_name(),
+                        description: out.identity.description.clone(),
+                        stream_kind: "excluded",
+                        source: None,
+                        first_presented_at: None,
+                        presented_frames: 0,
+                        frame_callback_pending: false,
+                        last_error: None,
+                        last_error_repeats: 0,
+                        degraded: None,
+                        source_size: None,
+                        quality_preset: None,
+                        quality_level: 0,
+                        quality: "full".to_string(),
+                        decode_nice: None,
+                        decoder_pid: None,
+                        hw_decode: None,
+                        effect: None,
+                        overlay: None,
+                        trim: None,
+                        crop: None,
+                        filter: SampleFilter::Linear.as_str(),
+                        fit: Fit::Cover.as_str(),
+                        decoder_shared_with: None,
+                        buffer_scale: 1,
+                        buffer_transform: OutputTransform::Normal.as_str(),
+                        clamped: false,
+                        color: None,
+                        input_region: None,
+                        callback_latency: None,
+                        frame_arrival: None,
+                        damage: None,
+                        flash_guard: None,
+                        suspect_mode: out.suspect_mode,
+                        layout: None,
+                        cells: Vec::new(),
+                    }),
+            )
+            .collect()
+    }
+
+    fn reload(&mut self, hard: bool) -> Option<String> {
+        let shared = self.wgpu_shared.as_mut()?;
+        Some(shared.force_reload(&self.state.outputs, hard))
+    }
+
+    fn set_transient_video(
+        &mut self,
+        monitor: &str,
+        entry: Option<VideoMapEntry>,
+    ) -> Option<String> {
+        let shared = self.wgpu_shared.as_mut()?;
+        Some(shared.set_transient(&self.state.outputs, monitor, entry))
+    }
+
+    fn clear_transient_videos(&mut self) -> Option<String> {
+        let shared = self.wgpu_shared.as_mut()?;
+        Some(shared.clear_transient(&self.state.outputs))
+    }
+
+    fn set_reduce_motion(&mut self, on: bool) -> Option<String> {
+        self.state.reduce_motion = on;
+        let shared = self.wgpu_shared.as_mut()?;
+        Some(shared.set_reduce_motion(&self.state.outputs, on))
+    }
 
+    fn transient_overrides(&self) -> usize {
+        self.wgpu_shared
+            .as_ref()
+            .map_or(0, |shared| shared.video_map_state.transient.len())
+    }
+
+    fn transient_videos(&self) -> VideoMap {
+        self.wgpu_shared
+            .as_ref()
+            .map(|shared| shared.video_map_state.transient.clone())
+            .unwrap_or_default()
+    }
+
+    fn playback_positions(&self) -> Vec<PlaybackPosition> {
+        let Some(shared) = &self.wgpu_shared else {
+            return Vec::new();
+        };
+        // Layout cells and followers are left out: a cell's stream is not an
+        // output, and a follower shows its leader's frames.
+        shared
+            .video_streams
+            .iter()
+            .filter(|(id, stream)| cell_index(**id).is_none() && stream.shared_from.is_none())
+            .filter_map(|(_, stream)| {
+                Some(PlaybackPosition {
+                    monitor: stream.monitor.clone(),
+                    video: stream.current_entry.as_ref()?.video.clone(),
+                    position_ms: stream.frame_source.playback_ms()?,
+                })
+            })
+            .collect()
+    }
+
+    fn restore_state(&mut self, restored: Restored) {
+        self.restored = Some(restored);
+    }
+
+    fn set_paused_outputs(&mut self, monitors: &[String]) {
+        self.state.paused_outputs = monitors.to_vec();
+    }
+
+    fn set_frozen(&mut self, frozen: bool) {
+        self.state.frozen = frozen;
+    }
+
+    fn check_video_map(&mut self) -> Option<Duration> {
+        // Before the streams exist there is nothing to compare against yet.
+        Some(
+            self.wgpu_shared
+                .as_mut()
+                .map_or(MAP_CHECK_INTERVAL, WgpuShared::poll_video_map),
+        )
+    }
+
+    fn set_fps_ceiling(&mut self, fps: u32) {
+        self.state.fps_ceiling = fps;
+    }
+
+    fn set_aliases(&mut self, aliases: &Aliases) {
+        self.state.aliases = aliases.clone();
+        for out in self.state.outputs.values_mut() {
+            out.identity.apply_aliases(aliases);
+        }
+    }
+
+    fn gpu_status(&self) -> Option<GpuAdapterStatus> {
+        self.wgpu_shared.as_ref().map(|shared| shared.gpu.clone())
+    }
+
+    fn headroom(&self) -> Option<Estimate> {
+        self.wgpu_shared.as_ref()?.headroom.clone()
+    }
+
+    fn report_summary(&self) -> Option<String> {
         let configured = self
             .state
             .layer_surfaces
@@ -186,49 +711,46 @@ impl LayerBackend for WaylandLayerBackend {
             .state
             .layer_surfaces
             .iter()
-            .map(|slot| {
-                format!(
-                    "{}:{}",
-                    slot.output_global_name,
-                    slot.layer_surface.id().protocol_id()
-                )
-            })
+            .map(|slot| format!("{}:{}", slot.output_global_name, slot.role.protocol_id()))
             .collect::<Vec<_>>()
             .join(",");
+        Some(format!(
+            "surfaces={} configured={} ready={} pending_callbacks={} uploaded_video_frames={} {} outputs=[{}]",
+            self.state.layer_surfaces.len(),
+            configured,
+            ready,
+            pending_callbacks,
+            shared_uploaded_frames(self),
+            shared_mipgen_stats(self),
+            outputs
+        ))
+    }
 
-        let ready_outputs = self.state.ready_output_ids();
-        if let Some(shared) = self.wgpu_shared.as_mut() {
-            shared.render_textured(
-                self.frame_index,
-                &self.state.outputs,
-                &self.state.layer_surfaces,
-                &ready_outputs,
-            )?;
-        }
-        if !ready_outputs.is_empty() {
-            self.state
-                .mark_presented_and_request_frames(&qh, &ready_outputs);
-            if let Some(conn) = self.connection.as_ref() {
-                conn.flush()
-                    .map_err(|err| format!("wayland connection flush failed: {err}"))?;
-            }
-            self.frame_index = self.frame_index.wrapping_add(1);
-        }
+    fn profile_report(&self) -> Option<String> {
+        Some(
+            self.profiler
+                .report_json(&rolling_stats(&self.state, self.wgpu_shared.as_ref())),
+        )
+    }
+}
 
-        if self.frame_index % 120 == 0 {
-            println!(
-                "[backend:{}] render frame surfaces={} live-layer-surfaces={} configured={} ready={} pending_callbacks={} uploaded_video_frames={} outputs=[{}]",
-                self.name(),
-                surfaces.len(),
-                self.state.layer_surfaces.len(),
-                configured,
-                ready,
-                pending_callbacks,
-                shared_uploaded_frames(self),
-                outputs
-            );
-        }
-        Ok(())
+/// Callback latency per output and frame arrival per decoder, for the
+/// profile dump.
+fn rolling_stats(state: &WaylandLayerState, shared: Option<&WgpuShared>) -> RollingStats {
+    RollingStats {
+        callbacks: state.callback_latencies(),
+        arrivals: shared
+            .map(|shared| {
+                shared
+                    .video_streams
+                    .iter()
+                    .filter_map(|(stream_id, stream)| {
+                        let stats = stream.frame_source.frame_arrival()?;
+                        Some((stream_name(&state.outputs, *stream_id, stream), stats))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
     }
 }
 
@@ -240,12 +762,49 @@ fn shared_uploaded_frames(backend: &WaylandLayerBackend) -> u64 {
         .unwrap_or(0)
 }
 
+fn shared_mipgen_stats(backend: &WaylandLayerBackend) -> String {
+    let Some(shared) = backend.wgpu_shared.as_ref() else {
+        return "mipgen=0".to_string();
+    };
+    let avg_us = shared
+        .mipgen_time
+        .as_micros()
+        .checked_div(shared.mipgen_runs as u128)
+        .unwrap_or(0);
+    format!(
+        "downscale={} mipgen={} mipgen_avg_us={}",
+        shared.program.downscale.as_str(),
+        shared.mipgen_runs,
+        avg_us
+    )
+}
+
 #[derive(Default)]
 struct WaylandLayerState {
     compositor: Option<wl_compositor::WlCompositor>,
     layer_shell: Option<ZwlrLayerShellV1>,
+    /// `xdg_wm_base`, for the windowed fallback when there is no layer-shell.
+    xdg_wm_base: Option<XdgWmBase>,
+    /// `wp_viewporter`, to stretch a buffer clamped to the GPU limit over its
+    /// output; optional.
+    viewporter: Option<WpViewporter>,
+    /// `wp_color_manager_v1`, to learn each output's colorimetry and tag
+    /// the surfaces converted to it; optional.
+    color_manager: Option<WpColorManagerV1>,
     outputs: BTreeMap<u32, OutputSlot>,
     layer_surfaces: Vec<LayerSurfaceSlot>,
+    allowlist: OutputAllowlist,
+    aliases: Aliases,
+    /// Render loop rate; outputs refreshing slower are paced to their own rate.
+    fps_ceiling: u32,
+    /// Set on a software adapter: every output is paced to at most this rate.
+    software_fps_cap: Option<u32>,
+    /// Outputs frozen by name (`pause --monitor`).
+    paused_outputs: Vec<String>,
+    /// Every output frozen by a global `freeze` pause.
+    frozen: bool,
+    /// `reduce-motion`, kept here until the video map exists.
+    reduce_motion: bool,
 }
 
 impl WaylandLayerState {
@@ -259,32 +818,75 @@ impl WaylandLayerState {
             .as_ref()
             .ok_or_else(|| "missing wl_compositor".to_string())?
             .clone();
-        let layer_shell = self
-            .layer_shell
-            .as_ref()
-            .ok_or_else(|| "missing zwlr_layer_shell_v1".to_string())?
-            .clone();
+        let layer_shell = self.layer_shell.clone();
+        let wm_base = self.xdg_wm_base.clone();
 
-        for output in self.outputs.values() {
+        // Outputs whose name is not known yet get a surface; the allowlist is
+        // applied once their properties are complete (wl_output.done).
+        for output in self.outputs.values().filter(|out| !out.excluded) {
             let surface = compositor.create_surface(qh, ());
-            let layer_surface = layer_shell.get_layer_surface(
-                &surface,
-                Some(&output.output),
-                zwlr_layer_shell_v1::Layer::Background,
-                "kitsune-rendercore".to_string(),
-                qh,
-                self.layer_surfaces.len() as u32,
-            );
-
-            layer_surface.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
-            // Wallpaper surfaces should not reserve layout space from the compositor.
-            layer_surface.set_exclusive_zone(0);
-            layer_surface.set_size(0, 0);
+            let role = match (&layer_shell, &wm_base) {
+                (Some(layer_shell), _) => {
+                    let layer_surface = layer_shell.get_layer_surface(
+                        &surface,
+                        Some(&output.output),
+                        zwlr_layer_shell_v1::Layer::Background,
+                        "kitsune-rendercore".to_string(),
+                        qh,
+                        output.global_name,
+                    );
+                    layer_surface
+                        .set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+                    // Wallpaper surfaces should not reserve layout space from the compositor.
+                    layer_surface.set_exclusive_zone(0);
+                    layer_surface.set_size(0, 0);
+                    // Nor take input: clicks pass through to the desktop and no focus
+                    // or pointer activity is generated, so the wallpaper can neither
+                    // steal clicks nor count as user activity for idle.
+                    layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+                    SurfaceRole::Layer(layer_surface)
+                }
+                (None, Some(wm_base)) => {
+                    // Windowed fallback: a fullscreen window on the output. It
+                    // sits among the other windows rather than below them.
+                    let xdg_surface = wm_base.get_xdg_surface(&surface, qh, output.global_name);
+                    let toplevel = xdg_surface.get_toplevel(qh, output.global_name);
+                    toplevel.set_title("Kitsune RenderCore".to_string());
+                    toplevel.set_app_id("kitsune-rendercore".to_string());
+                    toplevel.set_fullscreen(Some(&output.output));
+                    SurfaceRole::Window {
+                        xdg_surface,
+                        toplevel,
+                        size: (0, 0),
+                    }
+                }
+                (None, None) => {
+                    surface.destroy();
+                    return Err("missing zwlr_layer_shell_v1 and xdg_wm_base".to_string());
+                }
+            };
+            let region = compositor.create_region(qh, ());
+            surface.set_input_region(Some(&region));
+            region.destroy();
+            // Buffers are rendered in the output's logical (post-rotation) orientation,
+            // so the compositor must not rotate them again, until it asks for a
+            // preferred buffer transform.
+            surface.set_buffer_transform(wl_output::Transform::Normal);
             surface.commit();
+            let viewport = self
+                .viewporter
+                .as_ref()
+                .map(|viewporter| viewporter.get_viewport(&surface, qh, ()));
+            let color_surface = self
+                .color_manager
+                .as_ref()
+                .map(|manager| manager.get_surface(&surface, qh, ()));
 
             self.layer_surfaces.push(LayerSurfaceSlot {
                 surface,
-                layer_surface,
+                role,
+                viewport,
+                color_surface,
                 output_global_name: output.global_name,
                 configured: false,
                 configured_width: None,
@@ -292,30 +894,303 @@ impl WaylandLayerState {
                 needs_redraw: false,
                 frame_callback_pending: false,
                 frame_callback: None,
+                first_presented_at: None,
+                presented_frames: 0,
+                next_render_at: None,
+                preferred_scale: None,
+                preferred_transform: None,
+                must_redraw: false,
+                callback_latency: CallbackLatency::default(),
+                input_region: "empty",
             });
         }
 
         Ok(())
     }
 
-    fn ready_output_ids(&self) -> Vec<u32> {
+    /// Follows an output's image description through `wp_color_management_v1`,
+    /// when the compositor has it.
+    fn watch_output_color(&mut self, output_id: u32, qh: &QueueHandle<Self>) {
+        let (Some(manager), Some(out)) = (
+            self.color_manager.as_ref(),
+            self.outputs.get_mut(&output_id),
+        ) else {
+            return;
+        };
+        if out.color_output.is_none() {
+            out.color_output = Some(manager.get_output(&out.output, qh, output_id));
+        }
+        out.request_image_description(qh);
+    }
+
+    /// The output's surface draws again, e.g. with a new color pipeline,
+    /// even while frozen.
+    fn redraw_output(&mut self, output_id: u32) {
+        for slot in &mut self.layer_surfaces {
+            if slot.output_global_name == output_id {
+                slot.must_redraw = true;
+            }
+        }
+    }
+
+    /// An output was unplugged, or the allowlist rejected one that still has
+    /// a surface.
+    fn has_retired_outputs(&self) -> bool {
+        self.outputs.values().any(|out| out.gone)
+            || self.layer_surfaces.iter().any(|slot| {
+                self.outputs
+                    .get(&slot.output_global_name)
+                    .is_some_and(OutputSlot::retired)
+            })
+    }
+
+    /// Destroys the surfaces of outputs the allowlist rejected or the
+    /// compositor removed, and forgets the removed outputs. Callers must
+    /// release any wgpu surface built on them first (see
+    /// [`WgpuShared::release_outputs`]).
+    fn drop_retired_surfaces(&mut self) {
+        let outputs = &self.outputs;
+        self.layer_surfaces.retain(|slot| {
+            let retired = outputs
+                .get(&slot.output_global_name)
+                .is_some_and(OutputSlot::retired);
+            if retired {
+                slot.destroy();
+            }
+            !retired
+        });
+        let gone = self
+            .outputs
+            .values()
+            .filter(|out| out.gone)
+            .map(|out| out.global_name)
+            .collect::<Vec<_>>();
+        for output_id in gone {
+            if let Some(out) = self.outputs.remove(&output_id) {
+                println!("[rendercore] output {} removed", out.display_name());
+                out.release();
+            }
+        }
+    }
+
+    /// Outputs whose name or description changed since the last call; their
+    /// video is resolved again on the next frame.
+    fn take_remaps(&mut self) -> Vec<u32> {
+        self.outputs
+            .values_mut()
+            .filter_map(|out| {
+                std::mem::take(&mut out.identity.needs_remap).then_some(out.global_name)
+            })
+            .collect()
+    }
+
+    /// See [`update_collisions`]; removed outputs are left out.
+    fn update_name_collisions(&mut self) {
+        update_collisions(
+            self.outputs
+                .values_mut()
+                .filter(|out| !out.gone)
+                .map(|out| (out.global_name, &mut out.identity)),
+        );
+    }
+
+    fn slot_mut(&mut self, output_id: u32) -> Option<&mut LayerSurfaceSlot> {
+        self.layer_surfaces
+            .iter_mut()
+            .find(|slot| slot.output_global_name == output_id)
+    }
+
+    /// A layer surface or window was given its size (0 = the output's).
+    fn configure_slot(&mut self, output_id: u32, width: u32, height: u32) {
+        let Some(slot) = self.slot_mut(output_id) else {
+            return;
+        };
+        journal::record(
+            "configure",
+            &[
+                ("output_id", Value::U64(slot.output_global_name as u64)),
+                ("width", Value::U64(width as u64)),
+                ("height", Value::U64(height as u64)),
+            ],
+        );
+        slot.configured = true;
+        slot.configured_width = (width > 0).then_some(width);
+        slot.configured_height = (height > 0).then_some(height);
+        slot.needs_redraw = true;
+        slot.must_redraw = true;
+        if width > 0 && height > 0 {
+            slot.surface.commit();
+        }
+    }
+
+    fn close_slot(&mut self, output_id: u32) {
+        if let Some(slot) = self.slot_mut(output_id) {
+            slot.configured = false;
+            slot.configured_width = None;
+            slot.configured_height = None;
+            slot.needs_redraw = false;
+            slot.frame_callback_pending = false;
+            slot.frame_callback = None;
+            slot.callback_latency.forget_pending();
+        }
+    }
+
+    fn has_pending_redraw(&self) -> bool {
+        self.layer_surfaces
+            .iter()
+            .any(|slot| slot.configured && slot.needs_redraw)
+    }
+
+    /// Whether the output was frozen by name (`pause --monitor`) or by a
+    /// global `freeze` pause.
+    fn is_output_paused(&self, output_id: u32) -> bool {
+        self.frozen
+            || !self.paused_outputs.is_empty()
+                && self
+                    .outputs
+                    .get(&output_id)
+                    .is_some_and(|out| self.paused_outputs.contains(&out.display_name()))
+    }
+
+    /// Outputs due for a frame. `idle` ones (a still frame already on screen)
+    /// are skipped like paused ones unless they must redraw.
+    fn ready_output_ids(&self, now: Instant, idle: impl Fn(u32) -> bool) -> Vec<u32> {
         self.layer_surfaces
             .iter()
             .filter(|slot| slot.configured && slot.needs_redraw)
+            .filter(|slot| {
+                slot.must_redraw
+                    || !(self.is_output_paused(slot.output_global_name)
+                        || idle(slot.output_global_name))
+            })
+            .filter(|slot| {
+                slot.next_render_at
+                    .is_none_or(|at| at <= now + PACING_SLACK)
+            })
             .map(|slot| slot.output_global_name)
             .collect()
     }
 
-    fn mark_presented_and_request_frames(&mut self, qh: &QueueHandle<Self>, outputs: &[u32]) {
-        for (index, slot) in self.layer_surfaces.iter_mut().enumerate() {
-            if !outputs.iter().any(|id| *id == slot.output_global_name) {
+    /// The output's refresh interval, 60Hz until its mode is known.
+    fn refresh_interval(&self, output_id: u32) -> Duration {
+        let hz = self
+            .outputs
+            .get(&output_id)
+            .and_then(|out| out.refresh_hz)
+            .unwrap_or(60);
+        Duration::from_nanos(1_000_000_000 / hz.max(1) as u64)
+    }
+
+    /// Flags outputs whose requested frame callback is long overdue.
+    fn check_callback_stalls(&mut self, now: Instant) {
+        let refresh = self
+            .layer_surfaces
+            .iter()
+            .map(|slot| self.refresh_interval(slot.output_global_name))
+            .collect::<Vec<_>>();
+        let stalled = self
+            .layer_surfaces
+            .iter_mut()
+            .zip(refresh)
+            .filter_map(|(slot, refresh)| {
+                slot.callback_latency
+                    .check_stall(now, refresh)
+                    .then_some(slot.output_global_name)
+            })
+            .collect::<Vec<_>>();
+        for output_id in stalled {
+            self.log_callback_change(output_id, LatencyChange::Stalled);
+        }
+    }
+
+    fn log_callback_change(&self, output_id: u32, change: LatencyChange) {
+        let name = output_label(&self.outputs, output_id);
+        let stats = self
+            .layer_surfaces
+            .iter()
+            .find(|slot| slot.output_global_name == output_id)
+            .map(|slot| slot.callback_latency.stats())
+            .unwrap_or_default();
+        let ms = |d: Option<Duration>| d.map_or(0.0, |d| d.as_secs_f64() * 1e3);
+        match change {
+            LatencyChange::Slow => println!(
+                "[rendercore] warning: output={name} (id={output_id}) frame callbacks are late (p50={:.1}ms p99={:.1}ms, refresh {:.1}ms): the compositor is delaying frames",
+                ms(stats.p50),
+                ms(stats.p99),
+                self.refresh_interval(output_id).as_secs_f64() * 1e3
+            ),
+            LatencyChange::Recovered => println!(
+                "[rendercore] output={name} (id={output_id}) frame callbacks on time again (p50={:.1}ms p99={:.1}ms)",
+                ms(stats.p50),
+                ms(stats.p99)
+            ),
+            LatencyChange::Stalled => println!(
+                "[rendercore] output={name} (id={output_id}) frame callbacks stalled: the compositor sends none (output hidden or off?)"
+            ),
+            LatencyChange::Resumed(after) => println!(
+                "[rendercore] output={name} (id={output_id}) frame callbacks resumed after {:.1}s",
+                after.as_secs_f64()
+            ),
+        }
+    }
+
+    /// Frame callback latency per output, for the profile dump.
+    fn callback_latencies(&self) -> Vec<(String, CallbackStats)> {
+        self.layer_surfaces
+            .iter()
+            .map(|slot| {
+                let name = output_label(&self.outputs, slot.output_global_name);
+                (name, slot.callback_latency.stats())
+            })
+            .collect()
+    }
+
+    /// Frame interval of an output whose panel refreshes slower than the
+    /// render loop, or of any output when a software adapter caps the rate;
+    /// `None` when the loop rate is the limit.
+    fn pacing_interval(&self, output_id: u32) -> Option<Duration> {
+        let refresh = self.outputs.get(&output_id)?.refresh_hz;
+        let rate = [refresh, self.software_fps_cap]
+            .into_iter()
+            .flatten()
+            .min()?;
+        (self.fps_ceiling > 0 && rate < self.fps_ceiling)
+            .then(|| Duration::from_nanos(1_000_000_000 / rate as u64))
+    }
+
+    fn mark_presented_and_request_frames(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        outputs: &[u32],
+        now: Instant,
+    ) {
+        let intervals = outputs
+            .iter()
+            .map(|id| (*id, self.pacing_interval(*id)))
+            .collect::<Vec<_>>();
+        for slot in &mut self.layer_surfaces {
+            let Some((_, interval)) = intervals
+                .iter()
+                .find(|(id, _)| *id == slot.output_global_name)
+            else {
                 continue;
-            }
+            };
+            // Fixed cadence like the frame scheduler; restart it after falling
+            // a whole interval behind.
+            slot.next_render_at = interval.map(|interval| {
+                let base = slot
+                    .next_render_at
+                    .filter(|at| now < *at + interval)
+                    .unwrap_or(now);
+                base + interval
+            });
             slot.needs_redraw = false;
+            slot.must_redraw = false;
             if !slot.frame_callback_pending {
-                let cb = slot.surface.frame(qh, index as u32);
+                let cb = slot.surface.frame(qh, slot.output_global_name);
                 slot.frame_callback = Some(cb);
                 slot.frame_callback_pending = true;
+                slot.callback_latency.requested(now);
                 slot.surface.commit();
             }
         }
@@ -325,24 +1200,326 @@ impl WaylandLayerState {
 struct OutputSlot {
     global_name: u32,
     output: wl_output::WlOutput,
-    name: Option<String>,
+    identity: OutputIdentity,
+    /// Set once the allowlist rejects the output; it never gets (or loses) its
+    /// layer surface, GPU surface and decoder.
+    excluded: bool,
+    /// The compositor removed the global (unplugged); torn down like an
+    /// excluded output on the next frame, then forgotten.
+    gone: bool,
     width: Option<u32>,
     height: Option<u32>,
     refresh_hz: Option<u32>,
+    transform: OutputTransform,
+    /// Last mode rejected as implausible (0x0, 16384x16384), until a sane
+    /// one arrives; the previous good size stays in use meanwhile.
+    suspect_mode: Option<(i32, i32)>,
+    /// `wp_color_management_output_v1`, while the compositor has the protocol.
+    color_output: Option<WpColorManagementOutputV1>,
+    /// The output's image description, once ready, and the one asked for
+    /// after it changed.
+    image_description: Option<WpImageDescriptionV1>,
+    pending_description: Option<WpImageDescriptionV1>,
+    /// What the ready description's information said; filled in until its
+    /// `done`.
+    colorimetry: Option<OutputColorimetry>,
+    pending_colorimetry: OutputColorimetry,
 }
 
-struct LayerSurfaceSlot {
-    surface: WlSurface,
-    layer_surface: ZwlrLayerSurfaceV1,
-    output_global_name: u32,
-    configured: bool,
-    configured_width: Option<u32>,
-    configured_height: Option<u32>,
+/// The output's name for logs and status, or `wl-output-<id>` for an id
+/// without a slot (its output already forgotten).
+fn output_label(outputs: &BTreeMap<u32, OutputSlot>, output_id: u32) -> String {
+    outputs.get(&output_id).map_or_else(
+        || format!("wl-output-{output_id}"),
+        OutputSlot::display_name,
+    )
+}
+
+/// What tells an output apart in the video map: what the compositor calls
+/// it, and whether that changed since its stream was resolved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct OutputIdentity {
+    name: Option<String>,
+    description: Option<String>,
+    /// Another output reports the same name.
+    name_collision: bool,
+    /// Monitor aliases naming this output, sorted.
+    aliases: Vec<String>,
+    /// An alias took the output's name for another output, so map entries
+    /// under that name are not this output's.
+    name_shadowed: bool,
+    /// Name/description changed after its stream was resolved.
+    needs_remap: bool,
+}
+
+impl OutputIdentity {
+    fn display_name(&self, output_id: u32) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("wl-output-{output_id}"))
+    }
+
+    /// Key used to look the output up in the video map: its first alias the
+    /// map has, else the output name; for colliding names, the longest map key
+    /// found in the description.
+    fn map_key(&self, output_id: u32, map: &VideoMap) -> String {
+        if let Some(alias) = self.aliases.iter().find(|alias| map.contains_key(*alias)) {
+            return alias.clone();
+        }
+        if self.name_collision
+            && let Some(description) = &self.description
+        {
+            let description = description.to_lowercase();
+            if let Some(key) = map
+                .keys()
+                .filter(|key| description.contains(&key.to_lowercase()))
+                .max_by_key(|key| key.len())
+            {
+                return key.clone();
+            }
+        }
+        if self.name_shadowed {
+            // No map key looks like this, so the default applies.
+            return format!("wl-output-{output_id}");
+        }
+        self.display_name(output_id)
+    }
+
+    /// `wl_output.name`. A name that differs from the one the stream was
+    /// resolved with, including one arriving after the surface exists,
+    /// resolves the output again. Returns whether it is the first name.
+    fn set_name(&mut self, name: String) -> bool {
+        let first = self.name.is_none();
+        if self.name.as_deref() != Some(name.as_str()) {
+            self.needs_remap = true;
+        }
+        self.name = Some(name);
+        first
+    }
+
+    /// `wl_output.description`; only outputs told apart by it are resolved
+    /// again when it changes.
+    fn set_description(&mut self, description: String) {
+        if self.name_collision && self.description.as_deref() != Some(description.as_str()) {
+            self.needs_remap = true;
+        }
+        self.description = Some(description);
+    }
+
+    /// Recomputes which aliases name the output; it is re-resolved against
+    /// the map when they changed.
+    fn apply_aliases(&mut self, aliases: &Aliases) {
+        let matched = aliases.matching(self.name.as_deref(), self.description.as_deref());
+        let shadowed = self
+            .name
+            .as_deref()
+            .is_some_and(|name| aliases.is_alias(name) && !matched.iter().any(|a| a == name));
+        if matched != self.aliases || shadowed != self.name_shadowed {
+            self.aliases = matched;
+            self.name_shadowed = shadowed;
+            self.needs_remap = true;
+        }
+    }
+}
+
+/// Flags every output in `live` sharing its name with another (e.g. a laptop
+/// panel and an identical dock monitor) so the map lookup falls back to their
+/// descriptions. Outputs left out (removed ones) no longer count, so the one
+/// left behind goes back to its name. Outputs whose flag changed are resolved
+/// again.
+fn update_collisions<'a>(live: impl IntoIterator<Item = (u32, &'a mut OutputIdentity)>) {
+    let mut live = live.into_iter().collect::<Vec<_>>();
+    let groups = shared_names(
+        live.iter()
+            .filter_map(|(output_id, identity)| Some((*output_id, identity.name.as_deref()?))),
+    )
+    .into_iter()
+    .map(|(name, ids)| (name.to_string(), ids))
+    .collect::<Vec<_>>();
+    for (output_id, identity) in &mut live {
+        let collides = groups.iter().any(|(_, ids)| ids.contains(output_id));
+        if identity.name_collision && !collides {
+            println!(
+                "[rendercore] output {} no longer shares its name",
+                identity.display_name(*output_id)
+            );
+        }
+        if identity.name_collision != collides {
+            identity.name_collision = collides;
+            identity.needs_remap = true;
+        }
+    }
+    for (name, ids) in groups {
+        if !live
+            .iter()
+            .any(|(output_id, identity)| ids.contains(output_id) && identity.needs_remap)
+        {
+            continue;
+        }
+        eprintln!(
+            "[rendercore] warning: outputs {} share the name '{}'; map entries matching their description (e.g. serial) are used to tell them apart",
+            ids.iter()
+                .map(|id| format!("id={id}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            name
+        );
+    }
+}
+
+/// Names reported by more than one output, with the ids reporting each.
+fn shared_names<'a>(outputs: impl IntoIterator<Item = (u32, &'a str)>) -> Vec<(&'a str, Vec<u32>)> {
+    let mut by_name = BTreeMap::<&str, Vec<u32>>::new();
+    for (output_id, name) in outputs {
+        by_name.entry(name).or_default().push(output_id);
+    }
+    by_name
+        .into_iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .collect()
+}
+
+impl OutputSlot {
+    fn retired(&self) -> bool {
+        self.excluded || self.gone
+    }
+
+    /// Destroys the output's protocol objects; its surface must be gone.
+    fn release(self) {
+        for description in [self.image_description, self.pending_description]
+            .into_iter()
+            .flatten()
+        {
+            description.destroy();
+        }
+        if let Some(color_output) = self.color_output {
+            color_output.destroy();
+        }
+        if self.output.version() >= 3 {
+            self.output.release();
+        }
+    }
+
+    /// Asks for the output's current image description, dropping an earlier
+    /// request still unanswered.
+    fn request_image_description(&mut self, qh: &QueueHandle<WaylandLayerState>) {
+        let Some(color_output) = &self.color_output else {
+            return;
+        };
+        let description = color_output.get_image_description(qh, self.global_name);
+        if let Some(stale) = self.pending_description.replace(description) {
+            stale.destroy();
+        }
+    }
+
+    fn display_name(&self) -> String {
+        self.identity.display_name(self.global_name)
+    }
+
+    fn map_key(&self, map: &VideoMap) -> String {
+        self.identity.map_key(self.global_name, map)
+    }
+
+    /// Mode size in the orientation the user sees. wl_output modes are reported in
+    /// panel-native orientation, so 90/270 transforms swap the axes.
+    fn logical_size(&self) -> (u32, u32) {
+        let width = self.width.unwrap_or(1920).max(1);
+        let height = self.height.unwrap_or(1080).max(1);
+        if self.transform.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+struct LayerSurfaceSlot {
+    surface: WlSurface,
+    role: SurfaceRole,
+    /// Present when the compositor has `wp_viewporter`; only given a
+    /// destination while the buffer is clamped (see [`fit_surface`]).
+    viewport: Option<WpViewport>,
+    /// Present when the compositor has `wp_color_management_v1`; carries the
+    /// output's image description while the shader converts to it.
+    color_surface: Option<WpColorManagementSurfaceV1>,
+    output_global_name: u32,
+    configured: bool,
+    configured_width: Option<u32>,
+    configured_height: Option<u32>,
     needs_redraw: bool,
     frame_callback_pending: bool,
     frame_callback: Option<wl_callback::WlCallback>,
+    first_presented_at: Option<Instant>,
+    presented_frames: u64,
+    /// Set while the output is paced below the loop rate (see `pacing_interval`).
+    next_render_at: Option<Instant>,
+    /// From `wl_surface.preferred_buffer_scale`/`_transform` (wl_compositor
+    /// v6); `None` until the compositor sends them, and some never do.
+    preferred_scale: Option<i32>,
+    preferred_transform: Option<OutputTransform>,
+    /// The compositor needs a new buffer (configure, preferred scale or
+    /// transform), so a frozen output draws once more.
+    must_redraw: bool,
+    /// How long the compositor takes to answer this surface's frame callbacks.
+    callback_latency: CallbackLatency,
+    /// The input region set on the surface, for status; always `empty`.
+    input_region: &'static str,
+}
+
+/// What makes a slot's surface a wallpaper: a background layer surface, or
+/// with `KRC_ON_NO_LAYER_SHELL=windowed` a fullscreen xdg toplevel.
+enum SurfaceRole {
+    Layer(ZwlrLayerSurfaceV1),
+    Window {
+        xdg_surface: XdgSurface,
+        toplevel: XdgToplevel,
+        /// From the last `xdg_toplevel.configure`, applied on the
+        /// `xdg_surface.configure` that follows; 0 lets the client choose.
+        size: (u32, u32),
+    },
+}
+
+impl LayerSurfaceSlot {
+    /// Destroys the surface's protocol objects, its role before the
+    /// `wl_surface` itself. Any wgpu surface on it must be gone already.
+    fn destroy(&self) {
+        if let Some(viewport) = &self.viewport {
+            viewport.destroy();
+        }
+        if let Some(color_surface) = &self.color_surface {
+            color_surface.destroy();
+        }
+        self.role.destroy();
+        self.surface.destroy();
+    }
+}
+
+impl SurfaceRole {
+    fn protocol_id(&self) -> u32 {
+        match self {
+            Self::Layer(layer_surface) => layer_surface.id().protocol_id(),
+            Self::Window { toplevel, .. } => toplevel.id().protocol_id(),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Self::Layer(layer_surface) => layer_surface.destroy(),
+            Self::Window {
+                xdg_surface,
+                toplevel,
+                ..
+            } => {
+                toplevel.destroy();
+                xdg_surface.destroy();
+            }
+        }
+    }
 }
 
+/// Early tolerance for a paced output's deadline, for loop wakeup jitter.
+const PACING_SLACK: Duration = Duration::from_millis(1);
+
 struct WgpuShared {
     _instance: wgpu::Instance,
     _adapter: wgpu::Adapter,
@@ -350,111 +1527,1443 @@ struct WgpuShared {
     queue: wgpu::Queue,
     render_surfaces: Vec<RenderSurface>,
     program: RenderProgram,
-    started_at: Instant,
+    /// Time since start, and the `utc` clock for outputs that follow it.
+    render_clock: RenderClock,
+    /// `KRC_AMBIENT_SEED`, mixed into each output's ambient drift phase.
+    ambient_seed: u64,
+    /// Every output's own stream, plus one per filled cell of a `layout=`
+    /// entry under [`cell_stream_id`].
     video_streams: BTreeMap<u32, VideoStream>,
+    /// Outputs showing a `layout=` entry; their own stream stays empty and
+    /// the cells are drawn instead.
+    layouts: BTreeMap<u32, Layout>,
     video_map_state: VideoMapState,
     uploaded_video_frames: u64,
+    /// Mip chains regenerated and the CPU time spent encoding them.
+    mipgen_runs: u64,
+    mipgen_time: Duration,
+    /// `KRC_PROFILE` GPU time of the particle overlays.
+    overlay_timer: Option<OverlayTimer>,
+    text_program: TextProgram,
+    /// `[widgets.clock]`, re-read when config.toml changes.
+    clock: Clock,
+    config_file: Option<PathBuf>,
+    config_mtime: Option<SystemTime>,
+    /// Source size from `KRC_QUALITY` / `KRC_SOURCE_WIDTH/HEIGHT`, GPU-clamped;
+    /// entries with their own `quality=` use `entry_base_size` instead.
+    base_source_size: (u32, u32),
+    max_texture_dimension: u32,
+    auto_quality: AutoQuality,
+    /// `KRC_SHARE_DECODERS`: outputs playing the same frames use one decoder.
+    share_decoders: bool,
+    /// `KRC_FADE_IN_MS`; zero shows the first frame at full brightness.
+    fade_in: Duration,
+    /// `KRC_DAMAGE_TRACKING`: present only outputs whose pixels changed and
+    /// tell the compositor which parts did.
+    damage_tracking: bool,
+    gpu: GpuAdapterStatus,
+    /// Whether the configured streams fit this machine; re-estimated when
+    /// the streams (or their hwaccel verdicts) change.
+    headroom: Option<Estimate>,
+    headroom_streams: Vec<StreamLoad>,
+    next_headroom_check: Instant,
+}
+
+/// How often the decode load is compared with the last estimate.
+const HEADROOM_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Layout cell streams sit above every `wl_output` global name: cell `n` of
+/// output `id` is `CELL_ID_STRIDE * (n + 1) + id`.
+const CELL_ID_STRIDE: u32 = 1 << 24;
+
+fn cell_stream_id(output_id: u32, cell: usize) -> u32 {
+    CELL_ID_STRIDE * (cell as u32 + 1) + output_id
+}
+
+/// The output a stream draws on: itself, or a cell's output.
+fn owner_output(stream_id: u32) -> u32 {
+    stream_id % CELL_ID_STRIDE
+}
+
+/// The cell a stream plays, `None` for an output's own stream.
+fn cell_index(stream_id: u32) -> Option<usize> {
+    (stream_id >= CELL_ID_STRIDE).then(|| (stream_id / CELL_ID_STRIDE - 1) as usize)
+}
+
+/// Display or buffer pixels `(x0, y0, x1, y1)`.
+type Rect = (u32, u32, u32, u32);
+
+/// Cell `index` of `layout` over a `display` area, as display pixels
+/// `(x0, y0, x1, y1)`. Edges are shared, so the cells tile the area exactly.
+fn cell_rect(layout: &Layout, index: usize, (width, height): (u32, u32)) -> Rect {
+    let (columns, rows) = (layout.columns, layout.rows);
+    let (column, row) = (index as u32 % columns, index as u32 / columns);
+    (
+        column * width / columns,
+        row * height / rows,
+        (column + 1) * width / columns,
+        (row + 1) * height / rows,
+    )
+}
+
+/// A display-pixel rectangle in the buffer, which is stored with
+/// `transform` applied: the inverse of the shader's `display_uv`.
+fn buffer_rect(
+    transform: OutputTransform,
+    (width, height): (u32, u32),
+    (x0, y0, x1, y1): Rect,
+) -> Rect {
+    match transform.wire_value() {
+        1 => (y0, width - x1, y1, width - x0),
+        2 => (width - x1, height - y1, width - x0, height - y0),
+        3 => (height - y1, x0, height - y0, x1),
+        4 => (width - x1, y0, width - x0, y1),
+        5 => (y0, x0, y1, x1),
+        6 => (x0, height - y1, x1, height - y0),
+        7 => (height - y1, width - x1, height - y0, width - x0),
+        _ => (x0, y0, x1, y1),
+    }
+}
+
+/// How a cell shows up in logs, status and reload outcomes; 1-based.
+fn cell_label(output_name: &str, cell: usize) -> String {
+    format!("{output_name} cell {}", cell + 1)
+}
+
+fn log_layout(output_id: u32, output_name: &str, layout: &Layout) {
+    let filled = layout.cells.iter().flatten().count();
+    println!(
+        "[rendercore] output={output_name} (id={output_id}) layout {}: {filled} of {} cells filled, the rest black",
+        layout.grid(),
+        layout.cell_count()
+    );
+}
+
+/// A cell's share of a landscape source size: one grid step, even.
+fn cell_source_size((width, height): (u32, u32), layout: &Layout) -> (u32, u32) {
+    (
+        ((width / layout.columns) & !1).max(2),
+        ((height / layout.rows) & !1).max(2),
+    )
 }
 
 struct RenderSurface {
     output_global_name: u32,
+    /// Size in the orientation the user sees, in buffer pixels.
     width: u32,
     height: u32,
+    /// The buffer is below the output's resolution because the output is
+    /// larger than the GPU's texture limit.
+    clamped: bool,
+    /// `wl_surface.preferred_buffer_scale`/`_transform` the buffers follow;
+    /// 1 and normal when the compositor sends neither.
+    buffer_scale: i32,
+    buffer_transform: OutputTransform,
     surface: wgpu::Surface<'static>,
     config: wgpu::SurfaceConfiguration,
+    /// The next present damages the whole buffer: the surface is new or was
+    /// reconfigured.
+    full_damage: bool,
+    /// Streams and display rects of the last present; a different set (a
+    /// layout or its cells changed) damages the whole buffer.
+    drawn: Vec<(u32, Option<Rect>)>,
+    damage: DamageTally,
+    /// The conversion the shader applies for `gamut=`; `None` until the
+    /// first frame resolves it.
+    color: Option<ColorPipeline>,
+    /// The image description the surface is tagged with.
+    tagged_with: Option<WpImageDescriptionV1>,
+    /// The monitor's EDID chromaticities, read once `gamut=native` asks.
+    edid: Option<Option<Primaries>>,
+    /// The clock widget; kept while it is shown or still on screen.
+    clock: Option<TextLayer>,
+}
+
+/// Presents of one output and the share of its buffer they damaged.
+#[derive(Default)]
+struct DamageTally {
+    presents: u64,
+    skipped: u64,
+    damaged: f64,
+}
+
+impl DamageTally {
+    fn record(&mut self, rects: &[Rect], (width, height): (u32, u32)) {
+        let area = rects
+            .iter()
+            .map(|(x0, y0, x1, y1)| (x1 - x0) as f64 * (y1 - y0) as f64)
+            .sum::<f64>();
+        self.presents += 1;
+        self.damaged += (area / (width.max(1) as f64 * height.max(1) as f64)).min(1.0);
+    }
+
+    fn stats(&self) -> DamageStats {
+        DamageStats {
+            presents: self.presents,
+            skipped: self.skipped,
+            avg_percent: if self.presents == 0 {
+                0.0
+            } else {
+                self.damaged * 100.0 / self.presents as f64
+            },
+        }
+    }
+}
+
+/// What a stream looked like in its output's last present: its uniforms and
+/// the decoded frame it sampled. Drawing it the same way changes no pixels.
+#[derive(Clone, Copy, PartialEq)]
+struct DrawnFrame {
+    decoder: u32,
+    texture_serial: u64,
+    uploads: u64,
+    uniform: FrameUniform,
+}
+
+/// One output's draw this frame, worked out before its texture is acquired.
+struct OutputFrame {
+    output_id: u32,
+    display_size: (u32, u32),
+    buffer_transform: OutputTransform,
+    draws: Vec<(u32, Option<Rect>)>,
+    /// Buffer rects this present changes; never empty.
+    damage: Vec<Rect>,
+    frames: Vec<(u32, DrawnFrame)>,
+    /// The clock's text and display rect, when this present draws it.
+    clock: Option<(String, Rect)>,
 }
 
 struct RenderProgram {
+    /// Frame pipelines per surface format, built on first use: some drivers
+    /// give one output an sRGB format and another a linear one.
+    pipelines: Vec<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline_cache: Option<PipelineCacheFile>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// For `filter=nearest` entries; streams bind one of the two.
+    nearest_sampler: wgpu::Sampler,
+    downscale: DownscaleMode,
+    mip_blit: MipBlit,
+    /// The fragment shader reads `time_sec` (`KRC_WAVE_EFFECT`), so every
+    /// frame changes.
+    animated: bool,
+    /// `overlay=` particles, drawn over a stream with its bind group.
+    overlay_shader: wgpu::ShaderModule,
+    /// Built the first time an output with an overlay is drawn in a format.
+    overlay_pipelines: Vec<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+}
+
+impl RenderProgram {
+    fn sampler(&self, filter: SampleFilter) -> &wgpu::Sampler {
+        match filter {
+            SampleFilter::Linear => &self.sampler,
+            SampleFilter::Nearest => &self.nearest_sampler,
+        }
+    }
+
+    fn pipeline(&self, format: wgpu::TextureFormat) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines
+            .iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, pipeline)| pipeline)
+    }
+
+    fn overlay_pipeline(&self, format: wgpu::TextureFormat) -> Option<&wgpu::RenderPipeline> {
+        self.overlay_pipelines
+            .iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, pipeline)| pipeline)
+    }
+
+    fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        overlay: bool,
+    ) -> wgpu::RenderPipeline {
+        let (label, module, vs, fs, blend) = if overlay {
+            (
+                "kitsune-rendercore-overlay-pipeline",
+                &self.overlay_shader,
+                "vs_overlay",
+                "fs_overlay",
+                wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            )
+        } else {
+            (
+                "kitsune-rendercore-frame-pipeline",
+                &self.shader,
+                "vs_main",
+                "fs_main",
+                wgpu::BlendState::REPLACE,
+            )
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: Some(vs),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: Some(fs),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: self.pipeline_cache.as_ref().map(|c| &c.cache),
+        })
+    }
+
+    fn ensure_overlay_pipeline(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        if self.overlay_pipeline(format).is_some() {
+            return;
+        }
+        let pipeline = self.build_pipeline(device, format, true);
+        self.overlay_pipelines.push((format, pipeline));
+        println!("[rendercore] overlay pipeline: {format:?}");
+        if let Some(cache) = &self.pipeline_cache {
+            cache.save();
+        }
+    }
+
+    fn ensure_pipeline(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        if self.pipeline(format).is_some() {
+            return;
+        }
+        let pipeline = self.build_pipeline(device, format, false);
+        self.pipelines.push((format, pipeline));
+        println!(
+            "[rendercore] frame pipelines: {}",
+            self.pipelines
+                .iter()
+                .map(|(f, _)| format!("{f:?}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if let Some(cache) = &self.pipeline_cache {
+            cache.save();
+        }
+    }
+}
+
+/// Driver pipeline cache (`wgpu::Features::PIPELINE_CACHE`, Vulkan) kept in
+/// the cache dir between runs, so startup skips most shader compilation.
+struct PipelineCacheFile {
+    cache: wgpu::PipelineCache,
+    path: PathBuf,
+}
+
+impl PipelineCacheFile {
+    fn open(device: &wgpu::Device, adapter: &wgpu::Adapter) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return None;
+        }
+        let key = wgpu::util::pipeline_cache_key(&adapter.get_info())?;
+        let path = cache_dir().ok()?.join(key);
+        let data = std::fs::read(&path).ok();
+        // SAFETY: the data was written by `save` from `get_data` for the same
+        // cache key (adapter and driver); wgpu validates it and falls back to
+        // an empty cache when it does not fit.
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("kitsune-rendercore-pipeline-cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Some(Self { cache, path })
+    }
+
+    fn save(&self) {
+        let Some(data) = self.cache.get_data() else {
+            return;
+        };
+        if let Err(err) = file_access::write_atomic(&self.path, &data, "pipeline cache") {
+            eprintln!("[rendercore] failed to save the pipeline cache: {err}");
+        }
+    }
+}
+
+/// How an oversized source is filtered down to the output (`KRC_DOWNSCALE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DownscaleMode {
+    /// Mipmaps whenever the source is larger than the output.
+    Auto,
+    /// Same as auto; kept separate so logs show it was forced.
+    Mipmap,
+    /// 2x2 box filter in the fragment shader above 1.5x, no mipmaps.
+    Simple,
+    /// Single bilinear tap (the old behavior).
+    Off,
+}
+
+impl DownscaleMode {
+    fn from_env() -> Self {
+        match std::env::var("KRC_DOWNSCALE")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "" | "auto" => Self::Auto,
+            "mipmap" | "mipmaps" => Self::Mipmap,
+            "simple" | "box" => Self::Simple,
+            "off" | "none" => Self::Off,
+            other => {
+                eprintln!("[rendercore] KRC_DOWNSCALE='{other}' invalid, using auto");
+                Self::Auto
+            }
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Mipmap => "mipmap",
+            Self::Simple => "simple",
+            Self::Off => "off",
+        }
+    }
+
+    fn uses_mipmaps(self) -> bool {
+        matches!(self, Self::Auto | Self::Mipmap)
+    }
+}
+
+/// Render-based downsample used to build source mip chains (wgpu has no
+/// automatic mip generation). Sampling and writing the sRGB texture averages in
+/// linear light, so downscaled detail keeps its brightness.
+struct MipBlit {
     pipeline: wgpu::RenderPipeline,
     bind_group_layout: wgpu::BindGroupLayout,
     sampler: wgpu::Sampler,
-    uniform_buffer: wgpu::Buffer,
+}
+
+/// Per-stream mip targets: `bind_groups[i]` samples level `i`, `views[i]` is level `i + 1`.
+struct MipChain {
+    views: Vec<wgpu::TextureView>,
+    bind_groups: Vec<wgpu::BindGroup>,
 }
 
 struct VideoStream {
+    /// Output name the stream was last resolved for; events carry it.
+    monitor: String,
     bind_group: wgpu::BindGroup,
+    /// Sampler `bind_group` was built with; rebound when the entry's
+    /// `filter=` changes.
+    filter: SampleFilter,
     source_texture: wgpu::Texture,
+    /// Tells a rebuilt `source_texture` apart from the one a follower is bound to.
+    texture_serial: u64,
+    /// Set while this stream shows another output's texture instead of running
+    /// its own decoder.
+    shared_from: Option<SharedTexture>,
     source_width: u32,
     source_height: u32,
     frame_source: FrameSource,
     frame_pixels: Vec<u8>,
-    current_video: Option<String>,
+    /// Per-stream so every output's pass sees its own aspect and rotate/flip.
+    uniform_buffer: wgpu::Buffer,
+    /// Frames written to `source_texture`; followers compare their leader's.
+    uploads: u64,
+    /// How the stream was drawn in its output's last present; `None` after
+    /// a rebind, so the next one damages it.
+    drawn_as: Option<DrawnFrame>,
+    /// Present only when the source is larger than its output.
+    mip_chain: Option<MipChain>,
+    mips_dirty: bool,
+    current_entry: Option<VideoMapEntry>,
+    last_error: Option<(Instant, String)>,
+    /// Times `last_error` recurred verbatim right after itself; most are not
+    /// logged (see `log_limit`), so status shows the count instead.
+    last_error_repeats: u64,
+    /// Failed decodes/presents since the last good frame.
+    consecutive_failures: u32,
+    /// `KRC_AUTO_QUALITY` level the texture and decoder were built for.
+    quality_level: u8,
+    /// Identity of the video file the decoder opened, to notice in-place replacements.
+    video_file: Option<VideoFileId>,
+    /// A new identity seen once; the decoder restarts when the next check sees
+    /// it unchanged, so a file still being copied is not opened half-written.
+    pending_video_file: Option<VideoFileId>,
     decode_interval: Duration,
     next_decode_at: Instant,
+    /// Decoder for the entry being switched to. `frame_source` keeps playing
+    /// the old video until this one has decoded its first frame.
+    incoming: Option<IncomingSource>,
+    /// Set while the entry's video path is gone or not answering.
+    missing: Option<MissingVideo>,
+    /// Set while the entry's decoder cannot start because ffmpeg is not
+    /// found.
+    awaiting_ffmpeg: Option<AwaitingFfmpeg>,
+    /// Set when a decoder opens for the entry; cleared once a present shows
+    /// its first frame, which publishes `stream_presented`.
+    awaiting_present: bool,
+    /// Render clock at which the output was frozen (`pause --monitor`): no
+    /// decoding, and effects keep this time so a redraw shows the same frame.
+    frozen_at: Option<Duration>,
+    /// `KRC_FLASH_GUARD`: smooths or holds frames that flash.
+    flash_guard: Option<FlashGuard>,
+    /// `KRC_START_DELAY_MS`: when the decoder may open. The output stays
+    /// black until then.
+    start_at: Option<Instant>,
+    /// `KRC_FADE_IN_MS` progress from 0 to 1; `None` until the first frame
+    /// is up.
+    fade: Option<f32>,
+    /// When `fade` last advanced; gaps in drawing count for at most
+    /// [`FADE_MAX_STEP`], so a pause holds the fade where it was.
+    fade_step_at: Instant,
+    /// Where the first decoder starts, restored from before a restart;
+    /// later opens start at the beginning.
+    resume_ms: Option<u64>,
+    /// When a decoder following the `utc` clock may next be reopened for
+    /// being off it; once per [`CLOCK_RESEEK_INTERVAL`] at most.
+    next_reseek_at: Instant,
 }
 
-struct VideoMapState {
-    map_file: PathBuf,
-    default_video: Option<String>,
-    env_map: BTreeMap<String, String>,
-    merged_map: BTreeMap<String, String>,
-    last_mtime: Option<SystemTime>,
-    last_reload_check: Instant,
-    reload_interval: Duration,
-}
+/// Least time between two reopens of a decoder that keeps falling behind the
+/// `utc` clock (too slow for the video), so it plays late instead of
+/// reopening without end.
+const CLOCK_RESEEK_INTERVAL: Duration = Duration::from_secs(10);
 
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-struct FrameUniform {
-    time_sec: f32,
-    aspect: f32,
-    _pad: [f32; 2],
+/// Longest gap between two draws a fade-in advances by.
+const FADE_MAX_STEP: Duration = Duration::from_millis(100);
+
+/// What a reload has to do for an output whose entry changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryChange {
+    Unchanged,
+    /// Only shader-side options (flip, effect, overlay, a rotation that keeps the
+    /// decode shape): the next frame's uniforms pick them up.
+    Uniforms,
+    /// The source texture changes size (`quality=`, a rotation between
+    /// landscape and portrait decode): the stream is rebuilt, decoder included.
+    Rebuild,
+    /// Another video, trim window or timebase, or an entry added or removed.
+    Restart,
 }
 
-const FRAME_SHADER_WGSL_PLAIN: &str = r#"
-struct VsOut {
-    @builtin(position) pos: vec4<f32>,
-    @location(0) uv: vec2<f32>,
-};
+impl EntryChange {
+    /// `base_size` is the unrotated source size an entry asks for.
+    fn classify(
+        old: Option<&VideoMapEntry>,
+        new: Option<&VideoMapEntry>,
+        base_size: impl Fn(&VideoMapEntry) -> (u32, u32),
+    ) -> Self {
+        let (old, new) = match (old, new) {
+            (None, None) => return Self::Unchanged,
+            (Some(old), Some(new)) => (old, new),
+            _ => return Self::Restart,
+        };
+        // A still output is not redrawn on its own, so any change to one
+        // re-extracts its frame instead of waiting for a uniform update.
+        if old.video != new.video
+            || old.options.trim != new.options.trim
+            || old.options.timebase != new.options.timebase
+            || old.options.still != new.options.still
+            || (new.options.still.is_some() && old.options != new.options)
+        {
+            Self::Restart
+        } else if base_size(old) != base_size(new)
+            || old.options.rotate.swaps_axes() != new.options.rotate.swaps_axes()
+        {
+            Self::Rebuild
+        } else if old.options != new.options {
+            Self::Uniforms
+        } else {
+            Self::Unchanged
+        }
+    }
 
-struct FrameUniform {
-    time_sec: f32,
-    aspect: f32,
-    _pad0: f32,
-    _pad1: f32,
-};
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Unchanged => "unchanged",
+            Self::Uniforms => "uniforms only (render-side options)",
+            Self::Rebuild => "stream rebuild (source size changes)",
+            Self::Restart => "decoder restart (video, trim, still, timebase or mapping changed)",
+        }
+    }
+}
 
-@group(0) @binding(0) var src_tex: texture_2d<f32>;
-@group(0) @binding(1) var src_sampler: sampler;
-@group(0) @binding(2) var<uniform> uniforms: FrameUniform;
+/// A `crop=` region as `(x, y, w, h)` pixels of the video, and the video's size.
+type AppliedCrop = ((u32, u32, u32, u32), (u32, u32));
 
-@vertex
-fn vs_main(@builtin(vertex_index) vid: u32) -> VsOut {
-    var out: VsOut;
-    var pos = array<vec2<f32>, 3>(
-        vec2<f32>(-1.0, -3.0),
-        vec2<f32>(-1.0,  1.0),
-        vec2<f32>( 3.0,  1.0)
-    );
-    let p = pos[vid];
-    out.pos = vec4<f32>(p, 0.0, 1.0);
-    out.uv = 0.5 * (p + vec2<f32>(1.0, 1.0));
-    return out;
+/// The texture of the stream whose decoder a follower borrows.
+struct SharedTexture {
+    leader: u32,
+    leader_monitor: String,
+    serial: u64,
+    texture: wgpu::Texture,
 }
 
-@fragment
-fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
-    let base_uv = vec2<f32>(in.uv.x, 1.0 - in.uv.y);
-    let uv = fract(base_uv);
-    let _unused_time = uniforms.time_sec;
-    let _unused_aspect = uniforms.aspect;
-    let col = textureSample(src_tex, src_sampler, uv).rgb;
-    return vec4<f32>(col, 1.0);
+/// What decides the frames a decoder produces. Render-side options (rotate,
+/// flip, effect) live in each output's uniform, so streams that differ only in
+/// those show the same texture; a rotation that changes the decode shape shows
+/// up in `source_size`.
+#[derive(Debug, Clone, PartialEq)]
+struct DecoderKey {
+    video: String,
+    trim: Option<Trim>,
+    still: Option<StillFrame>,
+    source_size: (u32, u32),
+    mip_levels: u32,
+    decode_interval: Duration,
+    timebase: Timebase,
 }
-"#;
 
-const FRAME_SHADER_WGSL_WAVE: &str = r#"
-struct VsOut {
-    @builtin(position) pos: vec4<f32>,
-    @location(0) uv: vec2<f32>,
-};
+impl DecoderKey {
+    /// The key of a stream playing `entry` into a texture of `source_size`
+    /// with `mip_levels`, decoding a frame every `decode_interval`.
+    fn new(
+        entry: &VideoMapEntry,
+        source_size: (u32, u32),
+        mip_levels: u32,
+        decode_interval: Duration,
+    ) -> Self {
+        Self {
+            video: entry.video.clone(),
+            trim: entry.options.trim,
+            still: entry.options.still,
+            source_size,
+            mip_levels,
+            decode_interval,
+            timebase: Timebase::resolve(entry.options.timebase),
+        }
+    }
+}
 
-struct FrameUniform {
-    time_sec: f32,
-    aspect: f32,
-    _pad0: f32,
-    _pad1: f32,
-};
+/// Maps each stream that can show another's texture to the stream that decodes
+/// it: the lowest output id with an equal key. Streams without a key (no video,
+/// or a switch warming up) decode on their own.
+fn decoder_leaders(keys: &[(u32, Option<DecoderKey>)]) -> BTreeMap<u32, u32> {
+    let mut leaders = BTreeMap::new();
+    for (idx, (output_id, key)) in keys.iter().enumerate() {
+        let Some(key) = key else {
+            continue;
+        };
+        let leader = keys[..idx]
+            .iter()
+            .find(|(_, other)| other.as_ref() == Some(key))
+            .map(|(leader, _)| *leader);
+        if let Some(leader) = leader {
+            leaders.insert(*output_id, leader);
+        }
+    }
+    leaders
+}
+
+static NEXT_TEXTURE_SERIAL: AtomicU64 = AtomicU64::new(1);
+
+/// A switch in progress: the warming decoder and what to report once it
+/// takes over.
+struct IncomingSource {
+    warm: WarmSource,
+    /// The entry still playing in `frame_source`.
+    previous: Option<VideoMapEntry>,
+    reason: String,
+    options: VideoOptions,
+}
+
+/// A video that is temporarily missing (its path does not exist, or a network
+/// share stopped answering): the stream shows its fallback and reopens the
+/// entry on its own, backing off between attempts.
+struct MissingVideo {
+    options: VideoOptions,
+    attempts: u32,
+    retry_at: Instant,
+}
+
+/// First wait before reopening a missing video; doubles per attempt up to
+/// [`MISSING_RETRY_MAX`].
+const MISSING_RETRY_MIN: Duration = Duration::from_secs(2);
+const MISSING_RETRY_MAX: Duration = Duration::from_secs(30);
+
+/// A decoder that could not start because ffmpeg was not found. ffmpeg is
+/// looked for again every [`FFMPEG_PROBE_INTERVAL`], so installing it, or
+/// pointing `KRC_FFMPEG` or `[tools] ffmpeg` at it, needs no restart.
+struct AwaitingFfmpeg {
+    options: VideoOptions,
+    probe_at: Instant,
+}
+
+const FFMPEG_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+impl AwaitingFfmpeg {
+    fn new(options: VideoOptions, now: Instant) -> Self {
+        Self {
+            options,
+            probe_at: now + FFMPEG_PROBE_INTERVAL,
+        }
+    }
+
+    /// Runs `locate` once the probe is due: where ffmpeg is now, or `None`
+    /// with the next probe scheduled.
+    fn probe(
+        &mut self,
+        now: Instant,
+        locate: impl FnOnce() -> Option<(PathBuf, &'static str)>,
+    ) -> Option<(PathBuf, &'static str)> {
+        if now < self.probe_at {
+            return None;
+        }
+        let found = locate();
+        if found.is_none() {
+            self.probe_at = now + FFMPEG_PROBE_INTERVAL;
+        }
+        found
+    }
+}
+
+fn missing_retry_delay(attempts: u32) -> Duration {
+    MISSING_RETRY_MIN
+        .saturating_mul(1 << attempts.saturating_sub(1).min(8))
+        .min(MISSING_RETRY_MAX)
+}
+
+impl VideoStream {
+    /// `None` while there is no video, a switch is warming up, or the output
+    /// is frozen.
+    fn decoder_key(&self) -> Option<DecoderKey> {
+        let entry = self
+            .current_entry
+            .as_ref()
+            .filter(|_| self.incoming.is_none() && self.frozen_at.is_none())?;
+        Some(DecoderKey::new(
+            entry,
+            (self.source_width, self.source_height),
+            self.source_texture.mip_level_count(),
+            self.decode_interval,
+        ))
+    }
+
+    /// What the stream's effects and playback follow: its entry's
+    /// `timebase=`, else `KRC_TIMEBASE`.
+    fn timebase(&self) -> Timebase {
+        Timebase::resolve(
+            self.current_entry
+                .as_ref()
+                .and_then(|entry| entry.options.timebase),
+        )
+    }
+
+    /// The video's size, once the texture holds the whole frame (its shape
+    /// matches the video's).
+    fn whole_frame(&self) -> Option<(u32, u32)> {
+        let entry = self.current_entry.as_ref()?;
+        let native = frame_source::native_size(&entry.video)?;
+        let texture_aspect = self.source_width as f64 / self.source_height.max(1) as f64;
+        let native_aspect = native.0 as f64 / native.1 as f64;
+        if (texture_aspect / native_aspect - 1.0).abs() > 0.01 {
+            return None;
+        }
+        Some(native)
+    }
+
+    /// The entry's crop in pixels of the video, with the video's size, once
+    /// the texture holds the whole frame.
+    fn crop_rect(&self) -> Option<AppliedCrop> {
+        let crop = self.current_entry.as_ref()?.options.crop?;
+        let native = self.whole_frame()?;
+        Some((crop.pixels(native)?, native))
+    }
+
+    /// What a `fit=integer` entry scales: its crop, else the whole frame.
+    fn integer_region(&self) -> Option<AppliedCrop> {
+        let entry = self.current_entry.as_ref()?;
+        if entry.options.fit != Fit::Integer {
+            return None;
+        }
+        match entry.options.crop {
+            Some(_) => self.crop_rect(),
+            None => {
+                let native = self.whole_frame()?;
+                Some(((0, 0, native.0, native.1), native))
+            }
+        }
+    }
+
+    fn record_error(&mut self, output_id: u32, err: impl Into<String>) {
+        let err = err.into();
+        log_limit::eprint_limited(&format!("output id={output_id}"), &err, || {
+            format!("[rendercore] output id={output_id}: {err}")
+        });
+        self.last_error_repeats = match &self.last_error {
+            Some((_, last)) if *last == err => self.last_error_repeats + 1,
+            _ => 0,
+        };
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.consecutive_failures.is_power_of_two() {
+            events::publish(|| RenderEvent::Degraded {
+                monitor: self.monitor.clone(),
+                reason: err.clone(),
+                consecutive_failures: self.consecutive_failures,
+            });
+        }
+        self.last_error = Some((Instant::now(), err));
+    }
+
+    /// A frame was decoded: ends a run of failures.
+    fn record_frame(&mut self) {
+        if self.consecutive_failures == 0 {
+            return;
+        }
+        events::publish(|| RenderEvent::Recovered {
+            monitor: self.monitor.clone(),
+            failures: self.consecutive_failures,
+        });
+        self.consecutive_failures = 0;
+    }
+
+    /// Runs a decoded frame through `KRC_FLASH_GUARD`; false holds the frame
+    /// on screen instead of uploading it.
+    fn guard_flashes(&mut self, output_id: u32, now: Instant) -> bool {
+        let Some(guard) = self
+            .flash_guard
+            .as_mut()
+            .filter(|_| self.frame_source.is_video())
+        else {
+            return true;
+        };
+        let (show, change) = guard.inspect(
+            now,
+            &mut self.frame_pixels,
+            self.source_width,
+            self.source_height,
+        );
+        if let Some(state) = change {
+            let transitions = guard.recent_transitions();
+            match state {
+                GuardState::Frozen => println!(
+                    "[rendercore] warning: output={} (id={}) flashes {} times a second, holding the frame",
+                    self.monitor,
+                    output_id,
+                    transitions / 2
+                ),
+                GuardState::Smoothing => println!(
+                    "[rendercore] output={} (id={}) luminance swings {} times a second, smoothing frames",
+                    self.monitor, output_id, transitions
+                ),
+                GuardState::Clear => println!(
+                    "[rendercore] output={} (id={}) flash guard clear",
+                    self.monitor, output_id
+                ),
+            }
+            journal::record(
+                "flash_guard",
+                &[
+                    ("output_id", Value::U64(output_id as u64)),
+                    ("state", Value::Str(state.as_str())),
+                    ("transitions", Value::U64(transitions as u64)),
+                ],
+            );
+        }
+        show
+    }
+
+    /// Publishes `stream_started` when a decoder is open for the current entry.
+    fn record_started(&self, options: VideoOptions) {
+        let Some(entry) = self
+            .current_entry
+            .as_ref()
+            .filter(|_| self.frame_source.is_video())
+        else {
+            return;
+        };
+        events::publish(|| RenderEvent::Started {
+            monitor: self.monitor.clone(),
+            video: entry.video.clone(),
+            decoder: format!(
+                "ffmpeg hwaccel={} fps={}",
+                options.hwaccel.as_str(),
+                options.fps
+            ),
+            source_size: (self.source_width, self.source_height),
+        });
+    }
+
+    /// Starts opening `entry`'s decoder off the render thread. `frame_source`
+    /// (`previous`) keeps playing until [`Self::poll_incoming`] swaps it.
+    /// During the start delay nothing opens: [`Self::open_when_due`] opens
+    /// whatever the entry is by then.
+    fn open_incoming(
+        &mut self,
+        output_id: u32,
+        entry: VideoMapEntry,
+        previous: Option<VideoMapEntry>,
+        reason: &str,
+        options: VideoOptions,
+    ) {
+        if self.start_at.is_some() {
+            return;
+        }
+        // The clock decides where a `utc` video is; a restored position
+        // would only be reopened at once.
+        let resume_ms = self.resume_ms.take();
+        let start = match (Timebase::resolve(entry.options.timebase), resume_ms) {
+            (Timebase::Utc, _) => StartAt::Clock,
+            (Timebase::Monotonic, Some(at_ms)) => StartAt::Resume(at_ms),
+            (Timebase::Monotonic, None) => StartAt::Beginning,
+        };
+        match FrameSource::warm(
+            entry.video,
+            entry.options.trim,
+            entry.options.still,
+            start,
+            self.source_width,
+            self.source_height,
+            options,
+        ) {
+            Ok(warm) => {
+                // Reopening where the clock is keeps showing the same video.
+                self.awaiting_present |= reason != "clock-resync";
+                self.incoming = Some(IncomingSource {
+                    warm,
+                    previous,
+                    reason: reason.to_string(),
+                    options,
+                });
+            }
+            Err(err) => {
+                self.record_stopped(previous.as_ref(), reason);
+                self.frame_source = FrameSource::None;
+                self.record_error(output_id, err);
+            }
+        }
+    }
+
+    /// Whether the next frame is due: by the frame timer, or for a decoder
+    /// following the `utc` clock, once the clock reaches it. One that is
+    /// more than [`timebase::RESEEK_AFTER`] off the clock reopens where the
+    /// clock puts the video, playing on until the new decoder has a frame.
+    fn decode_due(
+        &mut self,
+        output_id: u32,
+        now: Instant,
+        clock: ClockReading,
+        options: VideoOptions,
+    ) -> bool {
+        let Some(lead) = self.frame_source.clock_lead_ms(clock.get(self.timebase())) else {
+            return now >= self.next_decode_at;
+        };
+        let off = lead.unsigned_abs() as u128 > timebase::RESEEK_AFTER.as_millis();
+        if off
+            && self.incoming.is_none()
+            && now >= self.next_reseek_at
+            && let Some(entry) = self.current_entry.clone()
+        {
+            println!(
+                "[rendercore] output={} (id={output_id}) is {:+.3}s off the utc clock, reopening its decoder there",
+                self.monitor,
+                lead as f64 / 1000.0
+            );
+            self.next_reseek_at = now + CLOCK_RESEEK_INTERVAL;
+            self.open_incoming(
+                output_id,
+                entry.clone(),
+                Some(entry),
+                "clock-resync",
+                options,
+            );
+        }
+        lead <= 0
+    }
+
+    /// Opens the decoder held back by `KRC_START_DELAY_MS` once the delay is up.
+    fn open_when_due(&mut self, output_id: u32, now: Instant, options: VideoOptions) {
+        if self.start_at.is_none_or(|at| now < at) {
+            return;
+        }
+        self.start_at = None;
+        // A follower shows its leader's frames.
+        if self.shared_from.is_some() {
+            return;
+        }
+        if let Some(entry) = self.current_entry.clone() {
+            self.open_incoming(output_id, entry, None, "startup", options);
+        }
+    }
+
+    /// Whether nothing of the entry has been shown yet: the start delay is
+    /// running or the first decoder is still opening.
+    fn awaiting_first_frame(&self) -> bool {
+        self.start_at.is_some() || (self.fade.is_none() && self.incoming.is_some())
+    }
+
+    /// Brightness for this draw: black during the start delay; with
+    /// `KRC_FADE_IN_MS`, black until the first frame is up (`waiting`, which
+    /// covers a leader's decoder too), then a ramp to full. The ramp only
+    /// advances while the output is drawn and not paused.
+    fn fade_brightness(&mut self, waiting: bool, fade_in: Duration, now: Instant) -> f32 {
+        if self.start_at.is_some() {
+            return 0.0;
+        }
+        if fade_in.is_zero() {
+            return 1.0;
+        }
+        let Some(progress) = self.fade else {
+            if !waiting {
+                self.fade = Some(0.0);
+                self.fade_step_at = now;
+            }
+            return 0.0;
+        };
+        if progress < 1.0 && self.frozen_at.is_none() {
+            let step = now
+                .saturating_duration_since(self.fade_step_at)
+                .min(FADE_MAX_STEP);
+            self.fade = Some((progress + step.as_secs_f32() / fade_in.as_secs_f32()).min(1.0));
+        }
+        self.fade_step_at = now;
+        self.fade.unwrap_or(1.0)
+    }
+
+    /// Still black or ramping up, so the output has to keep being drawn.
+    fn fading(&self) -> bool {
+        self.start_at.is_some() || self.fade.is_some_and(|progress| progress < 1.0)
+    }
+
+    /// A running decoder failed. When its file is gone or its filesystem no
+    /// longer answers (a share went to sleep and ffmpeg gave up), the video
+    /// counts as missing instead of restarting the decoder every frame.
+    fn decode_failed(&mut self, output_id: u32, err: String, base: VideoOptions) {
+        let gone = self.current_entry.as_ref().and_then(|entry| {
+            match path_probe::metadata(&entry.video) {
+                Probe::Done(Some(_)) => None,
+                Probe::Done(None) => Some("video path is gone"),
+                Probe::Slow => Some("video path is not answering"),
+            }
+        });
+        let Some(gone) = gone else {
+            self.record_error(output_id, err);
+            return;
+        };
+        let mut options = base;
+        options.fps = quality::degraded_fps(options.fps, self.quality_level);
+        self.record_stopped(self.current_entry.as_ref(), "video-missing");
+        self.mark_missing(output_id, format!("{err} ({gone})"), options);
+    }
+
+    /// Reopens a missing video once its retry is due.
+    fn retry_missing(&mut self, output_id: u32) {
+        let Some(missing) = &self.missing else {
+            return;
+        };
+        if self.incoming.is_some() || Instant::now() < missing.retry_at {
+            return;
+        }
+        let options = missing.options;
+        match self.current_entry.clone() {
+            Some(entry) => self.open_incoming(output_id, entry, None, "retry", options),
+            None => self.missing = None,
+        }
+    }
+
+    /// Looks for ffmpeg again once its probe is due, and starts the decoder
+    /// when it is there now.
+    fn retry_ffmpeg(&mut self, output_id: u32) {
+        let Some(awaiting) = &mut self.awaiting_ffmpeg else {
+            return;
+        };
+        if self.incoming.is_some() {
+            return;
+        }
+        let Some((path, source)) = awaiting.probe(Instant::now(), || Tool::Ffmpeg.locate()) else {
+            return;
+        };
+        let options = awaiting.options;
+        self.awaiting_ffmpeg = None;
+        let Some(entry) = self.current_entry.clone() else {
+            return;
+        };
+        println!(
+            "[rendercore] output id={output_id}: ffmpeg found at {} (from {source}), starting the decoder",
+            path.display()
+        );
+        self.open_incoming(output_id, entry, None, "ffmpeg-found", options);
+    }
+
+    /// Why the stream is not playing its entry as configured: `None` when
+    /// it is, or when nothing failed yet.
+    fn degraded(&self) -> Option<&'static str> {
+        if self.awaiting_ffmpeg.is_some() {
+            Some("ffmpeg-not-found")
+        } else if self.missing.is_some() {
+            Some("video-missing")
+        } else if self.consecutive_failures > 0 {
+            Some("failing")
+        } else {
+            None
+        }
+    }
+
+    /// Shows the fallback for a video that is not there right now and
+    /// schedules the next attempt to open it.
+    fn mark_missing(&mut self, output_id: u32, err: String, options: VideoOptions) {
+        self.frame_source = FrameSource::None;
+        self.video_file = None;
+        self.pending_video_file = None;
+        let attempts = self.missing.as_ref().map_or(0, |m| m.attempts) + 1;
+        let delay = missing_retry_delay(attempts);
+        self.record_error(
+            output_id,
+            format!(
+                "{err}; temporarily missing, retrying in {}s",
+                delay.as_secs()
+            ),
+        );
+        self.missing = Some(MissingVideo {
+            options,
+            attempts,
+            retry_at: Instant::now() + delay,
+        });
+    }
+
+    /// Swaps in the warming decoder once it has a frame. Returns true when
+    /// `frame_pixels` holds that first frame (or black for a still that could
+    /// not be extracted), ready to upload. Past `timeout`
+    /// the old decoder is stopped anyway, leaving its last frame on screen;
+    /// past `open_timeout` without a frame the video counts as missing.
+    fn poll_incoming(&mut self, output_id: u32, timeout: Duration, open_timeout: Duration) -> bool {
+        self.retry_missing(output_id);
+        self.retry_ffmpeg(output_id);
+        let Some(mut incoming) = self.incoming.take() else {
+            return false;
+        };
+        match incoming.warm.poll() {
+            WarmPoll::Pending if incoming.warm.waited() >= open_timeout => {
+                let waited = incoming.warm.waited().as_secs();
+                let err = match incoming.warm.opening() {
+                    true => format!("video path did not answer within {waited}s"),
+                    false => format!("decoder produced no frame within {waited}s"),
+                };
+                self.record_stopped(incoming.previous.as_ref(), &incoming.reason);
+                self.mark_missing(output_id, err, incoming.options);
+                false
+            }
+            WarmPoll::Pending => {
+                if self.frame_source.is_video() && incoming.warm.waited() >= timeout {
+                    println!(
+                        "[rendercore] output id={output_id}: new decoder has no frame after {}ms, stopping the old one",
+                        timeout.as_millis()
+                    );
+                    self.record_stopped(incoming.previous.as_ref(), &incoming.reason);
+                    self.frame_source = FrameSource::None;
+                }
+                self.incoming = Some(incoming);
+                false
+            }
+            WarmPoll::Ready(source, pixels, file) => {
+                self.record_stopped(incoming.previous.as_ref(), &incoming.reason);
+                match self.missing.take() {
+                    Some(missing) => println!(
+                        "[rendercore] output id={output_id}: video is back after {} attempt(s)",
+                        missing.attempts
+                    ),
+                    None => println!(
+                        "[rendercore] output id={output_id}: switched after {}ms",
+                        incoming.warm.waited().as_millis()
+                    ),
+                }
+                self.frame_source = source;
+                self.frame_pixels = pixels;
+                self.video_file = Some(VideoFileId::from_metadata(&file));
+                self.pending_video_file = None;
+                self.record_frame();
+                self.record_started(incoming.options);
+                true
+            }
+            WarmPoll::Missing(err) => {
+                self.record_stopped(incoming.previous.as_ref(), &incoming.reason);
+                self.mark_missing(output_id, err, incoming.options);
+                false
+            }
+            WarmPoll::Failed(err, file) => {
+                self.record_stopped(incoming.previous.as_ref(), &incoming.reason);
+                self.frame_source = FrameSource::None;
+                self.missing = None;
+                self.video_file = file.as_ref().map(VideoFileId::from_metadata);
+                let ffmpeg_missing = Tool::Ffmpeg.used() && Tool::Ffmpeg.locate().is_none();
+                self.awaiting_ffmpeg =
+                    ffmpeg_missing.then(|| AwaitingFfmpeg::new(incoming.options, Instant::now()));
+                match ffmpeg_missing {
+                    true => self.record_error(
+                        output_id,
+                        format!(
+                            "{err}; looking for it again every {}s",
+                            FFMPEG_PROBE_INTERVAL.as_secs()
+                        ),
+                    ),
+                    false => self.record_error(output_id, err),
+                }
+                // A still that cannot be extracted shows black rather than
+                // the procedural pattern, and idles like one that could.
+                let still = self
+                    .current_entry
+                    .as_ref()
+                    .is_some_and(|entry| entry.options.still.is_some());
+                if still {
+                    for pixel in self.frame_pixels.chunks_exact_mut(4) {
+                        pixel.copy_from_slice(&[0, 0, 0, 255]);
+                    }
+                    self.frame_source = FrameSource::Still;
+                }
+                still
+            }
+        }
+    }
+
+    /// Publishes `stream_stopped` when `entry`'s decoder is about to close.
+    /// Empties an output's own stream when a layout takes the output over:
+    /// its decoder stops and the cells are drawn instead.
+    fn clear_for_layout(&mut self, reason: &str) {
+        let playing = match self.incoming.take() {
+            Some(incoming) => incoming.previous,
+            None => self.current_entry.clone(),
+        };
+        self.record_stopped(playing.as_ref(), reason);
+        self.current_entry = None;
+        self.missing = None;
+        self.awaiting_ffmpeg = None;
+        self.frame_source = FrameSource::None;
+    }
+
+    fn record_stopped(&self, entry: Option<&VideoMapEntry>, reason: &str) {
+        let Some(entry) = entry.filter(|_| self.frame_source.is_video()) else {
+            return;
+        };
+        events::publish(|| RenderEvent::Stopped {
+            monitor: self.monitor.clone(),
+            video: entry.video.clone(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// `KRC_SWITCH_TIMEOUT_MS` default.
+const DEFAULT_SWITCH_TIMEOUT: Duration = Duration::from_millis(2000);
+
+/// How long the map file must stay unchanged before a reload reads it, so
+/// a burst of writes or a delete-then-write save is one reload.
+const MAP_SETTLE: Duration = Duration::from_millis(100);
+
+/// Found by [`WgpuShared::poll_video_map`], not applied yet: a renderer
+/// paused without frames applies them on resume.
+#[derive(Default)]
+struct MapChanges {
+    map: bool,
+    env: bool,
+    /// Streams whose video file was replaced on disk.
+    stale: Vec<u32>,
+}
+
+/// The map file's mtime as the polls see it. A new mtime only counts once a
+/// poll [`MAP_SETTLE`] later still sees it, so a delete-then-write save or a
+/// burst of writes is one reload.
+#[derive(Debug, Default)]
+struct MapMtime {
+    last: Option<SystemTime>,
+    /// A new mtime seen once, waiting to hold still.
+    pending: Option<Option<SystemTime>>,
+}
+
+impl MapMtime {
+    /// Notes the mtime a poll found. `None` while a new one settles (poll
+    /// again after [`MAP_SETTLE`]); otherwise whether the map changed since
+    /// it was last read. An env file change reads the map right away.
+    fn observe(&mut self, current: Option<SystemTime>, env_changed: bool) -> Option<bool> {
+        if current != self.last && self.pending != Some(current) && !env_changed {
+            self.pending = Some(current);
+            return None;
+        }
+        let changed = current != self.last;
+        self.last = current;
+        self.pending = None;
+        Some(changed)
+    }
+}
+
+/// The map file as last read successfully. While the file is missing or
+/// unreadable (an editor saving by delete-then-write, a dotfile manager
+/// re-linking it) this map stays, so outputs do not flash to the default and
+/// back; only a present, empty file clears the entries.
+struct LastGoodMap {
+    doc: MapDocument,
+    /// Set while the map file cannot be read, so that is logged once.
+    unreadable: bool,
+}
+
+impl LastGoodMap {
+    /// The map at startup. No map file then is a normal setup (env map
+    /// only), not an outage.
+    fn load(path: &Path) -> Self {
+        match read_map_document(path) {
+            Ok(doc) => Self {
+                doc,
+                unreadable: false,
+            },
+            Err(_) => Self {
+                doc: load_map_document(path),
+                unreadable: true,
+            },
+        }
+    }
+
+    /// Re-reads the map file, or keeps the last map while it cannot be read;
+    /// later reloads retry.
+    fn reread(&mut self, path: &Path) -> MapDocument {
+        match read_map_document(path) {
+            Ok(doc) => {
+                if std::mem::take(&mut self.unreadable) {
+                    println!(
+                        "[rendercore] map file {} readable, reloading it",
+                        display_path(&path.to_string_lossy())
+                    );
+                }
+                self.doc = doc.clone();
+                doc
+            }
+            Err(err) => {
+                if !std::mem::replace(&mut self.unreadable, true) {
+                    eprintln!(
+                        "[rendercore] warning: map file {} unreadable ({err}{}); keeping the last map until it is back",
+                        display_path(&path.to_string_lossy()),
+                        file_access::owner_hint(path)
+                    );
+                }
+                self.doc.clone()
+            }
+        }
+    }
+}
+
+/// `KRC_VIDEO_OPEN_TIMEOUT_MS` default.
+const DEFAULT_OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct VideoMapState {
+    map_file: PathBuf,
+    /// The map file's `[default]` (v2), else `KRC_VIDEO_DEFAULT`.
+    default_video: Option<VideoMapEntry>,
+    env_default: Option<VideoMapEntry>,
+    env_map: VideoMap,
+    /// `set-video transient` overrides; win over the map file, never written.
+    transient: VideoMap,
+    merged_map: VideoMap,
+    /// `reduce-motion`: [`Self::resolve`] turns every entry into a still.
+    reduce_motion: bool,
+    last_good: LastGoodMap,
+    mtime: MapMtime,
+    env_file: Option<EnvFileWatcher>,
+    video_options: VideoOptions,
+    /// What the polls since the last frame found, applied with the next one.
+    changes: MapChanges,
+    /// How long a switch keeps the old video playing while the new decoder
+    /// warms up (`KRC_SWITCH_TIMEOUT_MS`); zero switches immediately.
+    switch_timeout: Duration,
+    /// How long a decoder may take to open its path and produce a first
+    /// frame before the video counts as missing (`KRC_VIDEO_OPEN_TIMEOUT_MS`).
+    open_timeout: Duration,
+}
+
+/// sRGB so filtering (bilinear, mip generation, box filter) happens in linear light.
+const SOURCE_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// `time_sec` wraps to 0 after this long. An f32 of seconds still steps by a
+/// quarter millisecond below 4096, while after weeks of uptime it steps by
+/// whole frames. Every built-in shader effect completes whole cycles in it,
+/// so the wrap is seamless.
+const SHADER_TIME_WRAP_SEC: u64 = 3600;
+
+/// The render clock as the shader's `time_sec`, wrapped before it becomes f32.
+fn shader_time(elapsed: Duration) -> f32 {
+    (elapsed.as_secs() % SHADER_TIME_WRAP_SEC) as f32 + elapsed.subsec_nanos() as f32 * 1e-9
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable)]
+struct FrameUniform {
+    /// Seconds of render clock modulo [`SHADER_TIME_WRAP_SEC`].
+    time_sec: f32,
+    aspect: f32,
+    rotate_quarters: u32,
+    flip_bits: u32,
+    /// Box-filter tap offset in source UV (`KRC_DOWNSCALE=simple`); zero means one tap.
+    box_offset: [f32; 2],
+    /// `effect=ambient` pan in source UV, applied after the zoom.
+    motion_offset: [f32; 2],
+    /// `effect=ambient` zoom, 1.0 when off.
+    zoom: f32,
+    /// `wl_output.transform` the buffer is stored in, undone before the
+    /// per-entry mapping.
+    buffer_transform: u32,
+    /// `KRC_FADE_IN_MS` multiplier, applied once to the final color; 1.0
+    /// once the output has faded in.
+    brightness: f32,
+    /// `overlay=` preset as [`particles::shader_kind`]; 0 draws no particles.
+    overlay: u32,
+    /// `crop=` window in source UV (origin, size); `[0, 0, 1, 1]` shows it all.
+    crop: [f32; 4],
+    /// Where the picture sits in display UV (origin, size) for `fit=integer`;
+    /// black outside. `[0, 0, 1, 1]` fills the output.
+    fit: [f32; 4],
+    /// `gamut=`: linear sRGB to the output's primaries, as three padded
+    /// columns; [`IDENTITY_GAMUT`] leaves the colors alone.
+    gamut: [[f32; 4]; 3],
+    /// 1 when the output's curve is a power of `gamma` rather than the sRGB
+    /// curve the surface format applies.
+    transfer: u32,
+    gamma: f32,
+    _pad2: [f32; 2],
+    /// `overlay=` density, speed and wind, then one logical pixel in heights
+    /// of the drawn area; zero when the overlay is off.
+    overlay_params: [f32; 4],
+}
+
+const IDENTITY_GAMUT: [[f32; 4]; 3] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+];
+
+/// Shared by every fragment variant: bindings, the fullscreen triangle, and the
+/// per-entry rotate/flip UV mapping.
+const FRAME_SHADER_WGSL_COMMON: &str = r#"
+struct VsOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+struct FrameUniform {
+    time_sec: f32,
+    aspect: f32,
+    rotate_quarters: u32,
+    flip_bits: u32,
+    box_offset: vec2<f32>,
+    motion_offset: vec2<f32>,
+    zoom: f32,
+    buffer_transform: u32,
+    brightness: f32,
+    overlay: u32,
+    crop: vec4<f32>,
+    fit: vec4<f32>,
+    gamut: mat3x3<f32>,
+    transfer: u32,
+    gamma: f32,
+    _pad2: vec2<f32>,
+    overlay_params: vec4<f32>,
+};
 
 @group(0) @binding(0) var src_tex: texture_2d<f32>;
 @group(0) @binding(1) var src_sampler: sampler;
@@ -474,318 +2983,2229 @@ fn vs_main(@builtin(vertex_index) vid: u32) -> VsOut {
     return out;
 }
 
-@fragment
-fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
-    let base_uv = vec2<f32>(in.uv.x, 1.0 - in.uv.y);
-    let wave = vec2<f32>(
-        sin(uniforms.time_sec * 0.45 + base_uv.y * 8.0) * 0.005,
-        cos(uniforms.time_sec * 0.40 + base_uv.x * 7.0) * 0.005 * uniforms.aspect
+// Maps screen UV (origin top-left) to source UV: flip first, then undo the
+// clockwise content rotation, then the ambient zoom and pan around the center.
+// The host keeps the pan within the zoom margin, so the result stays in 0..1.
+// Buffer UV to the UV the user sees: the buffer holds the output image already
+// transformed by wl_surface.set_buffer_transform (flip first, then rotation
+// counter-clockwise), which the compositor undoes.
+fn display_uv(b: vec2<f32>) -> vec2<f32> {
+    switch uniforms.buffer_transform {
+        case 1u: { return vec2<f32>(1.0 - b.y, b.x); }
+        case 2u: { return vec2<f32>(1.0 - b.x, 1.0 - b.y); }
+        case 3u: { return vec2<f32>(b.y, 1.0 - b.x); }
+        case 4u: { return vec2<f32>(1.0 - b.x, b.y); }
+        case 5u: { return vec2<f32>(b.y, b.x); }
+        case 6u: { return vec2<f32>(b.x, 1.0 - b.y); }
+        case 7u: { return vec2<f32>(1.0 - b.y, 1.0 - b.x); }
+        default: { return b; }
+    }
+}
+
+// Display UV to the picture's own 0..1 box for fit=integer.
+fn fit_uv(p: vec2<f32>) -> vec2<f32> {
+    return (p - uniforms.fit.xy) / uniforms.fit.zw;
+}
+
+// Whether a screen UV falls in the fit=integer border.
+fn letterboxed(screen_uv: vec2<f32>) -> bool {
+    let p = fit_uv(display_uv(screen_uv));
+    return any(p < vec2<f32>(0.0)) || any(p > vec2<f32>(1.0));
+}
+
+// gamut=: linear sRGB to the output's primaries, then its transfer curve. The
+// surface format encodes with the sRGB curve, so a power curve is produced by
+// handing it the value that curve turns into col^(1/gamma).
+fn output_gamut(col: vec3<f32>) -> vec3<f32> {
+    let mapped = clamp(uniforms.gamut * col, vec3<f32>(0.0), vec3<f32>(1.0));
+    if (uniforms.transfer == 0u) {
+        return mapped;
+    }
+    let encoded = pow(mapped, vec3<f32>(1.0 / uniforms.gamma));
+    return select(
+        pow((encoded + vec3<f32>(0.055)) / 1.055, vec3<f32>(2.4)),
+        encoded / 12.92,
+        encoded <= vec3<f32>(0.04045)
     );
-    let uv = fract(base_uv + wave);
-    let col = textureSample(src_tex, src_sampler, uv).rgb;
-    return vec4<f32>(col, 1.0);
 }
-"#;
 
-fn init_wgpu_shared(
-    connection: &Connection,
-    outputs: &BTreeMap<u32, OutputSlot>,
-    layer_surfaces: &[LayerSurfaceSlot],
-) -> Result<WgpuShared, String> {
-    let instance = wgpu::Instance::default();
-    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance,
-        compatible_surface: None,
-        force_fallback_adapter: false,
-    }))
-    .ok_or_else(|| "wgpu request_adapter returned None".to_string())?;
-    let adapter_limits = adapter.limits();
+// The last step of every variant: the output's gamut, the fit=integer border,
+// then the fade.
+fn output_color(col: vec3<f32>, screen_uv: vec2<f32>) -> vec4<f32> {
+    return vec4<f32>(select(output_gamut(col), vec3<f32>(0.0), letterboxed(screen_uv)) * uniforms.brightness, 1.0);
+}
 
-    let (device, queue) = pollster::block_on(adapter.request_device(
-        &wgpu::DeviceDescriptor {
-            label: Some("kitsune-rendercore-device"),
-            required_features: wgpu::Features::empty(),
-            required_limits: adapter_limits.clone(),
-            memory_hints: wgpu::MemoryHints::Performance,
-        },
-        None,
-    ))
-    .map_err(|err| format!("wgpu request_device failed: {err}"))?;
+fn content_uv(screen_uv: vec2<f32>) -> vec2<f32> {
+    var p = fit_uv(display_uv(screen_uv));
+    if ((uniforms.flip_bits & 1u) != 0u) {
+        p.x = 1.0 - p.x;
+    }
+    if ((uniforms.flip_bits & 2u) != 0u) {
+        p.y = 1.0 - p.y;
+    }
+    switch uniforms.rotate_quarters {
+        case 1u: {
+            p = vec2<f32>(p.y, 1.0 - p.x);
+        }
+        case 2u: {
+            p = vec2<f32>(1.0 - p.x, 1.0 - p.y);
+        }
+        case 3u: {
+            p = vec2<f32>(1.0 - p.y, p.x);
+        }
+        default: {}
+    }
+    return vec2<f32>(0.5) + (p - vec2<f32>(0.5)) / uniforms.zoom + uniforms.motion_offset;
+}
 
-    let display_ptr = NonNull::new(connection.backend().display_ptr() as *mut _)
-        .ok_or_else(|| "wayland display pointer is null".to_string())?;
-    let raw_display_handle = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(display_ptr));
+// Narrows a 0..1 content UV to the entry's crop window.
+fn crop_uv(uv: vec2<f32>) -> vec2<f32> {
+    return uniforms.crop.xy + uv * uniforms.crop.zw;
+}
 
-    let mut render_surfaces = Vec::new();
-    for slot in layer_surfaces {
-        let Some(out) = outputs.get(&slot.output_global_name) else {
-            continue;
-        };
-        let width = out.width.unwrap_or(1920).max(1);
-        let height = out.height.unwrap_or(1080).max(1);
-        let window_ptr = NonNull::new(slot.surface.id().as_ptr() as *mut _)
-            .ok_or_else(|| "wayland surface pointer is null".to_string())?;
-        let raw_window_handle = RawWindowHandle::Wayland(WaylandWindowHandle::new(window_ptr));
+// The texture is sRGB, so the taps are averaged in linear light.
+fn sample_src(uv: vec2<f32>) -> vec3<f32> {
+    let o = uniforms.box_offset;
+    if (o.x == 0.0 && o.y == 0.0) {
+        return textureSample(src_tex, src_sampler, uv).rgb;
+    }
+    let sum = textureSample(src_tex, src_sampler, uv + vec2<f32>(-o.x, -o.y)).rgb
+        + textureSample(src_tex, src_sampler, uv + vec2<f32>(o.x, -o.y)).rgb
+        + textureSample(src_tex, src_sampler, uv + vec2<f32>(-o.x, o.y)).rgb
+        + textureSample(src_tex, src_sampler, uv + vec2<f32>(o.x, o.y)).rgb;
+    return sum * 0.25;
+}
+"#;
+
+const MIP_BLIT_WGSL: &str = r#"
+struct VsOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0) var src_tex: texture_2d<f32>;
+@group(0) @binding(1) var src_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> VsOut {
+    var out: VsOut;
+    var pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>( 3.0,  1.0)
+    );
+    let p = pos[vid];
+    out.pos = vec4<f32>(p, 0.0, 1.0);
+    out.uv = vec2<f32>(0.5 * (p.x + 1.0), 0.5 * (1.0 - p.y));
+    return out;
+}
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    return textureSample(src_tex, src_sampler, in.uv);
+}
+"#;
+
+const FRAME_SHADER_WGSL_PLAIN: &str = r#"
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    let screen_uv = vec2<f32>(in.uv.x, 1.0 - in.uv.y);
+    let base_uv = content_uv(screen_uv);
+    let uv = crop_uv(fract(base_uv));
+    let _unused_time = uniforms.time_sec;
+    let _unused_aspect = uniforms.aspect;
+    let col = sample_src(uv);
+    return output_color(col, screen_uv);
+}
+"#;
+
+const FRAME_SHADER_WGSL_WAVE: &str = r#"
+// 258 and 229 whole cycles per 3600 s time_sec wrap (about 0.45 and 0.40
+// rad/s), so the wave does not jump when time_sec wraps.
+const WAVE_RATE_X: f32 = 0.45029495;
+const WAVE_RATE_Y: f32 = 0.39968040;
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    let screen_uv = vec2<f32>(in.uv.x, 1.0 - in.uv.y);
+    let base_uv = content_uv(screen_uv);
+    let wave = vec2<f32>(
+        sin(uniforms.time_sec * WAVE_RATE_X + base_uv.y * 8.0) * 0.005,
+        cos(uniforms.time_sec * WAVE_RATE_Y + base_uv.x * 7.0) * 0.005 * uniforms.aspect
+    );
+    let uv = crop_uv(fract(base_uv + wave));
+    let col = sample_src(uv);
+    return output_color(col, screen_uv);
+}
+"#;
+
+fn init_wgpu_shared(
+    connection: &Connection,
+    outputs: &BTreeMap<u32, OutputSlot>,
+    layer_surfaces: &[LayerSurfaceSlot],
+    reduce_motion: bool,
+    restored: Restored,
+) -> Result<WgpuShared, String> {
+    startup::begin(Step::Adapter)?;
+    let instance = wgpu::Instance::default();
+    let nodes = render_nodes();
+    let compositor = compositor_device(connection, &nodes);
+    let selection = select_adapter(&instance, &nodes, compositor.as_ref())?;
+    println!("[rendercore] gpu: {}", selection.summary());
+    if let Some(mismatch) = &selection.mismatch {
+        println!("[rendercore] warning: {mismatch}");
+    }
+    let fps_cap = selection.software.then(software_fps_cap).flatten();
+    if selection.software {
+        println!(
+            "[rendercore] warning: rendering on a software adapter, every frame costs CPU; {}",
+            fps_cap.map_or_else(
+                || "no fps cap (KRC_SOFTWARE_MAX_FPS=0)".to_string(),
+                |cap| format!("outputs and decoders capped at {cap}fps (KRC_SOFTWARE_MAX_FPS)")
+            )
+        );
+    }
+    let info = selection.adapter.get_info();
+    let gpu = GpuAdapterStatus {
+        name: info.name,
+        backend: info.backend.to_str().to_string(),
+        device_type: format!("{:?}", info.device_type).to_ascii_lowercase(),
+        fps_cap,
+    };
+    let adapter = selection.adapter;
+    let adapter_limits = adapter.limits();
+    startup::begin(Step::Device)?;
+    let (device, queue) = request_device(&adapter)?;
+
+    startup::begin(Step::Surfaces)?;
+    let display_ptr = NonNull::new(connection.backend().display_ptr() as *mut _)
+        .ok_or_else(|| "wayland display pointer is null".to_string())?;
+    let raw_display_handle = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(display_ptr));
+
+    let mut render_surfaces = Vec::new();
+    for slot in layer_surfaces {
+        let Some(out) = outputs.get(&slot.output_global_name) else {
+            continue;
+        };
+        // The configured size when the compositor already sent one, so the
+        // first frame (the prewarm clear) fits without a reconfigure.
+        let surface_size = slot
+            .configured_width
+            .zip(slot.configured_height)
+            .unwrap_or(out.logical_size());
+        let fit = fit_surface(surface_size, 1, adapter_limits.max_texture_dimension_2d);
+        let clamped = apply_surface_fit(
+            Some(slot),
+            &format!(
+                "output={} (id={})",
+                out.display_name(),
+                slot.output_global_name
+            ),
+            surface_size,
+            1,
+            fit,
+            false,
+            adapter_limits.max_texture_dimension_2d,
+        );
+        let (width, height) = fit.size;
+        let window_ptr = NonNull::new(slot.surface.id().as_ptr() as *mut _)
+            .ok_or_else(|| "wayland surface pointer is null".to_string())?;
+        let raw_window_handle = RawWindowHandle::Wayland(WaylandWindowHandle::new(window_ptr));
+
+        let surface = unsafe {
+            instance
+                .create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
+                    raw_display_handle,
+                    raw_window_handle,
+                })
+                .map_err(|err| format!("wgpu create_surface_unsafe failed: {err}"))?
+        };
+
+        let caps = surface.get_capabilities(&adapter);
+        if caps.formats.is_empty() {
+            return Err("wgpu surface has no supported formats".to_string());
+        }
+        let format = caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(caps.formats[0]);
+        let present_mode = if caps.present_modes.contains(&wgpu::PresentMode::Mailbox) {
+            wgpu::PresentMode::Mailbox
+        } else {
+            wgpu::PresentMode::Fifo
+        };
+        let alpha_mode = caps
+            .alpha_modes
+            .iter()
+            .copied()
+            .find(|m| *m == wgpu::CompositeAlphaMode::Auto)
+            .unwrap_or(caps.alpha_modes[0]);
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode,
+            alpha_mode,
+            view_formats: vec![format],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&device, &config);
+        render_surfaces.push(RenderSurface {
+            output_global_name: slot.output_global_name,
+            width,
+            height,
+            clamped,
+            buffer_scale: 1,
+            buffer_transform: OutputTransform::Normal,
+            surface,
+            config,
+            full_damage: true,
+            drawn: Vec::new(),
+            damage: DamageTally::default(),
+            color: None,
+            tagged_with: None,
+            edid: None,
+            clock: None,
+        });
+    }
+    if render_surfaces.is_empty() {
+        return Err("no render surfaces created for outputs".to_string());
+    }
+    startup::begin(Step::Pipelines)?;
+    let mut program = init_render_program(&device, &adapter)?;
+    let prewarm_started = Instant::now();
+    for surface in &render_surfaces {
+        program.ensure_pipeline(&device, surface.config.format);
+    }
+    if no_prewarm_from_env() {
+        println!("[rendercore] prewarm skipped (KRC_NO_PREWARM)");
+    } else {
+        let cleared = prewarm(&device, &queue, &program, &render_surfaces, layer_surfaces);
+        println!(
+            "[rendercore] prewarm: {} frame pipeline(s) compiled and drawn, {cleared}/{} surface(s) cleared in {:.1}ms",
+            program.pipelines.len(),
+            render_surfaces.len(),
+            prewarm_started.elapsed().as_secs_f64() * 1e3
+        );
+    }
+    let source_size = choose_source_resolution(adapter_limits.max_texture_dimension_2d);
+    println!(
+        "[rendercore] source texture selected={}x{} (max_texture_dimension_2d={})",
+        source_size.0, source_size.1, adapter_limits.max_texture_dimension_2d
+    );
+    startup::begin(Step::Streams)?;
+    let env_file = EnvFileWatcher::from_env();
+    if let Some(watcher) = &env_file {
+        println!(
+            "[rendercore] watching env file {}",
+            display_path(&watcher.path().to_string_lossy())
+        );
+    }
+    let video_options = capped_video_options(fps_cap);
+    let map_file = map_file_path_from_env()?;
+    let env_map = env_video_map();
+    let last_good = LastGoodMap::load(&map_file);
+    let map_doc = &last_good.doc;
+    println!("[rendercore] map file format: {}", map_doc.format.label());
+    let env_default = default_video_from_env();
+    let default_video = map_doc.default.clone().or_else(|| env_default.clone());
+    let merged_map = merge_maps(
+        merge_maps(env_map.clone(), map_doc.entries.clone()),
+        restored.transient.clone(),
+    );
+    let last_mtime = std::fs::metadata(&map_file)
+        .ok()
+        .and_then(|m| m.modified().ok());
+    let video_map_state = VideoMapState {
+        map_file,
+        default_video,
+        env_default,
+        env_map,
+        transient: restored.transient,
+        merged_map,
+        reduce_motion,
+        last_good,
+        mtime: MapMtime {
+            last: last_mtime,
+            pending: None,
+        },
+        env_file,
+        video_options,
+        changes: MapChanges::default(),
+        switch_timeout: std::env::var("KRC_SWITCH_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map_or(DEFAULT_SWITCH_TIMEOUT, Duration::from_millis),
+        open_timeout: std::env::var("KRC_VIDEO_OPEN_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|ms| *ms > 0)
+            .map_or(DEFAULT_OPEN_TIMEOUT, Duration::from_millis),
+    };
+    video_map_state.record_loaded();
+    let start_delay = millis_from_env("KRC_START_DELAY_MS");
+    let fade_in = millis_from_env("KRC_FADE_IN_MS");
+    let start_at = (!start_delay.is_zero()).then(|| {
+        println!(
+            "[rendercore] start delay {}ms: outputs stay black, decoders open after it",
+            start_delay.as_millis()
+        );
+        Instant::now() + start_delay
+    });
+    if !fade_in.is_zero() {
+        println!(
+            "[rendercore] fade-in {}ms from each output's first frame",
+            fade_in.as_millis()
+        );
+    }
+    let mut video_streams = BTreeMap::new();
+    let mut layout_outputs = Vec::new();
+    for (output_id, out) in outputs.iter().filter(|(_, out)| !out.excluded) {
+        let output_name = out.display_name();
+        let map_key = out.map_key(&video_map_state.merged_map);
+        let mut selected_video = video_map_state.resolve(&map_key);
+        record_stream_selected(*output_id, &output_name, &map_key, selected_video.as_ref());
+        // The cells get their streams once the surfaces are known, below.
+        if let Some(entry) = selected_video.take_if(|entry| entry.layout.is_some()) {
+            layout_outputs.push((*output_id, output_name.clone(), map_key.clone(), entry));
+        }
+        let options = selected_video
+            .as_ref()
+            .map(|entry| entry.options)
+            .unwrap_or_default();
+        let base_size = entry_base_size(
+            source_size,
+            adapter_limits.max_texture_dimension_2d,
+            options,
+        );
+        let stream_size = selected_video
+            .as_ref()
+            .and_then(|entry| whole_frame_size(entry, adapter_limits.max_texture_dimension_2d))
+            .unwrap_or_else(|| oriented_source_size(base_size, out.logical_size(), options));
+        // Only an output still showing the same video picks up where it was.
+        let resume_ms = selected_video.as_ref().and_then(|entry| {
+            restored
+                .positions
+                .iter()
+                .find(|pos| pos.monitor == output_name && pos.video == entry.video)
+                .map(|pos| pos.position_ms)
+        });
+        let stream = init_video_stream(
+            &device,
+            &queue,
+            &program,
+            stream_size,
+            out.logical_size(),
+            selected_video,
+            video_options,
+            start_at,
+            resume_ms,
+            output_id,
+            &output_name,
+        )?;
+        video_streams.insert(*output_id, stream);
+    }
+
+    let overlay_timer = OverlayTimer::from_env(&device, &queue);
+    let text_program = TextProgram::new(&device);
+    let config_file = config_file_path_from_env().ok();
+    let clock_config = config_file
+        .as_deref()
+        .map(ClockConfig::load)
+        .unwrap_or_default();
+    if clock_config.enabled {
+        println!("[rendercore] clock widget: {clock_config}");
+    }
+    let mut shared = WgpuShared {
+        _instance: instance,
+        _adapter: adapter,
+        device,
+        queue,
+        render_surfaces,
+        program,
+        render_clock: RenderClock::from_env(),
+        ambient_seed: ambient_seed_from_env(),
+        video_streams,
+        layouts: BTreeMap::new(),
+        video_map_state,
+        uploaded_video_frames: 0,
+        mipgen_runs: 0,
+        mipgen_time: Duration::ZERO,
+        overlay_timer,
+        text_program,
+        clock: Clock::new(clock_config),
+        config_mtime: config_file.as_deref().and_then(file_mtime),
+        config_file,
+        base_source_size: source_size,
+        max_texture_dimension: adapter_limits.max_texture_dimension_2d,
+        auto_quality: AutoQuality::from_env(),
+        share_decoders: share_decoders_from_env(),
+        damage_tracking: damage_tracking_from_env(),
+        fade_in,
+        gpu,
+        headroom: None,
+        headroom_streams: Vec::new(),
+        next_headroom_check: Instant::now(),
+    };
+    for (output_id, output_name, map_key, entry) in layout_outputs {
+        let Some(layout) = entry.layout.clone() else {
+            continue;
+        };
+        log_layout(output_id, &output_name, &layout);
+        let cells = layout.cell_count();
+        shared.layouts.insert(output_id, layout);
+        for cell in 0..cells {
+            let Some(cell_entry) = entry.cell_entry(cell) else {
+                continue;
+            };
+            let stream_id = cell_stream_id(output_id, cell);
+            let label = cell_label(&output_name, cell);
+            record_stream_selected(stream_id, &label, &map_key, Some(&cell_entry));
+            shared.add_cell_stream(stream_id, &label, cell_entry, start_at);
+        }
+    }
+    Ok(shared)
+}
+
+/// `KRC_NO_PREWARM=1` leaves the first draw and first present to the first
+/// frame, for telling startup hitches apart.
+fn no_prewarm_from_env() -> bool {
+    std::env::var("KRC_NO_PREWARM")
+        .ok()
+        .is_some_and(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+}
+
+/// Takes the first-use cost out of the first real frame: draws every frame
+/// pipeline and the mip blit once into 1x1 offscreen targets (drivers finish
+/// compiling on first draw, not at pipeline creation), then presents one
+/// black frame on every configured surface at once, so all outputs appear
+/// together instead of as each one's first frame is ready. Returns how many
+/// surfaces were cleared.
+fn prewarm(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    program: &RenderProgram,
+    render_surfaces: &[RenderSurface],
+    layer_surfaces: &[LayerSurfaceSlot],
+) -> usize {
+    let one_pixel = wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+    let texture = |format: wgpu::TextureFormat, usage: wgpu::TextureUsages| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("kitsune-rendercore-prewarm-texture"),
+            size: one_pixel,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        })
+    };
+    let source = texture(SOURCE_TEXTURE_FORMAT, wgpu::TextureUsages::TEXTURE_BINDING);
+    let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("kitsune-rendercore-prewarm-uniform"),
+        size: std::mem::size_of::<FrameUniform>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM,
+        mapped_at_creation: false,
+    });
+    let frame_bind_group = frame_bind_group(
+        device,
+        program,
+        &source,
+        &uniform_buffer,
+        SampleFilter::Linear,
+    );
+    let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+    let mip_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("kitsune-rendercore-prewarm-mip-bg"),
+        layout: &program.mip_blit.bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&source_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&program.mip_blit.sampler),
+            },
+        ],
+    });
+    let mip_target = texture(
+        SOURCE_TEXTURE_FORMAT,
+        wgpu::TextureUsages::RENDER_ATTACHMENT,
+    );
+    let mut draws = vec![(
+        mip_target.create_view(&wgpu::TextureViewDescriptor::default()),
+        &program.mip_blit.pipeline,
+        &mip_bind_group,
+    )];
+    for (format, pipeline) in &program.pipelines {
+        let target = texture(*format, wgpu::TextureUsages::RENDER_ATTACHMENT);
+        draws.push((
+            target.create_view(&wgpu::TextureViewDescriptor::default()),
+            pipeline,
+            &frame_bind_group,
+        ));
+    }
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("kitsune-rendercore-prewarm-encoder"),
+    });
+    for (view, pipeline, bind_group) in &draws {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("kitsune-rendercore-prewarm-pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, *bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    // Only surfaces the compositor already configured may take a buffer.
+    let frames = render_surfaces
+        .iter()
+        .filter(|rs| {
+            layer_surfaces
+                .iter()
+                .any(|slot| slot.output_global_name == rs.output_global_name && slot.configured)
+        })
+        .filter_map(|rs| rs.surface.get_current_texture().ok())
+        .collect::<Vec<_>>();
+    for frame in &frames {
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("kitsune-rendercore-prewarm-clear"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+    }
+    queue.submit([encoder.finish()]);
+    let cleared = frames.len();
+    for frame in frames {
+        frame.present();
+    }
+    device.poll(wgpu::Maintain::Wait);
+    cleared
+}
+
+/// `reload` / `set-video` reply entries, one per output looked at.
+fn outcomes_json(outcomes: &[(String, &'static str, String)]) -> String {
+    outcomes
+        .iter()
+        .map(|(name, action, video)| {
+            format!(
+                "{{\"name\":\"{}\",\"action\":\"{}\",\"video\":\"{}\"}}",
+                escape_json(name),
+                action,
+                escape_json(video)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl VideoMapState {
+    /// What the output keyed `map_key` shows: its entry, else the default,
+    /// as a still while reduce-motion is on. Every stream is resolved here,
+    /// so transient overrides and layout cells are reduced alike.
+    fn resolve(&self, map_key: &str) -> Option<VideoMapEntry> {
+        let entry = resolve_output_video(&self.merged_map, self.default_video.as_ref(), map_key);
+        if self.reduce_motion {
+            entry.map(VideoMapEntry::reduced_motion)
+        } else {
+            entry
+        }
+    }
+
+    fn record_loaded(&self) {
+        if !journal::is_recording() {
+            return;
+        }
+        journal::record(
+            "map_loaded",
+            &[
+                ("map_file", Value::Str(&self.map_file.display().to_string())),
+                ("entries", Value::Str(&format_video_map(&self.merged_map))),
+                (
+                    "default",
+                    Value::Str(
+                        &self
+                            .default_video
+                            .as_ref()
+                            .map(VideoMapEntry::to_string)
+                            .unwrap_or_default(),
+                    ),
+                ),
+            ],
+        );
+    }
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok().and_then(|m| m.modified().ok())
+}
+
+/// What identifies the content of a video path: replacing the file (new inode)
+/// or rewriting it in place (new mtime/size) both change it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VideoFileId {
+    dev: u64,
+    ino: u64,
+    mtime: Option<SystemTime>,
+    size: u64,
+}
+
+impl VideoFileId {
+    /// `None` while the path has not answered within the probe budget; the
+    /// inner `None` when it is gone.
+    fn probe(path: &str) -> Option<Option<Self>> {
+        match path_probe::metadata(path) {
+            Probe::Done(meta) => Some(meta.as_ref().map(Self::from_metadata)),
+            Probe::Slow => None,
+        }
+    }
+
+    fn from_metadata(meta: &std::fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Self {
+            dev: meta.dev(),
+            ino: meta.ino(),
+            mtime: meta.modified().ok(),
+            size: meta.len(),
+        }
+    }
+
+    fn mtime_secs(&self) -> u64 {
+        self.mtime
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+fn record_stream_selected(
+    output_id: u32,
+    output_name: &str,
+    map_key: &str,
+    entry: Option<&VideoMapEntry>,
+) {
+    if !journal::is_recording() {
+        return;
+    }
+    journal::record(
+        "stream_selected",
+        &[
+            ("output_id", Value::U64(output_id as u64)),
+            ("output", Value::Str(output_name)),
+            ("map_key", Value::Str(map_key)),
+            (
+                "video",
+                Value::Str(&entry.map(VideoMapEntry::to_string).unwrap_or_default()),
+            ),
+        ],
+    );
+}
+
+impl WgpuShared {
+    /// Drops the GPU surfaces and streams of the outputs `gone` selects, then
+    /// waits for the GPU to finish with them, so their `wl_surface`s can be
+    /// destroyed next. Streams stopped with a `stop_reason` are reported.
+    fn release_outputs(
+        &mut self,
+        gone: impl Fn(u32) -> bool,
+        stop_reason: impl Fn(u32) -> Option<&'static str>,
+    ) {
+        let surfaces = self.render_surfaces.len();
+        self.render_surfaces
+            .retain(|rs| !gone(rs.output_global_name));
+        self.video_streams.retain(|id, stream| {
+            let output_id = owner_output(*id);
+            let keep = !gone(output_id);
+            if !keep && let Some(reason) = stop_reason(output_id) {
+                stream.record_stopped(stream.current_entry.as_ref(), reason);
+            }
+            keep
+        });
+        self.layouts.retain(|id, _| !gone(*id));
+        if self.render_surfaces.len() != surfaces {
+            self.device.poll(wgpu::Maintain::Wait);
+        }
+    }
+
+    /// The runtime's map check: polls the config file, the env file, the map
+    /// file and the mapped videos, and notes what changed for the next frame.
+    /// Returns when to poll again: [`MAP_SETTLE`] after a map change seen
+    /// for the first time, so it is read once it holds still.
+    fn poll_video_map(&mut self) -> Duration {
+        self.reload_widget_config(false);
+        let env_changed = self
+            .video_map_state
+            .env_file
+            .as_mut()
+            .is_some_and(EnvFileWatcher::poll);
+        let state = &mut self.video_map_state;
+        let current_mtime = file_mtime(&state.map_file);
+        let mut next = MAP_CHECK_INTERVAL;
+        match state.mtime.observe(current_mtime, env_changed) {
+            None => next = MAP_SETTLE,
+            Some(map_changed) => {
+                state.changes.map |= map_changed || env_changed;
+                state.changes.env |= env_changed;
+            }
+        }
+        for output_id in self.replaced_video_files(true) {
+            let stale = &mut self.video_map_state.changes.stale;
+            if !stale.contains(&output_id) {
+                stale.push(output_id);
+            }
+        }
+        next
+    }
+
+    /// Re-resolves streams when a poll found the map file or env file changed,
+    /// and always for `remapped` outputs (name arrived or changed after their
+    /// stream was created).
+    fn maybe_reload_video_map(&mut self, outputs: &BTreeMap<u32, OutputSlot>, remapped: &[u32]) {
+        let MapChanges {
+            map: map_changed,
+            env: env_changed,
+            stale,
+        } = std::mem::take(&mut self.video_map_state.changes);
+        if !map_changed && remapped.is_empty() && stale.is_empty() {
+            return;
+        }
+        let reason = if env_changed {
+            "env-file"
+        } else if map_changed {
+            "map-reload"
+        } else if !stale.is_empty() {
+            "video-file-changed"
+        } else {
+            "output-named"
+        };
+        self.reload_streams(outputs, remapped, map_changed, env_changed, &stale, reason);
+    }
+
+    /// `reload` control command: re-reads the env file and the map now, ignoring
+    /// mtimes. `hard` also restarts every decoder whose mapping did not change.
+    fn force_reload(&mut self, outputs: &BTreeMap<u32, OutputSlot>, hard: bool) -> String {
+        if let Some(env_file) = self.video_map_state.env_file.as_mut() {
+            env_file.force_reload();
+        }
+        self.video_map_state.mtime.last = file_mtime(&self.video_map_state.map_file);
+        self.video_map_state.changes = MapChanges::default();
+        self.reload_widget_config(true);
+        let restart = if hard {
+            self.video_streams.keys().copied().collect()
+        } else {
+            self.replaced_video_files(false)
+        };
+        let outcomes = self.reload_streams(outputs, &[], true, true, &restart, "reload");
+        format!(
+            "{{\"ok\":true,\"hard\":{hard},\"outputs\":[{}]}}",
+            outcomes_json(&outcomes)
+        )
+    }
+
+    /// `set-video transient`: replaces (or drops) the override for `monitor`
+    /// and re-resolves every output against the map file plus overrides.
+    fn set_transient(
+        &mut self,
+        outputs: &BTreeMap<u32, OutputSlot>,
+        monitor: &str,
+        entry: Option<VideoMapEntry>,
+    ) -> String {
+        let transient = &mut self.video_map_state.transient;
+        let changed = match entry {
+            Some(entry) => {
+                println!(
+                    "[rendercore] transient override monitor={} video={}",
+                    display_text(monitor),
+                    entry.display_label()
+                );
+                transient.insert(monitor.to_string(), entry);
+                true
+            }
+            None => transient.remove(monitor).is_some(),
+        };
+        self.video_map_state.mtime.last = file_mtime(&self.video_map_state.map_file);
+        let outcomes = self.reload_streams(outputs, &[], true, false, &[], "transient");
+        format!(
+            "{{\"ok\":true,\"override_changed\":{changed},\"transient\":{},\"outputs\":[{}]}}",
+            self.video_map_state.transient.len(),
+            outcomes_json(&outcomes)
+        )
+    }
+
+    /// `reduce-motion on|off`: re-resolves every output, so each stream's
+    /// entry becomes (or stops being) a still of its first frame.
+    fn set_reduce_motion(&mut self, outputs: &BTreeMap<u32, OutputSlot>, on: bool) -> String {
+        let changed = self.video_map_state.reduce_motion != on;
+        self.video_map_state.reduce_motion = on;
+        let outcomes = if changed {
+            let all = outputs.keys().copied().collect::<Vec<_>>();
+            self.reload_streams(outputs, &all, false, false, &[], "reduce-motion")
+        } else {
+            Vec::new()
+        };
+        format!(
+            "{{\"ok\":true,\"reduce_motion\":{on},\"changed\":{changed},\"outputs\":[{}]}}",
+            outcomes_json(&outcomes)
+        )
+    }
+
+    /// `clear-transient`: drops every override, leaving the map file and env.
+    fn clear_transient(&mut self, outputs: &BTreeMap<u32, OutputSlot>) -> String {
+        let cleared = std::mem::take(&mut self.video_map_state.transient).len();
+        if cleared > 0 {
+            println!("[rendercore] cleared {cleared} transient override(s)");
+        }
+        self.video_map_state.mtime.last = file_mtime(&self.video_map_state.map_file);
+        let outcomes = self.reload_streams(outputs, &[], true, false, &[], "transient");
+        format!(
+            "{{\"ok\":true,\"cleared\":{cleared},\"outputs\":[{}]}}",
+            outcomes_json(&outcomes)
+        )
+    }
+
+    /// Streams whose video file was replaced since its decoder started. With
+    /// `debounce`, a new identity must be seen on two checks in a row (one `stat`
+    /// per stream per check), so a file still being written is left alone.
+    fn replaced_video_files(&mut self, debounce: bool) -> Vec<u32> {
+        let mut replaced = Vec::new();
+        for (output_id, stream) in &mut self.video_streams {
+            // An open in progress or a missing video's retry settles the
+            // identity itself.
+            let Some(entry) = stream
+                .current_entry
+                .as_ref()
+                .filter(|_| stream.incoming.is_none() && stream.missing.is_none())
+            else {
+                continue;
+            };
+            let Some(current) = VideoFileId::probe(&entry.video) else {
+                continue;
+            };
+            if current == stream.video_file {
+                stream.pending_video_file = None;
+                continue;
+            }
+            // Gone (mid-rename) is not a replacement yet; wait for the new file.
+            let Some(current) = current else {
+                stream.pending_video_file = None;
+                continue;
+            };
+            if debounce && stream.pending_video_file != Some(current) {
+                stream.pending_video_file = Some(current);
+                continue;
+            }
+            match stream.video_file {
+                Some(old) => println!(
+                    "[rendercore] output id={output_id} video file replaced: {} (mtime {} -> {}, size {} -> {}), restarting decoder",
+                    entry.video,
+                    old.mtime_secs(),
+                    current.mtime_secs(),
+                    old.size,
+                    current.size
+                ),
+                None => println!(
+                    "[rendercore] output id={output_id} video file appeared: {} (mtime {}, size {}), restarting decoder",
+                    entry.video,
+                    current.mtime_secs(),
+                    current.size
+                ),
+            }
+            replaced.push(*output_id);
+        }
+        replaced
+    }
+
+    /// Re-resolves every output (when the map or env changed) or just the
+    /// `remapped` ones, and restarts the decoders in `restart` even if their
+    /// entry is unchanged. Returns `(output, action, video)` per output looked at;
+    /// action is `unchanged`, `options`, `rebuilt`, `switched` or `restarted`.
+    fn reload_streams(
+        &mut self,
+        outputs: &BTreeMap<u32, OutputSlot>,
+        remapped: &[u32],
+        map_changed: bool,
+        env_changed: bool,
+        restart: &[u32],
+        reason: &str,
+    ) -> Vec<(String, &'static str, String)> {
+        let mut options_changed = false;
+        if map_changed {
+            // Decoder options (fps/speed/hwaccel) only change through the env file;
+            // when they do, every running decoder is restarted with the new values.
+            if env_changed {
+                self.video_map_state.env_map = env_video_map();
+                self.video_map_state.env_default = default_video_from_env();
+                let opts = capped_video_options(self.gpu.fps_cap);
+                options_changed = opts != self.video_map_state.video_options;
+                self.video_map_state.video_options = opts;
+            }
+
+            let state = &mut self.video_map_state;
+            let map_doc = state.last_good.reread(&state.map_file);
+            self.video_map_state.default_video = map_doc
+                .default
+                .clone()
+                .or_else(|| self.video_map_state.env_default.clone());
+            self.video_map_state.merged_map = merge_maps(
+                merge_maps(self.video_map_state.env_map.clone(), map_doc.entries),
+                self.video_map_state.transient.clone(),
+            );
+            self.video_map_state.record_loaded();
+        }
+
+        let mut outcomes = Vec::new();
+        for (output_id, out) in outputs {
+            let force_restart = restart.contains(output_id);
+            let restart_cells = restart
+                .iter()
+                .any(|id| cell_index(*id).is_some() && owner_output(*id) == *output_id);
+            if !map_changed && !remapped.contains(output_id) && !force_restart && !restart_cells {
+                continue;
+            }
+            if !self.video_streams.contains_key(output_id) {
+                continue;
+            }
+            let output_name = out.display_name();
+            let map_key = out.map_key(&self.video_map_state.merged_map);
+            let mut desired = self.video_map_state.resolve(&map_key);
+            // A layout's videos play in its cells; the output's own stream stays empty.
+            let layout_entry = desired.take_if(|entry| entry.layout.is_some());
+            if layout_entry.is_some() || self.layouts.contains_key(output_id) {
+                outcomes.extend(self.sync_cells(
+                    *output_id,
+                    &output_name,
+                    &map_key,
+                    layout_entry.as_ref(),
+                    restart,
+                    options_changed,
+                    reason,
+                ));
+            }
+            if layout_entry.is_some() {
+                if let Some(stream) = self.video_streams.get_mut(output_id) {
+                    stream.clear_for_layout(reason);
+                }
+                continue;
+            }
+            let video_label = desired
+                .as_ref()
+                .map(|entry| entry.video.clone())
+                .unwrap_or_default();
+            let forced = if force_restart {
+                Some("decoder restart (requested)")
+            } else if options_changed {
+                Some("decoder restart (fps/speed/hwaccel changed)")
+            } else {
+                None
+            };
+            if let Some(action) =
+                self.reload_stream(*output_id, &output_name, &map_key, desired, forced, reason)
+            {
+                outcomes.push((output_name, action, video_label));
+            }
+        }
+        outcomes
+    }
+
+    /// Points one stream (an output's own or a layout cell's) at `desired`.
+    /// `forced` names why the decoder restarts even if the entry is unchanged.
+    /// Returns the action for [`Self::reload_streams`], `None` without a stream.
+    fn reload_stream(
+        &mut self,
+        stream_id: u32,
+        label: &str,
+        map_key: &str,
+        desired: Option<VideoMapEntry>,
+        forced: Option<&'static str>,
+        reason: &str,
+    ) -> Option<&'static str> {
+        let (change, awaiting_present) = {
+            let stream = self.video_streams.get(&stream_id)?;
+            let change =
+                EntryChange::classify(stream.current_entry.as_ref(), desired.as_ref(), |entry| {
+                    self.stream_source_size(stream_id, entry)
+                });
+            (change, stream.awaiting_present)
+        };
+        if change == EntryChange::Unchanged && forced.is_none() {
+            // Switched by the map watcher (or an earlier reload) but not on
+            // screen yet.
+            return Some(if awaiting_present {
+                "loading"
+            } else {
+                "unchanged"
+            });
+        }
+        let stream = self.video_streams.get_mut(&stream_id)?;
+        record_stream_selected(stream_id, label, map_key, desired.as_ref());
+        stream.monitor = label.to_string();
+        let previous = std::mem::replace(&mut stream.current_entry, desired.clone());
+        let same_video = matches!(
+            (&previous, &desired),
+            (Some(previous), Some(entry)) if previous.video == entry.video
+        );
+        let classification = forced.unwrap_or_else(|| change.describe());
+        // The decoder keeps running: uniforms pick the options up next frame,
+        // and reorient_streams rebuilds the texture when its size changes.
+        if let Some(entry) = &desired
+            && matches!(change, EntryChange::Uniforms | EntryChange::Rebuild)
+            && forced.is_none()
+        {
+            println!(
+                "[rendercore] reloaded monitor={} (id={}) {classification}, options:{}",
+                label, stream_id, entry.options
+            );
+            return Some(if change == EntryChange::Rebuild {
+                "rebuilt"
+            } else {
+                "options"
+            });
+        }
+        let mut details = Vec::new();
+        if let Some(entry) = &desired {
+            details.push(("video", Value::Str(&entry.video)));
+        }
+        if let Some(entry) = &previous {
+            details.push(("previous", Value::Str(&entry.video)));
+        }
+        details.push(("reason", Value::Str(reason)));
+        event_file::record("wallpaper_change", Some(label), &details);
+        hooks::fire(
+            HookEvent::WallpaperChange,
+            HookContext {
+                monitor: Some(label),
+                video: desired.as_ref().map(|entry| entry.video.as_str()),
+                reason: Some(reason),
+                ..HookContext::default()
+            },
+        );
+        let mut opts = self.video_map_state.video_options;
+        opts.fps = quality::degraded_fps(opts.fps, stream.quality_level);
+        stream.decode_interval = opts.frame_interval();
+        stream.next_decode_at = Instant::now();
+        // Filled in from the file the new decoder opens.
+        stream.video_file = None;
+        stream.pending_video_file = None;
+        stream.missing = None;
+        stream.awaiting_ffmpeg = None;
+        stream.consecutive_failures = 0;
+        // A switch still warming up is replaced; what plays is still its previous entry.
+        let playing = match stream.incoming.take() {
+            Some(incoming) => incoming.previous,
+            None => previous,
+        };
+        let switch_timeout = self.video_map_state.switch_timeout;
+        match desired {
+            Some(entry) if !switch_timeout.is_zero() => {
+                println!(
+                    "[rendercore] reloaded monitor={} (id={}) video={} ({reason}, {classification}), warming up",
+                    label,
+                    stream_id,
+                    entry.display_label()
+                );
+                stream.open_incoming(stream_id, entry, playing, reason, opts);
+            }
+            Some(entry) => {
+                println!(
+                    "[rendercore] reloaded monitor={} (id={}) video={} ({reason}, {classification})",
+                    label,
+                    stream_id,
+                    entry.display_label()
+                );
+                stream.record_stopped(playing.as_ref(), reason);
+                stream.frame_source = FrameSource::None;
+                stream.open_incoming(stream_id, entry, None, reason, opts);
+            }
+            None => {
+                println!(
+                    "[rendercore] reloaded monitor={} (id={}) video=<none> (procedural fallback, {classification})",
+                    label, stream_id
+                );
+                stream.record_stopped(playing.as_ref(), reason);
+                stream.frame_source = FrameSource::None;
+            }
+        }
+        Some(if same_video { "restarted" } else { "switched" })
+    }
+
+    /// Brings an output's cell streams in line with its `layout=` entry
+    /// (`None` once it has none): cells that got a video start a decoder
+    /// sized to the cell, cells left empty stop theirs, and the rest reload
+    /// like an output's own stream, so changing one cell's path restarts
+    /// only that cell. Outcomes are named `MONITOR cell N`.
+    #[allow(clippy::too_many_arguments)]
+    fn sync_cells(
+        &mut self,
+        output_id: u32,
+        output_name: &str,
+        map_key: &str,
+        entry: Option<&VideoMapEntry>,
+        restart: &[u32],
+        options_changed: bool,
+        reason: &str,
+    ) -> Vec<(String, &'static str, String)> {
+        let old_cells = self.layouts.get(&output_id).map_or(0, Layout::cell_count);
+        match entry.and_then(|entry| entry.layout.clone()) {
+            Some(layout) => {
+                if self.layouts.get(&output_id) != Some(&layout) {
+                    log_layout(output_id, output_name, &layout);
+                }
+                self.layouts.insert(output_id, layout);
+            }
+            None => {
+                if self.layouts.remove(&output_id).is_some() {
+                    println!(
+                        "[rendercore] output={output_name} (id={output_id}) layout off ({reason})"
+                    );
+                }
+            }
+        }
+        let cells = self
+            .layouts
+            .get(&output_id)
+            .map_or(0, Layout::cell_count)
+            .max(old_cells);
+        let mut outcomes = Vec::new();
+        for cell in 0..cells {
+            let stream_id = cell_stream_id(output_id, cell);
+            let label = cell_label(output_name, cell);
+            let Some(desired) = entry.and_then(|entry| entry.cell_entry(cell)) else {
+                if let Some(stream) = self.video_streams.remove(&stream_id) {
+                    println!(
+                        "[rendercore] output={label} (id={stream_id}) cell left empty ({reason})"
+                    );
+                    stream.record_stopped(stream.current_entry.as_ref(), reason);
+                    outcomes.push((label, "switched", String::new()));
+                }
+                continue;
+            };
+            let video_label = desired.video.clone();
+            if !self.video_streams.contains_key(&stream_id) {
+                record_stream_selected(stream_id, &label, map_key, Some(&desired));
+                if self.add_cell_stream(stream_id, &label, desired, None) {
+                    outcomes.push((label, "switched", video_label));
+                }
+                continue;
+            }
+            let forced = if restart.contains(&stream_id) {
+                Some("decoder restart (requested)")
+            } else if options_changed {
+                Some("decoder restart (fps/speed/hwaccel changed)")
+            } else {
+                None
+            };
+            if let Some(action) =
+                self.reload_stream(stream_id, &label, map_key, Some(desired), forced, reason)
+            {
+                outcomes.push((label, action, video_label));
+            }
+        }
+        outcomes
+    }
+
+    /// Starts a cell's stream with a texture and decoder sized to the cell.
+    /// Returns whether it was added; the output needs a render surface first.
+    fn add_cell_stream(
+        &mut self,
+        stream_id: u32,
+        label: &str,
+        entry: VideoMapEntry,
+        start_at: Option<Instant>,
+    ) -> bool {
+        let Some(display_size) = self.stream_display_size(stream_id) else {
+            return false;
+        };
+        let size = match whole_frame_size(&entry, self.max_texture_dimension) {
+            Some(size) => size,
+            None => oriented_source_size(
+                self.stream_source_size(stream_id, &entry),
+                display_size,
+                entry.options,
+            ),
+        };
+        match init_video_stream(
+            &self.device,
+            &self.queue,
+            &self.program,
+            size,
+            display_size,
+            Some(entry),
+            self.video_map_state.video_options,
+            start_at,
+            None,
+            &stream_id,
+            label,
+        ) {
+            Ok(stream) => {
+                self.video_streams.insert(stream_id, stream);
+                true
+            }
+            Err(err) => {
+                eprintln!("[rendercore] warning: output={label} (id={stream_id}): {err}");
+                false
+            }
+        }
+    }
+
+    /// Recreates the texture and decoder of any stream whose shape no longer matches
+    /// its output's orientation (e.g. after a runtime rotation to portrait), or
+    /// whose auto quality level changed.
+    fn reorient_streams(&mut self, outputs: &BTreeMap<u32, OutputSlot>) -> Result<(), String> {
+        let surface_sizes = self
+            .video_streams
+            .keys()
+            .filter_map(|id| Some((*id, self.stream_display_size(*id)?)))
+            .collect::<Vec<_>>();
+        let base_fps = self.video_map_state.video_options.fps;
+        for (output_id, surface_size) in surface_sizes {
+            let Some(stream) = self.video_streams.get(&output_id) else {
+                continue;
+            };
+            let options = stream
+                .current_entry
+                .as_ref()
+                .map(|entry| entry.options)
+                .unwrap_or_default();
+            let layout = cell_index(output_id).and(self.layouts.get(&owner_output(output_id)));
+            let output_name = match layout {
+                Some(_) => stream.monitor.clone(),
+                None => output_label(outputs, output_id),
+            };
+            let level = self.auto_quality.level(&output_name);
+            let frame_size = stream
+                .current_entry
+                .as_ref()
+                .and_then(|entry| whole_frame_size(entry, self.max_texture_dimension));
+            let desired = match frame_size {
+                // The whole frame, in the video's own orientation.
+                Some(size) => quality::degraded_source_size(size, level, base_fps),
+                None => {
+                    let mut entry_size =
+                        entry_base_size(self.base_source_size, self.max_texture_dimension, options);
+                    if let Some(layout) = layout {
+                        entry_size = cell_source_size(entry_size, layout);
+                    }
+                    let base_size = quality::degraded_source_size(entry_size, level, base_fps);
+                    oriented_source_size(base_size, surface_size, options)
+                }
+            };
+            let current = (stream.source_width, stream.source_height);
+            if current == desired && stream.quality_level == level {
+                continue;
+            }
+            let reason = if stream.quality_level == level && frame_size.is_some() {
+                println!(
+                    "[rendercore] output={} (id={}) {} -> whole frame {}x{}",
+                    output_name,
+                    output_id,
+                    options.crop.map_or_else(
+                        || format!("fit={}", options.fit.as_str()),
+                        |crop| format!("crop {crop}")
+                    ),
+                    desired.0,
+                    desired.1
+                );
+                if options.crop.is_some() {
+                    "crop"
+                } else {
+                    "fit"
+                }
+            } else if stream.quality_level == level && current != (desired.1, desired.0) {
+                println!(
+                    "[rendercore] output={} (id={}) quality preset {} -> source {}x{}",
+                    output_name,
+                    output_id,
+                    options.quality.map_or("default", QualityPreset::as_str),
+                    desired.0,
+                    desired.1
+                );
+                "quality-preset"
+            } else if stream.quality_level != level {
+                println!(
+                    "[rendercore] output={} (id={}) quality level {} -> source {}x{} fps={}",
+                    output_name,
+                    output_id,
+                    level,
+                    desired.0,
+                    desired.1,
+                    quality::degraded_fps(base_fps, level)
+                );
+                "auto-quality"
+            } else {
+                println!(
+                    "[rendercore] output={} (id={}) orientation changed -> source {}x{}",
+                    output_name, output_id, desired.0, desired.1
+                );
+                "orientation"
+            };
+            stream.record_stopped(stream.current_entry.as_ref(), reason);
+            let current_entry = stream.current_entry.clone();
+            let previous_error = stream.last_error.clone();
+            let previous_repeats = stream.last_error_repeats;
+            let (start_at, fade) = (stream.start_at, stream.fade);
+            let mut video_options = self.video_map_state.video_options;
+            video_options.fps = quality::degraded_fps(base_fps, level);
+            let mut stream = init_video_stream(
+                &self.device,
+                &self.queue,
+                &self.program,
+                desired,
+                surface_size,
+                current_entry,
+                video_options,
+                start_at,
+                None,
+                &output_id,
+                &output_name,
+            )?;
+            stream.quality_level = level;
+            stream.fade = fade;
+            if stream.last_error.is_none() {
+                stream.last_error = previous_error;
+                stream.last_error_repeats = previous_repeats;
+            }
+            self.video_streams.insert(output_id, stream);
+        }
+        Ok(())
+    }
+
+    /// The render clock, with the `utc` one when some stream follows it.
+    fn read_clock(&mut self) -> ClockReading {
+        let utc = self
+            .video_streams
+            .values()
+            .any(|stream| stream.timebase() == Timebase::Utc);
+        self.render_clock.read(utc)
+    }
+
+    /// Freezes the streams of outputs paused by name and lets the others run.
+    fn freeze_streams(&mut self, paused: impl Fn(u32) -> bool) {
+        let now = self.read_clock();
+        for (output_id, stream) in &mut self.video_streams {
+            match (paused(owner_output(*output_id)), stream.frozen_at) {
+                (true, None) => stream.frozen_at = Some(now.get(stream.timebase())),
+                (false, Some(_)) => {
+                    stream.frozen_at = None;
+                    stream.next_decode_at = Instant::now();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Points every stream at the decoder it should use: a stream whose decoder
+    /// key matches a lower output's drops its own decoder and samples that
+    /// output's texture; one whose key no longer matches (an entry or its
+    /// options changed, the leader went away) goes back to decoding itself.
+    fn regroup_decoders(&mut self) {
+        let keys = self
+            .video_streams
+            .iter()
+            .map(|(output_id, stream)| {
+                let key = self.share_decoders.then(|| stream.decoder_key()).flatten();
+                (*output_id, key)
+            })
+            .collect::<Vec<_>>();
+        let leaders = decoder_leaders(&keys);
+        for (output_id, _) in keys {
+            let target = leaders.get(&output_id).and_then(|leader_id| {
+                let leader = self.video_streams.get(leader_id)?;
+                Some(SharedTexture {
+                    leader: *leader_id,
+                    leader_monitor: leader.monitor.clone(),
+                    serial: leader.texture_serial,
+                    texture: leader.source_texture.clone(),
+                })
+            });
+            let Some(stream) = self.video_streams.get_mut(&output_id) else {
+                continue;
+            };
+            let current = stream
+                .shared_from
+                .as_ref()
+                .map(|shared| (shared.leader, shared.serial));
+            let rebind = current != target.as_ref().map(|shared| (shared.leader, shared.serial));
+            match target {
+                // A restart (hard reload, orientation) may have opened a
+                // decoder for a stream that is still a follower.
+                Some(shared) if rebind || stream.frame_source.is_video() => {
+                    if current.is_none() {
+                        println!(
+                            "[rendercore] output={} (id={}) shares the decoder of output={} (id={})",
+                            stream.monitor, output_id, shared.leader_monitor, shared.leader
+                        );
+                    }
+                    stream.record_stopped(stream.current_entry.as_ref(), "decoder-shared");
+                    stream.frame_source = FrameSource::None;
+                    stream.bind_group = frame_bind_group(
+                        &self.device,
+                        &self.program,
+                        &shared.texture,
+                        &stream.uniform_buffer,
+                        stream.filter,
+                    );
+                    stream.drawn_as = None;
+                    stream.shared_from = Some(shared);
+                }
+                Some(_) => {}
+                None if rebind => self.stop_sharing(output_id),
+                None => {}
+            }
+        }
+    }
+
+    /// Rebinds a follower to its own texture, seeded with the frame it was
+    /// showing, and reopens its decoder unless a switch is already warming one.
+    fn stop_sharing(&mut self, output_id: u32) {
+        let Some(stream) = self.video_streams.get_mut(&output_id) else {
+            return;
+        };
+        let Some(shared) = stream.shared_from.take() else {
+            return;
+        };
+        stream.bind_group = frame_bind_group(
+            &self.device,
+            &self.program,
+            &stream.source_texture,
+            &stream.uniform_buffer,
+            stream.filter,
+        );
+        stream.drawn_as = None;
+        if shared.texture.size() == stream.source_texture.size() {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("kitsune-rendercore-unshare-encoder"),
+                });
+            encoder.copy_texture_to_texture(
+                shared.texture.as_image_copy(),
+                stream.source_texture.as_image_copy(),
+                stream.source_texture.size(),
+            );
+            self.queue.submit(Some(encoder.finish()));
+            stream.mips_dirty = stream.mip_chain.is_some();
+        }
+        println!(
+            "[rendercore] output={} (id={}) stops sharing the decoder of output={} (id={})",
+            stream.monitor, output_id, shared.leader_monitor, shared.leader
+        );
+        stream.next_decode_at = Instant::now();
+        let Some(entry) = stream
+            .current_entry
+            .clone()
+            .filter(|_| stream.incoming.is_none() && !stream.frame_source.is_video())
+        else {
+            return;
+        };
+        let mut opts = self.video_map_state.video_options;
+        opts.fps = quality::degraded_fps(opts.fps, stream.quality_level);
+        stream.open_incoming(output_id, entry, None, "decoder-unshared", opts);
+    }
+
+    /// Whether the output's texture holds a still frame with nothing more to
+    /// extract: it stays as presented until the entry or its file changes.
+    /// A particle overlay keeps it moving, and a clock (shown, or still on
+    /// screen after being turned off) keeps it ticking.
+    fn shows_still(&self, output_id: u32) -> bool {
+        if self.clock_shown(output_id)
+            || self
+                .render_surfaces
+                .iter()
+                .find(|rs| rs.output_global_name == output_id)
+                .and_then(|rs| rs.clock.as_ref())
+                .is_some_and(|layer| layer.shown.is_some())
+        {
+            return false;
+        }
+        let settled = self.video_streams.get(&output_id).is_some_and(|stream| {
+            stream.incoming.is_none()
+                && stream.missing.is_none()
+                && stream.awaiting_ffmpeg.is_none()
+                && !stream.fading()
+                && stream
+                    .current_entry
+                    .as_ref()
+                    .is_none_or(|entry| entry.options.overlay == Overlay::None)
+        });
+        settled
+            && self
+                .video_streams
+                .get(&self.decoding_output(output_id))
+                .is_some_and(|stream| stream.frame_source.is_still())
+    }
+
+    /// The decoders the configured entries need, at their configured size and
+    /// rate: one per group of outputs that can share (`KRC_SHARE_DECODERS`),
+    /// none for stills or videos that are missing.
+    fn stream_loads(&self) -> Vec<StreamLoad> {
+        let options = self.video_map_state.video_options;
+        let mut loads = Vec::<(StreamLoad, Option<Trim>)>::new();
+        for (stream_id, stream) in &self.video_streams {
+            let Some(entry) = stream.current_entry.as_ref().filter(|entry| {
+                entry.options.still.is_none()
+                    && stream.missing.is_none()
+                    && stream.awaiting_ffmpeg.is_none()
+            }) else {
+                continue;
+            };
+            let size = self.stream_source_size(*stream_id, entry);
+            let leader = loads.iter_mut().find(|(load, trim)| {
+                self.share_decoders
+                    && load.video == entry.video
+                    && load.size == size
+                    && *trim == entry.options.trim
+            });
+            if let Some((leader, _)) = leader {
+                leader.shared_with.push(stream.monitor.clone());
+                continue;
+            }
+            let decode = match hw_decode::verdict(&entry.video, options.hwaccel) {
+                HwDecode::Hardware(_) => DecodePath::Hardware,
+                HwDecode::Pending => DecodePath::Pending,
+                _ => DecodePath::Software,
+            };
+            let load = StreamLoad {
+                output: stream.monitor.clone(),
+                shared_with: Vec::new(),
+                video: entry.video.clone(),
+                size,
+                fps: options.fps,
+                decode,
+                max_level: quality::max_level(options.fps, size),
+            };
+            loads.push((load, entry.options.trim));
+        }
+        loads.into_iter().map(|(load, _)| load).collect()
+    }
+
+    /// Re-estimates the headroom when the decoders the map asks for changed
+    /// (startup, a reload, a finished hwaccel probe), logs the verdict, and
+    /// with `KRC_AUTO_QUALITY` sets the level each output starts at from it.
+    fn check_headroom(&mut self, now: Instant) {
+        if now < self.next_headroom_check {
+            return;
+        }
+        self.next_headroom_check = now + HEADROOM_CHECK_INTERVAL;
+        let streams = self.stream_loads();
+        if self.headroom.is_some() && streams == self.headroom_streams {
+            return;
+        }
+        let machine = MachineFacts::detect(self.share_decoders);
+        let estimate = headroom::estimate(&streams, &machine);
+        // Costed as software until the probes finish; the verdict is logged
+        // and acted on once they have.
+        if !estimate.pending && !streams.is_empty() {
+            log_headroom(&estimate, &streams);
+            let floors = headroom::plan_levels(&streams, &machine);
+            let reason = format!(
+                "headroom {}: decoders need ~{:.1} of {:.0} cores",
+                estimate.verdict.as_str(),
+                estimate.need_cores,
+                estimate.capacity_cores
+            );
+            let base_fps = self.video_map_state.video_options.fps;
+            for load in &streams {
+                for output in std::iter::once(&load.output).chain(&load.shared_with) {
+                    let floor = floors
+                        .iter()
+                        .find(|(name, _)| name == output)
+                        .map_or(0, |(_, level)| *level);
+                    if let Some(change) =
+                        self.auto_quality
+                            .set_floor(output, floor, load.max_level, &reason, now)
+                    {
+                        // Applied by reorient_streams right after.
+                        quality::record_change(&change, base_fps, load.size);
+                    }
+                }
+            }
+        }
+        self.headroom_streams = streams;
+        self.headroom = Some(estimate);
+    }
+
+    /// Unrotated source size for a stream's entry; see [`stream_source_size`].
+    fn stream_source_size(&self, stream_id: u32, entry: &VideoMapEntry) -> (u32, u32) {
+        stream_source_size(
+            self.base_source_size,
+            self.max_texture_dimension,
+            &self.layouts,
+            stream_id,
+            entry,
+        )
+    }
 
-        let surface = unsafe {
-            instance
-                .create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
-                    raw_display_handle,
-                    raw_window_handle,
-                })
-                .map_err(|err| format!("wgpu create_surface_unsafe failed: {err}"))?
-        };
+    /// Size a stream is shown at, in the orientation the user sees: its
+    /// output's, or its cell's share of it.
+    fn stream_display_size(&self, stream_id: u32) -> Option<(u32, u32)> {
+        let owner = owner_output(stream_id);
+        let rs = self
+            .render_surfaces
+            .iter()
+            .find(|rs| rs.output_global_name == owner)?;
+        let size = (rs.width, rs.height);
+        Some(match cell_index(stream_id).zip(self.layouts.get(&owner)) {
+            Some((cell, layout)) => {
+                let (x0, y0, x1, y1) = cell_rect(layout, cell, size);
+                (x1 - x0, y1 - y0)
+            }
+            None => size,
+        })
+    }
 
-        let caps = surface.get_capabilities(&adapter);
-        if caps.formats.is_empty() {
-            return Err("wgpu surface has no supported formats".to_string());
-        }
-        let format = caps
-            .formats
+    /// Fills a stream's uniforms for drawing it over a `display_size` area (a
+    /// whole output or one layout cell) of a buffer stored with
+    /// `buffer_transform`. `clock` is the render clock, shared by every
+    /// output so their effects stay in step. Returns what was written.
+    fn write_uniform(
+        &mut self,
+        stream_id: u32,
+        display_size: (u32, u32),
+        buffer_transform: OutputTransform,
+        clock: ClockReading,
+        now: Instant,
+    ) -> Option<FrameUniform> {
+        let aspect = (display_size.0 as f32 / (display_size.1.max(1) as f32)).max(0.0001);
+        let waiting = [stream_id, self.decoding_output(stream_id)]
             .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(caps.formats[0]);
-        let present_mode = if caps.present_modes.contains(&wgpu::PresentMode::Mailbox) {
-            wgpu::PresentMode::Mailbox
+            .any(|id| {
+                self.video_streams
+                    .get(id)
+                    .is_some_and(VideoStream::awaiting_first_frame)
+            });
+        let fade_in = self.fade_in;
+        let owner = self
+            .render_surfaces
+            .iter()
+            .find(|rs| rs.output_global_name == owner_output(stream_id));
+        let buffer_scale = owner.map_or(1, |rs| rs.buffer_scale.max(1));
+        let color = owner.and_then(|rs| rs.color.as_ref());
+        let gamut = color.map_or(IDENTITY_GAMUT, ColorPipeline::shader_matrix);
+        let gamma = color.and_then(|color| color.transfer.exponent());
+        let stream = self.video_streams.get_mut(&stream_id)?;
+        let brightness = stream.fade_brightness(waiting, fade_in, now);
+        let options = stream
+            .current_entry
+            .as_ref()
+            .map(|entry| entry.options)
+            .unwrap_or_default();
+        let (crop, fit) = match stream.integer_region() {
+            Some(region) => {
+                let (_, _, w, h) = region.0;
+                let shown = if options.rotate.swaps_axes() {
+                    (h, w)
+                } else {
+                    (w, h)
+                };
+                (region_uv(region), integer_fit(shown, display_size))
+            }
+            None => (
+                crop_window(stream.crop_rect(), display_size, options),
+                [0.0, 0.0, 1.0, 1.0],
+            ),
+        };
+        let box_offset = if self.program.downscale == DownscaleMode::Simple {
+            // Only the window's texels land on the output, within the
+            // part of it the picture covers.
+            let [x, y] = box_filter_offset(
+                (
+                    (stream.source_width as f32 * crop[2]).round() as u32,
+                    (stream.source_height as f32 * crop[3]).round() as u32,
+                ),
+                (
+                    (display_size.0 as f32 * fit[2]).round() as u32,
+                    (display_size.1 as f32 * fit[3]).round() as u32,
+                ),
+                options,
+            );
+            [x * crop[2], y * crop[3]]
         } else {
-            wgpu::PresentMode::Fifo
+            [0.0, 0.0]
         };
-        let alpha_mode = caps
-            .alpha_modes
-            .iter()
-            .copied()
-            .find(|m| *m == wgpu::CompositeAlphaMode::Auto)
-            .unwrap_or(caps.alpha_modes[0]);
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format,
-            width,
-            height,
-            present_mode,
-            alpha_mode,
-            view_formats: vec![format],
-            desired_maximum_frame_latency: 2,
+        // Ambient motion runs on the f64 clock: its period is the user's, so
+        // it cannot share the shader wrap, and f64 stays exact for years.
+        let clock = stream.frozen_at.unwrap_or(clock.get(stream.timebase()));
+        let time_sec = shader_time(clock);
+        let (zoom, motion_offset) = ambient_motion(
+            options,
+            clock.as_secs_f64(),
+            ambient_phase(&stream.monitor, self.ambient_seed),
+        );
+        let uniform = FrameUniform {
+            time_sec,
+            aspect,
+            rotate_quarters: options.rotate.quarter_turns(),
+            flip_bits: options.flip.bits(),
+            box_offset,
+            motion_offset,
+            zoom,
+            buffer_transform: buffer_transform.wire_value(),
+            brightness,
+            overlay: particles::shader_kind(options.overlay),
+            crop,
+            fit,
+            gamut,
+            transfer: u32::from(gamma.is_some()),
+            gamma: gamma.unwrap_or(1.0),
+            _pad2: [0.0; 2],
+            overlay_params: if options.overlay == Overlay::None {
+                [0.0; 4]
+            } else {
+                let params = options.overlay_params;
+                let px = buffer_scale as f32 / display_size.1.max(1) as f32;
+                [params.density(), params.speed(), params.wind(), px]
+            },
         };
-        surface.configure(&device, &config);
-        render_surfaces.push(RenderSurface {
-            output_global_name: slot.output_global_name,
-            width,
-            height,
-            surface,
-            config,
-        });
+        self.queue
+            .write_buffer(&stream.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        Some(uniform)
     }
-    let surface_format = render_surfaces
-        .first()
-        .map(|s| s.config.format)
-        .ok_or_else(|| "no render surfaces created for outputs".to_string())?;
-    let program = init_render_program(&device, surface_format)?;
-    let source_size = choose_source_resolution(adapter_limits.max_texture_dimension_2d);
-    println!(
-        "[rendercore] source texture selected={}x{} (max_texture_dimension_2d={})",
-        source_size.0, source_size.1, adapter_limits.max_texture_dimension_2d
-    );
-    let video_options = VideoOptions::from_env();
-    let map_file = map_file_path_from_env();
-    let env_map = std::env::var("KRC_VIDEO_MAP")
-        .ok()
-        .map(|v| parse_video_map_env(&v))
-        .unwrap_or_default();
-    let file_map = parse_video_map_file(&map_file);
-    let merged_map = merge_maps(env_map.clone(), file_map);
-    let last_mtime = std::fs::metadata(&map_file)
-        .ok()
-        .and_then(|m| m.modified().ok());
-    let video_map_state = VideoMapState {
-        map_file,
-        default_video: std::env::var("KRC_VIDEO_DEFAULT")
-            .ok()
-            .or_else(|| std::env::var("KRC_VIDEO").ok()),
-        env_map,
-        merged_map,
-        last_mtime,
-        last_reload_check: Instant::now(),
-        reload_interval: Duration::from_millis(1000),
-    };
-    let mut video_streams = BTreeMap::new();
-    for (output_id, out) in outputs {
-        let output_name = out
-            .name
-            .clone()
-            .unwrap_or_else(|| format!("wl-output-{output_id}"));
-        let selected_video = video_map_state
-            .merged_map
-            .get(&output_name)
-            .cloned()
-            .or_else(|| video_map_state.default_video.clone());
-        let stream = init_video_stream(
-            &device,
-            &queue,
-            &program,
-            source_size,
-            selected_video,
-            video_options,
-            output_id,
-            &output_name,
-        )?;
-        video_streams.insert(*output_id, stream);
+
+    /// How a stream drawn with `uniform` looks: `time_sec` only matters to
+    /// an animated shader or under a particle overlay.
+    fn drawn_frame(&self, stream_id: u32, mut uniform: FrameUniform) -> DrawnFrame {
+        if !self.program.animated && uniform.overlay == 0 {
+            uniform.time_sec = 0.0;
+        }
+        let decoder = self.decoding_output(stream_id);
+        let source = self.video_streams.get(&decoder);
+        DrawnFrame {
+            decoder,
+            texture_serial: source.map_or(0, |stream| stream.texture_serial),
+            uploads: source.map_or(0, |stream| stream.uploads),
+            uniform,
+        }
     }
 
-    Ok(WgpuShared {
-        _instance: instance,
-        _adapter: adapter,
-        device,
-        queue,
-        render_surfaces,
-        program,
-        started_at: Instant::now(),
-        video_streams,
-        video_map_state,
-        uploaded_video_frames: 0,
-    })
-}
+    /// The draws of `plan` whose entry has an overlay, with the particles
+    /// each one gets.
+    fn overlay_draws(&self, plan: &OutputFrame) -> Vec<(u32, Option<Rect>, u32)> {
+        plan.draws
+            .iter()
+            .filter_map(|(stream_id, rect)| {
+                let options = self
+                    .video_streams
+                    .get(stream_id)?
+                    .current_entry
+                    .as_ref()?
+                    .options;
+                let size = rect.map_or(plan.display_size, |(x0, y0, x1, y1)| (x1 - x0, y1 - y0));
+                let instances = particles::instance_count(options, size);
+                (instances > 0).then_some((*stream_id, *rect, instances))
+            })
+            .collect()
+    }
 
-impl WgpuShared {
-    fn maybe_reload_video_map(&mut self, outputs: &BTreeMap<u32, OutputSlot>) {
-        if self.video_map_state.last_reload_check.elapsed() < self.video_map_state.reload_interval {
-            return;
+    /// Whether the output shows the clock: its entry's `clock=`, else
+    /// `[widgets.clock] enabled`. A layout's cells all carry its options.
+    fn clock_shown(&self, output_id: u32) -> bool {
+        let entry = self
+            .drawn_streams(output_id)
+            .first()
+            .and_then(|id| self.video_streams.get(id))
+            .and_then(|stream| stream.current_entry.as_ref())
+            .and_then(|entry| entry.options.clock);
+        self.clock.config.shown(entry)
+    }
+
+    /// Brings the output's clock layer up to date. Returns what this frame
+    /// shows of it, and the display rects that differ from its last present:
+    /// the old and new place of the text, once a second at most.
+    fn update_clock(
+        &mut self,
+        output_id: u32,
+        display_size: (u32, u32),
+        buffer_transform: OutputTransform,
+    ) -> (Option<(String, Rect)>, Vec<Rect>) {
+        let shown = self.clock_shown(output_id);
+        let Some(rs) = self
+            .render_surfaces
+            .iter_mut()
+            .find(|rs| rs.output_global_name == output_id)
+        else {
+            return (None, Vec::new());
+        };
+        let buffer_scale = rs.buffer_scale.max(1) as u32;
+        let target = if shown {
+            let text = self.clock.text().to_string();
+            let layer = rs.clock.get_or_insert_with(|| TextLayer::new(&self.device));
+            layer
+                .set_text(&self.device, &self.queue, &self.text_program, &text)
+                .map(|size| {
+                    let config = &self.clock.config;
+                    let rect = config.placement.rect(size, display_size, buffer_scale);
+                    layer.write_uniform(
+                        &self.queue,
+                        rect,
+                        display_size,
+                        buffer_transform,
+                        config.style,
+                    );
+                    (text, rect)
+                })
+        } else {
+            None
+        };
+        let on_screen = rs.clock.as_ref().and_then(|layer| layer.shown.clone());
+        if !shown && on_screen.is_none() {
+            rs.clock = None;
         }
-        self.video_map_state.last_reload_check = Instant::now();
+        if target == on_screen {
+            return (target, Vec::new());
+        }
+        let changed = on_screen
+            .map(|(_, rect)| rect)
+            .into_iter()
+            .chain(target.as_ref().map(|(_, rect)| *rect))
+            .collect();
+        (target, changed)
+    }
 
-        let current_mtime = std::fs::metadata(&self.video_map_state.map_file)
-            .ok()
-            .and_then(|m| m.modified().ok());
-        if current_mtime == self.video_map_state.last_mtime {
+    /// Re-reads `[widgets.clock]` when config.toml changed, or always with
+    /// `force`, so the clock follows the file without a restart.
+    fn reload_widget_config(&mut self, force: bool) {
+        let Some(path) = &self.config_file else {
+            return;
+        };
+        let mtime = file_mtime(path);
+        if !force && mtime == self.config_mtime {
             return;
         }
-        self.video_map_state.last_mtime = current_mtime;
+        self.config_mtime = mtime;
+        let config = ClockConfig::load(path);
+        if config != self.clock.config {
+            println!("[rendercore] clock widget: {config}");
+            self.clock.set_config(config);
+        }
+    }
 
-        let file_map = parse_video_map_file(&self.video_map_state.map_file);
-        self.video_map_state.merged_map =
-            merge_maps(self.video_map_state.env_map.clone(), file_map);
+    /// The streams drawn on an output: its layout's filled cells, else its own.
+    fn drawn_streams(&self, output_id: u32) -> Vec<u32> {
+        match self.layouts.get(&output_id) {
+            Some(layout) => (0..layout.cell_count())
+                .map(|cell| cell_stream_id(output_id, cell))
+                .filter(|id| self.video_streams.contains_key(id))
+                .collect(),
+            None => vec![output_id],
+        }
+    }
 
-        for (output_id, out) in outputs {
-            let output_name = out
-                .name
-                .clone()
-                .unwrap_or_else(|| format!("wl-output-{output_id}"));
-            let desired = self
-                .video_map_state
-                .merged_map
-                .get(&output_name)
-                .cloned()
-                .or_else(|| self.video_map_state.default_video.clone());
-            let Some(stream) = self.video_streams.get_mut(output_id) else {
+    /// The output whose decoder fills `output_id`'s texture.
+    fn decoding_output(&self, output_id: u32) -> u32 {
+        self.video_streams
+            .get(&output_id)
+            .and_then(|stream| stream.shared_from.as_ref())
+            .map_or(output_id, |shared| shared.leader)
+    }
+
+    /// Whether the stream's frames come from a decoder opened for its
+    /// current entry: its own, or the leader's it shares, swapped in and
+    /// not failed.
+    fn shows_current_entry(&self, stream_id: u32) -> bool {
+        let decoder = self.video_streams.get(&self.decoding_output(stream_id));
+        decoder.is_some_and(|stream| {
+            stream.incoming.is_none()
+                && stream.start_at.is_none()
+                && !matches!(stream.frame_source, FrameSource::None)
+        })
+    }
+
+    /// Each cell of an output's layout, for status.
+    fn cell_status(&self, output_id: u32, layout: &Layout) -> Vec<CellStatus> {
+        (0..layout.cell_count())
+            .map(|cell| {
+                let stream_id = cell_stream_id(output_id, cell);
+                let stream = self.video_streams.get(&stream_id);
+                let decoder = self.video_streams.get(&self.decoding_output(stream_id));
+                CellStatus {
+                    cell: cell + 1,
+                    stream_kind: match (stream, decoder.map(|s| &s.frame_source)) {
+                        (None, _) => "empty",
+                        (_, Some(source)) if source.is_video() => "video",
+                        (_, Some(source)) if source.is_still() => "still",
+                        _ => "procedural",
+                    },
+                    source: layout.cell(cell).map(str::to_string),
+                    last_error: stream
+                        .and_then(|s| s.last_error.as_ref())
+                        .map(|(_, err)| err.clone()),
+                }
+            })
+            .collect()
+    }
+
+    /// Level and description of an output's auto quality, for status.
+    fn quality_status(&self, output_id: u32) -> (u8, String) {
+        let stream = self.video_streams.get(&output_id);
+        let level = stream.map(|stream| stream.quality_level).unwrap_or(0);
+        let base_fps = self.video_map_state.video_options.fps;
+        let base_size = stream
+            .and_then(|stream| stream.current_entry.as_ref())
+            .map_or(self.base_source_size, |entry| {
+                self.stream_source_size(output_id, entry)
+            });
+        (level, quality::describe_level(level, base_fps, base_size))
+    }
+
+    /// Re-resolves each output's color pipeline from its entry's `gamut=` and
+    /// what the compositor says about the output, and tags its surface with
+    /// the output's image description while the shader converts to it.
+    fn update_color(
+        &mut self,
+        outputs: &BTreeMap<u32, OutputSlot>,
+        layer_surfaces: &[LayerSurfaceSlot],
+    ) {
+        let gamuts = self
+            .render_surfaces
+            .iter()
+            .map(|rs| {
+                self.drawn_streams(rs.output_global_name)
+                    .first()
+                    .and_then(|id| self.video_streams.get(id))
+                    .and_then(|stream| stream.current_entry.as_ref())
+                    .map_or(Gamut::Auto, |entry| entry.options.gamut)
+            })
+            .collect::<Vec<_>>();
+        for (rs, gamut) in self.render_surfaces.iter_mut().zip(gamuts) {
+            let Some(out) = outputs.get(&rs.output_global_name) else {
                 continue;
             };
-            if stream.current_video == desired {
+            if gamut == Gamut::Native && rs.edid.is_none() {
+                rs.edid = Some(out.identity.name.as_deref().and_then(color::edid_primaries));
+            }
+            let pipeline =
+                ColorPipeline::resolve(gamut, out.colorimetry.as_ref(), rs.edid.flatten().as_ref());
+            let description = out.image_description.as_ref().filter(|_| pipeline.tagged);
+            if rs.tagged_with.as_ref() != description {
+                if let Some(color_surface) = layer_surfaces
+                    .iter()
+                    .find(|slot| slot.output_global_name == rs.output_global_name)
+                    .and_then(|slot| slot.color_surface.as_ref())
+                {
+                    match description {
+                        Some(description) => color_surface.set_image_description(
+                            description,
+                            wp_color_manager_v1::RenderIntent::Perceptual,
+                        ),
+                        None => color_surface.unset_image_description(),
+                    }
+                }
+                rs.tagged_with = description.cloned();
+            }
+            if rs.color.as_ref() == Some(&pipeline) {
                 continue;
             }
-            stream.current_video = desired.clone();
-            let opts = VideoOptions::from_env();
-            stream.decode_interval = Duration::from_secs_f32((1.0f32 / opts.fps as f32).max(0.001));
-            stream.next_decode_at = Instant::now();
-            stream.frame_source = if let Some(path) = desired {
-                println!(
-                    "[rendercore] reloaded monitor={} (id={}) video={}",
-                    output_name, output_id, path
-                );
-                FrameSource::from_video_path(path, stream.source_width, stream.source_height, opts)
-            } else {
+            // The sRGB passthrough every output starts with is not news.
+            if rs.color.is_some() || pipeline.source != "none" || pipeline.note.is_some() {
                 println!(
-                    "[rendercore] reloaded monitor={} (id={}) video=<none> (procedural fallback)",
-                    output_name, output_id
+                    "[rendercore] output={} (id={}) color {}",
+                    out.display_name(),
+                    rs.output_global_name,
+                    pipeline.summary()
                 );
-                FrameSource::None
-            };
+            }
+            rs.color = Some(pipeline);
+            rs.full_damage = true;
         }
     }
 
+    /// Draws the ready outputs whose pixels change. Returns the outputs it
+    /// presented and, with damage tracking, the ones skipped as unchanged.
     fn render_textured(
         &mut self,
-        frame_index: u64,
+        remapped: &[u32],
         outputs: &BTreeMap<u32, OutputSlot>,
         layer_surfaces: &[LayerSurfaceSlot],
         ready_outputs: &[u32],
-    ) -> Result<(), String> {
-        self.maybe_reload_video_map(outputs);
+        profiler: &mut FrameProfiler,
+    ) -> Result<(Vec<u32>, Vec<u32>), String> {
+        let t = profiler.start();
+        self.maybe_reload_video_map(outputs, remapped);
+        profiler.end(Phase::MapReload, t);
         if ready_outputs.is_empty() {
-            return Ok(());
+            return Ok((Vec::new(), Vec::new()));
         }
 
         for rs in &mut self.render_surfaces {
             let Some(out) = outputs.get(&rs.output_global_name) else {
                 continue;
             };
-            let configured_size = layer_surfaces
-                .iter()
-                .find(|slot| slot.output_global_name == rs.output_global_name)
-                .and_then(|slot| match (slot.configured_width, slot.configured_height) {
-                    (Some(width), Some(height)) if width > 0 && height > 0 => Some((width, height)),
-                    _ => None,
-                });
-            let (width, height) = configured_size.unwrap_or((
-                out.width.unwrap_or(1920).max(1),
-                out.height.unwrap_or(1080).max(1),
-            ));
-            if width != rs.width || height != rs.height {
-                rs.width = width;
-                rs.height = height;
-                rs.config.width = width;
-                rs.config.height = height;
-                rs.surface.configure(&self.device, &rs.config);
-            }
-        }
-
-        let mut acquired = Vec::new();
-        let should_render = |output_id: u32| {
-            ready_outputs
+            let slot = layer_surfaces
                 .iter()
-                .any(|candidate| *candidate == output_id)
-        };
-        for (idx, rs) in self.render_surfaces.iter_mut().enumerate() {
-            if !should_render(rs.output_global_name) {
+                .find(|slot| slot.output_global_name == rs.output_global_name);
+            let configured_size =
+                slot.and_then(
+                    |slot| match (slot.configured_width, slot.configured_height) {
+                        (Some(width), Some(height)) if width > 0 && height > 0 => {
+                            Some((width, height))
+                        }
+                        _ => None,
+                    },
+                );
+            // The configure size is surface-local; when the compositor names a
+            // preferred buffer scale and transform, buffers follow them instead
+            // of being scaled by the compositor.
+            let (preferred_scale, transform) = match (configured_size, slot) {
+                (Some(_), Some(slot)) => (
+                    slot.preferred_scale.unwrap_or(1),
+                    slot.preferred_transform.unwrap_or_default(),
+                ),
+                _ => (1, OutputTransform::Normal),
+            };
+            let surface_size = configured_size.unwrap_or(out.logical_size());
+            let fit = fit_surface(surface_size, preferred_scale, self.max_texture_dimension);
+            let ((width, height), scale) = (fit.size, fit.scale);
+            if width == rs.width
+                && height == rs.height
+                && scale == rs.buffer_scale
+                && transform == rs.buffer_transform
+            {
                 continue;
             }
-            let frame = match rs.surface.get_current_texture() {
-                Ok(frame) => frame,
-                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                    rs.surface.configure(&self.device, &rs.config);
-                    rs.surface.get_current_texture().map_err(|err| {
-                        format!("wgpu reacquire surface texture failed on output {idx}: {err}")
-                    })?
-                }
-                Err(wgpu::SurfaceError::Timeout) => {
-                    continue;
-                }
-                Err(wgpu::SurfaceError::OutOfMemory) => {
-                    return Err("wgpu surface out of memory".to_string());
-                }
-                Err(wgpu::SurfaceError::Other) => {
-                    continue;
-                }
+            if let Some(slot) = slot
+                && (scale, transform) != (rs.buffer_scale, rs.buffer_transform)
+            {
+                slot.surface.set_buffer_scale(scale);
+                slot.surface
+                    .set_buffer_transform(output_transform_to_wl(transform));
+                println!(
+                    "[rendercore] output={} (id={}) buffer {}x{} scale={} transform={} (compositor preference)",
+                    out.display_name(),
+                    rs.output_global_name,
+                    width,
+                    height,
+                    scale,
+                    transform.as_str()
+                );
+            }
+            rs.clamped = apply_surface_fit(
+                slot,
+                &format!(
+                    "output={} (id={})",
+                    out.display_name(),
+                    rs.output_global_name
+                ),
+                surface_size,
+                preferred_scale,
+                fit,
+                rs.clamped,
+                self.max_texture_dimension,
+            );
+            rs.width = width;
+            rs.height = height;
+            rs.buffer_scale = scale;
+            rs.buffer_transform = transform;
+            // The buffer is stored pre-transformed: axes swap for 90/270.
+            let (buffer_width, buffer_height) = if transform.swaps_axes() {
+                (height, width)
+            } else {
+                (width, height)
             };
-            acquired.push((rs.output_global_name, frame));
-        }
-
-        if acquired.is_empty() {
-            return Ok(());
+            rs.config.width = buffer_width;
+            rs.config.height = buffer_height;
+            rs.surface.configure(&self.device, &rs.config);
+            rs.full_damage = true;
         }
+        self.check_headroom(Instant::now());
+        self.reorient_streams(outputs)?;
+        self.regroup_decoders();
+        self.update_color(outputs, layer_surfaces);
 
         let now = Instant::now();
-        for output_id in ready_outputs {
-            let Some(stream) = self.video_streams.get_mut(output_id) else {
+        let reading = self.read_clock();
+        let switch_timeout = self.video_map_state.switch_timeout;
+        let open_timeout = self.video_map_state.open_timeout;
+        let mut decoding = Vec::new();
+        let ready_streams = ready_outputs
+            .iter()
+            .flat_map(|output_id| self.drawn_streams(*output_id))
+            .collect::<Vec<_>>();
+        for output_id in &ready_streams {
+            if let Some(stream) = self
+                .video_streams
+                .get_mut(output_id)
+                .filter(|stream| stream.frozen_at.is_none())
+            {
+                let mut options = self.video_map_state.video_options;
+                options.fps = quality::degraded_fps(options.fps, stream.quality_level);
+                stream.open_when_due(*output_id, now, options);
+            }
+            let decoder = self.decoding_output(*output_id);
+            if !decoding.contains(&decoder) {
+                decoding.push(decoder);
+            }
+        }
+        for output_id in &decoding {
+            let Some(stream) = self
+                .video_streams
+                .get_mut(output_id)
+                .filter(|stream| stream.frozen_at.is_none())
+            else {
                 continue;
             };
-            if now < stream.next_decode_at {
+            let switched = stream.poll_incoming(*output_id, switch_timeout, open_timeout);
+            let mut options = self.video_map_state.video_options;
+            options.fps = quality::degraded_fps(options.fps, stream.quality_level);
+            if !switched && !stream.decode_due(*output_id, now, reading, options) {
                 continue;
             }
-            if stream
-                .frame_source
-                .fill_next_frame(&mut stream.frame_pixels)
-            {
+            let t = profiler.start();
+            let work_started = self.auto_quality.is_enabled().then(Instant::now);
+            let filled = switched
+                || match stream
+                    .frame_source
+                    .fill_next_frame(&mut stream.frame_pixels)
+                {
+                    Ok(filled) => filled,
+                    Err(err) => {
+                        stream.decode_failed(*output_id, err, self.video_map_state.video_options);
+                        false
+                    }
+                };
+            profiler.end(Phase::Decode, t);
+            if filled && !stream.guard_flashes(*output_id, now) {
+                stream.next_decode_at = now + stream.decode_interval;
+                continue;
+            }
+            if filled {
+                // A switch's first frame was counted when it was handed over.
+                if !switched {
+                    stream.record_frame();
+                }
+                let t = profiler.start();
                 self.queue.write_texture(
                     wgpu::TexelCopyTextureInfo {
                         texture: &stream.source_texture,
@@ -805,40 +5225,274 @@ impl WgpuShared {
                         depth_or_array_layers: 1,
                     },
                 );
+                if profiler.is_enabled() {
+                    let name = stream_name(outputs, *output_id, stream);
+                    profiler.end_upload(&name, stream.frame_pixels.len() as u64, t);
+                }
+                if let Some(started) = work_started {
+                    let name = stream_name(outputs, *output_id, stream);
+                    let base_fps = self.video_map_state.video_options.fps;
+                    let base_size =
+                        stream
+                            .current_entry
+                            .as_ref()
+                            .map_or(self.base_source_size, |entry| {
+                                stream_source_size(
+                                    self.base_source_size,
+                                    self.max_texture_dimension,
+                                    &self.layouts,
+                                    *output_id,
+                                    entry,
+                                )
+                            });
+                    let max_level = quality::max_level(base_fps, base_size);
+                    if let Some(change) = self.auto_quality.record(
+                        &name,
+                        started.elapsed(),
+                        stream.decode_interval,
+                        max_level,
+                        Instant::now(),
+                    ) {
+                        // Applied by reorient_streams on the next frame.
+                        quality::record_change(&change, base_fps, base_size);
+                    }
+                }
                 self.uploaded_video_frames = self.uploaded_video_frames.wrapping_add(1);
+                stream.uploads = stream.uploads.wrapping_add(1);
+                stream.mips_dirty = true;
                 stream.next_decode_at = now + stream.decode_interval;
             }
         }
+        // A reload that only changed `filter=` leaves the texture alone; the
+        // bind group picks up the other sampler here.
+        for output_id in &ready_streams {
+            let Some(stream) = self.video_streams.get_mut(output_id) else {
+                continue;
+            };
+            let filter = stream
+                .current_entry
+                .as_ref()
+                .map_or(SampleFilter::Linear, |entry| entry.options.filter);
+            if filter == stream.filter {
+                continue;
+            }
+            let texture = stream
+                .shared_from
+                .as_ref()
+                .map_or(&stream.source_texture, |shared| &shared.texture);
+            stream.bind_group = frame_bind_group(
+                &self.device,
+                &self.program,
+                texture,
+                &stream.uniform_buffer,
+                filter,
+            );
+            stream.filter = filter;
+            stream.drawn_as = None;
+        }
+
+        // Uniforms go first: an output none of whose streams drew differently
+        // since its last present is skipped instead of presenting the same
+        // pixels again.
+        let mut planned = Vec::new();
+        let mut unchanged = Vec::new();
+        for output_id in ready_outputs {
+            let Some(rs) = self
+                .render_surfaces
+                .iter()
+                .find(|rs| rs.output_global_name == *output_id)
+            else {
+                continue;
+            };
+            // What the user sees; the buffer itself is stored pre-transformed.
+            let display_size = (rs.width, rs.height);
+            let buffer_size = (rs.config.width, rs.config.height);
+            let buffer_transform = rs.buffer_transform;
+            // A layout draws each cell into its part of the buffer; the
+            // cells without a video stay black.
+            let draws = match self.layouts.get(output_id) {
+                Some(layout) => (0..layout.cell_count())
+                    .map(|cell| {
+                        let rect = cell_rect(layout, cell, display_size);
+                        (cell_stream_id(*output_id, cell), Some(rect))
+                    })
+                    .filter(|(id, _)| self.video_streams.contains_key(id))
+                    .collect::<Vec<_>>(),
+                None => {
+                    if !self.video_streams.contains_key(output_id) {
+                        return Err(format!("missing video stream for output {output_id}"));
+                    }
+                    vec![(*output_id, None)]
+                }
+            };
+            let must_redraw = layer_surfaces
+                .iter()
+                .any(|slot| slot.output_global_name == *output_id && slot.must_redraw);
+            let full = !self.damage_tracking || rs.full_damage || must_redraw || rs.drawn != draws;
+            let mut damage = Vec::new();
+            let mut frames = Vec::new();
+            for (stream_id, rect) in &draws {
+                let size = rect.map_or(display_size, |(x0, y0, x1, y1)| (x1 - x0, y1 - y0));
+                let Some(uniform) =
+                    self.write_uniform(*stream_id, size, buffer_transform, reading, now)
+                else {
+                    continue;
+                };
+                let frame = self.drawn_frame(*stream_id, uniform);
+                let changed = self
+                    .video_streams
+                    .get(stream_id)
+                    .is_none_or(|stream| stream.drawn_as != Some(frame));
+                if changed && !full {
+                    let rect = rect.unwrap_or((0, 0, display_size.0, display_size.1));
+                    damage.push(buffer_rect(buffer_transform, display_size, rect));
+                }
+                frames.push((*stream_id, frame));
+            }
+            let (clock, clock_damage) =
+                self.update_clock(*output_id, display_size, buffer_transform);
+            if !full {
+                damage.extend(
+                    clock_damage
+                        .into_iter()
+                        .map(|rect| buffer_rect(buffer_transform, display_size, rect)),
+                );
+            }
+            if full {
+                damage = vec![(0, 0, buffer_size.0, buffer_size.1)];
+            }
+            damage.retain(|(x0, y0, x1, y1)| x1 > x0 && y1 > y0);
+            if damage.is_empty() {
+                unchanged.push(*output_id);
+                continue;
+            }
+            planned.push(OutputFrame {
+                output_id: *output_id,
+                display_size,
+                buffer_transform,
+                draws,
+                damage,
+                frames,
+                clock,
+            });
+        }
+        if self.damage_tracking {
+            for rs in &mut self.render_surfaces {
+                if unchanged.contains(&rs.output_global_name) {
+                    rs.damage.skipped += 1;
+                }
+            }
+        }
+
+        let mut acquired = Vec::new();
+        for mut plan in planned {
+            let Some((idx, rs)) = self
+                .render_surfaces
+                .iter_mut()
+                .enumerate()
+                .find(|(_, rs)| rs.output_global_name == plan.output_id)
+            else {
+                continue;
+            };
+            let t = profiler.start();
+            let frame = match rs.surface.get_current_texture() {
+                Ok(frame) => frame,
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                    rs.surface.configure(&self.device, &rs.config);
+                    plan.damage = vec![(0, 0, rs.config.width, rs.config.height)];
+                    rs.surface.get_current_texture().map_err(|err| {
+                        format!("wgpu reacquire surface texture failed on output {idx}: {err}")
+                    })?
+                }
+                Err(wgpu::SurfaceError::Timeout) => {
+                    if let Some(stream) = self.video_streams.get_mut(&rs.output_global_name) {
+                        stream.record_error(rs.output_global_name, "surface texture timeout");
+                    }
+                    continue;
+                }
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    return Err("wgpu surface out of memory".to_string());
+                }
+                Err(wgpu::SurfaceError::Other) => {
+                    if let Some(stream) = self.video_streams.get_mut(&rs.output_global_name) {
+                        stream.record_error(rs.output_global_name, "surface texture error");
+                    }
+                    continue;
+                }
+            };
+            if profiler.is_enabled() {
+                let name = outputs
+                    .get(&rs.output_global_name)
+                    .map(OutputSlot::display_name)
+                    .unwrap_or_default();
+                profiler.end_acquire(&name, t);
+            }
+            acquired.push((plan, frame));
+        }
 
-        let elapsed = self.started_at.elapsed().as_secs_f32();
+        if acquired.is_empty() {
+            return Ok((Vec::new(), unchanged));
+        }
 
+        let encode_started = profiler.start();
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("kitsune-rendercore-frame-encoder"),
             });
 
-        for (output_id, frame) in &acquired {
+        let mipgen_started = Instant::now();
+        let mut mipgen_runs = 0u64;
+        let drawn = acquired
+            .iter()
+            .flat_map(|(plan, _)| plan.draws.iter().map(|(id, _)| *id))
+            .collect::<Vec<_>>();
+        for output_id in &drawn {
+            let decoder = self.decoding_output(*output_id);
+            let Some(stream) = self.video_streams.get_mut(&decoder) else {
+                continue;
+            };
+            if let Some(chain) = &stream.mip_chain
+                && stream.mips_dirty
+            {
+                encode_mip_chain(&mut encoder, &self.program.mip_blit, chain);
+                stream.mips_dirty = false;
+                mipgen_runs += 1;
+            }
+        }
+        if mipgen_runs > 0 {
+            self.mipgen_runs = self.mipgen_runs.wrapping_add(mipgen_runs);
+            self.mipgen_time += mipgen_started.elapsed();
+        }
+
+        // Particles go over their stream's picture; an entry without an
+        // overlay adds no draw at all.
+        let overlays = acquired
+            .iter()
+            .map(|(plan, _)| self.overlay_draws(plan))
+            .collect::<Vec<_>>();
+        for ((_, frame), overlay) in acquired.iter().zip(&overlays) {
+            let format = frame.texture.format();
+            self.program.ensure_pipeline(&self.device, format);
+            if !overlay.is_empty() {
+                self.program.ensure_overlay_pipeline(&self.device, format);
+            }
+        }
+        for (plan, frame) in &acquired {
+            if plan.clock.is_some() {
+                self.text_program
+                    .ensure_pipeline(&self.device, frame.texture.format());
+            }
+        }
+        if let Some(timer) = self.overlay_timer.as_mut() {
+            for (output, elapsed) in timer.collect(&self.device) {
+                profiler.add_overlay_gpu(&output, elapsed);
+            }
+        }
+        for ((plan, frame), overlay) in acquired.iter().zip(&overlays) {
             let view = frame
                 .texture
                 .create_view(&wgpu::TextureViewDescriptor::default());
-            let aspect =
-                (frame.texture.width() as f32 / (frame.texture.height().max(1) as f32)).max(0.0001);
-            let uniform = FrameUniform {
-                time_sec: elapsed + frame_index as f32 * 0.0001,
-                aspect,
-                _pad: [0.0; 2],
-            };
-            self.queue.write_buffer(
-                &self.program.uniform_buffer,
-                0,
-                bytemuck::bytes_of(&uniform),
-            );
-            let bind_group = self
-                .video_streams
-                .get(output_id)
-                .map(|s| &s.bind_group)
-                .ok_or_else(|| format!("missing video stream for output {output_id}"))?;
             let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("kitsune-rendercore-textured-pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -853,22 +5507,315 @@ impl WgpuShared {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            pass.set_pipeline(&self.program.pipeline);
-            pass.set_bind_group(0, bind_group, &[]);
-            pass.draw(0..3, 0..1);
+            let pipeline = self
+                .program
+                .pipeline(frame.texture.format())
+                .ok_or_else(|| "missing frame pipeline".to_string())?;
+            pass.set_pipeline(pipeline);
+            for (stream_id, rect) in &plan.draws {
+                let Some(stream) = self.video_streams.get(stream_id) else {
+                    continue;
+                };
+                if !set_draw_area(&mut pass, plan, *rect) {
+                    continue;
+                }
+                pass.set_bind_group(0, &stream.bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+            if !overlay.is_empty() {
+                let pipeline = self
+                    .program
+                    .overlay_pipeline(frame.texture.format())
+                    .ok_or_else(|| "missing overlay pipeline".to_string())?;
+                let name = outputs
+                    .get(&plan.output_id)
+                    .map(OutputSlot::display_name)
+                    .unwrap_or_default();
+                if let Some(timestamps) = self
+                    .overlay_timer
+                    .as_mut()
+                    .and_then(|timer| timer.pass_writes(&name))
+                {
+                    drop(pass);
+                    pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("kitsune-rendercore-overlay-pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                        occlusion_query_set: None,
+                        timestamp_writes: Some(timestamps),
+                    });
+                }
+                pass.set_pipeline(pipeline);
+                for (stream_id, rect, instances) in overlay {
+                    let Some(stream) = self.video_streams.get(stream_id) else {
+                        continue;
+                    };
+                    if !set_draw_area(&mut pass, plan, *rect) {
+                        continue;
+                    }
+                    pass.set_bind_group(0, &stream.bind_group, &[]);
+                    pass.draw(0..6, 0..*instances);
+                }
+            }
+            // Widgets go last, over the particles, across the whole buffer.
+            if plan.clock.is_some()
+                && let Some(layer) = self
+                    .render_surfaces
+                    .iter()
+                    .find(|rs| rs.output_global_name == plan.output_id)
+                    .and_then(|rs| rs.clock.as_ref())
+            {
+                let pipeline = self
+                    .text_program
+                    .pipeline(frame.texture.format())
+                    .ok_or_else(|| "missing text pipeline".to_string())?;
+                let (width, height) = plan.display_size;
+                set_draw_area(&mut pass, plan, Some((0, 0, width, height)));
+                pass.set_pipeline(pipeline);
+                layer.draw(&mut pass);
+            }
+        }
+        if let Some(timer) = &self.overlay_timer {
+            timer.resolve(&mut encoder);
         }
 
-        self.queue.submit([encoder.finish()]);
-        for (_, frame) in acquired {
+        let commands = encoder.finish();
+        profiler.end(Phase::Encode, encode_started);
+        let t = profiler.start();
+        self.queue.submit([commands]);
+        if let Some(timer) = self.overlay_timer.as_mut() {
+            timer.submitted();
+        }
+        profiler.end(Phase::Submit, t);
+        let t = profiler.start();
+        let mut presented = Vec::with_capacity(acquired.len());
+        let mut shown = Vec::new();
+        for (plan, frame) in acquired {
+            // Every buffer is drawn whole, so the damage only tells the
+            // compositor what to recompose; the present commits it.
+            // `damage_buffer` needs wl_surface v4.
+            if self.damage_tracking
+                && let Some(slot) = layer_surfaces
+                    .iter()
+                    .find(|slot| slot.output_global_name == plan.output_id)
+                && slot.surface.version() >= 4
+            {
+                for (x0, y0, x1, y1) in &plan.damage {
+                    slot.surface.damage_buffer(
+                        *x0 as i32,
+                        *y0 as i32,
+                        (x1 - x0) as i32,
+                        (y1 - y0) as i32,
+                    );
+                }
+            }
             frame.present();
+            if let Some(rs) = self
+                .render_surfaces
+                .iter_mut()
+                .find(|rs| rs.output_global_name == plan.output_id)
+            {
+                rs.full_damage = false;
+                rs.damage
+                    .record(&plan.damage, (rs.config.width, rs.config.height));
+                rs.drawn = plan.draws;
+                if let Some(layer) = rs.clock.as_mut() {
+                    layer.shown = plan.clock;
+                }
+            }
+            for (stream_id, drawn) in plan.frames {
+                if let Some(stream) = self.video_streams.get_mut(&stream_id) {
+                    stream.drawn_as = Some(drawn);
+                    if stream.awaiting_present {
+                        shown.push(stream_id);
+                    }
+                }
+            }
+            presented.push(plan.output_id);
         }
-        Ok(())
+        // After every present: a follower looks at its leader.
+        for stream_id in shown {
+            if !self.shows_current_entry(stream_id) {
+                continue;
+            }
+            if let Some(stream) = self.video_streams.get_mut(&stream_id) {
+                stream.awaiting_present = false;
+                if let Some(entry) = &stream.current_entry {
+                    events::publish(|| RenderEvent::Presented {
+                        monitor: stream.monitor.clone(),
+                        video: entry.video.clone(),
+                    });
+                }
+            }
+        }
+        profiler.end(Phase::Present, t);
+        Ok((presented, unchanged))
+    }
+}
+
+fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue), String> {
+    pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("kitsune-rendercore-device"),
+            required_features: adapter.features()
+                & (wgpu::Features::PIPELINE_CACHE | wgpu::Features::TIMESTAMP_QUERY),
+            required_limits: adapter.limits(),
+            memory_hints: wgpu::MemoryHints::Performance,
+        },
+        None,
+    ))
+    .map_err(|err| format!("wgpu request_device failed: {err}"))
+}
+
+/// Offscreen upload and render rate for `bench`: `streams` sources of `size`
+/// set up like an output's stream and drawn with the frame pipeline into
+/// same-sized targets, waiting for the GPU after every frame. No compositor is
+/// involved, so presentation is not part of it.
+pub fn bench_render(
+    size: (u32, u32),
+    streams: u32,
+    duration: Duration,
+) -> Result<RenderBench, String> {
+    let instance = wgpu::Instance::default();
+    let selection = select_adapter(&instance, &render_nodes(), None)?;
+    let summary = selection.summary();
+    let adapter = selection.adapter;
+    let (device, queue) = request_device(&adapter)?;
+    let (width, height) = clamp_source_size(size, adapter.limits().max_texture_dimension_2d);
+    let mut program = init_render_program(&device, &adapter)?;
+    let target_format = wgpu::TextureFormat::Bgra8UnormSrgb;
+    program.ensure_pipeline(&device, target_format);
+    let pipeline = program
+        .pipeline(target_format)
+        .ok_or_else(|| "missing frame pipeline".to_string())?;
+    let mut bench_streams = Vec::new();
+    for id in 0..streams.max(1) {
+        let stream = init_video_stream(
+            &device,
+            &queue,
+            &program,
+            (width, height),
+            (width, height),
+            None,
+            VideoOptions::from_env(),
+            None,
+            None,
+            &id,
+            &format!("bench-{id}"),
+        )?;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("kitsune-rendercore-bench-target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: target_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        queue.write_buffer(
+            &stream.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&FrameUniform {
+                time_sec: 0.0,
+                aspect: width as f32 / height.max(1) as f32,
+                rotate_quarters: 0,
+                flip_bits: 0,
+                box_offset: [0.0, 0.0],
+                motion_offset: [0.0, 0.0],
+                zoom: 1.0,
+                buffer_transform: 0,
+                brightness: 1.0,
+                overlay: 0,
+                crop: [0.0, 0.0, 1.0, 1.0],
+                fit: [0.0, 0.0, 1.0, 1.0],
+                gamut: IDENTITY_GAMUT,
+                transfer: 0,
+                gamma: 1.0,
+                _pad2: [0.0; 2],
+                overlay_params: [0.0; 4],
+            }),
+        );
+        bench_streams.push((stream, view));
+    }
+
+    let draw_frame = || {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("kitsune-rendercore-bench-encoder"),
+        });
+        for (stream, view) in &bench_streams {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &stream.source_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &stream.frame_pixels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width * 4),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("kitsune-rendercore-bench-pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &stream.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+        device.poll(wgpu::Maintain::Wait);
+    };
+    // The first frame pays for pipeline and driver warm-up.
+    draw_frame();
+    let started = Instant::now();
+    let mut frames = 0u64;
+    while started.elapsed() < duration {
+        draw_frame();
+        frames += 1;
     }
+    let seconds = started.elapsed().as_secs_f64();
+    let frame_bytes = width as f64 * height as f64 * 4.0 * bench_streams.len() as f64;
+    Ok(RenderBench {
+        adapter: summary,
+        fps: frames as f64 / seconds,
+        upload_bytes_per_sec: frames as f64 * frame_bytes / seconds,
+    })
 }
 
 fn init_render_program(
     device: &wgpu::Device,
-    surface_format: wgpu::TextureFormat,
+    adapter: &wgpu::Adapter,
 ) -> Result<RenderProgram, String> {
     let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
         label: Some("kitsune-rendercore-source-sampler"),
@@ -880,12 +5827,15 @@ fn init_render_program(
         mipmap_filter: wgpu::FilterMode::Linear,
         ..Default::default()
     });
-
-    let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("kitsune-rendercore-frame-uniform"),
-        size: std::mem::size_of::<FrameUniform>() as u64,
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false,
+    let nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("kitsune-rendercore-source-sampler-nearest"),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
     });
 
     let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -909,7 +5859,7 @@ fn init_render_program(
             },
             wgpu::BindGroupLayoutEntry {
                 binding: 2,
-                visibility: wgpu::ShaderStages::FRAGMENT,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -929,22 +5879,92 @@ fn init_render_program(
     } else {
         println!("[rendercore] wave effect disabled (plain video)");
     }
-    let shader_source = if wave_enabled {
+    let fragment_source = if wave_enabled {
         FRAME_SHADER_WGSL_WAVE
     } else {
         FRAME_SHADER_WGSL_PLAIN
     };
+    let shader_source = format!("{FRAME_SHADER_WGSL_COMMON}{fragment_source}");
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("kitsune-rendercore-frame-shader"),
         source: wgpu::ShaderSource::Wgsl(shader_source.into()),
     });
+    let overlay_source = format!(
+        "{FRAME_SHADER_WGSL_COMMON}{}",
+        particles::OVERLAY_SHADER_WGSL
+    );
+    let overlay_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("kitsune-rendercore-overlay-shader"),
+        source: wgpu::ShaderSource::Wgsl(overlay_source.into()),
+    });
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("kitsune-rendercore-frame-pipeline-layout"),
         bind_group_layouts: &[&bind_group_layout],
         push_constant_ranges: &[],
     });
+    let pipeline_cache = PipelineCacheFile::open(device, adapter);
+    if pipeline_cache.is_some() {
+        println!("[rendercore] pipeline cache enabled");
+    }
+
+    let downscale = DownscaleMode::from_env();
+    println!("[rendercore] downscale mode={}", downscale.as_str());
+
+    Ok(RenderProgram {
+        pipelines: Vec::new(),
+        shader,
+        pipeline_layout,
+        pipeline_cache,
+        bind_group_layout,
+        sampler,
+        nearest_sampler,
+        downscale,
+        mip_blit: init_mip_blit(device),
+        animated: wave_enabled,
+        overlay_shader,
+        overlay_pipelines: Vec::new(),
+    })
+}
+
+fn init_mip_blit(device: &wgpu::Device) -> MipBlit {
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("kitsune-rendercore-mip-sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("kitsune-rendercore-mip-bgl"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("kitsune-rendercore-mip-shader"),
+        source: wgpu::ShaderSource::Wgsl(MIP_BLIT_WGSL.into()),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("kitsune-rendercore-mip-pipeline-layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
     let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("kitsune-rendercore-frame-pipeline"),
+        label: Some("kitsune-rendercore-mip-pipeline"),
         layout: Some(&pipeline_layout),
         vertex: wgpu::VertexState {
             module: &shader,
@@ -957,7 +5977,7 @@ fn init_render_program(
             entry_point: Some("fs_main"),
             compilation_options: wgpu::PipelineCompilationOptions::default(),
             targets: &[Some(wgpu::ColorTargetState {
-                format: surface_format,
+                format: SOURCE_TEXTURE_FORMAT,
                 blend: Some(wgpu::BlendState::REPLACE),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
@@ -968,27 +5988,435 @@ fn init_render_program(
         multiview: None,
         cache: None,
     });
-
-    Ok(RenderProgram {
+    MipBlit {
         pipeline,
         bind_group_layout,
         sampler,
-        uniform_buffer,
+    }
+}
+
+/// Mip levels needed so trilinear sampling never minifies by more than 2x
+/// between levels; 1 (no chain) when the source fits the output.
+fn mip_level_count(source_size: (u32, u32), output_size: (u32, u32), options: EntryOptions) -> u32 {
+    let (out_w, out_h) = if options.rotate.swaps_axes() {
+        (output_size.1, output_size.0)
+    } else {
+        output_size
+    };
+    let ratio = (source_size.0 as f32 / out_w.max(1) as f32)
+        .max(source_size.1 as f32 / out_h.max(1) as f32);
+    if ratio <= 1.0 {
+        return 1;
+    }
+    let full_chain = 32 - source_size.0.max(source_size.1).max(1).leading_zeros();
+    (1 + ratio.log2().ceil() as u32).min(full_chain)
+}
+
+/// Tap offset for the `simple` box filter, or zero when the source is at most
+/// 1.5x the output on both axes.
+fn box_filter_offset(
+    source_size: (u32, u32),
+    target_size: (u32, u32),
+    options: EntryOptions,
+) -> [f32; 2] {
+    let (target_w, target_h) = if options.rotate.swaps_axes() {
+        (target_size.1, target_size.0)
+    } else {
+        target_size
+    };
+    let ratio_x = source_size.0 as f32 / target_w.max(1) as f32;
+    let ratio_y = source_size.1 as f32 / target_h.max(1) as f32;
+    if ratio_x.max(ratio_y) <= 1.5 {
+        return [0.0, 0.0];
+    }
+    // Each bilinear tap already averages 2x2 texels; placing the taps a quarter
+    // of the pixel footprint apart covers roughly the whole footprint.
+    [
+        0.25 * ratio_x / source_size.0.max(1) as f32,
+        0.25 * ratio_y / source_size.1.max(1) as f32,
+    ]
+}
+
+/// Source UV window `[x, y, w, h]` for a crop (`rect` in pixels of a video of
+/// `native` size): the centred part of the region with the output's aspect, so
+/// the region covers the output the way a whole video does. The full texture
+/// without a crop.
+fn crop_window(
+    crop: Option<AppliedCrop>,
+    display_size: (u32, u32),
+    options: EntryOptions,
+) -> [f32; 4] {
+    let Some(((x, y, w, h), native)) = crop else {
+        return [0.0, 0.0, 1.0, 1.0];
+    };
+    let (target_w, target_h) = if options.rotate.swaps_axes() {
+        (display_size.1, display_size.0)
+    } else {
+        display_size
+    };
+    let target = target_w.max(1) as f64 / target_h.max(1) as f64;
+    let (mut x, mut y, mut w, mut h) = (x as f64, y as f64, w as f64, h as f64);
+    if w / h > target {
+        let fit = h * target;
+        x += (w - fit) / 2.0;
+        w = fit;
+    } else {
+        let fit = w / target;
+        y += (h - fit) / 2.0;
+        h = fit;
+    }
+    fraction_of(native, [x, y, w, h])
+}
+
+/// Source UV window of a whole region, without cutting it to the output's
+/// aspect (`fit=integer` shows it all).
+fn region_uv(((x, y, w, h), native): AppliedCrop) -> [f32; 4] {
+    fraction_of(native, [x as f64, y as f64, w as f64, h as f64])
+}
+
+fn fraction_of((native_w, native_h): (u32, u32), [x, y, w, h]: [f64; 4]) -> [f32; 4] {
+    [
+        (x / native_w as f64) as f32,
+        (y / native_h as f64) as f32,
+        (w / native_w as f64) as f32,
+        (h / native_h as f64) as f32,
+    ]
+}
+
+/// `fit=integer` placement in display UV (origin, size): the picture (`shown`
+/// pixels, oriented as on screen) scaled by the largest whole multiple that
+/// fits `display`, or divided by the smallest whole number that makes it fit
+/// when it is larger, then centred on whole pixels so every source pixel
+/// covers the same number of output pixels.
+fn integer_fit(shown: (u32, u32), display: (u32, u32)) -> [f32; 4] {
+    let (sw, sh) = (shown.0.max(1), shown.1.max(1));
+    let (dw, dh) = (display.0.max(1), display.1.max(1));
+    let scale = (dw / sw).min(dh / sh);
+    let (w, h) = if scale >= 1 {
+        (sw * scale, sh * scale)
+    } else {
+        let divisor = sw.div_ceil(dw).max(sh.div_ceil(dh));
+        ((sw / divisor).max(1), (sh / divisor).max(1))
+    };
+    let (x, y) = ((dw - w) / 2, (dh - h) / 2);
+    [
+        x as f32 / dw as f32,
+        y as f32 / dh as f32,
+        w as f32 / dw as f32,
+        h as f32 / dh as f32,
+    ]
+}
+
+/// Zoom and pan for `effect=ambient` at `t` seconds. The zoom eases between 1
+/// and 1 + amplitude on a cosine, so it turns around without a jolt; the pan
+/// follows two slower, incommensurate sines scaled by the current zoom margin,
+/// so the video edges never come into view. `(1.0, [0, 0])` when off.
+fn ambient_motion(options: EntryOptions, t: f64, phase: f64) -> (f32, [f32; 2]) {
+    if options.effect != Effect::Ambient {
+        return (1.0, [0.0, 0.0]);
+    }
+    let params = options.ambient;
+    let period = params.period_sec() as f64;
+    let cycle = |length: f64| (t / length).fract() * std::f64::consts::TAU;
+    let zoom = 1.0 + params.amplitude() as f64 * 0.5 * (1.0 - cycle(period).cos());
+    let margin = 0.5 * (1.0 - 1.0 / zoom) * params.drift() as f64;
+    let offset = [
+        margin * (cycle(period * 1.618) + phase).sin(),
+        margin * (cycle(period * 2.414) + phase).cos(),
+    ];
+    (zoom as f32, [offset[0] as f32, offset[1] as f32])
+}
+
+/// Per-output drift phase in radians: a hash of the output name and the seed,
+/// so outputs do not pan in lockstep but repeat the same path across runs.
+fn ambient_phase(monitor: &str, seed: u64) -> f64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64 ^ seed;
+    for byte in monitor.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    (hash % 3600) as f64 / 3600.0 * std::f64::consts::TAU
+}
+
+/// The wait reason while layer-shell is missing, told apart from the others
+/// when the wait gives up.
+const NO_LAYER_SHELL: &str =
+    "zwlr_layer_shell_v1 is not available (compositor may not support layer-shell)";
+
+/// `KRC_WAIT_FOR_COMPOSITOR_SEC` default.
+const DEFAULT_COMPOSITOR_WAIT: Duration = Duration::from_secs(15);
+const COMPOSITOR_RETRY: Duration = Duration::from_millis(250);
+
+fn compositor_wait_from_env() -> Duration {
+    let Ok(raw) = std::env::var("KRC_WAIT_FOR_COMPOSITOR_SEC") else {
+        return DEFAULT_COMPOSITOR_WAIT;
+    };
+    raw.trim()
+        .parse::<u64>()
+        .map(Duration::from_secs)
+        .unwrap_or_else(|_| {
+            eprintln!(
+                "[rendercore] ignoring KRC_WAIT_FOR_COMPOSITOR_SEC={raw}: expected whole seconds"
+            );
+            DEFAULT_COMPOSITOR_WAIT
+        })
+}
+
+/// Retries `attempt` every 250ms for up to `limit`. `attempt` returns
+/// `Ok(Err(reason))` while `what` is not there yet and `Err` to give up at
+/// once. Logs only when it has to wait: once when waiting starts, once when
+/// it ends.
+fn wait_for<T>(
+    what: &str,
+    limit: Duration,
+    mut attempt: impl FnMut() -> Result<Result<T, String>, String>,
+) -> Result<T, String> {
+    let started = Instant::now();
+    let mut waiting = false;
+    loop {
+        startup::still_busy()?;
+        let reason = match attempt()? {
+            Ok(value) => {
+                if waiting {
+                    println!(
+                        "[rendercore] {what} ready after {}ms",
+                        started.elapsed().as_millis()
+                    );
+                }
+                return Ok(value);
+            }
+            Err(reason) => reason,
+        };
+        if started.elapsed() >= limit {
+            if waiting {
+                println!(
+                    "[rendercore] gave up waiting for {what} after {}ms",
+                    started.elapsed().as_millis()
+                );
+            }
+            return Err(reason);
+        }
+        if !waiting {
+            println!(
+                "[rendercore] waiting up to {}s for {what} ({reason})",
+                limit.as_secs()
+            );
+            waiting = true;
+        }
+        std::thread::sleep(COMPOSITOR_RETRY);
+    }
+}
+
+/// `KRC_VIDEO_FPS` and friends, with decode held to `fps_cap` on a software
+/// adapter: frames decoded faster than they are drawn are wasted.
+fn capped_video_options(fps_cap: Option<u32>) -> VideoOptions {
+    let mut options = VideoOptions::from_env();
+    if let Some(cap) = fps_cap {
+        options.fps = options.fps.min(cap);
+    }
+    options
+}
+
+/// Logs and journals a headroom estimate: a line when it fits, a warning
+/// naming the heaviest streams and what would help when it does not.
+fn log_headroom(estimate: &Estimate, streams: &[StreamLoad]) {
+    let summary = format!(
+        "{} stream(s) need ~{:.1} of {:.0} cores ({:.1}x)",
+        streams.len(),
+        estimate.need_cores,
+        estimate.capacity_cores,
+        estimate.ratio
+    );
+    if estimate.verdict == Verdict::Ok {
+        println!("[rendercore] headroom ok: {summary}");
+    } else {
+        let heaviest = estimate
+            .heaviest
+            .iter()
+            .filter_map(|(output, cores)| {
+                let load = streams.iter().find(|load| load.output == *output)?;
+                Some(format!(
+                    "{output} {}x{}@{} {} ~{cores:.1} cores",
+                    load.size.0,
+                    load.size.1,
+                    load.fps,
+                    load.decode.as_str()
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "[rendercore] warning: headroom {}: {summary}; heaviest: {heaviest}; try: {}",
+            estimate.verdict.as_str(),
+            estimate.suggestions.join("; ")
+        );
+    }
+    journal::record(
+        "headroom",
+        &[
+            ("verdict", Value::Str(estimate.verdict.as_str())),
+            ("streams", Value::U64(streams.len() as u64)),
+            (
+                "need_centicores",
+                Value::U64((estimate.need_cores * 100.0).round() as u64),
+            ),
+            ("capacity_cores", Value::U64(estimate.capacity_cores as u64)),
+        ],
+    );
+}
+
+fn damage_tracking_from_env() -> bool {
+    let enabled = std::env::var("KRC_DAMAGE_TRACKING")
+        .map(|v| {
+            !matches!(
+                v.to_ascii_lowercase().as_str(),
+                "0" | "false" | "no" | "off"
+            )
+        })
+        .unwrap_or(true);
+    if !enabled {
+        println!("[rendercore] damage tracking disabled (KRC_DAMAGE_TRACKING)");
+    }
+    enabled
+}
+
+fn share_decoders_from_env() -> bool {
+    let share = std::env::var("KRC_SHARE_DECODERS")
+        .map(|v| {
+            !matches!(
+                v.to_ascii_lowercase().as_str(),
+                "0" | "false" | "no" | "off"
+            )
+        })
+        .unwrap_or(true);
+    if !share {
+        println!("[rendercore] decoder sharing disabled (KRC_SHARE_DECODERS)");
+    }
+    share
+}
+
+/// A duration in milliseconds (`KRC_START_DELAY_MS`, `KRC_FADE_IN_MS`);
+/// zero when unset or unreadable.
+fn millis_from_env(key: &str) -> Duration {
+    let Ok(raw) = std::env::var(key) else {
+        return Duration::ZERO;
+    };
+    raw.trim()
+        .parse()
+        .map(Duration::from_millis)
+        .unwrap_or_else(|_| {
+            eprintln!("[rendercore] ignoring {key}={raw}: expected milliseconds");
+            Duration::ZERO
+        })
+}
+
+fn ambient_seed_from_env() -> u64 {
+    let Ok(raw) = std::env::var("KRC_AMBIENT_SEED") else {
+        return 0;
+    };
+    raw.trim().parse().unwrap_or_else(|_| {
+        eprintln!("[rendercore] ignoring KRC_AMBIENT_SEED={raw}: expected an unsigned integer");
+        0
     })
 }
 
+fn create_mip_chain(
+    device: &wgpu::Device,
+    blit: &MipBlit,
+    texture: &wgpu::Texture,
+    levels: u32,
+) -> MipChain {
+    let level_view = |level: u32| {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("kitsune-rendercore-mip-level"),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            ..Default::default()
+        })
+    };
+    let bind_groups = (0..levels - 1)
+        .map(|level| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("kitsune-rendercore-mip-bg"),
+                layout: &blit.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&level_view(level)),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&blit.sampler),
+                    },
+                ],
+            })
+        })
+        .collect();
+    let views = (1..levels).map(level_view).collect();
+    MipChain { views, bind_groups }
+}
+
+fn encode_mip_chain(encoder: &mut wgpu::CommandEncoder, blit: &MipBlit, chain: &MipChain) {
+    for (target, bind_group) in chain.views.iter().zip(&chain.bind_groups) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("kitsune-rendercore-mip-pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&blit.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn init_video_stream(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     program: &RenderProgram,
     source_size: (u32, u32),
-    selected_video: Option<String>,
+    output_size: (u32, u32),
+    selected_video: Option<VideoMapEntry>,
     video_options: VideoOptions,
+    start_at: Option<Instant>,
+    resume_ms: Option<u64>,
     output_id: &u32,
     output_name: &str,
 ) -> Result<VideoStream, String> {
     let (source_width, source_height) = source_size;
     let frame_pixels = procedural_pixels(source_width, source_height);
+    let mip_levels = if program.downscale.uses_mipmaps() {
+        let options = selected_video
+            .as_ref()
+            .map(|entry| entry.options)
+            .unwrap_or_default();
+        mip_level_count(source_size, output_size, options)
+    } else {
+        1
+    };
+    let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+    if mip_levels > 1 {
+        usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        println!(
+            "[rendercore] output={} (id={}) source {}x{} > output {}x{}: {} mip levels",
+            output_name,
+            output_id,
+            source_width,
+            source_height,
+            output_size.0,
+            output_size.1,
+            mip_levels
+        );
+    }
     let source_texture = device.create_texture(&wgpu::TextureDescriptor {
         label: Some("kitsune-rendercore-source-texture"),
         size: wgpu::Extent3d {
@@ -996,13 +6424,15 @@ fn init_video_stream(
             height: source_height,
             depth_or_array_layers: 1,
         },
-        mip_level_count: 1,
+        mip_level_count: mip_levels,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        format: SOURCE_TEXTURE_FORMAT,
+        usage,
         view_formats: &[],
     });
+    let mip_chain = (mip_levels > 1)
+        .then(|| create_mip_chain(device, &program.mip_blit, &source_texture, mip_levels));
     queue.write_texture(
         wgpu::TexelCopyTextureInfo {
             texture: &source_texture,
@@ -1022,8 +6452,102 @@ fn init_video_stream(
             depth_or_array_layers: 1,
         },
     );
-    let texture_view = source_texture.create_view(&wgpu::TextureViewDescriptor::default());
-    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+    let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("kitsune-rendercore-frame-uniform"),
+        size: std::mem::size_of::<FrameUniform>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let filter = selected_video
+        .as_ref()
+        .map_or(SampleFilter::Linear, |entry| entry.options.filter);
+    let bind_group = frame_bind_group(device, program, &source_texture, &uniform_buffer, filter);
+
+    match &selected_video {
+        Some(entry) => println!(
+            "[rendercore] output={} (id={}) video={}",
+            output_name,
+            output_id,
+            entry.display_label()
+        ),
+        None => println!(
+            "[rendercore] output={} (id={}) video=<none> (procedural fallback)",
+            output_name, output_id
+        ),
+    }
+    let mut stream = VideoStream {
+        monitor: output_name.to_string(),
+        bind_group,
+        filter,
+        source_texture,
+        texture_serial: NEXT_TEXTURE_SERIAL.fetch_add(1, Ordering::Relaxed),
+        shared_from: None,
+        source_width,
+        source_height,
+        frame_source: FrameSource::None,
+        frame_pixels,
+        uniform_buffer,
+        uploads: 0,
+        drawn_as: None,
+        mips_dirty: mip_chain.is_some(),
+        mip_chain,
+        current_entry: selected_video,
+        last_error: None,
+        last_error_repeats: 0,
+        consecutive_failures: 0,
+        quality_level: 0,
+        video_file: None,
+        pending_video_file: None,
+        decode_interval: video_options.frame_interval(),
+        next_decode_at: Instant::now(),
+        incoming: None,
+        missing: None,
+        awaiting_ffmpeg: None,
+        awaiting_present: false,
+        frozen_at: None,
+        flash_guard: FlashGuard::from_env(),
+        start_at,
+        fade: None,
+        fade_step_at: Instant::now(),
+        resume_ms,
+        next_reseek_at: Instant::now(),
+    };
+    // The decoder opens off this thread (or after the start delay); the
+    // procedural frame shows until then.
+    if let Some(entry) = stream.current_entry.clone() {
+        stream.open_incoming(*output_id, entry, None, "startup", video_options);
+    }
+    Ok(stream)
+}
+
+/// Binds `texture` (the stream's own or a leader's) with the stream's uniform
+/// and the sampler for its `filter=`.
+/// Points `pass` at a layout cell's part of the buffer; a whole-output draw
+/// (`None`) keeps the full viewport, as it is never drawn after a cell.
+/// `false` when the cell has no pixels.
+fn set_draw_area(pass: &mut wgpu::RenderPass<'_>, plan: &OutputFrame, rect: Option<Rect>) -> bool {
+    let Some(rect) = rect else {
+        return true;
+    };
+    let (x0, y0, x1, y1) = buffer_rect(plan.buffer_transform, plan.display_size, rect);
+    if x1 <= x0 || y1 <= y0 {
+        return false;
+    }
+    let (x, y, w, h) = (x0, y0, x1 - x0, y1 - y0);
+    pass.set_viewport(x as f32, y as f32, w as f32, h as f32, 0.0, 1.0);
+    pass.set_scissor_rect(x, y, w, h);
+    true
+}
+
+fn frame_bind_group(
+    device: &wgpu::Device,
+    program: &RenderProgram,
+    texture: &wgpu::Texture,
+    uniform_buffer: &wgpu::Buffer,
+    filter: SampleFilter,
+) -> wgpu::BindGroup {
+    let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: Some("kitsune-rendercore-frame-bg"),
         layout: &program.bind_group_layout,
         entries: &[
@@ -1033,41 +6557,32 @@ fn init_video_stream(
             },
             wgpu::BindGroupEntry {
                 binding: 1,
-                resource: wgpu::BindingResource::Sampler(&program.sampler),
+                resource: wgpu::BindingResource::Sampler(program.sampler(filter)),
             },
             wgpu::BindGroupEntry {
                 binding: 2,
-                resource: program.uniform_buffer.as_entire_binding(),
+                resource: uniform_buffer.as_entire_binding(),
             },
         ],
-    });
+    })
+}
 
-    let frame_source = if let Some(path) = selected_video.clone() {
-        println!(
-            "[rendercore] output={} (id={}) video={}",
-            output_name, output_id, path
-        );
-        FrameSource::from_video_path(path, source_width, source_height, video_options)
+/// Orients the configured (landscape) source size to match the output, so a
+/// portrait output decodes portrait-shaped frames instead of a stretched landscape crop.
+/// A 90/270 content rotation is sampled sideways, so the decode shape flips with it.
+fn oriented_source_size(
+    source_size: (u32, u32),
+    output_size: (u32, u32),
+    options: EntryOptions,
+) -> (u32, u32) {
+    let (width, height) = source_size;
+    let output_portrait = (output_size.1 > output_size.0) != options.rotate.swaps_axes();
+    let source_portrait = height > width;
+    if output_portrait != source_portrait {
+        (height, width)
     } else {
-        println!(
-            "[rendercore] output={} (id={}) video=<none> (procedural fallback)",
-            output_name, output_id
-        );
-        FrameSource::None
-    };
-    let current_video = selected_video;
-
-    Ok(VideoStream {
-        bind_group,
-        source_texture,
-        source_width,
-        source_height,
-        frame_source,
-        frame_pixels,
-        current_video,
-        decode_interval: Duration::from_secs_f32((1.0f32 / video_options.fps as f32).max(0.001)),
-        next_decode_at: Instant::now(),
-    })
+        (width, height)
+    }
 }
 
 fn procedural_pixels(width: u32, height: u32) -> Vec<u8> {
@@ -1087,17 +6602,73 @@ fn procedural_pixels(width: u32, height: u32) -> Vec<u8> {
     pixels
 }
 
+/// Landscape source size for one entry: its own `quality=` preset when set
+/// (clamped to the GPU limit like the global size), else the global size.
+fn entry_base_size(
+    global_size: (u32, u32),
+    max_texture_dimension_2d: u32,
+    options: EntryOptions,
+) -> (u32, u32) {
+    match options.quality {
+        Some(preset) => clamp_source_size(preset.source_size(), max_texture_dimension_2d),
+        None => global_size,
+    }
+}
+
+/// Size of the whole frame for a `crop=` or `fit=integer` entry: the video's
+/// own size, even and within the GPU limit, so every crop of one video
+/// decodes the same frames and integer scaling works on real pixels. `None`
+/// for other entries, or while the size is still being probed.
+fn whole_frame_size(entry: &VideoMapEntry, max_texture_dimension_2d: u32) -> Option<(u32, u32)> {
+    if entry.options.crop.is_none() && entry.options.fit != Fit::Integer {
+        return None;
+    }
+    let native = frame_source::native_size(&entry.video)?;
+    let (width, height) = clamp_source_size(native, max_texture_dimension_2d);
+    Some(((width & !1).max(2), (height & !1).max(2)))
+}
+
+/// Unrotated source size for one stream: [`whole_frame_size`] for a cropped
+/// or integer-fit entry whose size is known, else [`entry_base_size`], of
+/// which a layout cell gets its share.
+fn stream_source_size(
+    global_size: (u32, u32),
+    max_texture_dimension_2d: u32,
+    layouts: &BTreeMap<u32, Layout>,
+    stream_id: u32,
+    entry: &VideoMapEntry,
+) -> (u32, u32) {
+    if let Some(size) = whole_frame_size(entry, max_texture_dimension_2d) {
+        return size;
+    }
+    let size = entry_base_size(global_size, max_texture_dimension_2d, entry.options);
+    match cell_index(stream_id).and(layouts.get(&owner_output(stream_id))) {
+        Some(layout) => cell_source_size(size, layout),
+        None => size,
+    }
+}
+
+/// The name auto quality and the profiler know a stream by: its output's,
+/// or `MONITOR cell N` for a layout cell.
+fn stream_name(
+    outputs: &BTreeMap<u32, OutputSlot>,
+    stream_id: u32,
+    stream: &VideoStream,
+) -> String {
+    match cell_index(stream_id) {
+        Some(_) => stream.monitor.clone(),
+        None => outputs
+            .get(&stream_id)
+            .map(OutputSlot::display_name)
+            .unwrap_or_default(),
+    }
+}
+
 fn choose_source_resolution(max_texture_dimension_2d: u32) -> (u32, u32) {
-    let preset = std::env::var("KRC_QUALITY").ok().and_then(|v| {
-        let v = v.to_ascii_lowercase();
-        match v.as_str() {
-            "low" | "720p" => Some((1280u32, 720u32)),
-            "medium" | "1080p" => Some((1920u32, 1080u32)),
-            "high" | "1440p" => Some((2560u32, 1440u32)),
-            "ultra" | "4k" | "2160p" => Some((3840u32, 2160u32)),
-            _ => None,
-        }
-    });
+    let preset = std::env::var("KRC_QUALITY")
+        .ok()
+        .and_then(|v| QualityPreset::parse(&v).ok())
+        .map(QualityPreset::source_size);
 
     let mut width = preset.map(|p| p.0).unwrap_or(960);
     let mut height = preset.map(|p| p.1).unwrap_or(540);
@@ -1117,165 +6688,1229 @@ fn choose_source_resolution(max_texture_dimension_2d: u32) -> (u32, u32) {
         height = h;
     }
 
-    if width <= max_texture_dimension_2d && height <= max_texture_dimension_2d {
-        return (width, height);
+    let (clamped_w, clamped_h) = clamp_source_size((width, height), max_texture_dimension_2d);
+    if (clamped_w, clamped_h) != (width, height) {
+        eprintln!(
+            "[rendercore] requested source {}x{} exceeds GPU max {}; clamped to {}x{}",
+            width, height, max_texture_dimension_2d, clamped_w, clamped_h
+        );
+    }
+    (clamped_w, clamped_h)
+}
+
+/// A surface buffer within the GPU's texture limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SurfaceFit {
+    /// Buffer size in the orientation the user sees.
+    size: (u32, u32),
+    scale: i32,
+    /// Smaller than the surface even at scale 1: a viewport has to stretch it.
+    shrunk: bool,
+}
+
+impl SurfaceFit {
+    /// Whether the output gets fewer pixels than it asked for.
+    fn clamped(self, preferred_scale: i32) -> bool {
+        self.shrunk || self.scale < preferred_scale
+    }
+}
+
+/// Fits a surface of `surface_size` (surface-local, in the orientation the
+/// user sees) at the compositor's preferred `scale` into the GPU limit. The
+/// scale steps down first, which the compositor upscales as for any low-DPI
+/// client; at scale 1 the size shrinks, keeping its aspect.
+fn fit_surface(surface_size: (u32, u32), scale: i32, max_texture_dimension_2d: u32) -> SurfaceFit {
+    let longest = surface_size.0.max(surface_size.1);
+    let mut scale = scale.max(1);
+    while scale > 1 && longest.saturating_mul(scale as u32) > max_texture_dimension_2d {
+        scale -= 1;
+    }
+    let size = (surface_size.0 * scale as u32, surface_size.1 * scale as u32);
+    let fitted = clamp_source_size(size, max_texture_dimension_2d);
+    SurfaceFit {
+        size: fitted,
+        scale,
+        shrunk: fitted != size,
+    }
+}
+
+/// Gives the slot's viewport the whole surface as destination while the
+/// buffer is shrunk (and unsets it otherwise), and logs when an output starts
+/// or stops being clamped. Returns whether it is clamped now.
+fn apply_surface_fit(
+    slot: Option<&LayerSurfaceSlot>,
+    label: &str,
+    surface_size: (u32, u32),
+    preferred_scale: i32,
+    fit: SurfaceFit,
+    was_clamped: bool,
+    max_texture_dimension_2d: u32,
+) -> bool {
+    let viewport = slot.and_then(|slot| slot.viewport.as_ref());
+    if let Some(viewport) = viewport {
+        if fit.shrunk {
+            viewport.set_destination(surface_size.0 as i32, surface_size.1 as i32);
+        } else {
+            viewport.set_destination(-1, -1);
+        }
+    }
+    let clamped = fit.clamped(preferred_scale);
+    if clamped && !was_clamped {
+        let how = if !fit.shrunk {
+            format!("buffer scale {}, upscaled by the compositor", fit.scale)
+        } else if viewport.is_some() {
+            "stretched over the output with wp_viewporter".to_string()
+        } else {
+            "the compositor has no wp_viewporter, so it does not fill the output".to_string()
+        };
+        println!(
+            "[rendercore] warning: {label} needs a {}x{} buffer, over the GPU limit of {max_texture_dimension_2d}; rendering {}x{} ({how})",
+            surface_size.0 * preferred_scale.max(1) as u32,
+            surface_size.1 * preferred_scale.max(1) as u32,
+            fit.size.0,
+            fit.size.1
+        );
+    } else if !clamped && was_clamped {
+        println!(
+            "[rendercore] {label} fits the GPU limit again, rendering {}x{}",
+            fit.size.0, fit.size.1
+        );
+    }
+    clamped
+}
+
+/// Scales `size` down, keeping its aspect, until both sides fit the GPU limit.
+fn clamp_source_size(size: (u32, u32), max_texture_dimension_2d: u32) -> (u32, u32) {
+    let (width, height) = size;
+    if width <= max_texture_dimension_2d && height <= max_texture_dimension_2d {
+        return size;
+    }
+    let scale_w = max_texture_dimension_2d as f64 / width as f64;
+    let scale_h = max_texture_dimension_2d as f64 / height as f64;
+    let scale = scale_w.min(scale_h).min(1.0);
+    (
+        ((width as f64 * scale).floor() as u32).max(1),
+        ((height as f64 * scale).floor() as u32).max(1),
+    )
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for WaylandLayerState {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            match interface.as_str() {
+                "wl_compositor" => {
+                    let v = version.min(6);
+                    state.compositor = Some(registry.bind(name, v, qh, ()));
+                }
+                "zwlr_layer_shell_v1" => {
+                    let v = version.min(4);
+                    state.layer_shell = Some(registry.bind(name, v, qh, ()));
+                }
+                "xdg_wm_base" => {
+                    let v = version.min(5);
+                    state.xdg_wm_base = Some(registry.bind(name, v, qh, ()));
+                }
+                "wp_viewporter" => {
+                    state.viewporter = Some(registry.bind(name, 1, qh, ()));
+                }
+                "wp_color_manager_v1" => {
+                    state.color_manager = Some(registry.bind(name, 1, qh, ()));
+                    let outputs = state.outputs.keys().copied().collect::<Vec<_>>();
+                    for output_id in outputs {
+                        state.watch_output_color(output_id, qh);
+                    }
+                }
+                "wl_output" => {
+                    let v = version.min(4);
+                    let output: wl_output::WlOutput = registry.bind(name, v, qh, name);
+                    journal::record("output_added", &[("output_id", Value::U64(name as u64))]);
+                    state.outputs.insert(
+                        name,
+                        OutputSlot {
+                            global_name: name,
+                            output,
+                            identity: OutputIdentity::default(),
+                            excluded: false,
+                            gone: false,
+                            width: None,
+                            height: None,
+                            refresh_hz: None,
+                            transform: OutputTransform::Normal,
+                            suspect_mode: None,
+                            color_output: None,
+                            image_description: None,
+                            pending_description: None,
+                            colorimetry: None,
+                            pending_colorimetry: OutputColorimetry::default(),
+                        },
+                    );
+                    state.watch_output_color(name, qh);
+                }
+                _ => {}
+            }
+        } else if let wl_registry::Event::GlobalRemove { name } = event
+            && let Some(out) = state.outputs.get_mut(&name)
+        {
+            journal::record("output_removed", &[("output_id", Value::U64(name as u64))]);
+            // Torn down on the next frame, after its GPU surface.
+            out.gone = true;
+            state.update_name_collisions();
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for WaylandLayerState {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        global_name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let renamed = matches!(event, wl_output::Event::Name { .. });
+        let Some(out) = state.outputs.get_mut(global_name) else {
+            return;
+        };
+        // Name and description are complete now; `desc:` aliases need both.
+        if let wl_output::Event::Done = event {
+            out.identity.apply_aliases(&state.aliases);
+        }
+
+        match event {
+            wl_output::Event::Name { name } => {
+                journal::record(
+                    "output_name",
+                    &[
+                        ("output_id", Value::U64(*global_name as u64)),
+                        ("name", Value::Str(&name)),
+                    ],
+                );
+                if out.identity.set_name(name.clone()) {
+                    hooks::fire(
+                        HookEvent::OutputAdded,
+                        HookContext {
+                            monitor: Some(&name),
+                            ..HookContext::default()
+                        },
+                    );
+                }
+            }
+            wl_output::Event::Description { description } => {
+                out.identity.set_description(description);
+            }
+            wl_output::Event::Done
+                if !out.excluded
+                    && !state.allowlist.allows(
+                        out.identity.name.as_deref(),
+                        out.identity.description.as_deref(),
+                        &out.identity.aliases,
+                    ) =>
+            {
+                out.excluded = true;
+                println!(
+                    "[rendercore] output {} excluded by output allowlist ({})",
+                    out.display_name(),
+                    state.allowlist.entries().join(",")
+                );
+            }
+            wl_output::Event::Geometry {
+                transform: WEnum::Value(transform),
+                ..
+            } => {
+                let transform = output_transform_from_wl(transform);
+                if transform != out.transform {
+                    journal::record(
+                        "output_transform",
+                        &[
+                            ("output_id", Value::U64(*global_name as u64)),
+                            ("transform", Value::Str(&format!("{transform:?}"))),
+                        ],
+                    );
+                    out.transform = transform;
+                }
+            }
+            wl_output::Event::Mode {
+                flags,
+                width,
+                height,
+                refresh,
+            } => {
+                if let WEnum::Value(bits) = flags
+                    && bits.contains(wl_output::Mode::Current)
+                    && !plausible_mode(width, height)
+                {
+                    let name = out.display_name();
+                    if out.suspect_mode.is_none() {
+                        eprintln!(
+                            "[rendercore] warning: output {name}: ignored implausible mode {width}x{height}, keeping {}",
+                            match (out.width, out.height) {
+                                (Some(w), Some(h)) => format!("{w}x{h}"),
+                                _ => "the default size".to_string(),
+                            }
+                        );
+                    }
+                    out.suspect_mode = Some((width, height));
+                    journal::record(
+                        "output_mode_rejected",
+                        &[
+                            ("output_id", Value::U64(*global_name as u64)),
+                            ("width", Value::U64(width.max(0) as u64)),
+                            ("height", Value::U64(height.max(0) as u64)),
+                        ],
+                    );
+                } else if let WEnum::Value(bits) = flags
+                    && bits.contains(wl_output::Mode::Current)
+                {
+                    let name = out.display_name();
+                    if out.suspect_mode.take().is_some() {
+                        println!("[rendercore] output {name}: mode {width}x{height} accepted");
+                    }
+                    out.width = Some(width as u32);
+                    out.height = Some(height as u32);
+                    match filter_refresh(out.refresh_hz, refresh) {
+                        RefreshSample::Changed(hz) => {
+                            out.refresh_hz = Some(hz);
+                            let ceiling = state.fps_ceiling;
+                            if ceiling > 0 && hz > ceiling {
+                                println!(
+                                    "[rendercore] output {name}: panel refreshes at {hz}Hz, wallpaper capped at {ceiling}fps (render loop / KRC_MAX_OUTPUT_FPS); video decode stays at KRC_VIDEO_FPS"
+                                );
+                            } else if hz < ceiling {
+                                println!(
+                                    "[rendercore] output {name}: panel refreshes at {hz}Hz, wallpaper renders at {hz}fps"
+                                );
+                            }
+                        }
+                        RefreshSample::Jitter => {}
+                        RefreshSample::Implausible => println!(
+                            "[rendercore] output {name}: ignored refresh {refresh}mHz, keeping {}",
+                            out.refresh_hz
+                                .map_or("the default".to_string(), |hz| format!("{hz}Hz"))
+                        ),
+                    }
+                    journal::record(
+                        "output_mode",
+                        &[
+                            ("output_id", Value::U64(*global_name as u64)),
+                            ("width", Value::U64(width as u64)),
+                            ("height", Value::U64(height as u64)),
+                            ("refresh_mhz", Value::U64(refresh.max(0) as u64)),
+                        ],
+                    );
+                }
+            }
+            _ => {}
+        }
+        if renamed {
+            state.update_name_collisions();
+        }
+    }
+}
+
+fn output_transform_to_wl(transform: OutputTransform) -> wl_output::Transform {
+    match transform {
+        OutputTransform::Normal => wl_output::Transform::Normal,
+        OutputTransform::Rotate90 => wl_output::Transform::_90,
+        OutputTransform::Rotate180 => wl_output::Transform::_180,
+        OutputTransform::Rotate270 => wl_output::Transform::_270,
+        OutputTransform::Flipped => wl_output::Transform::Flipped,
+        OutputTransform::Flipped90 => wl_output::Transform::Flipped90,
+        OutputTransform::Flipped180 => wl_output::Transform::Flipped180,
+        OutputTransform::Flipped270 => wl_output::Transform::Flipped270,
+    }
+}
+
+fn output_transform_from_wl(transform: wl_output::Transform) -> OutputTransform {
+    match transform {
+        wl_output::Transform::_90 => OutputTransform::Rotate90,
+        wl_output::Transform::_180 => OutputTransform::Rotate180,
+        wl_output::Transform::_270 => OutputTransform::Rotate270,
+        wl_output::Transform::Flipped => OutputTransform::Flipped,
+        wl_output::Transform::Flipped90 => OutputTransform::Flipped90,
+        wl_output::Transform::Flipped180 => OutputTransform::Flipped180,
+        wl_output::Transform::Flipped270 => OutputTransform::Flipped270,
+        _ => OutputTransform::Normal,
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, u32> for WaylandLayerState {
+    fn event(
+        state: &mut Self,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        output_id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer_surface.ack_configure(serial);
+                state.configure_slot(*output_id, width, height);
+            }
+            zwlr_layer_surface_v1::Event::Closed => state.close_slot(*output_id),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<XdgWmBase, ()> for WaylandLayerState {
+    fn event(
+        _: &mut Self,
+        wm_base: &XdgWmBase,
+        event: xdg_wm_base::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, u32> for WaylandLayerState {
+    fn event(
+        state: &mut Self,
+        xdg_surface: &XdgSurface,
+        event: xdg_surface::Event,
+        output_id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let xdg_surface::Event::Configure { serial } = event else {
+            return;
+        };
+        xdg_surface.ack_configure(serial);
+        let size = match state.slot_mut(*output_id).map(|slot| &slot.role) {
+            Some(SurfaceRole::Window { size, .. }) => *size,
+            _ => (0, 0),
+        };
+        state.configure_slot(*output_id, size.0, size.1);
+    }
+}
+
+impl Dispatch<XdgToplevel, u32> for WaylandLayerState {
+    fn event(
+        state: &mut Self,
+        _: &XdgToplevel,
+        event: xdg_toplevel::Event,
+        output_id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            xdg_toplevel::Event::Configure { width, height, .. } => {
+                if let Some(SurfaceRole::Window { size, .. }) =
+                    state.slot_mut(*output_id).map(|slot| &mut slot.role)
+                {
+                    *size = (width.max(0) as u32, height.max(0) as u32);
+                }
+            }
+            // Closing the window (a keybinding, the overview) clears it like a
+            // closed layer surface.
+            xdg_toplevel::Event::Close => state.close_slot(*output_id),
+            _ => {}
+        }
     }
+}
 
-    let scale_w = max_texture_dimension_2d as f64 / width as f64;
-    let scale_h = max_texture_dimension_2d as f64 / height as f64;
-    let scale = scale_w.min(scale_h).min(1.0);
-    let clamped_w = ((width as f64 * scale).floor() as u32).max(1);
-    let clamped_h = ((height as f64 * scale).floor() as u32).max(1);
-    eprintln!(
-        "[rendercore] requested source {}x{} exceeds GPU max {}; clamped to {}x{}",
-        width, height, max_texture_dimension_2d, clamped_w, clamped_h
-    );
-    (clamped_w, clamped_h)
+impl Dispatch<wl_callback::WlCallback, u32> for WaylandLayerState {
+    fn event(
+        state: &mut Self,
+        _: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        output_id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let wl_callback::Event::Done { .. } = event else {
+            return;
+        };
+        let refresh = state.refresh_interval(*output_id);
+        let Some(slot) = state.slot_mut(*output_id) else {
+            return;
+        };
+        slot.frame_callback_pending = false;
+        slot.frame_callback = None;
+        if slot.configured {
+            slot.needs_redraw = true;
+        }
+        if let Some(change) = slot.callback_latency.done(Instant::now(), refresh) {
+            state.log_callback_change(*output_id, change);
+        }
+    }
 }
 
-impl Dispatch<wl_registry::WlRegistry, ()> for WaylandLayerState {
+impl Dispatch<WlSurface, ()> for WaylandLayerState {
     fn event(
         state: &mut Self,
-        registry: &wl_registry::WlRegistry,
-        event: wl_registry::Event,
+        surface: &WlSurface,
+        event: wl_surface::Event,
         _: &(),
         _: &Connection,
-        qh: &QueueHandle<Self>,
+        _: &QueueHandle<Self>,
     ) {
-        if let wl_registry::Event::Global {
-            name,
-            interface,
-            version,
-        } = event
-        {
-            match interface.as_str() {
-                "wl_compositor" => {
-                    let v = version.min(6);
-                    state.compositor = Some(registry.bind(name, v, qh, ()));
-                }
-                "zwlr_layer_shell_v1" => {
-                    let v = version.min(4);
-                    state.layer_shell = Some(registry.bind(name, v, qh, ()));
+        let Some(slot) = state
+            .layer_surfaces
+            .iter_mut()
+            .find(|slot| slot.surface == *surface)
+        else {
+            return;
+        };
+        // Picked up by render_textured's resize check on the next frame.
+        match event {
+            wl_surface::Event::PreferredBufferScale { factor } if factor >= 1 => {
+                if slot.preferred_scale != Some(factor) {
+                    println!(
+                        "[rendercore] output id={} compositor prefers buffer scale {factor}",
+                        slot.output_global_name
+                    );
                 }
-                "wl_output" => {
-                    let v = version.min(4);
-                    let output: wl_output::WlOutput = registry.bind(name, v, qh, name);
-                    state.outputs.insert(
-                        name,
-                        OutputSlot {
-                            global_name: name,
-                            output,
-                            name: None,
-                            width: None,
-                            height: None,
-                            refresh_hz: None,
-                        },
+                slot.preferred_scale = Some(factor);
+            }
+            wl_surface::Event::PreferredBufferTransform {
+                transform: WEnum::Value(transform),
+            } => {
+                let transform = output_transform_from_wl(transform);
+                if slot.preferred_transform != Some(transform) {
+                    println!(
+                        "[rendercore] output id={} compositor prefers buffer transform {}",
+                        slot.output_global_name,
+                        transform.as_str()
                     );
                 }
-                _ => {}
+                slot.preferred_transform = Some(transform);
             }
+            _ => return,
+        }
+        if slot.configured {
+            slot.needs_redraw = true;
+            slot.must_redraw = true;
         }
     }
 }
 
-impl Dispatch<wl_output::WlOutput, u32> for WaylandLayerState {
+delegate_noop!(WaylandLayerState: ignore wl_compositor::WlCompositor);
+delegate_noop!(WaylandLayerState: ignore wl_region::WlRegion);
+delegate_noop!(WaylandLayerState: ignore ZwlrLayerShellV1);
+delegate_noop!(WaylandLayerState: ignore WpViewporter);
+delegate_noop!(WaylandLayerState: ignore WpViewport);
+delegate_noop!(WaylandLayerState: ignore WpColorManagerV1);
+delegate_noop!(WaylandLayerState: ignore WpColorManagementSurfaceV1);
+
+impl Dispatch<WpColorManagementOutputV1, u32> for WaylandLayerState {
     fn event(
         state: &mut Self,
-        _: &wl_output::WlOutput,
-        event: wl_output::Event,
+        _: &WpColorManagementOutputV1,
+        event: wp_color_management_output_v1::Event,
         global_name: &u32,
         _: &Connection,
-        _: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wp_color_management_output_v1::Event::ImageDescriptionChanged = event
+            && let Some(out) = state.outputs.get_mut(global_name)
+        {
+            out.request_image_description(qh);
+        }
+    }
+}
+
+impl Dispatch<WpImageDescriptionV1, u32> for WaylandLayerState {
+    fn event(
+        state: &mut Self,
+        description: &WpImageDescriptionV1,
+        event: wp_image_description_v1::Event,
+        global_name: &u32,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
     ) {
         let Some(out) = state.outputs.get_mut(global_name) else {
+            description.destroy();
             return;
         };
-
+        if out.pending_description.as_ref() != Some(description) {
+            return;
+        }
+        out.pending_description = None;
         match event {
-            wl_output::Event::Name { name } => {
-                out.name = Some(name);
+            wp_image_description_v1::Event::Ready { .. } => {
+                description.get_information(qh, *global_name);
+                out.pending_colorimetry = OutputColorimetry::default();
+                if let Some(old) = out.image_description.replace(description.clone()) {
+                    old.destroy();
+                }
             }
-            wl_output::Event::Mode {
-                flags,
-                width,
-                height,
-                refresh,
-            } => {
-                if let WEnum::Value(bits) = flags {
-                    if bits.contains(wl_output::Mode::Current) {
-                        out.width = Some(width.max(1) as u32);
-                        out.height = Some(height.max(1) as u32);
-                        out.refresh_hz = Some(((refresh as f32) / 1000.0).round().max(1.0) as u32);
-                    }
+            wp_image_description_v1::Event::Failed { msg, .. } => {
+                eprintln!(
+                    "[rendercore] warning: output={} (id={}) has no image description: {}",
+                    out.display_name(),
+                    global_name,
+                    msg
+                );
+                description.destroy();
+                if let Some(old) = out.image_description.take() {
+                    old.destroy();
                 }
+                out.colorimetry = None;
+                state.redraw_output(*global_name);
             }
             _ => {}
         }
     }
 }
 
-impl Dispatch<ZwlrLayerSurfaceV1, u32> for WaylandLayerState {
+impl Dispatch<WpImageDescriptionInfoV1, u32> for WaylandLayerState {
     fn event(
         state: &mut Self,
-        layer_surface: &ZwlrLayerSurfaceV1,
-        event: zwlr_layer_surface_v1::Event,
-        index: &u32,
+        _: &WpImageDescriptionInfoV1,
+        event: wp_image_description_info_v1::Event,
+        global_name: &u32,
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
+        let Some(out) = state.outputs.get_mut(global_name) else {
+            return;
+        };
+        let pending = &mut out.pending_colorimetry;
         match event {
-            zwlr_layer_surface_v1::Event::Configure {
-                serial,
-                width,
-                height,
+            wp_image_description_info_v1::Event::Primaries {
+                r_x,
+                r_y,
+                g_x,
+                g_y,
+                b_x,
+                b_y,
+                w_x,
+                w_y,
             } => {
-                layer_surface.ack_configure(serial);
-                if let Some(slot) = state.layer_surfaces.get_mut(*index as usize) {
-                    slot.configured = true;
-                    slot.configured_width = (width > 0).then_some(width);
-                    slot.configured_height = (height > 0).then_some(height);
-                    slot.needs_redraw = true;
-                    if width > 0 && height > 0 {
-                        slot.surface.commit();
-                    }
-                }
+                pending.primaries = Some(Primaries::from_millionths([
+                    r_x, r_y, g_x, g_y, b_x, b_y, w_x, w_y,
+                ]));
             }
-            zwlr_layer_surface_v1::Event::Closed => {
-                if let Some(slot) = state.layer_surfaces.get_mut(*index as usize) {
-                    slot.configured = false;
-                    slot.configured_width = None;
-                    slot.configured_height = None;
-                    slot.needs_redraw = false;
-                    slot.frame_callback_pending = false;
-                    slot.frame_callback = None;
+            wp_image_description_info_v1::Event::PrimariesNamed { primaries } => {
+                if let Some((name, primaries)) = Primaries::named(wenum_value(primaries)) {
+                    pending.primaries_name = Some(name);
+                    pending.primaries = Some(primaries);
                 }
             }
+            wp_image_description_info_v1::Event::TfNamed { tf } => {
+                pending.transfer = Some(Transfer::named(wenum_value(tf)));
+            }
+            wp_image_description_info_v1::Event::TfPower { eexp } => {
+                pending.transfer = Some(Transfer::Power(eexp));
+            }
+            wp_image_description_info_v1::Event::Done => {
+                let colorimetry = std::mem::take(pending);
+                println!(
+                    "[rendercore] output={} (id={}) image description: primaries={} transfer={}",
+                    out.display_name(),
+                    global_name,
+                    colorimetry.primaries_name.map_or_else(
+                        || colorimetry
+                            .primaries
+                            .map_or_else(|| "unknown".to_string(), |p| p.to_string()),
+                        str::to_string
+                    ),
+                    colorimetry
+                        .transfer
+                        .map_or_else(|| "unknown".to_string(), |tf| tf.to_string())
+                );
+                out.colorimetry = Some(colorimetry);
+                state.redraw_output(*global_name);
+            }
             _ => {}
         }
     }
 }
 
-impl Dispatch<wl_callback::WlCallback, u32> for WaylandLayerState {
-    fn event(
-        state: &mut Self,
-        _: &wl_callback::WlCallback,
-        event: wl_callback::Event,
-        index: &u32,
-        _: &Connection,
-        _: &QueueHandle<Self>,
-    ) {
-        if let wl_callback::Event::Done { .. } = event {
-            if let Some(slot) = state.layer_surfaces.get_mut(*index as usize) {
-                slot.frame_callback_pending = false;
-                slot.frame_callback = None;
-                if slot.configured {
-                    slot.needs_redraw = true;
+/// A protocol enum's wire value, known to this build or not.
+fn wenum_value<T: Into<u32>>(value: WEnum<T>) -> u32 {
+    match value {
+        WEnum::Value(value) => value.into(),
+        WEnum::Unknown(raw) => raw,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_names_reported_twice_are_shared() {
+        let shared = shared_names([(7, "eDP-1"), (9, "DP-1"), (12, "eDP-1"), (3, "HDMI-A-1")]);
+        assert_eq!(shared, vec![("eDP-1", vec![7, 12])]);
+        assert!(shared_names([(7, "eDP-1"), (9, "DP-1")]).is_empty());
+    }
+
+    #[test]
+    fn removing_one_of_a_pair_ends_the_collision() {
+        let mut outputs = vec![(7, "DP-1"), (9, "DP-1"), (12, "DP-1")];
+        assert_eq!(
+            shared_names(outputs.clone()),
+            vec![("DP-1", vec![7, 9, 12])]
+        );
+        outputs.retain(|(id, _)| *id != 12);
+        assert_eq!(shared_names(outputs.clone()), vec![("DP-1", vec![7, 9])]);
+        outputs.retain(|(id, _)| *id != 9);
+        assert!(shared_names(outputs).is_empty());
+    }
+
+    #[test]
+    fn unknown_outputs_are_labelled_by_id() {
+        assert_eq!(output_label(&BTreeMap::new(), 42), "wl-output-42");
+    }
+
+    fn entry(raw: &str) -> VideoMapEntry {
+        crate::video_map::parse_entry_value(raw, std::path::Path::new("/v")).unwrap()
+    }
+
+    const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+    /// The key a stream would get on a 1920x1080 global source size: the
+    /// entry's size, turned for a rotation that swaps the axes.
+    fn key_with(entry: &VideoMapEntry, decode_interval: Duration) -> DecoderKey {
+        let (w, h) = entry_base_size((1920, 1080), 8192, entry.options);
+        let size = if entry.options.rotate.swaps_axes() {
+            (h, w)
+        } else {
+            (w, h)
+        };
+        DecoderKey::new(entry, size, 1, decode_interval)
+    }
+
+    fn key(raw: &str) -> DecoderKey {
+        key_with(&entry(raw), FRAME_INTERVAL)
+    }
+
+    #[test]
+    fn render_side_options_share_a_decoder() {
+        let base = key("/v/a.mp4");
+        for raw in [
+            "/v/a.mp4 rotate=180",
+            "/v/a.mp4 flip=hv",
+            "/v/a.mp4 effect=ambient amplitude=0.1",
+            "/v/a.mp4 overlay=snow density=0.8",
+            "/v/a.mp4 filter=nearest",
+            "/v/a.mp4 gamut=srgb",
+            "/v/a.mp4 clock=on",
+            // The medium preset is the global size here.
+            "/v/a.mp4 quality=medium",
+            "/v/a.mp4 rotate=180 flip=v effect=ambient overlay=rain",
+        ] {
+            assert_eq!(key(raw), base, "{raw}");
+        }
+    }
+
+    #[test]
+    fn decoder_side_options_split_the_group() {
+        let base = key("/v/a.mp4");
+        for raw in [
+            "/v/b.mp4",
+            "/v/a.mp4 trim=5:65",
+            "still:/v/a.mp4@00:00:10",
+            "/v/a.mp4 quality=high",
+            "/v/a.mp4 rotate=90",
+            "/v/a.mp4 rotate=270 flip=h",
+        ] {
+            assert_ne!(key(raw), base, "{raw}");
+        }
+        assert_ne!(
+            key("/v/a.mp4 timebase=utc"),
+            key("/v/a.mp4 timebase=monotonic")
+        );
+        assert_ne!(
+            key_with(&entry("/v/a.mp4"), FRAME_INTERVAL * 2),
+            base,
+            "decode rate"
+        );
+        // Two portrait rotations decode the same shape.
+        assert_eq!(key("/v/a.mp4 rotate=90"), key("/v/a.mp4 rotate=270 flip=v"));
+    }
+
+    #[test]
+    fn the_first_stream_with_a_key_leads_its_group() {
+        let keys = vec![
+            (3, Some(key("/v/a.mp4"))),
+            (5, None),
+            (7, Some(key("/v/a.mp4 flip=h"))),
+            (9, Some(key("/v/a.mp4 quality=high"))),
+            (11, Some(key("/v/b.mp4"))),
+            (13, Some(key("/v/a.mp4 quality=high rotate=180"))),
+            (15, Some(key("/v/a.mp4 effect=ambient"))),
+        ];
+        let leaders = decoder_leaders(&keys);
+        assert_eq!(
+            leaders.into_iter().collect::<Vec<_>>(),
+            vec![(7, 3), (13, 9), (15, 3)]
+        );
+        assert!(decoder_leaders(&[(1, None), (2, None)]).is_empty());
+    }
+
+    #[test]
+    fn an_option_moving_an_entry_regroups_it() {
+        let mut keys = vec![
+            (3, Some(key("/v/a.mp4"))),
+            (7, Some(key("/v/a.mp4 flip=h"))),
+        ];
+        assert_eq!(decoder_leaders(&keys).get(&7), Some(&3));
+        keys[1].1 = Some(key("/v/a.mp4 flip=h quality=high"));
+        assert!(decoder_leaders(&keys).is_empty());
+        keys[0].1 = Some(key("/v/a.mp4 quality=high"));
+        assert_eq!(decoder_leaders(&keys).get(&7), Some(&3));
+    }
+
+    fn change(old: Option<&str>, new: Option<&str>) -> EntryChange {
+        let (old, new) = (old.map(entry), new.map(entry));
+        EntryChange::classify(old.as_ref(), new.as_ref(), |entry| {
+            entry_base_size((1920, 1080), 8192, entry.options)
+        })
+    }
+
+    #[test]
+    fn entry_changes_take_the_smallest_action() {
+        let a = Some("/v/a.mp4");
+        for (new, want) in [
+            ("/v/a.mp4", EntryChange::Unchanged),
+            ("/v/a.mp4 flip=h", EntryChange::Uniforms),
+            ("/v/a.mp4 rotate=180", EntryChange::Uniforms),
+            (
+                "/v/a.mp4 effect=ambient amplitude=0.2",
+                EntryChange::Uniforms,
+            ),
+            ("/v/a.mp4 overlay=snow", EntryChange::Uniforms),
+            ("/v/a.mp4 gamut=srgb filter=nearest", EntryChange::Uniforms),
+            ("/v/a.mp4 quality=medium", EntryChange::Uniforms),
+            ("/v/a.mp4 quality=high", EntryChange::Rebuild),
+            ("/v/a.mp4 rotate=90", EntryChange::Rebuild),
+            ("/v/b.mp4", EntryChange::Restart),
+            ("/v/a.mp4 trim=1:9", EntryChange::Restart),
+            ("/v/a.mp4 timebase=utc", EntryChange::Restart),
+            ("still:/v/a.mp4@00:00:05", EntryChange::Restart),
+        ] {
+            assert_eq!(change(a, Some(new)), want, "{new}");
+        }
+    }
+
+    #[test]
+    fn mapping_changes_restart_and_stills_never_update_in_place() {
+        assert_eq!(change(None, None), EntryChange::Unchanged);
+        assert_eq!(change(None, Some("/v/a.mp4")), EntryChange::Restart);
+        assert_eq!(change(Some("/v/a.mp4"), None), EntryChange::Restart);
+        let still = Some("still:/v/a.mp4@00:00:05");
+        assert_eq!(change(still, still), EntryChange::Unchanged);
+        assert_eq!(
+            change(still, Some("still:/v/a.mp4@00:00:05 flip=h")),
+            EntryChange::Restart
+        );
+        // The widest change wins: a new video with new flips restarts.
+        assert_eq!(
+            change(Some("/v/a.mp4"), Some("/v/b.mp4 flip=h quality=high")),
+            EntryChange::Restart
+        );
+        assert_eq!(
+            change(Some("/v/a.mp4 flip=v"), Some("/v/a.mp4 quality=ultra")),
+            EntryChange::Rebuild
+        );
+    }
+
+    #[test]
+    fn surface_within_the_limit_is_left_alone() {
+        let fit = fit_surface((1920, 1080), 2, 8192);
+        assert_eq!(
+            fit,
+            SurfaceFit {
+                size: (3840, 2160),
+                scale: 2,
+                shrunk: false,
+            }
+        );
+        assert!(!fit.clamped(2));
+        // A compositor that sends no scale gets scale 1.
+        assert_eq!(fit_surface((1920, 1080), 0, 8192).scale, 1);
+    }
+
+    #[test]
+    fn scale_steps_down_before_the_buffer_shrinks() {
+        let fit = fit_surface((2560, 1440), 2, 4096);
+        assert_eq!(fit.size, (2560, 1440));
+        assert_eq!(fit.scale, 1);
+        assert!(!fit.shrunk);
+        assert!(fit.clamped(2));
+
+        let fit = fit_surface((1920, 1080), 3, 4096);
+        assert_eq!((fit.size, fit.scale, fit.shrunk), ((3840, 2160), 2, false));
+    }
+
+    #[test]
+    fn too_wide_surface_shrinks_with_its_aspect() {
+        // The super-ultrawide from the report, on a GPU limited to 4096.
+        let fit = fit_surface((7680, 2160), 1, 4096);
+        assert_eq!(fit.size, (4096, 1152));
+        assert!(fit.shrunk);
+        assert!(fit.clamped(1));
+        assert_eq!(fit_surface((7680, 2160), 2, 4096).size, (4096, 1152));
+    }
+
+    #[test]
+    fn too_tall_surface_shrinks_with_its_aspect() {
+        let fit = fit_surface((1440, 8000), 1, 4096);
+        assert_eq!(fit.size, (737, 4096));
+        assert!(fit.shrunk);
+        // Exactly at the limit is not clamped, on either side.
+        assert!(!fit_surface((4096, 1000), 1, 4096).shrunk);
+        assert!(!fit_surface((1000, 4096), 1, 4096).shrunk);
+    }
+
+    #[test]
+    fn clamped_sizes_never_reach_zero() {
+        assert_eq!(clamp_source_size((100_000, 1), 4096), (4096, 1));
+        assert_eq!(clamp_source_size((1, 100_000), 4096), (1, 4096));
+    }
+
+    /// `integer_fit` back in output pixels: (x, y, w, h).
+    fn integer_fit_px(shown: (u32, u32), display: (u32, u32)) -> (u32, u32, u32, u32) {
+        let [x, y, w, h] = integer_fit(shown, display);
+        let px = |uv: f32, size: u32| (uv as f64 * size as f64).round() as u32;
+        (
+            px(x, display.0),
+            px(y, display.1),
+            px(w, display.0),
+            px(h, display.1),
+        )
+    }
+
+    #[test]
+    fn integer_fit_scales_by_whole_multiples_and_centres() {
+        let cases = [
+            // Exact multiples fill the output.
+            ((320, 180), (1920, 1080), (0, 0, 1920, 1080)),
+            ((320, 180), (2560, 1440), (0, 0, 2560, 1440)),
+            // SNES on 1080p: height limits the scale to 4.
+            ((256, 224), (1920, 1080), (448, 92, 1024, 896)),
+            // 1366x768 is not a multiple of anything common.
+            ((640, 360), (1366, 768), (43, 24, 1280, 720)),
+            // Game Boy on an ultrawide.
+            ((160, 144), (3440, 1440), (920, 0, 1600, 1440)),
+            // Landscape art on a portrait output.
+            ((320, 180), (1080, 1920), (60, 690, 960, 540)),
+        ];
+        for (shown, display, expected) in cases {
+            assert_eq!(
+                integer_fit_px(shown, display),
+                expected,
+                "{shown:?} on {display:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn integer_fit_divides_pictures_larger_than_the_output() {
+        assert_eq!(
+            integer_fit_px((3840, 2160), (1920, 1080)),
+            (0, 0, 1920, 1080)
+        );
+        // Too wide by a little: the next whole divisor, not a fraction.
+        assert_eq!(
+            integer_fit_px((2000, 1000), (1920, 1080)),
+            (460, 290, 1000, 500)
+        );
+        assert_eq!(
+            integer_fit_px((7680, 4320), (2560, 1080)),
+            (320, 0, 1920, 1080)
+        );
+    }
+
+    #[test]
+    fn integer_fit_keeps_square_pixels_inside_the_output() {
+        let shown_sizes = [
+            (1, 1),
+            (7, 5),
+            (160, 144),
+            (255, 223),
+            (1921, 1081),
+            (5000, 3),
+        ];
+        let displays = [(1, 1), (800, 600), (1366, 768), (2560, 1080), (1080, 2400)];
+        for shown in shown_sizes {
+            for display in displays {
+                let (x, y, w, h) = integer_fit_px(shown, display);
+                let label = format!("{shown:?} on {display:?}");
+                assert!(w >= 1 && h >= 1, "{label}");
+                assert!(x + w <= display.0 && y + h <= display.1, "{label}");
+                // Centred to within the one pixel an odd margin leaves.
+                assert!((display.0 - w - 2 * x) <= 1, "{label}");
+                assert!((display.1 - h - 2 * y) <= 1, "{label}");
+                if w >= shown.0 {
+                    assert!(w.is_multiple_of(shown.0), "{label}");
+                    assert_eq!(w / shown.0, h / shown.1, "{label}");
                 }
             }
         }
+        // Degenerate sizes do not divide by zero.
+        assert_eq!(integer_fit((0, 0), (0, 0)), [0.0, 0.0, 1.0, 1.0]);
     }
-}
 
-delegate_noop!(WaylandLayerState: ignore wl_compositor::WlCompositor);
-delegate_noop!(WaylandLayerState: ignore wl_surface::WlSurface);
-delegate_noop!(WaylandLayerState: ignore ZwlrLayerShellV1);
+    fn map_entries(doc: &MapDocument) -> Vec<(String, String)> {
+        doc.entries
+            .iter()
+            .map(|(monitor, entry)| (monitor.clone(), entry.video.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn delete_then_write_keeps_the_last_map() {
+        let dir = std::env::temp_dir().join(format!("krc-reload-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("video-map.conf");
+        let entry = |video: &str| vec![("DP-1".to_string(), video.to_string())];
+        std::fs::write(&path, "DP-1=/v/a.mp4\n").unwrap();
+        let mut map = LastGoodMap::load(&path);
+        assert_eq!(map_entries(&map.doc), entry("/v/a.mp4"));
+        assert!(!map.unreadable);
+
+        // The editor deletes the file: the reload keeps the last map.
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(map_entries(&map.reread(&path)), entry("/v/a.mp4"));
+        assert!(map.unreadable);
+        assert_eq!(map_entries(&map.reread(&path)), entry("/v/a.mp4"));
+
+        // ... and writes it again.
+        std::fs::write(&path, "DP-1=/v/b.mp4\n").unwrap();
+        assert_eq!(map_entries(&map.reread(&path)), entry("/v/b.mp4"));
+        assert!(!map.unreadable);
+
+        // A present, empty file does clear the mappings.
+        std::fs::write(&path, "").unwrap();
+        assert!(map.reread(&path).entries.is_empty());
+        std::fs::remove_file(&path).unwrap();
+        assert!(map.reread(&path).entries.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn no_map_file_at_startup_is_an_empty_map() {
+        let path =
+            std::env::temp_dir().join(format!("krc-reload-{}-none.toml", std::process::id()));
+        let map = LastGoodMap::load(&path);
+        assert!(map.doc.entries.is_empty());
+        assert!(map.unreadable);
+    }
+
+    #[test]
+    fn a_new_mtime_is_read_once_it_holds_still() {
+        let t = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let mut mtime = MapMtime {
+            last: t(10),
+            pending: None,
+        };
+        assert_eq!(mtime.observe(t(10), false), Some(false));
+        // Deleted: settle first, then one change.
+        assert_eq!(mtime.observe(None, false), None);
+        // Rewritten within the settle time: settle again on the new mtime.
+        assert_eq!(mtime.observe(t(11), false), None);
+        assert_eq!(mtime.observe(t(12), false), None);
+        assert_eq!(mtime.observe(t(12), false), Some(true));
+        assert_eq!(mtime.observe(t(12), false), Some(false));
+        // Back to the old mtime within the settle time is no change at all.
+        assert_eq!(mtime.observe(t(13), false), None);
+        assert_eq!(mtime.observe(t(12), false), Some(false));
+        // An env file change reads the map without waiting.
+        assert_eq!(mtime.observe(t(14), true), Some(true));
+        assert_eq!(mtime.pending, None);
+    }
+
+    #[test]
+    fn shader_time_keeps_frame_steps_at_any_uptime() {
+        let frame = Duration::from_secs(1) / 60;
+        let day = 86_400;
+        for uptime in [60, day, 30 * day, 365 * day] {
+            // Start a second before a wrap, so the frames cross one.
+            let start = Duration::from_secs(uptime - uptime % SHADER_TIME_WRAP_SEC)
+                + Duration::from_secs(SHADER_TIME_WRAP_SEC - 1);
+            let mut wraps = 0;
+            for n in 0..120 {
+                let now = shader_time(start + frame * n);
+                let next = shader_time(start + frame * (n + 1));
+                assert!((0.0..=SHADER_TIME_WRAP_SEC as f32).contains(&now));
+                if next < now {
+                    wraps += 1;
+                    continue;
+                }
+                let step = (next - now) as f64;
+                assert!(
+                    (step - frame.as_secs_f64()).abs() < 5e-4,
+                    "uptime {uptime} s frame {n}: step {step}"
+                );
+            }
+            assert_eq!(wraps, 1, "uptime {uptime} s");
+        }
+    }
+
+    #[test]
+    fn wave_rates_complete_whole_cycles_per_wrap() {
+        for name in ["WAVE_RATE_X", "WAVE_RATE_Y"] {
+            let rate = FRAME_SHADER_WGSL_WAVE
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(&format!("const {name}: f32 = ")))
+                .and_then(|value| value.trim_end_matches(';').parse::<f64>().ok())
+                .unwrap();
+            let cycles = rate * SHADER_TIME_WRAP_SEC as f64 / std::f64::consts::TAU;
+            // Less than a thousandth of a cycle off: no visible jump at the wrap.
+            assert!((cycles - cycles.round()).abs() < 1e-3, "{name}: {cycles}");
+        }
+    }
+
+    #[test]
+    fn ffmpeg_installed_mid_run_is_found_on_the_next_probe() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("krc-ffmpeg-probe-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake = dir.join("bin/ffmpeg");
+        // `[tools] ffmpeg` points at a path that does not exist yet.
+        let locate = || {
+            Tool::Ffmpeg.locate_configured(Some((
+                fake.to_string_lossy().into_owned(),
+                "config.toml [tools]",
+            )))
+        };
+        let options = VideoOptions {
+            fps: 30,
+            speed: 1.0,
+            hwaccel: hw_decode::HwAccel::None,
+        };
+        let start = Instant::now();
+        let mut awaiting = AwaitingFfmpeg::new(options, start);
+
+        // Not probed before the interval is up, even with ffmpeg there.
+        let probed = std::cell::Cell::new(0);
+        let counting = || {
+            probed.set(probed.get() + 1);
+            locate()
+        };
+        assert_eq!(
+            awaiting.probe(start + Duration::from_secs(1), counting),
+            None
+        );
+        assert_eq!(probed.get(), 0);
+
+        let first = start + FFMPEG_PROBE_INTERVAL;
+        assert_eq!(awaiting.probe(first, locate), None);
+        assert_eq!(awaiting.probe_at, first + FFMPEG_PROBE_INTERVAL);
+
+        // Installed, but not executable yet: still missing.
+        std::fs::create_dir_all(fake.parent().unwrap()).unwrap();
+        std::fs::write(&fake, "#!/bin/sh\nexit 0\n").unwrap();
+        let second = first + FFMPEG_PROBE_INTERVAL;
+        assert_eq!(awaiting.probe(second, locate), None);
+
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+        // Found, but only once the next probe is due.
+        assert_eq!(
+            awaiting.probe(second + Duration::from_secs(29), locate),
+            None
+        );
+        let found = awaiting.probe(second + FFMPEG_PROBE_INTERVAL, locate);
+        assert_eq!(found, Some((fake.clone(), "config.toml [tools]")));
+        assert_eq!(awaiting.options, options);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn map(lines: &str) -> VideoMap {
+        let (map, issues) = crate::video_map::parse_video_map_lines(lines, Path::new("/"));
+        assert!(issues.is_empty(), "{issues:?}");
+        map
+    }
+
+    /// The remap flag as `take_remaps` consumes it.
+    fn take_remap(identity: &mut OutputIdentity) -> bool {
+        std::mem::take(&mut identity.needs_remap)
+    }
+
+    #[test]
+    fn a_name_arriving_after_the_surface_resolves_the_entry_again() {
+        let map = map("DP-1=/v/dp.mp4\n");
+        let mut identity = OutputIdentity::default();
+        // The surface was created before `wl_output.name`: the stream was
+        // resolved under the placeholder, which no entry matches.
+        assert_eq!(identity.map_key(7, &map), "wl-output-7");
+        assert!(!take_remap(&mut identity));
+
+        assert!(identity.set_name("DP-1".to_string()));
+        assert!(take_remap(&mut identity));
+        assert_eq!(identity.map_key(7, &map), "DP-1");
+
+        // The same name again (a re-sent `done` batch) is not a change.
+        assert!(!identity.set_name("DP-1".to_string()));
+        assert!(!take_remap(&mut identity));
+        // A rename is.
+        assert!(!identity.set_name("DP-2".to_string()));
+        assert!(take_remap(&mut identity));
+        assert_eq!(identity.map_key(7, &map), "DP-2");
+    }
+
+    #[test]
+    fn descriptions_only_remap_outputs_told_apart_by_them() {
+        let mut identity = OutputIdentity::default();
+        identity.set_name("eDP-1".to_string());
+        take_remap(&mut identity);
+        identity.set_description("BOE 0x095F".to_string());
+        assert!(!take_remap(&mut identity));
+        identity.name_collision = true;
+        identity.set_description("BOE 0x0960".to_string());
+        assert!(take_remap(&mut identity));
+        identity.set_description("BOE 0x0960".to_string());
+        assert!(!take_remap(&mut identity));
+    }
+
+    #[test]
+    fn collisions_follow_outputs_coming_and_going() {
+        let map = map("eDP-1=/v/any.mp4\nSHARP=/v/laptop.mp4\nDell U2720Q=/v/dock.mp4\n");
+        let named = |name: &str, description: &str| {
+            let mut identity = OutputIdentity::default();
+            identity.set_name(name.to_string());
+            identity.set_description(description.to_string());
+            take_remap(&mut identity);
+            identity
+        };
+        let mut laptop = named("eDP-1", "Sharp Corporation LQ134N1");
+        let mut dock = named("eDP-1", "Dell Inc. DELL U2720Q 1A2B");
+        let mut other = named("DP-3", "LG 27GP850");
+
+        update_collisions([(7, &mut laptop), (9, &mut dock), (12, &mut other)]);
+        assert!(laptop.name_collision && dock.name_collision && !other.name_collision);
+        assert!(take_remap(&mut laptop) && take_remap(&mut dock));
+        assert!(!take_remap(&mut other));
+        assert_eq!(laptop.map_key(7, &map), "SHARP");
+        assert_eq!(dock.map_key(9, &map), "Dell U2720Q");
+
+        // Nothing changed: no remaps.
+        update_collisions([(7, &mut laptop), (9, &mut dock), (12, &mut other)]);
+        assert!(!take_remap(&mut laptop) && !take_remap(&mut dock));
+
+        // The dock is unplugged: the laptop goes back to its name.
+        update_collisions([(7, &mut laptop), (12, &mut other)]);
+        assert!(!laptop.name_collision);
+        assert!(take_remap(&mut laptop));
+        assert_eq!(laptop.map_key(7, &map), "eDP-1");
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+use wayland_client::protocol::{wl_output, wl_registry};
+use wayland_client::{Connection, Dispatch, QueueHandle};
+
+/// Output names straight from the Wayland registry (`wl_output.name`, v4),
+/// for CLI commands when no compositor IPC is reachable. Connects through
+/// `WAYLAND_SOCKET` or `WAYLAND_DISPLAY` like the renderer does.
+pub fn wayland_output_names() -> Result<Vec<String>, String> {
+    let connection = Connection::connect_to_env()
+        .map_err(|err| format!("failed to connect wayland display: {err}"))?;
+    let mut event_queue = connection.new_event_queue();
+    let qh = event_queue.handle();
+    connection.display().get_registry(&qh, ());
+    let mut state = OutputNames::default();
+    // First roundtrip binds the outputs, the second delivers their names.
+    for _ in 0..2 {
+        event_queue
+            .roundtrip(&mut state)
+            .map_err(|err| format!("wayland roundtrip failed: {err}"))?;
+    }
+    if state.old_outputs > 0 && state.names.is_empty() {
+        return Err("compositor only offers wl_output < v4, which has no output names".to_string());
+    }
+    let mut names = state.names.into_values().collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+#[derive(Default)]
+struct OutputNames {
+    names: BTreeMap<u32, String>,
+    old_outputs: usize,
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for OutputNames {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+            && interface == "wl_output"
+        {
+            if version < 4 {
+                state.old_outputs += 1;
+                return;
+            }
+            let _: wl_output::WlOutput = registry.bind(name, 4, qh, name);
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for OutputNames {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        global_name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            state.names.insert(*global_name, name);
+        }
+    }
+}
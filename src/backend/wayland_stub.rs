@@ -1,9 +1,186 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::aliases::Aliases;
 use crate::backend::LayerBackend;
-use crate::monitor::{LayerRole, MonitorInfo, MonitorSurfaceSpec};
+use crate::backend::stub_scenario::{self, Scenario, ScenarioPlayer, TopologyChange};
+use crate::config;
+use crate::env_file;
+use crate::hooks::{self, HookContext, HookEvent};
+use crate::journal::{self, Value};
+use crate::monitor::{
+    LayerRole, MonitorInfo, MonitorSurfaceSpec, OutputAllowlist, OutputRuntimeStatus,
+    OutputTransform,
+};
+use crate::quality::{self, AutoQuality};
 
 #[derive(Default)]
 pub struct WaylandLayerStubBackend {
     bootstrapped: bool,
+    allowlist: OutputAllowlist,
+    aliases: Aliases,
+    monitors: Vec<(String, (u32, u32))>,
+    excluded: Vec<String>,
+    first_frame_at: Option<Instant>,
+    frames: u64,
+    quality: AutoQuality,
+    sim: SlowFrameSim,
+    scenario: Option<StubScenario>,
+}
+
+/// A `KRC_STUB_SCENARIO` being played: the outputs present now and the
+/// surfaces built for them, used instead of the ones from bootstrap.
+struct StubScenario {
+    initial: Vec<MonitorInfo>,
+    player: ScenarioPlayer,
+    started_at: Instant,
+    topology: Vec<MonitorInfo>,
+    surfaces: Vec<MonitorSurfaceSpec>,
+    /// Made-up `wl_output` ids for the journal, one per output added.
+    output_ids: BTreeMap<String, u32>,
+    next_output_id: u32,
+}
+
+impl StubScenario {
+    fn load(path: &Path) -> Result<Self, String> {
+        let scenario = Scenario::load(path)?;
+        println!(
+            "[backend:wayland-layer-stub] scenario {}: {} monitor(s), {} event(s)",
+            path.display(),
+            scenario.monitors.len(),
+            scenario.events.len()
+        );
+        Ok(Self {
+            initial: scenario.monitors,
+            player: ScenarioPlayer::new(scenario.events),
+            started_at: Instant::now(),
+            topology: Vec::new(),
+            surfaces: Vec::new(),
+            output_ids: BTreeMap::new(),
+            next_output_id: 1,
+        })
+    }
+
+    /// Journals an output appearing the way the Wayland backend does, and
+    /// fires `on_output_added`.
+    fn record_added(&mut self, monitor: &MonitorInfo) {
+        let output_id = self.next_output_id;
+        self.next_output_id += 1;
+        self.output_ids.insert(monitor.name.clone(), output_id);
+        journal::record(
+            "output_added",
+            &[("output_id", Value::U64(output_id as u64))],
+        );
+        journal::record(
+            "output_name",
+            &[
+                ("output_id", Value::U64(output_id as u64)),
+                ("name", Value::Str(&monitor.name)),
+            ],
+        );
+        self.record_mode(monitor);
+        hooks::fire(
+            HookEvent::OutputAdded,
+            HookContext {
+                monitor: Some(&monitor.name),
+                ..HookContext::default()
+            },
+        );
+    }
+
+    fn record_mode(&self, monitor: &MonitorInfo) {
+        let Some(output_id) = self.output_ids.get(&monitor.name) else {
+            return;
+        };
+        journal::record(
+            "output_mode",
+            &[
+                ("output_id", Value::U64(*output_id as u64)),
+                ("width", Value::U64(monitor.width as u64)),
+                ("height", Value::U64(monitor.height as u64)),
+                ("refresh_mhz", Value::U64(monitor.refresh_hz as u64 * 1000)),
+            ],
+        );
+    }
+
+    /// Applies the events that are due; returns whether the outputs changed.
+    fn advance(&mut self) -> bool {
+        let elapsed = self.started_at.elapsed();
+        let due = self.player.due(elapsed).to_vec();
+        for event in &due {
+            println!(
+                "[backend:wayland-layer-stub] scenario +{:.1}s: {}",
+                event.at.as_secs_f64(),
+                event.change
+            );
+            if let Err(err) = stub_scenario::apply(&mut self.topology, &event.change) {
+                println!("[backend:wayland-layer-stub] warning: scenario event skipped: {err}");
+                continue;
+            }
+            match &event.change {
+                TopologyChange::Add(monitor) => self.record_added(monitor),
+                TopologyChange::Mode { name, .. } => {
+                    if let Some(monitor) = self.topology.iter().find(|m| m.name == *name) {
+                        self.record_mode(monitor);
+                    }
+                }
+                TopologyChange::Remove(name) => {
+                    if let Some(output_id) = self.output_ids.remove(name) {
+                        journal::record(
+                            "output_removed",
+                            &[("output_id", Value::U64(output_id as u64))],
+                        );
+                    }
+                }
+            }
+        }
+        !due.is_empty()
+    }
+}
+
+/// `KRC_SIM_FRAME_DELAY_MS`: pretend each output's frame costs this much at its
+/// configured size (scaled by source pixels at lower quality levels), for the
+/// first `KRC_SIM_SLOW_FOR_S` seconds if set. Lets `KRC_AUTO_QUALITY` degrade
+/// and recover without a GPU.
+#[derive(Default)]
+struct SlowFrameSim {
+    delay: Duration,
+    slow_until: Option<Instant>,
+    base_fps: u32,
+}
+
+impl SlowFrameSim {
+    fn from_env() -> Self {
+        let env_u64 = |key: &str| std::env::var(key).ok().and_then(|v| v.parse::<u64>().ok());
+        let delay = Duration::from_millis(env_u64("KRC_SIM_FRAME_DELAY_MS").unwrap_or(0));
+        let slow_until = env_u64("KRC_SIM_SLOW_FOR_S")
+            .filter(|_| !delay.is_zero())
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        let base_fps = config::video_fps(env_file::var("KRC_VIDEO_FPS").as_deref()).value;
+        if !delay.is_zero() {
+            println!(
+                "[rendercore] simulating {}ms per output frame{}",
+                delay.as_millis(),
+                slow_until
+                    .map(|_| " for a limited time".to_string())
+                    .unwrap_or_default()
+            );
+        }
+        Self {
+            delay,
+            slow_until,
+            base_fps,
+        }
+    }
+
+    fn frame_cost(&self, base_size: (u32, u32), size: (u32, u32), now: Instant) -> Duration {
+        if self.slow_until.is_some_and(|until| now >= until) {
+            return Duration::ZERO;
+        }
+        let ratio = (size.0 * size.1) as f64 / (base_size.0 * base_size.1).max(1) as f64;
+        self.delay.mul_f64(ratio)
+    }
 }
 
 impl LayerBackend for WaylandLayerStubBackend {
@@ -13,6 +190,13 @@ impl LayerBackend for WaylandLayerStubBackend {
 
     fn bootstrap(&mut self) -> Result<(), String> {
         self.bootstrapped = true;
+        self.allowlist = OutputAllowlist::from_env();
+        self.quality = AutoQuality::from_env();
+        self.sim = SlowFrameSim::from_env();
+        self.scenario = match std::env::var("KRC_STUB_SCENARIO") {
+            Ok(path) if !path.trim().is_empty() => Some(StubScenario::load(Path::new(&path))?),
+            _ => None,
+        };
         println!("[backend:{}] bootstrap ok", self.name());
         Ok(())
     }
@@ -22,6 +206,16 @@ impl LayerBackend for WaylandLayerStubBackend {
             return Err("backend not bootstrapped".to_string());
         }
 
+        if let Some(scenario) = &mut self.scenario {
+            if scenario.topology.is_empty() {
+                for monitor in scenario.initial.clone() {
+                    scenario.topology.push(monitor.clone());
+                    scenario.record_added(&monitor);
+                }
+            }
+            return Ok(scenario.topology.clone());
+        }
+
         // Stub topology used until smithay-client-toolkit integration.
         Ok(vec![
             MonitorInfo {
@@ -29,12 +223,14 @@ impl LayerBackend for WaylandLayerStubBackend {
                 width: 1920,
                 height: 1080,
                 refresh_hz: 60,
+                transform: OutputTransform::Normal,
             },
             MonitorInfo {
                 name: "HDMI-A-1".to_string(),
                 width: 1920,
                 height: 1080,
                 refresh_hz: 60,
+                transform: OutputTransform::Normal,
             },
         ])
     }
@@ -49,6 +245,21 @@ impl LayerBackend for WaylandLayerStubBackend {
 
         let surfaces = monitors
             .iter()
+            .filter(|m| {
+                let aliases = self.aliases.matching(Some(&m.name), None);
+                if self.allowlist.allows(Some(&m.name), None, &aliases) {
+                    return true;
+                }
+                if !self.excluded.contains(&m.name) {
+                    println!(
+                        "[backend:{}] output {} excluded by output allowlist",
+                        self.name(),
+                        m.name
+                    );
+                    self.excluded.push(m.name.clone());
+                }
+                false
+            })
             .cloned()
             .map(|m| MonitorSurfaceSpec {
                 monitor: m,
@@ -63,11 +274,145 @@ impl LayerBackend for WaylandLayerStubBackend {
             return Err("backend not bootstrapped".to_string());
         }
 
-        println!(
-            "[backend:{}] render frame surfaces={}",
-            self.name(),
-            surfaces.len()
-        );
+        if let Some(mut scenario) = self.scenario.take() {
+            if scenario.advance() || scenario.surfaces.is_empty() {
+                let present = scenario
+                    .topology
+                    .iter()
+                    .map(|m| m.name.as_str())
+                    .collect::<Vec<_>>();
+                self.excluded
+                    .retain(|name| present.contains(&name.as_str()));
+                scenario.surfaces = self.build_surfaces(&scenario.topology)?;
+            }
+            self.scenario = Some(scenario);
+        }
+        let surfaces = match &self.scenario {
+            Some(scenario) => &scenario.surfaces,
+            None => surfaces,
+        };
+        self.monitors = surfaces
+            .iter()
+            .map(|s| (s.monitor.name.clone(), (s.monitor.width, s.monitor.height)))
+            .collect();
+        let base_fps = self.sim.base_fps;
+        let simulate = self.quality.is_enabled() || !self.sim.delay.is_zero();
+        for (name, base_size) in self.monitors.iter().filter(|_| simulate) {
+            let level = self.quality.level(name);
+            let size = quality::degraded_source_size(*base_size, level, base_fps);
+            let now = Instant::now();
+            let work = self.sim.frame_cost(*base_size, size, now);
+            std::thread::sleep(work);
+            let budget =
+                Duration::from_secs_f64(1.0 / quality::degraded_fps(base_fps, level).max(1) as f64);
+            let max_level = quality::max_level(base_fps, *base_size);
+            if let Some(change) = self.quality.record(name, work, budget, max_level, now) {
+                quality::record_change(&change, base_fps, *base_size);
+            }
+        }
+        self.first_frame_at.get_or_insert_with(Instant::now);
+        self.frames += 1;
         Ok(())
     }
+
+    fn set_aliases(&mut self, aliases: &Aliases) {
+        self.aliases = aliases.clone();
+    }
+
+    fn report_summary(&self) -> Option<String> {
+        Some(format!(
+            "surfaces={} backend_frames={}",
+            self.monitors.len(),
+            self.frames
+        ))
+    }
+
+    fn output_status(&self) -> Vec<OutputRuntimeStatus> {
+        self.monitors
+            .iter()
+            .map(|(name, base_size)| {
+                let level = self.quality.level(name);
+                OutputRuntimeStatus {
+                    name: name.clone(),
+                    description: None,
+                    stream_kind: "procedural",
+                    source: None,
+                    first_presented_at: self.first_frame_at,
+                    presented_frames: self.frames,
+                    frame_callback_pending: false,
+                    last_error: None,
+                    last_error_repeats: 0,
+                    degraded: None,
+                    source_size: Some(quality::degraded_source_size(
+                        *base_size,
+                        level,
+                        self.sim.base_fps,
+                    )),
+                    quality_preset: None,
+                    quality_level: level,
+                    quality: quality::describe_level(level, self.sim.base_fps, *base_size),
+                    decode_nice: None,
+                    decoder_pid: None,
+                    hw_decode: None,
+                    effect: None,
+                    overlay: None,
+                    trim: None,
+                    crop: None,
+                    filter: "linear",
+                    fit: "cover",
+                    decoder_shared_with: None,
+                    buffer_scale: 1,
+                    buffer_transform: "normal",
+                    clamped: false,
+                    color: None,
+                    input_region: None,
+                    callback_latency: None,
+                    frame_arrival: None,
+                    damage: None,
+                    flash_guard: None,
+                    suspect_mode: None,
+                    layout: None,
+                    cells: Vec::new(),
+                }
+            })
+            .chain(self.excluded.iter().map(|name| OutputRuntimeStatus {
+                name: name.clone(),
+                description: None,
+                stream_kind: "excluded",
+                source: None,
+                first_presented_at: None,
+                presented_frames: 0,
+                frame_callback_pending: false,
+                last_error: None,
+                last_error_repeats: 0,
+                degraded: None,
+                source_size: None,
+                quality_preset: None,
+                quality_level: 0,
+                quality: "full".to_string(),
+                decode_nice: None,
+                decoder_pid: None,
+                hw_decode: None,
+                effect: None,
+                overlay: None,
+                trim: None,
+                crop: None,
+                filter: "linear",
+                fit: "cover",
+                decoder_shared_with: None,
+                buffer_scale: 1,
+                buffer_transform: "normal",
+                clamped: false,
+                color: None,
+                input_region: None,
+                callback_latency: None,
+                frame_arrival: None,
+                damage: None,
+                flash_guard: None,
+                suspect_mode: None,
+                layout: None,
+                cells: Vec::new(),
+            }))
+            .collect()
+    }
 }
@@ -0,0 +1,514 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::backend::bench_render;
+use crate::headroom::COMFORTABLE_HEADROOM;
+use crate::json::escape_json;
+use crate::user_path::{cli_base_dir, display_path, normalize_video_path};
+use crate::video_map::{
+    default_video_from_env, env_video_map, load_map_document, map_file_path_from_env,
+};
+
+/// What `bench` measured for one hwaccel mode.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct DecodeBench {
+    /// Frames per second all decoders delivered together.
+    pub total_fps: f64,
+    /// The slowest single decoder.
+    pub min_stream_fps: f64,
+    /// Slowest time from spawn to the first frame.
+    pub first_frame: Duration,
+}
+
+/// What `bench` measured on the GPU.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct RenderBench {
+    pub adapter: String,
+    /// Frames per second with every stream uploaded and drawn once per frame.
+    pub fps: f64,
+    /// Texture upload rate those frames reached, in bytes per second.
+    pub upload_bytes_per_sec: f64,
+}
+
+/// Hwaccel modes the decode phase tries; `auto` is one of these in practice.
+const DECODE_MODES: [&str; 3] = ["none", "vaapi", "nvdec"];
+
+/// Frame rates the verdict is given for.
+const VERDICT_FPS: [u32; 2] = [30, 60];
+
+/// The CPU copy phase never needs long to settle.
+const MAX_COPY_DURATION: Duration = Duration::from_secs(2);
+
+struct BenchArgs {
+    video: Option<String>,
+    size: (u32, u32),
+    duration: Duration,
+    streams: u32,
+    json: bool,
+}
+
+/// `bench`: decode, copy and GPU throughput on this machine, without a
+/// compositor, and whether `--streams` outputs of `--size` would keep up.
+pub fn run_bench(args: &[String]) -> Result<(), String> {
+    let Some(args) = parse_args(args)? else {
+        print_bench_help();
+        return Ok(());
+    };
+    let video = match &args.video {
+        Some(raw) => Some(normalize_video_path(raw, &cli_base_dir())?),
+        None => configured_video(),
+    };
+    let (width, height) = args.size;
+    let frame_len = width as usize * height as usize * 4;
+
+    // Decoders and the GPU setup log to stdout; keep that out of the JSON.
+    let redirect = args.json.then(StdoutToStderr::start).flatten();
+    println!(
+        "[info] bench {} x {width}x{height}, {}s per measurement, video={}",
+        args.streams,
+        args.duration.as_secs_f64(),
+        video.as_deref().map_or("<none>".to_string(), display_path)
+    );
+    let decode = DECODE_MODES
+        .iter()
+        .map(|mode| {
+            let result = match &video {
+                Some(video) => {
+                    println!("[info] decoding with hwaccel={mode}...");
+                    decode_throughput(video, args.size, mode, args.streams, args.duration)
+                }
+                None => Err("no video: pass --video or map one".to_string()),
+            };
+            (*mode, result)
+        })
+        .collect::<Vec<_>>();
+    println!("[info] copying frames in memory...");
+    let copy = copy_bandwidth(frame_len, args.duration.min(MAX_COPY_DURATION));
+    println!("[info] uploading and rendering offscreen...");
+    let render = bench_render(args.size, args.streams, args.duration);
+    drop(redirect);
+
+    let verdicts = VERDICT_FPS
+        .iter()
+        .map(|fps| verdict(*fps, &args, frame_len, &decode, copy, &render))
+        .collect::<Vec<_>>();
+    if args.json {
+        print_json(&args, video.as_deref(), &decode, copy, &render, &verdicts);
+    } else {
+        print_table(&args, &decode, copy, &render, &verdicts);
+    }
+    Ok(())
+}
+
+/// `None` when help was asked for.
+fn parse_args(args: &[String]) -> Result<Option<BenchArgs>, String> {
+    let mut parsed = BenchArgs {
+        video: None,
+        size: (3840, 2160),
+        duration: Duration::from_secs(10),
+        streams: 1,
+        json: false,
+    };
+    let mut i = 0usize;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let mut value = || {
+            i += 1;
+            args.get(i)
+                .cloned()
+                .ok_or_else(|| format!("missing value for {flag}"))
+        };
+        match flag {
+            "--video" => parsed.video = Some(value()?),
+            "--size" => parsed.size = parse_size(&value()?)?,
+            "--duration" => parsed.duration = parse_duration("--duration", &value()?)?,
+            "--streams" => {
+                let raw = value()?;
+                parsed.streams = raw
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| (1..=16).contains(n))
+                    .ok_or_else(|| format!("invalid --streams '{raw}' (expected 1-16)"))?;
+            }
+            "--json" => parsed.json = true,
+            "--help" | "-h" => return Ok(None),
+            other => return Err(format!("unknown argument for bench: {other}")),
+        }
+        i += 1;
+    }
+    Ok(Some(parsed))
+}
+
+fn parse_size(raw: &str) -> Result<(u32, u32), String> {
+    raw.trim()
+        .split_once(['x', 'X'])
+        .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)))
+        .filter(|(w, h)| (16..=8192).contains(w) && (16..=8192).contains(h))
+        .ok_or_else(|| format!("invalid --size '{raw}' (expected WIDTHxHEIGHT, e.g. 3840x2160)"))
+}
+
+/// `10s`, `500ms` or plain seconds, for `flag`.
+pub fn parse_duration(flag: &str, raw: &str) -> Result<Duration, String> {
+    let trimmed = raw.trim();
+    let duration = if let Some(ms) = trimmed.strip_suffix("ms") {
+        ms.trim().parse::<u64>().ok().map(Duration::from_millis)
+    } else {
+        trimmed
+            .strip_suffix('s')
+            .unwrap_or(trimmed)
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|secs| secs.is_finite() && *secs > 0.0)
+            .map(Duration::from_secs_f64)
+    };
+    duration
+        .filter(|d| *d >= Duration::from_millis(100) && *d <= Duration::from_secs(600))
+        .ok_or_else(|| format!("invalid {flag} '{raw}' (expected e.g. 10s or 500ms, up to 600s)"))
+}
+
+/// The video the renderer would play when `--video` is not given: the
+/// default entry, else the first mapped one.
+pub fn configured_video() -> Option<String> {
+    let doc = map_file_path_from_env()
+        .ok()
+        .map(|path| load_map_document(&path));
+    doc.as_ref()
+        .and_then(|doc| doc.default.clone())
+        .or_else(default_video_from_env)
+        .or_else(|| doc.and_then(|doc| doc.entries.into_values().next()))
+        .or_else(|| env_video_map().into_values().next())
+        .map(|entry| entry.video)
+}
+
+#[cfg(feature = "wayland-layer")]
+fn decode_throughput(
+    video: &str,
+    size: (u32, u32),
+    mode: &str,
+    streams: u32,
+    duration: Duration,
+) -> Result<DecodeBench, String> {
+    use crate::frame_source::{VideoOptions, decode_throughput};
+    use crate::hw_decode::HwAccel;
+
+    let hwaccel = match mode {
+        "vaapi" => HwAccel::Vaapi,
+        "nvdec" => HwAccel::Nvdec,
+        _ => HwAccel::None,
+    };
+    let options = VideoOptions {
+        speed: 1.0,
+        hwaccel,
+        ..VideoOptions::from_env()
+    };
+    decode_throughput(video, size.0, size.1, options, streams, duration)
+}
+
+#[cfg(not(feature = "wayland-layer"))]
+fn decode_throughput(
+    _video: &str,
+    _size: (u32, u32),
+    _mode: &str,
+    _streams: u32,
+    _duration: Duration,
+) -> Result<DecodeBench, String> {
+    Err("built without the wayland-layer feature".to_string())
+}
+
+/// Bytes per second for copying frame-sized buffers, the copy each decoded
+/// frame takes on its way from the ffmpeg pipe to the upload.
+fn copy_bandwidth(frame_len: usize, duration: Duration) -> f64 {
+    let src = vec![0x5au8; frame_len];
+    let mut dst = vec![0u8; frame_len];
+    let started = Instant::now();
+    let mut copies = 0u64;
+    while started.elapsed() < duration {
+        dst.copy_from_slice(std::hint::black_box(&src));
+        std::hint::black_box(&mut dst);
+        copies += 1;
+    }
+    copies as f64 * frame_len as f64 / started.elapsed().as_secs_f64()
+}
+
+struct Verdict {
+    fps: u32,
+    /// Capacity over need of the tightest measured phase, and its name.
+    headroom: Option<(f64, &'static str)>,
+    /// Phases that could not be measured.
+    missing: Vec<&'static str>,
+}
+
+impl Verdict {
+    fn label(&self) -> &'static str {
+        match self.headroom {
+            None => "unknown",
+            Some((headroom, _)) if headroom >= COMFORTABLE_HEADROOM => "ok",
+            Some((headroom, _)) if headroom >= 1.0 => "marginal",
+            Some(_) => "too slow",
+        }
+    }
+}
+
+/// Headroom of each phase at `fps` per stream: decode needs `streams * fps`
+/// frames per second from the best mode, the CPU copies each frame twice
+/// (pipe read, upload staging), and the GPU must render `fps` frames of all
+/// streams.
+fn verdict(
+    fps: u32,
+    args: &BenchArgs,
+    frame_len: usize,
+    decode: &[(&str, Result<DecodeBench, String>)],
+    copy: f64,
+    render: &Result<RenderBench, String>,
+) -> Verdict {
+    let needed_frames = args.streams as f64 * fps as f64;
+    let mut phases = Vec::new();
+    let mut missing = Vec::new();
+    match decode
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok())
+        .map(|bench| bench.total_fps)
+        .reduce(f64::max)
+    {
+        Some(best) => phases.push((best / needed_frames, "decode")),
+        None => missing.push("decode"),
+    }
+    phases.push((copy / (needed_frames * frame_len as f64 * 2.0), "copy"));
+    match render {
+        Ok(bench) => phases.push((bench.fps / fps as f64, "gpu")),
+        Err(_) => missing.push("gpu"),
+    }
+    Verdict {
+        fps,
+        headroom: phases
+            .into_iter()
+            .reduce(|a, b| if b.0 < a.0 { b } else { a }),
+        missing,
+    }
+}
+
+fn size_label((width, height): (u32, u32), fps: u32) -> String {
+    match (width, height) {
+        (3840, 2160) => format!("4K{fps}"),
+        (2560, 1440) => format!("1440p{fps}"),
+        (1920, 1080) => format!("1080p{fps}"),
+        _ => format!("{width}x{height}@{fps}"),
+    }
+}
+
+fn gb_per_sec(bytes_per_sec: f64) -> String {
+    format!("{:.1} GB/s", bytes_per_sec / 1e9)
+}
+
+fn print_table(
+    args: &BenchArgs,
+    decode: &[(&str, Result<DecodeBench, String>)],
+    copy: f64,
+    render: &Result<RenderBench, String>,
+    verdicts: &[Verdict],
+) {
+    println!("{:<22} result", "phase");
+    for (mode, result) in decode {
+        let phase = format!("decode ({mode})");
+        match result {
+            Ok(bench) => println!(
+                "{phase:<22} {:.1} fps total, {:.1} fps slowest stream, first frame {} ms",
+                bench.total_fps,
+                bench.min_stream_fps,
+                bench.first_frame.as_millis()
+            ),
+            Err(err) => println!("{phase:<22} unavailable: {err}"),
+        }
+    }
+    println!("{:<22} {}", "cpu copy", gb_per_sec(copy));
+    match render {
+        Ok(bench) => println!(
+            "{:<22} {:.1} fps, upload {} ({})",
+            "gpu upload+render",
+            bench.fps,
+            gb_per_sec(bench.upload_bytes_per_sec),
+            bench.adapter
+        ),
+        Err(err) => println!("{:<22} unavailable: {err}", "gpu upload+render"),
+    }
+    let parts = verdicts
+        .iter()
+        .map(|v| {
+            let mut part = format!("{}: {}", size_label(args.size, v.fps), v.label());
+            if let Some((headroom, phase)) = v.headroom
+                && headroom < COMFORTABLE_HEADROOM
+            {
+                part.push_str(&format!(" ({phase} {headroom:.2}x)"));
+            }
+            part
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let streams = match args.streams {
+        1 => "1 stream".to_string(),
+        n => format!("{n} streams"),
+    };
+    let missing = verdicts
+        .first()
+        .filter(|v| !v.missing.is_empty())
+        .map(|v| format!(" [not measured: {}]", v.missing.join(", ")))
+        .unwrap_or_default();
+    println!("verdict: {streams} at {parts}{missing}");
+}
+
+fn print_json(
+    args: &BenchArgs,
+    video: Option<&str>,
+    decode: &[(&str, Result<DecodeBench, String>)],
+    copy: f64,
+    render: &Result<RenderBench, String>,
+    verdicts: &[Verdict],
+) {
+    let string_or_null = |value: Option<&str>| {
+        value.map_or("null".to_string(), |v| format!("\"{}\"", escape_json(v)))
+    };
+    let decode = decode
+        .iter()
+        .map(|(mode, result)| match result {
+            Ok(bench) => format!(
+                "{{\"hwaccel\":\"{mode}\",\"total_fps\":{:.2},\"min_stream_fps\":{:.2},\"first_frame_ms\":{},\"error\":null}}",
+                bench.total_fps,
+                bench.min_stream_fps,
+                bench.first_frame.as_millis()
+            ),
+            Err(err) => format!(
+                "{{\"hwaccel\":\"{mode}\",\"total_fps\":null,\"min_stream_fps\":null,\"first_frame_ms\":null,\"error\":\"{}\"}}",
+                escape_json(err)
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let render = match render {
+        Ok(bench) => format!(
+            "{{\"adapter\":\"{}\",\"fps\":{:.2},\"upload_bytes_per_sec\":{:.0},\"error\":null}}",
+            escape_json(&bench.adapter),
+            bench.fps,
+            bench.upload_bytes_per_sec
+        ),
+        Err(err) => format!(
+            "{{\"adapter\":null,\"fps\":null,\"upload_bytes_per_sec\":null,\"error\":\"{}\"}}",
+            escape_json(err)
+        ),
+    };
+    let verdicts = verdicts
+        .iter()
+        .map(|v| {
+            format!(
+                "{{\"fps\":{},\"result\":\"{}\",\"headroom\":{},\"limited_by\":{},\"not_measured\":[{}]}}",
+                v.fps,
+                v.label(),
+                v.headroom
+                    .map_or("null".to_string(), |(headroom, _)| format!("{headroom:.2}")),
+                string_or_null(v.headroom.map(|(_, phase)| phase)),
+                v.missing
+                    .iter()
+                    .map(|phase| format!("\"{phase}\""))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    println!(
+        "{{\"video\":{},\"size\":\"{}x{}\",\"streams\":{},\"duration_ms\":{},\"decode\":[{decode}],\"copy_bytes_per_sec\":{copy:.0},\"render\":{render},\"verdict\":[{verdicts}]}}",
+        string_or_null(video),
+        args.size.0,
+        args.size.1,
+        args.streams,
+        args.duration.as_millis()
+    );
+}
+
+/// Points stdout at stderr until dropped.
+struct StdoutToStderr {
+    saved: libc::c_int,
+}
+
+impl StdoutToStderr {
+    fn start() -> Option<Self> {
+        let _ = std::io::stdout().flush();
+        // SAFETY: plain descriptor calls on the standard descriptors and the
+        // copy made here, which nothing else owns; stdout was flushed so
+        // nothing buffered lands on the wrong side.
+        unsafe {
+            let saved = libc::dup(libc::STDOUT_FILENO);
+            if saved < 0 {
+                return None;
+            }
+            if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+                libc::close(saved);
+                return None;
+            }
+            Some(Self { saved })
+        }
+    }
+}
+
+impl Drop for StdoutToStderr {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+        // SAFETY: `saved` is the descriptor dup'ed in `start`, owned by this
+        // value and closed only here.
+        unsafe {
+            libc::dup2(self.saved, libc::STDOUT_FILENO);
+            libc::close(self.saved);
+        }
+    }
+}
+
+fn print_bench_help() {
+    println!("kitsune-rendercore bench");
+    println!("Usage:");
+    println!(
+        "  kitsune-rendercore bench [--video <PATH>] [--size <WxH>] [--duration <10s>] [--streams <N>] [--json]"
+    );
+    println!();
+    println!("Description:");
+    println!("  Measures, without a compositor, how fast this machine decodes (per hwaccel");
+    println!("  mode), copies frames in memory, and uploads and renders them offscreen with");
+    println!("  the renderer's own pipeline, then says whether N outputs at that size keep");
+    println!("  up at 30 and 60 fps.");
+    println!();
+    println!("Options:");
+    println!("  --video <PATH>     Video to decode (default: the mapped default or first entry).");
+    println!("  --size <WxH>       Decode and render size (default 3840x2160).");
+    println!("  --duration <TIME>  Length of each measurement, e.g. 10s or 500ms (default 10s).");
+    println!("  --streams <N>      Outputs to simulate, decoded and drawn together (default 1).");
+    println!("  --json             One JSON object on stdout; progress goes to stderr.");
+    println!();
+    println!("Example:");
+    println!("  kitsune-rendercore bench --video ~/Videos/live/a.mp4 --streams 3");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Device and inode behind a descriptor.
+    fn file_id(fd: libc::c_int) -> (u64, u64) {
+        // SAFETY: `stat` is written by fstat(2) before it is read, and only
+        // on success.
+        unsafe {
+            let mut stat: libc::stat = std::mem::zeroed();
+            assert_eq!(libc::fstat(fd, &mut stat), 0);
+            (stat.st_dev as u64, stat.st_ino as u64)
+        }
+    }
+
+    #[test]
+    fn stdout_points_at_stderr_until_dropped() {
+        let before = file_id(libc::STDOUT_FILENO);
+        let redirect = StdoutToStderr::start().unwrap();
+        assert_eq!(file_id(libc::STDOUT_FILENO), file_id(libc::STDERR_FILENO));
+        assert_eq!(file_id(redirect.saved), before);
+        drop(redirect);
+        assert_eq!(file_id(libc::STDOUT_FILENO), before);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::monitor::CallbackStats;
+
+/// Histogram bucket width; percentiles are reported at this resolution.
+const BUCKET: Duration = Duration::from_micros(250);
+
+/// Buckets up to 250ms; slower callbacks land in the last one.
+const BUCKETS: usize = 1000;
+
+/// Callbacks the rolling window holds, about 10s at 60Hz.
+const WINDOW: usize = 600;
+
+/// A callback later than this many refresh intervals is late.
+const LATE_INTERVALS: u32 = 2;
+
+/// The output counts as slow once this share of the window is late, and
+/// recovers below [`CALM_SHARE`], so a single window animation does not flag
+/// it but a compositor that keeps lagging does.
+const SLOW_SHARE: f64 = 0.25;
+const CALM_SHARE: f64 = 0.05;
+
+/// Fewer samples than this never flag an output as slow.
+const MIN_SAMPLES: usize = 60;
+
+/// A callback pending this long (or this many refresh intervals, whichever is
+/// longer) means the compositor stopped sending them: the output is hidden,
+/// off, or the compositor is wedged.
+const STALL_AFTER: Duration = Duration::from_secs(1);
+const STALL_INTERVALS: u32 = 10;
+
+/// A change worth a log line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyChange {
+    Slow,
+    Recovered,
+    Stalled,
+    /// Callbacks are back after a stall that lasted this long.
+    Resumed(Duration),
+}
+
+struct Sample {
+    bucket: u16,
+    late: bool,
+}
+
+/// Time from requesting a `wl_surface.frame` callback to its `done`, per
+/// output. Long latencies with a renderer that is within its own budget
+/// (see `KRC_PROFILE`) point at the compositor, e.g. during window animations.
+pub struct CallbackLatency {
+    requested_at: Option<Instant>,
+    window: VecDeque<Sample>,
+    counts: Vec<u32>,
+    late: usize,
+    slow: bool,
+    stalled: bool,
+}
+
+impl Default for CallbackLatency {
+    fn default() -> Self {
+        Self {
+            requested_at: None,
+            window: VecDeque::with_capacity(WINDOW),
+            counts: vec![0; BUCKETS],
+            late: 0,
+            slow: false,
+            stalled: false,
+        }
+    }
+}
+
+impl CallbackLatency {
+    /// A frame callback was just requested.
+    pub fn requested(&mut self, now: Instant) {
+        self.requested_at = Some(now);
+    }
+
+    /// The callback arrived; `refresh` is the output's refresh interval.
+    pub fn done(&mut self, now: Instant, refresh: Duration) -> Option<LatencyChange> {
+        let requested_at = self.requested_at.take()?;
+        let latency = now.saturating_duration_since(requested_at);
+        let resumed = std::mem::take(&mut self.stalled).then_some(LatencyChange::Resumed(latency));
+        let late = latency > refresh * LATE_INTERVALS;
+        let bucket = (latency.as_micros() / BUCKET.as_micros()).min(BUCKETS as u128 - 1) as u16;
+        if self.window.len() == WINDOW
+            && let Some(oldest) = self.window.pop_front()
+        {
+            self.counts[oldest.bucket as usize] -= 1;
+            self.late -= oldest.late as usize;
+        }
+        self.window.push_back(Sample { bucket, late });
+        self.counts[bucket as usize] += 1;
+        self.late += late as usize;
+        if resumed.is_some() {
+            return resumed;
+        }
+        let share = self.late as f64 / self.window.len() as f64;
+        if !self.slow && self.window.len() >= MIN_SAMPLES && share >= SLOW_SHARE {
+            self.slow = true;
+            return Some(LatencyChange::Slow);
+        }
+        if self.slow && share < CALM_SHARE {
+            self.slow = false;
+            return Some(LatencyChange::Recovered);
+        }
+        None
+    }
+
+    /// Flags the output once a pending callback is overdue; returns true on
+    /// the check that flags it.
+    pub fn check_stall(&mut self, now: Instant, refresh: Duration) -> bool {
+        let Some(requested_at) = self.requested_at else {
+            return false;
+        };
+        let limit = STALL_AFTER.max(refresh * STALL_INTERVALS);
+        if self.stalled || now.saturating_duration_since(requested_at) < limit {
+            return false;
+        }
+        self.stalled = true;
+        true
+    }
+
+    /// The surface was closed: its pending callback will never come.
+    pub fn forget_pending(&mut self) {
+        self.requested_at = None;
+        self.stalled = false;
+    }
+
+    pub fn stats(&self) -> CallbackStats {
+        CallbackStats {
+            samples: self.window.len(),
+            p50: self.percentile(0.50),
+            p99: self.percentile(0.99),
+            slow: self.slow,
+            stalled: self.stalled,
+        }
+    }
+
+    /// Upper edge of the bucket holding the `q` quantile.
+    fn percentile(&self, q: f64) -> Option<Duration> {
+        if self.window.is_empty() {
+            return None;
+        }
+        let rank = ((self.window.len() as f64 * q).ceil() as u32).max(1);
+        let mut seen = 0u32;
+        let bucket = self.counts.iter().position(|count| {
+            seen += count;
+            seen >= rank
+        })?;
+        Some(BUCKET * (bucket as u32 + 1))
+    }
+}
@@ -0,0 +1,365 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::paths::{APP_DIR, cache_dir};
+use crate::user_path::display_path;
+
+/// Something on disk that `clean` may remove. Every category lives in one of
+/// the renderer's own directories, resolved by the same helper the feature
+/// writing it uses. The config dir (map, env file, migration backups) and
+/// `KRC_RECORD` journals are the user's and never listed here.
+struct Category {
+    name: &'static str,
+    flag: &'static str,
+    what: &'static str,
+    root: fn() -> Result<PathBuf, String>,
+}
+
+const CATEGORIES: [Category; 1] = [Category {
+    name: "cache",
+    flag: "--cache",
+    what: "GPU pipeline caches, rebuilt on the next start",
+    root: cache_dir,
+}];
+
+struct CleanArgs {
+    selected: Vec<&'static str>,
+    dry_run: bool,
+    older_than: Option<Duration>,
+}
+
+#[derive(Default)]
+struct Usage {
+    items: u64,
+    bytes: u64,
+}
+
+/// `clean`: per category, the directory, how many files it holds and their
+/// size; removes the selected categories unless `--dry-run`. With no
+/// category selected it only reports.
+pub fn run_clean(args: &[String]) -> Result<(), String> {
+    let Some(args) = parse_args(args)? else {
+        print_clean_help();
+        return Ok(());
+    };
+    let report_only = args.selected.is_empty();
+    let mut freed = 0u64;
+    for category in &CATEGORIES {
+        let root = match (category.root)() {
+            Ok(root) => root,
+            Err(err) => {
+                println!("[warn] {}: {err}", category.name);
+                continue;
+            }
+        };
+        check_own_dir(&root)?;
+        let cutoff = args
+            .older_than
+            .and_then(|age| SystemTime::now().checked_sub(age));
+        let shown = display_path(&root.to_string_lossy());
+        let usage = if root.is_dir() {
+            let mut usage = Usage::default();
+            walk(&root, &root, cutoff, false, &mut usage)?;
+            usage
+        } else {
+            Usage::default()
+        };
+        println!(
+            "[info] {}: {shown} {} item(s), {} ({})",
+            category.name,
+            usage.items,
+            format_bytes(usage.bytes),
+            category.what
+        );
+        let selected = args.selected.contains(&category.name);
+        if report_only || !selected || usage.items == 0 {
+            continue;
+        }
+        if args.dry_run {
+            println!(
+                "[info] {}: would free {}",
+                category.name,
+                format_bytes(usage.bytes)
+            );
+            continue;
+        }
+        let mut removed = Usage::default();
+        walk(&root, &root, cutoff, true, &mut removed)?;
+        freed += removed.bytes;
+        println!(
+            "[ok] {}: removed {} item(s), freed {}",
+            category.name,
+            removed.items,
+            format_bytes(removed.bytes)
+        );
+    }
+    if !report_only && !args.dry_run {
+        println!("[ok] freed {} in total", format_bytes(freed));
+    }
+    Ok(())
+}
+
+/// `None` when help was asked for.
+fn parse_args(args: &[String]) -> Result<Option<CleanArgs>, String> {
+    let mut parsed = CleanArgs {
+        selected: Vec::new(),
+        dry_run: false,
+        older_than: None,
+    };
+    let mut i = 0usize;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--dry-run" => parsed.dry_run = true,
+            "--all" => parsed.selected = CATEGORIES.iter().map(|c| c.name).collect(),
+            "--older-than" => {
+                i += 1;
+                let raw = args
+                    .get(i)
+                    .ok_or_else(|| "missing value for --older-than".to_string())?;
+                parsed.older_than = Some(parse_age(raw)?);
+            }
+            "--help" | "-h" => return Ok(None),
+            flag => match CATEGORIES.iter().find(|c| c.flag == flag) {
+                Some(category) if !parsed.selected.contains(&category.name) => {
+                    parsed.selected.push(category.name)
+                }
+                Some(_) => {}
+                None => return Err(format!("unknown argument for clean: {flag}")),
+            },
+        }
+        i += 1;
+    }
+    Ok(Some(parsed))
+}
+
+/// `30d`, `12h`, `45m`, `90s`, or plain days.
+fn parse_age(raw: &str) -> Result<Duration, String> {
+    let trimmed = raw.trim();
+    let (number, unit_secs) = match trimmed.char_indices().last() {
+        Some((at, 'd')) => (&trimmed[..at], 86_400),
+        Some((at, 'h')) => (&trimmed[..at], 3_600),
+        Some((at, 'm')) => (&trimmed[..at], 60),
+        Some((at, 's')) => (&trimmed[..at], 1),
+        _ => (trimmed, 86_400),
+    };
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit_secs))
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid --older-than '{raw}' (expected e.g. 30d, 12h or 45m)"))
+}
+
+/// Refuses a category root that is not one of the renderer's own
+/// directories, so a broken XDG variable cannot point `clean` at `$HOME`.
+fn check_own_dir(root: &Path) -> Result<(), String> {
+    if root.is_absolute() && root.file_name().is_some_and(|name| name == APP_DIR) {
+        return Ok(());
+    }
+    Err(format!(
+        "refusing to clean {}: not a {APP_DIR} directory",
+        display_path(&root.to_string_lossy())
+    ))
+}
+
+/// Counts, and with `remove` deletes, the files under `dir` last modified
+/// before `cutoff` (all of them without one). Symlinks are never followed:
+/// a link is counted and removed as itself. Directories emptied on the way
+/// are removed too, except `root`.
+fn walk(
+    root: &Path,
+    dir: &Path,
+    cutoff: Option<SystemTime>,
+    remove: bool,
+    usage: &mut Usage,
+) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|err| {
+        format!(
+            "failed to read {}: {err}",
+            display_path(&dir.to_string_lossy())
+        )
+    })?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.starts_with(root) || path == root {
+            return Err(format!(
+                "refusing to touch {}: outside {}",
+                display_path(&path.to_string_lossy()),
+                display_path(&root.to_string_lossy())
+            ));
+        }
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            walk(root, &path, cutoff, remove, usage)?;
+            if remove {
+                // Only succeeds once empty; what was kept keeps its directory.
+                let _ = std::fs::remove_dir(&path);
+            }
+            continue;
+        }
+        let old_enough = match (cutoff, meta.modified()) {
+            (None, _) => true,
+            (Some(cutoff), Ok(modified)) => modified < cutoff,
+            (Some(_), Err(_)) => false,
+        };
+        if !old_enough {
+            continue;
+        }
+        if remove && let Err(err) = std::fs::remove_file(&path) {
+            println!(
+                "[warn] failed to remove {}: {err}",
+                display_path(&path.to_string_lossy())
+            );
+            continue;
+        }
+        usage.items += 1;
+        usage.bytes += meta.len();
+    }
+    Ok(())
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn print_clean_help() {
+    println!("kitsune-rendercore clean");
+    println!("Usage:");
+    println!("  kitsune-rendercore clean [--dry-run] [--cache] [--all] [--older-than <30d>]");
+    println!();
+    println!("Without a category flag, only lists each category with its directory, item count");
+    println!("and size. With one, removes those files and prints the space freed.");
+    println!();
+    println!("Categories:");
+    for category in &CATEGORIES {
+        println!("  {:<10} {}", category.flag, category.what);
+    }
+    println!();
+    println!("Options:");
+    println!("  --all                 Every category.");
+    println!("  --dry-run             Report what would be removed, remove nothing.");
+    println!("  --older-than <AGE>    Only files last modified before AGE ago (d, h, m, s).");
+    println!();
+    println!("The config directory (map, env file, backups) and KRC_RECORD journals are never");
+    println!("touched.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A scratch tree shaped like the cache directory: `<tmp>/<APP_DIR>`.
+    fn scratch(name: &str) -> (PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(format!("krc-clean-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let root = base.join(APP_DIR);
+        fs::create_dir_all(root.join("wgpu/nested")).unwrap();
+        (base, root)
+    }
+
+    fn write(path: &Path, bytes: usize, age: Duration) {
+        fs::write(path, vec![b'x'; bytes]).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn only_the_renderers_own_directories_are_cleaned() {
+        assert!(check_own_dir(&Path::new("/home/u/.cache").join(APP_DIR)).is_ok());
+        for root in ["/home/u", "/", "/home/u/.cache", "relative"] {
+            assert!(check_own_dir(Path::new(root)).is_err(), "{root}");
+        }
+        assert!(check_own_dir(Path::new(APP_DIR)).is_err(), "relative");
+        for (category, root) in CATEGORIES
+            .iter()
+            .filter_map(|c| Some((c.name, (c.root)().ok()?)))
+        {
+            assert!(
+                check_own_dir(&root).is_ok(),
+                "{category}: {}",
+                root.display()
+            );
+        }
+    }
+
+    #[test]
+    fn removal_honours_the_age_and_never_follows_links() {
+        let (base, root) = scratch("walk");
+        let outside = base.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        write(&outside.join("keep.bin"), 10, Duration::ZERO);
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        write(
+            &root.join("wgpu/old.bin"),
+            100,
+            Duration::from_secs(40 * 86_400),
+        );
+        write(
+            &root.join("wgpu/nested/old.bin"),
+            50,
+            Duration::from_secs(40 * 86_400),
+        );
+        write(&root.join("wgpu/new.bin"), 7, Duration::ZERO);
+
+        let cutoff = SystemTime::now().checked_sub(parse_age("30d").unwrap());
+        let mut seen = Usage::default();
+        walk(&root, &root, cutoff, false, &mut seen).unwrap();
+        assert_eq!((seen.items, seen.bytes), (2, 150));
+        assert!(
+            root.join("wgpu/old.bin").exists(),
+            "counting removes nothing"
+        );
+
+        let mut removed = Usage::default();
+        walk(&root, &root, cutoff, true, &mut removed).unwrap();
+        assert_eq!((removed.items, removed.bytes), (2, 150));
+        assert!(!root.join("wgpu/nested").exists(), "emptied directory");
+        assert!(root.join("wgpu/new.bin").exists());
+
+        let mut all = Usage::default();
+        walk(&root, &root, None, true, &mut all).unwrap();
+        assert_eq!(all.items, 2, "new.bin and the link itself");
+        assert!(root.is_dir(), "the root stays");
+        assert!(outside.join("keep.bin").exists(), "link target untouched");
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn arguments() {
+        let args =
+            |raw: &[&str]| parse_args(&raw.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+        let report = args(&[]).unwrap().unwrap();
+        assert!(report.selected.is_empty() && !report.dry_run);
+        let all = args(&["--all", "--cache", "--dry-run"]).unwrap().unwrap();
+        assert_eq!(all.selected, vec!["cache"]);
+        assert!(all.dry_run);
+        assert!(args(&["--help"]).unwrap().is_none());
+        assert!(args(&["--thumbnails"]).is_err());
+        assert!(args(&["--older-than"]).is_err());
+        assert_eq!(parse_age("12h").unwrap(), Duration::from_secs(43_200));
+        assert_eq!(parse_age("2").unwrap(), Duration::from_secs(172_800));
+        assert!(parse_age("soon").is_err());
+        assert!(parse_age("99999999999999999d").is_err());
+    }
+
+    #[test]
+    fn sizes_read_as_binary_units() {
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 << 30), "5.0 GiB");
+    }
+}
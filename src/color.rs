@@ -0,0 +1,811 @@
+//! Output colorimetry: how the video's sRGB pixels are mapped onto a panel
+//! that is not sRGB. The compositor's `wp_color_management_v1` description of
+//! the output is used when it has one; `gamut=` in the map covers the rest.
+//!
+//! The mapping is a 3x3 matrix from linear sRGB to linear output RGB
+//! (through CIE XYZ, with a Bradford white point adaptation) plus the
+//! output's transfer curve, both applied in the fragment shader.
+
+use std::path::Path;
+
+/// Row-major 3x3 matrix.
+pub type Matrix = [[f64; 3]; 3];
+
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub const IDENTITY: Matrix = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Largest coefficient `gamut=matrix:` accepts; real gamut maps stay well
+/// within it.
+const MATRIX_LIMIT: f64 = 4.0;
+
+const D65: (f64, f64) = (0.3127, 0.3290);
+const ILLUMINANT_C: (f64, f64) = (0.310, 0.316);
+const DCI_WHITE: (f64, f64) = (0.314, 0.351);
+
+/// CIE 1931 xy chromaticities of a color space's primaries and white point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct Primaries {
+    pub red: (f64, f64),
+    pub green: (f64, f64),
+    pub blue: (f64, f64),
+    pub white: (f64, f64),
+}
+
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+impl Primaries {
+    /// BT.709 primaries with a D65 white, what the video is assumed to be.
+    pub const SRGB: Self = Self {
+        red: (0.640, 0.330),
+        green: (0.300, 0.600),
+        blue: (0.150, 0.060),
+        white: D65,
+    };
+
+    /// `wp_color_manager_v1.primaries` by wire value, with the name status
+    /// shows for it.
+    pub fn named(code: u32) -> Option<(&'static str, Self)> {
+        let (name, red, green, blue, white) = match code {
+            1 => ("srgb", (0.640, 0.330), (0.300, 0.600), (0.150, 0.060), D65),
+            2 => (
+                "pal_m",
+                (0.670, 0.330),
+                (0.210, 0.710),
+                (0.140, 0.080),
+                ILLUMINANT_C,
+            ),
+            3 => ("pal", (0.640, 0.330), (0.290, 0.600), (0.150, 0.060), D65),
+            4 => ("ntsc", (0.630, 0.340), (0.310, 0.595), (0.155, 0.070), D65),
+            5 => (
+                "generic_film",
+                (0.681, 0.319),
+                (0.243, 0.692),
+                (0.145, 0.049),
+                ILLUMINANT_C,
+            ),
+            6 => (
+                "bt2020",
+                (0.708, 0.292),
+                (0.170, 0.797),
+                (0.131, 0.046),
+                D65,
+            ),
+            7 => (
+                "cie1931_xyz",
+                (1.0, 0.0),
+                (0.0, 1.0),
+                (0.0, 0.0),
+                (1.0 / 3.0, 1.0 / 3.0),
+            ),
+            8 => (
+                "dci_p3",
+                (0.680, 0.320),
+                (0.265, 0.690),
+                (0.150, 0.060),
+                DCI_WHITE,
+            ),
+            9 => (
+                "display_p3",
+                (0.680, 0.320),
+                (0.265, 0.690),
+                (0.150, 0.060),
+                D65,
+            ),
+            10 => (
+                "adobe_rgb",
+                (0.640, 0.330),
+                (0.210, 0.710),
+                (0.150, 0.060),
+                D65,
+            ),
+            _ => return None,
+        };
+        Some((
+            name,
+            Self {
+                red,
+                green,
+                blue,
+                white,
+            },
+        ))
+    }
+
+    /// The protocol's `primaries` event: x and y times 1 000 000, red,
+    /// green, blue then white.
+    pub fn from_millionths(values: [i32; 8]) -> Self {
+        let xy = |i: usize| (values[i] as f64 / 1e6, values[i + 1] as f64 / 1e6);
+        Self {
+            red: xy(0),
+            green: xy(2),
+            blue: xy(4),
+            white: xy(6),
+        }
+    }
+
+    /// The chromaticities of an EDID base block: bytes 25-26 hold the two
+    /// low bits of each 10-bit value, bytes 27-34 the high eight.
+    pub fn from_edid(edid: &[u8]) -> Option<Self> {
+        const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+        if edid.len() < 128 || edid[..8] != HEADER {
+            return None;
+        }
+        let low = [edid[25], edid[26]];
+        let value = |i: usize| {
+            let bits = (low[i / 4] >> (6 - 2 * (i % 4))) & 0b11;
+            ((edid[27 + i] as u32) << 2 | bits as u32) as f64 / 1024.0
+        };
+        let primaries = Self {
+            red: (value(0), value(1)),
+            green: (value(2), value(3)),
+            blue: (value(4), value(5)),
+            white: (value(6), value(7)),
+        };
+        // Panels that leave the block zeroed report nothing usable.
+        primaries.to_xyz().map(|_| primaries)
+    }
+
+    /// Within the EDID's 1/1024 steps of sRGB.
+    pub fn is_srgb(&self) -> bool {
+        let close =
+            |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).abs() < 0.002 && (a.1 - b.1).abs() < 0.002;
+        close(self.red, Self::SRGB.red)
+            && close(self.green, Self::SRGB.green)
+            && close(self.blue, Self::SRGB.blue)
+            && close(self.white, Self::SRGB.white)
+    }
+
+    /// Linear RGB to CIE XYZ: each primary's XYZ as a column, scaled so that
+    /// RGB (1, 1, 1) lands on the white point at Y = 1. `None` when the
+    /// primaries do not span a color space.
+    pub fn to_xyz(self) -> Option<Matrix> {
+        let points = [self.red, self.green, self.blue, self.white];
+        if points
+            .iter()
+            .any(|&(x, y)| !x.is_finite() || !y.is_finite() || y <= 0.0)
+        {
+            return None;
+        }
+        let [r, g, b] = [self.red, self.green, self.blue].map(xy_to_xyz);
+        let columns = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+        let scale = mul_vec(&invert(&columns)?, xy_to_xyz(self.white));
+        Some(columns.map(|row| [row[0] * scale[0], row[1] * scale[1], row[2] * scale[2]]))
+    }
+}
+
+impl std::fmt::Display for Primaries {
+    /// `r=0.680,0.320 g=0.265,0.690 b=0.150,0.060 w=0.313,0.329`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "r={:.3},{:.3} g={:.3},{:.3} b={:.3},{:.3} w={:.3},{:.3}",
+            self.red.0,
+            self.red.1,
+            self.green.0,
+            self.green.1,
+            self.blue.0,
+            self.blue.1,
+            self.white.0,
+            self.white.1
+        )
+    }
+}
+
+/// XYZ of a chromaticity at Y = 1.
+fn xy_to_xyz((x, y): (f64, f64)) -> [f64; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+fn mul_vec(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+/// `None` for a singular matrix.
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adjugate = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    let det = m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+    if !det.is_finite() || det.abs() < 1e-12 {
+        return None;
+    }
+    Some(adjugate.map(|row| row.map(|v| v / det)))
+}
+
+/// Chromatic adaptation between two white points in XYZ (Bradford).
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+fn adaptation(from: (f64, f64), to: (f64, f64)) -> Option<Matrix> {
+    const BRADFORD: Matrix = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    let source = mul_vec(&BRADFORD, xy_to_xyz(from));
+    let target = mul_vec(&BRADFORD, xy_to_xyz(to));
+    let mut scale = [[0.0; 3]; 3];
+    for i in 0..3 {
+        scale[i][i] = target[i] / source[i];
+    }
+    Some(mul(&invert(&BRADFORD)?, &mul(&scale, &BRADFORD)))
+}
+
+/// Linear `from` RGB to linear `to` RGB. `None` when either set of
+/// primaries is degenerate.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub fn gamut_matrix(from: &Primaries, to: &Primaries) -> Option<Matrix> {
+    let adapt = adaptation(from.white, to.white)?;
+    let matrix = mul(&invert(&to.to_xyz()?)?, &mul(&adapt, &from.to_xyz()?));
+    matrix
+        .iter()
+        .flatten()
+        .all(|v| v.is_finite())
+        .then_some(matrix)
+}
+
+/// The chromaticities in the EDID of the monitor on DRM connector
+/// `connector` (the `wl_output` name, e.g. `DP-1`).
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub fn edid_primaries(connector: &str) -> Option<Primaries> {
+    edid_primaries_in(Path::new("/sys/class/drm"), connector)
+}
+
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+fn edid_primaries_in(drm: &Path, connector: &str) -> Option<Primaries> {
+    std::fs::read_dir(drm)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            // `card1-DP-1`; `card1` itself and render nodes have no connector.
+            name.split_once('-')
+                .is_some_and(|(card, rest)| card.starts_with("card") && rest == connector)
+        })
+        .find_map(|entry| Primaries::from_edid(&std::fs::read(entry.path().join("edid")).ok()?))
+}
+
+/// The curve the output's values are encoded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub enum Transfer {
+    /// The piecewise sRGB curve, which the surface format already applies.
+    Srgb,
+    /// A pure power curve; the exponent times 10 000, as the protocol sends it.
+    Power(u32),
+    /// A curve the shader does not produce (PQ, HLG, log...), by protocol name.
+    Unsupported(&'static str),
+}
+
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+impl Transfer {
+    /// `wp_color_manager_v1.transfer_function` by wire value.
+    pub fn named(code: u32) -> Self {
+        match code {
+            1 => Self::Power(24_000),
+            2 => Self::Power(22_000),
+            3 => Self::Power(28_000),
+            5 => Self::Power(10_000),
+            9 | 10 | 14 => Self::Srgb,
+            4 => Self::Unsupported("st240"),
+            6 => Self::Unsupported("log_100"),
+            7 => Self::Unsupported("log_316"),
+            8 => Self::Unsupported("xvycc"),
+            11 => Self::Unsupported("st2084_pq"),
+            12 => Self::Unsupported("st428"),
+            13 => Self::Unsupported("hlg"),
+            _ => Self::Unsupported("unknown"),
+        }
+    }
+
+    /// The power curve's exponent; `None` for the sRGB curve.
+    pub fn exponent(self) -> Option<f32> {
+        match self {
+            Self::Power(eexp) => Some(eexp as f32 / 10_000.0),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Transfer {
+    /// `srgb`, `gamma2.2`, or the unsupported curve's name.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Srgb => write!(f, "srgb"),
+            Self::Power(eexp) => write!(f, "gamma{}", *eexp as f64 / 10_000.0),
+            Self::Unsupported(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// `gamut=` in the map: where an output's colorimetry comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Gamut {
+    /// The compositor's description of the output (`wp_color_management_v1`)
+    /// when it has one, else no conversion.
+    #[default]
+    Auto,
+    /// No conversion: the video's sRGB values go out as they are.
+    Srgb,
+    /// The primaries in the monitor's EDID, with the sRGB curve.
+    Native,
+    /// A linear sRGB -> panel RGB matrix, row-major, in millionths so
+    /// options stay `Eq`.
+    Matrix([i32; 9]),
+}
+
+impl Gamut {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let value = raw.trim().to_ascii_lowercase();
+        if let Some(list) = value.strip_prefix("matrix:") {
+            return Self::parse_matrix(list);
+        }
+        match value.as_str() {
+            "auto" | "" => Ok(Self::Auto),
+            "srgb" => Ok(Self::Srgb),
+            "native" => Ok(Self::Native),
+            other => Err(format!(
+                "invalid gamut value '{other}' (expected auto|srgb|native|matrix:M11,M12,...,M33)"
+            )),
+        }
+    }
+
+    fn parse_matrix(list: &str) -> Result<Self, String> {
+        let err = || {
+            format!(
+                "invalid gamut matrix '{list}' (expected 9 comma-separated numbers, row by row, within ±{MATRIX_LIMIT})"
+            )
+        };
+        let values = list
+            .split(',')
+            .map(|item| item.trim().parse::<f64>().map_err(|_| err()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.len() != 9
+            || values
+                .iter()
+                .any(|v| !v.is_finite() || v.abs() > MATRIX_LIMIT)
+        {
+            return Err(err());
+        }
+        let mut millionths = [0; 9];
+        for (slot, value) in millionths.iter_mut().zip(&values) {
+            *slot = (value * 1e6).round() as i32;
+        }
+        Ok(Self::Matrix(millionths))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Srgb => "srgb",
+            Self::Native => "native",
+            Self::Matrix(_) => "matrix",
+        }
+    }
+
+    /// A `matrix:` value as a row-major matrix.
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn matrix(self) -> Option<Matrix> {
+        let Self::Matrix(values) = self else {
+            return None;
+        };
+        let v = |i: usize| values[i] as f64 / 1e6;
+        Some([[v(0), v(1), v(2)], [v(3), v(4), v(5)], [v(6), v(7), v(8)]])
+    }
+}
+
+impl std::fmt::Display for Gamut {
+    /// The map value: `native`, or `matrix:` and its nine numbers.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self::Matrix(values) = self else {
+            return write!(f, "{}", self.as_str());
+        };
+        let values = values
+            .iter()
+            .map(|v| (*v as f64 / 1e6).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(f, "matrix:{values}")
+    }
+}
+
+/// What the compositor's image description of an output says, as far as
+/// the shader can use it.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct OutputColorimetry {
+    /// The protocol's name for the primaries, when it sent one.
+    pub primaries_name: Option<&'static str>,
+    pub primaries: Option<Primaries>,
+    pub transfer: Option<Transfer>,
+}
+
+/// How an output's pixels are converted after sampling: what the shader
+/// applies and what status reports.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct ColorPipeline {
+    /// The entry's `gamut=`.
+    pub gamut: Gamut,
+    /// Where the target colorimetry came from: `compositor`, `edid`,
+    /// `manual`, or `none` when the sRGB values pass through.
+    pub source: &'static str,
+    /// The target primaries, by name when they have one.
+    pub primaries: String,
+    pub transfer: Transfer,
+    /// Linear sRGB to linear output RGB; `None` for the identity.
+    pub matrix: Option<Matrix>,
+    /// The surface carries the output's own image description, so the
+    /// compositor does not convert the pixels a second time.
+    pub tagged: bool,
+    /// Why `gamut=` fell back to passing sRGB through.
+    pub note: Option<String>,
+}
+
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+impl ColorPipeline {
+    fn passthrough(gamut: Gamut, note: Option<String>) -> Self {
+        Self {
+            gamut,
+            source: "none",
+            primaries: "srgb".to_string(),
+            transfer: Transfer::Srgb,
+            matrix: None,
+            tagged: false,
+            note,
+        }
+    }
+
+    /// The pipeline `gamut` asks for on an output the compositor describes
+    /// as `compositor` (`None` without the protocol or before it answers)
+    /// and whose EDID lists `edid`.
+    pub fn resolve(
+        gamut: Gamut,
+        compositor: Option<&OutputColorimetry>,
+        edid: Option<&Primaries>,
+    ) -> Self {
+        match gamut {
+            Gamut::Srgb => Self::passthrough(gamut, None),
+            Gamut::Matrix(_) => Self {
+                gamut,
+                source: "manual",
+                primaries: "custom".to_string(),
+                transfer: Transfer::Srgb,
+                matrix: gamut.matrix(),
+                tagged: false,
+                note: None,
+            },
+            Gamut::Native => {
+                let Some(edid) = edid else {
+                    return Self::passthrough(
+                        gamut,
+                        Some("no EDID chromaticities for this output".to_string()),
+                    );
+                };
+                Self {
+                    gamut,
+                    source: "edid",
+                    primaries: edid.to_string(),
+                    transfer: Transfer::Srgb,
+                    matrix: Self::mapping(edid),
+                    tagged: false,
+                    note: None,
+                }
+            }
+            Gamut::Auto => {
+                let Some(colorimetry) = compositor else {
+                    return Self::passthrough(gamut, None);
+                };
+                let Some(primaries) = colorimetry.primaries else {
+                    return Self::passthrough(
+                        gamut,
+                        Some("the output's description has no primaries".to_string()),
+                    );
+                };
+                let transfer = colorimetry.transfer.unwrap_or(Transfer::Srgb);
+                if let Transfer::Unsupported(name) = transfer {
+                    return Self::passthrough(
+                        gamut,
+                        Some(format!(
+                            "output transfer {name} is not handled; left to the compositor"
+                        )),
+                    );
+                }
+                if gamut_matrix(&Primaries::SRGB, &primaries).is_none() {
+                    return Self::passthrough(
+                        gamut,
+                        Some("the output's primaries are degenerate".to_string()),
+                    );
+                }
+                Self {
+                    gamut,
+                    source: "compositor",
+                    primaries: colorimetry
+                        .primaries_name
+                        .map_or_else(|| primaries.to_string(), str::to_string),
+                    transfer,
+                    matrix: Self::mapping(&primaries),
+                    tagged: true,
+                    note: None,
+                }
+            }
+        }
+    }
+
+    /// sRGB to `target`; `None` when that is (close to) the identity.
+    fn mapping(target: &Primaries) -> Option<Matrix> {
+        if target.is_srgb() {
+            return None;
+        }
+        gamut_matrix(&Primaries::SRGB, target)
+    }
+
+    /// The matrix as the shader's `mat3x3<f32>`: three columns, each padded
+    /// to four floats.
+    pub fn shader_matrix(&self) -> [[f32; 4]; 3] {
+        let m = self.matrix.unwrap_or(IDENTITY);
+        std::array::from_fn(|col| [m[0][col] as f32, m[1][col] as f32, m[2][col] as f32, 0.0])
+    }
+
+    /// One line for logs and the text status, e.g. `auto: compositor
+    /// display_p3 gamma2.2, mapped, tagged`.
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "{}: {} {} {}",
+            self.gamut.as_str(),
+            self.source,
+            self.primaries,
+            self.transfer
+        );
+        text.push_str(if self.matrix.is_some() {
+            ", mapped"
+        } else {
+            ", unmapped"
+        });
+        if self.tagged {
+            text.push_str(", tagged");
+        }
+        if let Some(note) = &self.note {
+            text.push_str(&format!(" ({note})"));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &Matrix, expected: &Matrix, tolerance: f64) {
+        for (row, (a, e)) in actual.iter().zip(expected).enumerate() {
+            for col in 0..3 {
+                assert!(
+                    (a[col] - e[col]).abs() < tolerance,
+                    "[{row}][{col}]: {} vs {}\n{actual:?}",
+                    a[col],
+                    e[col]
+                );
+            }
+        }
+    }
+
+    fn display_p3() -> Primaries {
+        Primaries::named(9).unwrap().1
+    }
+
+    #[test]
+    fn srgb_to_xyz_matches_the_published_matrix() {
+        let expected = [
+            [0.4124, 0.3576, 0.1805],
+            [0.2126, 0.7152, 0.0722],
+            [0.0193, 0.1192, 0.9505],
+        ];
+        assert_close(&Primaries::SRGB.to_xyz().unwrap(), &expected, 1e-3);
+    }
+
+    #[test]
+    fn srgb_to_display_p3_and_bt2020() {
+        let p3 = [
+            [0.8225, 0.1774, 0.0000],
+            [0.0332, 0.9669, 0.0000],
+            [0.0171, 0.0724, 0.9108],
+        ];
+        assert_close(
+            &gamut_matrix(&Primaries::SRGB, &display_p3()).unwrap(),
+            &p3,
+            1e-3,
+        );
+        let bt2020 = [
+            [0.6274, 0.3293, 0.0433],
+            [0.0691, 0.9195, 0.0114],
+            [0.0164, 0.0880, 0.8956],
+        ];
+        let target = Primaries::named(6).unwrap().1;
+        assert_close(
+            &gamut_matrix(&Primaries::SRGB, &target).unwrap(),
+            &bt2020,
+            1e-3,
+        );
+    }
+
+    #[test]
+    fn same_white_keeps_white_and_round_trips() {
+        let there = gamut_matrix(&Primaries::SRGB, &display_p3()).unwrap();
+        for row in there {
+            assert!((row.iter().sum::<f64>() - 1.0).abs() < 1e-9, "{row:?}");
+        }
+        let back = gamut_matrix(&display_p3(), &Primaries::SRGB).unwrap();
+        assert_close(&mul(&back, &there), &IDENTITY, 1e-9);
+        assert_close(
+            &gamut_matrix(&Primaries::SRGB, &Primaries::SRGB).unwrap(),
+            &IDENTITY,
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn adaptation_is_identity_for_one_white_and_maps_between_two() {
+        assert_close(&adaptation(D65, D65).unwrap(), &IDENTITY, 1e-12);
+        let d50 = (0.3457, 0.3585);
+        let forward = adaptation(D65, d50).unwrap();
+        let adapted = mul_vec(&forward, xy_to_xyz(D65));
+        let expected = xy_to_xyz(d50);
+        for i in 0..3 {
+            assert!((adapted[i] - expected[i]).abs() < 1e-9, "{adapted:?}");
+        }
+        // Bradford D65 -> D50, as tabulated (XYZ rather than xy whites).
+        let published = [
+            [1.0478, 0.0229, -0.0501],
+            [0.0295, 0.9905, -0.0170],
+            [-0.0092, 0.0150, 0.7521],
+        ];
+        assert_close(&forward, &published, 2e-3);
+        assert_close(
+            &mul(&adaptation(d50, D65).unwrap(), &forward),
+            &IDENTITY,
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn dci_white_is_adapted_to_d65() {
+        let dci = Primaries::named(8).unwrap().1;
+        let matrix = gamut_matrix(&Primaries::SRGB, &dci).unwrap();
+        // sRGB white lands on DCI-P3's own white, RGB (1, 1, 1), after
+        // adaptation rather than on the greenish D65 point in DCI RGB.
+        let white = mul_vec(&matrix, [1.0, 1.0, 1.0]);
+        for channel in white {
+            assert!((channel - 1.0).abs() < 1e-9, "{white:?}");
+        }
+    }
+
+    #[test]
+    fn invert_round_trips_and_refuses_singular() {
+        let m = [[2.0, 1.0, 0.5], [0.0, 3.0, -1.0], [1.0, 0.0, 4.0]];
+        let inverse = invert(&m).unwrap();
+        assert_close(&mul(&m, &inverse), &IDENTITY, 1e-12);
+        assert_close(&mul(&inverse, &m), &IDENTITY, 1e-12);
+        assert_eq!(
+            invert(&[[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 1.0, 1.0]]),
+            None
+        );
+        assert_eq!(
+            invert(&[[f64::NAN, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+            None
+        );
+    }
+
+    #[test]
+    fn degenerate_primaries_have_no_matrix() {
+        let zeroed = Primaries::from_millionths([0; 8]);
+        assert_eq!(zeroed.to_xyz(), None);
+        assert_eq!(gamut_matrix(&Primaries::SRGB, &zeroed), None);
+        let collinear = Primaries {
+            red: (0.6, 0.3),
+            green: (0.4, 0.4),
+            blue: (0.2, 0.5),
+            white: D65,
+        };
+        assert_eq!(gamut_matrix(&Primaries::SRGB, &collinear), None);
+    }
+
+    #[test]
+    fn edid_chromaticities_decode_to_ten_bits() {
+        let mut edid = vec![0u8; 128];
+        edid[..8].copy_from_slice(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]);
+        // A typical sRGB panel's chromaticity block.
+        edid[25..35].copy_from_slice(&[0xee, 0x91, 0xa3, 0x54, 0x4c, 0x99, 0x26, 0x0f, 0x50, 0x54]);
+        let primaries = Primaries::from_edid(&edid).unwrap();
+        assert!(primaries.is_srgb(), "{primaries}");
+        assert_eq!(Primaries::from_edid(&edid[..100]), None);
+        edid[0] = 0x01;
+        assert_eq!(Primaries::from_edid(&edid), None);
+    }
+
+    #[test]
+    fn gamut_values_parse() {
+        assert_eq!(Gamut::parse(" Native ").unwrap(), Gamut::Native);
+        assert_eq!(Gamut::parse("").unwrap(), Gamut::Auto);
+        let gamut =
+            Gamut::parse("matrix:0.8225,0.1774,0,0.0332,0.9669,0,0.0171,0.0724,0.9108").unwrap();
+        assert_eq!(gamut.matrix().unwrap()[2], [0.0171, 0.0724, 0.9108]);
+        assert_eq!(Gamut::parse(&gamut.to_string()).unwrap(), gamut);
+        assert!(Gamut::parse("matrix:1,0,0,0,1,0,0,0").is_err());
+        assert!(Gamut::parse("matrix:5,0,0,0,1,0,0,0,1").is_err());
+        assert!(Gamut::parse("wide").is_err());
+    }
+
+    #[test]
+    fn pipelines_resolve_from_the_compositor_edid_or_map() {
+        let p3 = OutputColorimetry {
+            primaries_name: Some("display_p3"),
+            primaries: Some(display_p3()),
+            transfer: Some(Transfer::Power(22_000)),
+        };
+        let auto = ColorPipeline::resolve(Gamut::Auto, Some(&p3), None);
+        assert_eq!(
+            auto.summary(),
+            "auto: compositor display_p3 gamma2.2, mapped, tagged"
+        );
+        let shader = auto.shader_matrix();
+        // Columns: the first column is the red input's contribution.
+        assert!((shader[0][0] - 0.8225).abs() < 1e-3 && (shader[1][0] - 0.1774).abs() < 1e-3);
+
+        assert_eq!(
+            ColorPipeline::resolve(Gamut::Auto, None, None).summary(),
+            "auto: none srgb srgb, unmapped"
+        );
+        let hlg = OutputColorimetry {
+            transfer: Some(Transfer::named(13)),
+            ..p3.clone()
+        };
+        assert!(
+            ColorPipeline::resolve(Gamut::Auto, Some(&hlg), None)
+                .summary()
+                .contains("output transfer hlg is not handled")
+        );
+
+        let native = ColorPipeline::resolve(Gamut::Native, Some(&p3), Some(&Primaries::SRGB));
+        assert_eq!(
+            (native.source, native.matrix, native.tagged),
+            ("edid", None, false)
+        );
+        assert!(
+            ColorPipeline::resolve(Gamut::Native, None, None)
+                .note
+                .is_some()
+        );
+        let manual = ColorPipeline::resolve(
+            Gamut::parse("matrix:1,0,0,0,1,0,0,0,1").unwrap(),
+            None,
+            None,
+        );
+        assert_eq!((manual.source, manual.matrix), ("manual", Some(IDENTITY)));
+    }
+}
@@ -0,0 +1,456 @@
+//! The render loop's single entry point for outside input. The control
+//! socket thread, the D-Bus service and the SIGHUP handler never touch
+//! runtime state themselves: they queue a [`RuntimeCommand`] here and, for a
+//! request, wait for the reply the loop sends back once it has applied it.
+//!
+//! Ordering guarantees:
+//! - Commands are applied in the order they were queued, so one producer's
+//!   commands never overtake each other; between producers, arrival decides.
+//! - They are applied at one point of each loop iteration: after the periodic
+//!   probes (Steam, thermal, pause file) and before the pause decision and the
+//!   frame. A `pause` therefore takes effect in the iteration that applies it.
+//! - A SIGHUP received since the last iteration is applied first, as `reload`.
+//! - At most [`ITERATION_BUDGET`] commands are applied per iteration. The rest
+//!   wait for the next one, which then starts without sleeping, so a flood
+//!   delays commands instead of frames.
+//! - A full queue refuses new commands at once with "renderer busy"; nothing
+//!   ever blocks the render thread on a producer.
+//! - A reply is sent after the command is applied. Commands still queued when
+//!   the loop exits get "renderer is shutting down".
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError, sync_channel};
+use std::time::{Duration, Instant};
+
+use crate::reduce_motion::Mode;
+
+/// Commands waiting for the render loop, across all producers.
+const QUEUE_CAPACITY: usize = 64;
+/// Commands applied per loop iteration at most.
+pub const ITERATION_BUDGET: usize = 16;
+
+/// Something the render loop is asked to do, parsed from a control line
+/// (`pause-output DP-1`) or built directly by the D-Bus service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeCommand {
+    Quit,
+    Pause {
+        reason: String,
+    },
+    Resume,
+    /// `pause-output` / `resume-output`.
+    PauseOutput {
+        monitor: String,
+        paused: bool,
+    },
+    Reload {
+        hard: bool,
+    },
+    /// `set-video <persist|transient> <monitor>\t<path>`.
+    SetVideo {
+        transient: bool,
+        monitor: String,
+        video: String,
+    },
+    ClearTransient,
+    /// `events [<after-seq>]`; `None` only reports where the log is.
+    Events {
+        after: Option<u64>,
+    },
+    /// `reduce-motion on|off|toggle`.
+    ReduceMotion {
+        mode: Mode,
+    },
+    Status,
+    Ping,
+    Profile,
+    /// `snapshot`: saves the runtime state for the next start now
+    /// (`restart --preserve-state`).
+    Snapshot,
+}
+
+impl RuntimeCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        Ok(match (command, arg) {
+            ("quit", "") => Self::Quit,
+            ("pause", reason) => Self::Pause {
+                reason: reason.to_string(),
+            },
+            ("resume", "") => Self::Resume,
+            ("pause-output" | "resume-output", monitor) => Self::PauseOutput {
+                monitor: monitor.to_string(),
+                paused: command == "pause-output",
+            },
+            ("reload", "") => Self::Reload { hard: false },
+            ("reload", "hard") => Self::Reload { hard: true },
+            ("set-video", _) => Self::parse_set_video(line.split_once(' ').map_or("", |s| s.1))?,
+            ("clear-transient", "") => Self::ClearTransient,
+            ("reduce-motion", mode) => Self::ReduceMotion {
+                mode: Mode::parse(mode)
+                    .ok_or_else(|| "usage: reduce-motion <on|off|toggle>".to_string())?,
+            },
+            ("events", "") => Self::Events { after: None },
+            ("events", after) => Self::Events {
+                after: Some(
+                    after
+                        .parse()
+                        .map_err(|_| "usage: events [<after-seq>]".to_string())?,
+                ),
+            },
+            ("status", "") => Self::Status,
+            ("ping", "") => Self::Ping,
+            ("profile", "") => Self::Profile,
+            ("snapshot", "") => Self::Snapshot,
+            _ => return Err(format!("unknown command: {line}")),
+        })
+    }
+
+    /// Tabs separate the fields, so the monitor and path keep their spaces.
+    fn parse_set_video(arg: &str) -> Result<Self, String> {
+        let usage = "usage: set-video <persist|transient> <monitor>\t<path>";
+        let (mode, rest) = arg.split_once(' ').ok_or(usage)?;
+        let transient = match mode {
+            "persist" => false,
+            "transient" => true,
+            _ => return Err(usage.to_string()),
+        };
+        let (monitor, video) = rest.split_once('\t').unwrap_or((rest, ""));
+        Ok(Self::SetVideo {
+            transient,
+            monitor: monitor.trim().to_string(),
+            video: video.trim().to_string(),
+        })
+    }
+}
+
+/// Where a request's reply goes; dropped unanswered, the waiting side sees
+/// the queue go away.
+pub type Reply = Box<dyn FnOnce(String) + Send>;
+
+/// A queued command, with where its reply goes (`None` for a notification).
+pub struct Envelope {
+    pub command: RuntimeCommand,
+    reply: Option<Reply>,
+}
+
+impl Envelope {
+    pub fn answer(self, reply: String) {
+        if let Some(send) = self.reply {
+            send(reply);
+        }
+    }
+}
+
+/// The producers' end; cheap to clone, one per input.
+#[derive(Clone)]
+pub struct CommandSender {
+    sender: SyncSender<Envelope>,
+}
+
+impl CommandSender {
+    /// Queues `command` without waiting; `reply` is called with the answer.
+    pub fn send(&self, command: RuntimeCommand, reply: Option<Reply>) -> Result<(), String> {
+        self.sender
+            .try_send(Envelope { command, reply })
+            .map_err(|err| match err {
+                TrySendError::Full(_) => "renderer busy: command queue full".to_string(),
+                TrySendError::Disconnected(_) => "renderer is shutting down".to_string(),
+            })
+    }
+
+    /// Queues `command` and waits up to `timeout` for its reply.
+    pub fn request(&self, command: RuntimeCommand, timeout: Duration) -> Result<String, String> {
+        let (reply, answer) = sync_channel(1);
+        self.send(
+            command,
+            Some(Box::new(move |text| {
+                let _ = reply.send(text);
+            })),
+        )?;
+        answer.recv_timeout(timeout).map_err(|err| match err {
+            RecvTimeoutError::Timeout => "renderer did not answer in time".to_string(),
+            RecvTimeoutError::Disconnected => "renderer is shutting down".to_string(),
+        })
+    }
+}
+
+/// The render loop's end.
+pub struct CommandQueue {
+    sender: CommandSender,
+    receiver: Receiver<Envelope>,
+    /// Taken off the channel while the loop waited, not applied yet.
+    pending: VecDeque<Envelope>,
+}
+
+impl CommandQueue {
+    pub fn new() -> Self {
+        let (sender, receiver) = sync_channel(QUEUE_CAPACITY);
+        Self {
+            sender: CommandSender { sender },
+            receiver,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn sender(&self) -> CommandSender {
+        self.sender.clone()
+    }
+
+    /// The oldest queued command, if any.
+    pub fn next(&mut self) -> Option<Envelope> {
+        if let Some(envelope) = self.pending.pop_front() {
+            return Some(envelope);
+        }
+        self.receiver.try_recv().ok()
+    }
+
+    /// Sleeps until `deadline` or until a command arrives, whichever is
+    /// first. Used while paused, when the loop has no frame to wake for.
+    pub fn wait_until(&mut self, deadline: Instant) {
+        if !self.pending.is_empty() {
+            return;
+        }
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if let Ok(envelope) = self.receiver.recv_timeout(timeout) {
+            self.pending.push_back(envelope);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn control_lines_parse_to_commands() {
+        let cases = [
+            ("quit", RuntimeCommand::Quit),
+            (
+                "pause  game running ",
+                RuntimeCommand::Pause {
+                    reason: "game running".to_string(),
+                },
+            ),
+            (
+                "resume-output DP-1",
+                RuntimeCommand::PauseOutput {
+                    monitor: "DP-1".to_string(),
+                    paused: false,
+                },
+            ),
+            ("reload hard", RuntimeCommand::Reload { hard: true }),
+            (
+                "set-video transient Dell Inc. U2720Q\t/v/my clip.mp4",
+                RuntimeCommand::SetVideo {
+                    transient: true,
+                    monitor: "Dell Inc. U2720Q".to_string(),
+                    video: "/v/my clip.mp4".to_string(),
+                },
+            ),
+            ("events 42", RuntimeCommand::Events { after: Some(42) }),
+            ("events", RuntimeCommand::Events { after: None }),
+            (
+                "reduce-motion toggle",
+                RuntimeCommand::ReduceMotion { mode: Mode::Toggle },
+            ),
+            ("status\n", RuntimeCommand::Status),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(RuntimeCommand::parse(line), Ok(expected), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn malformed_lines_say_how_to_use_the_command() {
+        assert_eq!(
+            RuntimeCommand::parse("frobnicate now"),
+            Err("unknown command: frobnicate now".to_string())
+        );
+        assert!(RuntimeCommand::parse("status now").is_err());
+        assert!(RuntimeCommand::parse("reload soft").is_err());
+        for line in [
+            "set-video",
+            "set-video forever DP-1\t/v/a.mp4",
+            "events soon",
+            "reduce-motion maybe",
+        ] {
+            let err = RuntimeCommand::parse(line).unwrap_err();
+            assert!(err.starts_with("usage: "), "{line}: {err}");
+        }
+    }
+
+    #[test]
+    fn each_producer_keeps_its_order() {
+        let mut queue = CommandQueue::new();
+        let producers = (0..4u64)
+            .map(|producer| {
+                let sender = queue.sender();
+                std::thread::spawn(move || {
+                    for n in 0..12 {
+                        let after = Some(producer * 100 + n);
+                        sender.send(RuntimeCommand::Events { after }, None).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        let mut last = [None::<u64>; 4];
+        let mut count = 0;
+        while let Some(envelope) = queue.next() {
+            let RuntimeCommand::Events { after: Some(seq) } = envelope.command else {
+                panic!("unexpected {:?}", envelope.command);
+            };
+            let slot = &mut last[(seq / 100) as usize];
+            assert!(slot.is_none_or(|prev| prev < seq), "{seq} after {slot:?}");
+            *slot = Some(seq);
+            count += 1;
+        }
+        assert_eq!(count, 48);
+    }
+
+    #[test]
+    fn a_full_queue_refuses_instead_of_blocking() {
+        let mut queue = CommandQueue::new();
+        let sender = queue.sender();
+        for _ in 0..QUEUE_CAPACITY {
+            sender.send(RuntimeCommand::Ping, None).unwrap();
+        }
+        assert_eq!(
+            sender.send(RuntimeCommand::Ping, None),
+            Err("renderer busy: command queue full".to_string())
+        );
+        assert!(queue.next().is_some());
+        assert!(sender.send(RuntimeCommand::Ping, None).is_ok());
+    }
+
+    #[test]
+    fn requests_get_the_reply_or_a_shutdown_error() {
+        let mut queue = CommandQueue::new();
+        let sender = queue.sender();
+        let client = std::thread::spawn({
+            let sender = sender.clone();
+            move || sender.request(RuntimeCommand::Ping, Duration::from_secs(10))
+        });
+        queue.wait_until(Instant::now() + Duration::from_secs(10));
+        let envelope = queue.next().unwrap();
+        assert_eq!(envelope.command, RuntimeCommand::Ping);
+        envelope.answer("{\"ok\":true}".to_string());
+        assert_eq!(client.join().unwrap(), Ok("{\"ok\":true}".to_string()));
+
+        // Dropped unanswered: the client hears the renderer went away.
+        let client = std::thread::spawn({
+            let sender = sender.clone();
+            move || sender.request(RuntimeCommand::Status, Duration::from_secs(10))
+        });
+        queue.wait_until(Instant::now() + Duration::from_secs(10));
+        drop(queue.next().unwrap());
+        assert_eq!(
+            client.join().unwrap(),
+            Err("renderer is shutting down".to_string())
+        );
+
+        assert_eq!(
+            sender.request(RuntimeCommand::Ping, Duration::from_millis(20)),
+            Err("renderer did not answer in time".to_string())
+        );
+        drop(queue);
+        assert_eq!(
+            sender.send(RuntimeCommand::Ping, None),
+            Err("renderer is shutting down".to_string())
+        );
+    }
+
+    #[test]
+    fn wait_until_wakes_for_a_command_or_the_deadline() {
+        let mut queue = CommandQueue::new();
+        let started = Instant::now();
+        queue.wait_until(started + Duration::from_millis(30));
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        assert!(queue.next().is_none());
+
+        let sender = queue.sender();
+        let producer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            sender.send(RuntimeCommand::Resume, None).unwrap();
+        });
+        let started = Instant::now();
+        queue.wait_until(started + Duration::from_secs(10));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(queue.next().unwrap().command, RuntimeCommand::Resume);
+        producer.join().unwrap();
+    }
+
+    /// Eight clients hammer the queue while a loop renders "frames" every
+    /// 4 ms, applying at most [`ITERATION_BUDGET`] commands each: every
+    /// request is answered or refused, and the frames keep their pace.
+    #[test]
+    fn a_flood_of_requests_does_not_starve_the_loop() {
+        const FRAME: Duration = Duration::from_millis(4);
+        const FRAMES: u32 = 150;
+        let mut queue = CommandQueue::new();
+        let stop = Arc::new(AtomicBool::new(false));
+        let clients = (0..8)
+            .map(|_| {
+                let sender = queue.sender();
+                let stop = Arc::clone(&stop);
+                std::thread::spawn(move || {
+                    let (mut answered, mut refused) = (0u32, 0u32);
+                    while !stop.load(Ordering::Relaxed) {
+                        match sender.request(RuntimeCommand::Status, Duration::from_secs(5)) {
+                            Ok(reply) => {
+                                assert_eq!(reply, "status");
+                                answered += 1;
+                            }
+                            Err(err) if err.starts_with("renderer busy") => refused += 1,
+                            Err(_) if stop.load(Ordering::Relaxed) => break,
+                            Err(err) => panic!("{err}"),
+                        }
+                    }
+                    (answered, refused)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let started = Instant::now();
+        let mut late = 0;
+        for frame in 1..=FRAMES {
+            let mut applied = 0;
+            while applied < ITERATION_BUDGET {
+                let Some(envelope) = queue.next() else {
+                    break;
+                };
+                envelope.answer("status".to_string());
+                applied += 1;
+            }
+            let due = started + FRAME * frame;
+            late += usize::from(Instant::now() > due + FRAME);
+            std::thread::sleep(due.saturating_duration_since(Instant::now()));
+        }
+        stop.store(true, Ordering::Relaxed);
+        while let Some(envelope) = queue.next() {
+            envelope.answer("status".to_string());
+        }
+        // Clients still waiting get the drain above or, once the queue is
+        // gone, the shutdown error, and stop.
+        drop(queue);
+        let answered = clients
+            .into_iter()
+            .map(|client| client.join().unwrap().0)
+            .sum::<u32>();
+
+        let elapsed = started.elapsed();
+        assert!(answered > FRAMES, "only {answered} requests answered");
+        assert!(
+            elapsed < FRAME * FRAMES * 2,
+            "{FRAMES} frames took {elapsed:?}"
+        );
+        assert!(late < FRAMES as usize / 10, "{late} late frames");
+    }
+}
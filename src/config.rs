@@ -1,9 +1,86 @@
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::quiet_hours::QuietHours;
+
+const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+pub const DEFAULT_VIDEO_FPS: u32 = 30;
+pub const DEFAULT_VIDEO_SPEED: f32 = 1.0;
+
+/// Decode rates ffmpeg is asked for. Past them the filter graph degenerates:
+/// `setpts=PTS/50` decodes fifty source frames per frame shown and pegs a
+/// core, and `PTS/0.01` makes ffmpeg emit each frame a hundred times.
+pub const VIDEO_FPS_RANGE: RangeInclusive<u32> = 1..=240;
+pub const VIDEO_SPEED_RANGE: RangeInclusive<f32> = 0.1..=10.0;
+
+/// A setting as given and the value used for it, which differs (with a
+/// warning saying why) when the given one is unreadable or out of range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checked<T> {
+    pub value: T,
+    pub warning: Option<String>,
+}
+
+/// `KRC_VIDEO_FPS`, clamped to [`VIDEO_FPS_RANGE`].
+pub fn video_fps(raw: Option<&str>) -> Checked<u32> {
+    check_range("KRC_VIDEO_FPS", raw, DEFAULT_VIDEO_FPS, VIDEO_FPS_RANGE)
+}
+
+/// `KRC_VIDEO_SPEED`, clamped to [`VIDEO_SPEED_RANGE`].
+pub fn video_speed(raw: Option<&str>) -> Checked<f32> {
+    check_range(
+        "KRC_VIDEO_SPEED",
+        raw,
+        DEFAULT_VIDEO_SPEED,
+        VIDEO_SPEED_RANGE,
+    )
+}
+
+fn check_range<T>(key: &str, raw: Option<&str>, default: T, range: RangeInclusive<T>) -> Checked<T>
+where
+    T: FromStr + PartialOrd + Copy + Display,
+{
+    let Some(raw) = raw.map(str::trim) else {
+        return Checked {
+            value: default,
+            warning: None,
+        };
+    };
+    let (min, max) = (*range.start(), *range.end());
+    let (value, warning) = match raw.parse::<T>() {
+        Ok(value) if range.contains(&value) => (value, None),
+        Ok(value) if value < min => (
+            min,
+            Some(format!("{key}={raw} is below {min}, using {min}")),
+        ),
+        Ok(value) if value > max => (
+            max,
+            Some(format!("{key}={raw} is above {max}, using {max}")),
+        ),
+        // Unreadable, or NaN for a float.
+        _ => (
+            default,
+            Some(format!(
+                "ignoring {key}={raw}: expected {min}-{max}, using {default}"
+            )),
+        ),
+    };
+    Checked { value, warning }
+}
+
 #[derive(Debug, Clone)]
 pub struct RenderCoreConfig {
     pub target_fps: u32,
     pub use_vsync: bool,
     pub pause_on_maximized: bool,
     pub max_frames: Option<u64>,
+    /// Period of the `[rendercore] report` line; `None` turns it off.
+    pub report_interval: Option<Duration>,
+    /// `KRC_QUIET_HOURS`: when the renderer stops entirely.
+    pub quiet_hours: Option<QuietHours>,
 }
 
 impl Default for RenderCoreConfig {
@@ -12,11 +89,113 @@ impl Default for RenderCoreConfig {
             .ok()
             .and_then(|v| v.parse::<u64>().ok())
             .filter(|v| *v > 0);
+        // A ceiling for every output, whatever its panel refresh (VRR panels
+        // report up to 165Hz and more).
+        let max_output_fps = std::env::var("KRC_MAX_OUTPUT_FPS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .filter(|v| *v > 0);
+        let report_interval = match std::env::var("KRC_REPORT_INTERVAL_SEC") {
+            Ok(raw) => match raw.trim().parse::<u64>() {
+                Ok(0) => None,
+                Ok(secs) => Some(Duration::from_secs(secs)),
+                Err(_) => {
+                    eprintln!(
+                        "[rendercore] ignoring KRC_REPORT_INTERVAL_SEC={raw}: expected whole seconds"
+                    );
+                    Some(DEFAULT_REPORT_INTERVAL)
+                }
+            },
+            Err(_) => Some(DEFAULT_REPORT_INTERVAL),
+        };
+        let quiet_hours = std::env::var("KRC_QUIET_HOURS")
+            .ok()
+            .filter(|raw| !raw.trim().is_empty())
+            .and_then(|raw| {
+                QuietHours::parse(&raw)
+                    .inspect_err(|err| {
+                        eprintln!("[rendercore] ignoring KRC_QUIET_HOURS={raw}: {err}")
+                    })
+                    .ok()
+            });
         Self {
-            target_fps: 60,
+            target_fps: max_output_fps.map_or(60, |max| max.min(60)),
             use_vsync: true,
             pause_on_maximized: true,
             max_frames,
+            report_interval,
+            quiet_hours,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unset_values_use_the_defaults_silently() {
+        assert_eq!(
+            video_fps(None),
+            Checked {
+                value: DEFAULT_VIDEO_FPS,
+                warning: None,
+            }
+        );
+        assert_eq!(video_speed(None).value, DEFAULT_VIDEO_SPEED);
+        assert_eq!(video_speed(None).warning, None);
+    }
+
+    #[test]
+    fn range_bounds_are_accepted() {
+        for raw in ["1", "240", " 60 "] {
+            assert_eq!(video_fps(Some(raw)).warning, None, "{raw}");
+        }
+        for raw in ["0.1", "10", "1.5"] {
+            assert_eq!(video_speed(Some(raw)).warning, None, "{raw}");
+        }
+    }
+
+    #[test]
+    fn out_of_range_values_clamp_with_a_warning() {
+        let fps = video_fps(Some("0"));
+        assert_eq!(fps.value, 1);
+        assert_eq!(
+            fps.warning.as_deref(),
+            Some("KRC_VIDEO_FPS=0 is below 1, using 1")
+        );
+        assert_eq!(video_fps(Some("1000")).value, 240);
+
+        let slow = video_speed(Some("0.01"));
+        assert_eq!(slow.value, 0.1);
+        assert_eq!(
+            slow.warning.as_deref(),
+            Some("KRC_VIDEO_SPEED=0.01 is below 0.1, using 0.1")
+        );
+        let fast = video_speed(Some("50"));
+        assert_eq!(fast.value, 10.0);
+        assert_eq!(
+            fast.warning.as_deref(),
+            Some("KRC_VIDEO_SPEED=50 is above 10, using 10")
+        );
+    }
+
+    #[test]
+    fn unreadable_values_fall_back_to_the_default() {
+        for raw in ["fast", "", "-", "30fps"] {
+            let fps = video_fps(Some(raw));
+            assert_eq!(fps.value, DEFAULT_VIDEO_FPS, "{raw}");
+            assert!(fps.warning.unwrap().starts_with("ignoring KRC_VIDEO_FPS="));
+        }
+        for raw in ["NaN", "inf", "-inf", "x2"] {
+            let speed = video_speed(Some(raw));
+            let expected = match raw {
+                "inf" => 10.0,
+                "-inf" => 0.1,
+                _ => DEFAULT_VIDEO_SPEED,
+            };
+            assert_eq!(speed.value, expected, "{raw}");
+            assert!(speed.warning.is_some(), "{raw}");
         }
     }
 }
@@ -0,0 +1,995 @@
+use std::collections::BTreeMap;
+use std::ffi::{CString, OsString};
+use std::fs;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+
+use crate::app::monitors_for_all;
+use crate::config_file::config_file_path_from_env;
+use crate::file_access::{write_atomic, write_blocker};
+use crate::map_migrate::verify_round_trip;
+use crate::paths::config_dir;
+use crate::tools::Tool;
+use crate::user_path::{cli_base_dir, display_path, display_text, home_dir};
+use crate::video_map::{
+    MapDocument, MapFormat, VideoMapEntry, check_monitor_name, format_map_document,
+    map_file_path_from_env, parse_map_document, write_map_document,
+};
+
+/// Describes the rest of the bundle; its presence is what marks an archive as
+/// one of ours.
+const MANIFEST: &str = "kitsune-rendercore-bundle";
+const BUNDLE_VERSION: &str = "1";
+const VIDEOS_DIR: &str = "videos";
+const CONFIG_NAME: &str = "config.toml";
+const ENV_NAME: &str = "rendercore.env";
+
+/// `export-config`: the map, config.toml and `KRC_ENV_FILE`, optionally with
+/// the videos the map plays, packed into one archive `import-config` can
+/// install on another machine.
+pub fn run_export_config(args: &[String]) -> Result<(), String> {
+    let mut out = None::<PathBuf>;
+    let mut map_file = None::<PathBuf>;
+    let mut include_videos = false;
+    let mut force = false;
+    let mut i = 0usize;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        let mut value = |name: &str| {
+            i += 1;
+            args.get(i)
+                .cloned()
+                .ok_or_else(|| format!("missing value for {name}"))
+        };
+        match arg {
+            "--out" | "-o" => out = Some(PathBuf::from(value("--out")?)),
+            "--map-file" => map_file = Some(PathBuf::from(value("--map-file")?)),
+            "--include-videos" => include_videos = true,
+            "--force" => force = true,
+            "--help" | "-h" => {
+                print_export_help();
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument for export-config: {other}")),
+        }
+        i += 1;
+    }
+    let out =
+        out.ok_or_else(|| "export-config needs --out <FILE>, e.g. setup.tar.zst".to_string())?;
+    let out = cli_base_dir().join(out);
+    if out.exists() && !force {
+        return Err(format!(
+            "{} already exists; pass --force to replace it",
+            out.display()
+        ));
+    }
+
+    let staging = staging_dir("export")?;
+    let result = export_into(&staging, &out, map_file, include_videos);
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn export_into(
+    staging: &Path,
+    out: &Path,
+    map_file: Option<PathBuf>,
+    include_videos: bool,
+) -> Result<(), String> {
+    let mut manifest = vec![("version", BUNDLE_VERSION.to_string())];
+    let mut parts = Vec::new();
+
+    let map_path = map_file.map_or_else(map_file_path_from_env, Ok)?;
+    if map_path.exists() {
+        let contents = fs::read_to_string(&map_path)
+            .map_err(|e| format!("cannot read map file {}: {e}", map_path.display()))?;
+        let (doc, issues) = parse_map_document(&map_path, &contents);
+        if let Some(issue) = issues.first() {
+            return Err(format!(
+                "{} line {} is skipped by the renderer ({}); fix or remove it first so the export does not drop it (see 'watch-map --once')",
+                map_path.display(),
+                issue.line,
+                display_text(&issue.message)
+            ));
+        }
+        let name = match doc.format {
+            MapFormat::V1 => "video-map.conf",
+            MapFormat::V2 => "video-map.toml",
+        };
+        let (bundled, videos) = portable_map(&doc, include_videos)?;
+        if include_videos {
+            let dir = staging.join(VIDEOS_DIR);
+            fs::create_dir_all(&dir)
+                .map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+            let mut bytes = 0u64;
+            for (source, name) in &videos {
+                bytes += fs::copy(source, dir.join(name))
+                    .map_err(|e| format!("failed to copy video {}: {e}", display_path(source)))?;
+            }
+            parts.push(format!(
+                "{} video(s), {:.1} MiB",
+                videos.len(),
+                bytes as f64 / (1024.0 * 1024.0)
+            ));
+        }
+        // The bundled map must read back to the same entries wherever the
+        // import puts the videos; check it here, where they are known.
+        let bundled_path = staging.join(name);
+        let expected = MapDocument {
+            entries: doc
+                .entries
+                .iter()
+                .map(|(monitor, entry)| (monitor.clone(), staged_entry(entry, staging, &videos)))
+                .collect(),
+            default: doc
+                .default
+                .as_ref()
+                .map(|entry| staged_entry(entry, staging, &videos)),
+            ..doc.clone()
+        };
+        verify_round_trip(&expected, &bundled, &bundled_path)?;
+        write_map_document(&bundled_path, &bundled)?;
+        manifest.push(("map", name.to_string()));
+        parts.insert(
+            0,
+            format!(
+                "map ({}, {} entr{})",
+                doc.format.label(),
+                doc.entries.len(),
+                if doc.entries.len() == 1 { "y" } else { "ies" }
+            ),
+        );
+    } else {
+        println!(
+            "[info] no map file at {}; exporting without one",
+            map_path.display()
+        );
+    }
+
+    let config_path = config_file_path_from_env()?;
+    if config_path.exists() {
+        copy_part(&config_path, &staging.join(CONFIG_NAME))?;
+        manifest.push(("config", CONFIG_NAME.to_string()));
+        parts.push(CONFIG_NAME.to_string());
+    }
+    if let Some(env_path) = env_file_path()
+        && env_path.exists()
+    {
+        copy_part(&env_path, &staging.join(ENV_NAME))?;
+        manifest.push(("env", ENV_NAME.to_string()));
+        parts.push("env file".to_string());
+        println!(
+            "[info] {} is bundled verbatim; paths inside it (KRC_VIDEO_MAP, KRC_VIDEO_DEFAULT) are not rewritten",
+            env_path.display()
+        );
+    }
+    if parts.is_empty() {
+        return Err("nothing to export: no map file, config.toml or KRC_ENV_FILE".to_string());
+    }
+
+    let manifest_text = manifest
+        .iter()
+        .map(|(key, value)| format!("{key}={value}\n"))
+        .collect::<String>();
+    fs::write(staging.join(MANIFEST), manifest_text)
+        .map_err(|e| format!("failed to write bundle manifest: {e}"))?;
+    run_tar(&[
+        "-caf",
+        &out.to_string_lossy(),
+        "-C",
+        &staging.to_string_lossy(),
+        ".",
+    ])?;
+    println!("[ok] exported {} to {}", parts.join(", "), out.display());
+    Ok(())
+}
+
+/// The map as it goes into the bundle: with `include_videos` every video
+/// becomes `videos/<name>`, otherwise paths under the home directory become
+/// `~/...` so the import resolves them against the target's home. Returns the
+/// videos to copy as (source, bundled name).
+fn portable_map(
+    doc: &MapDocument,
+    include_videos: bool,
+) -> Result<(MapDocument, Vec<(String, String)>), String> {
+    let mut videos = Vec::<(String, String)>::new();
+    if include_videos {
+        let all = doc.entries.values().chain(doc.default.as_ref());
+        for video in all.flat_map(VideoMapEntry::videos) {
+            if videos.iter().any(|(source, _)| source == video) {
+                continue;
+            }
+            if !Path::new(video).is_file() {
+                return Err(format!(
+                    "video {} does not exist; fix the map or export without --include-videos",
+                    display_path(video)
+                ));
+            }
+            videos.push((video.to_string(), bundled_video_name(video, &videos)));
+        }
+    }
+    let home = home_dir().map(|home| home.to_string_lossy().trim_end_matches('/').to_string());
+    let rewrite = |entry: &VideoMapEntry| {
+        entry.map_videos(
+            |video| match videos.iter().find(|(source, _)| source == video) {
+                Some((_, name)) => format!("{VIDEOS_DIR}/{name}"),
+                None => match home
+                    .as_deref()
+                    .filter(|home| !home.is_empty())
+                    .and_then(|home| video.strip_prefix(home))
+                {
+                    Some(rest) if rest.starts_with('/') => format!("~{rest}"),
+                    _ => video.to_string(),
+                },
+            },
+        )
+    };
+    let bundled = MapDocument {
+        format: doc.format,
+        entries: doc
+            .entries
+            .iter()
+            .map(|(monitor, entry)| (monitor.clone(), rewrite(entry)))
+            .collect(),
+        default: doc.default.as_ref().map(rewrite),
+    };
+    Ok((bundled, videos))
+}
+
+/// The file name, made unique among the videos already picked.
+fn bundled_video_name(video: &str, taken: &[(String, String)]) -> String {
+    let name = Path::new(video)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "video".to_string());
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while taken.iter().any(|(_, used)| *used == candidate) {
+        candidate = format!("{n}-{name}");
+        n += 1;
+    }
+    candidate
+}
+
+/// What a bundled entry parses back to with the bundle unpacked at `staging`.
+fn staged_entry(
+    entry: &VideoMapEntry,
+    staging: &Path,
+    videos: &[(String, String)],
+) -> VideoMapEntry {
+    entry.map_videos(
+        |video| match videos.iter().find(|(source, _)| source == video) {
+            Some((_, name)) => staging
+                .join(VIDEOS_DIR)
+                .join(name)
+                .to_string_lossy()
+                .into_owned(),
+            None => video.to_string(),
+        },
+    )
+}
+
+/// `import-config`: unpacks a bundle from `export-config`, places its videos,
+/// checks every file the map refers to, and installs the map, config.toml
+/// and env file through temp files and renames.
+pub fn run_import_config(args: &[String]) -> Result<(), String> {
+    let mut bundle = None::<PathBuf>;
+    let mut videos_dir = None::<PathBuf>;
+    let mut map_file = None::<PathBuf>;
+    let mut force = false;
+    let mut allow_missing = false;
+    let mut i = 0usize;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        let mut value = |name: &str| {
+            i += 1;
+            args.get(i)
+                .cloned()
+                .ok_or_else(|| format!("missing value for {name}"))
+        };
+        match arg {
+            "--videos-dir" => videos_dir = Some(PathBuf::from(value("--videos-dir")?)),
+            "--map-file" => map_file = Some(PathBuf::from(value("--map-file")?)),
+            "--force" => force = true,
+            "--allow-missing" => allow_missing = true,
+            "--help" | "-h" => {
+                print_import_help();
+                return Ok(());
+            }
+            other if other.starts_with('-') => {
+                return Err(format!("unknown argument for import-config: {other}"));
+            }
+            other if bundle.is_none() => bundle = Some(PathBuf::from(other)),
+            other => return Err(format!("unexpected argument for import-config: {other}")),
+        }
+        i += 1;
+    }
+    let bundle = bundle.ok_or_else(|| "import-config needs the bundle file".to_string())?;
+    let bundle = cli_base_dir().join(bundle);
+    if !bundle.is_file() {
+        return Err(format!("bundle {} does not exist", bundle.display()));
+    }
+    let options = ImportOptions {
+        videos_dir: videos_dir.map(|dir| cli_base_dir().join(dir)),
+        map_file,
+        force,
+        allow_missing,
+    };
+
+    let staging = staging_dir("import")?;
+    let result = run_tar(&[
+        "-xf",
+        &bundle.to_string_lossy(),
+        "-C",
+        &staging.to_string_lossy(),
+    ])
+    .and_then(|()| import_from(&staging, &options));
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+struct ImportOptions {
+    videos_dir: Option<PathBuf>,
+    map_file: Option<PathBuf>,
+    force: bool,
+    allow_missing: bool,
+}
+
+/// One file to install, and what it replaces.
+struct Install {
+    target: PathBuf,
+    contents: Vec<u8>,
+    label: &'static str,
+}
+
+fn import_from(staging: &Path, options: &ImportOptions) -> Result<(), String> {
+    let manifest = read_manifest(staging)?;
+    let mut installs = Vec::<Install>::new();
+    let mut videos = Vec::<(PathBuf, PathBuf)>::new();
+    let mut map = None::<(PathBuf, MapDocument)>;
+
+    if let Some(name) = manifest.get("map") {
+        let bundled_path = staging.join(bundled_name(name)?);
+        let contents = fs::read_to_string(&bundled_path)
+            .map_err(|e| format!("bundle map {name} is unreadable: {e}"))?;
+        let (mut doc, issues) = parse_map_document(&bundled_path, &contents);
+        if let Some(issue) = issues.first() {
+            return Err(format!(
+                "bundle map line {} does not parse here: {}",
+                issue.line,
+                display_text(&issue.message)
+            ));
+        }
+        let map_path = match &options.map_file {
+            Some(path) => path.clone(),
+            None => match map_file_path_from_env()? {
+                path if path.exists() || std::env::var_os("KRC_VIDEO_MAP_FILE").is_some() => path,
+                _ => config_dir()?.join(name),
+            },
+        };
+        let videos_dir = match &options.videos_dir {
+            Some(dir) => dir.clone(),
+            None => map_path
+                .parent()
+                .map_or_else(cli_base_dir, Path::to_path_buf)
+                .join(VIDEOS_DIR),
+        };
+        let staged_videos = staging.join(VIDEOS_DIR);
+        let mut escaping = None::<String>;
+        let entries = doc.entries.values_mut().chain(doc.default.as_mut());
+        for entry in entries {
+            *entry = entry.map_videos(|video| {
+                let Ok(rel) = Path::new(video).strip_prefix(&staged_videos) else {
+                    return video.to_string();
+                };
+                if !stays_inside(rel) {
+                    escaping.get_or_insert_with(|| video.to_string());
+                    return video.to_string();
+                }
+                let target = videos_dir.join(rel);
+                if !videos.iter().any(|(_, placed)| *placed == target) {
+                    videos.push((PathBuf::from(video), target.clone()));
+                }
+                target.to_string_lossy().into_owned()
+            });
+        }
+        if let Some(video) = escaping {
+            return Err(format!(
+                "bundle map plays {}, which is outside the bundle's videos; refusing to import it",
+                display_path(&video)
+            ));
+        }
+        // A link unpacked from the bundle could point anywhere on this
+        // machine; only files really inside the unpacked videos are copied.
+        let unpacked = fs::canonicalize(&staged_videos).ok();
+        for (source, _) in &videos {
+            let inside = fs::symlink_metadata(source).is_ok_and(|meta| meta.is_file())
+                && fs::canonicalize(source)
+                    .ok()
+                    .zip(unpacked.as_ref())
+                    .is_some_and(|(real, root)| real.starts_with(root));
+            if !inside {
+                return Err(format!(
+                    "bundle video {} is not a plain file inside the bundle; refusing to import it",
+                    display_path(&source.to_string_lossy())
+                ));
+            }
+        }
+
+        let existing = fs::read_to_string(&map_path).ok();
+        let is_toml = map_path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        let format = if existing.is_some() || is_toml {
+            MapFormat::detect(&map_path, existing.as_deref())
+        } else {
+            doc.format
+        };
+        if format != doc.format {
+            if format == MapFormat::V1 && doc.default.is_some() {
+                return Err(format!(
+                    "{} is a v1 map, which cannot hold the bundle's [default]; pass --map-file with a .toml path",
+                    map_path.display()
+                ));
+            }
+            for monitor in doc.entries.keys() {
+                check_monitor_name(format, monitor)?;
+            }
+            println!(
+                "[info] the bundle map is {}; writing it as {} to match {}",
+                doc.format.label(),
+                format.label(),
+                map_path.display()
+            );
+        }
+        let installed = MapDocument {
+            format,
+            ..doc.clone()
+        };
+        verify_round_trip(&doc, &installed, &map_path)?;
+        map = Some((map_path, installed));
+    }
+    if let Some(name) = manifest.get("config") {
+        installs.push(Install {
+            target: config_file_path_from_env()?,
+            contents: read_part(staging, name)?,
+            label: "config.toml",
+        });
+    }
+    if let Some(name) = manifest.get("env") {
+        let target = match env_file_path() {
+            Some(path) => path,
+            None => config_dir()?.join(ENV_NAME),
+        };
+        installs.push(Install {
+            target,
+            contents: read_part(staging, name)?,
+            label: "env file",
+        });
+    }
+
+    // Everything is checked before the first write, so a refused import
+    // leaves the machine as it was.
+    let mut missing = Vec::new();
+    if let Some((_, doc)) = &map {
+        for (monitor, entry) in doc
+            .entries
+            .iter()
+            .map(|(monitor, entry)| (monitor.as_str(), entry))
+            .chain(doc.default.as_ref().map(|entry| ("[default]", entry)))
+        {
+            for video in entry.videos() {
+                let placed = videos.iter().any(|(_, target)| target.as_os_str() == video);
+                if !placed && !Path::new(video).is_file() {
+                    missing.push((monitor, video.to_string()));
+                }
+            }
+        }
+    }
+    for (monitor, video) in &missing {
+        println!(
+            "[warn] {monitor}: video {} does not exist on this machine",
+            display_path(video)
+        );
+    }
+    if !missing.is_empty() && !options.allow_missing {
+        return Err(format!(
+            "{} referenced video(s) are missing; copy them first, export with --include-videos, or pass --allow-missing",
+            missing.len()
+        ));
+    }
+    let mut conflicts = Vec::new();
+    for (source, target) in &videos {
+        if let Ok(meta) = fs::metadata(target)
+            && fs::metadata(source).map(|m| m.len()).ok() != Some(meta.len())
+        {
+            conflicts.push(target.clone());
+        }
+    }
+    if let Some((map_path, doc)) = &map
+        && fs::read_to_string(map_path).is_ok_and(|current| current != format_map_document(doc))
+    {
+        conflicts.push(map_path.clone());
+    }
+    for install in &installs {
+        if fs::read(&install.target).is_ok_and(|current| current != install.contents) {
+            conflicts.push(install.target.clone());
+        }
+    }
+    if !conflicts.is_empty() && !options.force {
+        for path in &conflicts {
+            println!("[warn] {} exists with other contents", path.display());
+        }
+        return Err("pass --force to replace them (each is kept as <file>.bak first)".to_string());
+    }
+
+    for (source, target) in &videos {
+        if fs::metadata(target).map(|m| m.len()).ok() == fs::metadata(source).map(|m| m.len()).ok()
+        {
+            continue;
+        }
+        let contents = fs::read(source)
+            .map_err(|e| format!("bundle video {} is unreadable: {e}", source.display()))?;
+        install_file(target, &contents, conflicts.contains(target))?;
+    }
+    if !videos.is_empty() {
+        println!(
+            "[ok] {} video(s) in {}",
+            videos.len(),
+            videos[0].1.parent().unwrap_or(Path::new("/")).display()
+        );
+    }
+    for install in &installs {
+        install_file(
+            &install.target,
+            &install.contents,
+            conflicts.contains(&install.target),
+        )?;
+        println!("[ok] {} -> {}", install.label, install.target.display());
+    }
+    if let Some((map_path, doc)) = &map {
+        // Last, so a running renderer that reloads it finds the videos in place.
+        if conflicts.contains(map_path) {
+            backup(map_path)?;
+        }
+        write_map_document(map_path, doc)?;
+        println!(
+            "[ok] map ({}, {} entr{}) -> {}",
+            doc.format.label(),
+            doc.entries.len(),
+            if doc.entries.len() == 1 { "y" } else { "ies" },
+            map_path.display()
+        );
+        report_monitors(doc);
+    }
+    if manifest.contains_key("env") && env_file_path().is_none() {
+        println!(
+            "[info] set KRC_ENV_FILE={} for the renderer to read the imported env file",
+            config_dir()?.join(ENV_NAME).display()
+        );
+    }
+    Ok(())
+}
+
+/// Flags map entries for monitors this machine does not have; they are kept,
+/// since a monitor may only be plugged in later.
+fn report_monitors(doc: &MapDocument) {
+    let list = match monitors_for_all() {
+        Ok(list) => list,
+        Err(err) => {
+            println!("[info] cannot list this machine's monitors to check the map: {err}");
+            return;
+        }
+    };
+    let unknown = doc
+        .entries
+        .keys()
+        .filter(|monitor| !list.names.contains(monitor))
+        .map(|monitor| display_text(monitor))
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        println!(
+            "[warn] no such monitor in the {}: {} (imported anyway; rename with set-video/unset-video)",
+            list.source,
+            unknown.join(", ")
+        );
+    }
+}
+
+fn read_manifest(staging: &Path) -> Result<BTreeMap<String, String>, String> {
+    let raw = fs::read_to_string(staging.join(MANIFEST)).map_err(|_| {
+        "not an export-config bundle: it has no kitsune-rendercore-bundle manifest".to_string()
+    })?;
+    let manifest = raw
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect::<BTreeMap<_, _>>();
+    match manifest.get("version").map(String::as_str) {
+        Some(BUNDLE_VERSION) => Ok(manifest),
+        Some(other) => Err(format!(
+            "bundle version {other} is not supported by this kitsune-rendercore (expected {BUNDLE_VERSION})"
+        )),
+        None => Err("bundle manifest has no version".to_string()),
+    }
+}
+
+/// A manifest file name; never a path out of the unpacked bundle.
+fn bundled_name(name: &str) -> Result<&str, String> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains('/') => Ok(name),
+        _ => Err(format!(
+            "bundle manifest names an invalid file '{}'",
+            display_text(name)
+        )),
+    }
+}
+
+/// Whether `rel`, a path under the bundle's videos, names something below
+/// the directory it is joined to: no `..`, root or prefix components.
+fn stays_inside(rel: &Path) -> bool {
+    rel.components().next().is_some()
+        && rel
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+fn read_part(staging: &Path, name: &str) -> Result<Vec<u8>, String> {
+    fs::read(staging.join(bundled_name(name)?))
+        .map_err(|e| format!("bundle file {name} is unreadable: {e}"))
+}
+
+/// `KRC_ENV_FILE`, when set.
+fn env_file_path() -> Option<PathBuf> {
+    std::env::var("KRC_ENV_FILE")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+fn copy_part(source: &Path, target: &Path) -> Result<(), String> {
+    fs::copy(source, target)
+        .map(|_| ())
+        .map_err(|e| format!("failed to copy {}: {e}", source.display()))
+}
+
+/// Writes through a temp file and a rename, as the map writer does, after
+/// keeping the replaced file as `<file>.bak` when asked.
+fn install_file(target: &Path, contents: &[u8], keep_old: bool) -> Result<(), String> {
+    if let Some(why) = write_blocker(target, "file") {
+        return Err(why);
+    }
+    if keep_old {
+        backup(target)?;
+    }
+    write_atomic(target, contents, "file")
+}
+
+fn backup(path: &Path) -> Result<(), String> {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".bak");
+    fs::copy(path, &name)
+        .map(|_| ())
+        .map_err(|e| format!("failed to back up {}: {e}", path.display()))
+}
+
+/// A fresh directory for packing or unpacking, under the system temp dir:
+/// created by mkdtemp(3) with a random name and mode 0700, so nobody else
+/// can plant files or links in it.
+fn staging_dir(purpose: &str) -> Result<PathBuf, String> {
+    let parent = std::env::temp_dir();
+    let template = parent.join(format!("kitsune-rendercore-{purpose}-XXXXXX"));
+    let mut template = CString::new(template.as_os_str().as_bytes())
+        .map_err(|_| format!("temp dir {} holds a NUL byte", parent.display()))?
+        .into_bytes_with_nul();
+    // SAFETY: `template` is NUL-terminated, ends in the six X's mkdtemp(3)
+    // replaces in place, and outlives the call.
+    let dir = unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) };
+    if dir.is_null() {
+        return Err(format!(
+            "failed to create a staging directory in {}: {}",
+            parent.display(),
+            std::io::Error::last_os_error()
+        ));
+    }
+    template.pop();
+    Ok(PathBuf::from(OsString::from_vec(template)))
+}
+
+/// Runs tar; with `-a` it picks the compression from the archive's suffix,
+/// and on extraction it detects it.
+fn run_tar(args: &[&str]) -> Result<(), String> {
+    let output = Tool::Tar
+        .command()
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Tool::Tar.spawn_error(e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ");
+    Err(format!(
+        "tar exited with {}: {}",
+        output.status,
+        display_text(&message)
+    ))
+}
+
+fn print_export_help() {
+    println!("kitsune-rendercore export-config");
+    println!("Usage:");
+    println!(
+        "  kitsune-rendercore export-config --out <FILE> [--include-videos] [--map-file <PATH>] [--force]"
+    );
+    println!();
+    println!("Description:");
+    println!("  Packs the map file, config.toml and the KRC_ENV_FILE env file into one archive");
+    println!("  (compression from the suffix, e.g. setup.tar.zst). Video paths under the home");
+    println!("  directory are stored as ~/..., so they follow the home directory on import.");
+    println!("  --include-videos also packs every video the map plays, as videos/<name>.");
+}
+
+fn print_import_help() {
+    println!("kitsune-rendercore import-config");
+    println!("Usage:");
+    println!(
+        "  kitsune-rendercore import-config <FILE> [--videos-dir <PATH>] [--map-file <PATH>] [--force] [--allow-missing]"
+    );
+    println!();
+    println!("Description:");
+    println!("  Installs a bundle from export-config. Bundled videos go to --videos-dir");
+    println!("  (default: videos/ next to the map file) and the map points at them; ~ in");
+    println!("  paths resolves to this machine's home. Every video the map refers to must");
+    println!("  exist unless --allow-missing. Files that exist with other contents are only");
+    println!("  replaced with --force, and kept as <file>.bak. Monitors this machine does not");
+    println!("  have are imported and listed.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("krc-bundle-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn v2_doc(path: &Path, monitors: &[(&str, &str)]) -> MapDocument {
+        let mut text = "version = 2\n".to_string();
+        for (monitor, video) in monitors {
+            text.push_str(&format!("\n[monitors.{monitor}]\nvideo = \"{video}\"\n"));
+        }
+        let (doc, issues) = parse_map_document(path, &text);
+        assert!(issues.is_empty(), "{issues:?}");
+        doc
+    }
+
+    fn video_of<'a>(doc: &'a MapDocument, monitor: &str) -> &'a str {
+        doc.entries[monitor].video.as_str()
+    }
+
+    #[test]
+    fn bundled_video_names_stay_unique() {
+        let mut taken = Vec::<(String, String)>::new();
+        for video in ["/a/clip.mp4", "/b/clip.mp4", "/c/clip.mp4", "/a/other.mp4"] {
+            let name = bundled_video_name(video, &taken);
+            taken.push((video.to_string(), name));
+        }
+        let names = taken
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["clip.mp4", "2-clip.mp4", "3-clip.mp4", "other.mp4"]);
+    }
+
+    #[test]
+    fn manifest_names_cannot_leave_the_bundle() {
+        assert_eq!(bundled_name("video-map.toml"), Ok("video-map.toml"));
+        for bad in [
+            "",
+            ".",
+            "..",
+            "../config.toml",
+            "videos/a.mp4",
+            "/etc/passwd",
+        ] {
+            assert!(bundled_name(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn manifest_needs_the_supported_version() {
+        let dir = temp_dir("manifest");
+        assert!(
+            read_manifest(&dir)
+                .unwrap_err()
+                .contains("not an export-config bundle")
+        );
+        fs::write(dir.join(MANIFEST), "map=video-map.toml\n").unwrap();
+        assert!(read_manifest(&dir).unwrap_err().contains("no version"));
+        fs::write(dir.join(MANIFEST), "version=9\n").unwrap();
+        assert!(read_manifest(&dir).unwrap_err().contains("version 9"));
+        fs::write(dir.join(MANIFEST), "version = 1\nmap = video-map.toml\n").unwrap();
+        let manifest = read_manifest(&dir).unwrap();
+        assert_eq!(manifest["map"], "video-map.toml");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn portable_map_packs_each_video_once() {
+        let dir = temp_dir("portable");
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        let first = dir.join("a/clip.mp4");
+        let second = dir.join("b/clip.mp4");
+        fs::write(&first, b"one").unwrap();
+        fs::write(&second, b"two").unwrap();
+        let (first, second) = (first.to_string_lossy(), second.to_string_lossy());
+        let doc = v2_doc(
+            &dir.join("video-map.toml"),
+            &[("DP-1", &first), ("DP-2", &second), ("DP-3", &first)],
+        );
+
+        let (bundled, videos) = portable_map(&doc, true).unwrap();
+        assert_eq!(
+            videos,
+            [
+                (first.to_string(), "clip.mp4".to_string()),
+                (second.to_string(), "2-clip.mp4".to_string()),
+            ]
+        );
+        assert_eq!(video_of(&bundled, "DP-1"), "videos/clip.mp4");
+        assert_eq!(video_of(&bundled, "DP-2"), "videos/2-clip.mp4");
+        assert_eq!(video_of(&bundled, "DP-3"), "videos/clip.mp4");
+
+        fs::remove_file(dir.join("b/clip.mp4")).unwrap();
+        let err = portable_map(&doc, true).unwrap_err();
+        assert!(err.contains("does not exist"), "{err}");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn portable_map_writes_home_paths_with_a_tilde() {
+        let Some(home) = home_dir() else {
+            return;
+        };
+        let home = home.to_string_lossy().trim_end_matches('/').to_string();
+        if home.is_empty() {
+            return;
+        }
+        let under = format!("{home}/Videos/rain.mp4");
+        let beside = format!("{home}-other/rain.mp4");
+        let doc = v2_doc(
+            Path::new("/srv/video-map.toml"),
+            &[
+                ("DP-1", &under),
+                ("DP-2", &beside),
+                ("DP-3", "/srv/loops/snow.mp4"),
+            ],
+        );
+        let (bundled, videos) = portable_map(&doc, false).unwrap();
+        assert!(videos.is_empty());
+        assert_eq!(video_of(&bundled, "DP-1"), "~/Videos/rain.mp4");
+        assert_eq!(video_of(&bundled, "DP-2"), beside);
+        assert_eq!(video_of(&bundled, "DP-3"), "/srv/loops/snow.mp4");
+    }
+
+    #[test]
+    fn install_keeps_the_replaced_file_when_asked() {
+        let dir = temp_dir("install");
+        let target = dir.join("config.toml");
+        install_file(&target, b"first", false).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"first");
+        assert!(!dir.join("config.toml.bak").exists());
+
+        install_file(&target, b"second", true).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"second");
+        assert_eq!(fs::read(dir.join("config.toml.bak")).unwrap(), b"first");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn import_places_videos_and_refuses_conflicts_without_force() {
+        let dir = temp_dir("import");
+        let staging = dir.join("staging");
+        fs::create_dir_all(staging.join(VIDEOS_DIR)).unwrap();
+        fs::write(staging.join(VIDEOS_DIR).join("clip.mp4"), b"video").unwrap();
+        fs::write(
+            staging.join("video-map.toml"),
+            "version = 2\n\n[monitors.DP-1]\nvideo = \"videos/clip.mp4\"\n",
+        )
+        .unwrap();
+        fs::write(staging.join(MANIFEST), "version=1\nmap=video-map.toml\n").unwrap();
+        let map_path = dir.join("target/video-map.toml");
+        let videos_dir = dir.join("target/clips");
+        let options = |force| ImportOptions {
+            videos_dir: Some(videos_dir.clone()),
+            map_file: Some(map_path.clone()),
+            force,
+            allow_missing: false,
+        };
+
+        import_from(&staging, &options(false)).unwrap();
+        assert_eq!(fs::read(videos_dir.join("clip.mp4")).unwrap(), b"video");
+        let contents = fs::read_to_string(&map_path).unwrap();
+        let (doc, issues) = parse_map_document(&map_path, &contents);
+        assert!(issues.is_empty());
+        assert_eq!(
+            Path::new(video_of(&doc, "DP-1")),
+            videos_dir.join("clip.mp4")
+        );
+        // The same bundle again is a no-op, not a conflict.
+        import_from(&staging, &options(false)).unwrap();
+
+        fs::write(&map_path, "version = 2\n").unwrap();
+        let err = import_from(&staging, &options(false)).unwrap_err();
+        assert!(err.contains("--force"), "{err}");
+        assert_eq!(fs::read_to_string(&map_path).unwrap(), "version = 2\n");
+
+        import_from(&staging, &options(true)).unwrap();
+        assert_eq!(fs::read_to_string(&map_path).unwrap(), contents);
+        assert_eq!(
+            fs::read_to_string(dir.join("target/video-map.toml.bak")).unwrap(),
+            "version = 2\n"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hostile_bundles_cannot_place_files_outside_the_videos_dir() {
+        let dir = temp_dir("hostile");
+        let staging = dir.join("staging");
+        fs::create_dir_all(staging.join(VIDEOS_DIR)).unwrap();
+        fs::write(dir.join("secret"), b"secret").unwrap();
+        std::os::unix::fs::symlink(
+            dir.join("secret"),
+            staging.join(VIDEOS_DIR).join("link.mp4"),
+        )
+        .unwrap();
+        fs::write(staging.join(MANIFEST), "version=1\nmap=video-map.toml\n").unwrap();
+        let videos_dir = dir.join("target/clips");
+        let options = ImportOptions {
+            videos_dir: Some(videos_dir.clone()),
+            map_file: Some(dir.join("target/video-map.toml")),
+            force: true,
+            allow_missing: true,
+        };
+
+        for video in ["videos/../../escape.mp4", "videos/link.mp4"] {
+            fs::write(
+                staging.join("video-map.toml"),
+                format!("version = 2\n\n[monitors.DP-1]\nvideo = \"{video}\"\n"),
+            )
+            .unwrap();
+            let err = import_from(&staging, &options).unwrap_err();
+            assert!(err.contains("refusing to import"), "{video}: {err}");
+        }
+        assert!(!dir.join("escape.mp4").exists());
+        assert!(!dir.join("target").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn staging_dirs_are_private_and_unique() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = staging_dir("test").unwrap();
+        let second = staging_dir("test").unwrap();
+        assert_ne!(first, second);
+        let mode = fs::metadata(&first).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        let _ = fs::remove_dir_all(&first);
+        let _ = fs::remove_dir_all(&second);
+    }
+}
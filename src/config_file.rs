@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::file_access::write_atomic;
+use crate::paths::config_dir;
+
+pub fn default_config_file_path() -> Result<PathBuf, String> {
+    Ok(config_dir()?.join("config.toml"))
+}
+
+pub fn config_file_path_from_env() -> Result<PathBuf, String> {
+    match std::env::var("KRC_CONFIG_FILE") {
+        Ok(path) if !path.trim().is_empty() => Ok(PathBuf::from(path)),
+        _ => default_config_file_path(),
+    }
+}
+
+/// The small TOML subset the renderer reads: `[section]` headers and
+/// `key = value` lines, where values are quoted strings, numbers, booleans, or
+/// single-line arrays of strings.
+/// Values are kept as text; each consumer parses the keys it owns.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl ConfigFile {
+    /// Loads the config file; a missing file (or no resolvable path) is an
+    /// empty config.
+    pub fn load_from_env() -> Self {
+        match config_file_path_from_env() {
+            Ok(path) => Self::load(&path),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => parse_config_str(&contents),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Every key of `[section]`, in key order.
+    pub fn section(&self, section: &str) -> Option<&BTreeMap<String, String>> {
+        self.sections.get(section)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .get(section)
+            .and_then(|values| values.get(key))
+            .map(String::as_str)
+    }
+
+    pub fn get_u64(&self, section: &str, key: &str) -> Option<u64> {
+        let raw = self.get(section, key)?;
+        match raw.parse::<u64>() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                eprintln!(
+                    "[rendercore] config [{section}] {key}='{raw}' ignored: expected a number"
+                );
+                None
+            }
+        }
+    }
+
+    /// A list value, written either as `["a", "b"]` or as `"a,b"`. Empty items
+    /// are dropped.
+    pub fn get_list(&self, section: &str, key: &str) -> Option<Vec<String>> {
+        let raw = self.get(section, key)?;
+        let body = match raw.strip_prefix('[') {
+            Some(rest) => match rest.strip_suffix(']') {
+                Some(body) => body,
+                None => {
+                    eprintln!(
+                        "[rendercore] config [{section}] {key}='{raw}' ignored: unterminated list"
+                    );
+                    return None;
+                }
+            },
+            None => raw,
+        };
+        Some(
+            body.split(',')
+                .map(|item| item.trim().trim_matches('"').trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+        )
+    }
+}
+
+/// Sets (`Some`) or removes (`None`) one `key` of `[section]` in the config
+/// file, keeping every other line and comment. The value is written as a
+/// quoted string; a missing section is appended. Returns whether the file
+/// changed.
+pub fn set_config_value(
+    path: &Path,
+    section: &str,
+    key: &str,
+    value: Option<&str>,
+) -> Result<bool, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(format!("failed to read {}: {err}", path.display())),
+    };
+    let line_for = |value: &str| format!("{key} = \"{}\"", quote_value(value));
+    let mut lines = contents.lines().map(str::to_string).collect::<Vec<_>>();
+    let mut current = String::new();
+    // Last non-blank line of the section, where a new key goes.
+    let mut section_end = None::<usize>;
+    let mut found = None::<usize>;
+    for (idx, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix('[')
+            && let Some((name, _)) = name.split_once(']')
+        {
+            current = name.trim().to_string();
+            if current == section {
+                section_end = Some(idx);
+            }
+            continue;
+        }
+        if current != section || trimmed.is_empty() {
+            continue;
+        }
+        section_end = Some(idx);
+        if !trimmed.starts_with('#')
+            && trimmed
+                .split_once('=')
+                .is_some_and(|(line_key, _)| line_key.trim() == key)
+        {
+            found = Some(idx);
+        }
+    }
+    match (found, value) {
+        (Some(idx), Some(value)) => {
+            let line = line_for(value);
+            if lines[idx] == line {
+                return Ok(false);
+            }
+            lines[idx] = line;
+        }
+        (Some(idx), None) => {
+            lines.remove(idx);
+        }
+        (None, Some(value)) => match section_end {
+            Some(idx) => lines.insert(idx + 1, line_for(value)),
+            None => {
+                if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push(format!("[{section}]"));
+                lines.push(line_for(value));
+            }
+        },
+        (None, None) => return Ok(false),
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    write_atomic(path, out.as_bytes(), "config file")?;
+    Ok(true)
+}
+
+/// Escapes a string for a quoted value, the inverse of [`parse_value`].
+fn quote_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+fn parse_config_str(contents: &str) -> ConfigFile {
+    let mut config = ConfigFile::default();
+    let mut section = String::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            match name.split_once(']') {
+                Some((name, rest)) if is_blank_or_comment(rest) => {
+                    section = name.trim().to_string();
+                }
+                _ => eprintln!(
+                    "[rendercore] config line {} ignored: malformed section header",
+                    idx + 1
+                ),
+            }
+            continue;
+        }
+        let Some((key, raw_value)) = line.split_once('=') else {
+            eprintln!(
+                "[rendercore] config line {} ignored: expected key = value",
+                idx + 1
+            );
+            continue;
+        };
+        match parse_value(raw_value.trim()) {
+            Ok(value) => {
+                config
+                    .sections
+                    .entry(section.clone())
+                    .or_default()
+                    .insert(key.trim().to_string(), value);
+            }
+            Err(err) => eprintln!("[rendercore] config line {} ignored: {err}", idx + 1),
+        }
+    }
+    config
+}
+
+/// One value: a quoted string with `\n \t \" \\` escapes, or bare text up
+/// to a trailing comment. Shared with the v2 map parser.
+pub fn parse_value(raw: &str) -> Result<String, String> {
+    let Some(body) = raw.strip_prefix('"') else {
+        // Bare value (number/bool): cut a trailing comment.
+        let value = raw.split('#').next().unwrap_or("").trim();
+        if value.is_empty() {
+            return Err("missing value".to_string());
+        }
+        return Ok(value.to_string());
+    };
+    let mut out = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let rest = chars.as_str();
+                if !is_blank_or_comment(rest) {
+                    return Err(format!("unexpected text after string: {rest}"));
+                }
+                return Ok(out);
+            }
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some(other) => return Err(format!("unsupported escape \\{other}")),
+                None => break,
+            },
+            c => out.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+fn is_blank_or_comment(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || rest.starts_with('#')
+}
@@ -0,0 +1,135 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::command_queue::{CommandSender, RuntimeCommand};
+use crate::instance::instance_id_from_env;
+use crate::json::escape_json;
+use crate::paths::{self, ensure_dir};
+
+/// How long a client may wait for the render loop, which answers within one
+/// iteration unless it is stuck.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn control_socket_path_from_env() -> PathBuf {
+    std::env::var("KRC_CONTROL_SOCKET")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| paths::control_socket(&instance_id_from_env()))
+}
+
+/// Line-oriented control socket: one request line per connection, one reply,
+/// then the connection is closed. Clients are served on a thread of their
+/// own, which queues each request for the render loop and writes back its
+/// reply, so a slow client never holds up a frame.
+pub struct ControlServer {
+    listener: Option<UnixListener>,
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    pub fn bind(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            ensure_dir(parent)?;
+        }
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(format!(
+                    "another renderer is already serving {}",
+                    path.display()
+                ));
+            }
+            // Left behind by a renderer that did not exit cleanly.
+            let _ = std::fs::remove_file(path);
+        }
+        let listener = UnixListener::bind(path)
+            .map_err(|e| format!("failed to bind {}: {e}", path.display()))?;
+        Ok(Self {
+            listener: Some(listener),
+            path: path.to_path_buf(),
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Starts answering clients, queueing their requests on `commands`.
+    pub fn serve(&mut self, commands: CommandSender) -> Result<(), String> {
+        let Some(listener) = self.listener.take() else {
+            return Ok(());
+        };
+        let stop = self.stop.clone();
+        let thread = thread::Builder::new()
+            .name("krc-control".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    match stream {
+                        Ok(stream) => serve_client(&stream, &commands),
+                        Err(err) => eprintln!("[rendercore] control socket accept failed: {err}"),
+                    }
+                }
+            })
+            .map_err(|e| format!("failed to start the control socket thread: {e}"))?;
+        self.thread = Some(thread);
+        Ok(())
+    }
+}
+
+fn serve_client(stream: &UnixStream, commands: &CommandSender) {
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(200)));
+    let _ = stream.set_write_timeout(Some(Duration::from_millis(200)));
+    let mut line = String::new();
+    if BufReader::new(stream).read_line(&mut line).is_err() {
+        return;
+    }
+    // Malformed requests are answered here, without bothering the loop.
+    let reply = RuntimeCommand::parse(&line)
+        .and_then(|command| commands.request(command, REPLY_TIMEOUT))
+        .unwrap_or_else(|err| format!("{{\"error\":\"{}\"}}", escape_json(&err)));
+    let mut writer = stream;
+    let _ = writer.write_all(reply.as_bytes());
+    let _ = writer.write_all(b"\n");
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            // Wakes the blocking accept so the thread sees `stop`.
+            let _ = UnixStream::connect(&self.path);
+            let _ = thread.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Sends one request to a running renderer and returns its reply.
+pub fn request(command: &str) -> Result<String, String> {
+    let path = control_socket_path_from_env();
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("renderer not reachable at {}: {e}", path.display()))?;
+    let timeout = Some(Duration::from_secs(2));
+    let _ = stream.set_read_timeout(timeout);
+    let _ = stream.set_write_timeout(timeout);
+    stream
+        .write_all(format!("{command}\n").as_bytes())
+        .map_err(|e| format!("failed to send control request: {e}"))?;
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .map_err(|e| format!("failed to read control reply: {e}"))?;
+    Ok(reply.trim().to_string())
+}
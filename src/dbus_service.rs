@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use zbus::blocking::Connection;
+use zbus::blocking::connection::Builder;
+use zbus::fdo;
+use zbus::zvariant::Value as Variant;
+
+use crate::command_queue::{CommandSender, RuntimeCommand};
+use crate::json::{JsonValue, parse_json};
+
+const INTERFACE: &str = "org.kitsune.RenderCore1";
+const OBJECT_PATH: &str = "/org/kitsune/RenderCore1";
+
+/// `org.kitsune.RenderCore1` on the session bus. It is a second transport for
+/// the control socket: every method becomes a [`RuntimeCommand`] on the
+/// render loop's queue, answered through the same dispatcher. zbus runs the
+/// bus I/O on its own thread.
+pub struct DbusService {
+    connection: Connection,
+    name: String,
+    paused: Arc<AtomicBool>,
+}
+
+impl DbusService {
+    /// Owns the bus name for `instance` and exports the interface. Fails when
+    /// there is no session bus or the name is already owned.
+    pub fn start(instance: &str, commands: CommandSender) -> Result<Self, String> {
+        let name = bus_name(instance);
+        let paused = Arc::new(AtomicBool::new(false));
+        let iface = RenderCoreInterface {
+            commands,
+            paused: paused.clone(),
+            instance: instance.to_string(),
+        };
+        let connection = Builder::session()
+            .and_then(|b| b.name(name.as_str()))
+            .and_then(|b| b.serve_at(OBJECT_PATH, iface))
+            .and_then(Builder::build)
+            .map_err(|e| format!("cannot own {name} on the session bus: {e}"))?;
+        Ok(Self {
+            connection,
+            name,
+            paused,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Updates `Paused` and emits `PropertiesChanged` when it flipped.
+    pub fn set_paused(&self, paused: bool) {
+        if self.paused.swap(paused, Ordering::Relaxed) == paused {
+            return;
+        }
+        let changed = HashMap::from([("Paused", Variant::from(paused))]);
+        let invalidated: &[&str] = &[];
+        if let Err(err) = self.connection.emit_signal(
+            None::<&str>,
+            OBJECT_PATH,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+            &(INTERFACE, changed, invalidated),
+        ) {
+            eprintln!("[rendercore] dbus: failed to signal Paused={paused}: {err}");
+        }
+    }
+}
+
+impl Drop for DbusService {
+    fn drop(&mut self) {
+        match self.connection.release_name(self.name.as_str()) {
+            Ok(_) => println!("[rendercore] dbus: released {}", self.name),
+            Err(err) => eprintln!("[rendercore] dbus: failed to release {}: {err}", self.name),
+        }
+    }
+}
+
+/// `org.kitsune.RenderCore1` for the default instance, else
+/// `org.kitsune.RenderCore1.<id>` with the id reduced to what a bus name
+/// element may hold.
+pub fn bus_name(instance: &str) -> String {
+    if instance == "default" {
+        return INTERFACE.to_string();
+    }
+    let mut element = instance
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if element.starts_with(|c: char| c.is_ascii_digit()) {
+        element.insert(0, '_');
+    }
+    format!("{INTERFACE}.{element}")
+}
+
+struct RenderCoreInterface {
+    commands: CommandSender,
+    paused: Arc<AtomicBool>,
+    instance: String,
+}
+
+impl RenderCoreInterface {
+    /// Queues `command` for the render loop and waits for its reply; a reply
+    /// carrying `"error"` becomes a D-Bus error.
+    async fn call(&self, command: RuntimeCommand) -> fdo::Result<String> {
+        let (reply, receiver) = async_channel::bounded(1);
+        self.commands
+            .send(
+                command,
+                Some(Box::new(move |text| {
+                    let _ = reply.try_send(text);
+                })),
+            )
+            .map_err(fdo::Error::Failed)?;
+        let reply = receiver
+            .recv()
+            .await
+            .map_err(|_| fdo::Error::Failed("renderer is shutting down".to_string()))?;
+        if let Ok(value) = parse_json(&reply)
+            && let Some(err) = value.get("error").and_then(JsonValue::as_str)
+        {
+            return Err(fdo::Error::Failed(err.to_string()));
+        }
+        Ok(reply)
+    }
+}
+
+/// Reasons end up in one-line logs and journal entries.
+fn one_line(raw: &str) -> String {
+    raw.replace(['\n', '\r', '\t'], " ")
+}
+
+#[zbus::interface(name = "org.kitsune.RenderCore1")]
+impl RenderCoreInterface {
+    /// Same as `kitsune-rendercore pause`; `reason` is logged and journaled.
+    async fn pause(&self, reason: String) -> fdo::Result<()> {
+        self.call(RuntimeCommand::Pause {
+            reason: one_line(&reason).trim().to_string(),
+        })
+        .await
+        .map(drop)
+    }
+
+    /// Same as `kitsune-rendercore resume`.
+    async fn resume(&self) -> fdo::Result<()> {
+        self.call(RuntimeCommand::Resume).await.map(drop)
+    }
+
+    /// Same as `kitsune-rendercore pause --monitor`.
+    async fn pause_output(&self, monitor: String) -> fdo::Result<()> {
+        self.call(RuntimeCommand::PauseOutput {
+            monitor: monitor.trim().to_string(),
+            paused: true,
+        })
+        .await
+        .map(drop)
+    }
+
+    /// Same as `kitsune-rendercore resume --monitor`.
+    async fn resume_output(&self, monitor: String) -> fdo::Result<()> {
+        self.call(RuntimeCommand::PauseOutput {
+            monitor: monitor.trim().to_string(),
+            paused: false,
+        })
+        .await
+        .map(drop)
+    }
+
+    /// Same as `kitsune-rendercore reload`; returns its JSON report.
+    async fn reload_map(&self) -> fdo::Result<String> {
+        self.call(RuntimeCommand::Reload { hard: false }).await
+    }
+
+    /// Maps `monitor` to the absolute `path`. `transient` keeps it in memory
+    /// until restart instead of writing the map file; an empty `path` with
+    /// `transient` drops the override.
+    async fn set_video(
+        &self,
+        monitor: String,
+        path: String,
+        transient: bool,
+    ) -> fdo::Result<String> {
+        self.call(RuntimeCommand::SetVideo {
+            transient,
+            monitor: monitor.trim().to_string(),
+            video: path.trim().to_string(),
+        })
+        .await
+    }
+
+    /// The live status JSON `kitsune-rendercore status` reads.
+    async fn get_status(&self) -> fdo::Result<String> {
+        self.call(RuntimeCommand::Status).await
+    }
+
+    #[zbus(property)]
+    async fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// The renderer instance (`KRC_INSTANCE`) this service belongs to.
+    #[zbus(property)]
+    async fn active_profile(&self) -> String {
+        self.instance.clone()
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::proc_stat::ProcStat;
+
+/// How a decoder process is doing, read from `/proc/<pid>/stat` and `statm`
+/// for the live status.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecoderProcess {
+    pub pid: u32,
+    /// CPU use since the previous status request (100 = one core busy);
+    /// since the process started on the first one.
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub uptime: Duration,
+}
+
+/// The fields of `/proc/<pid>/stat` this module needs, in clock ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StatFields {
+    /// `utime + stime`.
+    cpu_ticks: u64,
+    /// `starttime`, since boot.
+    start_ticks: u64,
+}
+
+impl From<ProcStat> for StatFields {
+    fn from(stat: ProcStat) -> Self {
+        Self {
+            cpu_ticks: stat.cpu_ticks,
+            start_ticks: stat.start_ticks,
+        }
+    }
+}
+
+/// Resident pages, the second field of `/proc/<pid>/statm`.
+pub fn parse_statm(raw: &str) -> Option<u64> {
+    raw.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Seconds since boot, the first field of `/proc/uptime`.
+pub fn parse_uptime(raw: &str) -> Option<f64> {
+    raw.split_whitespace().next()?.parse().ok()
+}
+
+fn clock_ticks_per_sec() -> f64 {
+    // SAFETY: sysconf(3) only reads a configuration value.
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as f64,
+        _ => 100.0,
+    }
+}
+
+fn page_size() -> u64 {
+    // SAFETY: as above.
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as u64,
+        _ => 4096,
+    }
+}
+
+struct Sample {
+    at: Instant,
+    fields: StatFields,
+}
+
+/// Keeps each decoder's previous sample, so CPU use is a delta over the time
+/// between two status requests rather than an average over its whole life.
+#[derive(Default)]
+pub struct ProcessSampler {
+    previous: HashMap<u32, Sample>,
+}
+
+impl ProcessSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Samples `pid`; `None` once the process has exited (a decoder that
+    /// restarted between two requests), which status simply leaves out.
+    pub fn sample(&mut self, pid: u32, now: Instant) -> Option<DecoderProcess> {
+        let statm = std::fs::read_to_string(format!("/proc/{pid}/statm")).ok();
+        let Some((fields, rss_pages)) = ProcStat::of_pid(pid)
+            .map(StatFields::from)
+            .zip(statm.as_deref().and_then(parse_statm))
+        else {
+            self.previous.remove(&pid);
+            return None;
+        };
+        let ticks = clock_ticks_per_sec();
+        let boot_secs = std::fs::read_to_string("/proc/uptime")
+            .ok()
+            .as_deref()
+            .and_then(parse_uptime)?;
+        let uptime_secs = (boot_secs - fields.start_ticks as f64 / ticks).max(0.0);
+        let previous = self.previous.insert(pid, Sample { at: now, fields });
+        Some(DecoderProcess {
+            pid,
+            cpu_percent: cpu_percent(previous.as_ref(), fields, now, uptime_secs, ticks),
+            rss_bytes: rss_pages * page_size(),
+            uptime: Duration::from_secs_f64(uptime_secs),
+        })
+    }
+
+    /// Drops the samples of processes no output runs anymore.
+    pub fn retain(&mut self, pids: &[u32]) {
+        self.previous.retain(|pid, _| pids.contains(pid));
+    }
+}
+
+/// CPU use between `previous` and now; over the process's life without a
+/// previous sample, or when the pid now belongs to another process.
+fn cpu_percent(
+    previous: Option<&Sample>,
+    fields: StatFields,
+    now: Instant,
+    uptime_secs: f64,
+    ticks_per_sec: f64,
+) -> f64 {
+    let (ticks, secs) = match previous {
+        Some(prev)
+            if prev.fields.start_ticks == fields.start_ticks
+                && now > prev.at
+                && fields.cpu_ticks >= prev.fields.cpu_ticks =>
+        {
+            (
+                fields.cpu_ticks - prev.fields.cpu_ticks,
+                now.duration_since(prev.at).as_secs_f64(),
+            )
+        }
+        _ => (fields.cpu_ticks, uptime_secs),
+    };
+    if secs <= 0.0 {
+        return 0.0;
+    }
+    ticks as f64 / ticks_per_sec / secs * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(cpu_ticks: u64, start_ticks: u64) -> StatFields {
+        StatFields {
+            cpu_ticks,
+            start_ticks,
+        }
+    }
+
+    #[test]
+    fn statm_and_uptime_fields() {
+        assert_eq!(parse_statm("76288 20480 1536 40 0 30000 0\n"), Some(20480));
+        assert_eq!(parse_statm("76288"), None);
+        assert_eq!(parse_uptime("12345.67 45678.90\n"), Some(12345.67));
+        assert_eq!(parse_uptime(""), None);
+    }
+
+    #[test]
+    fn sysconf_values_are_sane() {
+        let ticks = clock_ticks_per_sec();
+        assert!((1.0..=10_000.0).contains(&ticks), "{ticks}");
+        assert!(page_size().is_power_of_two());
+        assert!(page_size() >= 4096);
+    }
+
+    #[test]
+    fn first_sample_averages_over_the_process_life() {
+        // 50 s of CPU over 100 s of life at 100 ticks a second.
+        let now = Instant::now();
+        let cpu = cpu_percent(None, fields(5000, 0), now, 100.0, 100.0);
+        assert!((cpu - 50.0).abs() < 1e-9);
+        assert_eq!(cpu_percent(None, fields(5000, 0), now, 0.0, 100.0), 0.0);
+    }
+
+    #[test]
+    fn later_samples_use_the_delta() {
+        let t0 = Instant::now();
+        let prev = Sample {
+            at: t0,
+            fields: fields(1000, 42),
+        };
+        // Two cores busy for the two seconds between requests.
+        let now = t0 + Duration::from_secs(2);
+        let cpu = cpu_percent(Some(&prev), fields(1400, 42), now, 500.0, 100.0);
+        assert!((cpu - 200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn a_reused_pid_starts_over() {
+        let t0 = Instant::now();
+        let prev = Sample {
+            at: t0,
+            fields: fields(90_000, 42),
+        };
+        let now = t0 + Duration::from_secs(2);
+        // Another start time: the life average of the new process.
+        let cpu = cpu_percent(Some(&prev), fields(100, 77), now, 10.0, 100.0);
+        assert!((cpu - 10.0).abs() < 1e-9);
+        // Fewer ticks than before cannot be the same process either.
+        let cpu = cpu_percent(Some(&prev), fields(100, 42), now, 10.0, 100.0);
+        assert!((cpu - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn samples_this_process_and_forgets_gone_ones() {
+        let pid = std::process::id();
+        let mut sampler = ProcessSampler::new();
+        let first = sampler.sample(pid, Instant::now()).unwrap();
+        assert_eq!(first.pid, pid);
+        assert!(first.rss_bytes > 0);
+        assert!(first.cpu_percent >= 0.0);
+        assert!(sampler.previous.contains_key(&pid));
+
+        sampler.retain(&[]);
+        assert!(sampler.previous.is_empty());
+        assert_eq!(sampler.sample(u32::MAX, Instant::now()), None);
+    }
+}
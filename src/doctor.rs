@@ -0,0 +1,287 @@
+use std::path::{Path, PathBuf};
+
+use crate::backend::gpu_report;
+use crate::bench::configured_video;
+use crate::config;
+use crate::config_file::config_file_path_from_env;
+use crate::control;
+use crate::env_file;
+use crate::file_access;
+use crate::hw_decode::{HwAccel, HwDecode, probe};
+use crate::json::{JsonValue, parse_json};
+use crate::paths::{config_dir, runtime_dir, state_dir};
+use crate::tools::{Tool, hyprland_ipc_socket};
+use crate::user_path::{display_path_short, display_text};
+use crate::version::{self, NO_VIDEO_SUPPORT};
+use crate::video_map::map_file_path_from_env;
+
+/// Why the renderer looks sandboxed, if it does. Inside a sandbox only the
+/// Wayland socket, ffmpeg and the XDG dirs are expected; host tools such as
+/// `systemctl` or `hyprctl` being absent is normal there.
+pub fn detect_sandbox() -> Option<String> {
+    if Path::new("/.flatpak-info").exists() {
+        return Some("flatpak".to_string());
+    }
+    if std::env::var_os("SNAP").is_some() {
+        return Some("snap".to_string());
+    }
+    if let Ok(kind) = std::env::var("container")
+        && !kind.trim().is_empty()
+    {
+        return Some(format!("container ({})", kind.trim()));
+    }
+    if std::env::var_os("WAYLAND_SOCKET").is_some() {
+        return Some("pre-connected Wayland socket (WAYLAND_SOCKET)".to_string());
+    }
+    None
+}
+
+/// `doctor`: checks what this renderer needs from its environment and exits
+/// non-zero when something required is missing.
+pub fn run_doctor() -> Result<(), String> {
+    let sandbox = detect_sandbox();
+    let mut problems = 0usize;
+    match &sandbox {
+        Some(kind) => println!("[info] sandbox: {kind}; host tools are optional"),
+        None => println!("[info] sandbox: none detected"),
+    }
+    println!(
+        "[info] build: {} backend, video={}",
+        if cfg!(feature = "wayland-layer") {
+            "wayland-layer"
+        } else {
+            "stub"
+        },
+        version::video_support()
+    );
+
+    match wayland_endpoint() {
+        Ok(endpoint) => println!("[ok] wayland: {endpoint}"),
+        Err(err) => {
+            println!("[fail] wayland: {err}");
+            problems += 1;
+        }
+    }
+
+    for tool in Tool::ALL {
+        if !tool.used() {
+            println!("[skip] {}: not used, {NO_VIDEO_SUPPORT}", tool.name());
+            continue;
+        }
+        match tool.locate() {
+            Some((path, source)) => {
+                println!("[ok] {}: {} (from {source})", tool.name(), path.display())
+            }
+            None if tool.required() => {
+                println!(
+                    "[fail] {}: not found; needed for {} (set {} or [tools] {})",
+                    tool.name(),
+                    tool.purpose(),
+                    tool.env_key(),
+                    tool.name()
+                );
+                problems += 1;
+            }
+            None if sandbox.is_some() => println!(
+                "[skip] {}: not available in the sandbox; affects {}",
+                tool.name(),
+                tool.purpose()
+            ),
+            None => println!(
+                "[warn] {}: not found; affects {} (set {} to point at it)",
+                tool.name(),
+                tool.purpose(),
+                tool.env_key()
+            ),
+        }
+    }
+
+    if !check_renderer_ffmpeg() {
+        problems += 1;
+    }
+    check_video_settings();
+    check_hwaccel();
+
+    match gpu_report() {
+        Ok(lines) => lines.iter().for_each(|line| println!("{line}")),
+        Err(err) => println!("[skip] gpu: {err}"),
+    }
+
+    match hyprland_ipc_socket() {
+        Some(socket) => println!("[ok] hyprland ipc: {}", socket.display()),
+        None => println!("[info] hyprland ipc: not reachable; monitor names come from Wayland"),
+    }
+
+    for (label, dir) in [
+        ("config dir", config_dir()),
+        ("state dir", state_dir()),
+        ("runtime dir", Ok(runtime_dir())),
+    ] {
+        match dir.and_then(|dir| check_writable(&dir).map(|()| dir)) {
+            Ok(dir) => println!("[ok] {label}: {} (writable)", dir.display()),
+            Err(err) => {
+                println!("[fail] {label}: {err}");
+                problems += 1;
+            }
+        }
+    }
+
+    // A CLI run once with sudo leaves these owned by root: set-video then
+    // fails and the renderer cannot reload them.
+    for (label, path) in [
+        ("map file", map_file_path_from_env()),
+        ("config file", config_file_path_from_env()),
+    ] {
+        let Ok(path) = path else {
+            continue;
+        };
+        if !path.exists() {
+            continue;
+        }
+        match file_access::startup_warning(&path, label) {
+            Some(warning) => {
+                println!("[fail] {warning}");
+                problems += 1;
+            }
+            None => println!("[ok] {label}: {} (readable, writable)", path.display()),
+        }
+    }
+
+    if problems == 0 {
+        println!("[ok] doctor found no problems");
+        Ok(())
+    } else {
+        Err(format!("doctor found {problems} problem(s)"))
+    }
+}
+
+/// Outputs of the running renderer whose decoder waits for ffmpeg. The
+/// service may run with another `$PATH` or environment than this shell, so
+/// it can be missing there while found here. False when it is.
+fn check_renderer_ffmpeg() -> bool {
+    let Some(status) = control::request("status")
+        .ok()
+        .and_then(|raw| parse_json(&raw).ok())
+    else {
+        return true;
+    };
+    let Some(JsonValue::Array(outputs)) = status.get("outputs") else {
+        return true;
+    };
+    let waiting = outputs
+        .iter()
+        .filter(|out| out.get("degraded").and_then(JsonValue::as_str) == Some("ffmpeg-not-found"))
+        .filter_map(|out| out.get("name").and_then(JsonValue::as_str))
+        .collect::<Vec<_>>();
+    if waiting.is_empty() {
+        return true;
+    }
+    let outputs = waiting.join(", ");
+    match Tool::Ffmpeg.locate() {
+        Some((path, _)) => {
+            println!(
+                "[fail] ffmpeg: the running renderer cannot find it ({outputs}) but {} is here; \
+                 the service runs with another PATH or environment: set [tools] ffmpeg = \"{}\" \
+                 in config.toml (picked up within 30s, no restart needed)",
+                path.display(),
+                path.display()
+            );
+            false
+        }
+        None => {
+            println!(
+                "[info] ffmpeg: the running renderer waits for it ({outputs}) and looks again every 30s; \
+                 no restart needed once it is installed"
+            );
+            true
+        }
+    }
+}
+
+/// `KRC_VIDEO_FPS` and `KRC_VIDEO_SPEED` as the renderer will use them. Out
+/// of range values are clamped, not fatal, so they only warn.
+fn check_video_settings() {
+    let fps = config::video_fps(env_file::var("KRC_VIDEO_FPS").as_deref());
+    let speed = config::video_speed(env_file::var("KRC_VIDEO_SPEED").as_deref());
+    for warning in [&fps.warning, &speed.warning].into_iter().flatten() {
+        println!("[warn] video: {warning}");
+    }
+    println!(
+        "[ok] video: decoding at {}fps, speed {}",
+        fps.value, speed.value
+    );
+}
+
+/// Decodes the first frames of the configured video with `KRC_HWACCEL` and
+/// tells whether the GPU really did the work. Never counted as a problem:
+/// software decode works, it just costs CPU.
+fn check_hwaccel() {
+    let hwaccel = HwAccel::from_env();
+    if hwaccel == HwAccel::None {
+        println!("[info] hwaccel: KRC_HWACCEL=none, decoding in software");
+        return;
+    }
+    if !Tool::Ffmpeg.used() {
+        println!("[skip] hwaccel: {NO_VIDEO_SUPPORT}");
+        return;
+    }
+    if Tool::Ffmpeg.locate().is_none() {
+        println!("[skip] hwaccel: ffmpeg not found");
+        return;
+    }
+    let Some(video) = configured_video() else {
+        println!("[skip] hwaccel: no video configured to probe");
+        return;
+    };
+    let shown = display_path_short(&video);
+    match probe(&video, hwaccel) {
+        HwDecode::Hardware(kind) => println!(
+            "[ok] hwaccel: KRC_HWACCEL={} decodes {shown} on the GPU ({kind})",
+            hwaccel.as_str()
+        ),
+        HwDecode::Fallback(message) => println!(
+            "[warn] hwaccel: KRC_HWACCEL={} fell back to software for {shown}: {}",
+            hwaccel.as_str(),
+            display_text(&message)
+        ),
+        other => println!(
+            "[warn] hwaccel: could not tell for {shown}: {}",
+            display_text(other.detail())
+        ),
+    }
+}
+
+fn wayland_endpoint() -> Result<String, String> {
+    if let Ok(fd) = std::env::var("WAYLAND_SOCKET") {
+        return match fd.trim().parse::<i32>() {
+            Ok(fd) if Path::new(&format!("/proc/self/fd/{fd}")).exists() => {
+                Ok(format!("WAYLAND_SOCKET fd {fd}"))
+            }
+            _ => Err(format!("WAYLAND_SOCKET='{fd}' is not an open fd")),
+        };
+    }
+    let display = std::env::var("WAYLAND_DISPLAY")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| "wayland-0".to_string());
+    let socket = if Path::new(&display).is_absolute() {
+        PathBuf::from(&display)
+    } else {
+        let runtime = std::env::var("XDG_RUNTIME_DIR")
+            .map_err(|_| "XDG_RUNTIME_DIR is not set".to_string())?;
+        PathBuf::from(runtime).join(&display)
+    };
+    if socket.exists() {
+        Ok(socket.display().to_string())
+    } else {
+        Err(format!("no socket at {}", socket.display()))
+    }
+}
+
+fn check_writable(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {e}", dir.display()))?;
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    std::fs::write(&probe, b"").map_err(|e| format!("{} is not writable: {e}", dir.display()))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
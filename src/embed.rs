@@ -0,0 +1,168 @@
+//! Running the renderer inside another program. [`RenderCoreBuilder`] sets
+//! it up with a config, optionally a custom [`LayerBackend`] and an event
+//! callback; [`RenderCore::run`] then bootstraps and renders on the calling
+//! thread until [`RenderHandle::shutdown`].
+//!
+//! The handle goes through the same command queue as the control socket and
+//! D-Bus, so its calls are applied between frames in the order they were
+//! made, from any thread. It can be used before `run` starts: commands wait in
+//! the queue while the renderer bootstraps.
+//!
+//! An embedded renderer leaves the process to its host: it installs no signal
+//! handlers, sends nothing to systemd, takes no D-Bus name and no instance
+//! lock, and serves the control socket and the HTTP endpoint only when asked
+//! to. Hooks, `KRC_RECORD` and `KRC_EVENT_LOG` are set up by the binary and
+//! stay off.
+
+use std::time::Duration;
+
+use crate::backend::LayerBackend;
+use crate::command_queue::{CommandSender, RuntimeCommand};
+use crate::config::RenderCoreConfig;
+use crate::events::RenderEvent;
+#[cfg(feature = "http-control")]
+use crate::http_control::HttpConfig;
+use crate::json::{JsonValue, parse_json};
+use crate::runtime::RenderRuntime;
+
+/// How long a handle call waits for its reply. Generous: a call made while
+/// the renderer bootstraps is answered once the loop starts.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct RenderCoreBuilder {
+    config: RenderCoreConfig,
+    backend: Option<Box<dyn LayerBackend>>,
+    on_event: Option<Box<dyn FnMut(RenderEvent)>>,
+    control_socket: bool,
+    #[cfg(feature = "http-control")]
+    http: Option<HttpConfig>,
+}
+
+impl RenderCoreBuilder {
+    pub fn new(config: RenderCoreConfig) -> Self {
+        Self {
+            config,
+            backend: None,
+            on_event: None,
+            control_socket: false,
+            #[cfg(feature = "http-control")]
+            http: None,
+        }
+    }
+
+    /// Renders through `backend` instead of the built-in one (Wayland layer
+    /// surfaces, or the stub without the `wayland-layer` feature).
+    pub fn backend(mut self, backend: Box<dyn LayerBackend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Called with every stream event, on the render thread between frames:
+    /// it should return quickly and hand longer work elsewhere.
+    pub fn on_event(mut self, callback: impl FnMut(RenderEvent) + 'static) -> Self {
+        self.on_event = Some(Box::new(callback));
+        self
+    }
+
+    /// Also serves the instance's control socket, so `kitsune-rendercore
+    /// status` and friends reach the embedded renderer. Off by default.
+    pub fn control_socket(mut self, serve: bool) -> Self {
+        self.control_socket = serve;
+        self
+    }
+
+    /// Also serves the HTTP control endpoint on `config.listen`; the
+    /// config file's `[http]` is not read. Off by default.
+    #[cfg(feature = "http-control")]
+    pub fn http_control(mut self, config: HttpConfig) -> Self {
+        self.http = Some(config);
+        self
+    }
+
+    pub fn build(self) -> (RenderCore, RenderHandle) {
+        let mut runtime = match self.backend {
+            Some(backend) => RenderRuntime::with_backend(self.config, backend),
+            None => RenderRuntime::new(self.config),
+        };
+        runtime.set_embedded(self.control_socket);
+        #[cfg(feature = "http-control")]
+        if let Some(config) = self.http {
+            runtime.set_http(config);
+        }
+        if let Some(callback) = self.on_event {
+            runtime.set_event_sink(callback);
+        }
+        let handle = RenderHandle {
+            sender: runtime.command_sender(),
+        };
+        (RenderCore { runtime }, handle)
+    }
+}
+
+/// The renderer, run on the thread that built it.
+pub struct RenderCore {
+    runtime: RenderRuntime,
+}
+
+impl RenderCore {
+    /// Bootstraps and renders until a handle shuts it down, `max_frames` is
+    /// reached or quiet hours begin (clear `quiet_hours` in the config to run
+    /// through them). Bootstrap errors are returned as they are.
+    pub fn run(mut self) -> Result<(), String> {
+        self.runtime.bootstrap()?;
+        self.runtime.run()?;
+        Ok(())
+    }
+}
+
+/// Controls a [`RenderCore`] from any thread; cheap to clone. Each call waits
+/// until the renderer applied it and returns its error, if any.
+#[derive(Clone)]
+pub struct RenderHandle {
+    sender: CommandSender,
+}
+
+impl RenderHandle {
+    /// Pauses every output, like `kitsune-rendercore pause`; `reason` is
+    /// logged.
+    pub fn pause(&self, reason: &str) -> Result<(), String> {
+        self.request(RuntimeCommand::Pause {
+            reason: reason.to_string(),
+        })
+    }
+
+    /// Lifts [`pause`](Self::pause). Other pause reasons (a Steam game, heat)
+    /// still apply.
+    pub fn resume(&self) -> Result<(), String> {
+        self.request(RuntimeCommand::Resume)
+    }
+
+    /// Shows `video` (an absolute path, `layout=...` or `path#still`) on
+    /// `monitor` until the renderer stops; the map file is not touched. An
+    /// empty `video` drops the override.
+    pub fn set_video(&self, monitor: &str, video: &str) -> Result<(), String> {
+        self.request(RuntimeCommand::SetVideo {
+            transient: true,
+            monitor: monitor.to_string(),
+            video: video.to_string(),
+        })
+    }
+
+    /// Ends [`RenderCore::run`] after the current frame. A renderer that
+    /// already stopped is not an error.
+    pub fn shutdown(&self) -> Result<(), String> {
+        match self.request(RuntimeCommand::Quit) {
+            Err(err) if err == "renderer is shutting down" => Ok(()),
+            result => result,
+        }
+    }
+
+    fn request(&self, command: RuntimeCommand) -> Result<(), String> {
+        let reply = self.sender.request(command, REPLY_TIMEOUT)?;
+        let value = parse_json(&reply).map_err(|e| format!("unreadable renderer reply: {e}"))?;
+        match value.get("error").and_then(JsonValue::as_str) {
+            Some(err) => Err(err.to_string()),
+            None => Ok(()),
+        }
+    }
+}
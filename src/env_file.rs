@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// Keys the renderer re-reads when `KRC_ENV_FILE` changes. Everything else in
+/// the file only takes effect on restart.
+const RELOADABLE_KEYS: &[&str] = &[
+    "KRC_VIDEO_MAP",
+    "KRC_VIDEO_DEFAULT",
+    "KRC_VIDEO",
+    "KRC_VIDEO_FPS",
+    "KRC_VIDEO_SPEED",
+    "KRC_HWACCEL",
+];
+
+/// Values from the last successful read of the env file, layered over the
+/// process environment. `None` means "no file", i.e. process-start values.
+static OVERLAY: RwLock<Option<BTreeMap<String, String>>> = RwLock::new(None);
+
+/// Reads a reloadable setting: env-file value first, then the process environment.
+pub fn var(key: &str) -> Option<String> {
+    if let Ok(overlay) = OVERLAY.read()
+        && let Some(value) = overlay.as_ref().and_then(|map| map.get(key))
+    {
+        return Some(value.clone());
+    }
+    std::env::var(key).ok()
+}
+
+/// Reads a reloadable setting with several names (`KRC_VIDEO_DEFAULT`, then
+/// `KRC_VIDEO`) source by source: the first name set in the env file, else
+/// the first set in the process environment. A value written to the file
+/// thus replaces the process-start one whichever of the names either uses.
+pub fn first_var(keys: &[&str]) -> Option<String> {
+    let overlay = OVERLAY.read().ok();
+    first_of(keys, overlay.as_ref().and_then(|o| o.as_ref()), |key| {
+        std::env::var(key).ok()
+    })
+}
+
+fn first_of(
+    keys: &[&str],
+    file: Option<&BTreeMap<String, String>>,
+    process: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    file.and_then(|file| keys.iter().find_map(|key| file.get(*key).cloned()))
+        .or_else(|| keys.iter().find_map(|key| process(key)))
+}
+
+/// Polls `KRC_ENV_FILE` by mtime, mirroring the map-file reload.
+pub struct EnvFileWatcher {
+    path: PathBuf,
+    last_mtime: Option<SystemTime>,
+}
+
+impl EnvFileWatcher {
+    /// Returns `None` when `KRC_ENV_FILE` is unset. Loads the file once (quietly,
+    /// so `status --json` stays clean) before the first map resolution.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("KRC_ENV_FILE")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from)?;
+        let mut watcher = Self {
+            path,
+            last_mtime: None,
+        };
+        watcher.last_mtime = watcher.current_mtime();
+        watcher.reload();
+        Some(watcher)
+    }
+
+    /// Returns true when the file changed (or disappeared) since the last poll.
+    #[cfg(feature = "wayland-layer")]
+    pub fn poll(&mut self) -> bool {
+        let mtime = self.current_mtime();
+        if mtime == self.last_mtime {
+            return false;
+        }
+        self.last_mtime = mtime;
+        match self.reload() {
+            Some(keys) => println!(
+                "[rendercore] env file reloaded: {} ({keys} reloadable keys)",
+                self.path.display()
+            ),
+            None => println!(
+                "[rendercore] env file {} removed, reverting to process-start environment",
+                self.path.display()
+            ),
+        }
+        true
+    }
+
+    /// Re-reads the file even if its mtime did not change (`reload` command).
+    #[cfg(feature = "wayland-layer")]
+    pub fn force_reload(&mut self) {
+        self.last_mtime = None;
+        self.poll();
+    }
+
+    #[cfg(feature = "wayland-layer")]
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    fn current_mtime(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .ok()
+            .and_then(|m| m.modified().ok())
+    }
+
+    /// Swaps the overlay; returns the number of keys read, or `None` if the file is gone.
+    fn reload(&self) -> Option<usize> {
+        let overlay = std::fs::read_to_string(&self.path)
+            .ok()
+            .map(|contents| parse_env_file(&contents));
+        let keys = overlay.as_ref().map(BTreeMap::len);
+        if let Ok(mut slot) = OVERLAY.write() {
+            *slot = overlay;
+        }
+        keys
+    }
+}
+
+/// Parses systemd-style `KEY=VALUE` lines, keeping only reloadable keys.
+fn parse_env_file(contents: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            eprintln!(
+                "[rendercore] env file line {} ignored: expected KEY=VALUE",
+                idx + 1
+            );
+            continue;
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            eprintln!(
+                "[rendercore] env file line {} ignored: invalid key '{key}'",
+                idx + 1
+            );
+            continue;
+        }
+        if !RELOADABLE_KEYS.contains(&key) {
+            continue;
+        }
+        values.insert(key.to_string(), unquote(value.trim()).to_string());
+    }
+    values
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_KEYS: [&str; 2] = ["KRC_VIDEO_DEFAULT", "KRC_VIDEO"];
+
+    fn file(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn process(pairs: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |key| {
+            pairs
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn the_env_file_beats_the_process_whichever_name_it_uses() {
+        let file = file(&[("KRC_VIDEO", "/file.mp4")]);
+        let env = process(&[("KRC_VIDEO_DEFAULT", "/process.mp4")]);
+        assert_eq!(
+            first_of(&DEFAULT_KEYS, Some(&file), env).as_deref(),
+            Some("/file.mp4")
+        );
+    }
+
+    #[test]
+    fn within_one_source_the_first_name_wins() {
+        let both = file(&[("KRC_VIDEO", "/b.mp4"), ("KRC_VIDEO_DEFAULT", "/a.mp4")]);
+        assert_eq!(
+            first_of(&DEFAULT_KEYS, Some(&both), process(&[])).as_deref(),
+            Some("/a.mp4")
+        );
+        let env = process(&[("KRC_VIDEO", "/b.mp4"), ("KRC_VIDEO_DEFAULT", "/a.mp4")]);
+        assert_eq!(
+            first_of(&DEFAULT_KEYS, None, env).as_deref(),
+            Some("/a.mp4")
+        );
+    }
+
+    #[test]
+    fn the_process_fills_in_when_the_file_has_neither_name() {
+        let other = file(&[("KRC_HWACCEL", "vaapi")]);
+        let env = process(&[("KRC_VIDEO", "/process.mp4")]);
+        assert_eq!(
+            first_of(&DEFAULT_KEYS, Some(&other), env).as_deref(),
+            Some("/process.mp4")
+        );
+        assert_eq!(first_of(&DEFAULT_KEYS, Some(&other), process(&[])), None);
+    }
+
+    #[test]
+    fn env_file_lines_are_parsed_like_systemd_reads_them() {
+        let parsed = parse_env_file(
+            "# comment\nexport KRC_VIDEO=\"/a b.mp4\"\n\nKRC_HWACCEL='vaapi'\nbroken\n",
+        );
+        assert_eq!(
+            parsed.get("KRC_VIDEO").map(String::as_str),
+            Some("/a b.mp4")
+        );
+        assert_eq!(parsed.get("KRC_HWACCEL").map(String::as_str), Some("vaapi"));
+        assert_eq!(parsed.len(), 2);
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::journal::Value;
+use crate::json::{JsonValue, escape_json, parse_json};
+use crate::user_path::{display_path, display_path_short, display_text};
+
+/// Bump when a line's layout or a field's meaning changes; every line carries
+/// it as `v` so scripts can refuse logs they do not understand.
+pub const EVENT_LOG_SCHEMA_VERSION: u64 = 1;
+
+/// Every `type` the renderer writes, for `events --help`.
+pub const EVENT_TYPES: [&str; 11] = [
+    "wallpaper_change",
+    "pause",
+    "resume",
+    "stream_started",
+    "stream_degraded",
+    "stream_recovered",
+    "stream_stopped",
+    "stream_presented",
+    "events_dropped",
+    "supervisor_restart",
+    "supervisor_gave_up",
+];
+
+/// Lines queued for the writer thread. Events are rare, so a full queue means
+/// the disk is stuck; later events are dropped and counted instead of
+/// stalling the render loop.
+const QUEUE_CAPACITY: usize = 1024;
+
+const DEFAULT_MAX_KB: u64 = 10 * 1024;
+const DEFAULT_KEEP: u32 = 3;
+const MAX_KEEP: u32 = 99;
+const DEFAULT_TAIL: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Leave flushing to the kernel; a power loss may take the last lines.
+    Never,
+    /// Sync a file before it is rotated away.
+    Rotate,
+    /// Sync after every line.
+    Always,
+}
+
+impl FsyncPolicy {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim() {
+            "never" => Some(Self::Never),
+            "rotate" => Some(Self::Rotate),
+            "always" => Some(Self::Always),
+            _ => None,
+        }
+    }
+}
+
+/// `KRC_EVENT_LOG` and its rotation and fsync settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventFileConfig {
+    pub path: PathBuf,
+    /// Rotate before a line would push the file past this size; 0 never rotates.
+    pub max_bytes: u64,
+    /// Rotated files kept as `PATH.1` (newest) to `PATH.N`.
+    pub keep: u32,
+    pub fsync: FsyncPolicy,
+}
+
+impl EventFileConfig {
+    /// `None` when `KRC_EVENT_LOG` is unset or empty. Unreadable settings warn
+    /// and fall back to their default.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var_os("KRC_EVENT_LOG").filter(|v| !v.is_empty())?;
+        let max_kb = match std::env::var("KRC_EVENT_LOG_MAX_KB") {
+            Ok(raw) => raw.trim().parse::<u64>().unwrap_or_else(|_| {
+                eprintln!(
+                    "[rendercore] warning: KRC_EVENT_LOG_MAX_KB={raw} is not a number, using {DEFAULT_MAX_KB}"
+                );
+                DEFAULT_MAX_KB
+            }),
+            Err(_) => DEFAULT_MAX_KB,
+        };
+        let keep = match std::env::var("KRC_EVENT_LOG_KEEP") {
+            Ok(raw) => match raw.trim().parse::<u32>() {
+                Ok(keep) if keep <= MAX_KEEP => keep,
+                _ => {
+                    eprintln!(
+                        "[rendercore] warning: KRC_EVENT_LOG_KEEP={raw} is not 0-{MAX_KEEP}, using {DEFAULT_KEEP}"
+                    );
+                    DEFAULT_KEEP
+                }
+            },
+            Err(_) => DEFAULT_KEEP,
+        };
+        let fsync = match std::env::var("KRC_EVENT_LOG_FSYNC") {
+            Ok(raw) => FsyncPolicy::parse(&raw).unwrap_or_else(|| {
+                eprintln!(
+                    "[rendercore] warning: KRC_EVENT_LOG_FSYNC={raw} is not never|rotate|always, using rotate"
+                );
+                FsyncPolicy::Rotate
+            }),
+            Err(_) => FsyncPolicy::Rotate,
+        };
+        Some(Self {
+            path: PathBuf::from(path),
+            max_bytes: max_kb.saturating_mul(1024),
+            keep,
+            fsync,
+        })
+    }
+}
+
+static SENDER: OnceLock<Option<SyncSender<String>>> = OnceLock::new();
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Starts the writer thread when `KRC_EVENT_LOG` is set. Safe to call more than once.
+pub fn init_from_env() {
+    SENDER.get_or_init(|| {
+        let config = EventFileConfig::from_env()?;
+        let shown = display_path(&config.path.to_string_lossy());
+        let (sender, receiver) = sync_channel(QUEUE_CAPACITY);
+        let spawned = thread::Builder::new()
+            .name("krc-event-log".to_string())
+            .spawn(move || write_loop(config, receiver));
+        match spawned {
+            Ok(_) => {
+                println!("[rendercore] appending events to {shown}");
+                Some(sender)
+            }
+            Err(err) => {
+                eprintln!(
+                    "[rendercore] warning: event log disabled: failed to start its writer: {err}"
+                );
+                None
+            }
+        }
+    });
+}
+
+/// Queues one event line for the writer thread; never blocks and never
+/// touches the disk. A no-op when `KRC_EVENT_LOG` is unset.
+pub fn record(kind: &str, monitor: Option<&str>, details: &[(&str, Value)]) {
+    let Some(Some(sender)) = SENDER.get() else {
+        return;
+    };
+    if let Err(TrySendError::Full(_)) = sender.try_send(format_line(kind, monitor, details)) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Appends one event on the calling thread, rotating as the writer thread
+/// does: for `supervise`, which may exit right after recording. A no-op when
+/// `KRC_EVENT_LOG` is unset.
+pub fn record_now(kind: &str, monitor: Option<&str>, details: &[(&str, Value)]) {
+    static CONFIG: OnceLock<Option<EventFileConfig>> = OnceLock::new();
+    let Some(config) = CONFIG.get_or_init(EventFileConfig::from_env) else {
+        return;
+    };
+    let mut writer = Writer {
+        config: config.clone(),
+        file: None,
+        size: 0,
+        failing: false,
+        reported_drops: 0,
+    };
+    writer.append(&format_line(kind, monitor, details));
+}
+
+/// `{"v":1,"ts":"2026-10-17T08:15:02.120Z","ts_ms":...,"type":"pause","monitor":null,"details":{"reason":"steam-game"}}`
+fn format_line(kind: &str, monitor: Option<&str>, details: &[(&str, Value)]) -> String {
+    let ts_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let monitor = match monitor {
+        Some(monitor) => format!("\"{}\"", escape_json(monitor)),
+        None => "null".to_string(),
+    };
+    let details = details
+        .iter()
+        .map(|(key, value)| match value {
+            Value::Str(s) => format!("\"{key}\":\"{}\"", escape_json(s)),
+            Value::U64(n) => format!("\"{key}\":{n}"),
+            Value::Bool(b) => format!("\"{key}\":{b}"),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        "{{\"v\":{EVENT_LOG_SCHEMA_VERSION},\"ts\":\"{}\",\"ts_ms\":{ts_ms},\"type\":\"{}\",\"monitor\":{monitor},\"details\":{{{details}}}}}\n",
+        utc_timestamp(ts_ms),
+        escape_json(kind)
+    )
+}
+
+/// RFC 3339 in UTC with milliseconds, from Unix milliseconds.
+fn utc_timestamp(ms: u64) -> String {
+    let secs = ms / 1000;
+    let time = secs % 86_400;
+    // Days since 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's
+    // civil_from_days), counting from 0000-03-01 so leap days end a year.
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        ms % 1000
+    )
+}
+
+/// `PATH.n`, the n-th newest rotated file.
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Owns the file on the writer thread. The file is reopened (and its
+/// directory recreated) whenever it disappears, so deleting the log or its
+/// whole directory while the renderer runs only starts a new file.
+struct Writer {
+    config: EventFileConfig,
+    file: Option<File>,
+    size: u64,
+    failing: bool,
+    reported_drops: u64,
+}
+
+fn write_loop(config: EventFileConfig, receiver: Receiver<String>) {
+    let mut writer = Writer {
+        config,
+        file: None,
+        size: 0,
+        failing: false,
+        reported_drops: 0,
+    };
+    while let Ok(line) = receiver.recv() {
+        let dropped = DROPPED.load(Ordering::Relaxed);
+        if dropped > writer.reported_drops {
+            let lost = dropped - writer.reported_drops;
+            writer.reported_drops = dropped;
+            eprintln!("[rendercore] warning: event log queue full, dropped {lost} event(s)");
+            writer.append(&format_line(
+                "events_dropped",
+                None,
+                &[("count", Value::U64(lost))],
+            ));
+        }
+        writer.append(&line);
+    }
+}
+
+impl Writer {
+    fn append(&mut self, line: &str) {
+        match self.try_append(line) {
+            Ok(()) if self.failing => {
+                self.failing = false;
+                println!(
+                    "[rendercore] event log {} writable again",
+                    display_path(&self.config.path.to_string_lossy())
+                );
+            }
+            Ok(()) => {}
+            Err(err) => {
+                self.file = None;
+                if !self.failing {
+                    self.failing = true;
+                    eprintln!(
+                        "[rendercore] warning: event log: {err}; events are lost until it is writable again"
+                    );
+                }
+            }
+        }
+    }
+
+    fn try_append(&mut self, line: &str) -> Result<(), String> {
+        // Removed or moved away since the last line: start a new file.
+        if self.file.is_some() && !self.config.path.exists() {
+            self.file = None;
+        }
+        if self.file.is_none() {
+            self.open()?;
+        }
+        let len = line.len() as u64;
+        if self.config.max_bytes > 0 && self.size > 0 && self.size + len > self.config.max_bytes {
+            self.rotate()?;
+        }
+        let shown = display_path(&self.config.path.to_string_lossy());
+        let Some(file) = self.file.as_mut() else {
+            return Err(format!("{shown} is not open"));
+        };
+        file.write_all(line.as_bytes())
+            .map_err(|e| format!("failed to write {shown}: {e}"))?;
+        self.size += len;
+        if self.config.fsync == FsyncPolicy::Always {
+            file.sync_data()
+                .map_err(|e| format!("failed to sync {shown}: {e}"))?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> Result<(), String> {
+        let path = &self.config.path;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| {
+                format!(
+                    "failed to create {}: {e}",
+                    display_path(&dir.to_string_lossy())
+                )
+            })?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                format!(
+                    "failed to open {}: {e}",
+                    display_path(&path.to_string_lossy())
+                )
+            })?;
+        self.size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// `PATH` becomes `PATH.1`, `PATH.1` becomes `PATH.2` and so on; the
+    /// oldest of the kept files is overwritten.
+    fn rotate(&mut self) -> Result<(), String> {
+        if let Some(file) = self.file.take()
+            && self.config.fsync != FsyncPolicy::Never
+        {
+            let _ = file.sync_all();
+        }
+        let path = &self.config.path;
+        let shown = display_path(&path.to_string_lossy());
+        if self.config.keep == 0 {
+            fs::remove_file(path).map_err(|e| format!("failed to rotate {shown}: {e}"))?;
+        } else {
+            for n in (1..self.config.keep).rev() {
+                let from = rotated_path(path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(path, n + 1))
+                        .map_err(|e| format!("failed to rotate {shown}: {e}"))?;
+                }
+            }
+            fs::rename(path, rotated_path(path, 1))
+                .map_err(|e| format!("failed to rotate {shown}: {e}"))?;
+        }
+        self.open()
+    }
+}
+
+struct EventsArgs {
+    file: Option<PathBuf>,
+    tail: usize,
+    types: Vec<String>,
+    json: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Option<EventsArgs>, String> {
+    let mut parsed = EventsArgs {
+        file: None,
+        tail: DEFAULT_TAIL,
+        types: Vec::new(),
+        json: false,
+    };
+    let mut i = 0usize;
+    while i < args.len() {
+        let value = |i: usize| {
+            args.get(i + 1)
+                .ok_or_else(|| format!("missing value for {}", args[i]))
+        };
+        match args[i].as_str() {
+            "--tail" | "-n" => {
+                let raw = value(i)?;
+                parsed.tail = raw
+                    .parse()
+                    .map_err(|_| format!("invalid --tail value: {raw}"))?;
+                i += 1;
+            }
+            "--type" => {
+                parsed.types.extend(
+                    value(i)?
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string),
+                );
+                i += 1;
+            }
+            "--file" => {
+                parsed.file = Some(PathBuf::from(value(i)?));
+                i += 1;
+            }
+            "--json" => parsed.json = true,
+            "--help" | "-h" => return Ok(None),
+            other => return Err(format!("unknown argument for events: {other}")),
+        }
+        i += 1;
+    }
+    Ok(Some(parsed))
+}
+
+/// `events`: the last lines of the `KRC_EVENT_LOG` file (and its rotated
+/// files, oldest first), optionally only some types, one line per event.
+pub fn run_events(args: &[String]) -> Result<(), String> {
+    let Some(args) = parse_args(args)? else {
+        print_events_help();
+        return Ok(());
+    };
+    let path = match args.file {
+        Some(path) => path,
+        None => EventFileConfig::from_env()
+            .map(|config| config.path)
+            .ok_or_else(|| {
+                "no event log: set KRC_EVENT_LOG (as for the renderer) or pass --file <PATH>"
+                    .to_string()
+            })?,
+    };
+    let mut files = (1..=MAX_KEEP)
+        .map(|n| rotated_path(&path, n))
+        .take_while(|rotated| rotated.exists())
+        .collect::<Vec<_>>();
+    files.reverse();
+    if path.exists() {
+        files.push(path.clone());
+    }
+    if files.is_empty() {
+        return Err(format!(
+            "event log {} does not exist yet",
+            display_path(&path.to_string_lossy())
+        ));
+    }
+    for kind in &args.types {
+        if !EVENT_TYPES.contains(&kind.as_str()) {
+            println!("[warn] unknown event type: {kind}");
+        }
+    }
+
+    let mut kept = VecDeque::with_capacity(args.tail.min(4096));
+    let mut unreadable = 0u64;
+    for file in &files {
+        let shown = display_path(&file.to_string_lossy());
+        let reader = File::open(file)
+            .map(BufReader::new)
+            .map_err(|e| format!("failed to open {shown}: {e}"))?;
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("failed to read {shown}: {e}"))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Ok(event) = parse_json(&line) else {
+                unreadable += 1;
+                continue;
+            };
+            let kind = event.get("type").and_then(JsonValue::as_str).unwrap_or("");
+            if !args.types.is_empty() && !args.types.iter().any(|t| t == kind) {
+                continue;
+            }
+            if args.tail == 0 {
+                continue;
+            }
+            if kept.len() == args.tail {
+                kept.pop_front();
+            }
+            kept.push_back((line, event));
+        }
+    }
+    for (line, event) in &kept {
+        if args.json {
+            println!("{line}");
+        } else {
+            println!("{}", format_event(event));
+        }
+    }
+    if unreadable > 0 {
+        println!("[warn] skipped {unreadable} line(s) that are not JSON");
+    }
+    Ok(())
+}
+
+/// `2026-10-17T08:15:02.120Z wallpaper_change DP-1 video=…/live/rain.mp4 reason=map-reload`
+fn format_event(event: &JsonValue) -> String {
+    let text = |key: &str| event.get(key).and_then(JsonValue::as_str).unwrap_or("?");
+    let monitor = match event.get("monitor") {
+        Some(JsonValue::String(monitor)) => display_text(monitor),
+        _ => "-".to_string(),
+    };
+    let mut line = format!("{} {} {monitor}", text("ts"), text("type"));
+    if let Some(JsonValue::Object(details)) = event.get("details") {
+        for (key, value) in details {
+            match value {
+                JsonValue::String(s) if key == "video" || key == "previous" => {
+                    line.push_str(&format!(" {key}={}", display_path_short(s)))
+                }
+                JsonValue::String(s) => line.push_str(&format!(" {key}={}", display_text(s))),
+                other => line.push_str(&format!(" {key}={}", other.to_json())),
+            }
+        }
+    }
+    line
+}
+
+fn print_events_help() {
+    println!("kitsune-rendercore events");
+    println!("Usage:");
+    println!(
+        "  kitsune-rendercore events [--tail <N>] [--type <TYPE>[,<TYPE>...]] [--json] [--file <PATH>]"
+    );
+    println!();
+    println!("Description:");
+    println!("  Print the last events from the log a renderer started with");
+    println!("  KRC_EVENT_LOG=/path/events.jsonl appends, including its rotated files");
+    println!("  (PATH.1, PATH.2, ...). Each line of the log is one JSON object:");
+    println!(
+        "    {{\"v\":{EVENT_LOG_SCHEMA_VERSION},\"ts\":\"...Z\",\"ts_ms\":...,\"type\":\"...\",\"monitor\":\"DP-1\"|null,\"details\":{{...}}}}"
+    );
+    println!();
+    println!("Options:");
+    println!("  --tail <N>     Show the last N matching events (default {DEFAULT_TAIL}).");
+    println!("  --type <TYPE>  Only these types, comma-separated: wallpaper_change, pause,");
+    println!("                 resume, stream_started, stream_degraded, stream_recovered,");
+    println!("                 stream_stopped, stream_presented, events_dropped,");
+    println!("                 supervisor_restart, supervisor_gave_up.");
+    println!("  --json         Print the log lines unchanged instead of one text line each.");
+    println!("  --file <PATH>  Log to read (default: KRC_EVENT_LOG).");
+}
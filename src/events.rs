@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TryRecvError, TrySendError, sync_channel};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::event_file;
+use crate::hooks::{self, HookContext, HookEvent};
+use crate::journal::{self, Value};
+use crate::json::escape_json;
+use crate::user_path::{display_path_short, display_text};
+
+/// Stream lifecycle of one output, published by the backend. The runtime's
+/// [`EventLog`] turns each one into a log line, a journal line, a hook, a
+/// `KRC_EVENT_LOG` line and a `status --watch` line, so those can never disagree about what happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub enum RenderEvent {
+    /// A decoder opened `video` and frames are expected.
+    Started {
+        monitor: String,
+        video: String,
+        decoder: String,
+        source_size: (u32, u32),
+    },
+    /// Decoding or presenting failed. Published on the 1st, 2nd, 4th, 8th...
+    /// failure in a row, so a dead decoder does not flood consumers.
+    Degraded {
+        monitor: String,
+        reason: String,
+        consecutive_failures: u32,
+    },
+    /// A frame arrived again after `failures` failed attempts.
+    Recovered { monitor: String, failures: u32 },
+    /// The decoder for `video` was closed (switched, resized, output excluded).
+    Stopped {
+        monitor: String,
+        video: String,
+        reason: String,
+    },
+    /// The output presented its first frame of `video` since the decoder
+    /// opened (or since it started sharing one): the change is on screen.
+    Presented { monitor: String, video: String },
+}
+
+impl RenderEvent {
+    pub const NAMES: [&'static str; 5] = [
+        "stream_started",
+        "stream_degraded",
+        "stream_recovered",
+        "stream_stopped",
+        "stream_presented",
+    ];
+
+    fn index(&self) -> usize {
+        match self {
+            Self::Started { .. } => 0,
+            Self::Degraded { .. } => 1,
+            Self::Recovered { .. } => 2,
+            Self::Stopped { .. } => 3,
+            Self::Presented { .. } => 4,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAMES[self.index()]
+    }
+
+    pub fn monitor(&self) -> &str {
+        match self {
+            Self::Started { monitor, .. }
+            | Self::Degraded { monitor, .. }
+            | Self::Recovered { monitor, .. }
+            | Self::Stopped { monitor, .. }
+            | Self::Presented { monitor, .. } => monitor,
+        }
+    }
+
+    /// Event-specific fields, after `monitor`, in journal form.
+    fn fields(&self) -> Vec<(&'static str, Value<'_>)> {
+        match self {
+            Self::Started {
+                video,
+                decoder,
+                source_size,
+                ..
+            } => vec![
+                ("video", Value::Str(video)),
+                ("decoder", Value::Str(decoder)),
+                ("source_width", Value::U64(source_size.0 as u64)),
+                ("source_height", Value::U64(source_size.1 as u64)),
+            ],
+            Self::Degraded {
+                reason,
+                consecutive_failures,
+                ..
+            } => vec![
+                ("reason", Value::Str(reason)),
+                (
+                    "consecutive_failures",
+                    Value::U64(*consecutive_failures as u64),
+                ),
+            ],
+            Self::Recovered { failures, .. } => {
+                vec![("failures", Value::U64(*failures as u64))]
+            }
+            Self::Stopped { video, reason, .. } => {
+                vec![("video", Value::Str(video)), ("reason", Value::Str(reason))]
+            }
+            Self::Presented { video, .. } => vec![("video", Value::Str(video))],
+        }
+    }
+
+    fn hook_event(&self) -> HookEvent {
+        match self {
+            Self::Started { .. } => HookEvent::StreamStarted,
+            Self::Degraded { .. } => HookEvent::StreamDegraded,
+            Self::Recovered { .. } => HookEvent::StreamRecovered,
+            Self::Stopped { .. } => HookEvent::StreamStopped,
+            Self::Presented { .. } => HookEvent::StreamPresented,
+        }
+    }
+}
+
+/// Queued per subscriber; a subscriber that falls this far behind loses events
+/// rather than stalling the render loop.
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+static SUBSCRIBERS: OnceLock<Mutex<Vec<SyncSender<RenderEvent>>>> = OnceLock::new();
+static SUBSCRIBER_COUNT: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+fn subscribers() -> &'static Mutex<Vec<SyncSender<RenderEvent>>> {
+    SUBSCRIBERS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Attaches a consumer; every event published from now on is queued for it.
+pub fn subscribe() -> Receiver<RenderEvent> {
+    let (sender, receiver) = sync_channel(SUBSCRIBER_CAPACITY);
+    if let Ok(mut list) = subscribers().lock() {
+        list.push(sender);
+        SUBSCRIBER_COUNT.store(list.len(), Ordering::Relaxed);
+    }
+    receiver
+}
+
+/// Hands the event from `make` to every subscriber without blocking. `make`
+/// only runs when someone is subscribed, so publishing with no consumer costs
+/// one atomic load.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub fn publish(make: impl FnOnce() -> RenderEvent) {
+    if SUBSCRIBER_COUNT.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let event = make();
+    let Ok(mut list) = subscribers().lock() else {
+        return;
+    };
+    list.retain(|sender| match sender.try_send(event.clone()) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_)) => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+        Err(TrySendError::Disconnected(_)) => false,
+    });
+    SUBSCRIBER_COUNT.store(list.len(), Ordering::Relaxed);
+}
+
+/// Events lost because a subscriber's queue was full.
+pub fn dropped() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Recent events kept for `status --watch`, which polls with the last
+/// sequence number it saw.
+const RECENT_EVENTS: usize = 256;
+
+/// The runtime's subscriber: drained once per loop wakeup, it logs, journals,
+/// fires hooks, counts, and keeps the recent events for watchers.
+pub struct EventLog {
+    receiver: Receiver<RenderEvent>,
+    started_at: Instant,
+    next_seq: u64,
+    recent: VecDeque<(u64, String)>,
+    counts: [u64; 5],
+    /// An embedder's callback, handed each event after it is logged.
+    sink: Option<EventSink>,
+}
+
+/// Where an embedder receives events; called on the render thread.
+pub type EventSink = Box<dyn FnMut(RenderEvent)>;
+
+impl EventLog {
+    pub fn attach() -> Self {
+        Self {
+            receiver: subscribe(),
+            started_at: Instant::now(),
+            next_seq: 1,
+            recent: VecDeque::with_capacity(RECENT_EVENTS),
+            counts: [0; 5],
+            sink: None,
+        }
+    }
+
+    pub fn set_sink(&mut self, sink: EventSink) {
+        self.sink = Some(sink);
+    }
+
+    pub fn drain(&mut self) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => self.consume(event),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return,
+            }
+        }
+    }
+
+    fn consume(&mut self, event: RenderEvent) {
+        let mut fields = vec![("monitor", Value::Str(event.monitor()))];
+        fields.extend(event.fields());
+        let line = fields
+            .iter()
+            .map(|(key, value)| match value {
+                Value::Str(s) if *key == "video" => format!("{key}={}", display_path_short(s)),
+                Value::Str(s) => format!("{key}={}", display_text(s)),
+                Value::U64(n) => format!("{key}={n}"),
+                Value::Bool(b) => format!("{key}={b}"),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!("[rendercore] {} {line}", event.name());
+        journal::record(event.name(), &fields);
+        event_file::record(event.name(), Some(event.monitor()), &event.fields());
+
+        let detail = match &event {
+            RenderEvent::Started {
+                decoder,
+                source_size,
+                ..
+            } => format!("{decoder} source={}x{}", source_size.0, source_size.1),
+            RenderEvent::Degraded {
+                consecutive_failures: failures,
+                ..
+            }
+            | RenderEvent::Recovered { failures, .. } => format!("failures={failures}"),
+            RenderEvent::Stopped { .. } | RenderEvent::Presented { .. } => String::new(),
+        };
+        let (video, reason) = match &event {
+            RenderEvent::Started { video, .. } | RenderEvent::Presented { video, .. } => {
+                (Some(video.as_str()), None)
+            }
+            RenderEvent::Degraded { reason, .. } => (None, Some(reason.as_str())),
+            RenderEvent::Recovered { .. } => (None, None),
+            RenderEvent::Stopped { video, reason, .. } => {
+                (Some(video.as_str()), Some(reason.as_str()))
+            }
+        };
+        hooks::fire(
+            event.hook_event(),
+            HookContext {
+                monitor: Some(event.monitor()),
+                video,
+                reason,
+                detail: Some(&detail).filter(|d| !d.is_empty()).map(String::as_str),
+            },
+        );
+
+        self.counts[event.index()] += 1;
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let mut json = format!(
+            "{{\"seq\":{seq},\"t_ms\":{},\"event\":\"{}\"",
+            self.started_at.elapsed().as_millis(),
+            event.name()
+        );
+        for (key, value) in &fields {
+            match value {
+                Value::Str(s) => json.push_str(&format!(",\"{key}\":\"{}\"", escape_json(s))),
+                Value::U64(n) => json.push_str(&format!(",\"{key}\":{n}")),
+                Value::Bool(b) => json.push_str(&format!(",\"{key}\":{b}")),
+            }
+        }
+        json.push('}');
+        if self.recent.len() == RECENT_EVENTS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((seq, json));
+        if let Some(sink) = &mut self.sink {
+            sink(event);
+        }
+    }
+
+    /// `events <after>` reply: kept events with a larger sequence number, the
+    /// sequence to ask from next, and how many the watcher missed because they
+    /// were no longer kept.
+    pub fn since_json(&self, after: u64) -> String {
+        let oldest = self.recent.front().map_or(self.next_seq, |(seq, _)| *seq);
+        let missed = oldest.saturating_sub(after.saturating_add(1));
+        let events = self
+            .recent
+            .iter()
+            .filter(|(seq, _)| *seq > after)
+            .map(|(_, json)| json.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"next\":{},\"missed\":{missed},\"events\":[{events}]}}",
+            self.next_seq - 1
+        )
+    }
+
+    /// Per-event totals for the live status, plus events dropped on the bus.
+    pub fn counts_json(&self) -> String {
+        let counts = RenderEvent::NAMES
+            .iter()
+            .zip(self.counts)
+            .map(|(name, count)| format!("\"{name}\":{count}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{{{counts},\"dropped\":{}}}", dropped())
+    }
+}
@@ -0,0 +1,281 @@
+//! Whether this user can read and replace the files the CLI edits. Running
+//! the CLI once with sudo leaves root-owned files (or a root-owned config
+//! directory) behind; writers check first and name the `chown` that fixes
+//! it instead of failing with a bare IO error, and the renderer warns about
+//! it at startup.
+
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::user_path::{current_uid, home_dir, user_name};
+
+fn access_ok(path: &Path, mode: libc::c_int) -> bool {
+    let Ok(raw) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: access(2) only reads the NUL-terminated path.
+    unsafe { libc::access(raw.as_ptr(), mode) == 0 }
+}
+
+/// Whether this user may write `path`, or create it: the nearest existing
+/// ancestor decides for a path that does not exist yet. Nothing is created.
+pub fn writable(path: &Path) -> bool {
+    path.ancestors()
+        .find(|p| p.exists())
+        .is_some_and(|existing| access_ok(existing, libc::W_OK))
+}
+
+pub fn readable(path: &Path) -> bool {
+    access_ok(path, libc::R_OK)
+}
+
+/// This user's login name, for the `chown` hints.
+fn me() -> String {
+    user_name(current_uid()).unwrap_or_else(|| "$USER".to_string())
+}
+
+/// Who owns `path`, when that is not this user.
+fn foreign_owner(path: &Path) -> Option<String> {
+    let uid = fs::metadata(path).ok()?.uid();
+    (uid != current_uid()).then(|| user_name(uid).unwrap_or_else(|| format!("uid {uid}")))
+}
+
+/// ` (owned by root; run: sudo chown alice: PATH)` when someone else owns
+/// `path`, else nothing; appended to read and write errors.
+pub fn owner_hint(path: &Path) -> String {
+    match foreign_owner(path) {
+        Some(owner) => format!(
+            " (owned by {owner}; run: sudo chown {}: {})",
+            me(),
+            path.display()
+        ),
+        None => String::new(),
+    }
+}
+
+/// The directory a temp file next to `path` goes in.
+fn parent_dir(path: &Path) -> &Path {
+    path.parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// Why this user cannot replace `path` through a temp file and a rename,
+/// with the command that fixes it; `None` when nothing is in the way. A file
+/// owned by someone else counts even when its directory would allow the
+/// rename: the CLI reads it first to keep the other entries, and the
+/// rename would quietly hand it to this user. Root is never blocked.
+pub fn write_blocker(path: &Path, what: &str) -> Option<String> {
+    let existing = parent_dir(path).ancestors().find(|p| p.exists())?;
+    if !access_ok(existing, libc::W_OK) {
+        // Only suggest a recursive chown inside the home directory: the
+        // nearest existing ancestor may be `/`.
+        let in_home =
+            home_dir().is_some_and(|home| existing.starts_with(&home) && existing != home);
+        return Some(match foreign_owner(existing).filter(|_| in_home) {
+            Some(owner) => format!(
+                "{what} directory {} is owned by {owner}; run: sudo chown -R {}: {}",
+                existing.display(),
+                me(),
+                existing.display()
+            ),
+            None => format!(
+                "{what} directory {} is not writable by {}",
+                existing.display(),
+                me()
+            ),
+        });
+    }
+    if current_uid() == 0 || !path.exists() {
+        return None;
+    }
+    if let Some(owner) = foreign_owner(path) {
+        return Some(format!(
+            "{what} {} is owned by {owner}; run: sudo chown {}: {}",
+            path.display(),
+            me(),
+            path.display()
+        ));
+    }
+    if !readable(path) || !access_ok(path, libc::W_OK) {
+        return Some(format!(
+            "{what} {} is not readable and writable by {}; run: chmod u+rw {}",
+            path.display(),
+            me(),
+            path.display()
+        ));
+    }
+    None
+}
+
+/// The renderer's startup check of a file it reads and the CLI rewrites.
+pub fn startup_warning(path: &Path, what: &str) -> Option<String> {
+    if path.exists() && !readable(path) {
+        return Some(format!(
+            "{what} {} cannot be read by {}{}",
+            path.display(),
+            me(),
+            owner_hint(path)
+        ));
+    }
+    write_blocker(path, what)
+        .map(|why| format!("{why}; set-video and other CLI edits will fail until then"))
+}
+
+/// Writes `contents` through a temp file and a rename, so a reader never
+/// sees half a file. The temp file takes the replaced file's mode, so a
+/// group-readable setup stays that way instead of getting the umask's; run
+/// as root (sudo), it also takes the owner of the file, or of the directory
+/// it is created in, so root does not end up owning the user's files.
+pub fn write_atomic(path: &Path, contents: &[u8], what: &str) -> Result<(), String> {
+    if let Some(why) = write_blocker(path, what) {
+        return Err(why);
+    }
+    let previous = fs::metadata(path).ok();
+    let dir = parent_dir(path);
+    let existing = dir.ancestors().find(|p| p.exists()).map(Path::to_path_buf);
+    let owner = previous
+        .as_ref()
+        .or(existing
+            .as_deref()
+            .and_then(|p| fs::metadata(p).ok())
+            .as_ref())
+        .map(|meta| (meta.uid(), meta.gid()))
+        .filter(|(uid, _)| current_uid() == 0 && *uid != 0);
+    let created = dir
+        .ancestors()
+        .take_while(|p| !p.exists())
+        .map(Path::to_path_buf)
+        .collect::<Vec<PathBuf>>();
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    if let Some((uid, gid)) = owner {
+        for created in &created {
+            let _ = std::os::unix::fs::chown(created, Some(uid), Some(gid));
+        }
+    }
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{what} path {} has no file name", path.display()))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.tmp-{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    fs::write(&tmp, contents).map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+    let finish = || {
+        if let Some(meta) = &previous {
+            fs::set_permissions(&tmp, fs::Permissions::from_mode(meta.mode() & 0o7777))?;
+        }
+        if let Some((uid, gid)) = owner {
+            std::os::unix::fs::chown(&tmp, Some(uid), Some(gid))?;
+        }
+        fs::rename(&tmp, path)
+    };
+    finish().map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("failed to replace {}: {e}", path.display())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("krc-file-access-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn write_atomic_creates_missing_directories() {
+        let dir = temp_dir("create");
+        let path = dir.join("state/nested/snapshot.json");
+        write_atomic(&path, b"{}\n", "runtime snapshot").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"{}\n");
+        assert_eq!(entries(path.parent().unwrap()), ["snapshot.json"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn write_atomic_replaces_and_keeps_the_mode() {
+        let dir = temp_dir("replace");
+        let path = dir.join("update-check");
+        fs::write(&path, "checked=1\nlatest=v0.1.0\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&path, b"checked=2\nlatest=v0.2.0\n", "update-check cache").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "checked=2\nlatest=v0.2.0\n"
+        );
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o7777, 0o640);
+        // Shrinking leaves nothing of the longer file behind.
+        write_atomic(&path, b"", "update-check cache").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        assert_eq!(entries(&dir), ["update-check"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_replace_leaves_no_temp_file() {
+        let dir = temp_dir("failed");
+        let path = dir.join("pipeline-cache");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), "x").unwrap();
+        let err = write_atomic(&path, b"data", "pipeline cache").unwrap_err();
+        assert!(err.contains("pipeline-cache"), "{err}");
+        assert_eq!(entries(&dir), ["pipeline-cache"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_only_directories_block_writes() {
+        if current_uid() == 0 {
+            // Root is never blocked.
+            return;
+        }
+        let dir = temp_dir("read-only");
+        let locked = dir.join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+        let path = locked.join("snapshot.json");
+        let err = write_atomic(&path, b"{}", "runtime snapshot").unwrap_err();
+        assert!(err.starts_with("runtime snapshot directory"), "{err}");
+        assert!(!path.exists());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn files_owned_by_someone_else_name_the_chown() {
+        let dir = temp_dir("owner");
+        assert_eq!(owner_hint(&dir), "");
+        let _ = fs::remove_dir_all(&dir);
+        if current_uid() == 0 {
+            // Everything root reads is its own to fix.
+            return;
+        }
+        let root = Path::new("/");
+        assert_eq!(fs::metadata(root).unwrap().uid(), 0);
+        let hint = owner_hint(root);
+        assert!(hint.starts_with(" (owned by "), "{hint}");
+        assert!(
+            hint.contains(&format!("run: sudo chown {}: /)", me())),
+            "{hint}"
+        );
+    }
+}
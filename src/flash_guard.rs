@@ -0,0 +1,358 @@
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// WCAG 2.x: a flash is a pair of opposing changes in relative luminance of
+/// 10% or more where the darker state is below 0.80; more than three flashes
+/// in any one second fail the general flash threshold.
+const DEFAULT_DELTA: f32 = 0.10;
+const DARK_BELOW: f32 = 0.80;
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Transitions in the window that start smoothing (two flashes), and that
+/// freeze the stream (more than three flashes).
+const SMOOTH_TRANSITIONS: usize = 4;
+const FREEZE_TRANSITIONS: usize = 7;
+
+/// A frozen stream resumes after this long without a single transition.
+const CALM_FOR: Duration = Duration::from_secs(3);
+
+/// Sample grid for the average luminance: a few hundred pixels per frame.
+const GRID_X: u32 = 32;
+const GRID_Y: u32 = 18;
+
+/// What the guard is doing to an output's frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardState {
+    Clear,
+    /// Each frame is blended with the previous one shown.
+    Smoothing,
+    /// Frames are not shown at all; the last one shown stays.
+    Frozen,
+}
+
+impl GuardState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Clear => "clear",
+            Self::Smoothing => "smoothing",
+            Self::Frozen => "flash-guard-triggered",
+        }
+    }
+}
+
+/// `KRC_FLASH_GUARD=1`: photosensitivity guard over decoded frames. The
+/// average relative luminance of each frame is tracked for opposing swings
+/// of `KRC_FLASH_GUARD_DELTA` (default 0.10); when they come too often the
+/// frames are blended with the previous one, and past the WCAG limit the
+/// stream is frozen until it calms down.
+pub struct FlashGuard {
+    delta: f32,
+    /// Luminance extremes since the last transition.
+    high: f32,
+    low: f32,
+    /// Direction of the last transition: 1 brighter, -1 darker, 0 none yet.
+    last_direction: i8,
+    primed: bool,
+    transitions: VecDeque<Instant>,
+    last_transition: Option<Instant>,
+    state: GuardState,
+    /// The last frame shown, blended into the next while smoothing.
+    shown: Vec<u8>,
+}
+
+impl FlashGuard {
+    /// A guard per stream when `KRC_FLASH_GUARD` is on, else `None`.
+    pub fn from_env() -> Option<Self> {
+        static DELTA: OnceLock<Option<f32>> = OnceLock::new();
+        let delta = (*DELTA.get_or_init(|| {
+            let enabled = std::env::var("KRC_FLASH_GUARD")
+                .ok()
+                .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false);
+            if !enabled {
+                return None;
+            }
+            let delta = match std::env::var("KRC_FLASH_GUARD_DELTA") {
+                Ok(raw) => match raw.trim().parse::<f32>() {
+                    Ok(delta) if (0.02..=0.5).contains(&delta) => delta,
+                    _ => {
+                        eprintln!(
+                            "[rendercore] ignoring KRC_FLASH_GUARD_DELTA={raw}: expected 0.02-0.5"
+                        );
+                        DEFAULT_DELTA
+                    }
+                },
+                Err(_) => DEFAULT_DELTA,
+            };
+            println!("[rendercore] flash guard enabled (luminance delta {delta})");
+            Some(delta)
+        }))?;
+        Some(Self::new(delta))
+    }
+
+    fn new(delta: f32) -> Self {
+        Self {
+            delta,
+            high: 0.0,
+            low: 0.0,
+            last_direction: 0,
+            primed: false,
+            transitions: VecDeque::new(),
+            last_transition: None,
+            state: GuardState::Clear,
+            shown: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> GuardState {
+        self.state
+    }
+
+    /// Transitions counted in the last second.
+    pub fn recent_transitions(&self) -> usize {
+        self.transitions.len()
+    }
+
+    /// Looks at a decoded RGBA frame, blending it in place while smoothing.
+    /// Returns whether it should be shown (false while frozen) and the new
+    /// state when it changed.
+    pub fn inspect(
+        &mut self,
+        now: Instant,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+    ) -> (bool, Option<GuardState>) {
+        let luminance = average_luminance(pixels, width, height);
+        self.track(now, luminance);
+        let previous = self.state;
+        let transitions = self.transitions.len();
+        let calm = self
+            .last_transition
+            .is_none_or(|at| now.duration_since(at) >= CALM_FOR);
+        self.state = match self.state {
+            _ if transitions >= FREEZE_TRANSITIONS => GuardState::Frozen,
+            GuardState::Frozen if !calm => GuardState::Frozen,
+            _ if transitions >= SMOOTH_TRANSITIONS => GuardState::Smoothing,
+            // Leaving a freeze blends back in rather than jumping.
+            GuardState::Frozen => GuardState::Smoothing,
+            GuardState::Smoothing if transitions > 0 => GuardState::Smoothing,
+            _ => GuardState::Clear,
+        };
+        let show = match self.state {
+            GuardState::Frozen => false,
+            GuardState::Smoothing => {
+                if self.shown.len() == pixels.len() {
+                    blend(pixels, &self.shown, self.smoothing(transitions));
+                }
+                self.shown.clear();
+                self.shown.extend_from_slice(pixels);
+                true
+            }
+            GuardState::Clear => {
+                self.shown = Vec::new();
+                true
+            }
+        };
+        let changed = (self.state != previous).then_some(self.state);
+        (show, changed)
+    }
+
+    /// Weight of the previous frame (of 256): rises with the flash rate.
+    fn smoothing(&self, transitions: usize) -> u16 {
+        (128 + 24 * transitions.saturating_sub(SMOOTH_TRANSITIONS) as u16).min(224)
+    }
+
+    /// Counts a transition each time the luminance swings by `delta` against
+    /// the last one, with the darker side below [`DARK_BELOW`].
+    fn track(&mut self, now: Instant, luminance: f32) {
+        while self
+            .transitions
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= WINDOW)
+        {
+            self.transitions.pop_front();
+        }
+        if !self.primed {
+            self.high = luminance;
+            self.low = luminance;
+            self.primed = true;
+            return;
+        }
+        self.high = self.high.max(luminance);
+        self.low = self.low.min(luminance);
+        let direction = if self.last_direction != 1
+            && luminance - self.low >= self.delta
+            && self.low < DARK_BELOW
+        {
+            1
+        } else if self.last_direction != -1
+            && self.high - luminance >= self.delta
+            && luminance < DARK_BELOW
+        {
+            -1
+        } else {
+            return;
+        };
+        self.last_direction = direction;
+        self.high = luminance;
+        self.low = luminance;
+        self.transitions.push_back(now);
+        self.last_transition = Some(now);
+    }
+}
+
+/// Mean relative luminance (0-1) of a sparse grid of pixels, in linear light.
+fn average_luminance(pixels: &[u8], width: u32, height: u32) -> f32 {
+    static LINEAR: OnceLock<[f32; 256]> = OnceLock::new();
+    let linear = LINEAR.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let c = i as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    });
+    if width == 0 || height == 0 {
+        return 0.0;
+    }
+    let mut sum = 0.0f32;
+    let mut count = 0u32;
+    for gy in 0..GRID_Y {
+        let y = (gy * 2 + 1) * height / (GRID_Y * 2);
+        for gx in 0..GRID_X {
+            let x = (gx * 2 + 1) * width / (GRID_X * 2);
+            let i = ((y * width + x) * 4) as usize;
+            let Some(px) = pixels.get(i..i + 3) else {
+                continue;
+            };
+            sum += 0.2126 * linear[px[0] as usize]
+                + 0.7152 * linear[px[1] as usize]
+                + 0.0722 * linear[px[2] as usize];
+            count += 1;
+        }
+    }
+    if count == 0 { 0.0 } else { sum / count as f32 }
+}
+
+/// `frame = (previous * weight + frame * (256 - weight)) / 256`, per byte.
+fn blend(frame: &mut [u8], previous: &[u8], weight: u16) {
+    let keep = 256 - weight;
+    for (new, old) in frame.iter_mut().zip(previous) {
+        *new = ((*old as u16 * weight + *new as u16 * keep) >> 8) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: u32 = 64;
+    const H: u32 = 36;
+
+    fn frame(level: u8) -> Vec<u8> {
+        [level, level, level, 255].repeat((W * H) as usize)
+    }
+
+    /// Feeds one frame of `level` per `levels` item at `fps` from `start`;
+    /// returns the frames shown and every state change.
+    fn play(
+        guard: &mut FlashGuard,
+        start: Instant,
+        fps: u32,
+        levels: impl IntoIterator<Item = u8>,
+    ) -> (usize, Vec<GuardState>) {
+        let mut shown = 0;
+        let mut changes = Vec::new();
+        for (i, level) in levels.into_iter().enumerate() {
+            let at = start + Duration::from_secs(1) * i as u32 / fps;
+            let (show, changed) = guard.inspect(at, &mut frame(level), W, H);
+            shown += usize::from(show);
+            changes.extend(changed);
+        }
+        (shown, changes)
+    }
+
+    /// A black/white strobe at `hz` full cycles per second, for `secs`.
+    fn strobe(hz: u32, secs: u32) -> impl Iterator<Item = u8> {
+        let per_half = (30 / (hz * 2)).max(1) as usize;
+        (0..30 * secs as usize).map(move |i| {
+            if (i / per_half).is_multiple_of(2) {
+                0
+            } else {
+                255
+            }
+        })
+    }
+
+    #[test]
+    fn luminance_is_linear_light() {
+        assert_eq!(average_luminance(&frame(0), W, H), 0.0);
+        assert!((average_luminance(&frame(255), W, H) - 1.0).abs() < 1e-4);
+        // sRGB 50% grey is about 21.6% of white's light.
+        assert!((average_luminance(&frame(128), W, H) - 0.2158).abs() < 1e-3);
+        let green = [0, 255, 0, 255].repeat((W * H) as usize);
+        assert!((average_luminance(&green, W, H) - 0.7152).abs() < 1e-4);
+        assert_eq!(average_luminance(&[], 0, 0), 0.0);
+    }
+
+    #[test]
+    fn steady_and_slowly_fading_video_is_left_alone() {
+        let mut guard = FlashGuard::new(DEFAULT_DELTA);
+        let fade = (0..90).map(|i| (i * 255 / 89) as u8);
+        let (shown, changes) = play(&mut guard, Instant::now(), 30, fade);
+        assert_eq!((shown, changes), (90, vec![]));
+        // At most one swing per second: below the three-flash limit.
+        assert!(guard.recent_transitions() <= 1);
+    }
+
+    #[test]
+    fn two_flashes_a_second_are_smoothed() {
+        let mut guard = FlashGuard::new(DEFAULT_DELTA);
+        let (shown, changes) = play(&mut guard, Instant::now(), 30, strobe(2, 3));
+        assert_eq!(shown, 90, "smoothing still shows every frame");
+        assert_eq!(changes.first(), Some(&GuardState::Smoothing));
+        assert!(!changes.contains(&GuardState::Frozen), "{changes:?}");
+    }
+
+    #[test]
+    fn a_fast_strobe_freezes_until_it_calms_down() {
+        let mut guard = FlashGuard::new(DEFAULT_DELTA);
+        let start = Instant::now();
+        let (shown, changes) = play(&mut guard, start, 30, strobe(5, 2));
+        assert_eq!(guard.state(), GuardState::Frozen);
+        assert_eq!(guard.state().as_str(), "flash-guard-triggered");
+        // Seven swings in: frozen within the first second, for the rest.
+        assert!(shown <= 7 * 3, "{shown} frames of the strobe shown");
+        assert!(changes.contains(&GuardState::Frozen));
+
+        // Still frames: frozen for CALM_FOR, then blended back in, then clear.
+        let calm = start + Duration::from_secs(2);
+        let (_, changes) = play(&mut guard, calm, 30, std::iter::repeat_n(40, 30 * 5));
+        assert_eq!(changes, vec![GuardState::Smoothing, GuardState::Clear]);
+    }
+
+    #[test]
+    fn swings_between_bright_states_are_not_flashes() {
+        let mut guard = FlashGuard::new(DEFAULT_DELTA);
+        // 0.80 and 1.0 relative luminance: no darker state below 0.80.
+        let levels = (0..60).map(|i: u32| if i.is_multiple_of(2) { 232 } else { 255 });
+        let (_, changes) = play(&mut guard, Instant::now(), 30, levels);
+        assert!(changes.is_empty(), "{changes:?}");
+        assert_eq!(guard.recent_transitions(), 0);
+    }
+
+    #[test]
+    fn blending_weighs_the_previous_frame() {
+        let mut new = [200, 0, 100];
+        blend(&mut new, &[0, 200, 100], 128);
+        assert_eq!(new, [100, 100, 100]);
+        let guard = FlashGuard::new(DEFAULT_DELTA);
+        assert_eq!(guard.smoothing(SMOOTH_TRANSITIONS), 128);
+        assert_eq!(guard.smoothing(SMOOTH_TRANSITIONS + 2), 176);
+        assert_eq!(guard.smoothing(100), 224);
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::monitor::ArrivalStats;
+use crate::user_path::display_path;
+
+/// Reads the rolling window holds, about 20s at 30 fps.
+const WINDOW: usize = 600;
+
+/// A starved read that waited this long, or this many frame intervals
+/// (whichever is longer), was held up by I/O: decoding a frame never takes
+/// that long, a disk spinning up or a share reconnecting does.
+const IO_GAP: Duration = Duration::from_millis(200);
+const IO_GAP_INTERVALS: u32 = 4;
+
+/// I/O starvations this close together are a pattern worth a hint.
+const HINT_EVENTS: usize = 3;
+const HINT_WINDOW: Duration = Duration::from_secs(60);
+
+/// How one frame read went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrival {
+    /// The decoder was ahead: part of the frame was already in the pipe.
+    Ready,
+    /// The pipe was empty when the frame was wanted.
+    Starved(Starvation),
+}
+
+/// Why the pipe ran dry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Starvation {
+    /// The frame came within a few intervals: the decoder is just behind.
+    Decode,
+    /// A long gap: the decoder itself was waiting on the file.
+    Io,
+}
+
+/// Classifies a read that started at `started` with `queued` bytes waiting in
+/// the pipe and had the whole frame at `arrived`; `interval` is the decoder's
+/// frame interval.
+pub fn classify(started: Instant, arrived: Instant, queued: usize, interval: Duration) -> Arrival {
+    if queued > 0 {
+        return Arrival::Ready;
+    }
+    let waited = arrived.saturating_duration_since(started);
+    if waited >= IO_GAP.max(interval * IO_GAP_INTERVALS) {
+        Arrival::Starved(Starvation::Io)
+    } else {
+        Arrival::Starved(Starvation::Decode)
+    }
+}
+
+/// How a decoder's frames arrive through its pipe: how long each read
+/// waited, how full the pipe was when it started, and how often it found the
+/// pipe empty. Decode slowness shows as short starvations, a sleeping disk
+/// as bursts of long ones.
+#[derive(Default)]
+pub struct FrameArrival {
+    waits: VecDeque<Duration>,
+    /// Share of the pipe filled at the start of each read in the window.
+    fills: VecDeque<f32>,
+    queued: usize,
+    capacity: usize,
+    starved_decode: u64,
+    starved_io: u64,
+    recent_io: VecDeque<Instant>,
+}
+
+impl FrameArrival {
+    /// Records one read; returns true when I/O starvations have become
+    /// frequent enough to name the file's filesystem.
+    pub fn record(
+        &mut self,
+        started: Instant,
+        arrived: Instant,
+        queued: usize,
+        capacity: usize,
+        interval: Duration,
+    ) -> bool {
+        if self.waits.len() == WINDOW {
+            self.waits.pop_front();
+            self.fills.pop_front();
+        }
+        self.waits
+            .push_back(arrived.saturating_duration_since(started));
+        self.fills
+            .push_back((queued as f32 / capacity.max(1) as f32).min(1.0));
+        self.queued = queued;
+        self.capacity = capacity;
+        match classify(started, arrived, queued, interval) {
+            Arrival::Ready => false,
+            Arrival::Starved(Starvation::Decode) => {
+                self.starved_decode += 1;
+                false
+            }
+            Arrival::Starved(Starvation::Io) => {
+                self.starved_io += 1;
+                self.recent_io.push_back(arrived);
+                while self
+                    .recent_io
+                    .front()
+                    .is_some_and(|at| arrived.saturating_duration_since(*at) > HINT_WINDOW)
+                {
+                    self.recent_io.pop_front();
+                }
+                self.recent_io.len() >= HINT_EVENTS
+            }
+        }
+    }
+
+    pub fn stats(&self) -> ArrivalStats {
+        let mut waits = self.waits.iter().copied().collect::<Vec<_>>();
+        waits.sort_unstable();
+        let p95 = (!waits.is_empty()).then(|| {
+            let rank = ((waits.len() as f64 * 0.95).ceil() as usize).max(1);
+            waits[rank - 1]
+        });
+        ArrivalStats {
+            samples: waits.len(),
+            p95,
+            queued_bytes: self.queued,
+            capacity_bytes: self.capacity,
+            avg_fill: if self.fills.is_empty() {
+                0.0
+            } else {
+                self.fills.iter().sum::<f32>() / self.fills.len() as f32
+            },
+            starved_decode: self.starved_decode,
+            starved_io: self.starved_io,
+        }
+    }
+}
+
+static HINTED: OnceLock<Mutex<Vec<String>>> = OnceLock::new();
+
+/// Logs, once per file however often its decoders restart, that `video`'s
+/// decoder keeps waiting on storage, naming the filesystem it lives on.
+pub fn hint_slow_storage(video: &str) {
+    let Ok(mut hinted) = HINTED.get_or_init(|| Mutex::new(Vec::new())).lock() else {
+        return;
+    };
+    if hinted.iter().any(|path| path == video) {
+        return;
+    }
+    hinted.push(video.to_string());
+    let filesystem = mount_of(Path::new(video)).map_or_else(
+        || "an unknown filesystem".to_string(),
+        |(dir, fstype, device)| format!("{dir} ({fstype}, {device})"),
+    );
+    eprintln!(
+        "[rendercore] warning: the decoder for {} starved {HINT_EVENTS} times within {}s waiting on {filesystem}; a disk that spins down or a network share stalls playback, a copy on local storage avoids it",
+        display_path(video),
+        HINT_WINDOW.as_secs()
+    );
+}
+
+/// The `/proc/self/mounts` entry holding `path`: mount point, filesystem
+/// type and device. Only the path's text is matched, nothing on the
+/// (possibly sleeping) filesystem is touched.
+fn mount_of(path: &Path) -> Option<(String, String, String)> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let device = unescape_mount(fields.next()?);
+            let dir = unescape_mount(fields.next()?);
+            let fstype = fields.next()?.to_string();
+            Some((dir, fstype, device))
+        })
+        .filter(|(dir, _, _)| path.starts_with(dir))
+        .max_by_key(|(dir, _, _)| dir.len())
+}
+
+/// Mount fields escape space, tab, newline and backslash as octal (`\040`).
+fn unescape_mount(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|d| d.iter().all(|b| (b'0'..=b'7').contains(b)));
+        match (bytes[i], octal) {
+            (b'\\', Some(digits)) => {
+                out.push(
+                    digits
+                        .iter()
+                        .fold(0u8, |acc, d| acc.wrapping_mul(8) + (d - b'0')),
+                );
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn long_gaps_are_io_past_four_intervals_or_200ms() {
+        let start = Instant::now();
+        let fast = Duration::from_millis(10);
+        let slow = Duration::from_millis(100);
+        let decode = Arrival::Starved(Starvation::Decode);
+        let io = Arrival::Starved(Starvation::Io);
+        assert_eq!(classify(start, start + 199 * MS, 0, fast), decode);
+        assert_eq!(classify(start, start + 200 * MS, 0, fast), io);
+        assert_eq!(classify(start, start + 399 * MS, 0, slow), decode);
+        assert_eq!(classify(start, start + 400 * MS, 0, slow), io);
+        // Bytes already queued mean the decoder was ahead, however long the
+        // rest of the frame took.
+        assert_eq!(classify(start, start + 1000 * MS, 1, fast), Arrival::Ready);
+    }
+
+    #[test]
+    fn three_io_starvations_within_a_minute_ask_for_a_hint() {
+        let interval = Duration::from_millis(33);
+        let start = Instant::now();
+        let mut arrival = FrameArrival::default();
+        let mut starve = |at: u64| {
+            let started = start + Duration::from_secs(at);
+            arrival.record(started, started + 500 * MS, 0, 4096, interval)
+        };
+        assert!(!starve(0));
+        assert!(!starve(30));
+        // The first starvation is 61s back and has left the window.
+        assert!(!starve(61));
+        assert!(starve(90));
+
+        let mut arrival = FrameArrival::default();
+        arrival.record(start, start + 500 * MS, 0, 4096, interval);
+        arrival.record(start, start + 10 * MS, 0, 4096, interval);
+        arrival.record(start, start + 600 * MS, 0, 4096, interval);
+        let stats = arrival.stats();
+        assert_eq!((stats.starved_decode, stats.starved_io), (1, 2));
+    }
+
+    #[test]
+    fn the_slow_storage_warning_is_logged_once_per_file() {
+        let video = format!("/nonexistent/krc-arrival-{}.mp4", std::process::id());
+        hint_slow_storage(&video);
+        hint_slow_storage(&video);
+        let hinted = HINTED.get().unwrap().lock().unwrap();
+        assert_eq!(hinted.iter().filter(|path| **path == video).count(), 1);
+    }
+
+    #[test]
+    fn p95_ranks_small_samples_up() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(33);
+        let mut arrival = FrameArrival::default();
+        assert_eq!(arrival.stats().p95, None);
+        arrival.record(start, start + 7 * MS, 1, 10, interval);
+        assert_eq!(arrival.stats().p95, Some(7 * MS));
+        for wait in 1..=19 {
+            arrival.record(start, start + wait * MS, 1, 10, interval);
+        }
+        // 20 samples: rank ceil(20 * 0.95) = 19, one below the largest.
+        assert_eq!(arrival.stats().samples, 20);
+        assert_eq!(arrival.stats().p95, Some(18 * MS));
+    }
+
+    #[test]
+    fn mount_fields_unescape_octal() {
+        assert_eq!(unescape_mount("/mnt/my\\040share"), "/mnt/my share");
+        assert_eq!(unescape_mount("a\\011b\\134c"), "a\tb\\c");
+        // A backslash not followed by three octal digits stays as it is.
+        assert_eq!(unescape_mount("a\\09b\\"), "a\\09b\\");
+    }
+}
@@ -1,8 +1,54 @@
-use std::io::{ErrorKind, Read};
-use std::path::Path;
-use std::process::{Child, ChildStdout, Command, Stdio};
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::sync::mpsc::{Receiver, TryRecvError, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, Copy)]
+use crate::bench::DecodeBench;
+use crate::config;
+use crate::env_file;
+use crate::hw_decode::{HwAccel, HwDecode};
+use crate::instance::send_signal;
+use crate::log_limit;
+use crate::monitor::ArrivalStats;
+use crate::path_probe::simulate_slow_fs;
+use crate::priority::DecodePriority;
+use crate::user_path::display_path;
+use crate::video_map::{StillFrame, Trim};
+
+#[cfg(feature = "video-ffmpeg")]
+use {
+    crate::frame_arrival::{self, FrameArrival},
+    crate::hw_decode,
+    crate::journal::{self, Value},
+    crate::priority::process_nice,
+    crate::timebase,
+    crate::tools::{Tool, probe_duration_ms, probe_video_size},
+    crate::user_path::display_path_short,
+    std::collections::HashMap,
+    std::io::Read,
+    std::os::fd::AsRawFd,
+    std::process::{Child, ChildStdout, Stdio},
+    std::sync::OnceLock,
+};
+
+#[cfg(not(feature = "video-ffmpeg"))]
+use crate::version::NO_VIDEO_SUPPORT;
+
+/// Where a decoder's first pass starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartAt {
+    /// The start of the trim window, or of the file.
+    Beginning,
+    /// This far into the file: a restart that preserved its state.
+    Resume(u64),
+    /// Where the `utc` timebase puts the video when the decoder spawns; the
+    /// decoder then reports how far it is off the clock, see
+    /// [`FrameSource::clock_lead_ms`].
+    Clock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct VideoOptions {
     pub fps: u32,
     pub speed: f32,
@@ -11,98 +57,575 @@ pub struct VideoOptions {
 
 impl VideoOptions {
     pub fn from_env() -> Self {
-        let fps = std::env::var("KRC_VIDEO_FPS")
-            .ok()
-            .and_then(|v| v.parse::<u32>().ok())
-            .filter(|v| *v > 0)
-            .unwrap_or(30);
-        let speed = std::env::var("KRC_VIDEO_SPEED")
-            .ok()
-            .and_then(|v| v.parse::<f32>().ok())
-            .filter(|v| *v > 0.0)
-            .unwrap_or(1.0);
+        let fps = config::video_fps(env_file::var("KRC_VIDEO_FPS").as_deref());
+        let speed = config::video_speed(env_file::var("KRC_VIDEO_SPEED").as_deref());
+        // Read again on every env file reload; one line a window is enough.
+        for warning in [&fps.warning, &speed.warning].into_iter().flatten() {
+            log_limit::eprint_limited("config", warning, || {
+                format!("[rendercore] warning: {warning}")
+            });
+        }
         let hwaccel = HwAccel::from_env();
         Self {
-            fps,
-            speed,
+            fps: fps.value,
+            speed: speed.value,
             hwaccel,
         }
     }
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum HwAccel {
-    Auto,
-    None,
-    Nvdec,
-    Vaapi,
-}
 
-impl HwAccel {
-    fn from_env() -> Self {
-        match std::env::var("KRC_HWACCEL")
-            .ok()
-            .map(|v| v.to_ascii_lowercase())
-            .as_deref()
-        {
-            Some("none") => Self::None,
-            Some("nvdec") | Some("cuda") => Self::Nvdec,
-            Some("vaapi") => Self::Vaapi,
-            _ => Self::Auto,
-        }
+    /// Time each decoded frame stays on screen. A slowdown holds frames
+    /// longer instead of having ffmpeg repeat them: at speed 0.1 the decoder
+    /// delivers `fps / 10` frames a second, each shown ten times as long.
+    pub fn frame_interval(&self) -> Duration {
+        let rate = self.fps.max(1) as f32 * self.speed.min(1.0);
+        Duration::from_secs_f32((1.0 / rate).max(0.001))
     }
 }
 
 pub enum FrameSource {
     None,
+    #[cfg(feature = "video-ffmpeg")]
     Ffmpeg(FfmpegSource),
+    /// A `still:` entry's frame is in the texture; nothing more to decode.
+    Still,
 }
 
 impl FrameSource {
     pub fn from_video_path(
         video_path: String,
+        trim: Option<Trim>,
         width: u32,
         height: u32,
         options: VideoOptions,
-    ) -> Self {
-        if !Path::new(&video_path).exists() {
-            eprintln!("[rendercore] video path does not exist: {video_path}");
-            return Self::None;
+    ) -> Result<Self, String> {
+        video_metadata(&video_path)?;
+        Self::spawn(video_path, trim, StartAt::Beginning, width, height, options)
+    }
+
+    #[cfg(feature = "video-ffmpeg")]
+    fn spawn(
+        video_path: String,
+        trim: Option<Trim>,
+        start: StartAt,
+        width: u32,
+        height: u32,
+        options: VideoOptions,
+    ) -> Result<Self, String> {
+        FfmpegSource::new(video_path, trim, start, width, height, options)
+            .map(Self::Ffmpeg)
+            .map_err(|err| format!("ffmpeg source disabled: {err}"))
+    }
+
+    #[cfg(not(feature = "video-ffmpeg"))]
+    fn spawn(
+        _video_path: String,
+        _trim: Option<Trim>,
+        _start: StartAt,
+        _width: u32,
+        _height: u32,
+        _options: VideoOptions,
+    ) -> Result<Self, String> {
+        Err(NO_VIDEO_SUPPORT.to_string())
+    }
+
+    pub fn is_video(&self) -> bool {
+        match self {
+            Self::None | Self::Still => false,
+            #[cfg(feature = "video-ffmpeg")]
+            Self::Ffmpeg(_) => true,
         }
+    }
 
-        match FfmpegSource::new(
-            video_path,
-            width,
-            height,
-            options.fps,
-            options.speed,
-            options.hwaccel,
-        ) {
-            Ok(source) => Self::Ffmpeg(source),
-            Err(err) => {
-                eprintln!("[rendercore] ffmpeg source disabled: {err}");
-                Self::None
-            }
+    pub fn is_still(&self) -> bool {
+        matches!(self, Self::Still)
+    }
+
+    /// The decoder's current nice value, read back from the kernel.
+    pub fn decoder_nice(&self) -> Option<i32> {
+        match self {
+            Self::None | Self::Still => None,
+            #[cfg(feature = "video-ffmpeg")]
+            Self::Ffmpeg(source) => process_nice(source.child.id()),
         }
     }
 
-    pub fn fill_next_frame(&mut self, dst: &mut [u8]) -> bool {
+    /// The running ffmpeg's pid, for its CPU and memory use in status.
+    pub fn decoder_pid(&self) -> Option<u32> {
         match self {
-            Self::None => false,
-            Self::Ffmpeg(source) => {
-                if let Err(err) = source.fill_next_frame(dst) {
-                    eprintln!("[rendercore] ffmpeg frame read failed: {err}");
-                    false
-                } else {
-                    true
+            Self::None | Self::Still => None,
+            #[cfg(feature = "video-ffmpeg")]
+            Self::Ffmpeg(source) => Some(source.child.id()),
+        }
+    }
+
+    /// Whether the decoder really decodes on the GPU; see [`hw_decode::verdict`].
+    pub fn hw_decode(&self) -> Option<HwDecode> {
+        match self {
+            Self::None | Self::Still => None,
+            #[cfg(feature = "video-ffmpeg")]
+            Self::Ffmpeg(source) => Some(hw_decode::verdict(&source.video_path, source.hwaccel)),
+        }
+    }
+
+    /// How the decoder's frames have been arriving; see [`FrameArrival`].
+    pub fn frame_arrival(&self) -> Option<ArrivalStats> {
+        match self {
+            Self::None | Self::Still => None,
+            #[cfg(feature = "video-ffmpeg")]
+            Self::Ffmpeg(source) => Some(source.arrival.stats()),
+        }
+    }
+
+    /// Roughly where in the video the decoder is, in milliseconds of the
+    /// file: counted from the frames read, so it runs ahead by what the pipe
+    /// holds. `None` without a decoder.
+    pub fn playback_ms(&self) -> Option<u64> {
+        match self {
+            Self::None | Self::Still => None,
+            #[cfg(feature = "video-ffmpeg")]
+            Self::Ffmpeg(source) => Some(source.playback_ms()),
+        }
+    }
+
+    /// For a decoder opened at [`StartAt::Clock`]: how far the next frame it
+    /// delivers is ahead of where `clock` puts the video, in milliseconds
+    /// (negative: behind). `None` for any other source.
+    #[cfg_attr(not(feature = "video-ffmpeg"), allow(unused_variables))]
+    pub fn clock_lead_ms(&self, clock: Duration) -> Option<i64> {
+        match self {
+            Self::None | Self::Still => None,
+            #[cfg(feature = "video-ffmpeg")]
+            Self::Ffmpeg(source) => source.clock_lead_ms(clock),
+        }
+    }
+
+    /// Opens a decoder like [`FrameSource::from_video_path`], but entirely on
+    /// a helper thread (path check, spawn and first frame), so the caller can
+    /// keep showing what it had until [`WarmSource::poll`] hands over a decoder
+    /// that is producing. A path on a share that stopped answering only parks
+    /// that thread. With `still`, the one frame is extracted instead and
+    /// handed over as [`FrameSource::Still`]. `start` picks where the first
+    /// pass starts; the decoder loops or restarts its trim window as usual
+    /// after it.
+    pub fn warm(
+        video_path: String,
+        trim: Option<Trim>,
+        still: Option<StillFrame>,
+        start: StartAt,
+        width: u32,
+        height: u32,
+        options: VideoOptions,
+    ) -> Result<WarmSource, String> {
+        let (sender, receiver) = sync_channel(1);
+        let child = Arc::new(Mutex::new(WarmChild::Opening));
+        let thread_child = Arc::clone(&child);
+        std::thread::Builder::new()
+            .name("krc-warm-decoder".to_string())
+            .spawn(move || {
+                if let Some(priority) = DecodePriority::get() {
+                    priority.apply_to_current_thread();
                 }
+                let _ = sender.send(warm_up(
+                    video_path,
+                    trim,
+                    still,
+                    start,
+                    (width, height),
+                    options,
+                    &thread_child,
+                ));
+            })
+            .map_err(|e| format!("failed to start decoder warm-up thread: {e}"))?;
+        Ok(WarmSource {
+            child,
+            started_at: Instant::now(),
+            receiver,
+            handed_over: false,
+        })
+    }
+
+    /// Returns `Ok(false)` when there is no decoder (procedural fallback or a
+    /// still frame).
+    #[cfg_attr(not(feature = "video-ffmpeg"), allow(unused_variables))]
+    pub fn fill_next_frame(&mut self, dst: &mut [u8]) -> Result<bool, String> {
+        match self {
+            Self::None | Self::Still => Ok(false),
+            #[cfg(feature = "video-ffmpeg")]
+            Self::Ffmpeg(source) => source
+                .fill_next_frame(dst)
+                .map(|()| true)
+                .map_err(|err| format!("ffmpeg frame read failed: {err}")),
+        }
+    }
+}
+
+/// Reads `streams` decoders of `video` side by side as fast as their pipes
+/// deliver, each for `duration` after its first frame. A mode the machine
+/// lacks (no CUDA, no VA-API device) fails on the first frame.
+pub fn decode_throughput(
+    video: &str,
+    width: u32,
+    height: u32,
+    options: VideoOptions,
+    streams: u32,
+    duration: Duration,
+) -> Result<DecodeBench, String> {
+    let frame_len = width as usize * height as usize * 4;
+    let workers = (0..streams.max(1))
+        .map(|_| {
+            let spawned_at = Instant::now();
+            let mut source =
+                FrameSource::from_video_path(video.to_string(), None, width, height, options)?;
+            std::thread::Builder::new()
+                .name("krc-bench-decode".to_string())
+                .spawn(move || {
+                    let mut pixels = vec![0u8; frame_len];
+                    source.fill_next_frame(&mut pixels)?;
+                    let first_frame = spawned_at.elapsed();
+                    let started = Instant::now();
+                    let mut frames = 0u64;
+                    while started.elapsed() < duration {
+                        source.fill_next_frame(&mut pixels)?;
+                        frames += 1;
+                    }
+                    Ok((frames as f64 / started.elapsed().as_secs_f64(), first_frame))
+                })
+                .map_err(|e| format!("failed to start decode bench thread: {e}"))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut results = Vec::with_capacity(workers.len());
+    for worker in workers {
+        let result: Result<(f64, Duration), String> = worker
+            .join()
+            .map_err(|_| "decode bench thread panicked".to_string())?;
+        results.push(result?);
+    }
+    Ok(DecodeBench {
+        total_fps: results.iter().map(|(fps, _)| fps).sum(),
+        min_stream_fps: results
+            .iter()
+            .map(|(fps, _)| *fps)
+            .fold(f64::INFINITY, f64::min),
+        first_frame: results
+            .iter()
+            .map(|(_, first)| *first)
+            .max()
+            .unwrap_or_default(),
+    })
+}
+
+/// What [`WarmSource::poll`] found.
+pub enum WarmPoll {
+    /// No complete frame yet.
+    Pending,
+    /// The decoder, its first frame, and the video file as it was opened.
+    Ready(FrameSource, Vec<u8>, Metadata),
+    /// The path does not exist right now or its filesystem did not answer:
+    /// worth retrying later, unlike [`WarmPoll::Failed`].
+    Missing(String),
+    /// The decoder failed; the file's metadata when it was there.
+    Failed(String, Option<Metadata>),
+}
+
+/// Where the warm-up thread is, so dropping a [`WarmSource`] can stop it.
+#[cfg_attr(not(feature = "video-ffmpeg"), allow(dead_code))]
+enum WarmChild {
+    /// Checking the path or spawning the decoder.
+    Opening,
+    /// Waiting for the first frame of this decoder pid.
+    Running(u32),
+    /// The thread no longer holds a live decoder it waits on.
+    Finished,
+    /// The [`WarmSource`] was dropped; a decoder spawned now is dropped too.
+    Cancelled,
+}
+
+/// A decoder being opened ahead of a switch, reading its first frame on a
+/// helper thread. Dropping it before the hand-over kills the decoder, which
+/// also unblocks the thread.
+pub struct WarmSource {
+    child: Arc<Mutex<WarmChild>>,
+    started_at: Instant,
+    receiver: Receiver<WarmPoll>,
+    handed_over: bool,
+}
+
+impl WarmSource {
+    pub fn poll(&mut self) -> WarmPoll {
+        if self.handed_over {
+            return WarmPoll::Failed("decoder already handed over".to_string(), None);
+        }
+        match self.receiver.try_recv() {
+            Ok(poll) => {
+                self.handed_over = true;
+                poll
             }
+            Err(TryRecvError::Empty) => WarmPoll::Pending,
+            Err(TryRecvError::Disconnected) => {
+                self.handed_over = true;
+                WarmPoll::Failed("decoder warm-up thread exited".to_string(), None)
+            }
+        }
+    }
+
+    pub fn waited(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Whether the thread is still on the path check or the spawn, i.e. the
+    /// filesystem has not answered yet.
+    pub fn opening(&self) -> bool {
+        self.child
+            .lock()
+            .is_ok_and(|child| matches!(*child, WarmChild::Opening))
+    }
+}
+
+impl Drop for WarmSource {
+    fn drop(&mut self) {
+        let Ok(mut child) = self.child.lock() else {
+            return;
+        };
+        // The thread reaps the child only after marking itself finished under
+        // this lock, so a pid seen running cannot have been reused yet.
+        if let WarmChild::Running(pid) = *child
+            && !self.handed_over
+        {
+            let _ = send_signal(pid, libc::SIGKILL);
         }
+        *child = WarmChild::Cancelled;
     }
 }
 
+/// The warm-up thread's work; see [`FrameSource::warm`].
+fn warm_up(
+    video_path: String,
+    trim: Option<Trim>,
+    still: Option<StillFrame>,
+    start: StartAt,
+    (width, height): (u32, u32),
+    options: VideoOptions,
+    child: &Mutex<WarmChild>,
+) -> WarmPoll {
+    let frame_len = width as usize * height as usize * 4;
+    simulate_slow_fs();
+    let file = match video_metadata(&video_path) {
+        Ok(file) => file,
+        Err(err) => return WarmPoll::Missing(err),
+    };
+    if let Some(still) = still {
+        return match extract_still(&video_path, still, (width, height), frame_len, child) {
+            Ok(pixels) => WarmPoll::Ready(FrameSource::Still, pixels, file),
+            Err(err) => WarmPoll::Failed(err, Some(file)),
+        };
+    }
+    match FrameSource::spawn(video_path, trim, start, width, height, options) {
+        #[cfg(feature = "video-ffmpeg")]
+        Ok(FrameSource::Ffmpeg(source)) => first_frame(source, frame_len, child, file),
+        Ok(FrameSource::None | FrameSource::Still) => {
+            WarmPoll::Failed("no decoder to warm up".to_string(), Some(file))
+        }
+        Err(err) => WarmPoll::Failed(err, Some(file)),
+    }
+}
+
+/// Reads the first frame of a decoder the warm-up thread spawned.
+#[cfg(feature = "video-ffmpeg")]
+fn first_frame(
+    mut source: FfmpegSource,
+    frame_len: usize,
+    child: &Mutex<WarmChild>,
+    file: Metadata,
+) -> WarmPoll {
+    match child.lock().as_deref_mut() {
+        Ok(state @ WarmChild::Opening) => *state = WarmChild::Running(source.child.id()),
+        // Dropped while the filesystem was slow: the decoder goes with `source`.
+        _ => return WarmPoll::Failed("decoder warm-up cancelled".to_string(), None),
+    }
+    let mut pixels = vec![0u8; frame_len];
+    // No restart on EOF here, so the pid stays the one to kill.
+    let first = source
+        .stdout
+        .read_exact(&mut pixels)
+        .map_err(|e| format!("failed to read the first frame: {e}"));
+    if let Ok(mut state) = child.lock()
+        && matches!(*state, WarmChild::Running(_))
+    {
+        *state = WarmChild::Finished;
+    }
+    if first.is_ok() {
+        source.frames += 1;
+    }
+    match first {
+        Ok(()) => WarmPoll::Ready(FrameSource::Ffmpeg(source), pixels, file),
+        Err(err) => WarmPoll::Failed(err, Some(file)),
+    }
+}
+
+/// Decodes the one frame a `still:` entry shows, scaled and cropped like the
+/// decoder's frames. The default time (10% in) needs the clip's length from
+/// ffprobe; without it the first frame is taken.
+#[cfg(feature = "video-ffmpeg")]
+fn extract_still(
+    video_path: &str,
+    still: StillFrame,
+    (width, height): (u32, u32),
+    frame_len: usize,
+    child: &Mutex<WarmChild>,
+) -> Result<Vec<u8>, String> {
+    let at_ms = match still.at_ms {
+        Some(at_ms) => at_ms,
+        None => match probe_duration_ms(video_path) {
+            Ok(duration_ms) => duration_ms / 10,
+            Err(err) => {
+                println!(
+                    "[rendercore] still frame of {}: {err}; taking the first frame",
+                    display_path_short(video_path)
+                );
+                0
+            }
+        },
+    };
+    let at = StillFrame { at_ms: Some(at_ms) };
+    let seek = format!("{:.3}", at_ms as f64 / 1000.0);
+    let vf = format!(
+        "scale={width}:{height}:force_original_aspect_ratio=increase,crop={width}:{height}"
+    );
+    let mut command = Tool::Ffmpeg.command();
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-ss", &seek, "-i"])
+        .arg(video_path)
+        .args([
+            "-an",
+            "-sn",
+            "-dn",
+            "-frames:v",
+            "1",
+            "-vf",
+            &vf,
+            "-pix_fmt",
+            "rgba",
+            "-f",
+            "rawvideo",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if let Some(priority) = DecodePriority::get() {
+        priority.apply_to_child(&mut command);
+    }
+    let mut process = command
+        .spawn()
+        .map_err(|err| Tool::Ffmpeg.spawn_error(err))?;
+    let registered = match child.lock().as_deref_mut() {
+        Ok(state @ WarmChild::Opening) => {
+            *state = WarmChild::Running(process.id());
+            true
+        }
+        _ => false,
+    };
+    let mut pixels = vec![0u8; frame_len];
+    let read = match (registered, process.stdout.take()) {
+        (false, _) => Err("still frame extraction cancelled".to_string()),
+        (true, None) => Err("ffmpeg stdout is not piped".to_string()),
+        (true, Some(mut stdout)) => {
+            stdout
+                .read_exact(&mut pixels)
+                .map_err(|err| match err.kind() {
+                    ErrorKind::UnexpectedEof => {
+                        format!("ffmpeg produced no frame at {at} (past the end of the video?)")
+                    }
+                    _ => format!("failed to read the still frame: {err}"),
+                })
+        }
+    };
+    if let Ok(mut state) = child.lock()
+        && matches!(*state, WarmChild::Running(_))
+    {
+        *state = WarmChild::Finished;
+    }
+    let _ = process.kill();
+    let _ = process.wait();
+    read?;
+    println!(
+        "[rendercore] still frame path={} at={at} target={width}x{height}",
+        display_path_short(video_path)
+    );
+    Ok(pixels)
+}
+
+#[cfg(not(feature = "video-ffmpeg"))]
+fn extract_still(
+    _video_path: &str,
+    _still: StillFrame,
+    _size: (u32, u32),
+    _frame_len: usize,
+    _child: &Mutex<WarmChild>,
+) -> Result<Vec<u8>, String> {
+    Err(NO_VIDEO_SUPPORT.to_string())
+}
+
+#[cfg(feature = "video-ffmpeg")]
+type SizeCache = Mutex<HashMap<String, Option<(u32, u32)>>>;
+
+/// The video's own size, for `crop=` entries that decode the whole frame.
+/// The first call for a video probes it on a helper thread and returns `None`,
+/// as does a video ffprobe cannot read (logged once).
+#[cfg(feature = "video-ffmpeg")]
+pub fn native_size(video: &str) -> Option<(u32, u32)> {
+    static SIZES: OnceLock<SizeCache> = OnceLock::new();
+    let cache = SIZES.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut sizes = cache.lock().ok()?;
+    if let Some(known) = sizes.get(video) {
+        return *known;
+    }
+    sizes.insert(video.to_string(), None);
+    drop(sizes);
+    let video = video.to_string();
+    let spawned = std::thread::Builder::new()
+        .name("krc-size-probe".to_string())
+        .spawn(move || match probe_video_size(&video) {
+            Ok(size) => {
+                if let Ok(mut sizes) = cache.lock() {
+                    sizes.insert(video, Some(size));
+                }
+            }
+            Err(err) => println!(
+                "[rendercore] warning: cannot read the size of {}, crop= is ignored: {err}",
+                display_path_short(&video)
+            ),
+        });
+    if let Err(err) = spawned {
+        eprintln!("[rendercore] failed to start size probe: {err}");
+    }
+    None
+}
+
+/// Without `video-ffmpeg` there is no ffprobe to ask; `crop=` entries are
+/// never decoded anyway.
+#[cfg(not(feature = "video-ffmpeg"))]
+pub fn native_size(_video: &str) -> Option<(u32, u32)> {
+    None
+}
+
+/// The video file's metadata, or why it cannot be opened right now.
+fn video_metadata(video_path: &str) -> Result<Metadata, String> {
+    std::fs::metadata(video_path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => format!("video path does not exist: {}", display_path(video_path)),
+        _ => format!(
+            "video path unavailable: {}: {err}",
+            display_path(video_path)
+        ),
+    })
+}
+
+#[cfg(feature = "video-ffmpeg")]
 pub struct FfmpegSource {
     video_path: String,
+    /// Window decoded with `-ss`/`-t`; the decoder is restarted at its end
+    /// instead of looping the whole file with `-stream_loop`.
+    trim: Option<Trim>,
     width: u32,
     height: u32,
     fps: u32,
@@ -110,24 +633,188 @@ pub struct FfmpegSource {
     hwaccel: HwAccel,
     child: Child,
     stdout: ChildStdout,
+    /// Boxed: its windows would double the size of every `FrameSource`.
+    arrival: Box<FrameArrival>,
+    /// Where in the file the running decoder started, and the frames read
+    /// from it since; see [`FfmpegSource::playback_ms`].
+    base_ms: u64,
+    frames: u64,
+    /// The running decoder started mid-file to resume a restart; its end is
+    /// expected, not a decoder that stopped looping.
+    resumed: bool,
+    /// Set when the decoder opened at [`StartAt::Clock`].
+    clock_window: Option<ClockWindow>,
+}
+
+/// The loop a decoder following the `utc` clock is paced against.
+#[cfg(feature = "video-ffmpeg")]
+#[derive(Debug, Clone, Copy)]
+struct ClockWindow {
+    start_ms: u64,
+    length_ms: u64,
+}
+
+/// What one decoder run covers of the file.
+#[cfg(feature = "video-ffmpeg")]
+#[derive(Debug, Clone, Copy)]
+enum Window {
+    /// The whole file, looped by ffmpeg.
+    Loop,
+    /// From `from_ms` for `duration_ms` (to the end without), then EOF.
+    Span {
+        from_ms: u64,
+        duration_ms: Option<u64>,
+    },
+}
+
+#[cfg(feature = "video-ffmpeg")]
+impl Window {
+    fn for_trim(trim: Option<Trim>) -> Self {
+        match trim {
+            Some(trim) => Self::Span {
+                from_ms: trim.start_ms.into(),
+                duration_ms: trim.duration_ms().map(u64::from),
+            },
+            None => Self::Loop,
+        }
+    }
+
+    /// The rest of the trim window (or the file) from `at_ms`; `None` when
+    /// that point is outside it, or the file's length to wrap it into is
+    /// unknown.
+    fn resume(video_path: &str, trim: Option<Trim>, at_ms: u64) -> Option<Self> {
+        match trim {
+            Some(trim) => {
+                let end_ms = trim.end_ms.map(u64::from);
+                (at_ms >= u64::from(trim.start_ms) && end_ms.is_none_or(|end| at_ms < end)).then(
+                    || Self::Span {
+                        from_ms: at_ms,
+                        duration_ms: end_ms.map(|end| end - at_ms),
+                    },
+                )
+            }
+            None => {
+                let length = u64::from(probe_duration_ms(video_path).ok()?);
+                (length > 0).then(|| Self::Span {
+                    from_ms: at_ms % length,
+                    duration_ms: None,
+                })
+            }
+        }
+    }
+
+    /// The rest of the loop from where `clock` puts the video, with the
+    /// loop itself; `None` when the file's length (without a trim end) is
+    /// unknown or the window is empty.
+    fn at_clock(
+        video_path: &str,
+        trim: Option<Trim>,
+        clock: Duration,
+        speed: f32,
+    ) -> Option<(Self, ClockWindow)> {
+        let start_ms = trim.map_or(0, |trim| u64::from(trim.start_ms));
+        let trim_end = trim.and_then(|trim| trim.end_ms).map(u64::from);
+        let end_ms = match trim_end {
+            Some(end) => end,
+            None => u64::from(probe_duration_ms(video_path).ok()?),
+        };
+        let length_ms = end_ms.checked_sub(start_ms).filter(|length| *length > 0)?;
+        let from_ms = timebase::video_position_ms(clock, speed, start_ms, length_ms);
+        let window = Self::Span {
+            from_ms,
+            duration_ms: trim_end.map(|end| end - from_ms),
+        };
+        Some((
+            window,
+            ClockWindow {
+                start_ms,
+                length_ms,
+            },
+        ))
+    }
+
+    fn start_ms(self) -> u64 {
+        match self {
+            Self::Loop => 0,
+            Self::Span { from_ms, .. } => from_ms,
+        }
+    }
 }
 
+#[cfg(feature = "video-ffmpeg")]
 impl FfmpegSource {
     fn new(
         video_path: String,
+        trim: Option<Trim>,
+        start: StartAt,
         width: u32,
         height: u32,
-        fps: u32,
-        speed: f32,
-        hwaccel: HwAccel,
+        options: VideoOptions,
     ) -> Result<Self, String> {
-        let (child, stdout) = spawn_ffmpeg(&video_path, width, height, fps, speed, hwaccel)?;
+        let VideoOptions {
+            fps,
+            speed,
+            hwaccel,
+        } = options;
+        let mut clock_window = None;
+        let resume = match start {
+            StartAt::Beginning => None,
+            StartAt::Resume(at_ms) => {
+                let window = Window::resume(&video_path, trim, at_ms);
+                if window.is_none() {
+                    println!(
+                        "[rendercore] not resuming {} at {at_ms}ms: outside its trim window or length unknown",
+                        display_path_short(&video_path)
+                    );
+                }
+                window
+            }
+            StartAt::Clock => {
+                let aligned = Window::at_clock(&video_path, trim, timebase::wall_clock(), speed);
+                if aligned.is_none() {
+                    println!(
+                        "[rendercore] {} does not follow the utc clock: length unknown or trim window empty",
+                        display_path_short(&video_path)
+                    );
+                }
+                aligned.map(|(window, loop_window)| {
+                    clock_window = Some(loop_window);
+                    window
+                })
+            }
+        };
+        let window = resume.unwrap_or(Window::for_trim(trim));
+        let (child, stdout) =
+            spawn_ffmpeg(&video_path, window, width, height, fps, speed, hwaccel)?;
+        // Starts the one-time probe, so status has a verdict by the time it is asked.
+        hw_decode::verdict(&video_path, hwaccel);
         println!(
-            "[rendercore] ffmpeg source enabled path={} target={}x{}@{} speed={} hwaccel={:?}",
-            video_path, width, height, fps, speed, hwaccel
+            "[rendercore] ffmpeg source enabled path={} target={}x{}@{} speed={} hwaccel={:?}{}",
+            display_path_short(&video_path),
+            width,
+            height,
+            fps,
+            speed,
+            hwaccel,
+            trim.map(|trim| format!(" trim={trim}")).unwrap_or_default()
         );
+        match (resume, clock_window) {
+            (Some(_), Some(loop_window)) => println!(
+                "[rendercore] {} follows the utc clock: starting at {}ms of a {}ms loop",
+                display_path_short(&video_path),
+                window.start_ms() - loop_window.start_ms,
+                loop_window.length_ms
+            ),
+            (Some(_), None) => println!(
+                "[rendercore] resuming {} at {}ms",
+                display_path_short(&video_path),
+                window.start_ms()
+            ),
+            _ => {}
+        }
         Ok(Self {
             video_path,
+            trim,
             width,
             height,
             fps,
@@ -135,14 +822,54 @@ impl FfmpegSource {
             hwaccel,
             child,
             stdout,
+            arrival: Box::default(),
+            base_ms: window.start_ms(),
+            frames: 0,
+            resumed: resume.is_some(),
+            clock_window,
         })
     }
 
+    /// Milliseconds of the file the frames read so far cover. A speedup
+    /// drops frames, so each one read stands for `speed / fps` seconds; a
+    /// slowdown only holds them longer on screen. Past the end of a looped
+    /// file it keeps counting.
+    fn playback_ms(&self) -> u64 {
+        let per_frame = 1000.0 * f64::from(self.speed.max(1.0)) / f64::from(self.fps.max(1));
+        self.base_ms + (self.frames as f64 * per_frame) as u64
+    }
+
+    /// See [`FrameSource::clock_lead_ms`]. A looped file keeps counting past
+    /// its end, so the position is wrapped into the loop first.
+    fn clock_lead_ms(&self, clock: Duration) -> Option<i64> {
+        let ClockWindow {
+            start_ms,
+            length_ms,
+        } = self.clock_window?;
+        let position = start_ms + self.playback_ms().saturating_sub(start_ms) % length_ms;
+        let target = timebase::video_position_ms(clock, self.speed, start_ms, length_ms);
+        Some(timebase::lead_ms(position, target, length_ms))
+    }
+
     fn restart(&mut self) -> Result<(), String> {
+        // A trimmed window ends every loop by design, a resumed pass once;
+        // only a whole-file decoder ending (it loops forever otherwise) is
+        // worth a journal line.
+        if self.trim.is_none() && !self.resumed {
+            journal::record(
+                "decoder_restart",
+                &[
+                    ("video", Value::Str(&self.video_path)),
+                    ("reason", Value::Str("eof")),
+                ],
+            );
+        }
         let _ = self.child.kill();
         let _ = self.child.wait();
+        let window = Window::for_trim(self.trim);
         let (child, stdout) = spawn_ffmpeg(
             &self.video_path,
+            window,
             self.width,
             self.height,
             self.fps,
@@ -151,24 +878,40 @@ impl FfmpegSource {
         )?;
         self.child = child;
         self.stdout = stdout;
+        self.base_ms = window.start_ms();
+        self.frames = 0;
+        self.resumed = false;
         Ok(())
     }
 
     fn fill_next_frame(&mut self, dst: &mut [u8]) -> Result<(), String> {
+        let started = Instant::now();
+        let (queued, capacity) = pipe_backlog(&self.stdout);
         if let Err(err) = self.stdout.read_exact(dst) {
             if err.kind() == ErrorKind::UnexpectedEof || err.kind() == ErrorKind::BrokenPipe {
+                // Not timed: a fresh decoder's first frame waits on its start.
                 self.restart()?;
                 self.stdout
                     .read_exact(dst)
                     .map_err(|e| format!("failed to read frame after restart: {e}"))?;
+                self.frames += 1;
                 return Ok(());
             }
             return Err(format!("failed to read ffmpeg frame: {err}"));
         }
+        self.frames += 1;
+        let interval = Duration::from_secs_f32(1.0 / self.fps.max(1) as f32);
+        if self
+            .arrival
+            .record(started, Instant::now(), queued, capacity, interval)
+        {
+            frame_arrival::hint_slow_storage(&self.video_path);
+        }
         Ok(())
     }
 }
 
+#[cfg(feature = "video-ffmpeg")]
 impl Drop for FfmpegSource {
     fn drop(&mut self) {
         let _ = self.child.kill();
@@ -176,53 +919,207 @@ impl Drop for FfmpegSource {
     }
 }
 
+/// Bytes waiting in the decoder's pipe and the pipe's size. A frame is far
+/// larger than the pipe, so this says whether ffmpeg is ahead (blocked on a
+/// full pipe) or behind (pipe empty), not how many frames are buffered.
+#[cfg(feature = "video-ffmpeg")]
+fn pipe_backlog(stdout: &ChildStdout) -> (usize, usize) {
+    let fd = stdout.as_raw_fd();
+    let mut pending: libc::c_int = 0;
+    // SAFETY: `fd` is the open pipe `stdout` borrows for the whole call, and
+    // FIONREAD writes a single c_int through the pointer, which outlives it.
+    let queued = match unsafe { libc::ioctl(fd, libc::FIONREAD, &mut pending) } {
+        0 => pending.max(0) as usize,
+        _ => 0,
+    };
+    // SAFETY: F_GETPIPE_SZ takes no argument and only reads the pipe's size;
+    // on a descriptor that is not a pipe it fails with -1, clamped to 0.
+    let capacity = unsafe { libc::fcntl(fd, libc::F_GETPIPE_SZ) };
+    (queued, capacity.max(0) as usize)
+}
+
+/// The `-vf` graph: retime, resample to `fps`, then cover-scale and crop to
+/// `width`x`height`. Only a speedup retimes in ffmpeg (`setpts` drops the
+/// frames in between); a slowdown keeps the source timing and is played by
+/// the renderer holding each frame longer, see [`VideoOptions::frame_interval`].
+#[cfg(feature = "video-ffmpeg")]
+fn video_filter(width: u32, height: u32, fps: u32, speed: f32) -> String {
+    let retime = if speed > 1.0 {
+        format!("setpts=PTS/{speed:.4},")
+    } else {
+        String::new()
+    };
+    format!(
+        "{retime}fps={fps},scale={width}:{height}:force_original_aspect_ratio=increase,crop={width}:{height}"
+    )
+}
+
+#[cfg(feature = "video-ffmpeg")]
 fn spawn_ffmpeg(
     video_path: &str,
+    window: Window,
     width: u32,
     height: u32,
     fps: u32,
     speed: f32,
     hwaccel: HwAccel,
 ) -> Result<(Child, ChildStdout), String> {
-    let vf = format!(
-        "setpts=PTS/{speed:.4},fps={fps},scale={width}:{height}:force_original_aspect_ratio=increase,crop={width}:{height}"
-    );
+    let vf = video_filter(width, height, fps, speed);
 
     let mut args = vec!["-hide_banner", "-loglevel", "error"];
-    match hwaccel {
-        HwAccel::Auto => args.extend(["-hwaccel", "auto"]),
-        HwAccel::Nvdec => args.extend(["-hwaccel", "cuda"]),
-        HwAccel::Vaapi => args.extend(["-hwaccel", "vaapi"]),
-        HwAccel::None => {}
+    if let Some(hwaccel) = hwaccel.ffmpeg_arg() {
+        args.extend(["-hwaccel", hwaccel]);
+    }
+    // Input-side `-ss`/`-t` decode only the window (the seek is frame-accurate
+    // since the video is re-encoded to raw frames); speed applies after it.
+    let (span_start, span_duration);
+    match window {
+        Window::Span {
+            from_ms,
+            duration_ms,
+        } => {
+            span_start = format!("{:.3}", from_ms as f64 / 1000.0);
+            args.extend(["-ss", &span_start]);
+            if let Some(duration_ms) = duration_ms {
+                span_duration = format!("{:.3}", duration_ms as f64 / 1000.0);
+                args.extend(["-t", &span_duration]);
+            }
+        }
+        Window::Loop => args.extend(["-stream_loop", "-1"]),
     }
     args.extend([
-        "-stream_loop",
-        "-1",
-        "-i",
-        video_path,
-        "-an",
-        "-sn",
-        "-dn",
-        "-vf",
-        &vf,
-        "-pix_fmt",
-        "rgba",
-        "-f",
-        "rawvideo",
+        "-i", video_path, "-an", "-sn", "-dn", "-vf", &vf, "-pix_fmt", "rgba", "-f", "rawvideo",
         "-",
     ]);
 
-    let mut child = Command::new("ffmpeg")
+    let mut command = Tool::Ffmpeg.command();
+    command
         .args(args)
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
-        .stderr(Stdio::null())
+        .stderr(Stdio::null());
+    let priority = DecodePriority::get();
+    if let Some(priority) = priority {
+        priority.apply_to_child(&mut command);
+    }
+    let mut child = command
         .spawn()
-        .map_err(|err| format!("failed to spawn ffmpeg: {err}"))?;
+        .map_err(|err| Tool::Ffmpeg.spawn_error(err))?;
+    if let Some(priority) = priority
+        && let Some(nice) = process_nice(child.id())
+        && nice != priority.nice
+    {
+        println!(
+            "[rendercore] decoder pid {} runs at nice {nice}, not KRC_DECODE_NICE={} (the renderer may already be niced higher)",
+            child.id(),
+            priority.nice
+        );
+    }
 
-    let stdout = child
-        .stdout
-        .take()
-        .ok_or_else(|| "ffmpeg stdout is not piped".to_string())?;
+    let Some(stdout) = child.stdout.take() else {
+        // Every spawned decoder is killed and waited for, even on this path.
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err("ffmpeg stdout is not piped".to_string());
+    };
     Ok((child, stdout))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each `-vf` filter as (name, arguments), checking the graph is a plain
+    /// comma-separated chain with no empty or malformed links.
+    #[cfg(feature = "video-ffmpeg")]
+    fn filter_chain(vf: &str) -> Vec<(&str, &str)> {
+        vf.split(',')
+            .map(|filter| {
+                let (name, args) = filter
+                    .split_once('=')
+                    .unwrap_or_else(|| panic!("filter without arguments in {vf}"));
+                assert!(!name.is_empty() && !args.is_empty(), "{vf}");
+                assert!(!args.contains(' '), "{vf}");
+                (name, args)
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "video-ffmpeg")]
+    #[test]
+    fn video_filter_is_well_formed_across_the_clamped_range() {
+        let speeds = ["0.01", "0.1", "0.5", "1", "1.0001", "2", "10", "50", "NaN"];
+        let rates = ["0", "1", "30", "240", "1000"];
+        for raw_speed in speeds {
+            for raw_fps in rates {
+                let speed = config::video_speed(Some(raw_speed)).value;
+                let fps = config::video_fps(Some(raw_fps)).value;
+                let vf = video_filter(1920, 1080, fps, speed);
+                let chain = filter_chain(&vf);
+                let names = chain.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                if speed > 1.0 {
+                    assert_eq!(names, ["setpts", "fps", "scale", "crop"], "{vf}");
+                    let divisor = chain[0].1.strip_prefix("PTS/").unwrap();
+                    let divisor = divisor.parse::<f32>().unwrap();
+                    assert!((1.0..=10.0).contains(&divisor), "{vf}");
+                } else {
+                    // A slowdown is never retimed by ffmpeg.
+                    assert_eq!(names, ["fps", "scale", "crop"], "{vf}");
+                }
+                let rate = chain[names.len() - 3].1.parse::<u32>().unwrap();
+                assert!(config::VIDEO_FPS_RANGE.contains(&rate), "{vf}");
+                assert_eq!(
+                    &chain[names.len() - 2..],
+                    [
+                        ("scale", "1920:1080:force_original_aspect_ratio=increase"),
+                        ("crop", "1920:1080"),
+                    ]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn slowdowns_stretch_the_frame_interval_instead() {
+        let options = |fps, speed| VideoOptions {
+            fps,
+            speed,
+            hwaccel: HwAccel::None,
+        };
+        let interval = |fps, speed| options(fps, speed).frame_interval().as_secs_f64();
+        assert!((interval(30, 1.0) - 1.0 / 30.0).abs() < 1e-6);
+        // Speedups are retimed by ffmpeg and keep the output rate.
+        assert!((interval(30, 10.0) - 1.0 / 30.0).abs() < 1e-6);
+        assert!((interval(30, 0.1) - 1.0 / 3.0).abs() < 1e-6);
+        assert!((interval(1, 0.1) - 10.0).abs() < 1e-4);
+        // The interval never reaches zero, even for values the clamps keep out.
+        assert!(interval(0, 1.0) > 0.0);
+        assert!(interval(100_000, 1.0) >= 0.001);
+    }
+
+    #[cfg(feature = "video-ffmpeg")]
+    #[test]
+    fn pipe_backlog_reports_queued_bytes_and_pipe_size() {
+        use std::io::Read;
+        use std::process::{Command, Stdio};
+
+        let mut child = Command::new("sh")
+            .args(["-c", "printf 0123456789"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.wait().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        let (queued, capacity) = pipe_backlog(&stdout);
+        assert_eq!(queued, 10);
+        // Linux pipes hold at least a page, 64 KiB by default.
+        assert!(capacity >= 4096, "{capacity}");
+
+        let mut head = [0u8; 4];
+        stdout.read_exact(&mut head).unwrap();
+        assert_eq!(pipe_backlog(&stdout), (6, capacity));
+        let mut rest = Vec::new();
+        stdout.read_to_end(&mut rest).unwrap();
+        assert_eq!(pipe_backlog(&stdout).0, 0);
+    }
+}
@@ -0,0 +1,469 @@
+use crate::quality;
+
+/// Capacity over need from which the machine counts as comfortable. Below it,
+/// but still above the need, it is marginal: other load, thermal throttling
+/// and uneven frame times eat into the rest. `bench` uses the same line.
+pub const COMFORTABLE_HEADROOM: f64 = 1.5;
+
+/// Pixels per second one core decodes in software and converts to RGBA for
+/// upload: about one 1080p60 stream.
+const SOFTWARE_PIXELS_PER_CORE: f64 = 1920.0 * 1080.0 * 60.0;
+
+/// With a verified hwaccel the CPU still scales, converts and copies each
+/// frame, at about a quarter of the software cost.
+const HARDWARE_PIXELS_PER_CORE: f64 = SOFTWARE_PIXELS_PER_CORE * 4.0;
+
+/// Cores kept for the compositor, the render thread and everything else.
+const RESERVED_CORES: f64 = 1.0;
+
+/// Streams named in a warning.
+const HEAVIEST_SHOWN: usize = 3;
+
+/// How a stream's decoder decodes, as far as the hwaccel probe knows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodePath {
+    Hardware,
+    Software,
+    /// The probe has not finished; costed as software until it does.
+    Pending,
+}
+
+impl DecodePath {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hardware => "hardware",
+            Self::Software => "software",
+            Self::Pending => "pending",
+        }
+    }
+}
+
+/// One running decoder as configured, before any `KRC_AUTO_QUALITY` step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamLoad {
+    pub output: String,
+    /// Other outputs showing this decoder's frames (`KRC_SHARE_DECODERS`).
+    pub shared_with: Vec<String>,
+    pub video: String,
+    pub size: (u32, u32),
+    pub fps: u32,
+    pub decode: DecodePath,
+    /// Deepest auto quality level for this stream, see [`quality::max_level`].
+    pub max_level: u8,
+}
+
+impl StreamLoad {
+    /// CPU cores the stream needs at `level`.
+    fn cores(&self, level: u8) -> f64 {
+        let (w, h) = quality::degraded_source_size(self.size, level, self.fps);
+        let fps = quality::degraded_fps(self.fps, level);
+        let per_core = match self.decode {
+            DecodePath::Hardware => HARDWARE_PIXELS_PER_CORE,
+            DecodePath::Software | DecodePath::Pending => SOFTWARE_PIXELS_PER_CORE,
+        };
+        w as f64 * h as f64 * fps as f64 / per_core
+    }
+}
+
+/// What the estimate knows about the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineFacts {
+    pub cores: u32,
+    /// `KRC_SHARE_DECODERS`: outputs playing the same video share a decoder.
+    pub share_decoders: bool,
+}
+
+impl MachineFacts {
+    pub fn detect(share_decoders: bool) -> Self {
+        Self {
+            cores: std::thread::available_parallelism().map_or(1, |n| n.get() as u32),
+            share_decoders,
+        }
+    }
+
+    fn capacity(self) -> f64 {
+        (self.cores as f64 - RESERVED_CORES).max(1.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Ok,
+    Marginal,
+    Insufficient,
+}
+
+impl Verdict {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Marginal => "marginal",
+            Self::Insufficient => "insufficient",
+        }
+    }
+
+    fn from_ratio(ratio: f64) -> Self {
+        if ratio >= COMFORTABLE_HEADROOM {
+            Self::Ok
+        } else if ratio >= 1.0 {
+            Self::Marginal
+        } else {
+            Self::Insufficient
+        }
+    }
+}
+
+/// Whether the configured streams fit the machine, and what to do if not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub verdict: Verdict,
+    /// Capacity over need; infinite without streams.
+    pub ratio: f64,
+    pub need_cores: f64,
+    pub capacity_cores: f64,
+    /// The costliest streams, heaviest first, with the cores each needs.
+    pub heaviest: Vec<(String, f64)>,
+    pub suggestions: Vec<String>,
+    /// Some stream's hwaccel probe has not finished.
+    pub pending: bool,
+}
+
+/// Estimates whether `streams` can be decoded at their configured size and
+/// rate: each costs CPU in proportion to the pixels it delivers per second,
+/// a quarter of that with a verified hwaccel, against all cores but one.
+pub fn estimate(streams: &[StreamLoad], machine: &MachineFacts) -> Estimate {
+    let capacity_cores = machine.capacity();
+    let need_cores = streams.iter().map(|s| s.cores(0)).sum::<f64>();
+    let ratio = if need_cores > 0.0 {
+        capacity_cores / need_cores
+    } else {
+        f64::INFINITY
+    };
+    let verdict = Verdict::from_ratio(ratio);
+    let mut heaviest = streams
+        .iter()
+        .map(|s| (s.output.clone(), s.cores(0)))
+        .collect::<Vec<_>>();
+    heaviest.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    heaviest.truncate(HEAVIEST_SHOWN);
+    let suggestions = if verdict == Verdict::Ok {
+        Vec::new()
+    } else {
+        suggest(streams, machine, &heaviest)
+    };
+    Estimate {
+        verdict,
+        ratio,
+        need_cores,
+        capacity_cores,
+        heaviest,
+        suggestions,
+        pending: streams.iter().any(|s| s.decode == DecodePath::Pending),
+    }
+}
+
+/// Mitigations for an estimate that is not ok, the most effective first.
+fn suggest(
+    streams: &[StreamLoad],
+    machine: &MachineFacts,
+    heaviest: &[(String, f64)],
+) -> Vec<String> {
+    let mut out = Vec::new();
+    if !machine.share_decoders {
+        let mut same = streams
+            .iter()
+            .filter(|s| {
+                streams
+                    .iter()
+                    .any(|o| o.output != s.output && o.video == s.video && o.size == s.size)
+            })
+            .map(|s| s.output.as_str())
+            .collect::<Vec<_>>();
+        if same.len() > 1 {
+            same.sort_unstable();
+            out.push(format!(
+                "KRC_SHARE_DECODERS=1 to decode the same video once for {}",
+                same.join(", ")
+            ));
+        }
+    }
+    let top = heaviest
+        .first()
+        .and_then(|(name, _)| streams.iter().find(|s| s.output == *name));
+    if let Some(top) = top {
+        if top.size.0.max(top.size.1) > 1920 {
+            out.push(format!(
+                "a lower quality preset for {} (kitsune-rendercore quality --monitor {} medium)",
+                top.output, top.output
+            ));
+        }
+        if top.decode == DecodePath::Software {
+            out.push(format!(
+                "hardware decode for {} (KRC_HWACCEL=auto; see decode= in status)",
+                top.output
+            ));
+        }
+    }
+    if streams.iter().any(|s| s.fps > 30) {
+        out.push("a lower frame rate (KRC_VIDEO_FPS=30)".to_string());
+    }
+    if out.is_empty() {
+        out.push("fewer video outputs, or KRC_AUTO_QUALITY=1 to degrade them".to_string());
+    }
+    out
+}
+
+/// The auto quality level each stream should at least run at for the set to
+/// fit: the heaviest stream that can still step down does, until the need is
+/// within capacity or nothing can step further. Streams not listed need none.
+pub fn plan_levels(streams: &[StreamLoad], machine: &MachineFacts) -> Vec<(String, u8)> {
+    let capacity = machine.capacity();
+    let mut levels = vec![0u8; streams.len()];
+    loop {
+        let need = streams
+            .iter()
+            .zip(&levels)
+            .map(|(s, level)| s.cores(*level))
+            .sum::<f64>();
+        if need <= capacity {
+            break;
+        }
+        let next = streams
+            .iter()
+            .zip(&levels)
+            .enumerate()
+            .filter(|(_, (s, level))| **level < s.max_level)
+            .max_by(|(_, (a, la)), (_, (b, lb))| a.cores(**la).total_cmp(&b.cores(**lb)))
+            .map(|(idx, _)| idx);
+        let Some(idx) = next else {
+            break;
+        };
+        levels[idx] += 1;
+    }
+    streams
+        .iter()
+        .zip(levels)
+        .filter(|(_, level)| *level > 0)
+        .flat_map(|(s, level)| {
+            std::iter::once(&s.output)
+                .chain(&s.shared_with)
+                .map(move |output| (output.clone(), level))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(
+        output: &str,
+        video: &str,
+        size: (u32, u32),
+        fps: u32,
+        decode: DecodePath,
+    ) -> StreamLoad {
+        StreamLoad {
+            output: output.to_string(),
+            shared_with: Vec::new(),
+            video: video.to_string(),
+            size,
+            fps,
+            decode,
+            max_level: quality::max_level(fps, size),
+        }
+    }
+
+    fn machine(cores: u32) -> MachineFacts {
+        MachineFacts {
+            cores,
+            share_decoders: false,
+        }
+    }
+
+    /// The "it stutters" report: one 4K60 video on five monitors of an old
+    /// four-core laptop, decoded in software.
+    fn old_laptop_five_4k() -> Vec<StreamLoad> {
+        ["DP-1", "DP-2", "DP-3", "HDMI-A-1", "eDP-1"]
+            .iter()
+            .map(|output| stream(output, "/v/4k.mp4", (3840, 2160), 60, DecodePath::Software))
+            .collect()
+    }
+
+    #[test]
+    fn no_streams_is_ok() {
+        let estimate = estimate(&[], &machine(4));
+        assert_eq!(estimate.verdict, Verdict::Ok);
+        assert!(estimate.ratio.is_infinite());
+        assert!(estimate.heaviest.is_empty());
+        assert!(estimate.suggestions.is_empty());
+    }
+
+    #[test]
+    fn one_1080p60_stream_costs_one_software_core() {
+        let streams = [stream(
+            "DP-1",
+            "/v/a.mp4",
+            (1920, 1080),
+            60,
+            DecodePath::Software,
+        )];
+        let on_four = estimate(&streams, &machine(4));
+        assert!((on_four.need_cores - 1.0).abs() < 1e-9);
+        assert_eq!(on_four.capacity_cores, 3.0);
+        assert_eq!(on_four.verdict, Verdict::Ok);
+
+        let on_two = estimate(&streams, &machine(2));
+        assert_eq!(on_two.verdict, Verdict::Marginal);
+        assert!(!on_two.suggestions.is_empty());
+
+        // One core is kept free, but a single-core machine still counts one.
+        assert_eq!(estimate(&streams, &machine(1)).capacity_cores, 1.0);
+    }
+
+    #[test]
+    fn hardware_decode_costs_a_quarter_and_pending_costs_software() {
+        let size = (3840, 2160);
+        let hw = estimate(
+            &[stream("DP-1", "/v/a.mp4", size, 60, DecodePath::Hardware)],
+            &machine(4),
+        );
+        let sw = estimate(
+            &[stream("DP-1", "/v/a.mp4", size, 60, DecodePath::Software)],
+            &machine(4),
+        );
+        let pending = estimate(
+            &[stream("DP-1", "/v/a.mp4", size, 60, DecodePath::Pending)],
+            &machine(4),
+        );
+        assert!((sw.need_cores / hw.need_cores - 4.0).abs() < 1e-9);
+        assert_eq!(pending.need_cores, sw.need_cores);
+        assert!(pending.pending);
+        assert!(!sw.pending);
+        assert_eq!(hw.verdict, Verdict::Ok);
+        assert_eq!(sw.verdict, Verdict::Insufficient);
+    }
+
+    #[test]
+    fn five_4k_streams_on_an_old_laptop_are_insufficient() {
+        let streams = old_laptop_five_4k();
+        let estimate = estimate(&streams, &machine(4));
+        assert_eq!(estimate.verdict, Verdict::Insufficient);
+        assert!(estimate.ratio < 1.0);
+        // Ties are named in output order, at most three of them.
+        let names = estimate
+            .heaviest
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["DP-1", "DP-2", "DP-3"]);
+        let suggestions = estimate.suggestions.join("\n");
+        assert!(
+            suggestions.starts_with("KRC_SHARE_DECODERS=1"),
+            "{suggestions}"
+        );
+        assert!(suggestions.contains("quality --monitor DP-1 medium"));
+        assert!(suggestions.contains("hardware decode for DP-1"));
+        assert!(suggestions.contains("KRC_VIDEO_FPS=30"));
+
+        let shared = MachineFacts {
+            share_decoders: true,
+            ..machine(4)
+        };
+        let estimate = super::estimate(&streams, &shared);
+        assert!(!estimate.suggestions.iter().any(|s| s.contains("SHARE")));
+    }
+
+    #[test]
+    fn heaviest_stream_is_named_first() {
+        let streams = [
+            stream("DP-1", "/v/a.mp4", (1920, 1080), 30, DecodePath::Software),
+            stream("DP-2", "/v/b.mp4", (2560, 1440), 60, DecodePath::Software),
+            stream("DP-3", "/v/c.mp4", (1280, 720), 30, DecodePath::Software),
+        ];
+        let estimate = estimate(&streams, &machine(2));
+        assert_eq!(estimate.heaviest[0].0, "DP-2");
+        assert_eq!(estimate.heaviest[2].0, "DP-3");
+        assert!(estimate.suggestions[0].contains("quality --monitor DP-2"));
+    }
+
+    #[test]
+    fn small_streams_fall_back_to_fewer_outputs() {
+        let streams = [
+            stream("DP-1", "/v/a.mp4", (1280, 720), 30, DecodePath::Hardware),
+            stream("DP-2", "/v/b.mp4", (1280, 720), 30, DecodePath::Hardware),
+        ];
+        let tiny = MachineFacts {
+            cores: 1,
+            share_decoders: false,
+        };
+        let mut many = streams.to_vec();
+        for n in 3..40 {
+            many.push(stream(
+                &format!("DP-{n}"),
+                &format!("/v/{n}.mp4"),
+                (1280, 720),
+                30,
+                DecodePath::Hardware,
+            ));
+        }
+        let estimate = estimate(&many, &tiny);
+        assert_ne!(estimate.verdict, Verdict::Ok);
+        assert_eq!(
+            estimate.suggestions,
+            ["fewer video outputs, or KRC_AUTO_QUALITY=1 to degrade them"]
+        );
+    }
+
+    #[test]
+    fn plan_is_empty_when_the_set_fits() {
+        let streams = [stream(
+            "DP-1",
+            "/v/a.mp4",
+            (1920, 1080),
+            60,
+            DecodePath::Software,
+        )];
+        assert!(plan_levels(&streams, &machine(4)).is_empty());
+    }
+
+    #[test]
+    fn plan_steps_the_heaviest_stream_first() {
+        let streams = [
+            stream("DP-1", "/v/a.mp4", (1920, 1080), 60, DecodePath::Software),
+            stream("DP-2", "/v/b.mp4", (3840, 2160), 60, DecodePath::Software),
+        ];
+        let plan = plan_levels(&streams, &machine(4));
+        assert_eq!(plan.first().map(|(name, _)| name.as_str()), Some("DP-2"));
+        let levels = |name: &str| {
+            plan.iter()
+                .find(|(output, _)| output == name)
+                .map_or(0, |(_, level)| *level)
+        };
+        assert!(levels("DP-2") >= levels("DP-1"));
+        let need = streams[0].cores(levels("DP-1")) + streams[1].cores(levels("DP-2"));
+        assert!(need <= machine(4).capacity());
+    }
+
+    #[test]
+    fn plan_covers_shared_outputs_and_stops_at_the_deepest_level() {
+        let mut streams = old_laptop_five_4k();
+        streams.truncate(1);
+        streams[0].shared_with = vec!["DP-2".to_string(), "DP-3".to_string()];
+        let plan = plan_levels(&streams, &machine(1));
+        let names = plan
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["DP-1", "DP-2", "DP-3"]);
+        assert!(
+            plan.iter()
+                .all(|(_, level)| *level == plan[0].1 && *level > 0)
+        );
+
+        let streams = old_laptop_five_4k();
+        let plan = plan_levels(&streams, &machine(2));
+        assert_eq!(plan.len(), streams.len());
+        assert!(plan.iter().all(|(_, level)| *level == streams[0].max_level));
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::sync_channel;
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::config_file::ConfigFile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HookEvent {
+    Pause,
+    Resume,
+    WallpaperChange,
+    OutputAdded,
+    StreamStarted,
+    StreamDegraded,
+    StreamRecovered,
+    StreamStopped,
+    StreamPresented,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 9] = [
+        HookEvent::Pause,
+        HookEvent::Resume,
+        HookEvent::WallpaperChange,
+        HookEvent::OutputAdded,
+        HookEvent::StreamStarted,
+        HookEvent::StreamDegraded,
+        HookEvent::StreamRecovered,
+        HookEvent::StreamStopped,
+        HookEvent::StreamPresented,
+    ];
+
+    /// Key under `[hooks]` in the config file.
+    pub fn config_key(self) -> &'static str {
+        match self {
+            Self::Pause => "on_pause",
+            Self::Resume => "on_resume",
+            Self::WallpaperChange => "on_wallpaper_change",
+            Self::OutputAdded => "on_output_added",
+            Self::StreamStarted => "on_stream_started",
+            Self::StreamDegraded => "on_stream_degraded",
+            Self::StreamRecovered => "on_stream_recovered",
+            Self::StreamStopped => "on_stream_stopped",
+            Self::StreamPresented => "on_stream_presented",
+        }
+    }
+
+    /// Value passed to the hook as `KRC_EVENT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pause => "pause",
+            Self::Resume => "resume",
+            Self::WallpaperChange => "wallpaper-change",
+            Self::OutputAdded => "output-added",
+            Self::StreamStarted => "stream-started",
+            Self::StreamDegraded => "stream-degraded",
+            Self::StreamRecovered => "stream-recovered",
+            Self::StreamStopped => "stream-stopped",
+            Self::StreamPresented => "stream-presented",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.name() == raw || event.config_key() == raw)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HookContext<'a> {
+    pub monitor: Option<&'a str>,
+    pub video: Option<&'a str>,
+    pub reason: Option<&'a str>,
+    /// Event-specific extras (`failures=3`, decoder and source size).
+    pub detail: Option<&'a str>,
+}
+
+pub struct HookConfig {
+    commands: BTreeMap<HookEvent, String>,
+    pub timeout: Duration,
+    pub debounce: Duration,
+}
+
+impl HookConfig {
+    pub fn from_config(config: &ConfigFile) -> Self {
+        let commands = HookEvent::ALL
+            .into_iter()
+            .filter_map(|event| {
+                let command = config.get("hooks", event.config_key())?.trim();
+                (!command.is_empty()).then(|| (event, command.to_string()))
+            })
+            .collect();
+        Self {
+            commands,
+            timeout: Duration::from_millis(
+                config
+                    .get_u64("hooks", "timeout_ms")
+                    .unwrap_or(10_000)
+                    .max(100),
+            ),
+            debounce: Duration::from_millis(config.get_u64("hooks", "debounce_ms").unwrap_or(1000)),
+        }
+    }
+
+    pub fn command(&self, event: HookEvent) -> Option<&str> {
+        self.commands.get(&event).map(String::as_str)
+    }
+}
+
+struct HookRunner {
+    config: HookConfig,
+    /// Last fire per hook and monitor, so a burst of reloads runs each hook once
+    /// while different monitors still get their own call.
+    last_fired: Mutex<BTreeMap<(HookEvent, String), Instant>>,
+}
+
+static HOOKS: OnceLock<Option<HookRunner>> = OnceLock::new();
+
+/// Loads `[hooks]` from the config file. With `enabled == false` (`--no-hooks`)
+/// every `fire` is a no-op.
+pub fn init(enabled: bool) {
+    HOOKS.get_or_init(|| {
+        if !enabled {
+            println!("[rendercore] hooks disabled (--no-hooks)");
+            return None;
+        }
+        let config = HookConfig::from_config(&ConfigFile::load_from_env());
+        if config.commands.is_empty() {
+            return None;
+        }
+        let names = config
+            .commands
+            .keys()
+            .map(|event| event.config_key())
+            .collect::<Vec<_>>()
+            .join(",");
+        println!("[rendercore] hooks configured: {names}");
+        Some(HookRunner {
+            config,
+            last_fired: Mutex::new(BTreeMap::new()),
+        })
+    });
+}
+
+/// Runs the hook for `event` in the background, if one is configured. Never
+/// blocks the caller and never returns an error: failures are only logged.
+pub fn fire(event: HookEvent, ctx: HookContext) {
+    let Some(Some(runner)) = HOOKS.get() else {
+        return;
+    };
+    let Some(command) = runner.config.command(event) else {
+        return;
+    };
+    if let Ok(mut last_fired) = runner.last_fired.lock() {
+        let key = (event, ctx.monitor.unwrap_or("").to_string());
+        if last_fired
+            .get(&key)
+            .is_some_and(|at| at.elapsed() < runner.config.debounce)
+        {
+            println!("[rendercore] hook {} debounced", event.config_key());
+            return;
+        }
+        last_fired.insert(key, Instant::now());
+    }
+    if let Err(err) = spawn_hook(command, event, ctx, runner.config.timeout) {
+        eprintln!("[rendercore] hook {} failed: {err}", event.config_key());
+    }
+}
+
+/// Spawns `sh -c <command>` with the hook env contract and a watchdog thread that
+/// kills it after `timeout`. The handle yields the exit status (`None` on timeout).
+/// The watchdog starts first, so a child is never spawned without a thread to
+/// reap it.
+pub fn spawn_hook(
+    command: &str,
+    event: HookEvent,
+    ctx: HookContext,
+    timeout: Duration,
+) -> Result<JoinHandle<Option<ExitStatus>>, String> {
+    let hook = event.config_key();
+    let (handoff, receiver) = sync_channel::<Child>(1);
+    let watchdog = thread::Builder::new()
+        .name(format!("krc-hook-{}", event.name()))
+        .spawn(move || wait_with_timeout(receiver.recv().ok()?, hook, timeout))
+        .map_err(|e| format!("failed to start hook watchdog: {e}"))?;
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("KRC_EVENT", event.name())
+        .env("KRC_MONITOR", ctx.monitor.unwrap_or(""))
+        .env("KRC_VIDEO", ctx.video.unwrap_or(""))
+        .env("KRC_REASON", ctx.reason.unwrap_or(""))
+        .env("KRC_DETAIL", ctx.detail.unwrap_or(""))
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to spawn sh: {e}"))?;
+    // The watchdog only exits early when `handoff` is dropped unsent.
+    let _ = handoff.send(child);
+    Ok(watchdog)
+}
+
+fn wait_with_timeout(mut child: Child, hook: &str, timeout: Duration) -> Option<ExitStatus> {
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    eprintln!("[rendercore] hook {hook} exited with {status}");
+                }
+                return Some(status);
+            }
+            Ok(None) if started.elapsed() >= timeout => {
+                eprintln!(
+                    "[rendercore] hook {hook} timed out after {}ms, killing it",
+                    timeout.as_millis()
+                );
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(err) => {
+                eprintln!("[rendercore] hook {hook} wait failed: {err}");
+                return None;
+            }
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! A small HTTP front for the control commands, for tools that speak HTTP
+//! but not Unix sockets (Home Assistant, a phone shortcut):
+//!
+//! - `GET /status`: the live status JSON.
+//! - `POST /pause`, `POST /resume`: the global pause; with `{"monitor": ..}`
+//!   only that output, like `pause --monitor`. `/pause` takes a `reason`.
+//! - `POST /set-video`: `{"monitor": .., "path": .., "transient": false}`.
+//! - `POST /reload`: `{"hard": true}` also restarts unchanged decoders.
+//!
+//! Requests are queued as the same [`RuntimeCommand`]s the control socket and
+//! D-Bus send, so the three cannot drift apart. Configured under `[http]` in
+//! the config file: `listen` (`7878` alone binds 127.0.0.1) and an optional
+//! `token` every request must send as `Authorization: Bearer <token>`. A
+//! listener outside loopback without a token is refused.
+//!
+//! A web page the user visits can reach a loopback port too. It cannot send
+//! a cross-origin `application/json` POST without a preflight this server
+//! never answers, so every POST must carry that content type; and on a
+//! loopback listener the `Host` header must name loopback and the port, so
+//! a page whose name was rebound to 127.0.0.1 cannot read `/status`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::command_queue::{CommandSender, RuntimeCommand};
+use crate::config_file::ConfigFile;
+use crate::json::{JsonValue, escape_json, parse_json};
+
+const SECTION: &str = "http";
+
+/// Request line plus headers; anything longer is refused.
+const MAX_HEAD: u64 = 8 * 1024;
+/// The largest JSON body taken; a `set-video` needs a fraction of this.
+const MAX_BODY: usize = 16 * 1024;
+/// How much of a refused body is read and thrown away.
+const DRAIN_LIMIT: u64 = 256 * 1024;
+
+/// Same as the control socket: the loop answers within one iteration.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a client may take to send its whole request, however it
+/// trickles in, and to take the response.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Clients served at once; more are refused with 503 until one finishes.
+const MAX_CLIENTS: usize = 16;
+
+/// `[http]` from the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpConfig {
+    pub listen: SocketAddr,
+    pub token: Option<String>,
+}
+
+impl HttpConfig {
+    /// `None` when `[http]` has no `listen`: the listener is off.
+    pub fn load_from_env() -> Result<Option<Self>, String> {
+        Self::from_config(&ConfigFile::load_from_env())
+    }
+
+    pub fn from_config(config: &ConfigFile) -> Result<Option<Self>, String> {
+        let Some(raw) = config.get(SECTION, "listen").map(str::trim) else {
+            return Ok(None);
+        };
+        let listen = match raw.parse::<u16>() {
+            Ok(port) => SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port),
+            Err(_) => raw.parse::<SocketAddr>().map_err(|_| {
+                format!(
+                    "[http] listen = \"{raw}\": expected a port or an address like 127.0.0.1:7878"
+                )
+            })?,
+        };
+        let token = config
+            .get(SECTION, "token")
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(str::to_string);
+        if token.is_none() && !listen.ip().is_loopback() {
+            return Err(format!(
+                "[http] listen = \"{raw}\" is reachable from other hosts; set [http] token too"
+            ));
+        }
+        Ok(Some(Self { listen, token }))
+    }
+}
+
+/// One request per connection. The accept thread hands each client to a
+/// thread of its own, so a slow one holds up nobody else; the accept thread
+/// is stopped and joined on drop, client threads end within
+/// [`CLIENT_TIMEOUT`] plus the reply.
+pub struct HttpServer {
+    listener: Option<TcpListener>,
+    addr: SocketAddr,
+    token: Option<String>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    pub fn bind(config: HttpConfig) -> Result<Self, String> {
+        let listener = TcpListener::bind(config.listen)
+            .map_err(|e| format!("failed to bind {}: {e}", config.listen))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("failed to read the bound address: {e}"))?;
+        Ok(Self {
+            listener: Some(listener),
+            addr,
+            token: config.token,
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Spawns the accept thread; requests become [`RuntimeCommand`]s on
+    /// `commands`.
+    pub fn serve(&mut self, commands: CommandSender) -> Result<(), String> {
+        let Some(listener) = self.listener.take() else {
+            return Ok(());
+        };
+        let stop = self.stop.clone();
+        let access = Arc::new(Access {
+            token: self.token.clone(),
+            loopback_port: self.addr.ip().is_loopback().then_some(self.addr.port()),
+        });
+        let clients = Arc::new(AtomicUsize::new(0));
+        let thread = thread::Builder::new()
+            .name("krc-http".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            eprintln!("[rendercore] http accept failed: {err}");
+                            continue;
+                        }
+                    };
+                    if clients.fetch_add(1, Ordering::Relaxed) >= MAX_CLIENTS {
+                        clients.fetch_sub(1, Ordering::Relaxed);
+                        let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+                        respond(&stream, error(503, "too many clients"));
+                        continue;
+                    }
+                    let (access, commands, done) =
+                        (access.clone(), commands.clone(), clients.clone());
+                    let spawned = thread::Builder::new()
+                        .name("krc-http-client".to_string())
+                        .spawn(move || {
+                            serve_client(stream, &access, &commands);
+                            done.fetch_sub(1, Ordering::Relaxed);
+                        });
+                    if let Err(err) = spawned {
+                        clients.fetch_sub(1, Ordering::Relaxed);
+                        eprintln!("[rendercore] http client thread failed to start: {err}");
+                    }
+                }
+            })
+            .map_err(|e| format!("failed to start the http thread: {e}"))?;
+        self.thread = Some(thread);
+        Ok(())
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            // A connection of our own gets the accept thread past `accept`.
+            let _ = TcpStream::connect(self.addr);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A response: status code and JSON body.
+type Response = (u16, String);
+
+fn error(status: u16, message: &str) -> Response {
+    (
+        status,
+        format!("{{\"error\":\"{}\"}}", escape_json(message)),
+    )
+}
+
+/// Who may send requests: the token, and on a loopback listener its port,
+/// which the `Host` header must name.
+struct Access {
+    token: Option<String>,
+    loopback_port: Option<u16>,
+}
+
+/// Reads from the client until one deadline for the whole request, so a
+/// client sending a byte now and then cannot keep its thread forever.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+fn serve_client(stream: TcpStream, access: &Access, commands: &CommandSender) {
+    let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+    let mut reader = BufReader::new(DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + CLIENT_TIMEOUT,
+    });
+    let response = handle(&mut reader, access, commands);
+    let status = response.0;
+    respond(&stream, response);
+    if status == 413 {
+        // Closing with the body unread resets the connection, and the client
+        // may lose the response; take a bounded amount of it first.
+        let _ = stream.shutdown(Shutdown::Write);
+        let _ = std::io::copy(&mut reader.take(DRAIN_LIMIT), &mut std::io::sink());
+    }
+}
+
+fn respond(stream: &TcpStream, (status, body): Response) {
+    let allow = match status {
+        405 => "Allow: GET, POST\r\n",
+        401 => "WWW-Authenticate: Bearer\r\n",
+        _ => "",
+    };
+    let response = format!(
+        "HTTP/1.1 {status} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{allow}Connection: close\r\n\r\n{body}\n",
+        reason_phrase(status),
+        body.len() + 1
+    );
+    let mut writer = stream;
+    let _ = writer.write_all(response.as_bytes());
+}
+
+fn handle(reader: &mut impl BufRead, access: &Access, commands: &CommandSender) -> Response {
+    let mut head = reader.by_ref().take(MAX_HEAD);
+    let mut request_line = String::new();
+    if head.read_line(&mut request_line).is_err() || !request_line.ends_with('\n') {
+        return error(400, "malformed request line");
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return error(400, "malformed request line");
+    };
+    let (method, path) = (
+        method.to_string(),
+        target.split('?').next().unwrap_or("").to_string(),
+    );
+
+    let mut content_length = 0usize;
+    let mut authorization = None::<String>;
+    let mut host = None::<String>;
+    let mut content_type = None::<String>;
+    loop {
+        let mut line = String::new();
+        match head.read_line(&mut line) {
+            Ok(_) if !line.ends_with('\n') => {
+                return error(431, "request headers too large or cut short");
+            }
+            Ok(_) => {}
+            Err(_) => return error(400, "malformed headers"),
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return error(400, "malformed header");
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            let Ok(length) = value.parse() else {
+                return error(400, "invalid Content-Length");
+            };
+            content_length = length;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("host") {
+            host = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.to_string());
+        }
+    }
+
+    if let Some(port) = access.loopback_port
+        && !host
+            .as_deref()
+            .is_some_and(|host| loopback_host(host, port))
+    {
+        return error(
+            403,
+            "the Host header must be 127.0.0.1, localhost or [::1] with the port",
+        );
+    }
+    if let Some(token) = access.token.as_deref() {
+        let sent = authorization
+            .as_deref()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        if !sent.is_some_and(|sent| same_token(sent, token)) {
+            return error(401, "missing or wrong bearer token");
+        }
+    }
+    // A page can send other types cross-origin without a preflight.
+    let json = content_type.as_deref().is_some_and(|value| {
+        value
+            .split(';')
+            .next()
+            .is_some_and(|kind| kind.trim().eq_ignore_ascii_case("application/json"))
+    });
+    if method == "POST" && !json {
+        return error(415, "POST requests need Content-Type: application/json");
+    }
+    if content_length > MAX_BODY {
+        return error(
+            413,
+            &format!("body over {MAX_BODY} bytes ({content_length})"),
+        );
+    }
+    let mut raw = vec![0u8; content_length];
+    if reader.read_exact(&mut raw).is_err() {
+        return error(400, "body shorter than Content-Length");
+    }
+    let body = match std::str::from_utf8(&raw).map(str::trim) {
+        Ok("") => JsonValue::Object(Default::default()),
+        Ok(text) => match parse_json(text) {
+            Ok(value @ JsonValue::Object(_)) => value,
+            Ok(_) => return error(400, "the body must be a JSON object"),
+            Err(err) => return error(400, &format!("invalid JSON body: {err}")),
+        },
+        Err(_) => return error(400, "the body is not UTF-8"),
+    };
+
+    let command = match route(&method, &path, &body) {
+        Ok(command) => command,
+        Err(response) => return response,
+    };
+    match commands.request(command, REPLY_TIMEOUT) {
+        // The renderer refused it (unknown monitor, no video map, ...).
+        Ok(reply) if parse_json(&reply).is_ok_and(|value| value.get("error").is_some()) => {
+            (400, reply)
+        }
+        Ok(reply) => (200, reply),
+        // Busy, shutting down or stuck: worth retrying later.
+        Err(err) => error(503, &err),
+    }
+}
+
+/// The command for a request, or the response refusing it.
+fn route(method: &str, path: &str, body: &JsonValue) -> Result<RuntimeCommand, Response> {
+    let text = |key: &str| -> Result<Option<String>, Response> {
+        match body.get(key) {
+            None | Some(JsonValue::Null) => Ok(None),
+            Some(value) => value
+                .as_str()
+                .map(|s| Some(s.trim().to_string()))
+                .ok_or_else(|| error(400, &format!("\"{key}\" must be a string"))),
+        }
+    };
+    let flag = |key: &str| -> Result<bool, Response> {
+        match body.get(key) {
+            None | Some(JsonValue::Null) => Ok(false),
+            Some(value) => value
+                .as_bool()
+                .ok_or_else(|| error(400, &format!("\"{key}\" must be true or false"))),
+        }
+    };
+    let allowed = match path {
+        "/status" => "GET",
+        "/pause" | "/resume" | "/set-video" | "/reload" => "POST",
+        _ => return Err(error(404, &format!("no endpoint {path}"))),
+    };
+    if method != allowed {
+        return Err(error(405, &format!("{path} takes {allowed}")));
+    }
+    Ok(match path {
+        "/status" => RuntimeCommand::Status,
+        "/pause" | "/resume" => match text("monitor")?.filter(|m| !m.is_empty()) {
+            Some(monitor) => RuntimeCommand::PauseOutput {
+                monitor,
+                paused: path == "/pause",
+            },
+            None if path == "/pause" => RuntimeCommand::Pause {
+                reason: text("reason")?
+                    .map(|reason| reason.replace(['\n', '\r', '\t'], " "))
+                    .filter(|reason| !reason.is_empty())
+                    .unwrap_or_else(|| "http".to_string()),
+            },
+            None => RuntimeCommand::Resume,
+        },
+        "/set-video" => {
+            let monitor = text("monitor")?
+                .filter(|m| !m.is_empty())
+                .ok_or_else(|| error(400, "missing \"monitor\""))?;
+            let transient = flag("transient")?;
+            // An empty transient path drops the override.
+            let video = match text("path")? {
+                Some(path) if !path.is_empty() || transient => path,
+                _ => return Err(error(400, "missing \"path\"")),
+            };
+            RuntimeCommand::SetVideo {
+                transient,
+                monitor,
+                video,
+            }
+        }
+        _ => RuntimeCommand::Reload {
+            hard: flag("hard")?,
+        },
+    })
+}
+
+/// `127.0.0.1`, `localhost` or `[::1]`, with `port` (or none for port 80).
+fn loopback_host(host: &str, port: u16) -> bool {
+    let (name, sent_port) = match host.rsplit_once(':') {
+        Some((name, sent)) if !sent.contains(']') => (name, sent.parse::<u16>().ok()),
+        _ => (host, Some(80)),
+    };
+    sent_port == Some(port)
+        && (name == "127.0.0.1" || name == "[::1]" || name.eq_ignore_ascii_case("localhost"))
+}
+
+/// Compares every byte, so the time taken does not tell how much matched.
+fn same_token(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        _ => "Service Unavailable",
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+
+use crate::env_file;
+use crate::journal::{self, Value};
+use crate::tools::Tool;
+use crate::user_path::{display_path, display_path_short, display_text};
+use crate::version::NO_VIDEO_SUPPORT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HwAccel {
+    Auto,
+    None,
+    Nvdec,
+    Vaapi,
+}
+
+impl HwAccel {
+    pub fn from_env() -> Self {
+        match env_file::var("KRC_HWACCEL")
+            .map(|v| v.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("none") => Self::None,
+            Some("nvdec") | Some("cuda") => Self::Nvdec,
+            Some("vaapi") => Self::Vaapi,
+            _ => Self::Auto,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::None => "none",
+            Self::Nvdec => "nvdec",
+            Self::Vaapi => "vaapi",
+        }
+    }
+
+    /// The value for ffmpeg's `-hwaccel`; `None` decodes in software.
+    pub fn ffmpeg_arg(self) -> Option<&'static str> {
+        match self {
+            Self::Auto => Some("auto"),
+            Self::None => None,
+            Self::Nvdec => Some("cuda"),
+            Self::Vaapi => Some("vaapi"),
+        }
+    }
+}
+
+/// Whether ffmpeg actually decodes a video on the GPU. Its `-hwaccel` is
+/// best effort: without a usable device or for an unsupported codec profile
+/// it quietly decodes in software.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HwDecode {
+    /// The probe has not finished yet.
+    Pending,
+    /// Hardware decode engaged, e.g. `vaapi` or `cuda`.
+    Hardware(String),
+    /// `KRC_HWACCEL=none`: software by request.
+    Software,
+    /// A hwaccel was asked for but ffmpeg decoded in software; its message.
+    Fallback(String),
+    /// The probe itself failed (no ffmpeg, unreadable file).
+    Unknown(String),
+}
+
+impl HwDecode {
+    /// For `status`: `vaapi`, `software`, `software(fallback)`...
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Pending => "pending",
+            Self::Hardware(kind) => kind,
+            Self::Software => "software",
+            Self::Fallback(_) => "software(fallback)",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+
+    /// ffmpeg's reason for a fallback, or why the probe failed.
+    pub fn detail(&self) -> &str {
+        match self {
+            Self::Fallback(message) | Self::Unknown(message) => message,
+            _ => "",
+        }
+    }
+}
+
+/// Pixel formats a decoder only picks when a hwaccel is in use, with the
+/// name reported for them.
+const HW_FORMATS: [(&str, &str); 10] = [
+    ("vaapi", "vaapi"),
+    ("vaapi_vld", "vaapi"),
+    ("cuda", "cuda"),
+    ("qsv", "qsv"),
+    ("vdpau", "vdpau"),
+    ("vulkan", "vulkan"),
+    ("drm_prime", "drm"),
+    ("videotoolbox_vld", "videotoolbox"),
+    ("d3d11", "d3d11va"),
+    ("dxva2_vld", "dxva2"),
+];
+
+/// ffmpeg lines that explain why a hwaccel did not engage.
+const FALLBACK_MARKERS: [&str; 6] = [
+    "Failed setup for format",
+    "hwaccel initialisation returned error",
+    "Device creation failed",
+    "Auto hwaccel disabled",
+    "doesn't support hardware accelerated",
+    "No support for codec",
+];
+
+/// Frames the probe decodes; enough for the decoder to settle on a format.
+const PROBE_FRAMES: &str = "30";
+
+type VerdictCache = Mutex<HashMap<(String, HwAccel), HwDecode>>;
+
+/// The cached verdict for `video` decoded with `hwaccel`. The first call for
+/// a combination starts a one-time probe on a helper thread and returns
+/// [`HwDecode::Pending`]; a fallback is logged and journaled once there.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub fn verdict(video: &str, hwaccel: HwAccel) -> HwDecode {
+    if hwaccel == HwAccel::None {
+        return HwDecode::Software;
+    }
+    static VERDICTS: OnceLock<VerdictCache> = OnceLock::new();
+    let cache = VERDICTS.get_or_init(|| Mutex::new(HashMap::new()));
+    let key = (video.to_string(), hwaccel);
+    let Ok(mut verdicts) = cache.lock() else {
+        return HwDecode::Pending;
+    };
+    if let Some(known) = verdicts.get(&key) {
+        return known.clone();
+    }
+    verdicts.insert(key.clone(), HwDecode::Pending);
+    drop(verdicts);
+    let spawned = std::thread::Builder::new()
+        .name("krc-hwaccel-probe".to_string())
+        .spawn(move || {
+            let found = probe(&key.0, key.1);
+            log_verdict(&key.0, key.1, &found);
+            if let Ok(mut verdicts) = cache.lock() {
+                verdicts.insert(key, found);
+            }
+        });
+    if let Err(err) = spawned {
+        return HwDecode::Unknown(format!("failed to start hwaccel probe: {err}"));
+    }
+    HwDecode::Pending
+}
+
+fn log_verdict(video: &str, hwaccel: HwAccel, found: &HwDecode) {
+    match found {
+        HwDecode::Hardware(kind) => println!(
+            "[rendercore] hwaccel={} engaged ({kind}) for {}",
+            hwaccel.as_str(),
+            display_path_short(video)
+        ),
+        HwDecode::Fallback(message) => {
+            println!(
+                "[rendercore] warning: hwaccel={} did not engage for {}, decoding in software: {}",
+                hwaccel.as_str(),
+                display_path_short(video),
+                display_text(message)
+            );
+            journal::record(
+                "hwaccel_fallback",
+                &[
+                    ("video", Value::Str(video)),
+                    ("hwaccel", Value::Str(hwaccel.as_str())),
+                    ("message", Value::Str(message)),
+                ],
+            );
+        }
+        HwDecode::Unknown(err) => println!(
+            "[rendercore] hwaccel probe failed for {}: {}",
+            display_path_short(video),
+            display_text(err)
+        ),
+        HwDecode::Pending | HwDecode::Software => {}
+    }
+}
+
+/// Decodes the first frames of `video` with `hwaccel` and ffmpeg's debug log
+/// on stderr, and reads which pixel format the decoder settled on.
+pub fn probe(video: &str, hwaccel: HwAccel) -> HwDecode {
+    let Some(arg) = hwaccel.ffmpeg_arg() else {
+        return HwDecode::Software;
+    };
+    if !Tool::Ffmpeg.used() {
+        return HwDecode::Unknown(NO_VIDEO_SUPPORT.to_string());
+    }
+    let output = Tool::Ffmpeg
+        .command()
+        .args(["-hide_banner", "-nostdin", "-loglevel", "debug"])
+        .args(["-hwaccel", arg, "-i", video])
+        .args([
+            "-an",
+            "-sn",
+            "-dn",
+            "-frames:v",
+            PROBE_FRAMES,
+            "-f",
+            "null",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(err) => return HwDecode::Unknown(Tool::Ffmpeg.spawn_error(err)),
+    };
+    let log = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        // The decoder itself fails the same way and reports it as its error.
+        return HwDecode::Unknown(format!(
+            "ffmpeg exited with {} on {}: {}",
+            output.status,
+            display_path(video),
+            log.lines()
+                .rev()
+                .map(strip_context)
+                .find(|line| !line.is_empty())
+                .unwrap_or_default()
+        ));
+    }
+    parse_log(&log)
+}
+
+/// The verdict from an ffmpeg debug log: the last format the decoder chose
+/// wins, since a failed hwaccel setup is followed by a software pick.
+fn parse_log(log: &str) -> HwDecode {
+    let failure = log
+        .lines()
+        .find(|line| FALLBACK_MARKERS.iter().any(|m| line.contains(m)))
+        .map(strip_context);
+    let chosen = log.lines().rev().find_map(|line| {
+        let rest = line.split("Format ").nth(1)?;
+        let (format, _) = rest.split_once(" chosen by get_format()")?;
+        Some(format.trim().to_string())
+    });
+    let auto = log.lines().find_map(|line| {
+        let rest = line.split("Using auto hwaccel type ").nth(1)?;
+        rest.split_whitespace().next().map(str::to_string)
+    });
+    match chosen {
+        Some(format) => match HW_FORMATS.iter().find(|(f, _)| *f == format) {
+            Some((_, kind)) => HwDecode::Hardware(kind.to_string()),
+            None => {
+                HwDecode::Fallback(failure.unwrap_or_else(|| format!("the decoder chose {format}")))
+            }
+        },
+        None => match (auto, failure) {
+            (_, Some(failure)) => HwDecode::Fallback(failure),
+            (Some(kind), None) => HwDecode::Hardware(kind),
+            (None, None) => HwDecode::Fallback("ffmpeg reported no hardware format".to_string()),
+        },
+    }
+}
+
+/// Drops the `[h264 @ 0x55d...]` prefix ffmpeg puts on codec messages.
+fn strip_context(line: &str) -> String {
+    let line = line.trim();
+    match line
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("] "))
+    {
+        Some((_, message)) => message.trim().to_string(),
+        None => line.to_string(),
+    }
+}
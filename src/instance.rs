@@ -0,0 +1,351 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::control;
+use crate::paths::{daemon_log, ensure_dir, lock_file};
+
+/// `KRC_INSTANCE`: lets several renderers run side by side (one per id), each
+/// with its own lock file and control socket. Defaults to `default`.
+pub fn instance_id_from_env() -> String {
+    std::env::var("KRC_INSTANCE")
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// Single-instance guard: an exclusive `flock` on the lock file, held for the
+/// life of the process and released by the kernel even on a crash. The file
+/// holds the owner's PID, which is only meaningful while it is locked.
+pub struct InstanceLock {
+    _file: File,
+}
+
+pub enum LockError {
+    /// Another process holds the lock; its PID if the file had one.
+    Held(Option<u32>),
+    Io(String),
+}
+
+impl InstanceLock {
+    pub fn acquire(path: &Path) -> Result<Self, LockError> {
+        if let Some(parent) = path.parent() {
+            ensure_dir(parent).map_err(LockError::Io)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| LockError::Io(format!("failed to open {}: {e}", path.display())))?;
+        if file.try_lock().is_err() {
+            let mut raw = String::new();
+            let _ = file.read_to_string(&mut raw);
+            return Err(LockError::Held(raw.trim().parse().ok()));
+        }
+        let written = file
+            .set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| writeln!(file, "{}", std::process::id()));
+        if let Err(err) = written {
+            return Err(LockError::Io(format!(
+                "failed to write {}: {err}",
+                path.display()
+            )));
+        }
+        Ok(Self { _file: file })
+    }
+
+    /// `Some` while a process holds the lock at `path`, with its PID once
+    /// the file has one. Only reads: the file is opened read-only and tried
+    /// with a shared lock, so probing never creates, truncates or takes over
+    /// a lock file.
+    pub fn holder(path: &Path) -> Option<Option<u32>> {
+        let mut file = File::open(path).ok()?;
+        if file.try_lock_shared().is_ok() {
+            return None;
+        }
+        let mut raw = String::new();
+        let _ = file.read_to_string(&mut raw);
+        Some(raw.trim().parse().ok())
+    }
+}
+
+/// Takes the instance lock; with `replace`, first asks the running instance to
+/// quit (control socket, then SIGTERM) and waits for it to release the lock.
+pub fn acquire_instance(instance: &str, replace: bool) -> Result<InstanceLock, String> {
+    let path = lock_file(instance);
+    let pid = match InstanceLock::acquire(&path) {
+        Ok(lock) => return Ok(lock),
+        Err(LockError::Io(err)) => return Err(err),
+        Err(LockError::Held(pid)) => pid,
+    };
+    let pid_label = pid
+        .map(|pid| pid.to_string())
+        .unwrap_or_else(|| "?".to_string());
+    if !replace {
+        return Err(format!(
+            "instance '{instance}' is already running (pid {pid_label}); use --replace to take over"
+        ));
+    }
+
+    println!("[rendercore] replacing instance '{instance}' (pid {pid_label})");
+    if control::request("quit").is_err()
+        && let Some(pid) = pid
+    {
+        let _ = send_signal(pid, libc::SIGTERM);
+    }
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        match InstanceLock::acquire(&path) {
+            Ok(lock) => return Ok(lock),
+            Err(LockError::Io(err)) => return Err(err),
+            Err(LockError::Held(_)) if Instant::now() >= deadline => {
+                return Err(format!(
+                    "instance '{instance}' (pid {pid_label}) did not exit within 5s"
+                ));
+            }
+            Err(LockError::Held(_)) => thread::sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+/// PID of the renderer holding `instance`'s lock, if one is running.
+pub fn running_pid(instance: &str) -> Option<u32> {
+    InstanceLock::holder(&lock_file(instance)).flatten()
+}
+
+static HANGUP: AtomicBool = AtomicBool::new(false);
+/// SIGTERM or SIGINT received while held by [`hold_shutdown_signals`]; 0 for none.
+static SHUTDOWN: AtomicI32 = AtomicI32::new(0);
+
+/// Signals `pid` directly rather than running `kill`, which a sandbox may not have.
+pub fn send_signal(pid: u32, signum: i32) -> Result<(), String> {
+    let pid = libc::pid_t::try_from(pid).map_err(|_| format!("invalid pid {pid}"))?;
+    // SAFETY: kill(2) takes plain integers and has no memory-safety
+    // preconditions.
+    if unsafe { libc::kill(pid, signum) } == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "failed to signal pid {pid}: {}",
+            std::io::Error::last_os_error()
+        ))
+    }
+}
+
+/// Installs `handler` for `signum`, or restores the default disposition
+/// with `None`. Interrupted system calls restart, as they did under
+/// `signal(2)`.
+fn set_handler(signum: libc::c_int, handler: Option<extern "C" fn(libc::c_int)>) {
+    // SAFETY: `action` is fully initialised (zeroed, then an empty mask)
+    // before sigaction(2) reads it, and the old action is not asked for.
+    // Every handler passed here only stores to an atomic, which is
+    // async-signal-safe.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction =
+            handler.map_or(libc::SIG_DFL, |handler| handler as libc::sighandler_t);
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signum, &action, std::ptr::null_mut());
+    }
+}
+
+extern "C" fn on_hangup(_: libc::c_int) {
+    HANGUP.store(true, Ordering::Relaxed);
+}
+
+/// Makes SIGHUP request a soft reload (picked up by `take_hangup` on the next
+/// loop wakeup) instead of terminating the renderer.
+pub fn install_hangup_handler() {
+    set_handler(libc::SIGHUP, Some(on_hangup));
+}
+
+pub fn take_hangup() -> bool {
+    HANGUP.swap(false, Ordering::Relaxed)
+}
+
+extern "C" fn on_shutdown(signum: libc::c_int) {
+    SHUTDOWN.store(signum, Ordering::Relaxed);
+}
+
+/// Makes SIGTERM and SIGINT only record the request (see
+/// [`shutdown_requested`]) until [`release_shutdown_signals`], so a slow
+/// step can stop at its next checkpoint instead of dying half-done.
+pub fn hold_shutdown_signals() {
+    SHUTDOWN.store(0, Ordering::Relaxed);
+    set_handler(libc::SIGTERM, Some(on_shutdown));
+    set_handler(libc::SIGINT, Some(on_shutdown));
+}
+
+/// The held SIGTERM or SIGINT, if one arrived.
+pub fn shutdown_requested() -> Option<i32> {
+    Some(SHUTDOWN.load(Ordering::Relaxed)).filter(|signum| *signum != 0)
+}
+
+/// Restores the default SIGTERM and SIGINT; one that was held is delivered
+/// now, ending the process as it would have when it arrived.
+pub fn release_shutdown_signals() {
+    set_handler(libc::SIGTERM, None);
+    set_handler(libc::SIGINT, None);
+    if let Some(signum) = shutdown_requested() {
+        let _ = send_signal(std::process::id(), signum);
+    }
+}
+
+/// `--daemonize`: re-runs this binary in the background with `child_args` and
+/// returns once it answers on its control socket, i.e. once the wallpaper is up.
+/// The child takes the instance lock itself, so the lock file holds its PID.
+pub fn daemonize(instance: &str, child_args: &[String]) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    let exe = std::env::current_exe().map_err(|e| format!("cannot locate own binary: {e}"))?;
+    let log_path = daemon_log(instance)?;
+    if let Some(parent) = log_path.parent() {
+        ensure_dir(parent)?;
+    }
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(|e| format!("failed to open {}: {e}", log_path.display()))?;
+    let log_err = log
+        .try_clone()
+        .map_err(|e| format!("failed to open {}: {e}", log_path.display()))?;
+    let mut child = Command::new(exe)
+        .args(child_args)
+        .stdin(Stdio::null())
+        .stdout(log)
+        .stderr(log_err)
+        // Own process group: Ctrl+C in the launching shell must not reach it.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("failed to start background renderer: {e}"))?;
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| format!("failed to wait for background renderer: {e}"))?
+        {
+            return Err(format!(
+                "background renderer exited during startup ({status}); see {}",
+                log_path.display()
+            ));
+        }
+        // Match the PID: with --replace the old instance may still be answering.
+        if control::request("ping")
+            .is_ok_and(|reply| reply.contains(&format!("\"pid\":{}}}", child.id())))
+        {
+            println!(
+                "[ok] renderer running in background (pid {}), log: {}",
+                child.id(),
+                log_path.display()
+            );
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            println!(
+                "[warn] renderer (pid {}) still starting after 30s, log: {}",
+                child.id(),
+                log_path.display()
+            );
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("krc-instance-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn holder_reads_the_owner_pid_without_touching_the_file() {
+        let path = lock_path("held.lock");
+        let lock = InstanceLock::acquire(&path).ok().unwrap();
+        let before = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(InstanceLock::holder(&path), Some(Some(std::process::id())));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+        // A probe holds nothing afterwards, so the owner keeps it exclusively.
+        assert!(matches!(
+            InstanceLock::acquire(&path),
+            Err(LockError::Held(Some(pid))) if pid == std::process::id()
+        ));
+        drop(lock);
+        assert_eq!(InstanceLock::holder(&path), None);
+    }
+
+    #[test]
+    fn holder_of_a_missing_file_is_none_and_creates_nothing() {
+        let path = lock_path("missing.lock");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(InstanceLock::holder(&path), None);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn holder_leaves_a_stale_file_to_the_next_owner() {
+        let path = lock_path("stale.lock");
+        std::fs::write(&path, "4242\n").unwrap();
+        assert_eq!(InstanceLock::holder(&path), None);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "4242\n");
+        let _lock = InstanceLock::acquire(&path).ok().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+    }
+
+    #[test]
+    fn hangups_are_caught_and_taken_once() {
+        install_hangup_handler();
+        send_signal(std::process::id(), libc::SIGHUP).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !HANGUP.load(Ordering::Relaxed) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(take_hangup());
+        assert!(!take_hangup());
+    }
+
+    #[test]
+    fn held_shutdown_signals_are_recorded_not_delivered() {
+        hold_shutdown_signals();
+        assert_eq!(shutdown_requested(), None);
+        send_signal(std::process::id(), libc::SIGTERM).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while shutdown_requested().is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(shutdown_requested(), Some(libc::SIGTERM));
+        // Releasing would deliver it and end the test process; clear it instead.
+        SHUTDOWN.store(0, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn signalling_checks_the_pid() {
+        // Signal 0 only checks that the process exists.
+        assert_eq!(send_signal(std::process::id(), 0), Ok(()));
+        assert!(
+            send_signal(u32::MAX, 0)
+                .unwrap_err()
+                .contains("invalid pid")
+        );
+        let err = send_signal(i32::MAX as u32, 0).unwrap_err();
+        assert!(err.starts_with("failed to signal pid"), "{err}");
+    }
+}
@@ -0,0 +1,92 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::json::escape_json;
+
+/// Bump when an event is renamed or a field changes meaning; `replay` refuses
+/// journals written with a different version.
+pub const JOURNAL_SCHEMA_VERSION: u64 = 2;
+
+pub enum Value<'a> {
+    Str(&'a str),
+    U64(u64),
+    Bool(bool),
+}
+
+struct JournalWriter {
+    out: BufWriter<File>,
+    started_at: Instant,
+}
+
+static JOURNAL: OnceLock<Option<Mutex<JournalWriter>>> = OnceLock::new();
+
+/// Opens the decision journal when `KRC_RECORD` is set. Safe to call more than once.
+pub fn init_from_env() {
+    JOURNAL.get_or_init(|| {
+        let path = std::env::var("KRC_RECORD").ok().filter(|v| !v.is_empty())?;
+        let file = match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("[rendercore] journal disabled: failed to open {path}: {err}");
+                return None;
+            }
+        };
+        println!("[rendercore] recording decision journal to {path}");
+        let writer = JournalWriter {
+            out: BufWriter::new(file),
+            started_at: Instant::now(),
+        };
+        let journal = Mutex::new(writer);
+        let wall_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        write_line(
+            &journal,
+            "journal_start",
+            &[
+                ("schema", Value::U64(JOURNAL_SCHEMA_VERSION)),
+                ("wall_ms", Value::U64(wall_ms)),
+                ("pid", Value::U64(std::process::id() as u64)),
+            ],
+        );
+        Some(journal)
+    });
+}
+
+#[cfg(feature = "wayland-layer")]
+pub fn is_recording() -> bool {
+    matches!(JOURNAL.get(), Some(Some(_)))
+}
+
+/// Appends one event line. A no-op when recording is disabled.
+pub fn record(event: &str, fields: &[(&str, Value)]) {
+    if let Some(Some(journal)) = JOURNAL.get() {
+        write_line(journal, event, fields);
+    }
+}
+
+fn write_line(journal: &Mutex<JournalWriter>, event: &str, fields: &[(&str, Value)]) {
+    let Ok(mut writer) = journal.lock() else {
+        return;
+    };
+    let t_ms = writer.started_at.elapsed().as_millis() as u64;
+    let mut line = format!(
+        "{{\"v\":{JOURNAL_SCHEMA_VERSION},\"t_ms\":{t_ms},\"event\":\"{}\"",
+        escape_json(event)
+    );
+    for (key, value) in fields {
+        match value {
+            Value::Str(s) => line.push_str(&format!(",\"{key}\":\"{}\"", escape_json(s))),
+            Value::U64(n) => line.push_str(&format!(",\"{key}\":{n}")),
+            Value::Bool(b) => line.push_str(&format!(",\"{key}\":{b}")),
+        }
+    }
+    line.push_str("}\n");
+    // Events are rare compared to frames, so flushing per line keeps the journal
+    // complete even when the process is killed.
+    let _ = writer.out.write_all(line.as_bytes());
+    let _ = writer.out.flush();
+}
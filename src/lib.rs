@@ -0,0 +1,97 @@
+//! Kitsune RenderCore as a library: the `kitsune-rendercore` binary is
+//! [`run`] over the command line, and another program can drive the same
+//! renderer through [`RenderCoreBuilder`], see `examples/`.
+
+mod aliases;
+mod app;
+mod backend;
+mod bench;
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+mod bitmap_font;
+#[cfg(feature = "wayland-layer")]
+mod callback_latency;
+mod clean;
+mod color;
+mod command_queue;
+mod config;
+mod config_bundle;
+mod config_file;
+mod control;
+#[cfg(feature = "dbus")]
+mod dbus_service;
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+mod decoder_process;
+mod doctor;
+mod embed;
+mod env_file;
+mod event_file;
+mod events;
+mod file_access;
+#[cfg(feature = "wayland-layer")]
+mod flash_guard;
+#[cfg(all(feature = "wayland-layer", feature = "video-ffmpeg"))]
+mod frame_arrival;
+#[cfg(feature = "wayland-layer")]
+mod frame_source;
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+mod headroom;
+mod hooks;
+#[cfg(feature = "http-control")]
+mod http_control;
+mod hw_decode;
+mod instance;
+mod journal;
+mod json;
+mod log_limit;
+mod map_apply;
+mod map_migrate;
+mod map_toml;
+mod map_watch;
+mod monitor;
+mod monitor_filter;
+#[cfg(feature = "wayland-layer")]
+mod path_probe;
+mod paths;
+mod pause;
+mod pause_file;
+mod priority;
+mod proc_stat;
+#[cfg(feature = "wayland-layer")]
+mod profile;
+mod quality;
+mod quiet_hours;
+mod reduce_motion;
+mod replay;
+mod resource_check;
+mod runtime;
+mod scheduler;
+mod service_logs;
+mod setup;
+mod snapshot;
+mod startup;
+mod status_format;
+mod status_schema;
+mod steam;
+mod supervisor;
+mod thermal;
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+mod timebase;
+mod tools;
+#[cfg(feature = "update-check")]
+mod update_check;
+mod user_path;
+mod version;
+mod video_map;
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+mod widgets;
+
+pub use app::run;
+pub use backend::LayerBackend;
+pub use config::RenderCoreConfig;
+pub use embed::{RenderCore, RenderCoreBuilder, RenderHandle};
+pub use events::RenderEvent;
+#[cfg(feature = "http-control")]
+pub use http_control::HttpConfig;
+pub use monitor::{
+    LayerRole, MonitorInfo, MonitorSurfaceSpec, OutputRuntimeStatus, OutputTransform,
+};
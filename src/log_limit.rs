@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long repeats of one message are counted before they are summarized.
+pub const REPEAT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Counts repeats of identical `(target, key)` messages: the first is logged
+/// in full, later ones within the window only counted, and each window with
+/// repeats ends in one summary line. A decoder that fails on every frame
+/// then costs one line a minute instead of sixty a second.
+pub struct RepeatLimiter {
+    window: Duration,
+    entries: HashMap<(String, String), Repeats>,
+    /// Earliest window end among the entries, so `flush` is cheap to call
+    /// every frame.
+    next_flush: Option<Instant>,
+}
+
+struct Repeats {
+    window_start: Instant,
+    suppressed: u64,
+}
+
+/// Repeats of one message left unlogged over a finished window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepeatSummary {
+    pub target: String,
+    pub key: String,
+    pub suppressed: u64,
+    pub window: Duration,
+}
+
+impl std::fmt::Display for RepeatSummary {
+    /// `output id=42: last message repeated 1799 times in the past 60 s: ffmpeg frame read failed: ...`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: last message repeated {} times in the past {} s: {}",
+            self.target,
+            self.suppressed,
+            self.window.as_secs(),
+            self.key
+        )
+    }
+}
+
+impl RepeatLimiter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: HashMap::new(),
+            next_flush: None,
+        }
+    }
+
+    /// Whether this occurrence should be logged in full; otherwise it is
+    /// counted toward the next summary.
+    pub fn allow(&mut self, target: &str, key: &str, now: Instant) -> bool {
+        if let Some(repeats) = self.entries.get_mut(&(target.to_string(), key.to_string())) {
+            repeats.suppressed += 1;
+            return false;
+        }
+        self.entries.insert(
+            (target.to_string(), key.to_string()),
+            Repeats {
+                window_start: now,
+                suppressed: 0,
+            },
+        );
+        let end = now + self.window;
+        self.next_flush = Some(self.next_flush.map_or(end, |next| next.min(end)));
+        true
+    }
+
+    /// Summaries of the windows that ended by `now`, or of every window with
+    /// `all` (at shutdown). A window with repeats starts a new one, so a
+    /// message that keeps coming gets a summary a window; one without
+    /// repeats is forgotten, and the message is logged in full next time.
+    pub fn flush(&mut self, now: Instant, all: bool) -> Vec<RepeatSummary> {
+        if !all && self.next_flush.is_none_or(|next| now < next) {
+            return Vec::new();
+        }
+        let window = self.window;
+        let mut summaries = Vec::new();
+        self.entries.retain(|(target, key), repeats| {
+            if !all && now.duration_since(repeats.window_start) < window {
+                return true;
+            }
+            if repeats.suppressed == 0 {
+                return false;
+            }
+            summaries.push(RepeatSummary {
+                target: target.clone(),
+                key: key.clone(),
+                suppressed: repeats.suppressed,
+                window,
+            });
+            repeats.window_start = now;
+            repeats.suppressed = 0;
+            !all
+        });
+        self.next_flush = self
+            .entries
+            .values()
+            .map(|repeats| repeats.window_start + window)
+            .min();
+        summaries.sort_by(|a, b| (&a.target, &a.key).cmp(&(&b.target, &b.key)));
+        summaries
+    }
+}
+
+static LIMITER: Mutex<Option<RepeatLimiter>> = Mutex::new(None);
+
+/// Logs `line()` to stderr unless the same `(target, key)` was logged within
+/// [`REPEAT_WINDOW`]. Returns whether it was logged.
+pub fn eprint_limited(target: &str, key: &str, line: impl FnOnce() -> String) -> bool {
+    let allowed = LIMITER.lock().map_or(true, |mut limiter| {
+        limiter
+            .get_or_insert_with(|| RepeatLimiter::new(REPEAT_WINDOW))
+            .allow(target, key, Instant::now())
+    });
+    if allowed {
+        eprintln!("{}", line());
+    }
+    allowed
+}
+
+/// Logs the summaries that are due; with `all`, every pending one (at exit).
+pub fn flush(all: bool) {
+    let summaries = match LIMITER.lock() {
+        Ok(mut limiter) => match limiter.as_mut() {
+            Some(limiter) => limiter.flush(Instant::now(), all),
+            None => return,
+        },
+        Err(_) => return,
+    };
+    for summary in summaries {
+        eprintln!("[rendercore] {summary}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    fn secs(t0: Instant, secs: u64) -> Instant {
+        t0 + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn first_occurrence_is_logged_and_repeats_are_counted() {
+        let t0 = Instant::now();
+        let mut limiter = RepeatLimiter::new(WINDOW);
+        assert!(limiter.allow("output id=1", "frame read failed", t0));
+        for frame in 1..=1799 {
+            let now = t0 + Duration::from_millis(frame * 33);
+            assert!(!limiter.allow("output id=1", "frame read failed", now));
+        }
+        // Other targets and other messages are limited on their own.
+        assert!(limiter.allow("output id=2", "frame read failed", t0));
+        assert!(limiter.allow("output id=1", "video path does not exist", t0));
+
+        assert!(limiter.flush(secs(t0, 59), false).is_empty());
+        let summaries = limiter.flush(secs(t0, 60), false);
+        assert_eq!(
+            summaries,
+            [RepeatSummary {
+                target: "output id=1".to_string(),
+                key: "frame read failed".to_string(),
+                suppressed: 1799,
+                window: WINDOW,
+            }]
+        );
+        assert_eq!(
+            summaries[0].to_string(),
+            "output id=1: last message repeated 1799 times in the past 60 s: frame read failed"
+        );
+    }
+
+    #[test]
+    fn a_message_that_keeps_coming_gets_one_summary_per_window() {
+        let t0 = Instant::now();
+        let mut limiter = RepeatLimiter::new(WINDOW);
+        assert!(limiter.allow("surface", "lost", t0));
+        assert!(!limiter.allow("surface", "lost", secs(t0, 10)));
+        assert_eq!(limiter.flush(secs(t0, 60), false)[0].suppressed, 1);
+
+        // Still suppressed: the repeat started a new window at 60 s.
+        assert!(!limiter.allow("surface", "lost", secs(t0, 70)));
+        assert!(!limiter.allow("surface", "lost", secs(t0, 80)));
+        assert!(limiter.flush(secs(t0, 119), false).is_empty());
+        assert_eq!(limiter.flush(secs(t0, 120), false)[0].suppressed, 2);
+    }
+
+    #[test]
+    fn a_quiet_window_forgets_the_message() {
+        let t0 = Instant::now();
+        let mut limiter = RepeatLimiter::new(WINDOW);
+        assert!(limiter.allow("reload", "map line 3 skipped", t0));
+        assert!(limiter.flush(secs(t0, 60), false).is_empty());
+        assert!(limiter.allow("reload", "map line 3 skipped", secs(t0, 61)));
+    }
+
+    #[test]
+    fn flush_all_drains_every_pending_summary_in_order() {
+        let t0 = Instant::now();
+        let mut limiter = RepeatLimiter::new(WINDOW);
+        for target in ["output id=2", "output id=1"] {
+            assert!(limiter.allow(target, "frame read failed", t0));
+            assert!(!limiter.allow(target, "frame read failed", secs(t0, 1)));
+        }
+        assert!(limiter.allow("output id=3", "frame read failed", t0));
+        let summaries = limiter.flush(secs(t0, 5), true);
+        let targets = summaries
+            .iter()
+            .map(|s| s.target.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(targets, ["output id=1", "output id=2"]);
+        assert!(limiter.flush(secs(t0, 500), true).is_empty());
+        assert!(limiter.allow("output id=1", "frame read failed", secs(t0, 6)));
+    }
+
+    #[test]
+    fn windows_end_per_message() {
+        let t0 = Instant::now();
+        let mut limiter = RepeatLimiter::new(WINDOW);
+        assert!(limiter.allow("a", "x", t0));
+        assert!(!limiter.allow("a", "x", secs(t0, 1)));
+        assert!(limiter.allow("b", "x", secs(t0, 30)));
+        assert!(!limiter.allow("b", "x", secs(t0, 31)));
+        let first = limiter.flush(secs(t0, 60), false);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].target, "a");
+        let second = limiter.flush(secs(t0, 90), false);
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].target, "b");
+    }
+}
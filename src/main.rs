@@ -1,16 +1,5 @@
-mod app;
-mod backend;
-mod config;
-#[cfg(feature = "wayland-layer")]
-mod frame_source;
-mod monitor;
-mod runtime;
-mod scheduler;
-mod steam;
-mod video_map;
-
 fn main() {
-    if let Err(err) = app::run() {
+    if let Err(err) = kitsune_rendercore::run() {
         eprintln!("rendercore error: {err}");
         std::process::exit(1);
     }
@@ -0,0 +1,409 @@
+//! `set-video` waiting for the running renderer to show the change. The CLI
+//! notes where the renderer's event log is before it writes the map, asks
+//! for a `reload` so the change applies now instead of at the next map
+//! check, and follows the events until every stream the reload switched has
+//! published `stream_presented` for its new video. A `stream_degraded` for
+//! one of them, or the timeout, fails the wait with the stream's reason. A
+//! paused renderer or output presents nothing until it resumes; its streams
+//! are not waited for, and fail the command naming the pause unless
+//! `--allow-paused` accepts them.
+
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::control;
+use crate::json::{JsonValue, parse_json};
+use crate::user_path::{display_path, display_text};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Reload actions after which the stream opens a decoder and a
+/// `stream_presented` follows.
+const DECODER_ACTIONS: [&str; 2] = ["switched", "restarted"];
+
+/// A wait begun before the map file is written.
+pub struct ApplyWait {
+    after: u64,
+}
+
+impl ApplyWait {
+    /// Notes the renderer's last event. Fails without a renderer on the
+    /// control socket, or when it reads another map file than `map_path`:
+    /// its reload would not see the change.
+    pub fn begin(map_path: &Path) -> Result<Self, String> {
+        let live = parse_json(&control::request("status")?)
+            .map_err(|e| format!("bad status reply: {e}"))?;
+        if let Some(renderer_map) = live.get("map_file").and_then(JsonValue::as_str)
+            && Path::new(renderer_map) != map_path
+        {
+            return Err(format!("the running renderer reads {renderer_map}"));
+        }
+        let after = events(None)?
+            .get("next")
+            .and_then(JsonValue::as_u64)
+            .unwrap_or(0);
+        Ok(Self { after })
+    }
+
+    /// Reloads the renderer and waits up to `timeout` for each stream the
+    /// reload switched to present a frame of its new video. A stream the map
+    /// check already switched reports `loading` until it is on screen; it is
+    /// waited for when it plays one of `videos`, so an output paused since
+    /// startup is not. Streams on a paused output fail the wait, or count as
+    /// applied with `allow_paused`. A backend without a video map rejects
+    /// the reload; there is nothing to wait for.
+    pub fn finish(
+        self,
+        videos: &[String],
+        timeout: Duration,
+        allow_paused: bool,
+    ) -> Result<(), String> {
+        let started = Instant::now();
+        let reply = parse_json(&control::request("reload")?)
+            .map_err(|e| format!("unreadable reload reply: {e}"))?;
+        if let Some(err) = reply.get("error").and_then(JsonValue::as_str) {
+            println!("[info] not waiting for the renderer: {err}");
+            return Ok(());
+        }
+        let mut waiting = Vec::new();
+        if let Some(JsonValue::Array(outputs)) = reply.get("outputs") {
+            for out in outputs {
+                let text = |key: &str| out.get(key).and_then(JsonValue::as_str).unwrap_or("");
+                let video = text("video");
+                let affected = match text("action") {
+                    "loading" => videos.iter().any(|v| v == video),
+                    action => DECODER_ACTIONS.contains(&action),
+                };
+                if affected && !video.is_empty() {
+                    waiting.push((text("name").to_string(), text("video").to_string()));
+                }
+            }
+        }
+        if waiting.is_empty() {
+            println!("[ok] renderer applied the change (no stream needed a new decoder)");
+            return Ok(());
+        }
+        let mut paused = set_aside_paused(&mut waiting, live_status().as_ref(), allow_paused);
+        if waiting.is_empty() {
+            return outcome(0, paused);
+        }
+        println!(
+            "[info] waiting up to {:.1}s for {} stream(s) to show the new video",
+            timeout.as_secs_f64(),
+            waiting.len()
+        );
+
+        let mut after = self.after;
+        let mut failed = 0usize;
+        loop {
+            let reply = events(Some(after))
+                .map_err(|err| format!("renderer stopped answering while waiting: {err}"))?;
+            let missed = reply.get("missed").and_then(JsonValue::as_u64).unwrap_or(0);
+            if missed > 0 {
+                eprintln!("[warn] missed {missed} event(s) while waiting");
+            }
+            if let Some(JsonValue::Array(list)) = reply.get("events") {
+                for event in list {
+                    let text = |key: &str| event.get(key).and_then(JsonValue::as_str).unwrap_or("");
+                    let Some(index) = waiting.iter().position(|(name, _)| name == text("monitor"))
+                    else {
+                        continue;
+                    };
+                    match text("event") {
+                        "stream_presented" if text("video") == waiting[index].1 => {
+                            let (name, video) = waiting.remove(index);
+                            println!(
+                                "[ok] {} shows {} ({}ms)",
+                                display_text(&name),
+                                display_path(&video),
+                                started.elapsed().as_millis()
+                            );
+                        }
+                        "stream_degraded" => {
+                            let (name, _) = waiting.remove(index);
+                            println!("[fail] {}: {}", display_text(&name), text("reason"));
+                            failed += 1;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            after = reply
+                .get("next")
+                .and_then(JsonValue::as_u64)
+                .unwrap_or(after);
+            if waiting.is_empty() {
+                break;
+            }
+            if started.elapsed() >= timeout {
+                // Paused while we waited: it shows the video on resume.
+                let live = live_status();
+                paused += set_aside_paused(&mut waiting, live.as_ref(), allow_paused);
+                for (name, video) in &waiting {
+                    println!(
+                        "[fail] {}: no frame of {} within {:.1}s{}",
+                        display_text(name),
+                        display_path(video),
+                        timeout.as_secs_f64(),
+                        stream_state(live.as_ref(), name)
+                    );
+                }
+                failed += waiting.len();
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        outcome(failed, paused)
+    }
+}
+
+/// The wait's result from the streams that failed and those refused for
+/// being paused.
+fn outcome(failed: usize, paused: usize) -> Result<(), String> {
+    match (failed, paused) {
+        (0, 0) => Ok(()),
+        (0, n) => Err(format!(
+            "{n} stream(s) are paused and will show the new video only on resume; pass --allow-paused to accept that"
+        )),
+        (n, 0) => Err(format!(
+            "the renderer did not show the new video on {n} stream(s); see: kitsune-rendercore status"
+        )),
+        (n, p) => Err(format!(
+            "the renderer did not show the new video on {n} stream(s) and {p} are paused; see: kitsune-rendercore status"
+        )),
+    }
+}
+
+fn events(after: Option<u64>) -> Result<JsonValue, String> {
+    let command = match after {
+        Some(after) => format!("events {after}"),
+        None => "events".to_string(),
+    };
+    let value =
+        parse_json(&control::request(&command)?).map_err(|e| format!("bad events reply: {e}"))?;
+    if let Some(err) = value.get("error").and_then(JsonValue::as_str) {
+        return Err(format!("renderer rejected events: {err}"));
+    }
+    Ok(value)
+}
+
+fn live_status() -> Option<JsonValue> {
+    control::request("status")
+        .ok()
+        .and_then(|raw| parse_json(&raw).ok())
+}
+
+/// Drops the streams whose output is paused from `waiting`: they present
+/// nothing until the output resumes. Each gets an `[info]` line with
+/// `allow_paused`, else a `[fail]` naming the pause; returns how many
+/// failed.
+fn set_aside_paused(
+    waiting: &mut Vec<(String, String)>,
+    live: Option<&JsonValue>,
+    allow_paused: bool,
+) -> usize {
+    let mut refused = 0usize;
+    waiting.retain(|(name, video)| {
+        let Some(reason) = pause_reason(live, name) else {
+            return true;
+        };
+        if allow_paused {
+            println!(
+                "[info] {} is paused ({reason}), will show {} on resume",
+                display_text(name),
+                display_path(video)
+            );
+        } else {
+            println!(
+                "[fail] {} is paused ({reason}): {} shows only on resume",
+                display_text(name),
+                display_path(video)
+            );
+            refused += 1;
+        }
+        false
+    });
+    refused
+}
+
+/// A stream name (`DP-1`, or a layout's `DP-1 cell 2`) as its output's name
+/// and cell.
+fn split_stream(name: &str) -> (&str, Option<usize>) {
+    match name.rsplit_once(" cell ") {
+        Some((output, cell)) => (output, cell.parse::<usize>().ok()),
+        None => (name, None),
+    }
+}
+
+/// The live status entry of `output`.
+fn live_output<'a>(live: Option<&'a JsonValue>, output: &str) -> Option<&'a JsonValue> {
+    let Some(JsonValue::Array(outputs)) = live?.get("outputs") else {
+        return None;
+    };
+    outputs
+        .iter()
+        .find(|out| out.get("name").and_then(JsonValue::as_str) == Some(output))
+}
+
+/// Why the output a stream is on is paused (its own pause, or the whole
+/// renderer's), or `None` while it plays.
+fn pause_reason(live: Option<&JsonValue>, name: &str) -> Option<String> {
+    let out = live_output(live, split_stream(name).0)?;
+    let paused = |value: &JsonValue| value.get("paused").and_then(JsonValue::as_bool) == Some(true);
+    let pauser = [Some(out), live]
+        .into_iter()
+        .flatten()
+        .find(|v| paused(v))?;
+    Some(
+        pauser
+            .get("pause_reason")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("?")
+            .to_string(),
+    )
+}
+
+/// `; degraded: ...; last error: ...` for a stream from the live status, or
+/// nothing.
+fn stream_state(live: Option<&JsonValue>, name: &str) -> String {
+    let (output, cell) = split_stream(name);
+    let Some(out) = live_output(live, output) else {
+        return String::new();
+    };
+    let mut state = String::new();
+    let stream = match cell {
+        Some(cell) => match out.get("cells") {
+            Some(JsonValue::Array(cells)) => cells
+                .iter()
+                .find(|c| c.get("cell").and_then(JsonValue::as_u64) == Some(cell as u64)),
+            _ => None,
+        },
+        None => {
+            if let Some(degraded) = out
+                .get("degraded")
+                .and_then(JsonValue::as_str)
+                .filter(|d| *d != "none")
+            {
+                state.push_str(&format!("; degraded: {degraded}"));
+            }
+            Some(out)
+        }
+    };
+    if let Some(err) = stream
+        .and_then(|s| s.get("last_error"))
+        .and_then(JsonValue::as_str)
+    {
+        state.push_str(&format!("; last error: {err}"));
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Live status: DP-1 plays, DP-2 is paused on its own, HDMI-A-1 is a
+    /// layout with a failing cell.
+    const LIVE: &str = r#"{"paused":false,"pause_reason":"none","outputs":[
+        {"name":"DP-1","paused":false,"pause_reason":"none","degraded":"none"},
+        {"name":"DP-2","paused":true,"pause_reason":"manual-output","degraded":"none","last_error":null},
+        {"name":"HDMI-A-1","paused":false,"degraded":"failing","last_error":"decoder exited",
+         "cells":[{"cell":1},{"cell":2,"last_error":"no such file"}]}
+    ]}"#;
+
+    fn live() -> JsonValue {
+        parse_json(LIVE).unwrap()
+    }
+
+    fn streams(names: &[&str]) -> Vec<(String, String)> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), format!("/v/{name}.mp4")))
+            .collect()
+    }
+
+    #[test]
+    fn output_pauses_are_found_per_stream() {
+        let live = live();
+        assert_eq!(pause_reason(Some(&live), "DP-1"), None);
+        assert_eq!(
+            pause_reason(Some(&live), "DP-2").as_deref(),
+            Some("manual-output")
+        );
+        assert_eq!(pause_reason(Some(&live), "HDMI-A-1 cell 2"), None);
+        assert_eq!(pause_reason(Some(&live), "DP-9"), None);
+        assert_eq!(pause_reason(None, "DP-2"), None);
+    }
+
+    #[test]
+    fn a_paused_renderer_pauses_every_output() {
+        let live = parse_json(&LIVE.replacen(
+            r#""paused":false,"pause_reason":"none""#,
+            r#""paused":true,"pause_reason":"steam-game""#,
+            1,
+        ))
+        .unwrap();
+        assert_eq!(
+            pause_reason(Some(&live), "DP-1").as_deref(),
+            Some("steam-game")
+        );
+        // The output's own reason wins.
+        assert_eq!(
+            pause_reason(Some(&live), "DP-2").as_deref(),
+            Some("manual-output")
+        );
+        assert_eq!(
+            pause_reason(Some(&live), "HDMI-A-1 cell 1").as_deref(),
+            Some("steam-game")
+        );
+    }
+
+    #[test]
+    fn paused_streams_are_not_waited_for() {
+        let live = live();
+        let mut waiting = streams(&["DP-1", "DP-2", "HDMI-A-1 cell 2"]);
+        assert_eq!(set_aside_paused(&mut waiting, Some(&live), true), 0);
+        assert_eq!(waiting, streams(&["DP-1", "HDMI-A-1 cell 2"]));
+        // Without a status reply nothing is set aside.
+        let mut waiting = streams(&["DP-2"]);
+        assert_eq!(set_aside_paused(&mut waiting, None, false), 0);
+        assert_eq!(waiting, streams(&["DP-2"]));
+    }
+
+    #[test]
+    fn paused_streams_fail_unless_allowed() {
+        let live = live();
+        let mut waiting = streams(&["DP-1", "DP-2"]);
+        let paused = set_aside_paused(&mut waiting, Some(&live), false);
+        assert_eq!((paused, waiting), (1, streams(&["DP-1"])));
+        let err = outcome(0, paused).unwrap_err();
+        assert!(err.contains("paused"), "{err}");
+        assert!(err.contains("--allow-paused"), "{err}");
+        let err = outcome(2, paused).unwrap_err();
+        assert!(err.contains("2 stream(s) and 1 are paused"), "{err}");
+
+        let mut waiting = streams(&["DP-1", "DP-2"]);
+        let paused = set_aside_paused(&mut waiting, Some(&live), true);
+        assert_eq!(outcome(0, paused), Ok(()));
+        assert!(outcome(1, paused).unwrap_err().contains("1 stream(s);"));
+    }
+
+    #[test]
+    fn stream_state_names_degraded_and_last_error() {
+        let live = live();
+        assert_eq!(stream_state(Some(&live), "DP-1"), "");
+        assert_eq!(
+            stream_state(Some(&live), "HDMI-A-1"),
+            "; degraded: failing; last error: decoder exited"
+        );
+        assert_eq!(
+            stream_state(Some(&live), "HDMI-A-1 cell 2"),
+            "; last error: no such file"
+        );
+        assert_eq!(stream_state(Some(&live), "HDMI-A-1 cell 1"), "");
+        assert_eq!(stream_state(None, "DP-1"), "");
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::video_map::{
+    MapDocument, MapFormat, check_monitor_name, format_map_document, map_file_path_from_env,
+    parse_map_document, write_map_document,
+};
+
+/// `migrate-map`: converts the map file to the other format next to it, keeps
+/// the original as `<file>.<format>.bak`, and refuses to write anything unless
+/// the converted text parses back to exactly the same entries.
+pub fn run_migrate_map(args: &[String]) -> Result<(), String> {
+    let mut map_file = None::<PathBuf>;
+    let mut output = None::<PathBuf>;
+    let mut target = MapFormat::V2;
+    let mut check_only = false;
+    let mut i = 0usize;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        let mut value = |name: &str| {
+            i += 1;
+            args.get(i)
+                .cloned()
+                .ok_or_else(|| format!("missing value for {name}"))
+        };
+        match arg {
+            "--map-file" => map_file = Some(PathBuf::from(value("--map-file")?)),
+            "--output" => output = Some(PathBuf::from(value("--output")?)),
+            "--to" => {
+                target = match value("--to")?.as_str() {
+                    "v1" | "1" => MapFormat::V1,
+                    "v2" | "2" => MapFormat::V2,
+                    other => return Err(format!("--to expects v1 or v2, got '{other}'")),
+                }
+            }
+            "--check" => check_only = true,
+            "--help" | "-h" => {
+                print_migrate_map_help();
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument for migrate-map: {other}")),
+        }
+        i += 1;
+    }
+
+    let source = map_file.map_or_else(map_file_path_from_env, Ok)?;
+    let contents = fs::read_to_string(&source)
+        .map_err(|e| format!("cannot read map file {}: {e}", source.display()))?;
+    let (doc, issues) = parse_map_document(&source, &contents);
+    if !issues.is_empty() {
+        for issue in &issues {
+            println!("[warn] line {}: {}", issue.line, issue.message);
+        }
+        return Err(format!(
+            "{} has {} line(s) the renderer skips; fix or remove them first so the migration does not drop them (see 'watch-map --once')",
+            source.display(),
+            issues.len()
+        ));
+    }
+    if doc.format == target {
+        return Err(format!(
+            "{} is already a {} map",
+            source.display(),
+            target.label()
+        ));
+    }
+    if target == MapFormat::V1 && doc.default.is_some() {
+        return Err(
+            "a v1 map cannot hold [default]; move it to KRC_VIDEO_DEFAULT and remove the table first"
+                .to_string(),
+        );
+    }
+    for monitor in doc.entries.keys() {
+        check_monitor_name(target, monitor)?;
+    }
+
+    let output = output.unwrap_or_else(|| {
+        source.with_extension(match target {
+            MapFormat::V1 => "conf",
+            MapFormat::V2 => "toml",
+        })
+    });
+    if output == source {
+        return Err(format!(
+            "output {} is the source file; pass --output",
+            output.display()
+        ));
+    }
+    let converted = MapDocument {
+        format: target,
+        ..doc.clone()
+    };
+    verify_round_trip(&doc, &converted, &output)?;
+    let entries = doc.entries.len();
+    let default_note = if doc.default.is_some() {
+        " and [default]"
+    } else {
+        ""
+    };
+    if check_only {
+        println!(
+            "[ok] round trip verified: {entries} entr{}{default_note} would be written to {} ({})",
+            if entries == 1 { "y" } else { "ies" },
+            output.display(),
+            target.label()
+        );
+        return Ok(());
+    }
+
+    if output.exists() {
+        return Err(format!(
+            "{} already exists; move it away or pass --output",
+            output.display()
+        ));
+    }
+    let backup = backup_path(&source, doc.format);
+    if backup.exists() {
+        return Err(format!(
+            "backup {} already exists; move it away first",
+            backup.display()
+        ));
+    }
+    write_map_document(&output, &converted)?;
+    fs::rename(&source, &backup).map_err(|e| {
+        format!(
+            "wrote {} but could not move {} to {}: {e}; remove one of them so only one map is active",
+            output.display(),
+            source.display(),
+            backup.display()
+        )
+    })?;
+    println!(
+        "[ok] migrated {entries} entr{}{default_note} from {} ({}) to {} ({})",
+        if entries == 1 { "y" } else { "ies" },
+        source.display(),
+        doc.format.label(),
+        output.display(),
+        target.label()
+    );
+    println!("[ok] original kept as {}", backup.display());
+    match std::env::var("KRC_VIDEO_MAP_FILE") {
+        Ok(path) if !path.trim().is_empty() && Path::new(&path) != output => println!(
+            "[warn] KRC_VIDEO_MAP_FILE={path}; point it at {} to use the migrated map",
+            output.display()
+        ),
+        _ => {}
+    }
+    println!(
+        "[info] a running renderer keeps its map path until restarted: kitsune-rendercore service restart"
+    );
+    Ok(())
+}
+
+/// Formats `converted`, parses the text back as if it were at `output`, and
+/// compares with what the source parsed to.
+pub fn verify_round_trip(
+    source: &MapDocument,
+    converted: &MapDocument,
+    output: &Path,
+) -> Result<(), String> {
+    let text = format_map_document(converted);
+    let (reparsed, issues) = parse_map_document(output, &text);
+    if let Some(issue) = issues.first() {
+        return Err(format!(
+            "round trip failed: converted line {} does not parse: {}",
+            issue.line, issue.message
+        ));
+    }
+    if reparsed.format != converted.format {
+        return Err(format!(
+            "round trip failed: {} would be read back as {}, not {}",
+            output.display(),
+            reparsed.format.label(),
+            converted.format.label()
+        ));
+    }
+    if reparsed.entries != source.entries || reparsed.default != source.default {
+        return Err("round trip failed: the converted map reads back differently".to_string());
+    }
+    Ok(())
+}
+
+fn backup_path(source: &Path, format: MapFormat) -> PathBuf {
+    let mut name = source.as_os_str().to_os_string();
+    name.push(format!(".{}.bak", format.label()));
+    PathBuf::from(name)
+}
+
+fn print_migrate_map_help() {
+    println!("kitsune-rendercore migrate-map");
+    println!("Usage:");
+    println!(
+        "  kitsune-rendercore migrate-map [--map-file <PATH>] [--output <PATH>] [--to v1|v2] [--check]"
+    );
+    println!();
+    println!("Description:");
+    println!("  Converts the map file between v1 (monitor=path lines, video-map.conf) and");
+    println!("  v2 (TOML tables, video-map.toml). The converted text must parse back to the");
+    println!("  same entries before anything is written; the original is renamed to");
+    println!("  <file>.v1.bak (or .v2.bak). Without --map-file the active map is migrated.");
+    println!();
+    println!("Options:");
+    println!("  --map-file <PATH>  Map file to convert (default: the active map file).");
+    println!("  --output <PATH>    Where to write (default: same name with .toml or .conf).");
+    println!("  --to <v1|v2>       Target format (default: v2).");
+    println!("  --check            Verify the round trip and print the result; write nothing.");
+}
@@ -0,0 +1,520 @@
+use std::path::Path;
+
+use crate::color::Gamut;
+use crate::config_file::parse_value;
+use crate::user_path::normalize_video_path;
+use crate::video_map::{
+    AmbientParams, Effect, EntryOptions, Fit, Flip, Layout, MapLineIssue, Overlay, OverlayParams,
+    QualityPreset, Rotation, SampleFilter, VideoMap, VideoMapEntry, clock_str, split_still,
+};
+
+/// Keys a `[default]` or `[monitors.NAME]` table may hold.
+const ENTRY_KEYS: &[&str] = &[
+    "video",
+    "rotate",
+    "flip",
+    "quality",
+    "effect",
+    "amplitude",
+    "period",
+    "drift",
+    "overlay",
+    "density",
+    "speed",
+    "wind",
+    "trim",
+    "crop",
+    "filter",
+    "fit",
+    "gamut",
+    "clock",
+    "timebase",
+];
+
+/// Map format v2 (`video-map.toml`): an optional `version = 2`, a `[default]`
+/// table and one `[monitors.NAME]` table per output, each holding `video`
+/// plus the same options v1 writes inline. Names that are not bare keys
+/// (`A-Z a-z 0-9 _ -`) are quoted: `[monitors."Dell Inc. U2720Q"]`.
+///
+/// Parsed with the same TOML subset as config.toml rather than a TOML crate:
+/// the crate depends on neither `toml` nor `serde` (serde only reaches the
+/// build through the optional `dbus` feature, `toml_edit` only inside a
+/// proc-macro), and the file needs no more than headers and one-line
+/// `key = value` pairs, which is all `format_map_toml` writes. Anything past
+/// that (inline tables, multi-line strings, `[[arrays]]`) is reported as a
+/// skipped line, never misread. A table with a bad key or value is skipped as
+/// a whole, like a bad v1 line.
+pub fn parse_map_toml(
+    contents: &str,
+    base: &Path,
+) -> (VideoMap, Option<VideoMapEntry>, Vec<MapLineIssue>) {
+    let mut parser = Parser {
+        base,
+        map: VideoMap::new(),
+        default: None,
+        issues: Vec::new(),
+        table: None,
+        skipping: false,
+    };
+    for (idx, line) in contents.lines().enumerate() {
+        parser.line(idx + 1, line.trim());
+    }
+    parser.finish_table();
+    (parser.map, parser.default, parser.issues)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TableName {
+    Default,
+    Monitor(String),
+}
+
+impl TableName {
+    fn label(&self) -> String {
+        match self {
+            TableName::Default => "[default]".to_string(),
+            TableName::Monitor(name) => format!("[monitors.{}]", toml_key(name)),
+        }
+    }
+}
+
+/// The table being read; `None` fields until their key shows up.
+struct OpenTable {
+    name: TableName,
+    line: usize,
+    video: Option<String>,
+    layout: Option<Layout>,
+    options: EntryOptions,
+    /// Set once a key fails, so the table is dropped at its end.
+    failed: bool,
+    seen: Vec<&'static str>,
+}
+
+struct Parser<'a> {
+    base: &'a Path,
+    map: VideoMap,
+    default: Option<VideoMapEntry>,
+    issues: Vec<MapLineIssue>,
+    /// `None` before the first header, or inside a table being skipped.
+    table: Option<OpenTable>,
+    /// Inside a table whose header was rejected: its keys are ignored quietly.
+    skipping: bool,
+}
+
+impl Parser<'_> {
+    fn issue(&mut self, line: usize, message: String) {
+        self.issues.push(MapLineIssue { line, message });
+    }
+
+    fn line(&mut self, line_no: usize, line: &str) {
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        if line.starts_with('[') {
+            self.finish_table();
+            match parse_header(line) {
+                Ok(name) => self.open_table(line_no, name),
+                Err(err) => {
+                    self.issue(line_no, err);
+                    self.skipping = true;
+                }
+            }
+            return;
+        }
+        if self.skipping {
+            return;
+        }
+        let Some((key, raw_value)) = line.split_once('=') else {
+            self.issue(line_no, "expected key = value".to_string());
+            return;
+        };
+        let key = key.trim();
+        let raw_value = raw_value.trim();
+        let value = match parse_value(raw_value) {
+            Ok(value) => value,
+            Err(err) => {
+                self.fail_key(line_no, key, err);
+                return;
+            }
+        };
+        let Some(table) = self.table.as_mut() else {
+            match key {
+                "version" if value == "2" => {}
+                "version" => self.issue(
+                    line_no,
+                    format!("unsupported map version '{value}' (this build reads 2)"),
+                ),
+                other => self.issue(
+                    line_no,
+                    format!("'{other}' must be inside [default] or a [monitors.NAME] table"),
+                ),
+            }
+            return;
+        };
+        let Some(&known) = ENTRY_KEYS.iter().find(|k| **k == key) else {
+            self.fail_key(
+                line_no,
+                key,
+                format!("unknown key (expected one of {})", ENTRY_KEYS.join(", ")),
+            );
+            return;
+        };
+        if table.seen.contains(&known) {
+            self.fail_key(line_no, key, "duplicate key".to_string());
+            return;
+        }
+        table.seen.push(known);
+        let result = match known {
+            "video" if !raw_value.starts_with('"') => {
+                Err("video must be a quoted string".to_string())
+            }
+            "video" if value.trim().is_empty() => Err("video path is empty".to_string()),
+            "video" if value.starts_with("layout=") => {
+                Layout::parse(&value["layout=".len()..], self.base).map(|layout| {
+                    let entry = VideoMapEntry::from_layout(layout, EntryOptions::default());
+                    table.video = Some(entry.video);
+                    table.layout = entry.layout;
+                })
+            }
+            "video" => split_still(&value).and_then(|(video, still)| {
+                if video.trim().is_empty() {
+                    return Err("video path is empty".to_string());
+                }
+                table.options.still = still;
+                table.video = Some(normalize_video_path(video, self.base)?);
+                Ok(())
+            }),
+            "rotate" => Rotation::parse(&value).map(|r| table.options.rotate = r),
+            "flip" => Flip::parse(&value).map(|f| table.options.flip = f),
+            "quality" => QualityPreset::parse_override(&value).map(|q| table.options.quality = q),
+            _ => table.options.set(known, &value),
+        };
+        if let Err(err) = result {
+            self.fail_key(line_no, key, err);
+        }
+    }
+
+    /// Reports a bad key and marks the open table to be skipped.
+    fn fail_key(&mut self, line_no: usize, key: &str, err: String) {
+        let label = self
+            .table
+            .as_ref()
+            .map(|t| t.name.label())
+            .unwrap_or_default();
+        if let Some(table) = self.table.as_mut() {
+            table.failed = true;
+        }
+        let prefix = if label.is_empty() {
+            String::new()
+        } else {
+            format!("{label} ")
+        };
+        self.issue(line_no, format!("{prefix}{key}: {err}"));
+    }
+
+    fn open_table(&mut self, line_no: usize, name: TableName) {
+        let duplicate = match &name {
+            TableName::Default => self.default.is_some(),
+            TableName::Monitor(monitor) => self.map.contains_key(monitor),
+        };
+        if duplicate {
+            self.issue(
+                line_no,
+                format!("{} is defined twice; this one is ignored", name.label()),
+            );
+            self.skipping = true;
+            return;
+        }
+        self.skipping = false;
+        self.table = Some(OpenTable {
+            name,
+            line: line_no,
+            video: None,
+            layout: None,
+            options: EntryOptions::default(),
+            failed: false,
+            seen: Vec::new(),
+        });
+    }
+
+    fn finish_table(&mut self) {
+        let Some(table) = self.table.take() else {
+            return;
+        };
+        if table.failed {
+            return;
+        }
+        let Some(video) = table.video else {
+            self.issue(
+                table.line,
+                format!("{} has no video = \"/path\"", table.name.label()),
+            );
+            return;
+        };
+        let entry = VideoMapEntry {
+            video,
+            options: table.options,
+            layout: table.layout,
+        };
+        match table.name {
+            TableName::Default => self.default = Some(entry),
+            TableName::Monitor(monitor) => {
+                self.map.insert(monitor, entry);
+            }
+        }
+    }
+}
+
+fn parse_header(line: &str) -> Result<TableName, String> {
+    let body = line
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .filter(|(_, rest)| {
+            let rest = rest.trim();
+            rest.is_empty() || rest.starts_with('#')
+        })
+        .map(|(body, _)| body.trim())
+        .ok_or_else(|| "malformed table header".to_string())?;
+    if body == "default" {
+        return Ok(TableName::Default);
+    }
+    let Some(key) = body.strip_prefix("monitors.") else {
+        return Err(format!(
+            "unknown table [{body}] (expected [default] or [monitors.NAME])"
+        ));
+    };
+    let key = key.trim();
+    let name = if key.starts_with('"') {
+        parse_value(key).map_err(|err| format!("monitor name in [{body}]: {err}"))?
+    } else if !key.is_empty() && key.chars().all(is_bare_key_char) {
+        key.to_string()
+    } else {
+        return Err(format!(
+            "monitor name in [{body}] must be quoted (e.g. [monitors.\"{key}\"])"
+        ));
+    };
+    if name.trim().is_empty() {
+        return Err("monitor name is empty".to_string());
+    }
+    Ok(TableName::Monitor(name))
+}
+
+fn is_bare_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// A monitor name as a TOML key: bare when possible, else a quoted string.
+fn toml_key(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(is_bare_key_char) {
+        name.to_string()
+    } else {
+        toml_string(name)
+    }
+}
+
+fn toml_string(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len() + 2);
+    out.push('"');
+    for c in raw.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Serializes a v2 map file, header comment included.
+pub fn format_map_toml(map: &VideoMap, default: Option<&VideoMapEntry>) -> String {
+    let mut out = String::from("# kitsune-rendercore video map, format v2\n");
+    out.push_str(
+        "# [monitors.NAME]: video = \"/path\" [rotate = 0|90|180|270] [flip = \"h|v|hv\"]\n",
+    );
+    out.push_str("#   [quality = \"low|medium|high|ultra\"]\n");
+    out.push_str("#   [effect = \"ambient\" amplitude = 0.05 period = 300 drift = 0-1]\n");
+    out.push_str("#   [overlay = \"snow|rain|fireflies\" density = 0.5 speed = 1 wind = -1..1]\n");
+    out.push_str("#   [trim = \"IN:OUT\" (seconds)]\n");
+    out.push_str("#   [crop = \"X,Y,W,H\" (pixels, or 0-1 fractions)]\n");
+    out.push_str("#   [filter = \"nearest|linear\"] [fit = \"cover|integer\"]\n");
+    out.push_str(
+        "#   [gamut = \"auto|srgb|native|matrix:M11,M12,...,M33\"] [clock = \"on|off\"]\n",
+    );
+    out.push_str("#   [timebase = \"monotonic|utc\"]\n");
+    out.push_str("#   video = \"still:/path@00:01:23\" shows that one frame instead\n");
+    out.push_str("#   video = \"layout=grid2x2:/a,/b,/c,/d\" tiles several videos, row by row\n");
+    out.push_str("# [default] applies to outputs without their own table\n");
+    out.push_str("version = 2\n");
+    if let Some(entry) = default {
+        out.push_str("\n[default]\n");
+        push_entry(&mut out, entry);
+    }
+    for (monitor, entry) in map {
+        out.push_str(&format!("\n[monitors.{}]\n", toml_key(monitor)));
+        push_entry(&mut out, entry);
+    }
+    out
+}
+
+fn push_entry(out: &mut String, entry: &VideoMapEntry) {
+    out.push_str(&format!("video = {}\n", toml_string(&entry.map_path())));
+    if entry.options.rotate != Rotation::Deg0 {
+        out.push_str(&format!("rotate = {}\n", entry.options.rotate.degrees()));
+    }
+    if entry.options.flip != Flip::None {
+        out.push_str(&format!(
+            "flip = {}\n",
+            toml_string(entry.options.flip.as_str())
+        ));
+    }
+    if let Some(quality) = entry.options.quality {
+        out.push_str(&format!("quality = {}\n", toml_string(quality.as_str())));
+    }
+    if entry.options.effect != Effect::None {
+        out.push_str(&format!(
+            "effect = {}\n",
+            toml_string(entry.options.effect.as_str())
+        ));
+    }
+    if entry.options.ambient != AmbientParams::default() {
+        for (key, value) in entry.options.ambient.pairs() {
+            out.push_str(&format!("{key} = {value}\n"));
+        }
+    }
+    if entry.options.overlay != Overlay::None {
+        out.push_str(&format!(
+            "overlay = {}\n",
+            toml_string(entry.options.overlay.as_str())
+        ));
+    }
+    if entry.options.overlay_params != OverlayParams::default() {
+        for (key, value) in entry.options.overlay_params.pairs() {
+            out.push_str(&format!("{key} = {value}\n"));
+        }
+    }
+    if let Some(trim) = entry.options.trim {
+        out.push_str(&format!("trim = {}\n", toml_string(&trim.to_string())));
+    }
+    if let Some(crop) = entry.options.crop {
+        out.push_str(&format!("crop = {}\n", toml_string(&crop.to_string())));
+    }
+    if entry.options.filter != SampleFilter::Linear {
+        out.push_str(&format!(
+            "filter = {}\n",
+            toml_string(entry.options.filter.as_str())
+        ));
+    }
+    if entry.options.fit != Fit::Cover {
+        out.push_str(&format!(
+            "fit = {}\n",
+            toml_string(entry.options.fit.as_str())
+        ));
+    }
+    if entry.options.gamut != Gamut::Auto {
+        out.push_str(&format!(
+            "gamut = {}\n",
+            toml_string(&entry.options.gamut.to_string())
+        ));
+    }
+    if let Some(clock) = entry.options.clock {
+        out.push_str(&format!("clock = {}\n", toml_string(clock_str(clock))));
+    }
+    if let Some(timebase) = entry.options.timebase {
+        out.push_str(&format!("timebase = {}\n", toml_string(timebase.as_str())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> (VideoMap, Option<VideoMapEntry>, Vec<MapLineIssue>) {
+        parse_map_toml(contents, Path::new("/maps"))
+    }
+
+    fn issue_lines(issues: &[MapLineIssue]) -> Vec<usize> {
+        issues.iter().map(|issue| issue.line).collect()
+    }
+
+    #[test]
+    fn default_and_monitor_tables() {
+        let (map, default, issues) = parse(
+            "version = 2\n[default]\nvideo = \"/v/d.mp4\" # trailing comment\n\n[monitors.DP-1]\nvideo = \"a.mp4\"\nrotate = 180\n",
+        );
+        assert!(issues.is_empty(), "{issues:?}");
+        assert_eq!(default.unwrap().video, "/v/d.mp4");
+        assert_eq!(map["DP-1"].video, "/maps/a.mp4");
+        assert_eq!(map["DP-1"].options.rotate.degrees(), 180);
+    }
+
+    #[test]
+    fn toml_beyond_the_subset_is_reported_not_misread() {
+        let (map, default, issues) = parse(concat!(
+            "[[monitors]]\n",                    // 1
+            "video = \"/v/a.mp4\"\n",            // 2 (inside the skipped table)
+            "[monitors.DP-1]\n",                 // 3
+            "video = { path = \"/v/a.mp4\" }\n", // 4
+            "[monitors.DP-2]\n",                 // 5
+            "video = \"\"\"/v/b.mp4\"\"\"\n",    // 6
+            "[monitors.DP-3]\n",                 // 7
+            "video = \"/v/c.mp4\"\n",            // 8
+        ));
+        assert_eq!(issue_lines(&issues), vec![1, 4, 6], "{issues:?}");
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec!["DP-3"]);
+        assert!(default.is_none());
+    }
+
+    #[test]
+    fn bad_tables_are_skipped_whole() {
+        let (map, _, issues) = parse(concat!(
+            "version = 3\n",              // 1
+            "video = \"/v/top.mp4\"\n",   // 2
+            "[profiles.work]\n",          // 3
+            "video = \"/v/a.mp4\"\n",     // 4
+            "[monitors.Dell U2720Q]\n",   // 5
+            "[monitors.DP-1]\n",          // 6
+            "video = \"/v/a.mp4\"\n",     // 7
+            "sped = 2\n",                 // 8
+            "[monitors.DP-2]\n",          // 9
+            "rotate = 90\n",              // 10
+            "[monitors.DP-3]\n",          // 11
+            "video = /v/unquoted.mp4\n",  // 12
+            "[monitors.DP-4]\n",          // 13
+            "video = \"/v/d.mp4\"\n",     // 14
+            "video = \"/v/e.mp4\"\n",     // 15
+            "[monitors.DP-5]\n",          // 16
+            "video = \"/v/ok.mp4\"\n",    // 17
+            "[monitors.DP-5]\n",          // 18
+            "video = \"/v/again.mp4\"\n", // 19
+        ));
+        assert_eq!(
+            issue_lines(&issues),
+            vec![1, 2, 3, 5, 8, 9, 12, 15, 18],
+            "{issues:?}"
+        );
+        assert!(issues[3].message.contains("must be quoted"));
+        assert!(issues[5].message.contains("no video"));
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec!["DP-5"]);
+        assert_eq!(map["DP-5"].video, "/v/ok.mp4");
+    }
+
+    #[test]
+    fn monitor_names_are_quoted_only_when_needed() {
+        assert_eq!(toml_key("DP-1"), "DP-1");
+        assert_eq!(toml_key("Dell Inc. U2720Q"), "\"Dell Inc. U2720Q\"");
+        assert_eq!(toml_key("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        let mut map = VideoMap::new();
+        for name in ["DP-1", "Dell Inc. U2720Q", "a\"b\\c"] {
+            map.insert(
+                name.to_string(),
+                parse("[default]\nvideo = \"/v/a.mp4\"\n").1.unwrap(),
+            );
+        }
+        let (reread, _, issues) = parse(&format_map_toml(&map, None));
+        assert!(issues.is_empty(), "{issues:?}");
+        assert_eq!(reread, map);
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::user_path::{display_path, display_text};
+use crate::video_map::{MapDocument, MapLineIssue, VideoMap, parse_map_document};
+
+/// Watches a map file and prints a report on every change: entry diff against
+/// the previous version, skipped lines, missing videos, and keys that match no
+/// connected output. Returns `Err` on the first report with errors when `strict`.
+pub fn watch_map(
+    path: &Path,
+    strict: bool,
+    once: bool,
+    connected_outputs: fn() -> Option<Vec<String>>,
+) -> Result<(), String> {
+    if !once {
+        println!("[watch-map] watching {} (Ctrl+C to stop)", path.display());
+    }
+    let mut previous = None::<MapDocument>;
+    let mut last_seen = None::<Option<(SystemTime, u64)>>;
+    loop {
+        let seen = fs::metadata(path)
+            .ok()
+            .and_then(|meta| Some((meta.modified().ok()?, meta.len())));
+        if last_seen != Some(seen) {
+            last_seen = Some(seen);
+            let errors = match fs::read_to_string(path) {
+                Ok(contents) => {
+                    let (doc, issues) = parse_map_document(path, &contents);
+                    let errors =
+                        print_report(path, previous.as_ref(), &doc, &issues, connected_outputs());
+                    previous = Some(doc);
+                    errors
+                }
+                Err(err) => {
+                    println!("[watch-map] {}: cannot read: {err}", path.display());
+                    1
+                }
+            };
+            if strict && errors > 0 {
+                return Err(format!("{} has {errors} error(s)", path.display()));
+            }
+            if once {
+                return Ok(());
+            }
+        }
+        thread::sleep(Duration::from_millis(300));
+    }
+}
+
+/// Prints one report and returns its error count. Unknown monitors are only
+/// warnings: the output may simply be unplugged right now.
+fn print_report(
+    path: &Path,
+    previous: Option<&MapDocument>,
+    doc: &MapDocument,
+    issues: &[MapLineIssue],
+    connected: Option<Vec<String>>,
+) -> usize {
+    let map = &doc.entries;
+    println!(
+        "[watch-map] {} ({}): {} entr{}",
+        path.display(),
+        doc.format.label(),
+        map.len(),
+        if map.len() == 1 { "y" } else { "ies" }
+    );
+    let empty = VideoMap::new();
+    let before = previous.map_or(&empty, |prev| &prev.entries);
+    let mut changed = false;
+    for (monitor, entry) in map {
+        let name = display_text(monitor);
+        match before.get(monitor) {
+            None => println!("  + {name}={}", entry.display_label()),
+            Some(old) if old != entry => println!(
+                "  ~ {name}: {} -> {}",
+                old.display_label(),
+                entry.display_label()
+            ),
+            Some(_) => continue,
+        }
+        changed = true;
+    }
+    for (monitor, entry) in before {
+        if !map.contains_key(monitor) {
+            println!("  - {}={}", display_text(monitor), entry.display_label());
+            changed = true;
+        }
+    }
+    let old_default = previous.and_then(|prev| prev.default.as_ref());
+    match (old_default, &doc.default) {
+        (None, Some(new)) => println!("  + [default] {}", new.display_label()),
+        (Some(old), None) => println!("  - [default] {}", old.display_label()),
+        (Some(old), Some(new)) if old != new => println!(
+            "  ~ [default]: {} -> {}",
+            old.display_label(),
+            new.display_label()
+        ),
+        _ => {}
+    }
+    changed |= old_default != doc.default.as_ref();
+    if !changed && previous.is_some() {
+        println!("  (no entry changes)");
+    }
+
+    let mut errors = 0usize;
+    let mut warnings = 0usize;
+    for issue in issues {
+        println!(
+            "  error: line {}: {}",
+            issue.line,
+            display_text(&issue.message)
+        );
+        errors += 1;
+    }
+    let default = doc.default.iter().map(|entry| ("[default]", entry));
+    for (monitor, entry) in map.iter().map(|(k, v)| (k.as_str(), v)).chain(default) {
+        for video in entry.videos() {
+            if !Path::new(video).exists() {
+                println!(
+                    "  error: {}: video not found: {}",
+                    display_text(monitor),
+                    display_path(video)
+                );
+                errors += 1;
+            }
+        }
+    }
+    match &connected {
+        Some(names) => {
+            for monitor in map.keys().filter(|monitor| !names.contains(monitor)) {
+                println!(
+                    "  warning: {} matches no connected output (connected: {})",
+                    display_text(monitor),
+                    names.join(", ")
+                );
+                warnings += 1;
+            }
+        }
+        None => println!("  note: connected outputs unknown, monitor names not checked"),
+    }
+    if errors == 0 && warnings == 0 {
+        println!("  ok");
+    } else {
+        println!("  {errors} error(s), {warnings} warning(s)");
+    }
+    errors
+}
@@ -1,9 +1,16 @@
+use std::time::{Duration, Instant};
+
+use crate::color::ColorPipeline;
+use crate::config_file::ConfigFile;
+use crate::hw_decode::HwDecode;
+
 #[derive(Debug, Clone)]
 pub struct MonitorInfo {
     pub name: String,
     pub width: u32,
     pub height: u32,
     pub refresh_hz: u32,
+    pub transform: OutputTransform,
 }
 
 #[derive(Debug, Clone)]
@@ -12,7 +19,380 @@ pub struct MonitorSurfaceSpec {
     pub layer: LayerRole,
 }
 
+/// Outputs the wallpaper may bind to, from `KRC_OUTPUTS="DP-1,DP-3"` or
+/// `[outputs] allow = [...]` in the config file (env wins). An entry matches the
+/// output name exactly or appears in the output description (case-insensitive).
+/// An empty list allows every output.
+#[derive(Debug, Clone, Default)]
+pub struct OutputAllowlist {
+    entries: Vec<String>,
+}
+
+impl OutputAllowlist {
+    pub fn from_env() -> Self {
+        let entries = match std::env::var("KRC_OUTPUTS") {
+            Ok(raw) if !raw.trim().is_empty() => raw
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+            _ => ConfigFile::load_from_env()
+                .get_list("outputs", "allow")
+                .unwrap_or_default(),
+        };
+        Self { entries }
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// `aliases` are the monitor aliases naming this output; an entry can be
+    /// one of them.
+    pub fn allows(
+        &self,
+        name: Option<&str>,
+        description: Option<&str>,
+        aliases: &[String],
+    ) -> bool {
+        if self.entries.is_empty() {
+            return true;
+        }
+        let description = description.map(str::to_lowercase);
+        self.entries.iter().any(|entry| {
+            name == Some(entry.as_str())
+                || aliases.contains(entry)
+                || description
+                    .as_deref()
+                    .is_some_and(|d| d.contains(&entry.to_lowercase()))
+        })
+    }
+}
+
+/// Per-output runtime facts for the live `status` (control socket).
+#[derive(Debug, Clone)]
+pub struct OutputRuntimeStatus {
+    pub name: String,
+    /// What the compositor calls the monitor (make, model, serial), which
+    /// `desc:` aliases match.
+    pub description: Option<String>,
+    /// `video`, `still` (a `still:` entry), `procedural`, `layout` (a
+    /// `layout=` entry, see `cells`), or `excluded` (not in `KRC_OUTPUTS`).
+    pub stream_kind: &'static str,
+    pub source: Option<String>,
+    pub first_presented_at: Option<Instant>,
+    pub presented_frames: u64,
+    pub frame_callback_pending: bool,
+    pub last_error: Option<(Instant, String)>,
+    /// Times `last_error` recurred right after itself; repeats are not all
+    /// logged.
+    pub last_error_repeats: u64,
+    /// Why the output is not playing its entry as configured:
+    /// `ffmpeg-not-found` (retried every 30s), `video-missing`, or `failing`
+    /// (errors since the last good frame); `None` when it is.
+    pub degraded: Option<&'static str>,
+    /// Decode size after the preset, orientation, GPU clamp and auto quality.
+    pub source_size: Option<(u32, u32)>,
+    /// The entry's own `quality=` preset; `None` follows `KRC_QUALITY`.
+    pub quality_preset: Option<&'static str>,
+    /// `KRC_AUTO_QUALITY` level (0 = as configured) and what it means.
+    pub quality_level: u8,
+    pub quality: String,
+    /// Nice value the decoder runs at, read back from `/proc`.
+    pub decode_nice: Option<i32>,
+    /// The output's own decoder process; `None` for stills, the procedural
+    /// pattern and outputs sharing another one's decoder.
+    pub decoder_pid: Option<u32>,
+    /// Whether the decoder's hwaccel actually engaged; `None` without one.
+    pub hw_decode: Option<HwDecode>,
+    /// Active `effect=` with its parameters, e.g. `ambient amplitude=0.05 period=300`.
+    pub effect: Option<String>,
+    /// Active `overlay=` with the parameters that are not the default, e.g.
+    /// `snow density=0.8`.
+    pub overlay: Option<String>,
+    /// The entry's `trim=` window (`IN:OUT` seconds) the decoder plays.
+    pub trim: Option<String>,
+    /// The entry's `crop=` region as `X,Y,W,H` pixels of the video, once applied.
+    pub crop: Option<String>,
+    /// The entry's `filter=` and `fit=` (`linear`/`cover` without one).
+    pub filter: &'static str,
+    pub fit: &'static str,
+    /// Output whose decoder feeds this one (`KRC_SHARE_DECODERS`).
+    pub decoder_shared_with: Option<String>,
+    /// Compositor-preferred buffer scale and transform the buffers follow.
+    pub buffer_scale: i32,
+    pub buffer_transform: &'static str,
+    /// The output is larger than the GPU's texture limit, so its buffer has
+    /// fewer pixels than the output.
+    pub clamped: bool,
+    /// The `gamut=` conversion the output's pixels go through; `None` where
+    /// the backend has no shader (or before the first frame).
+    pub color: Option<ColorPipeline>,
+    /// The input region of the output's surface: `empty`, so it never takes
+    /// clicks or focus; `None` without a real surface (stub backend,
+    /// excluded outputs).
+    pub input_region: Option<&'static str>,
+    /// Frame callback latency; `None` where the backend has no callbacks.
+    pub callback_latency: Option<CallbackStats>,
+    /// How the decoder's frames arrive (the leader's for a follower); `None`
+    /// without a running decoder.
+    pub frame_arrival: Option<ArrivalStats>,
+    /// How much of the buffer its presents damaged; `None` without damage
+    /// tracking (`KRC_DAMAGE_TRACKING=0`, or a backend without it).
+    pub damage: Option<DamageStats>,
+    /// `KRC_FLASH_GUARD` state: `clear`, `smoothing` or
+    /// `flash-guard-triggered`; `None` when the guard is off.
+    pub flash_guard: Option<&'static str>,
+    /// The last mode the compositor reported, when it was implausible and no
+    /// sane one has followed; the output keeps its previous size.
+    pub suspect_mode: Option<(i32, i32)>,
+    /// The `layout=` grid (`grid2x2`) when the output tiles several videos.
+    pub layout: Option<String>,
+    /// Every cell of `layout`, in grid order; empty without one.
+    pub cells: Vec<CellStatus>,
+}
+
+/// One cell of a `layout=` output.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct CellStatus {
+    /// 1-based, row by row.
+    pub cell: usize,
+    /// `video`, `still`, `procedural` (the video is missing or failing), or
+    /// `empty` for a cell left black.
+    pub stream_kind: &'static str,
+    pub source: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// How long the compositor takes to answer an output's frame callbacks, over
+/// a rolling window of recent ones.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct CallbackStats {
+    pub samples: usize,
+    pub p50: Option<Duration>,
+    pub p99: Option<Duration>,
+    /// A sustained share of callbacks took over two refresh intervals.
+    pub slow: bool,
+    /// A requested callback is long overdue: the compositor stopped sending
+    /// them (output hidden or off).
+    pub stalled: bool,
+}
+
+/// How a decoder's frames reach the renderer through its pipe, over a
+/// rolling window of recent reads.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct ArrivalStats {
+    pub samples: usize,
+    /// Time the render thread waited for a whole frame.
+    pub p95: Option<Duration>,
+    /// Bytes in the pipe when the last read started, and the pipe's size.
+    pub queued_bytes: usize,
+    pub capacity_bytes: usize,
+    /// Average share of the pipe filled when a read started, 0-1.
+    pub avg_fill: f32,
+    /// Reads that found the pipe empty: the decoder was a little behind, or
+    /// the frame took so long it was waiting on the file.
+    pub starved_decode: u64,
+    pub starved_io: u64,
+}
+
+/// An output's presents since it started, and how much of its buffer they
+/// changed.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct DamageStats {
+    pub presents: u64,
+    /// Frames due with nothing changed, so nothing was presented.
+    pub skipped: u64,
+    /// Average damaged share of the buffer per present, 0-100.
+    pub avg_percent: f64,
+}
+
+/// The adapter a GPU backend draws with, for the live `status`.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct GpuAdapterStatus {
+    pub name: String,
+    /// wgpu backend, e.g. `vulkan` or `gl`.
+    pub backend: String,
+    /// `discrete`, `integrated`, `cpu`...
+    pub device_type: String,
+    /// Frame rate every output and decoder is held to on a software adapter.
+    pub fps_cap: Option<u32>,
+}
+
+/// The shell the wallpaper surfaces were created with, for the live `status`.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub struct ShellStatus {
+    /// `layer-shell`, or `xdg-shell` for the windowed fallback.
+    pub protocol: &'static str,
+    /// `KRC_ON_NO_LAYER_SHELL`: `error`, `wait` or `windowed`.
+    pub on_missing: &'static str,
+    pub reason: String,
+}
+
+/// Refresh changes smaller than this, or than 5% of the current rate, are
+/// VRR jitter rather than a mode switch.
+const REFRESH_JITTER_HZ: u32 = 3;
+/// Reported rates outside this range are taken as bogus.
+const PLAUSIBLE_REFRESH_HZ: std::ops::RangeInclusive<u32> = 20..=1000;
+
+/// Mode sizes outside this range per axis are taken as bogus (a dock
+/// briefly reporting 0x0).
+const PLAUSIBLE_MODE_AXIS: std::ops::RangeInclusive<i32> = 64..=16384;
+/// Twice an 8K panel; a broken EDID claiming 16384x16384 is far past it.
+const MAX_MODE_PIXELS: u64 = 2 * 7680 * 4320;
+
+/// Whether a `wl_output` mode size is one a real panel could have. Modes
+/// past the GPU's texture limit still pass: their buffers are clamped.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub fn plausible_mode(width: i32, height: i32) -> bool {
+    PLAUSIBLE_MODE_AXIS.contains(&width)
+        && PLAUSIBLE_MODE_AXIS.contains(&height)
+        && width as u64 * height as u64 <= MAX_MODE_PIXELS
+}
+
+/// What a `wl_output` mode refresh means for the rate in use.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshSample {
+    /// A real change; use this rate (Hz).
+    Changed(u32),
+    /// Within jitter of the current rate; keep it.
+    Jitter,
+    /// Zero (unknown) or out of range; keep the current rate.
+    Implausible,
+}
+
+/// Filters a reported refresh (mHz, as `wl_output` sends it) against
+/// `current` (Hz). Compositors re-send the current mode with slightly
+/// different rates on VRR panels, and some send 0 for "unknown".
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub fn filter_refresh(current: Option<u32>, refresh_mhz: i32) -> RefreshSample {
+    let hz = (refresh_mhz.max(0) as f32 / 1000.0).round() as u32;
+    if !PLAUSIBLE_REFRESH_HZ.contains(&hz) {
+        return RefreshSample::Implausible;
+    }
+    match current {
+        Some(current) if current.abs_diff(hz) < REFRESH_JITTER_HZ.max(current / 20) => {
+            RefreshSample::Jitter
+        }
+        _ => RefreshSample::Changed(hz),
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum LayerRole {
     Background,
 }
+
+/// Output transform as reported by `wl_output::Event::Geometry`. Width/height in
+/// `MonitorInfo` are already in the post-transform (logical) orientation.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputTransform {
+    #[default]
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Flipped,
+    Flipped90,
+    Flipped180,
+    Flipped270,
+}
+
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+impl OutputTransform {
+    pub fn swaps_axes(self) -> bool {
+        matches!(
+            self,
+            Self::Rotate90 | Self::Rotate270 | Self::Flipped90 | Self::Flipped270
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Rotate90 => "90",
+            Self::Rotate180 => "180",
+            Self::Rotate270 => "270",
+            Self::Flipped => "flipped",
+            Self::Flipped90 => "flipped-90",
+            Self::Flipped180 => "flipped-180",
+            Self::Flipped270 => "flipped-270",
+        }
+    }
+
+    /// The `wl_output.transform` wire value (0-7).
+    pub fn wire_value(self) -> u32 {
+        match self {
+            Self::Normal => 0,
+            Self::Rotate90 => 1,
+            Self::Rotate180 => 2,
+            Self::Rotate270 => 3,
+            Self::Flipped => 4,
+            Self::Flipped90 => 5,
+            Self::Flipped180 => 6,
+            Self::Flipped270 => 7,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plausible_modes_table() {
+        let cases = [
+            // The DisplayLink dock's transient mode, and its max(1) outcome.
+            ((0, 0), false),
+            ((1, 1), false),
+            ((63, 1080), false),
+            ((1920, 63), false),
+            ((-1920, 1080), false),
+            // The broken EDID.
+            ((16384, 16384), false),
+            ((16385, 1080), false),
+            ((16384, 4320), false),
+            // Real panels, landscape and portrait.
+            ((64, 64), true),
+            ((1366, 768), true),
+            ((1920, 1080), true),
+            ((1080, 1920), true),
+            ((5120, 1440), true),
+            ((7680, 2160), true),
+            ((7680, 4320), true),
+            ((4320, 7680), true),
+            // Exactly twice an 8K panel still passes.
+            ((15360, 4320), true),
+            ((3840, 16384), true),
+        ];
+        for ((width, height), expected) in cases {
+            assert_eq!(plausible_mode(width, height), expected, "{width}x{height}");
+        }
+    }
+
+    #[test]
+    fn refresh_samples() {
+        assert_eq!(filter_refresh(None, 59_940), RefreshSample::Changed(60));
+        assert_eq!(filter_refresh(Some(60), 59_940), RefreshSample::Jitter);
+        assert_eq!(filter_refresh(Some(144), 141_000), RefreshSample::Jitter);
+        assert_eq!(
+            filter_refresh(Some(60), 144_000),
+            RefreshSample::Changed(144)
+        );
+        assert_eq!(filter_refresh(Some(60), 0), RefreshSample::Implausible);
+        assert_eq!(filter_refresh(None, -1), RefreshSample::Implausible);
+        assert_eq!(filter_refresh(None, 2_000_000), RefreshSample::Implausible);
+    }
+}
@@ -0,0 +1,292 @@
+//! `--except` / `--only` of the bulk commands (`set-video --all`,
+//! `unset-video --all`, `quality --all`, `pause --all`). Both take a
+//! comma-separated list of patterns; `*` matches any run of characters and
+//! `?` one, so `DP-*` covers every DisplayPort output. A pattern is tried
+//! against each form a monitor is known by: its connector name, its aliases,
+//! and `desc:<description>` when the renderer reported one. A `desc:` pattern,
+//! like an alias target, is found anywhere in the description, ignoring case.
+
+/// Which monitors a bulk command applies to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MonitorFilter {
+    /// Every monitor.
+    #[default]
+    All,
+    /// Every monitor no pattern matches (`--except`).
+    Except(Vec<String>),
+    /// Only the monitors a pattern matches (`--only`).
+    Only(Vec<String>),
+}
+
+impl MonitorFilter {
+    /// From the raw `--except` and `--only` values; giving both is an error.
+    pub fn from_flags(except: Option<&str>, only: Option<&str>) -> Result<Self, String> {
+        match (except.map(parse_list), only.map(parse_list)) {
+            (Some(_), Some(_)) => Err("--except and --only are mutually exclusive".to_string()),
+            (Some(patterns), None) if !patterns.is_empty() => Ok(Self::Except(patterns)),
+            (None, Some(patterns)) if !patterns.is_empty() => Ok(Self::Only(patterns)),
+            (Some(_), None) => Err("--except needs at least one monitor pattern".to_string()),
+            (None, Some(_)) => Err("--only needs at least one monitor pattern".to_string()),
+            (None, None) => Ok(Self::All),
+        }
+    }
+
+    pub fn is_all(&self) -> bool {
+        matches!(self, Self::All)
+    }
+
+    /// The flag it came from, for messages.
+    pub fn flag(&self) -> &'static str {
+        match self {
+            Self::All => "--all",
+            Self::Except(_) => "--except",
+            Self::Only(_) => "--only",
+        }
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        match self {
+            Self::All => &[],
+            Self::Except(patterns) | Self::Only(patterns) => patterns,
+        }
+    }
+
+    /// Whether the monitor known by `forms` (see [`monitor_forms`]) is
+    /// selected.
+    pub fn selects(&self, forms: &[String]) -> bool {
+        let hit = || {
+            self.patterns()
+                .iter()
+                .any(|pattern| forms.iter().any(|form| pattern_matches(pattern, form)))
+        };
+        match self {
+            Self::All => true,
+            Self::Except(_) => !hit(),
+            Self::Only(_) => hit(),
+        }
+    }
+
+    /// Patterns that match none of `monitors` (each given by its forms):
+    /// usually a typo that makes the command touch, or skip, the wrong
+    /// outputs.
+    pub fn unmatched<'a>(&'a self, monitors: &[Vec<String>]) -> Vec<&'a str> {
+        self.patterns()
+            .iter()
+            .filter(|pattern| {
+                !monitors
+                    .iter()
+                    .flatten()
+                    .any(|form| pattern_matches(pattern, form))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// A monitor's name, then its aliases, then `desc:<description>`.
+pub fn monitor_forms(name: &str, aliases: &[String], description: Option<&str>) -> Vec<String> {
+    let mut forms = vec![name.to_string()];
+    forms.extend(aliases.iter().cloned());
+    if let Some(description) = description.filter(|d| !d.is_empty()) {
+        forms.push(format!("desc:{description}"));
+    }
+    forms
+}
+
+/// Whether `pattern` matches one form of a monitor.
+pub fn pattern_matches(pattern: &str, form: &str) -> bool {
+    match (pattern.strip_prefix("desc:"), form.strip_prefix("desc:")) {
+        (Some(want), Some(description)) => glob(
+            &format!("*{}*", want.to_lowercase()),
+            &description.to_lowercase(),
+        ),
+        (Some(_), None) => false,
+        _ => glob(pattern, form),
+    }
+}
+
+/// `*` and `?` wildcards, everything else literal.
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // The last `*` seen and the text position it currently stands for.
+    let mut star = None::<(usize, usize)>;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn parse_list(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A video wall: five DisplayPort panels, an HDMI TV and the laptop.
+    fn wall() -> Vec<Vec<String>> {
+        let aliases = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        vec![
+            monitor_forms("DP-1", &aliases(&["left"]), Some("Dell Inc. U2720Q 1A2B")),
+            monitor_forms("DP-2", &[], Some("Dell Inc. U2720Q 3C4D")),
+            monitor_forms(
+                "DP-3",
+                &aliases(&["center", "main"]),
+                Some("LG Electronics 27GP850"),
+            ),
+            monitor_forms("DP-4", &[], None),
+            monitor_forms("DP-5", &aliases(&["right"]), Some("")),
+            monitor_forms(
+                "HDMI-A-1",
+                &aliases(&["tv"]),
+                Some("Samsung Electric Company QN90A"),
+            ),
+            monitor_forms("eDP-1", &aliases(&["laptop"]), Some("BOE 0x095F")),
+        ]
+    }
+
+    fn selected(filter: &MonitorFilter) -> Vec<String> {
+        wall()
+            .into_iter()
+            .filter(|forms| filter.selects(forms))
+            .map(|forms| forms[0].clone())
+            .collect()
+    }
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn forms_are_name_aliases_then_description() {
+        assert_eq!(
+            wall()[2],
+            names(&["DP-3", "center", "main", "desc:LG Electronics 27GP850"])
+        );
+        // An empty or missing description adds no form.
+        assert_eq!(wall()[3], names(&["DP-4"]));
+        assert_eq!(wall()[4], names(&["DP-5", "right"]));
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        assert!(glob("DP-*", "DP-12"));
+        assert!(glob("*", ""));
+        assert!(glob("?DP-?", "eDP-1"));
+        assert!(glob("*-A-*", "HDMI-A-1"));
+        assert!(glob("D*P*1", "DP-DP-1"));
+        assert!(!glob("DP-*", "eDP-1"));
+        assert!(!glob("DP-?", "DP-12"));
+        assert!(!glob("DP-1", "DP-10"));
+        assert!(!glob("", "DP-1"));
+        assert!(glob("dp-1", "dp-1") && !glob("dp-1", "DP-1"));
+    }
+
+    #[test]
+    fn desc_patterns_search_descriptions_ignoring_case() {
+        assert!(pattern_matches("desc:u2720q", "desc:Dell Inc. U2720Q 1A2B"));
+        assert!(pattern_matches(
+            "desc:Dell*3C4D",
+            "desc:Dell Inc. U2720Q 3C4D"
+        ));
+        assert!(!pattern_matches("desc:Dell", "DP-1"));
+        // A plain pattern is not looked up in the description.
+        assert!(!pattern_matches("Dell*", "desc:Dell Inc. U2720Q 1A2B"));
+    }
+
+    #[test]
+    fn except_and_only_are_inverses() {
+        let except = MonitorFilter::from_flags(Some("DP-*, tv"), None).unwrap();
+        let only = MonitorFilter::from_flags(None, Some("DP-*,tv")).unwrap();
+        assert_eq!(selected(&except), names(&["eDP-1"]));
+        assert_eq!(
+            selected(&only),
+            names(&["DP-1", "DP-2", "DP-3", "DP-4", "DP-5", "HDMI-A-1"])
+        );
+        assert_eq!(selected(&MonitorFilter::All).len(), wall().len());
+    }
+
+    #[test]
+    fn all_displayport_but_one() {
+        let all = wall();
+        let filter = MonitorFilter::from_flags(None, Some("DP-*")).unwrap();
+        let kept = all
+            .iter()
+            .filter(|forms| filter.selects(forms))
+            .filter(|forms| MonitorFilter::Except(names(&["DP-5"])).selects(forms))
+            .map(|forms| forms[0].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(kept, ["DP-1", "DP-2", "DP-3", "DP-4"]);
+    }
+
+    #[test]
+    fn aliases_and_descriptions_select_too() {
+        let filter = MonitorFilter::from_flags(None, Some("main,laptop,desc:samsung")).unwrap();
+        assert_eq!(selected(&filter), names(&["DP-3", "HDMI-A-1", "eDP-1"]));
+        let filter = MonitorFilter::from_flags(Some("desc:dell*,right"), None).unwrap();
+        assert_eq!(
+            selected(&filter),
+            names(&["DP-3", "DP-4", "HDMI-A-1", "eDP-1"])
+        );
+    }
+
+    #[test]
+    fn patterns_matching_nothing_are_reported() {
+        let filter =
+            MonitorFilter::from_flags(Some("DP-*,HDMI-B-*,desc:asus,center,dp-1"), None).unwrap();
+        assert_eq!(filter.unmatched(&wall()), ["HDMI-B-*", "desc:asus", "dp-1"]);
+        assert!(MonitorFilter::All.unmatched(&wall()).is_empty());
+        assert_eq!(
+            MonitorFilter::Only(names(&["DP-1"])).unmatched(&[]),
+            ["DP-1"]
+        );
+    }
+
+    #[test]
+    fn flags_are_validated() {
+        assert_eq!(
+            MonitorFilter::from_flags(None, None),
+            Ok(MonitorFilter::All)
+        );
+        assert_eq!(
+            MonitorFilter::from_flags(Some("DP-1"), Some("DP-2")).unwrap_err(),
+            "--except and --only are mutually exclusive"
+        );
+        assert!(
+            MonitorFilter::from_flags(Some(" , "), None)
+                .unwrap_err()
+                .contains("--except")
+        );
+        assert!(
+            MonitorFilter::from_flags(None, Some(""))
+                .unwrap_err()
+                .contains("--only")
+        );
+        let filter = MonitorFilter::from_flags(None, Some(" DP-1 ,,HDMI-A-1 ")).unwrap();
+        assert_eq!(filter.patterns(), names(&["DP-1", "HDMI-A-1"]));
+        assert_eq!((filter.flag(), filter.is_all()), ("--only", false));
+    }
+}
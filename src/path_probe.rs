@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError, sync_channel};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// How long the render thread waits for a fresh `stat` before moving on. A
+/// local file answers in microseconds; a network share that went to sleep can
+/// take tens of seconds.
+const PROBE_BUDGET: Duration = Duration::from_millis(5);
+
+/// What [`metadata`] found within its budget.
+pub enum Probe {
+    /// The `stat` finished: the file's metadata, `None` when it failed.
+    Done(Option<Metadata>),
+    /// Still waiting on the filesystem; asked again, the same `stat` is
+    /// checked instead of starting another.
+    Slow,
+}
+
+static IN_FLIGHT: OnceLock<Mutex<HashMap<String, Receiver<Option<Metadata>>>>> = OnceLock::new();
+
+/// `std::fs::metadata(path)` on a helper thread. Waits up to a few
+/// milliseconds for a new `stat`, and not at all for one still pending from an
+/// earlier call, so a path on a hung mount costs one parked thread rather than
+/// a frozen frame loop.
+pub fn metadata(path: &str) -> Probe {
+    let in_flight = IN_FLIGHT.get_or_init(|| Mutex::new(HashMap::new()));
+    let pending = in_flight.lock().ok().and_then(|mut map| map.remove(path));
+    let receiver = match pending {
+        Some(receiver) => match receiver.try_recv() {
+            Ok(meta) => return Probe::Done(meta),
+            Err(TryRecvError::Empty) => receiver,
+            Err(TryRecvError::Disconnected) => return Probe::Done(None),
+        },
+        None => {
+            let (sender, receiver) = sync_channel(1);
+            let owned = path.to_string();
+            let spawned = std::thread::Builder::new()
+                .name("krc-path-probe".to_string())
+                .spawn(move || {
+                    simulate_slow_fs();
+                    let _ = sender.send(std::fs::metadata(owned).ok());
+                });
+            if spawned.is_err() {
+                return Probe::Slow;
+            }
+            match receiver.recv_timeout(PROBE_BUDGET) {
+                Ok(meta) => return Probe::Done(meta),
+                Err(RecvTimeoutError::Timeout) => receiver,
+                Err(RecvTimeoutError::Disconnected) => return Probe::Done(None),
+            }
+        }
+    };
+    if let Ok(mut map) = in_flight.lock() {
+        map.insert(path.to_string(), receiver);
+    }
+    Probe::Slow
+}
+
+/// `KRC_SIM_SLOW_FS_MS`: every video path probe and decoder open sleeps this
+/// long first, as on a network share waking up. Lets the non-blocking paths be
+/// exercised without a NAS; the frame loop must keep its pace meanwhile.
+pub fn simulate_slow_fs() {
+    static DELAY: OnceLock<Duration> = OnceLock::new();
+    let delay = *DELAY.get_or_init(|| {
+        std::env::var("KRC_SIM_SLOW_FS_MS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_millis)
+            .unwrap_or_default()
+    });
+    if !delay.is_zero() {
+        std::thread::sleep(delay);
+    }
+}
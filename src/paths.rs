@@ -0,0 +1,456 @@
+use std::fs;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+
+use crate::config_file::config_file_path_from_env;
+use crate::control::control_socket_path_from_env;
+use crate::file_access::writable;
+use crate::instance::instance_id_from_env;
+use crate::json::escape_json;
+use crate::pause_file::pause_file_path_from_env;
+use crate::user_path::{display_path, home_dir};
+use crate::video_map::map_file_path_from_env;
+
+/// Name of the renderer's directory under each base directory.
+pub const APP_DIR: &str = "kitsune-rendercore";
+
+/// The XDG base directories the renderer keeps files in. Every directory the
+/// renderer owns is resolved here; modules owning a file format (the map,
+/// `config.toml`) only pick the file name inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Base {
+    /// The user's settings: map, `config.toml`, env file, migration backups.
+    Config,
+    /// Data worth keeping across restarts that is not configuration: logs.
+    State,
+    /// Data that can be rebuilt; `clean --cache` empties it.
+    Cache,
+    /// Per-session files: lock, control socket, pause file.
+    Runtime,
+}
+
+impl Base {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Config => "config",
+            Self::State => "state",
+            Self::Cache => "cache",
+            Self::Runtime => "runtime",
+        }
+    }
+
+    fn var(self) -> &'static str {
+        match self {
+            Self::Config => "XDG_CONFIG_HOME",
+            Self::State => "XDG_STATE_HOME",
+            Self::Cache => "XDG_CACHE_HOME",
+            Self::Runtime => "XDG_RUNTIME_DIR",
+        }
+    }
+
+    /// Default under the home directory, as the XDG spec gives it; the
+    /// runtime directory has none.
+    fn home_default(self) -> Option<&'static str> {
+        match self {
+            Self::Config => Some(".config"),
+            Self::State => Some(".local/state"),
+            Self::Cache => Some(".cache"),
+            Self::Runtime => None,
+        }
+    }
+
+    /// The directory and what it was resolved from. The variable is used when
+    /// it is an absolute path (as the XDG spec asks), else the home directory
+    /// from `HOME` or the passwd entry. Never guesses `.`: a relative fallback
+    /// would make a service and a terminal disagree on the path.
+    fn resolve(self) -> Result<(PathBuf, &'static str), String> {
+        if let Some(dir) = std::env::var_os(self.var()).map(PathBuf::from)
+            && dir.is_absolute()
+        {
+            return Ok((dir.join(APP_DIR), self.var()));
+        }
+        let Some(default) = self.home_default() else {
+            // No XDG_RUNTIME_DIR outside a login session (cron, containers).
+            return Ok((std::env::temp_dir().join(APP_DIR), "temp dir"));
+        };
+        let hint = match self {
+            Self::Config => "; set HOME, XDG_CONFIG_HOME, or KRC_VIDEO_MAP_FILE/KRC_CONFIG_FILE",
+            _ => "",
+        };
+        home_dir()
+            .map(|home| (home.join(default).join(APP_DIR), "home"))
+            .ok_or_else(|| {
+                format!(
+                    "cannot resolve the {} directory: {} and HOME are unset and this uid has no passwd entry{hint}",
+                    self.name(),
+                    self.var()
+                )
+            })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/kitsune-rendercore`, else `~/.config/kitsune-rendercore`.
+pub fn config_dir() -> Result<PathBuf, String> {
+    Base::Config.resolve().map(|(dir, _)| dir)
+}
+
+/// `$XDG_STATE_HOME/kitsune-rendercore`, else `~/.local/state/kitsune-rendercore`.
+pub fn state_dir() -> Result<PathBuf, String> {
+    Base::State.resolve().map(|(dir, _)| dir)
+}
+
+/// `$XDG_CACHE_HOME/kitsune-rendercore`, else `~/.cache/kitsune-rendercore`.
+pub fn cache_dir() -> Result<PathBuf, String> {
+    Base::Cache.resolve().map(|(dir, _)| dir)
+}
+
+/// `$XDG_RUNTIME_DIR/kitsune-rendercore`, else under the temp dir.
+pub fn runtime_dir() -> PathBuf {
+    Base::Runtime
+        .resolve()
+        .map_or_else(|_| std::env::temp_dir().join(APP_DIR), |(dir, _)| dir)
+}
+
+/// The single-instance lock of `instance`.
+pub fn lock_file(instance: &str) -> PathBuf {
+    runtime_dir().join(format!("{instance}.lock"))
+}
+
+/// Output of a `--daemonize`d renderer.
+pub fn daemon_log(instance: &str) -> Result<PathBuf, String> {
+    Ok(state_dir()?.join(format!("{instance}.log")))
+}
+
+/// `control.sock`, or `control-<id>.sock` for a non-default `KRC_INSTANCE`.
+pub fn control_socket(instance: &str) -> PathBuf {
+    runtime_dir().join(match instance {
+        "default" => "control.sock".to_string(),
+        id => format!("control-{id}.sock"),
+    })
+}
+
+/// `reduce-motion`, or `reduce-motion-<id>` for a non-default `KRC_INSTANCE`.
+/// In the state dir, so the flag survives a reboot.
+pub fn reduce_motion_file(instance: &str) -> Result<PathBuf, String> {
+    Ok(state_dir()?.join(match instance {
+        "default" => "reduce-motion".to_string(),
+        id => format!("reduce-motion-{id}"),
+    }))
+}
+
+/// `runtime-state.json`, or `runtime-state-<id>.json` for a non-default
+/// `KRC_INSTANCE`: what a restart restores (see `snapshot`).
+pub fn snapshot_file(instance: &str) -> Result<PathBuf, String> {
+    Ok(state_dir()?.join(match instance {
+        "default" => "runtime-state.json".to_string(),
+        id => format!("runtime-state-{id}.json"),
+    }))
+}
+
+/// `supervisor.json`, or `supervisor-<id>.json` for a non-default
+/// `KRC_INSTANCE`: what `supervise` is doing, and whether it gave up. In the
+/// state dir, so the failure marker survives a reboot.
+pub fn supervisor_file(instance: &str) -> Result<PathBuf, String> {
+    Ok(state_dir()?.join(match instance {
+        "default" => "supervisor.json".to_string(),
+        id => format!("supervisor-{id}.json"),
+    }))
+}
+
+/// Keeps one `supervise` per instance; the renderer it runs takes
+/// [`lock_file`].
+pub fn supervisor_lock(instance: &str) -> PathBuf {
+    runtime_dir().join(format!("{instance}.supervise.lock"))
+}
+
+pub fn pause_file() -> PathBuf {
+    runtime_dir().join("paused")
+}
+
+/// Creates `dir` and any missing parents readable by this user only, as the
+/// XDG spec asks of base directories. Existing directories are left as they are.
+pub fn ensure_dir(dir: &Path) -> Result<(), String> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|e| format!("failed to create {}: {e}", dir.display()))
+}
+
+/// Makes a config directory left in `~/.config` from before
+/// `XDG_CONFIG_HOME` was honored visible under `$XDG_CONFIG_HOME` too. The
+/// old directory stays where it is: a systemd service usually runs without
+/// the shell's `XDG_CONFIG_HOME` and keeps reading it. Called when the
+/// renderer starts; does nothing once the new directory exists.
+pub fn migrate_legacy() {
+    let (Ok(dir), Some(home)) = (config_dir(), home_dir()) else {
+        return;
+    };
+    let legacy = home.join(".config").join(APP_DIR);
+    if legacy == dir || dir.exists() || !legacy.is_dir() {
+        return;
+    }
+    let shown = |path: &Path| display_path(&path.to_string_lossy());
+    match link_legacy(&legacy, &dir) {
+        Ok(how) => println!(
+            "[rendercore] notice: config found in {}; {how} it as {} (XDG_CONFIG_HOME), the old directory is kept",
+            shown(&legacy),
+            shown(&dir)
+        ),
+        Err(err) => eprintln!(
+            "[rendercore] warning: config in {} not carried over: {err}",
+            shown(&legacy)
+        ),
+    }
+}
+
+/// Points `dir` at `legacy` with a symlink, so a service and a shell that
+/// resolve different directories still share one map; copies the files when
+/// the link cannot be made. Returns which it did.
+fn link_legacy(legacy: &Path, dir: &Path) -> Result<&'static str, String> {
+    if let Some(parent) = dir.parent() {
+        ensure_dir(parent)?;
+    }
+    if std::os::unix::fs::symlink(legacy, dir).is_ok() {
+        return Ok("linked");
+    }
+    copy_files(legacy, dir).map(|()| "copied")
+}
+
+/// Copies the regular files of `from` (the config dir has no subdirectories)
+/// into a new `to`, removing `to` again if any copy fails.
+fn copy_files(from: &Path, to: &Path) -> Result<(), String> {
+    ensure_dir(to)?;
+    let copied = fs::read_dir(from)
+        .map_err(|e| format!("failed to read {}: {e}", from.display()))
+        .and_then(|entries| {
+            entries.flatten().try_for_each(|entry| {
+                if !entry.file_type().is_ok_and(|kind| kind.is_file()) {
+                    return Ok(());
+                }
+                fs::copy(entry.path(), to.join(entry.file_name()))
+                    .map(|_| ())
+                    .map_err(|e| format!("failed to copy {}: {e}", entry.path().display()))
+            })
+        });
+    if copied.is_err() {
+        let _ = fs::remove_dir_all(to);
+    }
+    copied
+}
+
+/// One row of `paths`.
+struct Location {
+    name: &'static str,
+    dir: bool,
+    path: Result<PathBuf, String>,
+    /// The variable (or default) the path came from.
+    source: &'static str,
+}
+
+/// Every place the renderer reads or writes, with overrides applied.
+fn locations() -> Vec<Location> {
+    let instance = instance_id_from_env();
+    let base = |name, base: Base| {
+        let resolved = base.resolve();
+        Location {
+            name,
+            dir: true,
+            source: resolved.as_ref().map_or("unresolved", |(_, source)| source),
+            path: resolved.map(|(dir, _)| dir),
+        }
+    };
+    let file = |name, var: &'static str, path: Result<PathBuf, String>| Location {
+        name,
+        dir: false,
+        path,
+        source: if env_set(var) { var } else { "default" },
+    };
+    let mut out = vec![
+        base("config dir", Base::Config),
+        file("map file", "KRC_VIDEO_MAP_FILE", map_file_path_from_env()),
+        file(
+            "config file",
+            "KRC_CONFIG_FILE",
+            config_file_path_from_env(),
+        ),
+    ];
+    if let Some(env_file) = std::env::var_os("KRC_ENV_FILE").filter(|v| !v.is_empty()) {
+        out.push(file(
+            "env file",
+            "KRC_ENV_FILE",
+            Ok(PathBuf::from(env_file)),
+        ));
+    }
+    out.push(base("state dir", Base::State));
+    out.push(file("daemon log", "", daemon_log(&instance)));
+    out.push(file(
+        "reduce-motion flag",
+        "",
+        reduce_motion_file(&instance),
+    ));
+    out.push(file("runtime snapshot", "", snapshot_file(&instance)));
+    out.push(file("supervisor state", "", supervisor_file(&instance)));
+    out.push(base("cache dir", Base::Cache));
+    out.push(base("runtime dir", Base::Runtime));
+    out.push(file("lock file", "", Ok(lock_file(&instance))));
+    out.push(file("supervisor lock", "", Ok(supervisor_lock(&instance))));
+    out.push(file(
+        "control socket",
+        "KRC_CONTROL_SOCKET",
+        Ok(control_socket_path_from_env()),
+    ));
+    out.push(file(
+        "pause file",
+        "KRC_PAUSE_FILE",
+        Ok(pause_file_path_from_env()),
+    ));
+    if let Some(journal) = std::env::var_os("KRC_RECORD").filter(|v| !v.is_empty()) {
+        out.push(file("journal", "KRC_RECORD", Ok(PathBuf::from(journal))));
+    }
+    if let Some(events) = std::env::var_os("KRC_EVENT_LOG").filter(|v| !v.is_empty()) {
+        out.push(file(
+            "event log",
+            "KRC_EVENT_LOG",
+            Ok(PathBuf::from(events)),
+        ));
+    }
+    out
+}
+
+fn env_set(var: &str) -> bool {
+    !var.is_empty() && std::env::var_os(var).is_some_and(|v| !v.is_empty())
+}
+
+/// `paths [--json]`: each location the renderer uses, where it resolved
+/// from, and whether it exists and is writable.
+pub fn run_paths(args: &[String]) -> Result<(), String> {
+    let mut as_json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => as_json = true,
+            "--help" | "-h" => {
+                print_paths_help();
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument for paths: {other}")),
+        }
+    }
+    let locations = locations();
+    if as_json {
+        let rows = locations
+            .iter()
+            .map(|location| {
+                let (path, error) = match &location.path {
+                    Ok(path) => (
+                        format!("\"{}\"", escape_json(&path.to_string_lossy())),
+                        "null".to_string(),
+                    ),
+                    Err(err) => ("null".to_string(), format!("\"{}\"", escape_json(err))),
+                };
+                let (exists, writable) = location
+                    .path
+                    .as_ref()
+                    .map_or((false, false), |path| (path.exists(), writable(path)));
+                format!(
+                    "{{\"name\":\"{}\",\"kind\":\"{}\",\"path\":{path},\"source\":\"{}\",\"exists\":{exists},\"writable\":{writable},\"error\":{error}}}",
+                    location.name.replace(' ', "_"),
+                    if location.dir { "dir" } else { "file" },
+                    location.source
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        println!(
+            "{{\"instance\":\"{}\",\"paths\":[{rows}]}}",
+            escape_json(&instance_id_from_env())
+        );
+        return Ok(());
+    }
+    println!("instance: {}", instance_id_from_env());
+    for location in &locations {
+        let path = match &location.path {
+            Ok(path) => path,
+            Err(err) => {
+                println!("[warn] {}: {err}", location.name);
+                continue;
+            }
+        };
+        let shown = display_path(&path.to_string_lossy());
+        let source = match location.source {
+            "default" => String::new(),
+            source => format!(", from {source}"),
+        };
+        match (path.exists(), writable(path)) {
+            (true, true) => println!("[ok] {}: {shown} (writable{source})", location.name),
+            (true, false) => println!("[warn] {}: {shown} (not writable{source})", location.name),
+            (false, true) => println!(
+                "[info] {}: {shown} (not created yet{source})",
+                location.name
+            ),
+            (false, false) => println!(
+                "[warn] {}: {shown} (missing, cannot be created{source})",
+                location.name
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn print_paths_help() {
+    println!("kitsune-rendercore paths");
+    println!("Usage:");
+    println!("  kitsune-rendercore paths [--json]");
+    println!();
+    println!("Lists every location the renderer reads or writes for this instance");
+    println!("(KRC_INSTANCE): the config, state, cache and runtime dirs under their XDG");
+    println!("variables, and the files in them after KRC_* overrides, with whether each");
+    println!("exists and is writable. Nothing is created.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("krc-paths-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn legacy_config_is_linked_and_kept() {
+        let root = scratch("link");
+        let legacy = root.join("home/.config").join(APP_DIR);
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("video-map.conf"), "DP-1=/a.mp4\n").unwrap();
+        let dir = root.join("xdg").join(APP_DIR);
+
+        assert_eq!(link_legacy(&legacy, &dir), Ok("linked"));
+        assert!(legacy.join("video-map.conf").is_file());
+        // Both paths name the same files, so edits through either are seen by both.
+        fs::write(dir.join("video-map.conf"), "DP-1=/b.mp4\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(legacy.join("video-map.conf")).unwrap(),
+            "DP-1=/b.mp4\n"
+        );
+    }
+
+    #[test]
+    fn copy_files_takes_regular_files_only() {
+        let root = scratch("copy");
+        let from = root.join("from");
+        fs::create_dir_all(from.join("sub")).unwrap();
+        fs::write(from.join("config.toml"), "[http]\n").unwrap();
+        let to = root.join("to");
+
+        copy_files(&from, &to).unwrap();
+        assert_eq!(
+            fs::read_to_string(to.join("config.toml")).unwrap(),
+            "[http]\n"
+        );
+        assert!(!to.join("sub").exists());
+        assert!(from.join("config.toml").is_file());
+    }
+}
@@ -0,0 +1,330 @@
+use crate::journal::{self, Value};
+use crate::user_path::display_text;
+
+/// What the renderer does while a reason is active, mildest first. When several
+/// reasons are active the strongest behavior wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PauseBehavior {
+    /// Stop submitting frames; decoders and GPU resources stay alive.
+    SkipRender,
+    /// Keep the last presented frame on screen and stop decoding.
+    Freeze,
+}
+
+impl PauseBehavior {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SkipRender => "skip-render",
+            Self::Freeze => "freeze",
+        }
+    }
+}
+
+/// A pause source. `priority` decides which reason is reported when several
+/// are active (higher wins); it does not change whether the renderer pauses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PauseReason {
+    pub name: &'static str,
+    pub priority: u8,
+    pub behavior: PauseBehavior,
+}
+
+pub const STEAM_GAME: PauseReason = PauseReason {
+    name: "steam-game",
+    priority: 50,
+    behavior: PauseBehavior::SkipRender,
+};
+
+pub const MANUAL_FILE: PauseReason = PauseReason {
+    name: "manual-file",
+    priority: 100,
+    behavior: PauseBehavior::Freeze,
+};
+
+/// The CPU is too hot or starved (`KRC_THERMAL_PAUSE_C`,
+/// `KRC_CPU_PRESSURE_PAUSE`); freezing also stops the decoders.
+pub const THERMAL: PauseReason = PauseReason {
+    name: "thermal",
+    priority: 75,
+    behavior: PauseBehavior::Freeze,
+};
+
+/// Reported for an output paused on its own (`pause --monitor`). Not a
+/// [`PauseReason`]: it never pauses the render loop, only that output.
+pub const MANUAL_OUTPUT: &str = "manual-output";
+
+/// The effective state the runtime and backend act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PauseDecision {
+    pub reason: Option<&'static str>,
+    pub behavior: Option<PauseBehavior>,
+}
+
+impl PauseDecision {
+    pub const RUNNING: Self = Self {
+        reason: None,
+        behavior: None,
+    };
+
+    pub fn is_paused(&self) -> bool {
+        self.reason.is_some()
+    }
+
+    /// Paused with [`PauseBehavior::Freeze`]: every output keeps presenting
+    /// its last frame while the decoders stop. Under
+    /// [`PauseBehavior::SkipRender`] nothing is submitted at all.
+    pub fn freezes(&self) -> bool {
+        self.behavior == Some(PauseBehavior::Freeze)
+    }
+}
+
+/// One change of the effective decision. `reason` is the reason that was active
+/// before a resume, so hooks and logs can say what ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PauseTransition {
+    pub paused: bool,
+    pub reason: &'static str,
+    pub behavior: PauseBehavior,
+}
+
+/// Collects every pause source and resolves them into one decision per tick.
+/// Sources are independent, so e.g. a manual pause outlives a Steam game ending.
+/// Outputs paused by name sit beside the global sources: a global resume does
+/// not resume them.
+pub struct PauseController {
+    sources: Vec<(PauseReason, bool)>,
+    decision: PauseDecision,
+    outputs: Vec<String>,
+}
+
+impl Default for PauseController {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            decision: PauseDecision::RUNNING,
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl PauseController {
+    /// Sets whether `reason` currently wants the renderer paused. Takes effect on
+    /// the next `resolve`.
+    pub fn set(&mut self, reason: PauseReason, active: bool) {
+        match self.sources.iter_mut().find(|(r, _)| r.name == reason.name) {
+            Some(slot) => *slot = (reason, active),
+            None => self.sources.push((reason, active)),
+        }
+    }
+
+    pub fn decision(&self) -> PauseDecision {
+        self.decision
+    }
+
+    /// Freezes or unfreezes one output by name; applies at once, recorded
+    /// like a global transition. Returns whether anything changed.
+    pub fn set_output(&mut self, monitor: &str, paused: bool) -> bool {
+        match (self.outputs.iter().position(|m| m == monitor), paused) {
+            (None, true) => self.outputs.push(monitor.to_string()),
+            (Some(at), false) => {
+                self.outputs.remove(at);
+            }
+            _ => return false,
+        }
+        println!(
+            "[rendercore] output pause state: monitor={} {} paused_outputs=[{}]",
+            display_text(monitor),
+            if paused { "paused" } else { "running" },
+            self.outputs.join(",")
+        );
+        journal::record(
+            "output_pause",
+            &[
+                ("monitor", Value::Str(monitor)),
+                ("paused", Value::Bool(paused)),
+            ],
+        );
+        true
+    }
+
+    /// Outputs paused by name, in the order they were paused.
+    pub fn paused_outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    /// Why `monitor` is paused, if it is: its own pause, else the global reason.
+    pub fn output_reason(&self, monitor: &str) -> Option<&'static str> {
+        if self.outputs.iter().any(|m| m == monitor) {
+            return Some(MANUAL_OUTPUT);
+        }
+        self.decision.reason
+    }
+
+    /// Recomputes the effective decision; returns the transition when it changed
+    /// and records it once (journal + log).
+    pub fn resolve(&mut self) -> Option<PauseTransition> {
+        let active = self
+            .sources
+            .iter()
+            .filter(|(_, active)| *active)
+            .map(|(reason, _)| *reason);
+        let top = active.clone().max_by_key(|reason| reason.priority);
+        let behavior = active.map(|reason| reason.behavior).max();
+        let next = PauseDecision {
+            reason: top.map(|reason| reason.name),
+            behavior,
+        };
+        if next == self.decision {
+            return None;
+        }
+        let previous = std::mem::replace(&mut self.decision, next);
+        let transition = match (next.reason, next.behavior) {
+            (Some(reason), Some(behavior)) => PauseTransition {
+                paused: true,
+                reason,
+                behavior,
+            },
+            _ => PauseTransition {
+                paused: false,
+                reason: previous.reason.unwrap_or(""),
+                behavior: previous.behavior.unwrap_or(PauseBehavior::SkipRender),
+            },
+        };
+        let active_names = self
+            .sources
+            .iter()
+            .filter(|(_, active)| *active)
+            .map(|(reason, _)| reason.name)
+            .collect::<Vec<_>>()
+            .join(",");
+        println!(
+            "[rendercore] pause state: {} reason={} behavior={} active=[{}]",
+            if transition.paused {
+                "paused"
+            } else {
+                "running"
+            },
+            transition.reason,
+            transition.behavior.as_str(),
+            active_names
+        );
+        journal::record(
+            "pause",
+            &[
+                ("paused", Value::Bool(transition.paused)),
+                ("reason", Value::Str(transition.reason)),
+                ("behavior", Value::Str(transition.behavior.as_str())),
+            ],
+        );
+        Some(transition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(active: &[PauseReason]) -> PauseDecision {
+        let mut pause = PauseController::default();
+        for reason in active {
+            pause.set(*reason, true);
+        }
+        pause.resolve();
+        pause.decision()
+    }
+
+    #[test]
+    fn a_single_reason_keeps_its_behavior() {
+        let steam = resolved(&[STEAM_GAME]);
+        assert_eq!(steam.reason, Some("steam-game"));
+        assert_eq!(steam.behavior, Some(PauseBehavior::SkipRender));
+        assert!(steam.is_paused() && !steam.freezes());
+
+        let thermal = resolved(&[THERMAL]);
+        assert_eq!(thermal.behavior, Some(PauseBehavior::Freeze));
+        assert!(thermal.freezes());
+
+        let running = resolved(&[]);
+        assert_eq!(running, PauseDecision::RUNNING);
+        assert!(!running.is_paused() && !running.freezes());
+    }
+
+    #[test]
+    fn the_strongest_behavior_wins_and_priority_picks_the_reason() {
+        // Freeze beats skip-render whichever reason is reported.
+        let hot_game = resolved(&[STEAM_GAME, THERMAL]);
+        assert_eq!(hot_game.reason, Some("thermal"));
+        assert!(hot_game.freezes());
+
+        let all = resolved(&[THERMAL, STEAM_GAME, MANUAL_FILE]);
+        assert_eq!(all.reason, Some("manual-file"));
+        assert!(all.freezes());
+    }
+
+    #[test]
+    fn ending_the_freezing_reason_falls_back_to_skip_render() {
+        let mut pause = PauseController::default();
+        pause.set(STEAM_GAME, true);
+        pause.set(THERMAL, true);
+        pause.resolve();
+        pause.set(THERMAL, false);
+        let transition = pause.resolve().expect("the decision changed");
+        assert!(transition.paused);
+        assert_eq!(transition.reason, "steam-game");
+        assert_eq!(transition.behavior, PauseBehavior::SkipRender);
+        assert!(!pause.decision().freezes());
+    }
+
+    #[test]
+    fn resuming_reports_what_ended() {
+        let mut pause = PauseController::default();
+        pause.set(MANUAL_FILE, true);
+        pause.resolve();
+        assert_eq!(pause.resolve(), None);
+        pause.set(MANUAL_FILE, false);
+        let transition = pause.resolve().expect("resumed");
+        assert!(!transition.paused);
+        assert_eq!(transition.reason, "manual-file");
+        assert_eq!(transition.behavior, PauseBehavior::Freeze);
+    }
+
+    #[test]
+    fn outputs_paused_by_name_outlive_a_global_resume() {
+        let mut pause = PauseController::default();
+        assert!(pause.set_output("DP-1", true));
+        assert!(!pause.set_output("DP-1", true));
+        pause.set(STEAM_GAME, true);
+        pause.resolve();
+        assert_eq!(pause.output_reason("HDMI-A-1"), Some("steam-game"));
+        pause.set(STEAM_GAME, false);
+        pause.resolve();
+        assert_eq!(pause.output_reason("DP-1"), Some(MANUAL_OUTPUT));
+        assert_eq!(pause.output_reason("HDMI-A-1"), None);
+    }
+
+    #[test]
+    fn a_manual_pause_outlives_a_steam_game_ending() {
+        let mut pause = PauseController::default();
+        pause.set(STEAM_GAME, true);
+        pause.resolve();
+        pause.set(MANUAL_FILE, true);
+        let transition = pause.resolve().expect("the manual pause takes over");
+        assert_eq!(transition.reason, "manual-file");
+
+        // The game exits: the Steam reason clears, the renderer stays paused.
+        pause.set(STEAM_GAME, false);
+        assert_eq!(pause.resolve(), None);
+        let decision = pause.decision();
+        assert!(decision.is_paused());
+        assert_eq!(decision.reason, Some("manual-file"));
+        assert!(decision.freezes());
+        assert_eq!(pause.output_reason("DP-1"), Some("manual-file"));
+
+        // Only removing the pause file resumes, and says so.
+        pause.set(MANUAL_FILE, false);
+        let transition = pause.resolve().expect("resumed");
+        assert!(!transition.paused);
+        assert_eq!(transition.reason, "manual-file");
+        assert_eq!(pause.decision(), PauseDecision::RUNNING);
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::journal::{self, Value};
+use crate::paths;
+
+pub fn pause_file_path_from_env() -> PathBuf {
+    std::env::var("KRC_PAUSE_FILE")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(paths::pause_file)
+}
+
+/// Sentinel-file pause: the renderer stays paused while the file exists.
+/// The runtime checks it once per `check_interval` so the cost stays negligible.
+pub struct PauseFileTrigger {
+    path: PathBuf,
+    check_interval: Duration,
+    present: bool,
+}
+
+impl PauseFileTrigger {
+    pub fn from_env() -> Self {
+        Self {
+            path: pause_file_path_from_env(),
+            check_interval: Duration::from_millis(1000),
+            present: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn check_interval(&self) -> Duration {
+        self.check_interval
+    }
+
+    pub fn check(&mut self) -> bool {
+        let present = self.path.exists();
+        if present != self.present {
+            journal::record("pause_file", &[("present", Value::Bool(present))]);
+        }
+        self.present = present;
+        self.present
+    }
+}
+
+/// Creates the pause file (and its directory). Used by the `pause` subcommand.
+pub fn create_pause_file(path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    std::fs::write(path, b"")
+        .map_err(|e| format!("failed to create pause file {}: {e}", path.display()))
+}
+
+/// Removes the pause file; returns false when it was not there.
+pub fn remove_pause_file(path: &Path) -> Result<bool, String> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!(
+            "failed to remove pause file {}: {e}",
+            path.display()
+        )),
+    }
+}
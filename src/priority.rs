@@ -0,0 +1,232 @@
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::proc_stat::ProcStat;
+
+/// I/O scheduling class for decoders (`KRC_DECODE_IONICE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IoClass {
+    /// Best effort at the lowest level (7).
+    BestEffort,
+    /// Disk time only when nothing else wants it.
+    Idle,
+    Unchanged,
+}
+
+/// Lower priority for the decoding side: ffmpeg children and the decoder
+/// warm-up thread get `nice`, an I/O class and `SCHED_BATCH`. The render
+/// thread keeps its priority so presentation does not stutter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodePriority {
+    pub nice: i32,
+    io: IoClass,
+}
+
+// `linux/ioprio.h`; libc has the syscall number but not these.
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+const IOPRIO_CLASS_BE: libc::c_int = 2;
+const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+impl DecodePriority {
+    /// `KRC_DECODE_NICE` (1-19) and `KRC_DECODE_IONICE`, read once at first
+    /// use. `None` leaves decoders at the renderer's own priority.
+    pub fn get() -> Option<Self> {
+        static PRIORITY: OnceLock<Option<DecodePriority>> = OnceLock::new();
+        *PRIORITY.get_or_init(|| {
+            let raw = std::env::var("KRC_DECODE_NICE").ok()?;
+            let nice = match raw.trim().parse::<i32>() {
+                Ok(0) => return None,
+                Ok(nice) if (1..=19).contains(&nice) => nice,
+                _ => {
+                    eprintln!("[rendercore] ignoring KRC_DECODE_NICE={raw}: expected 1-19");
+                    return None;
+                }
+            };
+            let io = match std::env::var("KRC_DECODE_IONICE")
+                .map(|v| v.trim().to_ascii_lowercase())
+                .as_deref()
+            {
+                Ok("idle") => IoClass::Idle,
+                Ok("none") => IoClass::Unchanged,
+                Ok("best-effort") | Ok("") | Err(_) => IoClass::BestEffort,
+                Ok(other) => {
+                    eprintln!(
+                        "[rendercore] ignoring KRC_DECODE_IONICE={other}: expected best-effort, idle or none"
+                    );
+                    IoClass::BestEffort
+                }
+            };
+            Some(DecodePriority { nice, io })
+        })
+    }
+
+    pub fn describe(self) -> String {
+        let io = match self.io {
+            IoClass::BestEffort => "best-effort/7",
+            IoClass::Idle => "idle",
+            IoClass::Unchanged => "unchanged",
+        };
+        format!("nice={} ionice={io} policy=batch", self.nice)
+    }
+
+    /// Lowers the child before it execs. Each call is best effort: what the
+    /// kernel refuses is left as is and shows up in the read-back nice value.
+    #[cfg_attr(
+        not(all(feature = "wayland-layer", feature = "video-ffmpeg")),
+        allow(dead_code)
+    )]
+    pub fn apply_to_child(self, command: &mut Command) {
+        // SAFETY: the hook only makes raw syscalls, which are async-signal-safe.
+        unsafe {
+            command.pre_exec(move || {
+                self.lower(0);
+                Ok(())
+            });
+        }
+    }
+
+    /// Lowers the calling thread only (Linux applies these per thread).
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn apply_to_current_thread(self) {
+        // SAFETY: gettid(2) has no preconditions; see `lower`.
+        unsafe {
+            let tid = libc::gettid();
+            self.lower(tid);
+        }
+    }
+
+    /// `who` is a thread id, or 0 for the calling process.
+    unsafe fn lower(self, who: libc::pid_t) {
+        let param = libc::sched_param { sched_priority: 0 };
+        // SAFETY: plain syscalls on our own process or thread; `param` outlives
+        // the call, and ioprio_set(2) takes three ints.
+        unsafe {
+            libc::setpriority(libc::PRIO_PROCESS, who as libc::id_t, self.nice);
+            libc::sched_setscheduler(who, libc::SCHED_BATCH, &param);
+            let class = match self.io {
+                IoClass::BestEffort => Some((IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | 7),
+                IoClass::Idle => Some(IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT),
+                IoClass::Unchanged => None,
+            };
+            if let Some(prio) = class {
+                libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, who, prio);
+            }
+        }
+    }
+}
+
+/// The nice value of `pid` as the kernel reports it (`/proc/<pid>/stat`).
+#[cfg_attr(
+    not(all(feature = "wayland-layer", feature = "video-ffmpeg")),
+    allow(dead_code)
+)]
+pub fn process_nice(pid: u32) -> Option<i32> {
+    ProcStat::of_pid(pid).map(|stat| stat.nice)
+}
+
+/// With decode priority configured under systemd, suggests weighting the
+/// whole service down too when its cgroup still has the default weight: nice
+/// only ranks processes inside the service's cgroup, other cgroups compete by
+/// weight.
+pub fn log_cgroup_hint() {
+    let Some(priority) = DecodePriority::get() else {
+        return;
+    };
+    println!("[rendercore] decode priority: {}", priority.describe());
+    if std::env::var_os("INVOCATION_ID").is_none() {
+        return;
+    }
+    let Some(cgroup) = std::fs::read_to_string("/proc/self/cgroup")
+        .ok()
+        .and_then(|raw| {
+            raw.lines()
+                .find_map(|line| line.strip_prefix("0::").map(str::to_string))
+        })
+    else {
+        return;
+    };
+    let weight = std::fs::read_to_string(format!("/sys/fs/cgroup{cgroup}/cpu.weight"));
+    if weight.is_ok_and(|w| w.trim() == "100") {
+        println!(
+            "[rendercore] service runs at the default CPU weight; to yield to builds and games as a whole, run 'systemctl --user edit kitsune-rendercore.service' and add: [Service] CPUWeight=20 IOWeight=20"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The calling thread's I/O priority, as ioprio_get(2) reports it.
+    fn thread_ioprio(tid: libc::pid_t) -> libc::c_long {
+        // SAFETY: ioprio_get(2) takes two ints and only reads scheduler state.
+        unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, tid) }
+    }
+
+    #[test]
+    fn lowering_a_thread_leaves_the_others_alone() {
+        let priority = DecodePriority {
+            nice: 7,
+            io: IoClass::Idle,
+        };
+        let (tid, nice, policy, ioprio) = std::thread::spawn(move || {
+            priority.apply_to_current_thread();
+            // SAFETY: gettid(2) and sched_getscheduler(2) have no
+            // preconditions.
+            let (tid, policy) = unsafe {
+                let tid = libc::gettid();
+                (tid, libc::sched_getscheduler(tid))
+            };
+            let stat =
+                ProcStat::read(std::path::Path::new(&format!("/proc/self/task/{tid}"))).unwrap();
+            (tid, stat.nice, policy, thread_ioprio(tid))
+        })
+        .join()
+        .unwrap();
+        assert!(tid > 0);
+        assert_eq!(nice, 7);
+        assert_eq!(policy, libc::SCHED_BATCH);
+        assert_eq!(
+            ioprio,
+            libc::c_long::from(IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT)
+        );
+        // SAFETY: as above.
+        let own = unsafe { libc::sched_getscheduler(libc::gettid()) };
+        assert_ne!(own, libc::SCHED_BATCH);
+    }
+
+    #[test]
+    fn children_are_lowered_before_they_exec() {
+        let priority = DecodePriority {
+            nice: 5,
+            io: IoClass::BestEffort,
+        };
+        let mut command = Command::new("sleep");
+        command.arg("5");
+        priority.apply_to_child(&mut command);
+        let mut child = command.spawn().unwrap();
+        let nice = process_nice(child.id());
+        // SAFETY: sched_getscheduler(2) only reads the child's policy.
+        let policy = unsafe { libc::sched_getscheduler(child.id() as libc::pid_t) };
+        let ioprio = thread_ioprio(child.id() as libc::pid_t);
+        let _ = child.kill();
+        let _ = child.wait();
+        assert_eq!(nice, Some(5));
+        assert_eq!(policy, libc::SCHED_BATCH);
+        assert_eq!(
+            ioprio,
+            libc::c_long::from((IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | 7)
+        );
+    }
+
+    #[test]
+    fn describe_names_the_settings() {
+        let priority = DecodePriority {
+            nice: 10,
+            io: IoClass::Unchanged,
+        };
+        assert_eq!(priority.describe(), "nice=10 ionice=unchanged policy=batch");
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+/// The fields of `/proc/<pid>/stat` the renderer reads: the decoder sampler,
+/// decode priority, the resource check and the Steam scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcStat {
+    /// `comm`, the command name without its parentheses.
+    pub comm: String,
+    /// `state`: `R`, `S`, `Z`, ...
+    pub state: char,
+    pub ppid: u32,
+    /// `utime + stime`, in clock ticks.
+    pub cpu_ticks: u64,
+    pub nice: i32,
+    /// `starttime`, in clock ticks since boot.
+    pub start_ticks: u64,
+}
+
+impl ProcStat {
+    /// Parses the contents of `/proc/<pid>/stat`. The command name may
+    /// contain spaces and parentheses, so it runs to the last `)` and the
+    /// other fields are counted from there: state is field 3, ppid 4, utime
+    /// and stime 14 and 15, nice 19 and starttime 22.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let open = raw.find('(')?;
+        let close = raw.rfind(')')?;
+        let comm = raw.get(open + 1..close)?.to_string();
+        let fields = raw[close + 1..].split_whitespace().collect::<Vec<_>>();
+        let field = |n: usize| fields.get(n - 3).copied();
+        let number = |n: usize| field(n)?.parse::<u64>().ok();
+        Some(Self {
+            comm,
+            state: field(3)?.chars().next()?,
+            ppid: field(4)?.parse().ok()?,
+            cpu_ticks: number(14)? + number(15)?,
+            nice: field(19)?.parse().ok()?,
+            start_ticks: number(22)?,
+        })
+    }
+
+    /// Reads `<proc_path>/stat`, e.g. for `/proc/1234`; `None` once the
+    /// process is gone.
+    pub fn read(proc_path: &Path) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(proc_path.join("stat")).ok()?)
+    }
+
+    /// [`ProcStat::read`] for `pid` under `/proc`.
+    pub fn of_pid(pid: u32) -> Option<Self> {
+        Self::read(&Path::new("/proc").join(pid.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A real line for an ffmpeg decoder, reniced to 10.
+    const FFMPEG: &str = "4242 (ffmpeg) S 4100 4242 4100 0 -1 4194304 5234 0 0 0 1500 250 0 0 30 10 5 0 987654 312475648 20480 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 17 3 0 0 0 0 0";
+
+    #[test]
+    fn fields_are_read_by_position() {
+        let stat = ProcStat::parse(FFMPEG).unwrap();
+        assert_eq!(
+            stat,
+            ProcStat {
+                comm: "ffmpeg".to_string(),
+                state: 'S',
+                ppid: 4100,
+                cpu_ticks: 1750,
+                nice: 10,
+                start_ticks: 987654,
+            }
+        );
+    }
+
+    #[test]
+    fn command_names_may_hold_spaces_and_parentheses() {
+        let raw = FFMPEG.replace("(ffmpeg)", "(Web Content (x) ))");
+        let stat = ProcStat::parse(&raw).unwrap();
+        assert_eq!(stat.comm, "Web Content (x) )");
+        assert_eq!(stat.ppid, 4100);
+        assert_eq!(stat.start_ticks, 987654);
+    }
+
+    #[test]
+    fn negative_nice_and_zombies_parse() {
+        let raw = FFMPEG.replace(" S ", " Z ").replace(" 30 10 ", " 20 -5 ");
+        let stat = ProcStat::parse(&raw).unwrap();
+        assert_eq!(stat.state, 'Z');
+        assert_eq!(stat.nice, -5);
+    }
+
+    #[test]
+    fn truncated_or_garbled_lines_are_rejected() {
+        assert_eq!(ProcStat::parse(""), None);
+        assert_eq!(ProcStat::parse("4242 ffmpeg S 1"), None);
+        let cut = FFMPEG
+            .split_whitespace()
+            .take(21)
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(ProcStat::parse(&cut), None);
+        assert_eq!(ProcStat::parse(&FFMPEG.replace(" 1500 ", " x ")), None);
+    }
+
+    #[test]
+    fn reads_this_process() {
+        let stat = ProcStat::of_pid(std::process::id()).unwrap();
+        assert_ne!(stat.state, 'Z');
+        assert!(stat.ppid > 0);
+        assert_eq!(ProcStat::read(Path::new("/proc/does-not-exist")), None);
+    }
+}
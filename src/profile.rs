@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::json::escape_json;
+use crate::monitor::{ArrivalStats, CallbackStats};
+
+/// Stats the backend keeps in its own rolling windows, reported alongside
+/// the profiler's: frame callback latency per output and frame arrival per
+/// decoder.
+#[derive(Default)]
+pub struct RollingStats {
+    pub callbacks: Vec<(String, CallbackStats)>,
+    pub arrivals: Vec<(String, ArrivalStats)>,
+}
+
+/// Phases of one backend frame, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// `dispatch_pending`: handling queued Wayland events.
+    Dispatch,
+    /// `blocking_dispatch`: idle wait for a frame callback or configure.
+    DispatchWait,
+    MapReload,
+    Acquire,
+    Decode,
+    Upload,
+    Encode,
+    Submit,
+    Present,
+    Flush,
+}
+
+impl Phase {
+    const ALL: [Phase; 10] = [
+        Phase::Dispatch,
+        Phase::DispatchWait,
+        Phase::MapReload,
+        Phase::Acquire,
+        Phase::Decode,
+        Phase::Upload,
+        Phase::Encode,
+        Phase::Submit,
+        Phase::Present,
+        Phase::Flush,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Dispatch => "dispatch",
+            Self::DispatchWait => "dispatch_wait",
+            Self::MapReload => "map_reload",
+            Self::Acquire => "acquire",
+            Self::Decode => "decode",
+            Self::Upload => "upload",
+            Self::Encode => "encode",
+            Self::Submit => "submit",
+            Self::Present => "present",
+            Self::Flush => "flush",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Span {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Span {
+    fn add(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+
+    fn avg(&self) -> Duration {
+        self.total
+            .checked_div(self.count.max(1) as u32)
+            .unwrap_or_default()
+    }
+}
+
+/// `KRC_PROFILE=1` timing of `render_frame`, accumulated over a window and
+/// logged every `KRC_PROFILE_INTERVAL_S` seconds (default 10), or returned by
+/// the control socket `profile` command. When disabled every call is a branch
+/// on a cached bool and `start` never reads the clock.
+pub struct FrameProfiler {
+    enabled: bool,
+    interval: Duration,
+    window_start: Instant,
+    frames: u64,
+    phases: [Span; Phase::ALL.len()],
+    upload_bytes: BTreeMap<String, u64>,
+    acquire_wait: BTreeMap<String, Span>,
+    /// GPU time of each output's particle overlay, from timestamp queries.
+    overlay_gpu: BTreeMap<String, Span>,
+}
+
+impl Default for FrameProfiler {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: Duration::from_secs(10),
+            window_start: Instant::now(),
+            frames: 0,
+            phases: [Span::default(); Phase::ALL.len()],
+            upload_bytes: BTreeMap::new(),
+            acquire_wait: BTreeMap::new(),
+            overlay_gpu: BTreeMap::new(),
+        }
+    }
+}
+
+impl FrameProfiler {
+    pub fn from_env() -> Self {
+        let enabled = enabled_from_env();
+        let interval_s = std::env::var("KRC_PROFILE_INTERVAL_S")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(10);
+        if enabled {
+            println!("[rendercore] profiling enabled (dump every {interval_s}s)");
+        }
+        Self {
+            enabled,
+            interval: Duration::from_secs(interval_s),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Start of a span; `None` (and no clock read) when profiling is off.
+    pub fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    pub fn end(&mut self, phase: Phase, started: Option<Instant>) {
+        if let Some(started) = started {
+            self.phases[phase as usize].add(started.elapsed());
+        }
+    }
+
+    pub fn end_acquire(&mut self, output: &str, started: Option<Instant>) {
+        if let Some(started) = started {
+            let elapsed = started.elapsed();
+            self.phases[Phase::Acquire as usize].add(elapsed);
+            self.acquire_wait
+                .entry(output.to_string())
+                .or_default()
+                .add(elapsed);
+        }
+    }
+
+    pub fn end_upload(&mut self, stream: &str, bytes: u64, started: Option<Instant>) {
+        if let Some(started) = started {
+            self.phases[Phase::Upload as usize].add(started.elapsed());
+            *self.upload_bytes.entry(stream.to_string()).or_default() += bytes;
+        }
+    }
+
+    /// GPU time of one output's overlay draws; arrives a frame or two after
+    /// the frame it measures.
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn add_overlay_gpu(&mut self, output: &str, elapsed: Duration) {
+        if self.enabled {
+            self.overlay_gpu
+                .entry(output.to_string())
+                .or_default()
+                .add(elapsed);
+        }
+    }
+
+    /// Counts one frame and logs + resets the window when it is due, with
+    /// the backend's own stats from `rolling`.
+    pub fn frame_done(&mut self, rolling: impl FnOnce() -> RollingStats) {
+        if !self.enabled {
+            return;
+        }
+        self.frames += 1;
+        if self.window_start.elapsed() >= self.interval {
+            self.log_window(&rolling());
+            *self = Self {
+                enabled: self.enabled,
+                interval: self.interval,
+                ..Self::default()
+            };
+        }
+    }
+
+    fn log_window(&self, rolling: &RollingStats) {
+        let secs = self.window_start.elapsed().as_secs_f64().max(0.001);
+        println!(
+            "[rendercore] profile window={:.1}s frames={} ({:.1} fps)",
+            secs,
+            self.frames,
+            self.frames as f64 / secs
+        );
+        for phase in Phase::ALL {
+            let span = self.phases[phase as usize];
+            if span.count == 0 {
+                continue;
+            }
+            println!(
+                "[rendercore] profile   {:<13} n={:<6} avg={:>8.1}us max={:>8.1}us total={:>7.1}ms ({:.1}% of wall)",
+                phase.name(),
+                span.count,
+                span.avg().as_secs_f64() * 1e6,
+                span.max.as_secs_f64() * 1e6,
+                span.total.as_secs_f64() * 1e3,
+                span.total.as_secs_f64() / secs * 100.0
+            );
+        }
+        for (stream, bytes) in &self.upload_bytes {
+            println!(
+                "[rendercore] profile   upload {stream}: {:.1} MiB/s",
+                *bytes as f64 / secs / (1024.0 * 1024.0)
+            );
+        }
+        for (output, span) in &self.acquire_wait {
+            println!(
+                "[rendercore] profile   acquire {output}: avg={:.1}us max={:.1}us",
+                span.avg().as_secs_f64() * 1e6,
+                span.max.as_secs_f64() * 1e6
+            );
+        }
+        for (output, span) in &self.overlay_gpu {
+            println!(
+                "[rendercore] profile   overlay_gpu {output}: avg={:.1}us max={:.1}us n={}",
+                span.avg().as_secs_f64() * 1e6,
+                span.max.as_secs_f64() * 1e6,
+                span.count
+            );
+        }
+        let ms = |d: Option<Duration>| d.map_or(0.0, |d| d.as_secs_f64() * 1e3);
+        for (output, stats) in &rolling.callbacks {
+            println!(
+                "[rendercore] profile   callback {output}: p50={:.2}ms p99={:.2}ms n={}{}{}",
+                ms(stats.p50),
+                ms(stats.p99),
+                stats.samples,
+                if stats.slow { " slow" } else { "" },
+                if stats.stalled { " stalled" } else { "" }
+            );
+        }
+        for (stream, stats) in &rolling.arrivals {
+            println!(
+                "[rendercore] profile   arrival {stream}: p95={:.2}ms pipe={}/{}B avg_fill={:.0}% starved decode={} io={} n={}",
+                ms(stats.p95),
+                stats.queued_bytes,
+                stats.capacity_bytes,
+                stats.avg_fill * 100.0,
+                stats.starved_decode,
+                stats.starved_io,
+                stats.samples
+            );
+        }
+    }
+
+    /// Compact JSON of the current window for the control socket, with the
+    /// backend's `rolling` stats (each in its own rolling window).
+    pub fn report_json(&self, rolling: &RollingStats) -> String {
+        if !self.enabled {
+            return "{\"error\":\"profiling disabled (start the renderer with KRC_PROFILE=1)\"}"
+                .to_string();
+        }
+        let span_json = |span: &Span| {
+            format!(
+                "{{\"count\":{},\"avg_us\":{},\"max_us\":{},\"total_us\":{}}}",
+                span.count,
+                span.avg().as_micros(),
+                span.max.as_micros(),
+                span.total.as_micros()
+            )
+        };
+        let phases = Phase::ALL
+            .iter()
+            .map(|phase| {
+                format!(
+                    "\"{}\":{}",
+                    phase.name(),
+                    span_json(&self.phases[*phase as usize])
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let uploads = self
+            .upload_bytes
+            .iter()
+            .map(|(stream, bytes)| format!("\"{}\":{}", escape_json(stream), bytes))
+            .collect::<Vec<_>>()
+            .join(",");
+        let acquire = self
+            .acquire_wait
+            .iter()
+            .map(|(output, span)| format!("\"{}\":{}", escape_json(output), span_json(span)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let overlay_gpu = self
+            .overlay_gpu
+            .iter()
+            .map(|(output, span)| format!("\"{}\":{}", escape_json(output), span_json(span)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let us = |d: Option<Duration>| {
+            d.map_or_else(|| "null".to_string(), |d| d.as_micros().to_string())
+        };
+        let callback = rolling
+            .callbacks
+            .iter()
+            .map(|(output, stats)| {
+                format!(
+                    "\"{}\":{{\"samples\":{},\"p50_us\":{},\"p99_us\":{},\"slow\":{},\"stalled\":{}}}",
+                    escape_json(output),
+                    stats.samples,
+                    us(stats.p50),
+                    us(stats.p99),
+                    stats.slow,
+                    stats.stalled
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let arrival = rolling
+            .arrivals
+            .iter()
+            .map(|(stream, stats)| format!("\"{}\":{}", escape_json(stream), arrival_json(stats)))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"window_ms\":{},\"frames\":{},\"phases\":{{{}}},\"upload_bytes\":{{{}}},\"acquire_wait\":{{{}}},\"overlay_gpu\":{{{}}},\"callback_latency\":{{{}}},\"frame_arrival\":{{{}}}}}",
+            self.window_start.elapsed().as_millis(),
+            self.frames,
+            phases,
+            uploads,
+            acquire,
+            overlay_gpu,
+            callback,
+            arrival
+        )
+    }
+}
+
+/// `KRC_PROFILE=1`.
+pub fn enabled_from_env() -> bool {
+    std::env::var("KRC_PROFILE")
+        .ok()
+        .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+fn arrival_json(stats: &ArrivalStats) -> String {
+    format!(
+        "{{\"samples\":{},\"p95_us\":{},\"queued_bytes\":{},\"capacity_bytes\":{},\"avg_fill\":{:.3},\"starved_decode\":{},\"starved_io\":{}}}",
+        stats.samples,
+        stats
+            .p95
+            .map_or_else(|| "null".to_string(), |d| d.as_micros().to_string()),
+        stats.queued_bytes,
+        stats.capacity_bytes,
+        stats.avg_fill,
+        stats.starved_decode,
+        stats.starved_io
+    )
+}
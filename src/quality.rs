@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::journal::{self, Value};
+
+/// Source sizes the resolution steps walk down, largest first.
+const RESOLUTION_LADDER: [(u32, u32); 6] = [
+    (3840, 2160),
+    (2560, 1440),
+    (1920, 1080),
+    (1280, 720),
+    (960, 540),
+    (640, 360),
+];
+/// At most this many resolution steps below the configured source size.
+const MAX_RESOLUTION_STEPS: u8 = 2;
+/// The fps step is skipped when halving would go below this.
+const MIN_FPS: u32 = 15;
+/// A window counts as comfortable when no frame missed and the average frame
+/// took less than this fraction of the budget, so undoing one step (which
+/// roughly doubles the cost) still fits.
+const HEADROOM: f64 = 0.4;
+/// Comfortable windows needed before the first recovery step.
+const RECOVER_WINDOWS: u32 = 6;
+/// Cap for the recovery backoff (RECOVER_WINDOWS * this).
+const MAX_BACKOFF: u32 = 8;
+
+/// Opt-in (`KRC_AUTO_QUALITY=1`) per-output degrade policy. Every decoded frame
+/// reports its work time against the frame budget; at the end of each window
+/// an output whose miss ratio exceeds the threshold goes one level down, and
+/// one that had enough comfortable windows in a row goes one level up.
+///
+/// Levels, mildest first: 0 = as configured, then half fps (skipped when that
+/// would go below 15 fps), then one and two resolution presets down. State is
+/// kept in memory only, so a restart always starts at level 0.
+pub struct AutoQuality {
+    enabled: bool,
+    window: Duration,
+    miss_threshold: f64,
+    outputs: BTreeMap<String, OutputQuality>,
+}
+
+struct OutputQuality {
+    level: u8,
+    max_level: u8,
+    /// Level the headroom estimate asks for; recovery stops here.
+    floor: u8,
+    window_start: Instant,
+    frames: u32,
+    misses: u32,
+    work_total: Duration,
+    comfortable_windows: u32,
+    /// Doubles each time a recovery is undone by the next window, so a setup
+    /// sitting right at the edge settles instead of flapping.
+    backoff: u32,
+    just_recovered: bool,
+}
+
+/// One level change, for the backend to apply and for the log/journal.
+#[derive(Debug, Clone)]
+pub struct QualityChange {
+    pub output: String,
+    pub from: u8,
+    pub to: u8,
+    pub reason: String,
+}
+
+impl Default for AutoQuality {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: Duration::from_secs(10),
+            miss_threshold: 0.2,
+            outputs: BTreeMap::new(),
+        }
+    }
+}
+
+impl AutoQuality {
+    pub fn from_env() -> Self {
+        let enabled = std::env::var("KRC_AUTO_QUALITY")
+            .ok()
+            .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        let window_s = std::env::var("KRC_AUTO_QUALITY_WINDOW_S")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(10);
+        let miss_pct = std::env::var("KRC_AUTO_QUALITY_MISS_PCT")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|v| (1..=100).contains(v))
+            .unwrap_or(20);
+        if enabled {
+            println!(
+                "[rendercore] auto quality enabled: window={window_s}s miss_threshold={miss_pct}%"
+            );
+        }
+        Self {
+            enabled,
+            window: Duration::from_secs(window_s),
+            miss_threshold: miss_pct as f64 / 100.0,
+            outputs: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Current level of `output` (0 when disabled or never seen).
+    pub fn level(&self, output: &str) -> u8 {
+        self.outputs.get(output).map(|o| o.level).unwrap_or(0)
+    }
+
+    /// Records one frame of `output` that took `work` against `budget`, and
+    /// returns a level change when this closes a window that calls for one.
+    /// `max_level` comes from [`max_level`] for the output's configuration.
+    pub fn record(
+        &mut self,
+        output: &str,
+        work: Duration,
+        budget: Duration,
+        max_level: u8,
+        now: Instant,
+    ) -> Option<QualityChange> {
+        if !self.enabled {
+            return None;
+        }
+        let state = self
+            .outputs
+            .entry(output.to_string())
+            .or_insert_with(|| OutputQuality::new(max_level, now));
+        state.max_level = max_level;
+        state.frames += 1;
+        state.work_total += work;
+        if work > budget {
+            state.misses += 1;
+        }
+        if now.saturating_duration_since(state.window_start) < self.window {
+            return None;
+        }
+
+        let frames = state.frames.max(1);
+        let miss_ratio = state.misses as f64 / frames as f64;
+        let avg_work = state.work_total / frames;
+        let (misses, window_frames) = (state.misses, state.frames);
+        state.window_start = now;
+        state.frames = 0;
+        state.misses = 0;
+        state.work_total = Duration::ZERO;
+
+        let from = state.level;
+        if miss_ratio > self.miss_threshold {
+            state.comfortable_windows = 0;
+            if state.just_recovered {
+                state.backoff = (state.backoff * 2).min(MAX_BACKOFF);
+            }
+            state.just_recovered = false;
+            if state.level >= state.max_level {
+                return None;
+            }
+            state.level += 1;
+            return Some(QualityChange {
+                output: output.to_string(),
+                from,
+                to: state.level,
+                reason: format!(
+                    "{misses}/{window_frames} frames over the {:.1}ms budget",
+                    budget.as_secs_f64() * 1e3
+                ),
+            });
+        }
+        state.just_recovered = false;
+        let comfortable = misses == 0 && avg_work.as_secs_f64() < budget.as_secs_f64() * HEADROOM;
+        if !comfortable {
+            state.comfortable_windows = 0;
+            return None;
+        }
+        state.comfortable_windows += 1;
+        if state.level <= state.floor || state.comfortable_windows < RECOVER_WINDOWS * state.backoff
+        {
+            return None;
+        }
+        state.comfortable_windows = 0;
+        state.just_recovered = true;
+        state.level -= 1;
+        Some(QualityChange {
+            output: output.to_string(),
+            from,
+            to: state.level,
+            reason: format!(
+                "avg {:.1}ms of a {:.1}ms budget",
+                avg_work.as_secs_f64() * 1e3,
+                budget.as_secs_f64() * 1e3
+            ),
+        })
+    }
+
+    /// Sets the level `output` starts at and never recovers past, from the
+    /// headroom estimate (0 lifts it). Returns the change when the output
+    /// has to step down to reach it now, without waiting for missed frames.
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn set_floor(
+        &mut self,
+        output: &str,
+        floor: u8,
+        max_level: u8,
+        reason: &str,
+        now: Instant,
+    ) -> Option<QualityChange> {
+        if !self.enabled {
+            return None;
+        }
+        let state = self
+            .outputs
+            .entry(output.to_string())
+            .or_insert_with(|| OutputQuality::new(max_level, now));
+        state.max_level = max_level;
+        state.floor = floor.min(max_level);
+        if state.level >= state.floor {
+            return None;
+        }
+        let from = state.level;
+        state.level = state.floor;
+        state.comfortable_windows = 0;
+        Some(QualityChange {
+            output: output.to_string(),
+            from,
+            to: state.level,
+            reason: reason.to_string(),
+        })
+    }
+}
+
+impl OutputQuality {
+    fn new(max_level: u8, now: Instant) -> Self {
+        Self {
+            level: 0,
+            max_level,
+            floor: 0,
+            window_start: now,
+            frames: 0,
+            misses: 0,
+            work_total: Duration::ZERO,
+            comfortable_windows: 0,
+            backoff: 1,
+            just_recovered: false,
+        }
+    }
+}
+
+fn has_fps_step(base_fps: u32) -> bool {
+    base_fps / 2 >= MIN_FPS
+}
+
+fn resolution_steps(level: u8, base_fps: u32) -> u8 {
+    if has_fps_step(base_fps) {
+        level.saturating_sub(1)
+    } else {
+        level
+    }
+}
+
+/// Deepest level available for this configuration.
+pub fn max_level(base_fps: u32, base_size: (u32, u32)) -> u8 {
+    let smaller = RESOLUTION_LADDER
+        .iter()
+        .filter(|(w, _)| *w < base_size.0.max(base_size.1))
+        .count() as u8;
+    has_fps_step(base_fps) as u8 + smaller.min(MAX_RESOLUTION_STEPS)
+}
+
+pub fn degraded_fps(base_fps: u32, level: u8) -> u32 {
+    if level > 0 && has_fps_step(base_fps) {
+        base_fps / 2
+    } else {
+        base_fps
+    }
+}
+
+/// Source size at `level`: the n-th ladder preset narrower than the base,
+/// keeping the base aspect ratio and orientation.
+pub fn degraded_source_size(base: (u32, u32), level: u8, base_fps: u32) -> (u32, u32) {
+    let steps = resolution_steps(level, base_fps) as usize;
+    if steps == 0 {
+        return base;
+    }
+    let long = base.0.max(base.1);
+    let Some(&(preset_long, _)) = RESOLUTION_LADDER
+        .iter()
+        .filter(|(w, _)| *w < long)
+        .nth(steps - 1)
+    else {
+        return base;
+    };
+    let scale = preset_long as f64 / long as f64;
+    let even = |v: u32| (((v as f64 * scale).round() as u32) & !1).max(2);
+    (even(base.0), even(base.1))
+}
+
+/// Human-readable description of what `level` means, for logs and status.
+pub fn describe_level(level: u8, base_fps: u32, base_size: (u32, u32)) -> String {
+    if level == 0 {
+        return "full".to_string();
+    }
+    let (w, h) = degraded_source_size(base_size, level, base_fps);
+    format!("fps={} source={w}x{h}", degraded_fps(base_fps, level))
+}
+
+/// Logs and journals a level change.
+pub fn record_change(change: &QualityChange, base_fps: u32, base_size: (u32, u32)) {
+    let direction = if change.to > change.from {
+        "degraded"
+    } else {
+        "recovered"
+    };
+    let described = describe_level(change.to, base_fps, base_size);
+    println!(
+        "[rendercore] auto quality {direction} output={} level {}->{} ({described}): {}",
+        change.output, change.from, change.to, change.reason
+    );
+    journal::record(
+        "quality",
+        &[
+            ("output", Value::Str(&change.output)),
+            ("level", Value::U64(change.to as u64)),
+            ("quality", Value::Str(&described)),
+            ("reason", Value::Str(&change.reason)),
+        ],
+    );
+}
@@ -0,0 +1,324 @@
+use std::time::Duration;
+
+use crate::tools::Tool;
+
+/// Exit code of a renderer that stopped for quiet hours. The systemd unit
+/// counts it as a clean stop and does not restart; a timer starts the
+/// service again when the window ends.
+pub const QUIET_EXIT_CODE: i32 = 75;
+
+/// How long the in-process wait sleeps between wall-clock checks. The
+/// monotonic clock stops during suspend, so a single long sleep would
+/// overshoot a window that ended while the machine was asleep.
+pub const QUIET_POLL: Duration = Duration::from_secs(60);
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// `KRC_QUIET_HOURS=01:00-07:00`: a daily window, in local time, during
+/// which the renderer does not run at all. The window may cross midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    /// Minutes after midnight.
+    start: u32,
+    end: u32,
+}
+
+impl QuietHours {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let (start, end) = raw
+            .trim()
+            .split_once('-')
+            .ok_or_else(|| "expected HH:MM-HH:MM".to_string())?;
+        let start = parse_clock(start)?;
+        let end = parse_clock(end)?;
+        if start == end {
+            return Err("start and end are the same time".to_string());
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Whether `minute` (after local midnight) falls in the window.
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+
+    /// Whether the local time now falls in the window.
+    pub fn is_active_now(&self) -> bool {
+        local_now().is_some_and(|now| self.contains(now.seconds_of_day / 60))
+    }
+
+    /// Time from `seconds_of_day` (local) to the end of the window.
+    pub fn until_end(&self, seconds_of_day: u32) -> Duration {
+        let end = self.end * 60;
+        let secs = if end > seconds_of_day {
+            end - seconds_of_day
+        } else {
+            end + MINUTES_PER_DAY * 60 - seconds_of_day
+        };
+        Duration::from_secs(u64::from(secs))
+    }
+
+    /// The window's end as `YYYY-MM-DD HH:MM:00` local time, the next time
+    /// it comes; a `systemd-run --on-calendar` spec that fires once.
+    pub fn next_end_calendar(&self) -> Option<String> {
+        let now = local_now()?;
+        let today = local_time(now.epoch)?;
+        let date = (
+            today.tm_year + 1900,
+            (today.tm_mon + 1) as u32,
+            today.tm_mday as u32,
+        );
+        Some(self.end_calendar_from(date, now.seconds_of_day))
+    }
+
+    /// The spec for the next end after `seconds_of_day` on the local `date`
+    /// (year, month, day). Built from the date and the end's HH:MM rather
+    /// than by adding seconds, which lands an hour off on DST nights.
+    fn end_calendar_from(&self, date: (i32, u32, u32), seconds_of_day: u32) -> String {
+        let (year, month, day) = if self.end * 60 > seconds_of_day {
+            date
+        } else {
+            next_date(date)
+        };
+        format!("{year:04}-{month:02}-{day:02} {}:00", clock_label(self.end))
+    }
+
+    /// `07:00`.
+    pub fn end_label(&self) -> String {
+        clock_label(self.end)
+    }
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", clock_label(self.start), clock_label(self.end))
+    }
+}
+
+fn parse_clock(raw: &str) -> Result<u32, String> {
+    let raw = raw.trim();
+    let (hours, minutes) = raw
+        .split_once(':')
+        .ok_or_else(|| format!("expected HH:MM, got '{raw}'"))?;
+    let hours = hours.parse::<u32>().ok().filter(|h| *h < 24);
+    let minutes = minutes.parse::<u32>().ok().filter(|m| *m < 60);
+    match (hours, minutes) {
+        (Some(h), Some(m)) => Ok(h * 60 + m),
+        _ => Err(format!("'{raw}' is not a time of day")),
+    }
+}
+
+/// The calendar day after `(year, month, day)`.
+fn next_date((year, month, day): (i32, u32, u32)) -> (i32, u32, u32) {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if day < days_in_month {
+        (year, month, day + 1)
+    } else if month < 12 {
+        (year, month + 1, 1)
+    } else {
+        (year + 1, 1, 1)
+    }
+}
+
+fn clock_label(minute: u32) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+fn local_time(epoch: i64) -> Option<libc::tm> {
+    let time = libc::time_t::try_from(epoch).ok()?;
+    // SAFETY: `tm` is plain data (integers and a pointer), for which all
+    // zeroes is a valid value.
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    // SAFETY: both pointers are valid for the call; localtime_r(3) only
+    // writes `tm` and, unlike localtime, keeps no shared state.
+    let result = unsafe { libc::localtime_r(&time, &mut tm) };
+    (!result.is_null()).then_some(tm)
+}
+
+/// `strftime(3)` of `epoch` in the local time zone. `None` when the format
+/// holds a NUL, or its result is empty or longer than 255 bytes.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub fn format_local(epoch: i64, format: &str) -> Option<String> {
+    let format = std::ffi::CString::new(format).ok()?;
+    let tm = local_time(epoch)?;
+    let mut buf = [0u8; 256];
+    // SAFETY: `buf` is writable for `buf.len()` bytes, `format` is
+    // NUL-terminated and `tm` was filled by localtime_r.
+    let len = unsafe { libc::strftime(buf.as_mut_ptr().cast(), buf.len(), format.as_ptr(), &tm) };
+    (len > 0).then(|| String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+pub struct LocalNow {
+    pub epoch: i64,
+    pub seconds_of_day: u32,
+}
+
+/// The wall clock now, in the local time zone.
+pub fn local_now() -> Option<LocalNow> {
+    let epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs() as i64;
+    let tm = local_time(epoch)?;
+    Some(LocalNow {
+        epoch,
+        seconds_of_day: (tm.tm_hour * 3600 + tm.tm_min * 60 + tm.tm_sec.min(59)) as u32,
+    })
+}
+
+/// Waits in this process until the window is over, re-reading the wall
+/// clock every [`QUIET_POLL`] so a suspend across the end is noticed on
+/// resume.
+pub fn sleep_through(quiet: QuietHours) {
+    println!(
+        "[rendercore] quiet hours {quiet}: renderer stopped, resuming at {}",
+        quiet.end_label()
+    );
+    while quiet.is_active_now() {
+        let left = local_now().map_or(QUIET_POLL, |now| quiet.until_end(now.seconds_of_day));
+        std::thread::sleep(left.min(QUIET_POLL));
+    }
+    println!("[rendercore] quiet hours {quiet} over, starting the renderer");
+}
+
+/// The systemd unit this process runs in, from `/proc/self/cgroup`.
+fn own_unit() -> Option<String> {
+    let cgroup = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))?
+        .rsplit('/')
+        .next()
+        .filter(|unit| unit.ends_with(".service"))
+        .map(str::to_string)
+}
+
+/// Under systemd: has a one-shot user timer start this service again when
+/// the window ends, and returns when that is. The caller then exits with
+/// [`QUIET_EXIT_CODE`]. A realtime timer that came due during suspend
+/// fires on resume.
+pub fn schedule_service_start(quiet: QuietHours) -> Result<String, String> {
+    let unit = own_unit().unwrap_or_else(|| "kitsune-rendercore.service".to_string());
+    let at = quiet
+        .next_end_calendar()
+        .ok_or_else(|| "local time unavailable".to_string())?;
+    let timer = format!("{}-quiet-end", unit.trim_end_matches(".service"));
+    // Left over from a night the service was stopped by hand.
+    let _ = Tool::Systemctl
+        .command()
+        .args(["--user", "stop", &format!("{timer}.timer")])
+        .output();
+    let output = Tool::SystemdRun
+        .command()
+        .args([
+            "--user",
+            "--collect",
+            &format!("--unit={timer}"),
+            &format!("--on-calendar={at}"),
+            "--timer-property=AccuracySec=1s",
+            Tool::Systemctl.name(),
+            "--user",
+            "start",
+            &unit,
+        ])
+        .output()
+        .map_err(|err| Tool::SystemdRun.spawn_error(err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "systemd-run failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_time_agrees_with_strftime() {
+        // 2024-02-29 12:34:56 UTC; any zone keeps it within a day of that.
+        let epoch = 1_709_210_096;
+        let tm = local_time(epoch).unwrap();
+        let fields = format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        );
+        assert_eq!(format_local(epoch, "%Y-%m-%d %H:%M:%S"), Some(fields));
+        assert_eq!(tm.tm_sec, 56);
+        assert!(matches!(tm.tm_mday, 29 | 1));
+    }
+
+    #[test]
+    fn local_time_is_reentrant_across_threads() {
+        let expected = (0..8)
+            .map(|n| format_local(n * 86_400 * 40, "%F %T"))
+            .collect::<Vec<_>>();
+        let handles = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    (0..8)
+                        .map(|n| format_local(n * 86_400 * 40, "%F %T"))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn format_local_rejects_what_strftime_cannot_hold() {
+        assert_eq!(format_local(0, "a\0b"), None);
+        assert_eq!(format_local(0, ""), None);
+        assert_eq!(format_local(0, &"x".repeat(300)), None);
+    }
+
+    #[test]
+    fn next_end_is_a_calendar_spec_at_the_window_end() {
+        let quiet = QuietHours::parse("01:00-07:30").unwrap();
+        // Before the end it is today; at or after it, the next day, across
+        // month, leap-day and year boundaries.
+        let cases = [
+            ((2024, 3, 31), 3 * 3600, "2024-03-31 07:30:00"),
+            ((2024, 3, 31), 7 * 3600 + 30 * 60, "2024-04-01 07:30:00"),
+            ((2024, 2, 28), 23 * 3600, "2024-02-29 07:30:00"),
+            ((2023, 2, 28), 23 * 3600, "2023-03-01 07:30:00"),
+            ((2024, 4, 30), 12 * 3600, "2024-05-01 07:30:00"),
+            ((2024, 12, 31), 23 * 3600, "2025-01-01 07:30:00"),
+        ];
+        for (date, seconds_of_day, expected) in cases {
+            assert_eq!(quiet.end_calendar_from(date, seconds_of_day), expected);
+        }
+        let spec = quiet.next_end_calendar().unwrap();
+        assert_eq!(spec.len(), "YYYY-MM-DD HH:MM:00".len());
+    }
+
+    #[test]
+    fn windows_may_cross_midnight() {
+        let quiet = QuietHours::parse("23:00-07:00").unwrap();
+        assert!(quiet.contains(23 * 60));
+        assert!(quiet.contains(3 * 60));
+        assert!(!quiet.contains(7 * 60));
+        assert_eq!(quiet.until_end(23 * 3600), Duration::from_secs(8 * 3600));
+        assert!(QuietHours::parse("07:00-07:00").is_err());
+        assert!(QuietHours::parse("24:00-07:00").is_err());
+    }
+}
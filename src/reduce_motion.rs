@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+
+use crate::control;
+use crate::instance::instance_id_from_env;
+use crate::json::{JsonValue, parse_json};
+use crate::paths::{ensure_dir, reduce_motion_file};
+
+/// `reduce-motion on|off|toggle`; a toggle is resolved against the state it
+/// finds, the renderer's when it runs, else the flag file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    On,
+    Off,
+    Toggle,
+}
+
+impl Mode {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "on" => Some(Self::On),
+            "off" => Some(Self::Off),
+            "toggle" => Some(Self::Toggle),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::On => "on",
+            Self::Off => "off",
+            Self::Toggle => "toggle",
+        }
+    }
+
+    pub fn apply(self, current: bool) -> bool {
+        match self {
+            Self::On => true,
+            Self::Off => false,
+            Self::Toggle => !current,
+        }
+    }
+}
+
+fn flag_path() -> Result<PathBuf, String> {
+    reduce_motion_file(&instance_id_from_env())
+}
+
+/// Whether reduce-motion was left on, by this or an earlier renderer.
+pub fn is_on() -> bool {
+    flag_path().is_ok_and(|path| path.exists())
+}
+
+/// Creates or removes the flag file, so the setting survives restarts.
+pub fn persist(on: bool) -> Result<(), String> {
+    let path = flag_path()?;
+    if on {
+        if let Some(parent) = path.parent() {
+            ensure_dir(parent)?;
+        }
+        return std::fs::write(&path, b"")
+            .map_err(|e| format!("failed to write {}: {e}", path.display()));
+    }
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("failed to remove {}: {e}", path.display())),
+    }
+}
+
+/// `reduce-motion [on|off|toggle] [--json]`. With a running renderer the
+/// change goes through its control socket, which also saves it; otherwise
+/// only the flag file changes and the next start picks it up.
+pub fn run_reduce_motion(args: &[String]) -> Result<(), String> {
+    let mut mode = None::<Mode>;
+    let mut as_json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => as_json = true,
+            "--help" | "-h" => {
+                print_reduce_motion_help();
+                return Ok(());
+            }
+            raw if mode.is_none() => {
+                mode = Some(Mode::parse(raw).ok_or_else(|| {
+                    format!(
+                        "unknown argument for reduce-motion: {raw} (expected on, off or toggle)"
+                    )
+                })?);
+            }
+            other => return Err(format!("unknown argument for reduce-motion: {other}")),
+        }
+    }
+    let Some(mode) = mode else {
+        let on = is_on();
+        if as_json {
+            println!("{{\"reduce_motion\":{on}}}");
+        } else {
+            println!("reduce_motion={}", if on { "on" } else { "off" });
+        }
+        return Ok(());
+    };
+    match control::request(&format!("reduce-motion {}", mode.as_str())) {
+        Ok(reply) => {
+            let value =
+                parse_json(&reply).map_err(|e| format!("unreadable reduce-motion reply: {e}"))?;
+            if let Some(err) = value.get("error").and_then(JsonValue::as_str) {
+                return Err(err.to_string());
+            }
+            if as_json {
+                println!("{reply}");
+                return Ok(());
+            }
+            let on = value
+                .get("reduce_motion")
+                .and_then(JsonValue::as_bool)
+                .unwrap_or(false);
+            let outputs = match value.get("outputs") {
+                Some(JsonValue::Array(items)) => items.len(),
+                _ => 0,
+            };
+            match (on, value.get("changed").and_then(JsonValue::as_bool)) {
+                (true, Some(false)) => println!("[ok] reduce motion was already on"),
+                (false, Some(false)) => println!("[ok] reduce motion was already off"),
+                (true, _) => {
+                    println!("[ok] reduce motion on: {outputs} output(s) now show a still frame")
+                }
+                (false, _) => println!("[ok] reduce motion off: {outputs} output(s) play again"),
+            }
+        }
+        Err(err) => {
+            let was_on = is_on();
+            let on = mode.apply(was_on);
+            persist(on)?;
+            if as_json {
+                // Same shape as the renderer's reply, with no output to re-resolve.
+                println!(
+                    "{{\"ok\":true,\"reduce_motion\":{on},\"changed\":{},\"outputs\":[]}}",
+                    on != was_on
+                );
+                return Ok(());
+            }
+            println!(
+                "[ok] reduce motion {} (saved; applies when the renderer starts)",
+                if on { "on" } else { "off" }
+            );
+            println!("[info] {err}");
+        }
+    }
+    Ok(())
+}
+
+fn print_reduce_motion_help() {
+    println!("kitsune-rendercore reduce-motion");
+    println!("Usage:");
+    println!("  kitsune-rendercore reduce-motion [on|off|toggle] [--json]");
+    println!();
+    println!("Description:");
+    println!("  While on, every output shows the first frame of its video (the start of");
+    println!("  its trim window) as a still, and its decoder stops. The map is not");
+    println!("  touched: transient overrides and layouts are reduced the same way, and");
+    println!("  everything plays again when it is turned off. It is not a pause: stills");
+    println!("  keep rendering and status shows the output as running.");
+    println!();
+    println!("  The setting is kept in the state dir and survives restarts. Without an");
+    println!("  argument, prints whether it is on.");
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::journal::JOURNAL_SCHEMA_VERSION;
+use crate::json::{JsonValue, parse_json};
+use crate::pause::{MANUAL_FILE, PauseController, PauseTransition, STEAM_GAME, THERMAL};
+use crate::scheduler::FrameScheduler;
+use crate::user_path::cli_base_dir;
+use crate::video_map::{
+    VideoMap, VideoMapEntry, parse_entry_value, parse_video_map_str, resolve_output_video,
+};
+
+/// Re-runs the runtime decision logic (scheduler budget, pause latch, map
+/// resolution) over a journal recorded with `KRC_RECORD` and reports every
+/// point where the replayed decision differs from the recorded one.
+pub fn replay_journal(path: &Path, verbose: bool) -> Result<usize, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read journal {}: {e}", path.display()))?;
+
+    let mut machine = ReplayMachine::default();
+    let mut events = 0usize;
+    for (idx, line) in contents.lines().enumerate() {
+        let line_no = idx + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = parse_json(line)
+            .map_err(|e| format!("journal line {line_no} is not valid JSON: {e}"))?;
+        let version = event.get("v").and_then(JsonValue::as_u64).unwrap_or(0);
+        if version != JOURNAL_SCHEMA_VERSION {
+            return Err(format!(
+                "journal line {line_no} has schema v{version}, this build replays v{JOURNAL_SCHEMA_VERSION}"
+            ));
+        }
+        let kind = event
+            .get("event")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| format!("journal line {line_no} has no event name"))?;
+        let t_ms = event.get("t_ms").and_then(JsonValue::as_u64).unwrap_or(0);
+        if verbose {
+            println!("[replay] line={line_no} t={t_ms}ms event={kind}");
+        }
+        if let Some(divergence) = machine.apply(kind, &event) {
+            println!("[replay] DIVERGENCE line={line_no} t={t_ms}ms event={kind}: {divergence}");
+            machine.divergences += 1;
+        }
+        events += 1;
+    }
+
+    println!(
+        "[replay] events={} outputs={} pause_transitions={} decoder_restarts={} divergences={}",
+        events,
+        machine.outputs.len(),
+        machine.pause_transitions,
+        machine.decoder_restarts,
+        machine.divergences
+    );
+    Ok(machine.divergences)
+}
+
+#[derive(Default)]
+struct ReplayMachine {
+    outputs: BTreeMap<u32, ReplayOutput>,
+    merged_map: VideoMap,
+    default_video: Option<VideoMapEntry>,
+    pause: PauseController,
+    /// A source event arrived since the last resolve; the runtime resolves once
+    /// per tick, so the transition is checked at the next non-source event.
+    pause_dirty: bool,
+    pause_transitions: u64,
+    decoder_restarts: u64,
+    divergences: usize,
+}
+
+#[derive(Default)]
+struct ReplayOutput {
+    name: Option<String>,
+    mode: Option<(u64, u64)>,
+}
+
+impl ReplayMachine {
+    fn apply(&mut self, kind: &str, event: &JsonValue) -> Option<String> {
+        let str_field = |key: &str| event.get(key).and_then(JsonValue::as_str).unwrap_or("");
+        let u64_field = |key: &str| event.get(key).and_then(JsonValue::as_u64).unwrap_or(0);
+        let output_id = u64_field("output_id") as u32;
+
+        if self.pause_dirty && kind != "pause" {
+            self.pause_dirty = false;
+            if let Some(t) = self.pause.resolve() {
+                return Some(format!(
+                    "replayed pause={} ({}) was never recorded",
+                    t.paused, t.reason
+                ));
+            }
+        }
+
+        match kind {
+            "bootstrap" => {
+                let budget = FrameScheduler::new(u64_field("target_fps") as u32).frame_budget();
+                let recorded = u64_field("frame_budget_us");
+                if budget.as_micros() as u64 != recorded {
+                    return Some(format!(
+                        "frame budget recorded={recorded}us replayed={}us",
+                        budget.as_micros()
+                    ));
+                }
+            }
+            "output_added" => {
+                self.outputs.insert(output_id, ReplayOutput::default());
+            }
+            "output_removed" if self.outputs.remove(&output_id).is_none() => {
+                return Some(format!("removal of unknown output id={output_id}"));
+            }
+            "output_name" => {
+                self.outputs.entry(output_id).or_default().name =
+                    Some(str_field("name").to_string());
+            }
+            "output_mode" => {
+                self.outputs.entry(output_id).or_default().mode =
+                    Some((u64_field("width"), u64_field("height")));
+            }
+            "configure" if !self.outputs.contains_key(&output_id) => {
+                return Some(format!("configure for unknown output id={output_id}"));
+            }
+            "map_loaded" => {
+                // Recorded entries are already normalized, so the base never applies.
+                let base = cli_base_dir();
+                self.merged_map = parse_video_map_str(str_field("entries"), &base);
+                let default = str_field("default");
+                self.default_video = if default.is_empty() {
+                    None
+                } else {
+                    match parse_entry_value(default, &base) {
+                        Ok(entry) => Some(entry),
+                        Err(err) => return Some(format!("unreadable default entry: {err}")),
+                    }
+                };
+            }
+            "stream_selected" => {
+                let recorded_name = str_field("output");
+                if let Some(known) = self.outputs.get(&output_id).and_then(|o| o.name.as_deref())
+                    && known != recorded_name
+                {
+                    return Some(format!(
+                        "output id={output_id} resolved as '{recorded_name}' but its name is '{known}'"
+                    ));
+                }
+                // Journals before map_key existed looked outputs up by name.
+                let map_key = match str_field("map_key") {
+                    "" => recorded_name,
+                    key => key,
+                };
+                let replayed =
+                    resolve_output_video(&self.merged_map, self.default_video.as_ref(), map_key)
+                        .map(|entry| entry.to_string())
+                        .unwrap_or_default();
+                let recorded = str_field("video");
+                if replayed != recorded {
+                    return Some(format!(
+                        "output={recorded_name} recorded video='{recorded}' replayed video='{replayed}'"
+                    ));
+                }
+            }
+            "steam_game" | "pause_file" | "thermal" => {
+                let (reason, field) = match kind {
+                    "steam_game" => (STEAM_GAME, "running"),
+                    "pause_file" => (MANUAL_FILE, "present"),
+                    _ => (THERMAL, "active"),
+                };
+                let active = event
+                    .get(field)
+                    .and_then(JsonValue::as_bool)
+                    .unwrap_or(false);
+                self.pause.set(reason, active);
+                self.pause_dirty = true;
+                return None;
+            }
+            "pause" => {
+                let recorded = event
+                    .get("paused")
+                    .and_then(JsonValue::as_bool)
+                    .unwrap_or(false);
+                let recorded_reason = str_field("reason");
+                let recorded_behavior = str_field("behavior");
+                self.pause_transitions += 1;
+                self.pause_dirty = false;
+                return match self.pause.resolve() {
+                    Some(PauseTransition {
+                        paused,
+                        reason,
+                        behavior,
+                    }) if paused == recorded
+                        && reason == recorded_reason
+                        && behavior.as_str() == recorded_behavior =>
+                    {
+                        None
+                    }
+                    Some(t) => Some(format!(
+                        "pause recorded={recorded} ({recorded_reason}, {recorded_behavior}) replayed={} ({}, {})",
+                        t.paused,
+                        t.reason,
+                        t.behavior.as_str()
+                    )),
+                    None => Some(format!(
+                        "pause={recorded} recorded without a detector transition"
+                    )),
+                };
+            }
+            "decoder_restart" => {
+                self.decoder_restarts += 1;
+            }
+            _ => {}
+        }
+        None
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use crate::proc_stat::ProcStat;
+
+/// How often the renderer counts its descriptors and children.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Open descriptors above this warn unless `KRC_FD_WARN` says otherwise. A
+/// healthy renderer holds a few dozen plus one pipe per decoder.
+const DEFAULT_FD_WARN: usize = 512;
+
+/// A zombie child may exist for the instant between its exit and the wait;
+/// one seen on this many checks in a row was never reaped.
+const ZOMBIE_CHECKS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Counts {
+    fds: usize,
+    children: usize,
+    zombies: usize,
+}
+
+/// Periodic self-check of `/proc/self/fd` and the renderer's child
+/// processes, so a decoder that is killed but never waited for, or a pipe
+/// that outlives its decoder, shows up in the log long before the process
+/// runs out of descriptors.
+pub struct ResourceCheck {
+    fd_warn: Option<usize>,
+    debug: bool,
+    baseline: Option<Counts>,
+    fds_warned: bool,
+    zombie_streak: u32,
+}
+
+impl ResourceCheck {
+    /// `KRC_FD_WARN` (0 disables the descriptor warning) and
+    /// `KRC_RESOURCE_DEBUG` (log every check).
+    pub fn from_env() -> Self {
+        let fd_warn = match std::env::var("KRC_FD_WARN") {
+            Ok(raw) => match raw.trim().parse::<usize>() {
+                Ok(0) => None,
+                Ok(limit) => Some(limit),
+                Err(_) => {
+                    eprintln!("[rendercore] ignoring KRC_FD_WARN={raw}: expected a number");
+                    Some(DEFAULT_FD_WARN)
+                }
+            },
+            Err(_) => Some(DEFAULT_FD_WARN),
+        };
+        let debug = std::env::var("KRC_RESOURCE_DEBUG")
+            .ok()
+            .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        Self {
+            fd_warn,
+            debug,
+            baseline: None,
+            fds_warned: false,
+            zombie_streak: 0,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        CHECK_INTERVAL
+    }
+
+    /// Takes one sample; the first one is the baseline later ones are
+    /// compared against.
+    pub fn check(&mut self) {
+        let Some(now) = sample() else {
+            return;
+        };
+        let baseline = *self.baseline.get_or_insert(now);
+        if self.debug {
+            println!(
+                "[rendercore] resources fds={} (baseline {}) children={} (baseline {}) zombies={}",
+                now.fds, baseline.fds, now.children, baseline.children, now.zombies
+            );
+        }
+        if let Some(limit) = self.fd_warn {
+            if now.fds > limit && !self.fds_warned {
+                println!(
+                    "[rendercore] warning: {} open file descriptors (baseline {}, KRC_FD_WARN={limit}); decoder pipes may be leaking",
+                    now.fds, baseline.fds
+                );
+            }
+            self.fds_warned = now.fds > limit;
+        }
+        self.zombie_streak = if now.zombies > 0 {
+            self.zombie_streak + 1
+        } else {
+            0
+        };
+        if self.zombie_streak == ZOMBIE_CHECKS {
+            println!(
+                "[rendercore] warning: {} child process(es) exited but were never reaped",
+                now.zombies
+            );
+        }
+    }
+}
+
+/// Counts from `/proc`; `None` where it is not mounted.
+fn sample() -> Option<Counts> {
+    let fds = std::fs::read_dir("/proc/self/fd").ok()?.count();
+    let own_pid = std::process::id();
+    let mut children = 0;
+    let mut zombies = 0;
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let name = entry.file_name();
+        if !name
+            .to_str()
+            .is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit()))
+        {
+            continue;
+        }
+        let Some(stat) = ProcStat::read(&entry.path()) else {
+            continue;
+        };
+        if stat.ppid != own_pid {
+            continue;
+        }
+        children += 1;
+        if stat.state == 'Z' {
+            zombies += 1;
+        }
+    }
+    Some(Counts {
+        fds,
+        children,
+        zombies,
+    })
+}
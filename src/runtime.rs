@@ -1,11 +1,48 @@
-use std::thread;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
+use crate::aliases::Aliases;
 use crate::backend::{LayerBackend, create_default_backend};
+use crate::color::ColorPipeline;
+use crate::command_queue::{CommandQueue, CommandSender, ITERATION_BUDGET, RuntimeCommand};
 use crate::config::RenderCoreConfig;
-use crate::monitor::MonitorSurfaceSpec;
+use crate::config_file::config_file_path_from_env;
+use crate::control::{ControlServer, control_socket_path_from_env};
+#[cfg(feature = "dbus")]
+use crate::dbus_service::DbusService;
+use crate::decoder_process::{DecoderProcess, ProcessSampler};
+use crate::event_file;
+use crate::events::{EventLog, EventSink};
+use crate::file_access;
+use crate::hooks::{self, HookContext, HookEvent};
+#[cfg(feature = "http-control")]
+use crate::http_control::{HttpConfig, HttpServer};
+use crate::hw_decode::HwDecode;
+#[cfg(feature = "dbus")]
+use crate::instance::instance_id_from_env;
+use crate::instance::{install_hangup_handler, take_hangup};
+use crate::journal::{self, Value};
+use crate::json::escape_json;
+use crate::log_limit;
+use crate::monitor::{CellStatus, MonitorSurfaceSpec};
+use crate::pause::{MANUAL_FILE, PauseController, STEAM_GAME, THERMAL};
+use crate::pause_file::{PauseFileTrigger, create_pause_file, remove_pause_file};
+use crate::priority::log_cgroup_hint;
+use crate::reduce_motion;
+use crate::resource_check::ResourceCheck;
 use crate::scheduler::FrameScheduler;
+use crate::snapshot::{self, Restored, RuntimeSnapshot};
+use crate::startup::{self, Step};
 use crate::steam::SteamGameDetector;
+use crate::thermal::ThermalDetector;
+#[cfg(feature = "update-check")]
+use crate::update_check::{self, PassiveCheck};
+use crate::user_path::{display_path, display_text, normalize_video_path};
+use crate::version;
+use crate::video_map::{
+    EntryOptions, Layout, MAP_CHECK_INTERVAL, VideoMapEntry, map_file_path_from_env,
+    set_monitor_video, split_still,
+};
 
 pub struct RenderRuntime {
     config: RenderCoreConfig,
@@ -13,21 +50,203 @@ pub struct RenderRuntime {
     surfaces: Vec<MonitorSurfaceSpec>,
     scheduler: FrameScheduler,
     steam_detector: SteamGameDetector,
+    thermal: ThermalDetector,
+    pause_file: PauseFileTrigger,
+    pause: PauseController,
+    resources: ResourceCheck,
+    /// Stream lifecycle events from the backend, for hooks, the journal and
+    /// `status --watch`.
+    events: EventLog,
+    started_at: Instant,
+    wakeups: u64,
+    /// Resolved once at bootstrap and reported in status, so the CLI can tell
+    /// when it is looking at a different map file than the renderer.
+    map_file: PathBuf,
+    /// Previous `/proc` samples of the decoders, for CPU use in live status.
+    decoder_processes: ProcessSampler,
+    /// `[aliases]` from the config file, re-read on reload.
+    aliases: Aliases,
+    /// `reduce-motion`: every output shows a still; saved across restarts.
+    reduce_motion: bool,
+    /// `KRC_UPDATE_CHECK`; `None` when off.
+    #[cfg(feature = "update-check")]
+    update_check: Option<PassiveCheck>,
+    /// Running as the service rather than inside another program: SIGHUP,
+    /// systemd notifications and the D-Bus name are only taken then.
+    service: bool,
+    control_socket: bool,
+    /// The HTTP endpoint an embedder asked for; the service reads `[http]`
+    /// from the config file instead.
+    #[cfg(feature = "http-control")]
+    http: Option<HttpConfig>,
+    /// Created early when an embedder asked for a sender before `run`.
+    queue: Option<CommandQueue>,
+}
+
+/// Periodic background work driven by the render loop. Frames are paced
+/// separately by the [`FrameScheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Task {
+    SteamProbe,
+    ThermalProbe,
+    PauseFile,
+    Report,
+    ResourceCheck,
+    QuietHours,
+    Snapshot,
+    /// The backend polls the map and env files; it says when to look again.
+    MapCheck,
+    #[cfg(feature = "update-check")]
+    UpdateCheck,
+}
+
+/// How the render loop ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopExit {
+    /// `quit` over the control socket, or `KRC_MAX_FRAMES`.
+    Done,
+    /// `KRC_QUIET_HOURS` began; the caller tears everything down.
+    QuietHours,
+}
+
+/// How often the loop checks whether quiet hours began.
+const QUIET_HOURS_CHECK: Duration = Duration::from_secs(30);
+
+/// The first report comes early so a short test run still shows one.
+const FIRST_REPORT_AFTER: Duration = Duration::from_secs(5);
+
+/// How early a background task may run so it shares a wakeup with another one
+/// (normally the next frame) instead of waking the process on its own.
+const BACKGROUND_SLACK: Duration = Duration::from_millis(250);
+
+/// How often a frozen renderer still calls the backend, which only redraws
+/// the last frames when the compositor asks for a new buffer.
+const FROZEN_TICK: Duration = Duration::from_millis(250);
+
+struct Timer {
+    task: Task,
+    interval: Duration,
+    slack: Duration,
+    next: Instant,
+}
+
+/// Deadlines of every periodic task, so the loop sleeps exactly until the
+/// earliest one and runs only what is due. Callers pass `now`; the wheel never
+/// reads the clock itself.
+#[derive(Default)]
+struct TimerWheel {
+    timers: Vec<Timer>,
+}
+
+impl TimerWheel {
+    /// Registers `task`, first due at `first`.
+    fn add(&mut self, task: Task, interval: Duration, slack: Duration, first: Instant) {
+        self.timers.push(Timer {
+            task,
+            interval,
+            slack,
+            next: first,
+        });
+    }
+
+    /// Moves `task`'s next run to `at`; the cadence continues from there.
+    fn reschedule(&mut self, task: Task, at: Instant) {
+        for timer in self.timers.iter_mut().filter(|t| t.task == task) {
+            timer.next = at;
+        }
+    }
+
+    /// Drops `task`; it is never due again.
+    fn cancel(&mut self, task: Task) {
+        self.timers.retain(|t| t.task != task);
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.timers.iter().map(|t| t.next).min()
+    }
+
+    /// Returns the tasks due at `now` (within their slack) and schedules their
+    /// next run. Cadence is kept from the deadline, not from `now`, unless the
+    /// task fell a whole interval behind.
+    fn take_due(&mut self, now: Instant) -> Vec<Task> {
+        let mut due = Vec::new();
+        for timer in self.timers.iter_mut() {
+            if timer.next > now + timer.slack {
+                continue;
+            }
+            due.push(timer.task);
+            timer.next += timer.interval;
+            if timer.next <= now {
+                timer.next = now + timer.interval;
+            }
+        }
+        due
+    }
 }
 
 impl RenderRuntime {
     pub fn new(config: RenderCoreConfig) -> Self {
-        let scheduler = FrameScheduler::new(config.target_fps);
+        Self::with_backend(config, create_default_backend())
+    }
+
+    pub fn with_backend(config: RenderCoreConfig, mut backend: Box<dyn LayerBackend>) -> Self {
+        let scheduler = FrameScheduler::from_env(config.target_fps);
+        backend.set_fps_ceiling(scheduler.target_fps());
         Self {
             config,
-            backend: create_default_backend(),
+            backend,
             surfaces: Vec::new(),
             scheduler,
             steam_detector: SteamGameDetector::from_env(),
+            thermal: ThermalDetector::from_env(),
+            pause_file: PauseFileTrigger::from_env(),
+            pause: PauseController::default(),
+            resources: ResourceCheck::from_env(),
+            events: EventLog::attach(),
+            started_at: Instant::now(),
+            wakeups: 0,
+            map_file: PathBuf::new(),
+            decoder_processes: ProcessSampler::new(),
+            aliases: Aliases::default(),
+            reduce_motion: false,
+            #[cfg(feature = "update-check")]
+            update_check: None,
+            service: true,
+            control_socket: true,
+            #[cfg(feature = "http-control")]
+            http: None,
+            queue: None,
         }
     }
 
+    /// Runs inside another program: signals, systemd and D-Bus are left to
+    /// the host, and the control socket is only served when asked for.
+    pub fn set_embedded(&mut self, control_socket: bool) {
+        self.service = false;
+        self.control_socket = control_socket;
+    }
+
+    /// Serves the HTTP control endpoint with `config` (embedded only).
+    #[cfg(feature = "http-control")]
+    pub fn set_http(&mut self, config: HttpConfig) {
+        self.http = Some(config);
+    }
+
+    /// Hands every stream event to `sink` as well, on the render thread.
+    pub fn set_event_sink(&mut self, sink: EventSink) {
+        self.events.set_sink(sink);
+    }
+
+    /// A producer end of the command queue, usable from any thread before
+    /// or during `run`.
+    pub fn command_sender(&mut self) -> CommandSender {
+        self.queue.get_or_insert_with(CommandQueue::new).sender()
+    }
+
     pub fn bootstrap(&mut self) -> Result<(), String> {
+        println!("[rendercore] kitsune-rendercore {}", version::summary());
+        let bootstrap = startup::start(self.service);
+        startup::begin(Step::Config)?;
         println!(
             "[rendercore] bootstrap: target_fps={} vsync={} pause_on_maximized={} max_frames={:?}",
             self.config.target_fps,
@@ -35,6 +254,56 @@ impl RenderRuntime {
             self.config.pause_on_maximized,
             self.config.max_frames
         );
+        self.map_file = map_file_path_from_env()?;
+        println!(
+            "[rendercore] map file: {}",
+            display_path(&self.map_file.to_string_lossy())
+        );
+        let config_file = config_file_path_from_env().ok();
+        for (what, path) in [
+            ("map file", Some(self.map_file.as_path())),
+            ("config file", config_file.as_deref()),
+        ] {
+            if let Some(warning) = path.and_then(|path| file_access::startup_warning(path, what)) {
+                eprintln!("[rendercore] warning: {warning}");
+            }
+        }
+        if std::env::var_os("HOME").is_none() {
+            println!(
+                "[rendercore] HOME is not set; paths resolved from XDG_CONFIG_HOME or the passwd entry"
+            );
+        }
+        self.aliases = Aliases::load_from_env();
+        if !self.aliases.is_empty() {
+            println!(
+                "[rendercore] monitor aliases: {}",
+                self.aliases
+                    .entries()
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+        }
+        self.backend.set_aliases(&self.aliases);
+        self.reduce_motion = reduce_motion::is_on();
+        if self.reduce_motion {
+            println!("[rendercore] reduce motion is on: outputs show a still of their first frame");
+        }
+        self.backend.set_reduce_motion(self.reduce_motion);
+        if self.service {
+            self.restore_snapshot();
+        }
+        #[cfg(feature = "update-check")]
+        {
+            self.update_check = PassiveCheck::from_env();
+        }
+        #[cfg(not(feature = "update-check"))]
+        if std::env::var("KRC_UPDATE_CHECK").is_ok_and(|raw| !raw.trim().is_empty()) {
+            eprintln!(
+                "[rendercore] warning: ignoring KRC_UPDATE_CHECK: built without the update-check feature"
+            );
+        }
         self.backend.bootstrap()?;
         let monitors = self.backend.discover_monitors()?;
         self.surfaces = self.backend.build_surfaces(&monitors)?;
@@ -43,64 +312,1172 @@ impl RenderRuntime {
             self.backend.name(),
             monitors.len()
         );
+        journal::record(
+            "bootstrap",
+            &[
+                ("backend", Value::Str(self.backend.name())),
+                ("target_fps", Value::U64(self.config.target_fps as u64)),
+                (
+                    "frame_budget_us",
+                    Value::U64(self.scheduler.frame_budget().as_micros() as u64),
+                ),
+            ],
+        );
         for surface in &self.surfaces {
+            journal::record(
+                "surface",
+                &[
+                    ("monitor", Value::Str(&surface.monitor.name)),
+                    ("width", Value::U64(surface.monitor.width as u64)),
+                    ("height", Value::U64(surface.monitor.height as u64)),
+                    ("refresh_hz", Value::U64(surface.monitor.refresh_hz as u64)),
+                ],
+            );
             println!(
-                "[rendercore] surface monitor={} {}x{}@{} layer={:?}",
+                "[rendercore] surface monitor={} {}x{}@{} transform={:?} layer={:?}",
                 surface.monitor.name,
                 surface.monitor.width,
                 surface.monitor.height,
                 surface.monitor.refresh_hz,
+                surface.monitor.transform,
                 surface.layer
             );
         }
+        bootstrap.finish();
         Ok(())
     }
 
-    pub fn run(&mut self) -> Result<(), String> {
+    pub fn run(&mut self) -> Result<LoopExit, String> {
         println!(
-            "[rendercore] scheduler frame_budget={:?}",
-            self.scheduler.frame_budget()
+            "[rendercore] scheduler frame_budget={:?} precise_timing={}",
+            self.scheduler.frame_budget(),
+            self.scheduler.is_precise()
         );
         if self.steam_detector.is_enabled() {
             println!("[rendercore] pause-on-steam-game enabled");
         }
+        if self.thermal.is_enabled() {
+            println!(
+                "[rendercore] thermal pause enabled: temp>={} cpu_pressure>={}",
+                self.thermal
+                    .pause_c()
+                    .map_or_else(|| "off".to_string(), |c| format!("{c}C")),
+                self.thermal
+                    .pressure_pause()
+                    .map_or_else(|| "off".to_string(), |p| p.to_string())
+            );
+        }
+        log_cgroup_hint();
+        println!(
+            "[rendercore] pause file trigger: {}",
+            self.pause_file.path().display()
+        );
+
+        if self.service {
+            install_hangup_handler();
+        }
+        let mut control = match self
+            .control_socket
+            .then(|| ControlServer::bind(&control_socket_path_from_env()))
+        {
+            Some(Ok(server)) => {
+                println!("[rendercore] control socket: {}", server.path().display());
+                Some(server)
+            }
+            Some(Err(err)) => {
+                eprintln!("[rendercore] control socket disabled: {err}");
+                None
+            }
+            None => None,
+        };
+        #[cfg(feature = "http-control")]
+        let mut http = match if self.service {
+            HttpConfig::load_from_env()
+        } else {
+            Ok(self.http.take())
+        }
+        .and_then(|config| config.map(HttpServer::bind).transpose())
+        {
+            Ok(Some(server)) => {
+                println!("[rendercore] http control: http://{}", server.addr());
+                Some(server)
+            }
+            Ok(None) => None,
+            Err(err) => {
+                eprintln!("[rendercore] http control disabled: {err}");
+                None
+            }
+        };
+        // Declared after the servers so it is dropped first: clients still
+        // waiting on a queued command are told the renderer is shutting down
+        // instead of holding up the server's exit.
+        let mut queue = self.queue.take().unwrap_or_else(CommandQueue::new);
+        if let Some(Err(err)) = control.as_mut().map(|server| server.serve(queue.sender())) {
+            eprintln!("[rendercore] control socket disabled: {err}");
+            drop(control.take());
+        }
+        #[cfg(feature = "http-control")]
+        if let Some(Err(err)) = http.as_mut().map(|server| server.serve(queue.sender())) {
+            eprintln!("[rendercore] http control disabled: {err}");
+            drop(http.take());
+        }
+        #[cfg(feature = "dbus")]
+        let dbus = match self
+            .service
+            .then(|| DbusService::start(&instance_id_from_env(), queue.sender()))
+        {
+            Some(Ok(service)) => {
+                println!("[rendercore] dbus service: {}", service.name());
+                Some(service)
+            }
+            Some(Err(err)) => {
+                eprintln!("[rendercore] dbus service disabled: {err}");
+                None
+            }
+            None => None,
+        };
+
+        let mut timers = TimerWheel::default();
+        let now = Instant::now();
+        self.scheduler.reanchor(now);
+        if self.steam_detector.is_enabled() {
+            timers.add(
+                Task::SteamProbe,
+                self.steam_detector.poll_interval(),
+                BACKGROUND_SLACK,
+                now,
+            );
+        }
+        if self.thermal.is_enabled() {
+            timers.add(
+                Task::ThermalProbe,
+                self.thermal.poll_interval(),
+                BACKGROUND_SLACK,
+                now,
+            );
+        }
+        timers.add(
+            Task::PauseFile,
+            self.pause_file.check_interval(),
+            BACKGROUND_SLACK,
+            now,
+        );
+        if let Some(interval) = self.config.report_interval {
+            timers.add(
+                Task::Report,
+                interval,
+                BACKGROUND_SLACK,
+                now + FIRST_REPORT_AFTER.min(interval),
+            );
+        }
+        timers.add(
+            Task::ResourceCheck,
+            self.resources.interval(),
+            BACKGROUND_SLACK,
+            now,
+        );
+        #[cfg(feature = "update-check")]
+        if self.update_check.is_some() {
+            timers.add(
+                Task::UpdateCheck,
+                update_check::TICK,
+                BACKGROUND_SLACK,
+                now + update_check::FIRST_TICK_AFTER,
+            );
+        }
+        if let Some(quiet) = self.config.quiet_hours {
+            println!("[rendercore] quiet hours: {quiet}, the renderer stops entirely");
+            timers.add(Task::QuietHours, QUIET_HOURS_CHECK, BACKGROUND_SLACK, now);
+        }
+        // No slack: a change waiting to settle must not be looked at early.
+        timers.add(
+            Task::MapCheck,
+            MAP_CHECK_INTERVAL,
+            Duration::ZERO,
+            now + MAP_CHECK_INTERVAL,
+        );
+        if self.service {
+            timers.add(
+                Task::Snapshot,
+                snapshot::SAVE_INTERVAL,
+                BACKGROUND_SLACK,
+                now + snapshot::SAVE_INTERVAL,
+            );
+        }
+        let mut exit = LoopExit::Done;
 
         let mut frame: u64 = 0;
-        let mut paused_for_steam = false;
+        // Start of the current global pause, for `paused_ms` in the event log.
+        let mut paused_since = None::<Instant>;
+        // Next backend call while frozen (see `FROZEN_TICK`).
+        let mut frozen_tick = Instant::now();
         loop {
-            if let Some(max) = self.config.max_frames {
-                if frame >= max {
-                    println!("[rendercore] reached max_frames={max}, exiting loop");
-                    break;
-                }
+            self.wakeups += 1;
+            self.events.drain();
+            log_limit::flush(false);
+            if let Some(max) = self.config.max_frames
+                && frame >= max
+            {
+                println!("[rendercore] reached max_frames={max}, exiting loop");
+                break;
             }
 
-            let game_running = self.steam_detector.steam_game_running();
-            if game_running {
-                if !paused_for_steam {
-                    paused_for_steam = true;
-                    println!("[rendercore] steam game detected -> pausing wallpaper render");
+            let due = timers.take_due(Instant::now());
+            if due.contains(&Task::QuietHours)
+                && let Some(quiet) = self.config.quiet_hours
+                && quiet.is_active_now()
+            {
+                println!("[rendercore] quiet hours {quiet} began, exiting loop");
+                journal::record("quiet_hours", &[("window", Value::Str(&quiet.to_string()))]);
+                exit = LoopExit::QuietHours;
+                break;
+            }
+            for task in &due {
+                match task {
+                    Task::SteamProbe => self.pause.set(STEAM_GAME, self.steam_detector.probe()),
+                    Task::ThermalProbe => {
+                        let hot = self.thermal.probe(Instant::now());
+                        self.pause.set(THERMAL, hot);
+                    }
+                    Task::PauseFile => self.pause.set(MANUAL_FILE, self.pause_file.check()),
+                    Task::Report => self.report(frame),
+                    Task::ResourceCheck => self.resources.check(),
+                    Task::QuietHours => {}
+                    Task::Snapshot => self.save_snapshot_quietly(),
+                    Task::MapCheck => match self.backend.check_video_map() {
+                        Some(after) => timers.reschedule(Task::MapCheck, Instant::now() + after),
+                        None => timers.cancel(Task::MapCheck),
+                    },
+                    #[cfg(feature = "update-check")]
+                    Task::UpdateCheck => {
+                        if let Some(check) = &mut self.update_check {
+                            check.tick();
+                        }
+                    }
                 }
-                thread::sleep(Duration::from_millis(500));
-                continue;
             }
-            if paused_for_steam {
-                paused_for_steam = false;
-                println!("[rendercore] steam game closed -> resuming wallpaper render");
+            let (quit, backlog) = self.apply_commands(&mut queue, frame);
+            if quit {
+                println!("[rendercore] quit requested, exiting loop");
+                break;
             }
+            let was_paused = self.pause.decision().is_paused();
+            if let Some(transition) = self.pause.resolve() {
+                // Hooks see edges only: a reason change while paused is not a new pause.
+                let event = match (was_paused, transition.paused) {
+                    (false, true) => Some(HookEvent::Pause),
+                    (true, false) => Some(HookEvent::Resume),
+                    _ => None,
+                };
+                match event {
+                    Some(HookEvent::Pause) => {
+                        paused_since = Some(Instant::now());
+                        event_file::record(
+                            "pause",
+                            None,
+                            &[("reason", Value::Str(transition.reason))],
+                        );
+                    }
+                    Some(HookEvent::Resume) => {
+                        let paused_ms = paused_since
+                            .take()
+                            .map_or(0, |since| since.elapsed().as_millis() as u64);
+                        event_file::record(
+                            "resume",
+                            None,
+                            &[
+                                ("reason", Value::Str(transition.reason)),
+                                ("paused_ms", Value::U64(paused_ms)),
+                            ],
+                        );
+                    }
+                    _ => {}
+                }
+                if let Some(event) = event {
+                    hooks::fire(
+                        event,
+                        HookContext {
+                            reason: Some(transition.reason),
+                            ..HookContext::default()
+                        },
+                    );
+                }
+            }
+            let decision = self.pause.decision();
+            let paused = decision.is_paused();
+            let frozen = decision.freezes();
+            #[cfg(feature = "dbus")]
+            if let Some(dbus) = &dbus {
+                dbus.set_paused(paused);
+            }
+            let now = Instant::now();
+            self.scheduler.set_paused(paused, now);
+            self.backend.set_frozen(frozen);
 
-            let frame_start = Instant::now();
-            self.backend.render_frame(&self.surfaces)?;
-            if frame % 120 == 0 {
-                println!("[rendercore] frame={frame}");
+            if self.scheduler.is_due(now) {
+                self.scheduler.begin_frame(now);
+                self.backend.render_frame(&self.surfaces)?;
+                frame += 1;
+            } else if frozen && frozen_tick <= now {
+                // Frozen outputs keep their last frame on screen; the backend
+                // redraws it for a configure or a lost buffer, decodes nothing.
+                self.backend.render_frame(&self.surfaces)?;
+                frozen_tick = now + FROZEN_TICK;
             }
-            frame += 1;
 
-            let spent = frame_start.elapsed();
-            if spent < self.scheduler.frame_budget() {
-                thread::sleep(self.scheduler.frame_budget() - spent);
+            let frame_deadline = match (paused, frozen) {
+                (false, _) => Some(self.scheduler.next_deadline()),
+                (true, true) => Some(frozen_tick),
+                (true, false) => None,
+            };
+            let wake = match (timers.next_deadline(), frame_deadline) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            match wake {
+                // Commands left over from a flood; the budget already ran out.
+                _ if backlog => {}
+                // Paused: a command is the only reason to wake before the next
+                // timer or frozen redraw.
+                Some(deadline) if paused => queue.wait_until(deadline),
+                Some(deadline) => self.scheduler.sleep_until(deadline),
+                None => {}
             }
         }
-        Ok(())
+        if self.service {
+            self.save_snapshot_quietly();
+        }
+        self.backend.shutdown();
+        log_limit::flush(true);
+        Ok(exit)
+    }
+
+    /// Applies the snapshot a restart left, when it is still valid: frozen
+    /// outputs here, overrides and playback positions in the backend.
+    fn restore_snapshot(&mut self) {
+        let Some(saved) = snapshot::load_for_restore(&self.map_file) else {
+            return;
+        };
+        for monitor in &saved.paused_outputs {
+            self.pause.set_output(monitor, true);
+        }
+        if !saved.paused_outputs.is_empty() {
+            self.backend.set_paused_outputs(self.pause.paused_outputs());
+        }
+        println!(
+            "[rendercore] restored runtime state saved {}s ago: paused_outputs={} transient={} positions={}",
+            snapshot::now_ms().saturating_sub(saved.saved_at_ms) / 1000,
+            saved.paused_outputs.len(),
+            saved.transient.len(),
+            saved.positions.len()
+        );
+        journal::record(
+            "state_restored",
+            &[
+                (
+                    "paused_outputs",
+                    Value::U64(saved.paused_outputs.len() as u64),
+                ),
+                ("transient", Value::U64(saved.transient.len() as u64)),
+                ("positions", Value::U64(saved.positions.len() as u64)),
+            ],
+        );
+        self.backend.restore_state(Restored {
+            transient: saved.transient,
+            positions: saved.positions,
+        });
+    }
+
+    fn save_snapshot(&self) -> Result<(RuntimeSnapshot, PathBuf), String> {
+        let saved = RuntimeSnapshot {
+            saved_at_ms: snapshot::now_ms(),
+            map_hash: snapshot::map_hash(&self.map_file),
+            paused_outputs: self.pause.paused_outputs().to_vec(),
+            transient: self.backend.transient_videos(),
+            positions: self.backend.playback_positions(),
+        };
+        let path = saved.save()?;
+        Ok((saved, path))
+    }
+
+    /// The periodic save, and the ones after state changes: a failure is
+    /// logged (rate-limited), not reported to anyone.
+    fn save_snapshot_quietly(&self) {
+        if let Err(err) = self.save_snapshot() {
+            log_limit::eprint_limited("snapshot", &err, || {
+                format!("[rendercore] warning: runtime snapshot not saved: {err}")
+            });
+        }
+    }
+
+    /// `snapshot`: saves the state now, ahead of a restart.
+    fn control_snapshot(&self) -> String {
+        if !self.service {
+            return control_error("an embedded renderer keeps no runtime snapshot");
+        }
+        match self.save_snapshot() {
+            Ok((saved, path)) => {
+                println!(
+                    "[rendercore] runtime snapshot saved over control: {}",
+                    path.display()
+                );
+                format!(
+                    "{{\"ok\":true,\"path\":\"{}\",\"paused_outputs\":{},\"transient\":{},\"positions\":{}}}",
+                    escape_json(&path.to_string_lossy()),
+                    saved.paused_outputs.len(),
+                    saved.transient.len(),
+                    saved.positions.len()
+                )
+            }
+            Err(err) => control_error(&err),
+        }
+    }
+
+    /// The periodic one-line summary (`KRC_REPORT_INTERVAL_SEC`): loop rate and
+    /// lateness, pause state, then the backend's own counters.
+    fn report(&self, frame: u64) {
+        let rate = self.scheduler.frame_rate(Instant::now());
+        let mut line = format!(
+            "[rendercore] report uptime={}s frame={frame} fps={:.2}/{} late_avg={:.2}ms late_max={:.2}ms wakeups={} paused={}",
+            self.started_at.elapsed().as_secs(),
+            rate.achieved_fps,
+            self.scheduler.target_fps(),
+            rate.late_avg.as_secs_f64() * 1e3,
+            rate.late_max.as_secs_f64() * 1e3,
+            self.wakeups,
+            self.pause.decision().is_paused()
+        );
+        if let Some(summary) = self.backend.report_summary() {
+            line.push(' ');
+            line.push_str(&summary);
+        }
+        println!("{line}");
+    }
+
+    /// Applies one queued command and returns its JSON reply. `Quit` is
+    /// handled by the loop itself.
+    fn handle_command(&mut self, command: RuntimeCommand, frame: u64) -> String {
+        match command {
+            RuntimeCommand::Quit => "{\"ok\":true}".to_string(),
+            RuntimeCommand::Pause { reason } => self.control_pause(&reason),
+            RuntimeCommand::Resume => self.control_resume(),
+            RuntimeCommand::ReduceMotion { mode } => self.control_reduce_motion(mode),
+            RuntimeCommand::PauseOutput { monitor, paused } => {
+                self.control_pause_output(&monitor, paused)
+            }
+            // `events` alone only reports where the log is, for a new watcher.
+            RuntimeCommand::Events { after } => self.events.since_json(after.unwrap_or(u64::MAX)),
+            // A backend without a video map has no overrides to clear.
+            RuntimeCommand::ClearTransient => self
+                .backend
+                .clear_transient_videos()
+                .unwrap_or_else(|| "{\"ok\":true,\"cleared\":0,\"outputs\":[]}".to_string()),
+            RuntimeCommand::SetVideo {
+                transient,
+                monitor,
+                video,
+            } => self
+                .control_set_video(transient, &monitor, &video)
+                .unwrap_or_else(|err| control_error(&err)),
+            RuntimeCommand::Reload { hard } => {
+                self.reload_aliases();
+                self.backend.reload(hard).unwrap_or_else(|| {
+                    format!(
+                        "{{\"error\":\"backend {} has no video map to reload\"}}",
+                        self.backend.name()
+                    )
+                })
+            }
+            RuntimeCommand::Status => self.live_status_json(frame),
+            RuntimeCommand::Ping => format!("{{\"ok\":true,\"pid\":{}}}", std::process::id()),
+            RuntimeCommand::Profile => self.backend.profile_report().unwrap_or_else(|| {
+                format!(
+                    "{{\"error\":\"backend {} does not support profiling\"}}",
+                    self.backend.name()
+                )
+            }),
+            RuntimeCommand::Snapshot => self.control_snapshot(),
+        }
+    }
+
+    /// Applies queued commands, up to [`ITERATION_BUDGET`], a pending SIGHUP
+    /// first. Returns whether a `quit` was among them and whether the budget
+    /// ran out with commands possibly left.
+    fn apply_commands(&mut self, queue: &mut CommandQueue, frame: u64) -> (bool, bool) {
+        if take_hangup() {
+            println!("[rendercore] SIGHUP received, reloading map and env file");
+            self.reload_aliases();
+            if let Some(reply) = self.backend.reload(false) {
+                println!("[rendercore] reload: {reply}");
+            }
+        }
+        for _ in 0..ITERATION_BUDGET {
+            let Some(envelope) = queue.next() else {
+                return (false, false);
+            };
+            if envelope.command == RuntimeCommand::Quit {
+                envelope.answer("{\"ok\":true}".to_string());
+                return (true, false);
+            }
+            let reply = self.handle_command(envelope.command.clone(), frame);
+            // Overrides and frozen outputs are saved as they change; positions
+            // can wait for the periodic save.
+            if self.service
+                && matches!(
+                    envelope.command,
+                    RuntimeCommand::PauseOutput { .. }
+                        | RuntimeCommand::SetVideo { .. }
+                        | RuntimeCommand::ClearTransient
+                )
+            {
+                self.save_snapshot_quietly();
+            }
+            envelope.answer(reply);
+        }
+        (false, true)
+    }
+
+    /// Re-reads `[aliases]` ahead of a map reload, so map keys naming a new
+    /// alias resolve in the same pass.
+    fn reload_aliases(&mut self) {
+        let aliases = Aliases::load_from_env();
+        if aliases == self.aliases {
+            return;
+        }
+        println!(
+            "[rendercore] monitor aliases changed: {}",
+            match aliases.entries().keys().cloned().collect::<Vec<_>>() {
+                names if names.is_empty() => "none".to_string(),
+                names => names.join(","),
+            }
+        );
+        self.aliases = aliases;
+        self.backend.set_aliases(&self.aliases);
+    }
+
+    /// `pause [reason]`: creates the pause file, exactly like the `pause`
+    /// subcommand, and applies it now instead of on the next file check.
+    fn control_pause(&mut self, reason: &str) -> String {
+        if let Err(err) = create_pause_file(self.pause_file.path()) {
+            return control_error(&err);
+        }
+        let reason = if reason.is_empty() { "control" } else { reason };
+        println!(
+            "[rendercore] pause requested over control: {}",
+            display_text(reason)
+        );
+        journal::record("control_pause", &[("reason", Value::Str(reason))]);
+        self.pause.set(MANUAL_FILE, self.pause_file.check());
+        "{\"ok\":true}".to_string()
+    }
+
+    /// `resume`: removes the pause file. Other pause reasons (a Steam game)
+    /// still apply.
+    fn control_resume(&mut self) -> String {
+        let removed = match remove_pause_file(self.pause_file.path()) {
+            Ok(removed) => removed,
+            Err(err) => return control_error(&err),
+        };
+        if removed {
+            println!("[rendercore] resume requested over control");
+        }
+        self.pause.set(MANUAL_FILE, self.pause_file.check());
+        format!("{{\"ok\":true,\"removed\":{removed}}}")
+    }
+
+    /// `reduce-motion on|off|toggle`: saves the flag, then has the backend
+    /// re-resolve every output. Not a pause: stills keep rendering.
+    fn control_reduce_motion(&mut self, mode: reduce_motion::Mode) -> String {
+        let on = mode.apply(self.reduce_motion);
+        if let Err(err) = reduce_motion::persist(on) {
+            return control_error(&err);
+        }
+        let changed = on != self.reduce_motion;
+        self.reduce_motion = on;
+        if changed {
+            println!(
+                "[rendercore] reduce motion {} over control",
+                if on { "on" } else { "off" }
+            );
+            journal::record("reduce_motion", &[("on", Value::Bool(on))]);
+        }
+        self.backend.set_reduce_motion(on).unwrap_or_else(|| {
+            format!("{{\"ok\":true,\"reduce_motion\":{on},\"changed\":{changed},\"outputs\":[]}}")
+        })
+    }
+
+    /// `pause-output <monitor>` / `resume-output <monitor>`: freezes one output
+    /// on its last frame, or lets it run again. Transient, and independent of
+    /// the global pause: `resume` leaves it frozen.
+    fn control_pause_output(&mut self, monitor: &str, paused: bool) -> String {
+        if monitor.is_empty() {
+            let command = if paused {
+                "pause-output"
+            } else {
+                "resume-output"
+            };
+            return control_error(&format!("usage: {command} <monitor>"));
+        }
+        let statuses = self.backend.output_status();
+        let known = statuses
+            .iter()
+            .map(|out| out.name.clone())
+            .collect::<Vec<_>>();
+        // Outputs are frozen by name, so an alias has to name exactly one.
+        let monitor = if self.aliases.is_alias(monitor) {
+            let matched = statuses
+                .iter()
+                .filter(|out| {
+                    self.aliases
+                        .matches(monitor, Some(&out.name), out.description.as_deref())
+                })
+                .map(|out| out.name.as_str())
+                .collect::<Vec<_>>();
+            match matched[..] {
+                [name] => name.to_string(),
+                [] => {
+                    return control_error(&format!(
+                        "alias '{monitor}' matches no output (outputs: {})",
+                        known.join(", ")
+                    ));
+                }
+                _ => {
+                    return control_error(&format!(
+                        "alias '{monitor}' matches {} outputs: {}",
+                        matched.len(),
+                        matched.join(", ")
+                    ));
+                }
+            }
+        } else {
+            monitor.to_string()
+        };
+        let monitor = monitor.as_str();
+        // Resuming an output that went away still clears it.
+        if paused && !known.iter().any(|name| name == monitor) {
+            return control_error(&format!(
+                "no output named '{monitor}' (outputs: {})",
+                known.join(", ")
+            ));
+        }
+        let changed = self.pause.set_output(monitor, paused);
+        if changed {
+            self.backend.set_paused_outputs(self.pause.paused_outputs());
+        }
+        format!(
+            "{{\"ok\":true,\"monitor\":\"{}\",\"paused\":{paused},\"changed\":{changed}}}",
+            escape_json(monitor)
+        )
+    }
+
+    /// `set-video <persist|transient> <monitor>\t<path>`: `persist` writes the
+    /// map file like the `set-video` subcommand and drops any transient
+    /// override for the monitor; `transient` only changes the running renderer,
+    /// and an empty path there drops the override. Paths must be absolute,
+    /// since the caller's working directory is unknown here.
+    fn control_set_video(
+        &mut self,
+        transient: bool,
+        monitor: &str,
+        raw_video: &str,
+    ) -> Result<String, String> {
+        if monitor.is_empty() {
+            return Err("monitor is empty".to_string());
+        }
+        let entry = if raw_video.trim().is_empty() {
+            None
+        } else if let Some(layout) = raw_video.trim().strip_prefix("layout=") {
+            let layout = Layout::parse(layout, Path::new(""))?;
+            if let Some(video) = layout
+                .cells
+                .iter()
+                .flatten()
+                .find(|video| !Path::new(video).is_absolute())
+            {
+                return Err(format!("video path '{video}' must be absolute"));
+            }
+            Some(VideoMapEntry::from_layout(layout, EntryOptions::default()))
+        } else {
+            let (raw_video, still) = split_still(raw_video.trim())?;
+            let video = normalize_video_path(raw_video, Path::new(""))?;
+            if !Path::new(&video).is_absolute() {
+                return Err(format!("video path '{video}' must be absolute"));
+            }
+            Some(VideoMapEntry {
+                video,
+                options: EntryOptions {
+                    still,
+                    ..EntryOptions::default()
+                },
+                layout: None,
+            })
+        };
+        if transient {
+            let backend = self.backend.name();
+            return self
+                .backend
+                .set_transient_video(monitor, entry)
+                .ok_or_else(|| format!("backend {backend} has no video map to change"));
+        }
+        let entry =
+            entry.ok_or("video path is empty (only a transient override can be dropped)")?;
+        set_monitor_video(&self.map_file, monitor, &entry)?;
+        println!(
+            "[rendercore] map entry set over control: {} -> {}",
+            display_text(monitor),
+            entry.display_label()
+        );
+        Ok(self
+            .backend
+            .set_transient_video(monitor, None)
+            .unwrap_or_else(|| "{\"ok\":true,\"outputs\":[]}".to_string()))
+    }
+
+    /// Compact JSON consumed by `kitsune-rendercore status`.
+    fn live_status_json(&mut self, frame: u64) -> String {
+        let since_start = |at: Instant| at.saturating_duration_since(self.started_at).as_millis();
+        let statuses = self.backend.output_status();
+        let now = Instant::now();
+        let pids = statuses
+            .iter()
+            .filter_map(|out| out.decoder_pid)
+            .collect::<Vec<_>>();
+        self.decoder_processes.retain(&pids);
+        let processes = statuses
+            .iter()
+            .map(|out| {
+                out.decoder_pid
+                    .and_then(|pid| self.decoder_processes.sample(pid, now))
+            })
+            .collect::<Vec<_>>();
+        let outputs = statuses
+            .iter()
+            .zip(&processes)
+            .map(|(out, process)| {
+                let first_frame_ms = out
+                    .first_presented_at
+                    .map(|at| since_start(at).to_string())
+                    .unwrap_or_else(|| "null".to_string());
+                let (last_error, last_error_age_ms) = match &out.last_error {
+                    Some((at, err)) => (
+                        format!("\"{}\"", escape_json(err)),
+                        at.elapsed().as_millis().to_string(),
+                    ),
+                    None => ("null".to_string(), "null".to_string()),
+                };
+                let pause_reason = self.pause.output_reason(&out.name);
+                let callback_latency = out.callback_latency.map_or_else(
+                    || "null".to_string(),
+                    |stats| {
+                        let ms = |d: Option<Duration>| {
+                            d.map_or_else(
+                                || "null".to_string(),
+                                |d| format!("{:.2}", d.as_secs_f64() * 1e3),
+                            )
+                        };
+                        format!(
+                            "{{\"samples\":{},\"p50_ms\":{},\"p99_ms\":{},\"slow\":{},\"stalled\":{}}}",
+                            stats.samples,
+                            ms(stats.p50),
+                            ms(stats.p99),
+                            stats.slow,
+                            stats.stalled
+                        )
+                    },
+                );
+                let frame_arrival = out.frame_arrival.map_or_else(
+                    || "null".to_string(),
+                    |stats| {
+                        format!(
+                            "{{\"samples\":{},\"p95_ms\":{},\"queued_bytes\":{},\"capacity_bytes\":{},\"avg_fill\":{:.3},\"starved_decode\":{},\"starved_io\":{}}}",
+                            stats.samples,
+                            stats.p95.map_or_else(
+                                || "null".to_string(),
+                                |d| format!("{:.2}", d.as_secs_f64() * 1e3)
+                            ),
+                            stats.queued_bytes,
+                            stats.capacity_bytes,
+                            stats.avg_fill,
+                            stats.starved_decode,
+                            stats.starved_io
+                        )
+                    },
+                );
+                let damage = out.damage.map_or_else(
+                    || "null".to_string(),
+                    |stats| {
+                        format!(
+                            "{{\"presents\":{},\"skipped\":{},\"avg_percent\":{:.1}}}",
+                            stats.presents, stats.skipped, stats.avg_percent
+                        )
+                    },
+                );
+                let aliases = self
+                    .aliases
+                    .matching(Some(&out.name), out.description.as_deref())
+                    .iter()
+                    .map(|alias| format!("\"{}\"", escape_json(alias)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!(
+                    "{{\"name\":\"{}\",\"description\":\"{}\",\"aliases\":[{}],\"stream\":\"{}\",\"source\":\"{}\",\"first_frame_ms\":{},\"presented_frames\":{},\"frame_callback_pending\":{},\"last_error\":{},\"last_error_age_ms\":{},\"last_error_repeats\":{},\"degraded\":\"{}\",\"source_size\":\"{}\",\"quality_preset\":\"{}\",\"quality_level\":{},\"quality\":\"{}\",\"decode_nice\":{},\"decode\":\"{}\",\"decode_detail\":\"{}\",\"effect\":\"{}\",\"overlay\":\"{}\",\"trim\":\"{}\",\"crop\":\"{}\",\"filter\":\"{}\",\"fit\":\"{}\",\"decoder_shared_with\":\"{}\",\"buffer_scale\":{},\"buffer_transform\":\"{}\",\"clamped\":{},\"color\":{},\"input_region\":{},\"paused\":{},\"pause_reason\":\"{}\",\"callback_latency\":{},\"frame_arrival\":{},\"damage\":{},\"flash_guard\":\"{}\",\"decoder_process\":{},\"mode\":\"{}\",\"suspect_mode\":\"{}\",\"layout\":\"{}\",\"cells\":[{}]}}",
+                    escape_json(&out.name),
+                    escape_json(out.description.as_deref().unwrap_or("")),
+                    aliases,
+                    out.stream_kind,
+                    escape_json(out.source.as_deref().unwrap_or("")),
+                    first_frame_ms,
+                    out.presented_frames,
+                    out.frame_callback_pending,
+                    last_error,
+                    last_error_age_ms,
+                    out.last_error_repeats,
+                    out.degraded.unwrap_or("none"),
+                    out.source_size
+                        .map(|(w, h)| format!("{w}x{h}"))
+                        .unwrap_or_default(),
+                    out.quality_preset.unwrap_or(""),
+                    out.quality_level,
+                    escape_json(&out.quality),
+                    out.decode_nice
+                        .map_or_else(|| "null".to_string(), |nice| nice.to_string()),
+                    out.hw_decode.as_ref().map_or("", HwDecode::label),
+                    escape_json(out.hw_decode.as_ref().map_or("", HwDecode::detail)),
+                    escape_json(out.effect.as_deref().unwrap_or("")),
+                    escape_json(out.overlay.as_deref().unwrap_or("")),
+                    out.trim.as_deref().unwrap_or(""),
+                    out.crop.as_deref().unwrap_or(""),
+                    out.filter,
+                    out.fit,
+                    escape_json(out.decoder_shared_with.as_deref().unwrap_or("")),
+                    out.buffer_scale,
+                    out.buffer_transform,
+                    out.clamped,
+                    out.color
+                        .as_ref()
+                        .map_or_else(|| "null".to_string(), color_json),
+                    out.input_region
+                        .map_or_else(|| "null".to_string(), |region| format!("\"{region}\"")),
+                    pause_reason.is_some(),
+                    pause_reason.unwrap_or("none"),
+                    callback_latency,
+                    frame_arrival,
+                    damage,
+                    out.flash_guard.unwrap_or("off"),
+                    process.as_ref().map_or_else(|| "null".to_string(), decoder_process_json),
+                    if out.suspect_mode.is_some() { "suspect" } else { "ok" },
+                    out.suspect_mode
+                        .map(|(w, h)| format!("{w}x{h}"))
+                        .unwrap_or_default(),
+                    out.layout.as_deref().unwrap_or(""),
+                    cells_json(&out.cells)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let decision = self.pause.decision();
+        let rate = self.scheduler.frame_rate(Instant::now());
+        let scan = self.steam_detector.scan_stats();
+        let gpu = self.backend.gpu_status().map_or_else(
+            || "null".to_string(),
+            |gpu| {
+                format!(
+                    "{{\"name\":\"{}\",\"backend\":\"{}\",\"device_type\":\"{}\",\"fps_cap\":{}}}",
+                    escape_json(&gpu.name),
+                    gpu.backend,
+                    gpu.device_type,
+                    gpu.fps_cap
+                        .map_or_else(|| "null".to_string(), |cap| cap.to_string())
+                )
+            },
+        );
+        let shell = self.backend.shell_status().map_or_else(
+            || "null".to_string(),
+            |shell| {
+                format!(
+                    "{{\"protocol\":\"{}\",\"on_missing\":\"{}\",\"reason\":\"{}\"}}",
+                    shell.protocol,
+                    shell.on_missing,
+                    escape_json(&shell.reason)
+                )
+            },
+        );
+        let number =
+            |value: Option<f64>| value.map_or_else(|| "null".to_string(), |v| format!("{v:.2}"));
+        let readings = self.thermal.readings();
+        let thermal = format!(
+            "{{\"enabled\":{},\"active\":{},\"temp_c\":{},\"pause_c\":{},\"cpu_pressure\":{},\"cpu_pressure_pause\":{}}}",
+            self.thermal.is_enabled(),
+            self.thermal.is_active(),
+            number(readings.temp_c),
+            number(self.thermal.pause_c()),
+            number(readings.cpu_pressure),
+            number(self.thermal.pressure_pause())
+        );
+        let strings = |values: &mut dyn Iterator<Item = String>| {
+            values
+                .map(|value| format!("\"{}\"", escape_json(&value)))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let alias_reports = self
+            .aliases
+            .report(
+                &statuses
+                    .iter()
+                    .map(|out| (out.name.clone(), out.description.clone()))
+                    .collect::<Vec<_>>(),
+            )
+            .into_iter()
+            .map(|report| {
+                format!(
+                    "{{\"alias\":\"{}\",\"target\":\"{}\",\"outputs\":[{}],\"problem\":{}}}",
+                    escape_json(&report.alias),
+                    escape_json(&report.target),
+                    strings(&mut report.outputs.into_iter()),
+                    report.problem.map_or_else(
+                        || "null".to_string(),
+                        |problem| format!("\"{}\"", escape_json(&problem))
+                    )
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let headroom = self.backend.headroom().map_or_else(
+            || "null".to_string(),
+            |estimate| {
+                format!(
+                    "{{\"verdict\":\"{}\",\"ratio\":{},\"need_cores\":{:.2},\"capacity_cores\":{:.0},\"heaviest\":[{}],\"suggestions\":[{}],\"pending\":{}}}",
+                    estimate.verdict.as_str(),
+                    number(Some(estimate.ratio).filter(|ratio| ratio.is_finite())),
+                    estimate.need_cores,
+                    estimate.capacity_cores,
+                    strings(&mut estimate.heaviest.into_iter().map(|(output, _)| output)),
+                    strings(&mut estimate.suggestions.into_iter()),
+                    estimate.pending
+                )
+            },
+        );
+        let bootstrap = startup::last_timings().map_or_else(
+            || "null".to_string(),
+            |timings| {
+                format!(
+                    "{{\"total_ms\":{},\"steps\":{{{}}}}}",
+                    timings.total.as_millis(),
+                    timings
+                        .steps
+                        .iter()
+                        .map(|(step, took)| format!("\"{}\":{}", step.name(), took.as_millis()))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                )
+            },
+        );
+        format!(
+            "{{\"uptime_ms\":{},\"bootstrap\":{},\"backend\":\"{}\",\"gpu\":{},\"shell\":{},\"frames\":{},\"wakeups\":{},\"target_fps\":{},\"achieved_fps\":{:.2},\"frame_late_avg_us\":{},\"frame_late_max_us\":{},\"frame_reanchors\":{},\"steam_scan_ms\":{:.2},\"steam_scan_pids\":{},\"steam_scan_inspected\":{},\"steam_scan_over_budget\":{},\"thermal\":{},\"headroom\":{},\"map_file\":\"{}\",\"transient_overrides\":{},\"stream_events\":{},\"paused\":{},\"pause_reason\":\"{}\",\"reduce_motion\":{},\"aliases\":[{}],\"outputs\":[{}]}}",
+            self.started_at.elapsed().as_millis(),
+            bootstrap,
+            self.backend.name(),
+            gpu,
+            shell,
+            frame,
+            self.wakeups,
+            self.scheduler.target_fps(),
+            rate.achieved_fps,
+            rate.late_avg.as_micros(),
+            rate.late_max.as_micros(),
+            rate.reanchors,
+            scan.duration.as_secs_f64() * 1000.0,
+            scan.pids,
+            scan.inspected,
+            scan.over_budget,
+            thermal,
+            headroom,
+            escape_json(&self.map_file.to_string_lossy()),
+            self.backend.transient_overrides(),
+            self.events.counts_json(),
+            decision.is_paused(),
+            decision.reason.unwrap_or("none"),
+            self.reduce_motion,
+            alias_reports,
+            outputs
+        )
+    }
+}
+
+fn cells_json(cells: &[CellStatus]) -> String {
+    cells
+        .iter()
+        .map(|cell| {
+            format!(
+                "{{\"cell\":{},\"kind\":\"{}\",\"source\":\"{}\",\"last_error\":{}}}",
+                cell.cell,
+                cell.stream_kind,
+                escape_json(cell.source.as_deref().unwrap_or("")),
+                cell.last_error.as_deref().map_or_else(
+                    || "null".to_string(),
+                    |err| format!("\"{}\"", escape_json(err))
+                )
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn color_json(color: &ColorPipeline) -> String {
+    let matrix = color.matrix.map_or_else(
+        || "null".to_string(),
+        |rows| {
+            let rows = rows
+                .iter()
+                .map(|row| format!("[{:.6},{:.6},{:.6}]", row[0], row[1], row[2]))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("[{rows}]")
+        },
+    );
+    format!(
+        "{{\"gamut\":\"{}\",\"source\":\"{}\",\"primaries\":\"{}\",\"transfer\":\"{}\",\"matrix\":{},\"tagged\":{},\"note\":{}}}",
+        color.gamut.as_str(),
+        color.source,
+        escape_json(&color.primaries),
+        color.transfer,
+        matrix,
+        color.tagged,
+        color.note.as_deref().map_or_else(
+            || "null".to_string(),
+            |note| format!("\"{}\"", escape_json(note))
+        )
+    )
+}
+
+fn decoder_process_json(process: &DecoderProcess) -> String {
+    format!(
+        "{{\"pid\":{},\"cpu_percent\":{:.1},\"rss_bytes\":{},\"uptime_ms\":{}}}",
+        process.pid,
+        process.cpu_percent,
+        process.rss_bytes,
+        process.uptime.as_millis()
+    )
+}
+
+fn control_error(err: &str) -> String {
+    format!("{{\"error\":\"{}\"}}", escape_json(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+    const MS: Duration = Duration::from_millis(1);
+
+    fn wheel(start: Instant) -> TimerWheel {
+        let mut timers = TimerWheel::default();
+        timers.add(Task::PauseFile, SECOND, Duration::ZERO, start + SECOND);
+        timers.add(Task::Report, 5 * SECOND, 250 * MS, start + 2 * SECOND);
+        timers.add(Task::MapCheck, SECOND, Duration::ZERO, start + SECOND);
+        timers
+    }
+
+    #[test]
+    fn due_tasks_come_in_registration_order_and_keep_their_cadence() {
+        let start = Instant::now();
+        let mut timers = wheel(start);
+        assert_eq!(timers.next_deadline(), Some(start + SECOND));
+        assert!(timers.take_due(start + 999 * MS).is_empty());
+        assert_eq!(
+            timers.take_due(start + SECOND),
+            vec![Task::PauseFile, Task::MapCheck]
+        );
+        // Late by 300ms: the next run stays on the 1s grid.
+        assert_eq!(
+            timers.take_due(start + 2300 * MS),
+            vec![Task::PauseFile, Task::Report, Task::MapCheck]
+        );
+        assert_eq!(timers.next_deadline(), Some(start + 3 * SECOND));
+    }
+
+    #[test]
+    fn slack_runs_a_task_early_with_another_one() {
+        let start = Instant::now();
+        let mut timers = TimerWheel::default();
+        timers.add(Task::Report, 5 * SECOND, 250 * MS, start + SECOND);
+        assert_eq!(timers.take_due(start + 800 * MS), vec![Task::Report]);
+        assert_eq!(timers.next_deadline(), Some(start + 6 * SECOND));
+    }
+
+    #[test]
+    fn a_task_a_whole_interval_behind_restarts_from_now() {
+        let start = Instant::now();
+        let mut timers = TimerWheel::default();
+        timers.add(Task::PauseFile, SECOND, Duration::ZERO, start);
+        assert_eq!(timers.take_due(start + 10 * SECOND), vec![Task::PauseFile]);
+        assert_eq!(timers.next_deadline(), Some(start + 11 * SECOND));
+    }
+
+    #[test]
+    fn rescheduling_moves_one_task_and_its_cadence() {
+        let start = Instant::now();
+        let mut timers = wheel(start);
+        timers.take_due(start + SECOND);
+        // A map change waiting to settle is looked at again 100ms later.
+        timers.reschedule(Task::MapCheck, start + 1100 * MS);
+        assert_eq!(timers.next_deadline(), Some(start + 1100 * MS));
+        assert_eq!(timers.take_due(start + 1100 * MS), vec![Task::MapCheck]);
+        assert_eq!(
+            timers.take_due(start + 2 * SECOND),
+            vec![Task::PauseFile, Task::Report]
+        );
+        assert_eq!(timers.take_due(start + 2100 * MS), vec![Task::MapCheck]);
+    }
+
+    #[test]
+    fn a_cancelled_task_is_never_due_again() {
+        let start = Instant::now();
+        let mut timers = wheel(start);
+        timers.cancel(Task::MapCheck);
+        timers.cancel(Task::Snapshot);
+        assert_eq!(timers.take_due(start + SECOND), vec![Task::PauseFile]);
+        assert!(
+            !timers
+                .take_due(start + 60 * SECOND)
+                .contains(&Task::MapCheck)
+        );
+        timers.cancel(Task::PauseFile);
+        timers.cancel(Task::Report);
+        assert_eq!(timers.next_deadline(), None);
+    }
+
+    /// The live part of `status --json` as the stub backend reports it.
+    #[cfg(not(feature = "wayland-layer"))]
+    #[test]
+    fn live_status_follows_the_schema() {
+        use crate::json::{JsonValue, parse_json};
+        use crate::status_schema::{STATUS_JSON_SCHEMA, schema_errors};
+
+        let mut runtime = RenderRuntime::new(RenderCoreConfig {
+            target_fps: 10,
+            quiet_hours: None,
+            report_interval: None,
+            ..RenderCoreConfig::default()
+        });
+        runtime.set_embedded(false);
+        runtime.bootstrap().unwrap();
+        runtime.backend.render_frame(&runtime.surfaces).unwrap();
+        let live = parse_json(&runtime.live_status_json(0)).unwrap();
+        let schema = parse_json(STATUS_JSON_SCHEMA).unwrap();
+        let live_schema = schema
+            .get("properties")
+            .and_then(|p| p.get("live"))
+            .unwrap();
+        assert_eq!(
+            schema_errors(live_schema, &live, "live"),
+            Vec::<String>::new()
+        );
+        let Some(JsonValue::Array(outputs)) = live.get("outputs") else {
+            panic!("no outputs in {}", live.to_json());
+        };
+        assert!(!outputs.is_empty());
     }
 }
@@ -1,17 +1,269 @@
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
+/// With `KRC_PRECISE_TIMING=1` the loop sleeps until this long before a frame
+/// deadline and spins the rest, since `thread::sleep` often oversleeps by 1-3ms.
+const SPIN_MARGIN: Duration = Duration::from_micros(500);
+/// Achieved fps and lateness are measured over windows of this length.
+const STATS_WINDOW: Duration = Duration::from_secs(5);
+
+/// Absolute-deadline frame pacing: frame `n` is due at `origin + n / fps`, so
+/// oversleeping or a slow frame delays that one frame without shifting the
+/// ones after it, and the long-run rate matches the target exactly. Only
+/// falling a whole frame behind re-anchors the cadence at the current time.
 pub struct FrameScheduler {
+    target_fps: u32,
     frame_budget: Duration,
+    precise: bool,
+    origin: Instant,
+    next_frame: u64,
+    paused: bool,
+    stats: RateStats,
+}
+
+/// Frame timing over the current and the last completed window. A window
+/// starts at a frame and counts the frames after it, so N frames span N budgets.
+#[derive(Default)]
+struct RateStats {
+    window_start: Option<Instant>,
+    frames: u64,
+    late_total: Duration,
+    late_max: Duration,
+    reanchors: u64,
+    last: Option<FrameRate>,
+}
+
+/// Achieved rate over one stats window.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameRate {
+    pub achieved_fps: f64,
+    /// How late frames started on average, relative to their deadline.
+    pub late_avg: Duration,
+    pub late_max: Duration,
+    /// Times the cadence was re-anchored after falling a whole frame behind.
+    pub reanchors: u64,
 }
 
 impl FrameScheduler {
     pub fn new(target_fps: u32) -> Self {
         let safe_fps = target_fps.max(1);
         let frame_budget = Duration::from_nanos(1_000_000_000u64 / safe_fps as u64);
-        Self { frame_budget }
+        let now = Instant::now();
+        Self {
+            target_fps: safe_fps,
+            frame_budget,
+            precise: false,
+            origin: now,
+            next_frame: 0,
+            paused: false,
+            stats: RateStats::default(),
+        }
+    }
+
+    /// `new` plus `KRC_PRECISE_TIMING`, for the live render loop.
+    pub fn from_env(target_fps: u32) -> Self {
+        let mut scheduler = Self::new(target_fps);
+        scheduler.precise = std::env::var("KRC_PRECISE_TIMING")
+            .ok()
+            .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        scheduler
     }
 
     pub fn frame_budget(&self) -> Duration {
         self.frame_budget
     }
+
+    pub fn target_fps(&self) -> u32 {
+        self.target_fps
+    }
+
+    pub fn is_precise(&self) -> bool {
+        self.precise
+    }
+
+    /// Deadline of the next frame. Computed from the frame index rather than
+    /// by adding budgets, so the nanosecond rounding of the budget never adds up.
+    pub fn next_deadline(&self) -> Instant {
+        self.origin + self.offset(self.next_frame)
+    }
+
+    fn offset(&self, frame: u64) -> Duration {
+        let nanos = frame as u128 * 1_000_000_000 / self.target_fps as u128;
+        Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)
+    }
+
+    /// Start (`now`) the cadence over, e.g. when frames resume after a pause.
+    pub fn reanchor(&mut self, now: Instant) {
+        self.origin = now;
+        self.next_frame = 0;
+    }
+
+    /// While paused no frame is due; resuming re-anchors at `now` and starts a
+    /// fresh stats window so the pause does not count as missed frames.
+    pub fn set_paused(&mut self, paused: bool, now: Instant) {
+        if self.paused && !paused {
+            self.reanchor(now);
+            self.stats = RateStats::default();
+        }
+        self.paused = paused;
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        !self.paused && self.next_deadline() <= now
+    }
+
+    /// Marks the due frame as started at `now` and advances to the next
+    /// deadline. Lateness is carried by the fixed cadence, not added to it.
+    pub fn begin_frame(&mut self, now: Instant) {
+        let late = now.saturating_duration_since(self.next_deadline());
+        self.next_frame += 1;
+        if self.next_deadline() <= now {
+            // A whole frame behind (slow frame, suspend): catch up from now
+            // instead of rendering a burst of back-to-back frames.
+            self.reanchor(now);
+            self.next_frame = 1;
+            self.stats.reanchors += 1;
+        }
+        self.stats.record(late, now);
+    }
+
+    /// Sleeps until `deadline`; in precise mode the last `SPIN_MARGIN` is spun.
+    pub fn sleep_until(&self, deadline: Instant) {
+        let now = Instant::now();
+        if deadline <= now {
+            return;
+        }
+        if !self.precise {
+            thread::sleep(deadline - now);
+            return;
+        }
+        let remaining = deadline - now;
+        if remaining > SPIN_MARGIN {
+            thread::sleep(remaining - SPIN_MARGIN);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    /// The last completed stats window, or the current one before the first
+    /// window completes.
+    pub fn frame_rate(&self, now: Instant) -> FrameRate {
+        self.stats.last.unwrap_or_else(|| self.stats.current(now))
+    }
+}
+
+impl RateStats {
+    fn record(&mut self, late: Duration, now: Instant) {
+        let Some(window_start) = self.window_start else {
+            self.window_start = Some(now);
+            return;
+        };
+        self.frames += 1;
+        self.late_total += late;
+        self.late_max = self.late_max.max(late);
+        if now.saturating_duration_since(window_start) >= STATS_WINDOW {
+            *self = Self {
+                window_start: Some(now),
+                last: Some(self.current(now)),
+                ..Self::default()
+            };
+        }
+    }
+
+    fn current(&self, now: Instant) -> FrameRate {
+        let elapsed = self
+            .window_start
+            .map(|start| now.saturating_duration_since(start).as_secs_f64())
+            .unwrap_or(0.0);
+        FrameRate {
+            achieved_fps: if elapsed > 0.0 {
+                self.frames as f64 / elapsed
+            } else {
+                0.0
+            },
+            late_avg: self.late_total / self.frames.max(1) as u32,
+            late_max: self.late_max,
+            reanchors: self.reanchors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scheduler whose cadence starts at `t0`; every later instant is `t0`
+    /// plus an offset, so no test waits on the real clock.
+    fn scheduler(fps: u32) -> (FrameScheduler, Instant) {
+        let t0 = Instant::now();
+        let mut scheduler = FrameScheduler::new(fps);
+        scheduler.reanchor(t0);
+        (scheduler, t0)
+    }
+
+    #[test]
+    fn deadlines_follow_the_frame_index_without_rounding_drift() {
+        let (mut scheduler, t0) = scheduler(60);
+        for _ in 0..600 {
+            let due = scheduler.next_deadline();
+            scheduler.begin_frame(due);
+        }
+        // 1/60s is not a whole number of nanoseconds; summing budgets would
+        // be 400ns short here.
+        assert_eq!(scheduler.next_deadline() - t0, Duration::from_secs(10));
+        assert_eq!(scheduler.frame_budget(), Duration::from_nanos(16_666_666));
+    }
+
+    #[test]
+    fn oversleep_does_not_shift_later_deadlines() {
+        let (mut scheduler, t0) = scheduler(60);
+        let oversleep = Duration::from_millis(3);
+        for frame in 0..=300u64 {
+            let due = scheduler.next_deadline();
+            assert_eq!(due - t0, scheduler.offset(frame));
+            assert!(!scheduler.is_due(due - Duration::from_micros(1)));
+            assert!(scheduler.is_due(due + oversleep));
+            scheduler.begin_frame(due + oversleep);
+        }
+        // Frame 300 closes the first 5s window: 300 frames, each 3ms late.
+        let rate = scheduler.frame_rate(t0);
+        assert!((rate.achieved_fps - 60.0).abs() < 1e-9, "{rate:?}");
+        assert_eq!(rate.late_avg, oversleep);
+        assert_eq!(rate.late_max, oversleep);
+        assert_eq!(rate.reanchors, 0);
+    }
+
+    #[test]
+    fn a_whole_frame_behind_reanchors_instead_of_bursting() {
+        let (mut scheduler, t0) = scheduler(50);
+        scheduler.begin_frame(t0);
+        let stall = t0 + Duration::from_millis(100);
+        scheduler.begin_frame(stall);
+        assert_eq!(scheduler.next_deadline(), stall + Duration::from_millis(20));
+        assert!(!scheduler.is_due(stall));
+        assert_eq!(scheduler.frame_rate(stall).reanchors, 1);
+    }
+
+    #[test]
+    fn resuming_restarts_the_cadence_and_the_stats() {
+        let (mut scheduler, t0) = scheduler(30);
+        scheduler.begin_frame(t0);
+        scheduler.set_paused(true, t0);
+        let later = t0 + Duration::from_secs(60);
+        assert!(!scheduler.is_due(later));
+        scheduler.set_paused(false, later);
+        assert_eq!(scheduler.next_deadline(), later);
+        assert!(scheduler.is_due(later));
+        let rate = scheduler.frame_rate(later);
+        assert_eq!((rate.achieved_fps, rate.reanchors), (0.0, 0));
+    }
+
+    #[test]
+    fn zero_fps_is_treated_as_one() {
+        let (scheduler, _) = scheduler(0);
+        assert_eq!(scheduler.target_fps(), 1);
+        assert_eq!(scheduler.frame_budget(), Duration::from_secs(1));
+    }
 }
@@ -0,0 +1,437 @@
+use std::io::{BufRead, BufReader};
+use std::process::Stdio;
+
+use crate::tools::Tool;
+
+const UNIT: &str = "kitsune-rendercore.service";
+const PRIORITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+/// Characters that make a `--grep` pattern a regex rather than plain text.
+const REGEX_METACHARS: &[char] = &[
+    '.', '^', '$', '*', '+', '?', '(', ')', '[', ']', '{', '}', '|', '\\',
+];
+/// Line prefixes the renderer itself prints; `--render-only` keeps only these.
+const RENDER_PREFIXES: [&str; 3] = ["[rendercore]", "[backend:", "rendercore error:"];
+
+/// `service logs` options, mapped onto journalctl arguments.
+#[derive(Debug, Default)]
+struct LogsOptions {
+    since: Option<String>,
+    lines: Option<u32>,
+    priority: Option<String>,
+    follow: bool,
+    boot: Option<String>,
+    grep: Option<String>,
+    render_only: bool,
+}
+
+pub fn run_service_logs(args: &[String]) -> Result<(), String> {
+    let opts = parse_logs_args(args)?;
+    let Some(opts) = opts else {
+        print_service_logs_help();
+        return Ok(());
+    };
+
+    let mut journal_args = vec!["--user".to_string(), "-u".to_string(), UNIT.to_string()];
+    if let Some(since) = &opts.since {
+        journal_args.extend(["--since".to_string(), since.clone()]);
+    }
+    if let Some(lines) = opts.lines {
+        journal_args.extend(["-n".to_string(), lines.to_string()]);
+    }
+    if let Some(priority) = &opts.priority {
+        journal_args.extend(["-p".to_string(), priority.clone()]);
+    }
+    if let Some(boot) = &opts.boot {
+        journal_args.extend(["-b".to_string(), boot.clone()]);
+    }
+    if opts.follow {
+        journal_args.push("-f".to_string());
+    }
+    // journalctl builds without PCRE2 reject --grep; filter here instead.
+    let mut line_filter = None::<String>;
+    if let Some(pattern) = &opts.grep {
+        if journalctl_supports_grep() {
+            journal_args.extend(["--grep".to_string(), pattern.clone()]);
+        } else {
+            check_plain_pattern(pattern)?;
+            println!("[info] journalctl has no --grep support, filtering by plain substring");
+            line_filter = Some(pattern.clone());
+        }
+    }
+
+    let journalctl = Tool::Journalctl;
+    if line_filter.is_none() && !opts.render_only {
+        let status = journalctl
+            .command()
+            .args(&journal_args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .map_err(|e| journalctl.spawn_error(e))?;
+        return exit_status(status);
+    }
+
+    let mut child = journalctl
+        .command()
+        .args(&journal_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| journalctl.spawn_error(e))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| "journalctl stdout is not piped".to_string())?;
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        if opts.render_only && !RENDER_PREFIXES.iter().any(|p| line.contains(p)) {
+            continue;
+        }
+        if let Some(pattern) = &line_filter
+            && !matches_smart_case(&line, pattern)
+        {
+            continue;
+        }
+        println!("{line}");
+    }
+    let status = child
+        .wait()
+        .map_err(|e| format!("failed to wait for journalctl: {e}"))?;
+    exit_status(status)
+}
+
+fn exit_status(status: std::process::ExitStatus) -> Result<(), String> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("journalctl exited with status: {status}"))
+    }
+}
+
+/// Returns `None` for `--help`.
+fn parse_logs_args(args: &[String]) -> Result<Option<LogsOptions>, String> {
+    let mut opts = LogsOptions {
+        follow: true,
+        ..LogsOptions::default()
+    };
+    let mut explicit_follow = false;
+    let mut i = 0usize;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        let mut value = |name: &str| {
+            i += 1;
+            args.get(i)
+                .cloned()
+                .ok_or_else(|| format!("missing value for {name}"))
+        };
+        match arg {
+            "--since" => {
+                let since = value("--since")?;
+                validate_since(&since)?;
+                opts.since = Some(since);
+            }
+            "--lines" | "-n" => {
+                let raw = value("--lines")?;
+                let lines = raw
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("--lines expects a positive number, got '{raw}'"))?;
+                opts.lines = Some(lines);
+            }
+            "--priority" | "-p" => opts.priority = Some(parse_priority(&value("--priority")?)?),
+            "--boot" | "-b" => opts.boot = Some(parse_boot(&value("--boot")?)?),
+            "--grep" | "-g" => {
+                let pattern = value("--grep")?;
+                if pattern.is_empty() {
+                    return Err("--grep pattern is empty".to_string());
+                }
+                opts.grep = Some(pattern);
+            }
+            "--no-follow" => opts.follow = false,
+            "--follow" | "-f" => explicit_follow = true,
+            "--render-only" => opts.render_only = true,
+            "--help" | "-h" => return Ok(None),
+            other => return Err(format!("unknown argument for service logs: {other}")),
+        }
+        i += 1;
+    }
+    // A past boot never gets new lines; following it would just hang.
+    if opts.boot.as_deref().is_some_and(|b| b != "0") {
+        if explicit_follow {
+            return Err("--follow cannot be combined with a past --boot".to_string());
+        }
+        opts.follow = false;
+    }
+    if explicit_follow && !opts.follow {
+        return Err("--follow and --no-follow are mutually exclusive".to_string());
+    }
+    Ok(Some(opts))
+}
+
+fn parse_priority(raw: &str) -> Result<String, String> {
+    let lower = raw.trim().to_ascii_lowercase();
+    let name = match lower.as_str() {
+        "error" => "err",
+        "warn" => "warning",
+        "emergency" => "emerg",
+        "critical" => "crit",
+        other => other,
+    };
+    if PRIORITIES.contains(&name) {
+        return Ok(name.to_string());
+    }
+    if let Ok(level) = name.parse::<usize>()
+        && level < PRIORITIES.len()
+    {
+        return Ok(level.to_string());
+    }
+    Err(format!(
+        "--priority expects one of {} or 0-7, got '{raw}'",
+        PRIORITIES.join(", ")
+    ))
+}
+
+/// `0` (this boot), `-N` / `N` (offsets) or a 32-hex-digit boot id.
+fn parse_boot(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    let is_offset = raw
+        .strip_prefix(['-', '+'])
+        .unwrap_or(raw)
+        .parse::<u32>()
+        .is_ok();
+    let is_id = raw.len() == 32 && raw.chars().all(|c| c.is_ascii_hexdigit());
+    if is_offset || is_id {
+        Ok(raw.to_string())
+    } else {
+        Err(format!(
+            "--boot expects an offset (0 = this boot, -1 = previous) or a boot id, got '{raw}'"
+        ))
+    }
+}
+
+/// Accepts the journalctl time forms people actually use, so a typo fails here
+/// with a clear message instead of as a journalctl parse error:
+/// `today`, `yesterday`, `now`, `@<epoch>`, `-1h` / `+30min`, `2 hours ago`,
+/// `YYYY-MM-DD`, `YYYY-MM-DD HH:MM[:SS]` and `HH:MM[:SS]`.
+fn validate_since(raw: &str) -> Result<(), String> {
+    let spec = raw.trim();
+    let valid = match spec {
+        "today" | "yesterday" | "now" | "tomorrow" => true,
+        _ if spec.starts_with('@') => spec[1..].parse::<u64>().is_ok(),
+        _ if spec.starts_with(['-', '+']) => is_timespan(&spec[1..]),
+        _ if spec.ends_with(" ago") => is_timespan(&spec[..spec.len() - 4]),
+        _ => match spec.split_once(' ') {
+            Some((date, time)) => is_date(date) && is_time(time),
+            None => is_date(spec) || is_time(spec),
+        },
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "--since '{raw}' is not a time journalctl accepts; use e.g. today, yesterday, -2h, \"30 min ago\", 2026-01-31, \"2026-01-31 18:00\" or 18:00"
+        ))
+    }
+}
+
+/// `1h`, `30min`, `2 hours`, `1h 30min`: number/unit pairs, optionally spaced.
+fn is_timespan(raw: &str) -> bool {
+    const UNITS: [&str; 22] = [
+        "us", "usec", "ms", "msec", "s", "sec", "second", "seconds", "m", "min", "minute",
+        "minutes", "h", "hr", "hour", "hours", "d", "day", "days", "w", "week", "weeks",
+    ];
+    let compact = raw.split_whitespace().collect::<Vec<_>>().join("");
+    if compact.is_empty() {
+        return false;
+    }
+    let mut rest = compact.as_str();
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return false;
+        }
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if !UNITS.contains(&&rest[..unit_len]) {
+            return false;
+        }
+        rest = &rest[unit_len..];
+    }
+    true
+}
+
+fn is_date(raw: &str) -> bool {
+    let parts = raw.split('-').collect::<Vec<_>>();
+    matches!(parts.as_slice(), [y, m, d]
+        if y.len() == 4 && m.len() == 2 && d.len() == 2
+            && [y, m, d].iter().all(|p| p.chars().all(|c| c.is_ascii_digit()))
+            && (1..=12).contains(&m.parse::<u8>().unwrap_or(0))
+            && (1..=31).contains(&d.parse::<u8>().unwrap_or(0)))
+}
+
+fn is_time(raw: &str) -> bool {
+    let parts = raw.split(':').collect::<Vec<_>>();
+    let field = |p: &str, max: u8| {
+        p.len() == 2
+            && p.chars().all(|c| c.is_ascii_digit())
+            && p.parse::<u8>().unwrap_or(99) <= max
+    };
+    match parts.as_slice() {
+        [h, m] => field(h, 23) && field(m, 59),
+        [h, m, s] => field(h, 23) && field(m, 59) && field(s, 60),
+        _ => false,
+    }
+}
+
+/// `journalctl --version` lists `+PCRE2` when `--grep` is available.
+fn journalctl_supports_grep() -> bool {
+    Tool::Journalctl
+        .command()
+        .arg("--version")
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).contains("+PCRE2"))
+        .unwrap_or(false)
+}
+
+/// The substring fallback cannot honour a regex; matching `err.*quality`
+/// literally would silently show nothing, so such patterns are refused.
+fn check_plain_pattern(pattern: &str) -> Result<(), String> {
+    match pattern.chars().find(|c| REGEX_METACHARS.contains(c)) {
+        Some(c) => Err(format!(
+            "--grep '{pattern}' uses the regex character '{c}', but this journalctl was built without PCRE2 and has no --grep; only plain text can be filtered (without any of {})",
+            REGEX_METACHARS.iter().collect::<String>()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// journalctl's own rule: case-insensitive unless the pattern has uppercase.
+fn matches_smart_case(line: &str, pattern: &str) -> bool {
+    if pattern.chars().any(char::is_uppercase) {
+        line.contains(pattern)
+    } else {
+        line.to_lowercase().contains(&pattern.to_lowercase())
+    }
+}
+
+fn print_service_logs_help() {
+    println!("kitsune-rendercore service logs");
+    println!("Usage:");
+    println!(
+        "  kitsune-rendercore service logs [--since <TIME>] [--lines <N>] [--priority <LEVEL>]"
+    );
+    println!("                                 [--boot <N|ID>] [--grep <PATTERN>] [--no-follow]");
+    println!("                                 [--render-only]");
+    println!();
+    println!("Options:");
+    println!(
+        "  --since <TIME>      today, yesterday, -2h, \"30 min ago\", 2026-01-31 [18:00], 18:00."
+    );
+    println!("  --lines, -n <N>     Show the last N lines first.");
+    println!(
+        "  --priority, -p <L>  emerg|alert|crit|err|warning|notice|info|debug (or 0-7) and worse."
+    );
+    println!("  --boot, -b <N|ID>   0 = this boot, -1 = previous one (implies --no-follow).");
+    println!("  --grep, -g <PAT>    journalctl --grep; plain text only when journalctl lacks it.");
+    println!("  --no-follow         Print and exit instead of following (default: follow).");
+    println!("  --render-only       Only the renderer's own lines, hiding wgpu/driver noise.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<LogsOptions, String> {
+        let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        parse_logs_args(&args).map(|opts| opts.expect("not --help"))
+    }
+
+    #[test]
+    fn grep_fallback_refuses_regex_patterns() {
+        assert!(check_plain_pattern("quality changed").is_ok());
+        assert!(check_plain_pattern("DP-1: decoder").is_ok());
+        for pattern in ["err.*quality", "^\\[rendercore\\]", "a|b", "fps?", "x{2}"] {
+            let err = check_plain_pattern(pattern).unwrap_err();
+            assert!(err.contains("PCRE2") && err.contains(pattern), "{err}");
+        }
+    }
+
+    #[test]
+    fn smart_case_matches_like_journalctl() {
+        assert!(matches_smart_case("Quality LOW on DP-1", "quality"));
+        assert!(matches_smart_case("Quality LOW on DP-1", "DP-1"));
+        assert!(!matches_smart_case("quality low on dp-1", "DP-1"));
+    }
+
+    #[test]
+    fn options_map_onto_journalctl_values() {
+        let opts = parse(&[
+            "--since",
+            "2 hours ago",
+            "-n",
+            "200",
+            "-p",
+            "warn",
+            "-b",
+            "-1",
+        ])
+        .unwrap();
+        assert_eq!(opts.since.as_deref(), Some("2 hours ago"));
+        assert_eq!(opts.lines, Some(200));
+        assert_eq!(opts.priority.as_deref(), Some("warning"));
+        assert_eq!(opts.boot.as_deref(), Some("-1"));
+        assert!(!opts.follow, "a past boot is never followed");
+        assert!(parse(&[]).unwrap().follow);
+        assert!(parse(&["--follow", "--boot", "-1"]).is_err());
+        assert!(parse(&["--follow", "--no-follow"]).is_err());
+        assert!(parse(&["--lines", "0"]).is_err());
+        assert!(parse(&["--grep", ""]).is_err());
+        assert!(parse(&["--since"]).unwrap_err().contains("missing value"));
+    }
+
+    #[test]
+    fn since_values_are_checked_before_journalctl_sees_them() {
+        for ok in [
+            "today",
+            "-2h",
+            "+30min",
+            "30 min ago",
+            "1h 30min ago",
+            "@1760000000",
+            "2026-01-31",
+            "2026-01-31 18:00",
+            "18:00:59",
+        ] {
+            assert!(validate_since(ok).is_ok(), "{ok}");
+        }
+        for bad in [
+            "last tuesday",
+            "-2 fortnights",
+            "2026-13-01",
+            "25:00",
+            "@soon",
+            " ago",
+        ] {
+            assert!(validate_since(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn priorities_and_boots() {
+        assert_eq!(parse_priority("Error").unwrap(), "err");
+        assert_eq!(parse_priority("7").unwrap(), "7");
+        assert!(parse_priority("8").is_err());
+        assert_eq!(parse_boot("0").unwrap(), "0");
+        assert!(parse_boot(&"a".repeat(32)).is_ok());
+        assert!(parse_boot("previous").is_err());
+    }
+}
@@ -0,0 +1,764 @@
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::app::{monitors_for_all, video_entry_from_arg};
+use crate::config::{DEFAULT_VIDEO_FPS, VIDEO_FPS_RANGE, video_fps};
+use crate::control;
+use crate::file_access::write_atomic;
+use crate::instance::{instance_id_from_env, running_pid};
+use crate::paths::config_dir;
+use crate::tools::Tool;
+use crate::user_path::{cli_base_dir, display_path, display_text, home_dir, normalize_video_path};
+use crate::video_map::{
+    EntryOptions, QualityPreset, VideoMapEntry, check_monitor_name, load_map_document,
+    map_file_path_from_env, write_map_document,
+};
+
+/// The shipped user unit; `setup` installs it with this binary and env file.
+const SERVICE_UNIT: &str = include_str!("../systemd/kitsune-rendercore.service");
+const SERVICE_NAME: &str = "kitsune-rendercore.service";
+
+/// File name suffixes offered when a folder is given or listed.
+const VIDEO_EXTENSIONS: [&str; 7] = ["mp4", "mkv", "webm", "mov", "m4v", "avi", "gif"];
+
+/// Longest folder listing printed for one answer.
+const MAX_LISTED: usize = 20;
+
+const PREVIEW_TIME: Duration = Duration::from_secs(10);
+
+const QUALITY_CHOICES: [(&str, &str); 4] = [
+    (
+        "low",
+        "720p source: lightest decode and upload, soft on large screens",
+    ),
+    (
+        "medium",
+        "1080p source: sharp on 1080p outputs at modest CPU/GPU cost",
+    ),
+    (
+        "high",
+        "1440p source: sharp on 1440p, about twice the decode work of medium",
+    ),
+    (
+        "ultra",
+        "4k source: sharpest on 4k outputs, heaviest CPU decode and GPU upload",
+    ),
+];
+
+const FPS_CHOICES: [(&str, &str); 3] = [
+    ("24", "film rate: least CPU, fine for slow scenes"),
+    ("30", "default: smooth for most wallpapers"),
+    ("60", "fluid motion: about twice the decode work of 30"),
+];
+
+struct SetupArgs {
+    defaults: bool,
+    video: Option<String>,
+    quality: Option<String>,
+    fps: Option<String>,
+    service: bool,
+    finish: Option<Finish>,
+    map_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Finish {
+    Start,
+    Preview,
+}
+
+fn parse_args(args: &[String]) -> Result<Option<SetupArgs>, String> {
+    let mut parsed = SetupArgs {
+        defaults: false,
+        video: None,
+        quality: None,
+        fps: None,
+        service: false,
+        finish: None,
+        map_file: None,
+    };
+    let mut i = 0usize;
+    while i < args.len() {
+        let value = |i: usize| {
+            args.get(i + 1)
+                .cloned()
+                .ok_or_else(|| format!("missing value for {}", args[i]))
+        };
+        match args[i].as_str() {
+            "--defaults" => parsed.defaults = true,
+            "--video" => {
+                parsed.video = Some(value(i)?);
+                i += 1;
+            }
+            "--quality" => {
+                parsed.quality = Some(value(i)?);
+                i += 1;
+            }
+            "--fps" => {
+                parsed.fps = Some(value(i)?);
+                i += 1;
+            }
+            "--map-file" => {
+                parsed.map_file = Some(PathBuf::from(value(i)?));
+                i += 1;
+            }
+            "--service" => parsed.service = true,
+            "--start" => parsed.finish = Some(Finish::Start),
+            "--preview" => parsed.finish = Some(Finish::Preview),
+            "--help" | "-h" => return Ok(None),
+            other => return Err(format!("unknown argument for setup: {other}")),
+        }
+        i += 1;
+    }
+    Ok(Some(parsed))
+}
+
+/// Reads answers from the terminal. Without one (`--defaults`, a pipe, or
+/// end of input) every question is answered with `None`, which each step
+/// takes as "skip" or as the value given on the command line.
+struct Prompter {
+    interactive: bool,
+}
+
+impl Prompter {
+    fn ask(&mut self, question: &str) -> Option<String> {
+        if !self.interactive {
+            return None;
+        }
+        print!("{question} ");
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                println!();
+                self.interactive = false;
+                None
+            }
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> bool {
+        let hint = if default { "[Y/n]" } else { "[y/N]" };
+        loop {
+            match self.ask(&format!("{question} {hint}")).as_deref() {
+                None | Some("") => return default,
+                Some("y" | "Y" | "yes") => return true,
+                Some("n" | "N" | "no") => return false,
+                Some(_) => println!("[warn] answer y or n"),
+            }
+        }
+    }
+}
+
+/// `setup`: monitors, a video per monitor, quality and fps, then the map and
+/// env file are written in one go, the user service installed, and the
+/// renderer started or previewed. Every question can be skipped with Enter.
+pub fn run_setup(args: &[String]) -> Result<(), String> {
+    let Some(args) = parse_args(args)? else {
+        print_setup_help();
+        return Ok(());
+    };
+    let interactive = !args.defaults && std::io::stdin().is_terminal();
+    if !args.defaults && !interactive {
+        println!("[info] stdin is not a terminal, answering every question as --defaults");
+    }
+    let mut prompter = Prompter { interactive };
+    // Command-line values are checked before anything is asked.
+    let quality_arg = args
+        .quality
+        .as_deref()
+        .map(QualityPreset::parse_override)
+        .transpose()?;
+    let fps_arg = args.fps.as_deref().map(check_fps).transpose()?;
+    if interactive {
+        println!("kitsune-rendercore setup: press Enter to skip any question.");
+    }
+
+    let map_path = match args.map_file {
+        Some(path) => path,
+        None => map_file_path_from_env()?,
+    };
+    let env_path = env_file_path()?;
+    let env_contents = fs::read_to_string(&env_path).unwrap_or_default();
+
+    // 1. Monitors and their videos.
+    println!();
+    println!(
+        "[info] step 1/4: videos (map file {})",
+        display_path(&map_path.to_string_lossy())
+    );
+    let mut doc = load_map_document(&map_path);
+    let monitors = detect_monitors(&mut prompter);
+    let mut map_changes = Vec::new();
+    for monitor in &monitors {
+        let current = doc.entries.get(monitor);
+        match current {
+            Some(entry) => println!("  {}: now {}", display_text(monitor), entry.display_label()),
+            None => println!("  {}: no video yet", display_text(monitor)),
+        }
+        let Some(entry) = ask_video(&mut prompter, monitor, args.video.as_deref())? else {
+            println!("[skip] {}", display_text(monitor));
+            continue;
+        };
+        check_monitor_name(doc.format, monitor)?;
+        if current != Some(&entry) {
+            map_changes.push((monitor.clone(), entry));
+        }
+    }
+
+    // 2. Quality and 3. fps, stored in the env file the service reads.
+    println!();
+    println!("[info] step 2/4: source quality (KRC_QUALITY)");
+    let current_quality = env_value(&env_contents, "KRC_QUALITY");
+    let quality = match quality_arg {
+        Some(quality) => Some(quality.map_or("default", QualityPreset::as_str).to_string()),
+        None => ask_choice(
+            &mut prompter,
+            &QUALITY_CHOICES,
+            current_quality.as_deref(),
+            |raw| {
+                QualityPreset::parse_override(raw)
+                    .map(|preset| preset.map_or("default", QualityPreset::as_str).to_string())
+            },
+        ),
+    };
+    println!();
+    println!("[info] step 3/4: decode rate (KRC_VIDEO_FPS)");
+    let current_fps = env_value(&env_contents, "KRC_VIDEO_FPS");
+    let fps = match fps_arg {
+        Some(fps) => Some(fps.to_string()),
+        None => ask_choice(&mut prompter, &FPS_CHOICES, current_fps.as_deref(), |raw| {
+            check_fps(raw).map(|fps| fps.to_string())
+        }),
+    };
+    // `default` is no KRC_QUALITY line at all.
+    let mut env_changes = Vec::new();
+    if let Some(quality) = quality.map(|q| Some(q).filter(|q| q != "default"))
+        && quality != current_quality
+    {
+        env_changes.push(("KRC_QUALITY", quality));
+    }
+    if let Some(fps) = fps.filter(|f| current_fps.as_ref() != Some(f)) {
+        env_changes.push(("KRC_VIDEO_FPS", Some(fps)));
+    }
+
+    // Nothing is written until every answer is in.
+    println!();
+    if map_changes.is_empty() && env_changes.is_empty() {
+        println!("[ok] nothing to change");
+    } else {
+        for (monitor, entry) in &map_changes {
+            println!("  {}: {}", display_text(monitor), entry.display_label());
+        }
+        for (key, value) in &env_changes {
+            match value {
+                Some(value) => println!("  {key}={value}"),
+                None => println!("  {key} removed (default)"),
+            }
+        }
+        if !prompter.confirm("Write these settings?", true) {
+            return Err("setup cancelled, nothing was written".to_string());
+        }
+        if !map_changes.is_empty() {
+            for (monitor, entry) in map_changes {
+                doc.entries.insert(monitor, entry);
+            }
+            write_map_document(&map_path, &doc)?;
+            println!("[ok] wrote {}", display_path(&map_path.to_string_lossy()));
+        }
+        if !env_changes.is_empty() {
+            let contents = set_env_values(&env_contents, &env_changes);
+            write_atomic(&env_path, contents.as_bytes(), "env file")?;
+            println!("[ok] wrote {}", display_path(&env_path.to_string_lossy()));
+        }
+    }
+
+    // 4. Service and first start.
+    println!();
+    println!("[info] step 4/4: user service");
+    let install =
+        args.service || prompter.confirm("Install and enable the systemd user service?", false);
+    if install {
+        if let Err(err) = install_user_service(&env_path) {
+            println!("[warn] service not installed: {err}");
+        }
+    } else {
+        println!(
+            "[skip] service (install it later with: kitsune-rendercore setup --defaults --service)"
+        );
+    }
+
+    let instance = instance_id_from_env();
+    if let Some(pid) = running_pid(&instance) {
+        println!("[ok] a renderer is running (pid {pid}); it reloads the map by itself");
+        if !env_changes.is_empty() {
+            println!(
+                "[info] restart it to apply the env file changes: kitsune-rendercore service restart"
+            );
+        }
+        return Ok(());
+    }
+    let finish = match args.finish {
+        Some(finish) => Some(finish),
+        None => loop {
+            match prompter
+                .ask("Start the service now [s], preview here for 10 seconds [p], or Enter to finish:")
+                .as_deref()
+            {
+                None | Some("") => break None,
+                Some("s") => break Some(Finish::Start),
+                Some("p") => break Some(Finish::Preview),
+                Some(_) => println!("[warn] answer s, p or Enter"),
+            }
+        },
+    };
+    match finish {
+        Some(Finish::Start) => start_service(),
+        Some(Finish::Preview) => preview(&map_path, &env_path),
+        None => {
+            println!("[ok] setup done; start the renderer with: kitsune-rendercore service start");
+            Ok(())
+        }
+    }
+}
+
+/// Connected monitors, or names typed in when no source answers.
+fn detect_monitors(prompter: &mut Prompter) -> Vec<String> {
+    match monitors_for_all() {
+        Ok(list) if !list.names.is_empty() => {
+            println!(
+                "[info] monitors from the {}: {}",
+                list.source,
+                list.names.join(", ")
+            );
+            for name in &list.excluded {
+                println!("[skip] {name}: excluded by the renderer (KRC_OUTPUTS)");
+            }
+            list.names
+                .into_iter()
+                .filter(|name| !list.excluded.contains(name))
+                .collect()
+        }
+        Ok(list) => {
+            println!("[warn] no monitors found ({})", list.source);
+            ask_monitor_names(prompter)
+        }
+        Err(err) => {
+            println!("[warn] {err}");
+            ask_monitor_names(prompter)
+        }
+    }
+}
+
+fn ask_monitor_names(prompter: &mut Prompter) -> Vec<String> {
+    prompter
+        .ask("Monitor names, comma-separated (e.g. DP-1,HDMI-A-1):")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// One monitor's entry: from `--video` without a terminal, else asked until
+/// the answer is a video that exists, a folder's video picked by number, or
+/// Enter. `PATH?` lists what `PATH` could complete to.
+fn ask_video(
+    prompter: &mut Prompter,
+    monitor: &str,
+    given: Option<&str>,
+) -> Result<Option<VideoMapEntry>, String> {
+    if !prompter.interactive {
+        return given.map(|raw| resolve_video(raw, true)).transpose();
+    }
+    loop {
+        let question = format!(
+            "  video file or folder for {} ('PATH?' lists matches):",
+            display_text(monitor)
+        );
+        let Some(answer) = prompter.ask(&question).filter(|a| !a.is_empty()) else {
+            return Ok(None);
+        };
+        if let Some(prefix) = answer.strip_suffix('?') {
+            print_completions(prefix);
+            continue;
+        }
+        if let Ok(path) = normalize_video_path(&answer, &cli_base_dir())
+            && Path::new(&path).is_dir()
+        {
+            let videos = videos_in(Path::new(&path));
+            if videos.is_empty() {
+                println!("[warn] no videos in {}", display_path(&path));
+                continue;
+            }
+            for (n, video) in videos.iter().enumerate().take(MAX_LISTED) {
+                println!("    {}) {}", n + 1, display_text(&file_name(video)));
+            }
+            let pick = prompter.ask(&format!(
+                "  pick 1-{} (Enter to go back):",
+                videos.len().min(MAX_LISTED)
+            ));
+            if let Some(video) = pick
+                .and_then(|n| n.parse::<usize>().ok())
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|n| videos.get(n).filter(|_| n < MAX_LISTED))
+            {
+                return resolve_video(&video.to_string_lossy(), false).map(Some);
+            }
+            continue;
+        }
+        match resolve_video(&answer, false) {
+            Ok(entry) => return Ok(Some(entry)),
+            Err(err) => {
+                println!("[warn] {err}");
+                print_completions(&answer);
+            }
+        }
+    }
+}
+
+/// An answer as a map entry, through the same parsing as `set-video --video`,
+/// checked like `watch-map` checks the map: every video must exist. A folder
+/// is only accepted with `first_in_folder`, which takes its first video by name.
+fn resolve_video(raw: &str, first_in_folder: bool) -> Result<VideoMapEntry, String> {
+    let entry = video_entry_from_arg(raw, EntryOptions::default())?;
+    if entry.layout.is_none() && Path::new(&entry.video).is_dir() {
+        let dir = PathBuf::from(&entry.video);
+        let first = videos_in(&dir).into_iter().next();
+        return match first {
+            Some(video) if first_in_folder => {
+                println!(
+                    "[info] {} is a folder, using its first video",
+                    display_path(&entry.video)
+                );
+                resolve_video(&video.to_string_lossy(), false)
+            }
+            Some(_) => Err(format!("{} is a folder", display_path(&entry.video))),
+            None => Err(format!("no videos in {}", display_path(&entry.video))),
+        };
+    }
+    for video in entry.videos() {
+        if !Path::new(video).is_file() {
+            return Err(format!("video not found: {}", display_path(video)));
+        }
+    }
+    Ok(entry)
+}
+
+/// Videos directly in `dir`, by name.
+fn videos_in(dir: &Path) -> Vec<PathBuf> {
+    let mut videos = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file() && is_video(path))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    videos.sort();
+    videos
+}
+
+fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+        .into_owned()
+}
+
+/// Folders and videos `raw` could complete to: the contents of `raw` when it
+/// is a folder, else the names in its folder starting with its last part.
+fn print_completions(raw: &str) {
+    let Ok(path) = normalize_video_path(if raw.is_empty() { "." } else { raw }, &cli_base_dir())
+    else {
+        return;
+    };
+    let path = PathBuf::from(path);
+    let (dir, prefix) = if path.is_dir() {
+        (path, String::new())
+    } else {
+        match path.parent() {
+            Some(parent) => (parent.to_path_buf(), file_name(&path)),
+            None => return,
+        }
+    };
+    let mut matches = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| file_name(path).starts_with(&prefix))
+                .filter(|path| !file_name(path).starts_with('.') || prefix.starts_with('.'))
+                .filter(|path| path.is_dir() || is_video(path))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    matches.sort();
+    if matches.is_empty() {
+        println!(
+            "    (no folders or videos match in {})",
+            display_path(&dir.to_string_lossy())
+        );
+        return;
+    }
+    for path in matches.iter().take(MAX_LISTED) {
+        let slash = if path.is_dir() { "/" } else { "" };
+        println!("    {}{slash}", display_path(&path.to_string_lossy()));
+    }
+    if matches.len() > MAX_LISTED {
+        println!("    ... and {} more", matches.len() - MAX_LISTED);
+    }
+}
+
+/// Lists `choices`, then asks until the answer passes `check`. Enter keeps
+/// `current` (returns `None`).
+fn ask_choice(
+    prompter: &mut Prompter,
+    choices: &[(&str, &str)],
+    current: Option<&str>,
+    check: impl Fn(&str) -> Result<String, String>,
+) -> Option<String> {
+    if !prompter.interactive {
+        return None;
+    }
+    for (name, what) in choices {
+        println!("  {name:<7} {what}");
+    }
+    let question = match current {
+        Some(current) => format!("  choice (Enter keeps {current}):"),
+        None => "  choice (Enter keeps the default):".to_string(),
+    };
+    loop {
+        let answer = prompter.ask(&question).filter(|a| !a.is_empty())?;
+        match check(&answer) {
+            Ok(value) => return Some(value),
+            Err(err) => println!("[warn] {err}"),
+        }
+    }
+}
+
+/// `KRC_VIDEO_FPS` as the renderer reads it, refusing what it would clamp.
+fn check_fps(raw: &str) -> Result<u32, String> {
+    let checked = video_fps(Some(raw));
+    match checked.warning {
+        Some(_) => Err(format!(
+            "invalid fps '{}' (expected {}-{})",
+            raw.trim(),
+            VIDEO_FPS_RANGE.start(),
+            VIDEO_FPS_RANGE.end()
+        )),
+        None => Ok(checked.value),
+    }
+}
+
+/// `KRC_ENV_FILE`, else the `env` file the shipped unit reads.
+fn env_file_path() -> Result<PathBuf, String> {
+    match std::env::var("KRC_ENV_FILE") {
+        Ok(path) if !path.trim().is_empty() => Ok(PathBuf::from(path)),
+        _ => Ok(config_dir()?.join("env")),
+    }
+}
+
+/// The value of an uncommented `KEY=VALUE` line.
+fn env_value(contents: &str, key: &str) -> Option<String> {
+    contents.lines().rev().find_map(|line| {
+        let (k, v) = line.trim().split_once('=')?;
+        (k.trim() == key).then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+/// `contents` with each key's line replaced, appended when missing, or
+/// removed for `None`. Comments and other keys are kept as they are.
+fn set_env_values(contents: &str, values: &[(&str, Option<String>)]) -> String {
+    let mut lines = contents.lines().map(str::to_string).collect::<Vec<_>>();
+    for (key, value) in values {
+        let is_key = |l: &String| {
+            l.trim()
+                .split_once('=')
+                .is_some_and(|(k, _)| k.trim() == *key)
+        };
+        match value {
+            Some(value) => match lines.iter().position(is_key) {
+                Some(index) => lines[index] = format!("{key}={value}"),
+                None => lines.push(format!("{key}={value}")),
+            },
+            None => lines.retain(|l| !is_key(l)),
+        }
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user`, else `~/.config/systemd/user`.
+fn user_unit_dir() -> Result<PathBuf, String> {
+    match std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        Some(config) => Ok(PathBuf::from(config).join("systemd/user")),
+        None => home_dir()
+            .map(|home| home.join(".config/systemd/user"))
+            .ok_or_else(|| "cannot find the home directory".to_string()),
+    }
+}
+
+/// The shipped unit, running this binary and reading `env_path` (optional,
+/// so skipping the env step does not break the service).
+fn unit_contents(exe: &Path, env_path: &Path) -> String {
+    let quote = |path: &Path| {
+        let path = path.to_string_lossy();
+        if path.contains(char::is_whitespace) {
+            format!("\"{path}\"")
+        } else {
+            path.into_owned()
+        }
+    };
+    SERVICE_UNIT
+        .lines()
+        .map(|line| {
+            if line.starts_with("ExecStart=") {
+                format!("ExecStart={} --foreground", quote(exe))
+            } else if line.starts_with("EnvironmentFile=") {
+                format!("EnvironmentFile=-{}", quote(env_path))
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// Writes the user unit (an existing, different one is kept) and enables it.
+fn install_user_service(env_path: &Path) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| format!("cannot locate own binary: {e}"))?;
+    let unit_path = user_unit_dir()?.join(SERVICE_NAME);
+    let shown = display_path(&unit_path.to_string_lossy());
+    let contents = unit_contents(&exe, env_path);
+    match fs::read_to_string(&unit_path) {
+        Ok(existing) if existing == contents => println!("[ok] {shown} is up to date"),
+        Ok(_) => println!("[skip] {shown} exists and differs, keeping it"),
+        Err(_) => {
+            write_atomic(&unit_path, contents.as_bytes(), "unit file")?;
+            println!("[ok] wrote {shown}");
+        }
+    }
+    systemctl(&["--user", "daemon-reload"])?;
+    systemctl(&["--user", "enable", SERVICE_NAME])?;
+    println!("[ok] enabled {SERVICE_NAME}");
+    Ok(())
+}
+
+fn systemctl(args: &[&str]) -> Result<(), String> {
+    let status = Tool::Systemctl
+        .command()
+        .args(args)
+        .status()
+        .map_err(|e| Tool::Systemctl.spawn_error(e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} {} exited with status: {status}",
+            Tool::Systemctl.name(),
+            args.join(" ")
+        ))
+    }
+}
+
+fn start_service() -> Result<(), String> {
+    systemctl(&["--user", "start", SERVICE_NAME])?;
+    println!("[ok] started {SERVICE_NAME}; follow it with: kitsune-rendercore service logs");
+    Ok(())
+}
+
+/// Runs the renderer in the foreground with the new settings for
+/// [`PREVIEW_TIME`], then asks it to quit.
+fn preview(map_path: &Path, env_path: &Path) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| format!("cannot locate own binary: {e}"))?;
+    let env_contents = fs::read_to_string(env_path).unwrap_or_default();
+    let mut command = Command::new(exe);
+    command
+        .arg("--foreground")
+        .env("KRC_VIDEO_MAP_FILE", map_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    for key in ["KRC_QUALITY", "KRC_VIDEO_FPS"] {
+        if let Some(value) = env_value(&env_contents, key) {
+            command.env(key, value);
+        }
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("failed to start the preview: {e}"))?;
+    println!(
+        "[info] previewing for {} seconds...",
+        PREVIEW_TIME.as_secs()
+    );
+    let started = Instant::now();
+    while started.elapsed() < PREVIEW_TIME {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| format!("failed to wait for the preview: {e}"))?
+        {
+            return Err(format!("the preview renderer exited early ({status})"));
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let mut exited = false;
+    if control::request("quit").is_ok() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !exited && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(100));
+            exited = child.try_wait().ok().flatten().is_some();
+        }
+    }
+    if !exited {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    println!("[ok] preview done; start the renderer with: kitsune-rendercore service start");
+    Ok(())
+}
+
+fn print_setup_help() {
+    println!("kitsune-rendercore setup");
+    println!("Usage:");
+    println!(
+        "  kitsune-rendercore setup [--defaults] [--video <PATH>] [--quality <PRESET>] [--fps <N>] [--service] [--start|--preview] [--map-file <PATH>]"
+    );
+    println!();
+    println!("Description:");
+    println!("  First-run wizard: detects the monitors, asks for a video (or a folder to");
+    println!("  pick one from) per monitor, a source quality and a decode rate, writes the");
+    println!("  map and the env file the service reads, optionally installs and enables");
+    println!("  the systemd user service, then starts it or previews for 10 seconds.");
+    println!("  Press Enter to skip any question; nothing is written before the summary.");
+    println!();
+    println!("Options:");
+    println!("  --defaults       Ask nothing (also when stdin is not a terminal): skip every");
+    println!("                   step the options below do not answer.");
+    println!("  --video <PATH>   Video for every monitor (a folder: its first video by name).");
+    println!("  --quality <P>    KRC_QUALITY: low, medium, high, ultra or default.");
+    println!(
+        "  --fps <N>        KRC_VIDEO_FPS ({}-{}, default {DEFAULT_VIDEO_FPS}).",
+        VIDEO_FPS_RANGE.start(),
+        VIDEO_FPS_RANGE.end()
+    );
+    println!("  --service        Install and enable the user service.");
+    println!("  --start          Start the service at the end.");
+    println!("  --preview        Run the renderer here for 10 seconds at the end.");
+    println!(
+        "  --map-file <P>   Map file to write (default: KRC_VIDEO_MAP_FILE or the config dir)."
+    );
+}
@@ -0,0 +1,326 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::file_access::write_atomic;
+use crate::instance::instance_id_from_env;
+use crate::json::{JsonValue, escape_json, parse_json};
+use crate::paths::snapshot_file;
+use crate::video_map::{VideoMap, parse_video_map_lines};
+
+/// Bumped when a field changes meaning; a snapshot of another version is
+/// discarded rather than half-restored.
+const SNAPSHOT_VERSION: u64 = 1;
+
+/// `KRC_STATE_SNAPSHOT_TTL_SEC` default: a snapshot older than this is from
+/// an earlier session, not a restart.
+const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// How often the service refreshes its snapshot, so a restart that was not
+/// announced (a crash, a plain `systemctl restart`) loses at most this much.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where an output's decoder was when the snapshot was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaybackPosition {
+    pub monitor: String,
+    pub video: String,
+    pub position_ms: u64,
+}
+
+/// The runtime state a restart would otherwise lose. The map file, config
+/// and the reduce-motion flag are files already and are not repeated here.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeSnapshot {
+    pub saved_at_ms: u64,
+    /// [`map_hash`] of the map file the state was taken against.
+    pub map_hash: String,
+    /// `pause-output` monitors.
+    pub paused_outputs: Vec<String>,
+    /// `set-video transient` overrides.
+    pub transient: VideoMap,
+    pub positions: Vec<PlaybackPosition>,
+}
+
+/// What the backend gets back from a restored snapshot, before `bootstrap`.
+#[derive(Debug, Clone, Default)]
+pub struct Restored {
+    pub transient: VideoMap,
+    pub positions: Vec<PlaybackPosition>,
+}
+
+impl RuntimeSnapshot {
+    pub fn to_json(&self) -> String {
+        let strings = |items: &[String]| {
+            items
+                .iter()
+                .map(|item| format!("\"{}\"", escape_json(item)))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let transient = self
+            .transient
+            .iter()
+            .map(|(monitor, entry)| {
+                format!(
+                    "\"{}\":\"{}\"",
+                    escape_json(monitor),
+                    escape_json(&entry.to_string())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let positions = self
+            .positions
+            .iter()
+            .map(|pos| {
+                format!(
+                    "{{\"monitor\":\"{}\",\"video\":\"{}\",\"position_ms\":{}}}",
+                    escape_json(&pos.monitor),
+                    escape_json(&pos.video),
+                    pos.position_ms
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"version\":{SNAPSHOT_VERSION},\"saved_at_ms\":{},\"map_hash\":\"{}\",\"paused_outputs\":[{}],\"transient\":{{{transient}}},\"positions\":[{positions}]}}\n",
+            self.saved_at_ms,
+            escape_json(&self.map_hash),
+            strings(&self.paused_outputs)
+        )
+    }
+
+    pub fn from_json(raw: &str) -> Result<Self, String> {
+        let value = parse_json(raw)?;
+        match value.get("version").and_then(JsonValue::as_u64) {
+            Some(SNAPSHOT_VERSION) => {}
+            Some(other) => {
+                return Err(format!(
+                    "version {other}, this build reads {SNAPSHOT_VERSION}"
+                ));
+            }
+            None => return Err("no version".to_string()),
+        }
+        let text = |item: &JsonValue, key: &str| {
+            item.get(key)
+                .and_then(JsonValue::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| format!("missing {key}"))
+        };
+        let array = |key: &str| match value.get(key) {
+            Some(JsonValue::Array(items)) => items.clone(),
+            _ => Vec::new(),
+        };
+        let paused_outputs = array("paused_outputs")
+            .iter()
+            .filter_map(JsonValue::as_str)
+            .map(str::to_string)
+            .collect();
+        // Entries in map-file syntax, so every option round-trips; the paths
+        // were absolute when the overrides were set.
+        let lines = match value.get("transient") {
+            Some(JsonValue::Object(entries)) => entries
+                .iter()
+                .filter_map(|(monitor, entry)| Some(format!("{monitor}={}\n", entry.as_str()?)))
+                .collect::<String>(),
+            _ => String::new(),
+        };
+        let (transient, issues) = parse_video_map_lines(&lines, Path::new("/"));
+        if let Some(issue) = issues.first() {
+            return Err(format!("unreadable transient override: {}", issue.message));
+        }
+        let positions = array("positions")
+            .iter()
+            .map(|item| {
+                Ok(PlaybackPosition {
+                    monitor: text(item, "monitor")?,
+                    video: text(item, "video")?,
+                    position_ms: item
+                        .get("position_ms")
+                        .and_then(JsonValue::as_u64)
+                        .ok_or("missing position_ms")?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            saved_at_ms: value
+                .get("saved_at_ms")
+                .and_then(JsonValue::as_u64)
+                .ok_or("missing saved_at_ms")?,
+            map_hash: text(&value, "map_hash")?,
+            paused_outputs,
+            transient,
+            positions,
+        })
+    }
+
+    /// Writes the snapshot for this instance, replacing the previous one.
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let path = snapshot_file(&instance_id_from_env())?;
+        write_atomic(&path, self.to_json().as_bytes(), "runtime snapshot")?;
+        Ok(path)
+    }
+}
+
+/// FNV-1a of the map file's bytes: a snapshot only applies to the map it was
+/// taken against. `none` when the file cannot be read (env map only).
+pub fn map_hash(map_file: &Path) -> String {
+    let Ok(bytes) = std::fs::read(map_file) else {
+        return "none".to_string();
+    };
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+/// The snapshot a restart left for this instance, when it is recent enough
+/// (`KRC_STATE_SNAPSHOT_TTL_SEC`, `0` never restores) and was taken against
+/// the same map file. Anything else is discarded with a log line saying why.
+pub fn load_for_restore(map_file: &Path) -> Option<RuntimeSnapshot> {
+    let path = snapshot_file(&instance_id_from_env()).ok()?;
+    let raw = std::fs::read_to_string(&path).ok()?;
+    let discard = |why: String| {
+        println!(
+            "[rendercore] runtime snapshot {} discarded: {why}",
+            path.display()
+        );
+        let _ = std::fs::remove_file(&path);
+        None
+    };
+    let ttl = ttl_from_env();
+    if ttl.is_zero() {
+        return discard("restore is off (KRC_STATE_SNAPSHOT_TTL_SEC=0)".to_string());
+    }
+    let snapshot = match RuntimeSnapshot::from_json(&raw) {
+        Ok(snapshot) => snapshot,
+        Err(err) => return discard(err),
+    };
+    let age = Duration::from_millis(now_ms().saturating_sub(snapshot.saved_at_ms));
+    if age > ttl {
+        return discard(format!(
+            "saved {}s ago, older than {}s (KRC_STATE_SNAPSHOT_TTL_SEC)",
+            age.as_secs(),
+            ttl.as_secs()
+        ));
+    }
+    let hash = map_hash(map_file);
+    if hash != snapshot.map_hash {
+        return discard(format!(
+            "the map file changed since it was saved ({} -> {hash})",
+            snapshot.map_hash
+        ));
+    }
+    Some(snapshot)
+}
+
+fn ttl_from_env() -> Duration {
+    let Ok(raw) = std::env::var("KRC_STATE_SNAPSHOT_TTL_SEC") else {
+        return DEFAULT_TTL;
+    };
+    raw.trim()
+        .parse::<u64>()
+        .map(Duration::from_secs)
+        .unwrap_or_else(|_| {
+            eprintln!(
+                "[rendercore] ignoring KRC_STATE_SNAPSHOT_TTL_SEC={raw}: expected whole seconds"
+            );
+            DEFAULT_TTL
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> RuntimeSnapshot {
+        let (transient, issues) = parse_video_map_lines(
+            "DP-1=\"/v/a \\\"quoted\\\" b.mp4\" rotate=90 fit=integer gamut=matrix:1,0,0,0,1,0,0,0,1\nHDMI-A-1=/v/ünï=code.mp4 flip=h\n",
+            Path::new("/"),
+        );
+        assert!(issues.is_empty(), "{issues:?}");
+        assert_eq!(transient["DP-1"].video, "/v/a \"quoted\" b.mp4");
+        RuntimeSnapshot {
+            saved_at_ms: 1_760_000_000_123,
+            map_hash: "0123456789abcdef".to_string(),
+            paused_outputs: vec!["DP-2".to_string(), "eDP \"1\"".to_string()],
+            transient,
+            positions: vec![
+                PlaybackPosition {
+                    monitor: "DP-1".to_string(),
+                    video: "/v/a \"quoted\" b.mp4".to_string(),
+                    position_ms: 0,
+                },
+                PlaybackPosition {
+                    monitor: "HDMI-A-1".to_string(),
+                    video: "/v/ünï=code.mp4".to_string(),
+                    position_ms: u64::from(u32::MAX) * 10,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn snapshots_round_trip_through_json() {
+        let original = snapshot();
+        let json = original.to_json();
+        let back = RuntimeSnapshot::from_json(&json).unwrap();
+        assert_eq!(back.saved_at_ms, original.saved_at_ms);
+        assert_eq!(back.map_hash, original.map_hash);
+        assert_eq!(back.paused_outputs, original.paused_outputs);
+        assert_eq!(back.transient, original.transient);
+        assert_eq!(back.positions, original.positions);
+        assert_eq!(back.to_json(), json);
+    }
+
+    #[test]
+    fn empty_snapshots_round_trip() {
+        let back = RuntimeSnapshot::from_json(&RuntimeSnapshot::default().to_json()).unwrap();
+        assert!(back.paused_outputs.is_empty() && back.transient.is_empty());
+        assert!(back.positions.is_empty());
+    }
+
+    #[test]
+    fn other_versions_and_missing_fields_are_refused() {
+        let json = snapshot().to_json();
+        let err = RuntimeSnapshot::from_json(&json.replace("\"version\":1", "\"version\":2"))
+            .unwrap_err();
+        assert!(err.contains("version 2"), "{err}");
+        assert_eq!(
+            RuntimeSnapshot::from_json(&json.replace("\"version\":1,", "")).unwrap_err(),
+            "no version"
+        );
+        let err =
+            RuntimeSnapshot::from_json(&json.replace("\"position_ms\":0", "\"position_ms\":\"0\""))
+                .unwrap_err();
+        assert!(err.contains("position_ms"), "{err}");
+        let err = RuntimeSnapshot::from_json(&json.replace("rotate=90", "rotate=45")).unwrap_err();
+        assert!(err.contains("transient override"), "{err}");
+        assert!(RuntimeSnapshot::from_json("{\"version\":1").is_err());
+    }
+
+    #[test]
+    fn map_hash_follows_the_file_bytes() {
+        let dir = std::env::temp_dir().join(format!("krc-snapshot-{}-hash", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let map = dir.join("video-map.conf");
+        assert_eq!(map_hash(&map), "none");
+        std::fs::write(&map, "").unwrap();
+        // FNV-1a offset basis for no bytes.
+        assert_eq!(map_hash(&map), "cbf29ce484222325");
+        std::fs::write(&map, "DP-1=/v/a.mp4\n").unwrap();
+        let first = map_hash(&map);
+        assert_eq!(first.len(), 16);
+        std::fs::write(&map, "DP-1=/v/b.mp4\n").unwrap();
+        assert_ne!(map_hash(&map), first);
+        std::fs::write(&map, "DP-1=/v/a.mp4\n").unwrap();
+        assert_eq!(map_hash(&map), first);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,286 @@
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::instance::{hold_shutdown_signals, release_shutdown_signals, shutdown_requested};
+
+/// The steps of a bootstrap, in the order they run. A backend reports the
+/// ones it has with [`begin`]; the others are left out of the log and the
+/// summary. A new step is a new variant here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Config,
+    Compositor,
+    Adapter,
+    Device,
+    Surfaces,
+    Pipelines,
+    Streams,
+}
+
+impl Step {
+    const ALL: [Step; 7] = [
+        Step::Config,
+        Step::Compositor,
+        Step::Adapter,
+        Step::Device,
+        Step::Surfaces,
+        Step::Pipelines,
+        Step::Streams,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Config => "config",
+            Self::Compositor => "compositor",
+            Self::Adapter => "adapter",
+            Self::Device => "device",
+            Self::Surfaces => "surfaces",
+            Self::Pipelines => "pipelines",
+            Self::Streams => "streams",
+        }
+    }
+
+    fn doing(self) -> &'static str {
+        match self {
+            Self::Config => "reading config",
+            Self::Compositor => "connecting to the compositor",
+            Self::Adapter => "selecting a GPU adapter",
+            Self::Device => "creating the device",
+            Self::Surfaces => "configuring surfaces",
+            Self::Pipelines => "compiling pipelines",
+            Self::Streams => "opening video streams",
+        }
+    }
+
+    fn number(self) -> usize {
+        Self::ALL.iter().position(|step| *step == self).unwrap_or(0) + 1
+    }
+}
+
+/// Asked of systemd at each step: enough for a slow driver to compile
+/// pipelines, while a bootstrap stuck for good still times out.
+const STEP_ALLOWANCE: Duration = Duration::from_secs(30);
+
+/// How long a stop request waits for the bootstrap to reach a checkpoint
+/// before the process is ended where it stands.
+const CANCEL_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct Progress {
+    current: Option<(Step, Instant)>,
+    done: Vec<(Step, Duration)>,
+    started_at: Option<Instant>,
+    /// When systemd was last given more start time.
+    extended_at: Option<Instant>,
+    /// Running as the service: signals are held and systemd is notified.
+    /// An embedding process keeps both to itself.
+    service: bool,
+}
+
+impl Progress {
+    fn close_step(&mut self) {
+        if let Some((step, started)) = self.current.take() {
+            self.done.push((step, started.elapsed()));
+        }
+    }
+}
+
+/// How long a finished bootstrap took, per step in the order they ran.
+#[derive(Debug, Clone)]
+pub struct Timings {
+    pub steps: Vec<(Step, Duration)>,
+    pub total: Duration,
+}
+
+static PROGRESS: Mutex<Option<Progress>> = Mutex::new(None);
+/// The last finished bootstrap, for `status`.
+static LAST: Mutex<Option<Timings>> = Mutex::new(None);
+
+/// A bootstrap in progress, from [`start`]. While it lives, SIGTERM and
+/// SIGINT stop the bootstrap at its next step instead of killing it in the
+/// middle of one; a step that does not end within [`CANCEL_GRACE`] of the
+/// request is not waited for. Dropping it (finished or failed) restores the
+/// signals, delivering one that arrived meanwhile.
+pub struct Bootstrap {
+    active: Arc<AtomicBool>,
+    service: bool,
+}
+
+/// Starts tracking a bootstrap; steps are reported with [`begin`]. Without
+/// `service` (an embedding process) only the steps are logged and timed:
+/// signal dispositions and `NOTIFY_SOCKET` belong to the host.
+pub fn start(service: bool) -> Bootstrap {
+    if let Ok(mut progress) = PROGRESS.lock() {
+        *progress = Some(Progress {
+            started_at: Some(Instant::now()),
+            service,
+            ..Progress::default()
+        });
+    }
+    let active = Arc::new(AtomicBool::new(true));
+    if service {
+        hold_shutdown_signals();
+        let watched = Arc::clone(&active);
+        let _ = std::thread::Builder::new()
+            .name("krc-bootstrap-watch".to_string())
+            .spawn(move || watch_cancel(&watched));
+    }
+    Bootstrap { active, service }
+}
+
+impl Bootstrap {
+    /// Ends the last step, logs the timings and tells systemd the renderer
+    /// is ready.
+    pub fn finish(self) {
+        let Some(mut progress) = PROGRESS.lock().ok().and_then(|mut p| p.take()) else {
+            return;
+        };
+        progress.close_step();
+        let total = progress
+            .started_at
+            .map_or(Duration::ZERO, |started| started.elapsed());
+        println!(
+            "[rendercore] bootstrap done in {:.1}s: {}",
+            total.as_secs_f64(),
+            progress
+                .done
+                .iter()
+                .map(|(step, took)| format!("{}={}ms", step.name(), took.as_millis()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        if let Ok(mut last) = LAST.lock() {
+            *last = Some(Timings {
+                steps: progress.done,
+                total,
+            });
+        }
+        if self.service {
+            sd_notify("READY=1\nSTATUS=running");
+        }
+    }
+}
+
+impl Drop for Bootstrap {
+    fn drop(&mut self) {
+        self.active.store(false, Ordering::Relaxed);
+        if let Ok(mut progress) = PROGRESS.lock() {
+            progress.take();
+        }
+        if !self.service {
+            return;
+        }
+        if let Some(signum) = shutdown_requested() {
+            println!("[rendercore] bootstrap stopped by signal {signum}");
+        }
+        release_shutdown_signals();
+    }
+}
+
+/// Moves the bootstrap to `step`: logs `bootstrap 4/7: creating the
+/// device...` and asks systemd for more start time. Fails when a stop was
+/// requested, so the caller unwinds instead of starting the step. Outside a
+/// bootstrap it does nothing.
+pub fn begin(step: Step) -> Result<(), String> {
+    if let Some(signum) = shutdown_requested() {
+        return Err(format!("bootstrap cancelled by signal {signum}"));
+    }
+    let Ok(mut guard) = PROGRESS.lock() else {
+        return Ok(());
+    };
+    let Some(progress) = guard.as_mut() else {
+        return Ok(());
+    };
+    progress.close_step();
+    progress.current = Some((step, Instant::now()));
+    progress.extended_at = Some(Instant::now());
+    let line = format!(
+        "bootstrap {}/{}: {}...",
+        step.number(),
+        Step::ALL.len(),
+        step.doing()
+    );
+    println!("[rendercore] {line}");
+    if progress.service {
+        sd_notify(&format!(
+            "EXTEND_TIMEOUT_USEC={}\nSTATUS={line}",
+            STEP_ALLOWANCE.as_micros()
+        ));
+    }
+    Ok(())
+}
+
+/// Called from waits inside a step (a compositor that is not up yet):
+/// fails once a stop was requested, so the wait gives up early, and
+/// otherwise keeps extending systemd's start timeout while it lasts.
+#[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+pub fn still_busy() -> Result<(), String> {
+    if let Some(signum) = shutdown_requested() {
+        return Err(format!("bootstrap cancelled by signal {signum}"));
+    }
+    let Ok(mut guard) = PROGRESS.lock() else {
+        return Ok(());
+    };
+    if let Some(progress) = guard.as_mut()
+        && progress.service
+        && progress
+            .extended_at
+            .is_none_or(|at| at.elapsed() >= STEP_ALLOWANCE / 2)
+    {
+        progress.extended_at = Some(Instant::now());
+        sd_notify(&format!(
+            "EXTEND_TIMEOUT_USEC={}",
+            STEP_ALLOWANCE.as_micros()
+        ));
+    }
+    Ok(())
+}
+
+/// The last finished bootstrap; `None` before one finished.
+pub fn last_timings() -> Option<Timings> {
+    LAST.lock().ok().and_then(|last| last.clone())
+}
+
+fn watch_cancel(active: &AtomicBool) {
+    let mut requested_at = None;
+    while active.load(Ordering::Relaxed) {
+        if shutdown_requested().is_some() {
+            let requested_at = *requested_at.get_or_insert_with(Instant::now);
+            if requested_at.elapsed() >= CANCEL_GRACE {
+                let step = PROGRESS
+                    .lock()
+                    .ok()
+                    .and_then(|progress| progress.as_ref()?.current)
+                    .map_or("?", |(step, _)| step.name());
+                println!(
+                    "[rendercore] stop requested during bootstrap step {step}, which did not end within {}s; exiting",
+                    CANCEL_GRACE.as_secs()
+                );
+                release_shutdown_signals();
+                return;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Sends `state` to the service manager when it set `NOTIFY_SOCKET`
+/// (`Type=notify` units); a no-op otherwise.
+fn sd_notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let path = path.to_string_lossy();
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(path.as_ref()),
+    };
+    let sent = UnixDatagram::unbound()
+        .and_then(|socket| addr.and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr)));
+    if let Err(err) = sent {
+        eprintln!("[rendercore] warning: sd_notify to {path} failed: {err}");
+    }
+}
@@ -0,0 +1,364 @@
+use crate::json::{JsonValue, parse_json};
+use crate::status_schema::STATUS_JSON_SCHEMA;
+
+const FILTERS: &str = "basename, truncate:N, default:TEXT";
+
+/// Field names a template may use, read from the status schema so the two
+/// cannot drift: `root` holds dotted paths (`runtime.fps`, `live.frames`) plus
+/// the bare names they can be shortened to, `monitor` the per-monitor fields
+/// (map entry and live output merged).
+struct TemplateFields {
+    root: Vec<String>,
+    monitor: Vec<String>,
+}
+
+impl TemplateFields {
+    fn from_schema() -> Self {
+        let schema = parse_json(STATUS_JSON_SCHEMA).unwrap_or(JsonValue::Null);
+        let props = |node: Option<&JsonValue>| -> Vec<(String, JsonValue)> {
+            match node.and_then(|n| n.get("properties")) {
+                Some(JsonValue::Object(map)) => {
+                    map.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+                }
+                _ => Vec::new(),
+            }
+        };
+        // `live` is `oneOf: [null, object]`; its object variant carries the fields.
+        let object_variant = |node: &JsonValue| -> Option<JsonValue> {
+            match node.get("oneOf") {
+                Some(JsonValue::Array(variants)) => variants
+                    .iter()
+                    .find(|v| v.get("properties").is_some())
+                    .cloned(),
+                _ => Some(node.clone()),
+            }
+        };
+
+        let mut root = Vec::new();
+        let mut shortcuts = Vec::new();
+        let mut monitor = Vec::new();
+        for (key, node) in props(Some(&schema)) {
+            let node = object_variant(&node).unwrap_or(JsonValue::Null);
+            if key == "monitors" {
+                add_unique(&mut monitor, props(node.get("items")));
+                continue;
+            }
+            let children = props(Some(&node));
+            if children.is_empty() {
+                root.push(key);
+                continue;
+            }
+            for (child, child_node) in children {
+                if child == "outputs" {
+                    add_unique(&mut monitor, props(child_node.get("items")));
+                    continue;
+                }
+                root.push(format!("{key}.{child}"));
+                shortcuts.push(child);
+            }
+        }
+        for name in shortcuts {
+            if !root.contains(&name) {
+                root.push(name);
+            }
+        }
+        Self { root, monitor }
+    }
+
+    fn unknown(&self, field: &str) -> String {
+        format!(
+            "unknown field '{field}' in status template; valid fields: {}; per monitor: monitor:<NAME>.<FIELD> or {{{{#monitors}}}}...{{{{/monitors}}}} with {}",
+            self.root.join(", "),
+            self.monitor.join(", ")
+        )
+    }
+}
+
+fn add_unique(fields: &mut Vec<String>, props: Vec<(String, JsonValue)>) {
+    for (field, _) in props {
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+}
+
+/// Renders a `status --format` template against the status JSON document.
+///
+/// `{{path|filter|...}}` prints a field: dotted paths (`runtime.fps`,
+/// `live.frames`) or their last segment when it is unambiguous (`fps`).
+/// `{{monitor:DP-1.video}}` reads one monitor, and
+/// `{{#monitors}}...{{/monitors}}` (or `{{#monitors:SEP}}` to join with SEP)
+/// repeats its body per monitor, where bare names are monitor fields first.
+/// Fields without a value (no live renderer) print empty unless `default:` is used.
+pub fn render_status_template(template: &str, status: &JsonValue) -> Result<String, String> {
+    let fields = TemplateFields::from_schema();
+    render_segment(template, status, None, &fields)
+}
+
+fn render_segment(
+    template: &str,
+    status: &JsonValue,
+    monitor: Option<&str>,
+    fields: &TemplateFields,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 2..];
+        let close = after
+            .find("}}")
+            .ok_or_else(|| "unclosed '{{' in status template".to_string())?;
+        let tag = after[..close].trim();
+        rest = &after[close + 2..];
+
+        if let Some(open_tag) = tag.strip_prefix('#') {
+            let separator = match open_tag.split_once(':') {
+                Some(("monitors", sep)) => sep,
+                None if open_tag == "monitors" => "",
+                _ => return Err(format!("unknown loop '{{{{{tag}}}}}' (only #monitors)")),
+            };
+            if monitor.is_some() {
+                return Err("{{#monitors}} cannot be nested".to_string());
+            }
+            let end = rest
+                .find("{{/monitors}}")
+                .ok_or_else(|| "missing {{/monitors}} in status template".to_string())?;
+            let body = &rest[..end];
+            rest = &rest[end + "{{/monitors}}".len()..];
+            let names = monitor_names(status);
+            let rendered = names
+                .iter()
+                .map(|name| render_segment(body, status, Some(name), fields))
+                .collect::<Result<Vec<_>, _>>()?;
+            out.push_str(&rendered.join(separator));
+            continue;
+        }
+        if tag.starts_with('/') {
+            return Err(format!("unexpected '{{{{{tag}}}}}' in status template"));
+        }
+
+        let mut parts = tag.split('|');
+        let field = parts.next().unwrap_or("").trim();
+        let mut value = lookup(status, monitor, field, fields)?;
+        for filter in parts {
+            value = apply_filter(filter.trim(), value)?;
+        }
+        out.push_str(&value);
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn monitor_names(status: &JsonValue) -> Vec<String> {
+    match status.get("monitors") {
+        Some(JsonValue::Array(items)) => items
+            .iter()
+            .filter_map(|m| m.get("name").and_then(JsonValue::as_str))
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn lookup(
+    status: &JsonValue,
+    monitor: Option<&str>,
+    field: &str,
+    fields: &TemplateFields,
+) -> Result<String, String> {
+    if let Some(spec) = field.strip_prefix("monitor:") {
+        let (name, key) = spec
+            .rsplit_once('.')
+            .ok_or_else(|| format!("expected monitor:<NAME>.<FIELD>, got '{field}'"))?;
+        if !fields.monitor.iter().any(|f| f == key) {
+            return Err(fields.unknown(key));
+        }
+        return Ok(monitor_field(status, name, key));
+    }
+    if let Some(name) = monitor
+        && fields.monitor.iter().any(|f| f == field)
+    {
+        return Ok(monitor_field(status, name, field));
+    }
+    if !fields.root.iter().any(|f| f == field) {
+        return Err(fields.unknown(field));
+    }
+    let value = if field.contains('.') {
+        field.split('.').try_fold(status, |node, key| node.get(key))
+    } else {
+        status
+            .get(field)
+            .or_else(|| status.get("runtime").and_then(|r| r.get(field)))
+            .or_else(|| status.get("live").and_then(|l| l.get(field)))
+    };
+    Ok(value.map(scalar_text).unwrap_or_default())
+}
+
+/// A monitor field from the map entry, falling back to the live output of the same name.
+fn monitor_field(status: &JsonValue, name: &str, key: &str) -> String {
+    let find = |items: Option<&JsonValue>| match items {
+        Some(JsonValue::Array(items)) => items
+            .iter()
+            .find(|m| m.get("name").and_then(JsonValue::as_str) == Some(name))
+            .and_then(|m| m.get(key))
+            .cloned(),
+        _ => None,
+    };
+    find(status.get("monitors"))
+        .or_else(|| find(status.get("live").and_then(|l| l.get("outputs"))))
+        .as_ref()
+        .map(scalar_text)
+        .unwrap_or_default()
+}
+
+fn scalar_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => String::new(),
+        JsonValue::Bool(b) => b.to_string(),
+        JsonValue::Number(n) if n.fract() == 0.0 => format!("{}", *n as i64),
+        JsonValue::Number(n) => n.to_string(),
+        JsonValue::String(s) => s.clone(),
+        JsonValue::Array(_) | JsonValue::Object(_) => String::new(),
+    }
+}
+
+fn apply_filter(filter: &str, value: String) -> Result<String, String> {
+    let (name, arg) = match filter.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (filter, None),
+    };
+    match (name, arg) {
+        ("basename", None) => Ok(value
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or("")
+            .to_string()),
+        ("truncate", Some(arg)) => {
+            let max = arg
+                .parse::<usize>()
+                .map_err(|_| format!("truncate needs a number, got '{arg}'"))?;
+            if value.chars().count() <= max {
+                return Ok(value);
+            }
+            let mut cut = value
+                .chars()
+                .take(max.saturating_sub(1))
+                .collect::<String>();
+            cut.push('…');
+            Ok(cut)
+        }
+        ("default", Some(fallback)) if value.is_empty() || value == "<none>" => {
+            Ok(fallback.to_string())
+        }
+        ("default", Some(_)) => Ok(value),
+        _ => Err(format!("unknown filter '{filter}' (available: {FILTERS})")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOLDEN: &str = include_str!("../tests/fixtures/status.golden.json");
+
+    fn status(live: &str) -> JsonValue {
+        let mut status = parse_json(GOLDEN).unwrap();
+        if let JsonValue::Object(fields) = &mut status {
+            fields.insert("live".to_string(), parse_json(live).unwrap());
+        }
+        status
+    }
+
+    fn render(template: &str) -> Result<String, String> {
+        let live = r#"{"paused":false,"frames":1200,"fps":29.5,"outputs":[
+            {"name":"DP-1","stream":"video","fps":30},
+            {"name":"eDP-1","stream":"still","fps":0}]}"#;
+        render_status_template(template, &status(live))
+    }
+
+    #[test]
+    fn dotted_paths_and_their_shortcuts() {
+        assert_eq!(render("{{runtime.fps}} fps").unwrap(), "30 fps");
+        assert_eq!(render("{{hwaccel}}/{{live.frames}}").unwrap(), "vaapi/1200");
+        assert_eq!(render("{{service_state}}").unwrap(), "active");
+        assert_eq!(render("plain text").unwrap(), "plain text");
+    }
+
+    #[test]
+    fn monitor_fields_fall_back_to_the_live_output() {
+        assert_eq!(
+            render("{{monitor:DP-1.video|basename}} {{monitor:DP-1.stream}}").unwrap(),
+            "rain.mp4 video"
+        );
+        assert_eq!(render("{{monitor:eDP-1.stream}}").unwrap(), "still");
+        assert_eq!(render("[{{monitor:NOPE-1.video}}]").unwrap(), "[]");
+    }
+
+    #[test]
+    fn loops_repeat_per_map_monitor() {
+        assert_eq!(
+            render("{{#monitors:,}}{{name}}={{rotate}}{{/monitors}}").unwrap(),
+            "DP-1=90,HDMI-A-1=0"
+        );
+        assert_eq!(
+            render("{{#monitors}}<{{video|basename}}>{{/monitors}}").unwrap(),
+            "<rain.mp4><\"quoted\".mp4>"
+        );
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(apply_filter("basename", "/a/b/".into()).unwrap(), "b");
+        assert_eq!(apply_filter("truncate:4", "abcdef".into()).unwrap(), "abc…");
+        assert_eq!(
+            apply_filter("truncate:6", "abcdef".into()).unwrap(),
+            "abcdef"
+        );
+        assert_eq!(apply_filter("default:-", String::new()).unwrap(), "-");
+        assert_eq!(apply_filter("default:-", "<none>".into()).unwrap(), "-");
+        assert_eq!(apply_filter("default:-", "x".into()).unwrap(), "x");
+        assert_eq!(render("{{default_video|default:off}}").unwrap(), "off");
+        assert!(apply_filter("truncate:x", "a".into()).is_err());
+        assert!(
+            apply_filter("upper", "a".into())
+                .unwrap_err()
+                .contains(FILTERS)
+        );
+    }
+
+    #[test]
+    fn static_mode_prints_missing_live_fields_empty() {
+        let offline = render_status_template("[{{live.frames}}]", &status("null")).unwrap();
+        assert_eq!(offline, "[]");
+    }
+
+    #[test]
+    fn bad_templates_name_the_problem() {
+        let err = render("{{nope}}").unwrap_err();
+        assert!(err.contains("unknown field 'nope'"), "{err}");
+        assert!(
+            err.contains("runtime.fps") && err.contains("video"),
+            "{err}"
+        );
+        assert!(
+            render("{{monitor:DP-1.nope}}")
+                .unwrap_err()
+                .contains("'nope'")
+        );
+        assert!(render("{{fps").unwrap_err().contains("unclosed"));
+        assert!(render("{{#monitors}}x").unwrap_err().contains("missing"));
+        assert!(
+            render("{{#outputs}}{{/outputs}}")
+                .unwrap_err()
+                .contains("only #monitors")
+        );
+        assert!(render("{{/monitors}}").unwrap_err().contains("unexpected"));
+        assert!(
+            render("{{#monitors}}{{#monitors}}{{/monitors}}")
+                .unwrap_err()
+                .contains("nested")
+        );
+    }
+}
@@ -0,0 +1,566 @@
+/// Version of the `status --json` document. Bump it, and update
+/// `STATUS_JSON_SCHEMA` and `tests/fixtures/status.golden.json`, whenever a
+/// key is renamed or removed, changes type, or an enum gains a value.
+/// Adding a key is compatible and does not bump it.
+///
+/// 2: live `outputs[].stream` can be `still` and `layout`, and
+/// `pause_reason` can be `quiet-hours`.
+pub const STATUS_SCHEMA_VERSION: u32 = 2;
+
+/// JSON Schema (draft 2020-12) for `status --json`, printed by `status --schema`.
+/// Hand-maintained next to `build_status_json` and `live_status_json`.
+pub const STATUS_JSON_SCHEMA: &str = r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://github.com/KitotsuMolina/Kitsune-RenderCore/status.schema.json",
+  "title": "kitsune-rendercore status",
+  "type": "object",
+  "required": [
+    "schema_version", "map_file", "default_video", "runtime", "steam_pause_enabled",
+    "steam_game_running", "pause_reason", "pause_file", "reduce_motion", "service_state", "monitors",
+    "supervisor", "live"
+  ],
+  "properties": {
+    "schema_version": { "const": 2 },
+    "map_file": { "type": "string" },
+    "default_video": { "type": "string", "description": "\"<none>\" when unset" },
+    "runtime": {
+      "type": "object",
+      "required": ["fps", "speed", "quality", "hwaccel", "video"],
+      "properties": {
+        "fps": { "type": "string" },
+        "speed": { "type": "string" },
+        "quality": { "type": "string" },
+        "hwaccel": { "type": "string" },
+        "video": { "enum": ["ffmpeg", "none"], "description": "video decoding compiled in; \"none\" without the video-ffmpeg feature" }
+      }
+    },
+    "steam_pause_enabled": { "type": "boolean" },
+    "steam_game_running": { "type": "boolean" },
+    "pause_reason": { "type": "string", "description": "\"none\" when running; \"quiet-hours\" while the renderer is stopped for KRC_QUIET_HOURS" },
+    "pause_file": { "type": "string" },
+    "reduce_motion": { "type": "boolean", "description": "every output shows a still of its first frame; the running renderer's setting, else the saved flag" },
+    "service_state": { "type": "string" },
+    "monitors": {
+      "type": "array",
+      "items": {
+        "type": "object",
+        "required": ["name", "video", "rotate", "flip", "quality", "excluded"],
+        "properties": {
+          "name": { "type": "string" },
+          "video": { "type": "string", "description": "\"<none>\" when unmapped" },
+          "rotate": { "enum": [0, 90, 180, 270] },
+          "flip": { "enum": ["none", "h", "v", "hv"] },
+          "quality": {
+            "enum": ["default", "low", "medium", "high", "ultra"],
+            "description": "The entry's quality= preset; \"default\" follows KRC_QUALITY"
+          },
+          "excluded": { "type": "boolean" }
+        }
+      }
+    },
+    "supervisor": {
+      "description": "State of 'kitsune-rendercore supervise' for this instance; null when it never ran or stopped cleanly",
+      "oneOf": [
+        { "type": "null" },
+        {
+          "type": "object",
+          "required": ["pid", "state", "child_pid", "started_at_ms", "restarts", "recent_restarts", "max_restarts", "window_sec", "last_exit", "last_exit_at_ms"],
+          "properties": {
+            "pid": { "type": "integer" },
+            "state": {
+              "enum": ["running", "backoff", "gave-up", "dead"],
+              "description": "\"backoff\" while waiting to restart the renderer; \"gave-up\" after more than max_restarts restarts within window_sec, kept until the next supervise; \"dead\" when the supervisor is gone without saying so"
+            },
+            "child_pid": { "type": ["integer", "null"], "description": "The renderer it runs; null between runs" },
+            "started_at_ms": { "type": "integer", "description": "Unix milliseconds" },
+            "restarts": { "type": "integer", "minimum": 0, "description": "Restarts since the supervisor started" },
+            "recent_restarts": { "type": "integer", "minimum": 0 },
+            "max_restarts": { "type": "integer", "minimum": 0, "description": "KRC_SUPERVISE_MAX_RESTARTS" },
+            "window_sec": { "type": "integer", "minimum": 0, "description": "KRC_SUPERVISE_WINDOW_MIN in seconds" },
+            "last_exit": { "type": ["string", "null"], "description": "How the renderer last ended: \"exited with code N\", \"panicked (exit 101)\", \"killed by SIGSEGV, core dumped\"..." },
+            "last_exit_at_ms": { "type": ["integer", "null"] }
+          }
+        }
+      ]
+    },
+    "live": {
+      "description": "Reply of the renderer's control socket; null when it is not reachable",
+      "oneOf": [
+        { "type": "null" },
+        {
+          "type": "object",
+          "required": [
+            "uptime_ms", "bootstrap", "backend", "gpu", "shell", "frames", "wakeups", "target_fps", "achieved_fps",
+            "frame_late_avg_us", "frame_late_max_us", "frame_reanchors", "steam_scan_ms",
+            "steam_scan_pids", "steam_scan_inspected", "steam_scan_over_budget", "thermal", "headroom",
+            "map_file", "transient_overrides", "stream_events", "paused", "pause_reason", "reduce_motion",
+            "aliases", "outputs"
+          ],
+          "properties": {
+            "uptime_ms": { "type": "integer", "minimum": 0 },
+            "bootstrap": {
+              "type": ["object", "null"],
+              "description": "How long the renderer's last bootstrap took, per step in the order they ran",
+              "required": ["total_ms", "steps"],
+              "properties": {
+                "total_ms": { "type": "integer", "minimum": 0 },
+                "steps": {
+                  "type": "object",
+                  "description": "Milliseconds per step: config, compositor, adapter, device, surfaces, pipelines, streams; steps a backend does not have are left out",
+                  "additionalProperties": { "type": "integer", "minimum": 0 }
+                }
+              }
+            },
+            "backend": { "type": "string" },
+            "gpu": {
+              "description": "Adapter the renderer draws with; null for backends without a GPU",
+              "oneOf": [
+                { "type": "null" },
+                {
+                  "type": "object",
+                  "required": ["name", "backend", "device_type", "fps_cap"],
+                  "properties": {
+                    "name": { "type": "string" },
+                    "backend": { "type": "string", "description": "wgpu backend, e.g. \"vulkan\" or \"gl\"" },
+                    "device_type": {
+                      "enum": ["other", "integratedgpu", "discretegpu", "virtualgpu", "cpu"],
+                      "description": "\"cpu\" is a software adapter (llvmpipe, lavapipe)"
+                    },
+                    "fps_cap": {
+                      "type": ["integer", "null"],
+                      "description": "Rate outputs and decoders are held to on a software adapter (KRC_SOFTWARE_MAX_FPS); null when uncapped"
+                    }
+                  }
+                }
+              ]
+            },
+            "shell": {
+              "description": "Shell the wallpaper surfaces use and why; null for backends without a compositor",
+              "oneOf": [
+                { "type": "null" },
+                {
+                  "type": "object",
+                  "required": ["protocol", "on_missing", "reason"],
+                  "properties": {
+                    "protocol": {
+                      "enum": ["layer-shell", "xdg-shell"],
+                      "description": "\"xdg-shell\" is the windowed fallback: one fullscreen window per output"
+                    },
+                    "on_missing": { "enum": ["error", "wait", "windowed"], "description": "KRC_ON_NO_LAYER_SHELL" },
+                    "reason": { "type": "string" }
+                  }
+                }
+              ]
+            },
+            "frames": { "type": "integer", "minimum": 0 },
+            "wakeups": { "type": "integer", "minimum": 0 },
+            "target_fps": { "type": "integer", "minimum": 1 },
+            "achieved_fps": { "type": "number", "minimum": 0, "description": "Over the last 5s window" },
+            "frame_late_avg_us": { "type": "integer", "minimum": 0 },
+            "frame_late_max_us": { "type": "integer", "minimum": 0 },
+            "frame_reanchors": { "type": "integer", "minimum": 0, "description": "Times pacing fell a whole frame behind in the window" },
+            "steam_scan_ms": { "type": "number", "minimum": 0, "description": "Duration of the last Steam game /proc scan" },
+            "steam_scan_pids": { "type": "integer", "minimum": 0, "description": "PIDs whose stat the last scan read; 0 when pause-on-steam-game is off" },
+            "steam_scan_inspected": { "type": "integer", "minimum": 0, "description": "PIDs the last scan had no cached verdict for (cmdline/environ read)" },
+            "steam_scan_over_budget": { "type": "integer", "minimum": 0, "description": "Scans that exceeded KRC_STEAM_SCAN_BUDGET_MS" },
+            "thermal": {
+              "type": "object",
+              "description": "Thermal pause rule (KRC_THERMAL_PAUSE_C, KRC_CPU_PRESSURE_PAUSE) and its last readings; null readings when a source is off",
+              "required": ["enabled", "active", "temp_c", "pause_c", "cpu_pressure", "cpu_pressure_pause"],
+              "properties": {
+                "enabled": { "type": "boolean" },
+                "active": { "type": "boolean", "description": "Whether the rule currently holds the renderer paused" },
+                "temp_c": { "type": ["number", "null"], "description": "Hottest CPU thermal zone at the last check" },
+                "pause_c": { "type": ["number", "null"] },
+                "cpu_pressure": { "type": ["number", "null"], "minimum": 0, "maximum": 1, "description": "PSI cpu some avg10 as a fraction" },
+                "cpu_pressure_pause": { "type": ["number", "null"] }
+              }
+            },
+            "headroom": {
+              "description": "Whether the configured video decoders fit this machine's cores; null until estimated or without video",
+              "oneOf": [
+                { "type": "null" },
+                {
+                  "type": "object",
+                  "required": ["verdict", "ratio", "need_cores", "capacity_cores", "heaviest", "suggestions", "pending"],
+                  "properties": {
+                    "verdict": { "enum": ["ok", "marginal", "insufficient"] },
+                    "ratio": { "type": ["number", "null"], "minimum": 0, "description": "Capacity over need; null without streams" },
+                    "need_cores": { "type": "number", "minimum": 0, "description": "Estimated CPU cores the decoders need at full quality" },
+                    "capacity_cores": { "type": "number", "minimum": 0, "description": "Cores left for decoding (all but one)" },
+                    "heaviest": { "type": "array", "items": { "type": "string" }, "description": "Outputs with the costliest streams, heaviest first" },
+                    "suggestions": { "type": "array", "items": { "type": "string" }, "description": "Mitigations when the verdict is not ok" },
+                    "pending": { "type": "boolean", "description": "An hwaccel probe has not finished; the verdict costs that stream as software" }
+                  }
+                }
+              ]
+            },
+            "map_file": { "type": "string", "description": "Map file the renderer resolved at startup" },
+            "transient_overrides": { "type": "integer", "minimum": 0, "description": "set-video transient overrides (D-Bus SetVideo) in effect until restart" },
+            "stream_events": {
+              "type": "object",
+              "description": "Stream lifecycle events published since start, and events lost to full subscriber queues",
+              "required": ["stream_started", "stream_degraded", "stream_recovered", "stream_stopped", "stream_presented", "dropped"],
+              "additionalProperties": { "type": "integer", "minimum": 0 }
+            },
+            "paused": { "type": "boolean" },
+            "pause_reason": { "type": "string" },
+            "reduce_motion": { "type": "boolean", "description": "not a pause: stills keep rendering and paused stays false" },
+            "aliases": {
+              "type": "array",
+              "description": "[aliases] from the config file against the renderer's outputs",
+              "items": {
+                "type": "object",
+                "required": ["alias", "target", "outputs", "problem"],
+                "properties": {
+                  "alias": { "type": "string" },
+                  "target": { "type": "string", "description": "As written: desc:TEXT, name:OUTPUT, another alias or an output name" },
+                  "outputs": { "type": "array", "items": { "type": "string" } },
+                  "problem": { "type": ["string", "null"], "description": "Why the alias names no output or several, e.g. \"matches no output\" or an alias cycle" }
+                }
+              }
+            },
+            "outputs": {
+              "type": "array",
+              "items": {
+                "type": "object",
+                "required": [
+                  "name", "description", "aliases", "stream", "source", "first_frame_ms", "presented_frames",
+                  "frame_callback_pending", "last_error", "last_error_age_ms",
+                  "last_error_repeats", "degraded", "source_size", "quality_preset", "quality_level", "quality",
+                  "decode_nice", "decode", "decode_detail", "effect", "overlay", "trim", "crop", "filter", "fit", "decoder_shared_with",
+                  "buffer_scale", "buffer_transform", "clamped", "color", "input_region", "paused", "pause_reason",
+                  "callback_latency", "frame_arrival", "damage", "flash_guard", "decoder_process", "mode", "suspect_mode",
+                  "layout", "cells"
+                ],
+                "properties": {
+                  "name": { "type": "string" },
+                  "description": { "type": "string", "description": "The compositor's description; empty when it gives none" },
+                  "aliases": { "type": "array", "items": { "type": "string" }, "description": "Monitor aliases naming this output" },
+                  "stream": { "enum": ["video", "still", "procedural", "layout", "excluded"] },
+                  "source": { "type": "string" },
+                  "first_frame_ms": { "type": ["integer", "null"], "minimum": 0 },
+                  "presented_frames": { "type": "integer", "minimum": 0 },
+                  "frame_callback_pending": { "type": "boolean" },
+                  "last_error": { "type": ["string", "null"] },
+                  "last_error_age_ms": { "type": ["integer", "null"], "minimum": 0 },
+                  "last_error_repeats": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "description": "Times last_error recurred right after itself; repeats are logged once, then summarized every 60 s"
+                  },
+                  "source_size": {
+                    "type": "string",
+                    "description": "Effective decode size (WxH) after preset, orientation, GPU clamp and auto quality; empty without a stream"
+                  },
+                  "quality_preset": {
+                    "enum": ["", "low", "medium", "high", "ultra"],
+                    "description": "The map entry's quality= preset; empty when KRC_QUALITY applies"
+                  },
+                  "quality_level": {
+                    "type": "integer", "minimum": 0,
+                    "description": "KRC_AUTO_QUALITY step, 0 when running as configured"
+                  },
+                  "quality": { "type": "string", "description": "\"full\" or e.g. \"fps=30 source=1280x720\"" },
+                  "decode_nice": {
+                    "type": ["integer", "null"],
+                    "description": "Nice value the decoder runs at (KRC_DECODE_NICE), read back from /proc; null without a decoder"
+                  },
+                  "decode": {
+                    "type": "string",
+                    "description": "How the decoder really decodes, probed once per video and KRC_HWACCEL: e.g. \"vaapi\", \"cuda\", \"software\", \"software(fallback)\", \"pending\" or \"unknown\"; empty without a decoder"
+                  },
+                  "decode_detail": {
+                    "type": "string",
+                    "description": "ffmpeg's message for a fallback, or why the probe failed; empty otherwise"
+                  },
+                  "effect": {
+                    "type": "string",
+                    "description": "The entry's effect= and its parameters, e.g. \"ambient amplitude=0.05 period=300\"; empty when off"
+                  },
+                  "overlay": {
+                    "type": "string",
+                    "description": "The entry's overlay= preset and the parameters that differ from the default, e.g. \"snow density=0.8\"; empty when off"
+                  },
+                  "trim": {
+                    "type": "string",
+                    "description": "The entry's trim= window as IN:OUT seconds (OUT empty = to the end); empty when the whole video loops"
+                  },
+                  "crop": {
+                    "type": "string",
+                    "description": "The entry's crop= region as X,Y,W,H pixels of the video after clamping, or the configured value plus \" (pending)\" until the whole frame is decoded; empty without a crop"
+                  },
+                  "filter": {
+                    "enum": ["linear", "nearest"],
+                    "description": "How the video's pixels are sampled, from the entry's filter="
+                  },
+                  "fit": {
+                    "enum": ["cover", "integer"],
+                    "description": "How the video fills the output, from the entry's fit=; integer scales by whole multiples with black borders"
+                  },
+                  "decoder_shared_with": {
+                    "type": "string",
+                    "description": "Output whose decoder and texture this one shows (same video, trim, source size and fps); empty when it decodes on its own"
+                  },
+                  "buffer_scale": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Buffer scale from the compositor's wl_surface.preferred_buffer_scale; 1 when it sends none"
+                  },
+                  "buffer_transform": {
+                    "enum": ["normal", "90", "180", "270", "flipped", "flipped-90", "flipped-180", "flipped-270"],
+                    "description": "Transform buffers are stored in, from wl_surface.preferred_buffer_transform"
+                  },
+                  "clamped": {
+                    "type": "boolean",
+                    "description": "Whether the output is larger than the GPU's texture limit, so it renders at a lower buffer scale or a smaller buffer stretched with wp_viewporter"
+                  },
+                  "color": {
+                    "type": ["object", "null"],
+                    "description": "The color pipeline the output's pixels go through, from the entry's gamut=; null without a GPU backend or before the first frame",
+                    "required": ["gamut", "source", "primaries", "transfer", "matrix", "tagged", "note"],
+                    "properties": {
+                      "gamut": { "enum": ["auto", "srgb", "native", "matrix"] },
+                      "source": {
+                        "enum": ["compositor", "edid", "manual", "none"],
+                        "description": "Where the target colorimetry came from: the compositor's image description (wp_color_management_v1), the monitor's EDID, gamut=matrix:, or none when sRGB passes through"
+                      },
+                      "primaries": { "type": "string", "description": "Target primaries by protocol name (\"display_p3\"), as r=x,y g=x,y b=x,y w=x,y, \"custom\" for a matrix, or \"srgb\"" },
+                      "transfer": { "type": "string", "description": "\"srgb\", \"gammaN\", or the name of a curve left to the compositor (\"st2084_pq\")" },
+                      "matrix": {
+                        "type": ["array", "null"],
+                        "items": { "type": "array", "items": { "type": "number" }, "minItems": 3, "maxItems": 3 },
+                        "description": "Linear sRGB to linear output RGB, row by row; null when no conversion is applied"
+                      },
+                      "tagged": { "type": "boolean", "description": "Whether the surface carries the output's image description, so the compositor does not convert it again" },
+                      "note": { "type": ["string", "null"], "description": "Why gamut= fell back to passing sRGB through" }
+                    }
+                  },
+                  "paused": {
+                    "type": "boolean",
+                    "description": "Whether this output is frozen, by its own pause or the global one"
+                  },
+                  "pause_reason": {
+                    "type": "string",
+                    "description": "\"manual-output\" for pause --monitor, else the global pause reason; \"none\" when running"
+                  },
+                  "degraded": {
+                    "enum": ["none", "ffmpeg-not-found", "video-missing", "failing"],
+                    "description": "Why the output is not playing its entry as configured: \"ffmpeg-not-found\" while the decoder cannot start for lack of ffmpeg (looked for again every 30s, so installing it needs no restart), \"video-missing\" while the video path is gone or not answering, \"failing\" after errors since the last good frame"
+                  },
+                  "flash_guard": {
+                    "enum": ["off", "clear", "smoothing", "flash-guard-triggered"],
+                    "description": "KRC_FLASH_GUARD: \"smoothing\" while frames that swing in luminance are blended, \"flash-guard-triggered\" while the stream is held for flashing more than three times a second"
+                  },
+                  "input_region": {
+                    "enum": ["empty", null],
+                    "description": "Input region of the output's surface: \"empty\", so clicks pass through to the desktop and it never takes focus or counts as activity; null without a real surface (stub backend, excluded outputs)"
+                  },
+                  "callback_latency": {
+                    "type": ["object", "null"],
+                    "description": "Time from requesting a frame callback to the compositor's done, over the last ~600 callbacks; null where the backend has none",
+                    "required": ["samples", "p50_ms", "p99_ms", "slow", "stalled"],
+                    "properties": {
+                      "samples": { "type": "integer", "minimum": 0 },
+                      "p50_ms": { "type": ["number", "null"], "minimum": 0 },
+                      "p99_ms": { "type": ["number", "null"], "minimum": 0 },
+                      "slow": {
+                        "type": "boolean",
+                        "description": "A sustained share of callbacks took over two refresh intervals: the compositor, not the renderer, is delaying frames"
+                      },
+                      "stalled": {
+                        "type": "boolean",
+                        "description": "A requested callback is long overdue: the compositor stopped sending them (output hidden or off)"
+                      }
+                    }
+                  },
+                  "frame_arrival": {
+                    "type": ["object", "null"],
+                    "description": "How the decoder's frames reach the renderer through its pipe, over the last ~600 reads (the leader's decoder for a follower); null without a running decoder",
+                    "required": ["samples", "p95_ms", "queued_bytes", "capacity_bytes", "avg_fill", "starved_decode", "starved_io"],
+                    "properties": {
+                      "samples": { "type": "integer", "minimum": 0 },
+                      "p95_ms": {
+                        "type": ["number", "null"],
+                        "minimum": 0,
+                        "description": "Time the renderer waited for a whole frame"
+                      },
+                      "queued_bytes": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Bytes in the pipe when the last read started; a frame is larger than the pipe, so this tells a decoder that is ahead from one that is behind"
+                      },
+                      "capacity_bytes": { "type": "integer", "minimum": 0 },
+                      "avg_fill": { "type": "number", "minimum": 0, "maximum": 1 },
+                      "starved_decode": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Reads that found the pipe empty and got their frame within a few frame intervals: decoding is slow"
+                      },
+                      "starved_io": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Reads that found the pipe empty and waited longer (at least 200ms): the decoder was blocked on the file, e.g. a disk spinning up"
+                      }
+                    }
+                  },
+                  "damage": {
+                    "type": ["object", "null"],
+                    "description": "Presents since the output started and how much of the buffer they changed; null when KRC_DAMAGE_TRACKING=0 or the backend has no damage tracking",
+                    "required": ["presents", "skipped", "avg_percent"],
+                    "properties": {
+                      "presents": { "type": "integer", "minimum": 0 },
+                      "skipped": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Frames that were due with nothing changed on the output, so nothing was presented"
+                      },
+                      "avg_percent": {
+                        "type": "number",
+                        "minimum": 0,
+                        "maximum": 100,
+                        "description": "Average share of the buffer each present damaged"
+                      }
+                    }
+                  },
+                  "mode": {
+                    "enum": ["ok", "suspect"],
+                    "description": "\"suspect\" after the compositor reported an implausible mode (outside 64-16384 per axis, or over twice 8K); the output keeps its last good size until a sane mode arrives"
+                  },
+                  "suspect_mode": { "type": "string", "description": "The rejected mode as WxH while mode is suspect; empty otherwise" },
+                  "layout": { "type": "string", "description": "The layout= grid (gridCxR) when stream is layout; empty otherwise" },
+                  "cells": {
+                    "type": "array",
+                    "description": "Each layout cell, row by row; empty without a layout",
+                    "items": {
+                      "type": "object",
+                      "required": ["cell", "kind", "source", "last_error"],
+                      "properties": {
+                        "cell": { "type": "integer", "minimum": 1 },
+                        "kind": { "enum": ["video", "still", "procedural", "empty"] },
+                        "source": { "type": "string", "description": "The cell's video; empty for a black cell" },
+                        "last_error": { "type": ["string", "null"] }
+                      }
+                    }
+                  },
+                  "decoder_process": {
+                    "type": ["object", "null"],
+                    "description": "The output's own ffmpeg process, read from /proc; null for stills, the procedural pattern, outputs sharing another one's decoder, or a decoder that just exited",
+                    "required": ["pid", "cpu_percent", "rss_bytes", "uptime_ms"],
+                    "properties": {
+                      "pid": { "type": "integer", "minimum": 1 },
+                      "cpu_percent": {
+                        "type": "number",
+                        "minimum": 0,
+                        "description": "CPU use since the previous status request (100 = one core); since the decoder started on the first request"
+                      },
+                      "rss_bytes": { "type": "integer", "minimum": 0 },
+                      "uptime_ms": { "type": "integer", "minimum": 0 }
+                    }
+                  }
+                }
+              }
+            }
+          }
+        }
+      ]
+    }
+  }
+}"##;
+
+#[cfg(test)]
+use crate::json::JsonValue;
+
+/// What in `value` breaks `schema`: missing required keys, wrong types,
+/// values outside an `enum` or `const`, one line per problem with its path.
+/// Covers the keywords `STATUS_JSON_SCHEMA` uses, not all of JSON Schema.
+#[cfg(test)]
+pub fn schema_errors(schema: &JsonValue, value: &JsonValue, at: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    if let Some(JsonValue::Array(variants)) = schema.get("oneOf") {
+        let results = variants
+            .iter()
+            .map(|variant| schema_errors(variant, value, at))
+            .collect::<Vec<_>>();
+        let matching = results.iter().filter(|errors| errors.is_empty()).count();
+        if matching > 1 {
+            errors.push(format!("{at}: matches {matching} of the oneOf variants"));
+        } else if matching == 0 {
+            // The variants of the value's type say what is wrong with it.
+            let wrong_type = format!("{at}: {}, expected", json_type(value));
+            let mut same_type = results
+                .iter()
+                .filter(|errors| !errors[0].starts_with(&wrong_type))
+                .peekable();
+            match same_type.peek() {
+                Some(_) => errors.extend(same_type.flatten().cloned()),
+                None => errors.extend(results.into_iter().flatten()),
+            }
+        }
+        return errors;
+    }
+    if let Some(kind) = schema.get("type") {
+        let kinds = match kind {
+            JsonValue::Array(kinds) => kinds.iter().filter_map(JsonValue::as_str).collect(),
+            kind => kind.as_str().into_iter().collect::<Vec<_>>(),
+        };
+        let actual = json_type(value);
+        if !kinds
+            .iter()
+            .any(|kind| *kind == actual || (*kind == "number" && actual == "integer"))
+        {
+            errors.push(format!("{at}: {actual}, expected {}", kinds.join(" or ")));
+            return errors;
+        }
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        errors.push(format!(
+            "{at}: {} is not {}",
+            value.to_json(),
+            expected.to_json()
+        ));
+    }
+    if let Some(JsonValue::Array(allowed)) = schema.get("enum")
+        && !allowed.contains(value)
+    {
+        errors.push(format!("{at}: {} is not one of the enum", value.to_json()));
+    }
+    // `required` and `properties` say nothing about a value that is not an object.
+    if let (Some(JsonValue::Array(required)), JsonValue::Object(_)) =
+        (schema.get("required"), value)
+    {
+        for key in required.iter().filter_map(JsonValue::as_str) {
+            if value.get(key).is_none() {
+                errors.push(format!("{at}: missing \"{key}\""));
+            }
+        }
+    }
+    if let Some(JsonValue::Object(properties)) = schema.get("properties") {
+        for (key, property) in properties {
+            if let Some(field) = value.get(key) {
+                errors.extend(schema_errors(property, field, &format!("{at}.{key}")));
+            }
+        }
+    }
+    if let (Some(items), JsonValue::Array(values)) = (schema.get("items"), value) {
+        for (index, item) in values.iter().enumerate() {
+            errors.extend(schema_errors(items, item, &format!("{at}[{index}]")));
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+fn json_type(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(n) if n.fract() == 0.0 => "integer",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
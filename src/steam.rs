@@ -1,12 +1,55 @@
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
+use crate::journal::{self, Value};
+use crate::proc_stat::ProcStat;
+
+/// Over-budget scans are logged at most this often.
+const BUDGET_WARN_INTERVAL: Duration = Duration::from_secs(60);
+/// Dropped wholesale past this size; only early-stopping scans can grow it.
+const MAX_CACHED_PIDS: usize = 65_536;
+
 pub struct SteamGameDetector {
     enabled: bool,
+    /// `/proc`; a fixture tree in tests.
+    proc_dir: PathBuf,
     poll_interval: Duration,
     last_probe_at: Instant,
     last_result: bool,
+    /// Verdicts of processes already inspected, so a poll only reads `stat`
+    /// for them. See `ProcessKey` for when an entry is still valid.
+    verdicts: HashMap<u32, CachedVerdict>,
+    /// The process that matched last time, checked first on the next scan.
+    last_match: Option<(u32, ProcessKey)>,
+    scan_budget: Duration,
+    last_budget_warning: Option<Instant>,
+    stats: ScanStats,
+}
+
+/// Identifies one process image: PID reuse changes `starttime`, and an
+/// `exec` (e.g. a launcher turning into the game) changes `comm`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProcessKey {
+    starttime: u64,
+    comm: String,
+}
+
+struct CachedVerdict {
+    key: ProcessKey,
+    reason: Option<String>,
+}
+
+/// Cost of the last `/proc` scan, for the live status.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanStats {
+    pub duration: Duration,
+    /// PIDs whose `stat` was read.
+    pub pids: u32,
+    /// PIDs that were not in the cache and had cmdline (and maybe environ) read.
+    pub inspected: u32,
+    pub over_budget: u64,
 }
 
 impl SteamGameDetector {
@@ -21,18 +64,38 @@ impl SteamGameDetector {
             .filter(|v| *v >= 100)
             .unwrap_or(1500);
 
+        let budget_ms = std::env::var("KRC_STEAM_SCAN_BUDGET_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(50);
+
         Self {
             enabled,
+            proc_dir: PathBuf::from("/proc"),
             poll_interval: Duration::from_millis(poll_ms),
             last_probe_at: Instant::now() - Duration::from_millis(poll_ms),
             last_result: false,
+            verdicts: HashMap::new(),
+            last_match: None,
+            scan_budget: Duration::from_millis(budget_ms),
+            last_budget_warning: None,
+            stats: ScanStats::default(),
         }
     }
 
+    pub fn scan_stats(&self) -> ScanStats {
+        self.stats
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
 
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
     pub fn steam_game_running(&mut self) -> bool {
         if !self.enabled {
             return false;
@@ -40,44 +103,136 @@ impl SteamGameDetector {
         if self.last_probe_at.elapsed() < self.poll_interval {
             return self.last_result;
         }
+        self.probe()
+    }
+
+    /// Scans /proc now, ignoring the poll interval. The runtime calls this from
+    /// its own timer.
+    pub fn probe(&mut self) -> bool {
+        if !self.enabled {
+            return false;
+        }
         self.last_probe_at = Instant::now();
-        self.last_result = detect_steam_game_process();
+        let running = self.scan();
+        self.check_budget();
+        if running != self.last_result {
+            journal::record("steam_game", &[("running", Value::Bool(running))]);
+        }
+        self.last_result = running;
         self.last_result
     }
 }
 
-fn detect_steam_game_process() -> bool {
-    let proc_dir = Path::new("/proc");
-    let Ok(entries) = fs::read_dir(proc_dir) else {
-        return false;
-    };
-    let debug = std::env::var("KRC_STEAM_DEBUG")
-        .ok()
-        .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
-        .unwrap_or(false);
-
-    for entry in entries.flatten() {
-        let name = entry.file_name();
-        let pid = name.to_string_lossy();
-        if !pid.chars().all(|c| c.is_ascii_digit()) {
-            continue;
-        }
-
-        let p = entry.path();
-        if is_zombie_process(&p) {
-            continue;
-        }
-        if let Some(reason) = steam_game_reason(&p) {
-            if debug {
-                eprintln!(
-                    "[rendercore] steam-game-match pid={} reason={}",
-                    pid, reason
-                );
+impl SteamGameDetector {
+    /// One pass over /proc, stopping at the first game. Every live PID costs
+    /// one `stat` read; cmdline and environ are only read for processes the
+    /// cache has no verdict for. Cache entries of exited PIDs are dropped
+    /// after a full pass.
+    fn scan(&mut self) -> bool {
+        let started = Instant::now();
+        let mut stats = ScanStats {
+            over_budget: self.stats.over_budget,
+            ..ScanStats::default()
+        };
+        let found = self.scan_inner(&mut stats);
+        stats.duration = started.elapsed();
+        self.stats = stats;
+        found
+    }
+
+    fn scan_inner(&mut self, stats: &mut ScanStats) -> bool {
+        let proc_dir = self.proc_dir.as_path();
+        let debug = std::env::var("KRC_STEAM_DEBUG")
+            .ok()
+            .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+
+        // A running game usually keeps running: confirm it before walking /proc.
+        if let Some((pid, key)) = self.last_match.take() {
+            stats.pids += 1;
+            if read_process_key(&proc_dir.join(pid.to_string())).as_ref() == Some(&key) {
+                self.last_match = Some((pid, key));
+                return true;
+            }
+        }
+
+        let Ok(entries) = fs::read_dir(proc_dir) else {
+            return false;
+        };
+        if self.verdicts.len() > MAX_CACHED_PIDS {
+            self.verdicts.clear();
+        }
+        let mut alive = Vec::new();
+        for entry in entries.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let p = entry.path();
+            stats.pids += 1;
+            // Zombies have no stat state we want and no cmdline/environ.
+            let Some(key) = read_process_key(&p) else {
+                continue;
+            };
+            alive.push(pid);
+            let reason = match self.verdicts.get(&pid) {
+                Some(cached) if cached.key == key => cached.reason.clone(),
+                _ => {
+                    stats.inspected += 1;
+                    let reason = steam_game_reason(&p);
+                    self.verdicts.insert(
+                        pid,
+                        CachedVerdict {
+                            key: key.clone(),
+                            reason: reason.clone(),
+                        },
+                    );
+                    reason
+                }
+            };
+            if let Some(reason) = reason {
+                if debug {
+                    eprintln!(
+                        "[rendercore] steam-game-match pid={} reason={}",
+                        pid, reason
+                    );
+                }
+                self.last_match = Some((pid, key));
+                return true;
             }
-            return true;
         }
+        // Only a full pass knows every live PID.
+        alive.sort_unstable();
+        self.verdicts
+            .retain(|pid, _| alive.binary_search(pid).is_ok());
+        false
+    }
+
+    fn check_budget(&mut self) {
+        if self.stats.duration <= self.scan_budget {
+            return;
+        }
+        self.stats.over_budget += 1;
+        let now = Instant::now();
+        if self
+            .last_budget_warning
+            .is_some_and(|at| now.duration_since(at) < BUDGET_WARN_INTERVAL)
+        {
+            return;
+        }
+        self.last_budget_warning = Some(now);
+        eprintln!(
+            "[rendercore] steam scan took {:.1}ms (budget {}ms, KRC_STEAM_SCAN_BUDGET_MS): {} pids, {} inspected, {} slow scans so far; raise KRC_STEAM_POLL_MS or set KRC_PAUSE_ON_STEAM_GAME=false",
+            self.stats.duration.as_secs_f64() * 1000.0,
+            self.scan_budget.as_millis(),
+            self.stats.pids,
+            self.stats.inspected,
+            self.stats.over_budget
+        );
     }
-    false
 }
 
 fn steam_game_reason(proc_path: &Path) -> Option<String> {
@@ -104,10 +259,10 @@ fn steam_game_reason(proc_path: &Path) -> Option<String> {
     if let Some(raw) = environ.as_ref() {
         let env_blob = nul_join(raw);
         for key in ["SteamAppId", "SteamGameId", "STEAM_COMPAT_APP_ID"] {
-            if let Some(v) = env_var_value(&env_blob, key) {
-                if is_real_game_app_id(v) {
-                    return Some(format!("environ:{key}={v}"));
-                }
+            if let Some(v) = env_var_value(&env_blob, key)
+                && is_real_game_app_id(v)
+            {
+                return Some(format!("environ:{key}={v}"));
             }
         }
     }
@@ -133,18 +288,211 @@ fn is_real_game_app_id(v: &str) -> bool {
     !matches!(id, 7 | 228980 | 229000 | 480 | 769)
 }
 
-fn is_zombie_process(proc_path: &Path) -> bool {
-    let Ok(stat) = fs::read_to_string(proc_path.join("stat")) else {
-        return false;
-    };
-    let Some(end_comm) = stat.rfind(')') else {
-        return false;
-    };
-    let tail = stat[end_comm + 1..].trim_start();
-    let mut parts = tail.split_whitespace();
-    matches!(parts.next(), Some("Z"))
+/// `comm` and `starttime` from `/proc/PID/stat`, or `None` for a zombie or a
+/// process that is already gone.
+fn read_process_key(proc_path: &Path) -> Option<ProcessKey> {
+    let stat = ProcStat::read(proc_path).filter(|stat| stat.state != 'Z')?;
+    Some(ProcessKey {
+        starttime: stat.start_ticks,
+        comm: stat.comm,
+    })
 }
 
 fn nul_join(bytes: &[u8]) -> String {
     String::from_utf8_lossy(bytes).replace('\0', " ")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `/proc`-like tree holding only what the scan reads.
+    struct FakeProc {
+        dir: PathBuf,
+    }
+
+    impl FakeProc {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("krc-steam-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self { dir }
+        }
+
+        /// Writes (or replaces) a process; `environ` is `KEY=VALUE` pairs.
+        fn process(&self, pid: u32, comm: &str, starttime: u64, cmdline: &str, environ: &[&str]) {
+            self.process_in_state(pid, comm, 'S', starttime, cmdline, environ);
+        }
+
+        fn process_in_state(
+            &self,
+            pid: u32,
+            comm: &str,
+            state: char,
+            starttime: u64,
+            cmdline: &str,
+            environ: &[&str],
+        ) {
+            let dir = self.dir.join(pid.to_string());
+            fs::create_dir_all(&dir).unwrap();
+            // Fields 4-21 are filler; starttime is field 22.
+            let filler = vec!["0"; 18].join(" ");
+            let stat = format!("{pid} ({comm}) {state} {filler} {starttime} 0 0\n");
+            fs::write(dir.join("stat"), stat).unwrap();
+            fs::write(dir.join("cmdline"), cmdline.replace(' ', "\0")).unwrap();
+            let environ = environ
+                .iter()
+                .map(|kv| format!("{kv}\0"))
+                .collect::<String>();
+            fs::write(dir.join("environ"), environ).unwrap();
+        }
+
+        fn exit(&self, pid: u32) {
+            fs::remove_dir_all(self.dir.join(pid.to_string())).unwrap();
+        }
+
+        fn detector(&self) -> SteamGameDetector {
+            let mut detector = SteamGameDetector::from_env();
+            detector.enabled = true;
+            detector.proc_dir = self.dir.clone();
+            detector
+        }
+    }
+
+    impl Drop for FakeProc {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn unchanged_processes_are_inspected_once() {
+        let proc = FakeProc::new("cache");
+        proc.process(1, "systemd", 10, "/sbin/init", &[]);
+        proc.process(200, "bash", 500, "/bin/bash", &["HOME=/home/u"]);
+        proc.process(300, "firefox", 900, "/usr/lib/firefox/firefox", &[]);
+        // Non-PID entries such as /proc/self are not processes.
+        fs::create_dir_all(proc.dir.join("self")).unwrap();
+        let mut detector = proc.detector();
+
+        assert!(!detector.probe());
+        assert_eq!(detector.scan_stats().pids, 3);
+        assert_eq!(detector.scan_stats().inspected, 3);
+        assert!(!detector.probe());
+        assert_eq!(detector.scan_stats().inspected, 0);
+
+        // The cache trusts an unchanged (starttime, comm): a verdict is not
+        // re-read for the same process image.
+        proc.process(200, "bash", 500, "/bin/bash", &["SteamAppId=1091500"]);
+        assert!(!detector.probe());
+        assert_eq!(detector.scan_stats().inspected, 0);
+    }
+
+    #[test]
+    fn pid_reuse_and_exec_invalidate_the_cached_verdict() {
+        let proc = FakeProc::new("reuse");
+        proc.process(200, "bash", 500, "/bin/bash", &[]);
+        let mut detector = proc.detector();
+        assert!(!detector.probe());
+
+        // Same pid, new process: different starttime.
+        proc.process(
+            200,
+            "wine64",
+            777,
+            "wine64 game.exe",
+            &["SteamAppId=1091500"],
+        );
+        assert!(detector.probe());
+        assert_eq!(detector.scan_stats().inspected, 1);
+
+        proc.process(201, "launcher", 800, "/opt/launcher", &[]);
+        proc.exit(200);
+        assert!(!detector.probe());
+        assert!(!detector.probe());
+        // Same pid and starttime, but the launcher exec'd into the game.
+        proc.process(
+            201,
+            "game.bin",
+            800,
+            "/games/steamapps/common/Game/game.bin",
+            &[],
+        );
+        assert!(detector.probe());
+    }
+
+    #[test]
+    fn a_running_game_is_confirmed_without_a_full_walk() {
+        let proc = FakeProc::new("confirm");
+        for pid in 100..140 {
+            proc.process(pid, "worker", pid as u64, "/usr/bin/worker", &[]);
+        }
+        proc.process(500, "game", 42, "/lib/steamapps/common/G/game", &[]);
+        let mut detector = proc.detector();
+        assert!(detector.probe());
+        assert!(detector.probe());
+        assert_eq!(detector.scan_stats().pids, 1);
+
+        proc.exit(500);
+        assert!(!detector.probe());
+        // The gone game's stat, then every worker.
+        assert_eq!(detector.scan_stats().pids, 41);
+        assert!(!detector.probe());
+        assert_eq!(detector.scan_stats().inspected, 0);
+    }
+
+    #[test]
+    fn exited_pids_leave_the_cache_and_zombies_are_skipped() {
+        let proc = FakeProc::new("exit");
+        proc.process(10, "a", 1, "/bin/a", &[]);
+        proc.process(11, "b", 2, "/bin/b", &[]);
+        proc.process_in_state(12, "dead", 'Z', 3, "", &["SteamAppId=1091500"]);
+        let mut detector = proc.detector();
+        assert!(!detector.probe());
+        assert_eq!(detector.verdicts.len(), 2);
+        proc.exit(11);
+        assert!(!detector.probe());
+        assert_eq!(
+            detector.verdicts.keys().copied().collect::<Vec<_>>(),
+            vec![10]
+        );
+    }
+
+    #[test]
+    fn steam_itself_and_utility_app_ids_are_not_games() {
+        let proc = FakeProc::new("client");
+        proc.process(
+            1,
+            "steam",
+            1,
+            "/home/u/.steam/steam",
+            &["SteamAppId=1091500"],
+        );
+        proc.process(
+            2,
+            "steamwebhelper",
+            2,
+            "steamwebhelper --type=gpu",
+            &["SteamAppId=1091500"],
+        );
+        proc.process(3, "tool", 3, "/bin/tool", &["SteamAppId=228980"]);
+        proc.process(
+            4,
+            "tool",
+            4,
+            "/bin/tool",
+            &["SteamAppId=0", "SteamGameId=abc"],
+        );
+        let mut detector = proc.detector();
+        assert!(!detector.probe());
+
+        proc.process(
+            5,
+            "proton",
+            5,
+            "/bin/proton",
+            &["STEAM_COMPAT_APP_ID=1245620"],
+        );
+        assert!(detector.probe());
+    }
+}
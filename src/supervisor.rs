@@ -0,0 +1,444 @@
+//! `supervise`: a small parent process that runs the renderer as a child and
+//! starts it again when it dies. It only waits, signals and writes files, and
+//! never initialises the GPU or Wayland code the binary also contains, so a
+//! bug there cannot take it down too.
+//!
+//! A child that exits 0 (`quit`) or for quiet hours ends the supervisor with
+//! it; one that fails or is killed is restarted after a delay that doubles
+//! while runs keep ending within [`STABLE_RUN`], up to [`RESTART_DELAY_MAX`].
+//! More than `KRC_SUPERVISE_MAX_RESTARTS` restarts within
+//! `KRC_SUPERVISE_WINDOW_MIN` minutes is a crash loop: the supervisor gives
+//! up and leaves its state file saying so for `status` until the next
+//! `supervise`. On SIGTERM or SIGINT the child is asked to save its runtime
+//! snapshot, then gets the signal; SIGHUP is forwarded as the reload it is.
+//! After a crash the next child restores the last periodic snapshot.
+
+use std::collections::VecDeque;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::control;
+use crate::event_file;
+use crate::file_access;
+use crate::instance::{self, InstanceLock, LockError, instance_id_from_env};
+use crate::journal::Value;
+use crate::json::{JsonValue, escape_json, parse_json};
+use crate::paths::{snapshot_file, supervisor_file, supervisor_lock};
+use crate::quiet_hours::QUIET_EXIT_CODE;
+use crate::snapshot::now_ms;
+
+const DEFAULT_MAX_RESTARTS: u64 = 5;
+const DEFAULT_WINDOW_MIN: u64 = 10;
+
+/// First restart delay after a run that lasted; doubles per quick death.
+const RESTART_DELAY_MIN: Duration = Duration::from_secs(1);
+const RESTART_DELAY_MAX: Duration = Duration::from_secs(30);
+
+/// A run at least this long ends a streak of quick deaths.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// How long a forwarded SIGTERM or SIGINT may take before the child is
+/// killed.
+const STOP_GRACE: Duration = Duration::from_secs(10);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The supervisor's state file, `supervisor.json` in the state dir.
+#[derive(Debug, Clone, Default)]
+pub struct SupervisorState {
+    pub pid: u32,
+    /// `running`, `backoff` (waiting to restart) or `gave-up`; `status`
+    /// reports `dead` for a running or waiting supervisor that is gone.
+    pub state: String,
+    pub child_pid: Option<u32>,
+    pub started_at_ms: u64,
+    /// Restarts since the supervisor started.
+    pub restarts: u64,
+    /// Restarts within the window, against `max_restarts`.
+    pub recent_restarts: u64,
+    pub max_restarts: u64,
+    pub window_sec: u64,
+    pub last_exit: Option<String>,
+    pub last_exit_at_ms: Option<u64>,
+}
+
+impl SupervisorState {
+    pub fn to_json(&self) -> String {
+        let text = |value: &Option<String>| match value {
+            Some(value) => format!("\"{}\"", escape_json(value)),
+            None => "null".to_string(),
+        };
+        let number =
+            |value: Option<u64>| value.map_or_else(|| "null".to_string(), |n| n.to_string());
+        format!(
+            "{{\"pid\":{},\"state\":\"{}\",\"child_pid\":{},\"started_at_ms\":{},\"restarts\":{},\"recent_restarts\":{},\"max_restarts\":{},\"window_sec\":{},\"last_exit\":{},\"last_exit_at_ms\":{}}}",
+            self.pid,
+            escape_json(&self.state),
+            number(self.child_pid.map(u64::from)),
+            self.started_at_ms,
+            self.restarts,
+            self.recent_restarts,
+            self.max_restarts,
+            self.window_sec,
+            text(&self.last_exit),
+            number(self.last_exit_at_ms)
+        )
+    }
+
+    pub fn from_json(raw: &str) -> Result<Self, String> {
+        let value = parse_json(raw)?;
+        let number = |key: &str| value.get(key).and_then(JsonValue::as_u64);
+        let required = |key: &str| number(key).ok_or_else(|| format!("missing {key}"));
+        Ok(Self {
+            pid: required("pid")? as u32,
+            state: value
+                .get("state")
+                .and_then(JsonValue::as_str)
+                .ok_or("missing state")?
+                .to_string(),
+            child_pid: number("child_pid").map(|pid| pid as u32),
+            started_at_ms: required("started_at_ms")?,
+            restarts: required("restarts")?,
+            recent_restarts: required("recent_restarts")?,
+            max_restarts: required("max_restarts")?,
+            window_sec: required("window_sec")?,
+            last_exit: value
+                .get("last_exit")
+                .and_then(JsonValue::as_str)
+                .map(str::to_string),
+            last_exit_at_ms: number("last_exit_at_ms"),
+        })
+    }
+
+    /// The state file of this instance, if there is one; a supervisor that
+    /// no longer holds its lock while the file says it runs reads as `dead`.
+    pub fn load() -> Option<Self> {
+        let instance = instance_id_from_env();
+        let raw = std::fs::read_to_string(supervisor_file(&instance).ok()?).ok()?;
+        let mut state = Self::from_json(&raw).ok()?;
+        let alive = InstanceLock::holder(&supervisor_lock(&instance)).is_some();
+        if state.state != "gave-up" && !alive {
+            state.state = "dead".to_string();
+        }
+        Some(state)
+    }
+
+    fn save(&self, path: &Path) {
+        if let Err(err) =
+            file_access::write_atomic(path, self.to_json().as_bytes(), "supervisor state")
+        {
+            eprintln!("[rendercore] warning: supervisor state not saved: {err}");
+        }
+    }
+}
+
+/// `KRC_SUPERVISE_MAX_RESTARTS` or `KRC_SUPERVISE_WINDOW_MIN`.
+fn env_count(key: &str, default: u64) -> u64 {
+    let Ok(raw) = std::env::var(key) else {
+        return default;
+    };
+    match raw.trim().parse::<u64>() {
+        Ok(n) if n <= 10_000 => n,
+        _ => {
+            eprintln!(
+                "[rendercore] warning: ignoring {key}={raw}: expected 0-10000, using {default}"
+            );
+            default
+        }
+    }
+}
+
+fn signal_name(signum: i32) -> String {
+    match signum {
+        libc::SIGHUP => "SIGHUP".to_string(),
+        libc::SIGINT => "SIGINT".to_string(),
+        libc::SIGILL => "SIGILL".to_string(),
+        libc::SIGABRT => "SIGABRT".to_string(),
+        libc::SIGBUS => "SIGBUS".to_string(),
+        libc::SIGFPE => "SIGFPE".to_string(),
+        libc::SIGKILL => "SIGKILL".to_string(),
+        libc::SIGSEGV => "SIGSEGV".to_string(),
+        libc::SIGTERM => "SIGTERM".to_string(),
+        other => format!("signal {other}"),
+    }
+}
+
+/// How the child ended, in the words of a crash report: a panic exits 101,
+/// a crash is a signal and may have left a core dump.
+fn describe_exit(status: ExitStatus) -> String {
+    if let Some(signum) = status.signal() {
+        let core = if status.core_dumped() {
+            ", core dumped"
+        } else {
+            ""
+        };
+        return format!("killed by {}{core}", signal_name(signum));
+    }
+    match status.code() {
+        Some(101) => "panicked (exit 101)".to_string(),
+        Some(code) => format!("exited with code {code}"),
+        None => status.to_string(),
+    }
+}
+
+fn spawn_child(renderer_args: &[String]) -> Result<Child, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("cannot locate own binary: {e}"))?;
+    Command::new(exe)
+        .args(renderer_args)
+        .spawn()
+        .map_err(|e| format!("failed to start the renderer: {e}"))
+}
+
+/// Waits for the child, forwarding signals. Returns its exit status and
+/// whether it was asked to stop.
+fn wait_child(child: &mut Child) -> Result<(ExitStatus, bool), String> {
+    let mut stop_sent_at = None::<Instant>;
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| format!("failed to wait for the renderer: {e}"))?
+        {
+            // A stop sent to the whole process group (Ctrl+C, `timeout`)
+            // may end the child before the poll below sees it.
+            let stopping = stop_sent_at.is_some() || instance::shutdown_requested().is_some();
+            return Ok((status, stopping));
+        }
+        match (instance::shutdown_requested(), stop_sent_at) {
+            (Some(signum), None) => {
+                println!(
+                    "[rendercore] supervisor: {} received, stopping the renderer (pid {})",
+                    signal_name(signum),
+                    child.id()
+                );
+                // The renderer does not catch the signal; have it save its
+                // state first so the next start restores the latest.
+                if control::request("snapshot").is_err() {
+                    eprintln!(
+                        "[rendercore] warning: supervisor: renderer did not save its runtime snapshot; the next start restores the last periodic one"
+                    );
+                }
+                let _ = instance::send_signal(child.id(), signum);
+                stop_sent_at = Some(Instant::now());
+            }
+            (Some(_), Some(at)) if at.elapsed() >= STOP_GRACE => {
+                eprintln!(
+                    "[rendercore] warning: supervisor: renderer (pid {}) did not stop within {}s, killing it",
+                    child.id(),
+                    STOP_GRACE.as_secs()
+                );
+                let _ = instance::send_signal(child.id(), libc::SIGKILL);
+            }
+            _ => {}
+        }
+        if instance::take_hangup() {
+            let _ = instance::send_signal(child.id(), libc::SIGHUP);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Sleeps out a restart delay; false when a stop arrived meanwhile.
+fn wait_restart(delay: Duration) -> bool {
+    let until = Instant::now() + delay;
+    while Instant::now() < until {
+        if instance::shutdown_requested().is_some() {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL.min(until.saturating_duration_since(Instant::now())));
+    }
+    true
+}
+
+/// Moves the runtime snapshot out of the way after the child died soon after
+/// starting twice in a row: the state it restores may be what kills it.
+fn set_snapshot_aside(instance: &str) {
+    let Ok(path) = snapshot_file(instance) else {
+        return;
+    };
+    if !path.exists() {
+        return;
+    }
+    let aside = path.with_extension("json.crashed");
+    match std::fs::rename(&path, &aside) {
+        Ok(()) => println!(
+            "[rendercore] supervisor: renderer died soon after starting twice in a row; runtime snapshot set aside as {}",
+            aside.display()
+        ),
+        Err(err) => eprintln!(
+            "[rendercore] warning: supervisor: cannot set {} aside: {err}",
+            path.display()
+        ),
+    }
+}
+
+/// `supervise [RENDERER FLAGS]`: runs and restarts the renderer.
+pub fn run_supervise(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        print_supervise_help();
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--daemonize") {
+        return Err("supervise runs in the foreground; drop --daemonize".to_string());
+    }
+    let instance = instance_id_from_env();
+    let _lock = match InstanceLock::acquire(&supervisor_lock(&instance)) {
+        Ok(lock) => lock,
+        Err(LockError::Held(pid)) => {
+            return Err(format!(
+                "a supervisor for instance '{instance}' is already running (pid {})",
+                pid.map_or_else(|| "?".to_string(), |pid| pid.to_string())
+            ));
+        }
+        Err(LockError::Io(err)) => return Err(err),
+    };
+    let state_path = supervisor_file(&instance)?;
+    let previous = std::fs::read_to_string(&state_path)
+        .ok()
+        .and_then(|raw| SupervisorState::from_json(&raw).ok());
+    if let Some(previous) = previous.filter(|s| s.state == "gave-up") {
+        println!(
+            "[rendercore] supervisor: clearing the failure marker of the last supervisor ({} restarts, last exit: {})",
+            previous.restarts,
+            previous.last_exit.as_deref().unwrap_or("unknown")
+        );
+    }
+    event_file::init_from_env();
+    instance::hold_shutdown_signals();
+    instance::install_hangup_handler();
+
+    let max_restarts = env_count("KRC_SUPERVISE_MAX_RESTARTS", DEFAULT_MAX_RESTARTS);
+    let window =
+        Duration::from_secs(env_count("KRC_SUPERVISE_WINDOW_MIN", DEFAULT_WINDOW_MIN) * 60);
+    let mut state = SupervisorState {
+        pid: std::process::id(),
+        state: "running".to_string(),
+        started_at_ms: now_ms(),
+        max_restarts,
+        window_sec: window.as_secs(),
+        ..SupervisorState::default()
+    };
+    println!(
+        "[rendercore] supervisor: watching the renderer, at most {max_restarts} restarts in {} min",
+        window.as_secs() / 60
+    );
+    let mut recent = VecDeque::<Instant>::new();
+    let mut quick_deaths = 0u32;
+    loop {
+        let mut child = spawn_child(args)?;
+        let started = Instant::now();
+        state.state = "running".to_string();
+        state.child_pid = Some(child.id());
+        state.save(&state_path);
+        let child_pid = child.id();
+        let (status, stopping) = wait_child(&mut child)?;
+        let exit = describe_exit(status);
+        state.child_pid = None;
+        state.last_exit = Some(exit.clone());
+        state.last_exit_at_ms = Some(now_ms());
+        if stopping || status.success() || status.code() == Some(QUIET_EXIT_CODE) {
+            println!("[rendercore] supervisor: renderer {exit}, stopping");
+            let _ = std::fs::remove_file(&state_path);
+            if status.code() == Some(QUIET_EXIT_CODE) {
+                std::process::exit(QUIET_EXIT_CODE);
+            }
+            return Ok(());
+        }
+        let ran = started.elapsed();
+        quick_deaths = if ran >= STABLE_RUN {
+            0
+        } else {
+            quick_deaths + 1
+        };
+        let now = Instant::now();
+        while recent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > window)
+        {
+            recent.pop_front();
+        }
+        state.recent_restarts = recent.len() as u64;
+        if state.recent_restarts >= max_restarts {
+            state.state = "gave-up".to_string();
+            state.save(&state_path);
+            event_file::record_now(
+                "supervisor_gave_up",
+                None,
+                &[
+                    ("exit", Value::Str(&exit)),
+                    ("pid", Value::U64(u64::from(child_pid))),
+                    ("restarts", Value::U64(state.restarts)),
+                    ("window_sec", Value::U64(state.window_sec)),
+                ],
+            );
+            return Err(format!(
+                "renderer (pid {child_pid}) {exit}; {} restarts within {} min already, giving up (see 'kitsune-rendercore status')",
+                state.recent_restarts,
+                window.as_secs() / 60
+            ));
+        }
+        if quick_deaths == 2 {
+            set_snapshot_aside(&instance);
+        }
+        let delay = RESTART_DELAY_MIN
+            .saturating_mul(1u32 << quick_deaths.min(8))
+            .min(RESTART_DELAY_MAX);
+        let hint = if status.core_dumped() {
+            format!(" (coredumpctl info {child_pid})")
+        } else {
+            String::new()
+        };
+        eprintln!(
+            "[rendercore] supervisor: renderer (pid {child_pid}) {exit} after {}s{hint}; restarting in {}s",
+            ran.as_secs(),
+            delay.as_secs()
+        );
+        recent.push_back(now);
+        state.restarts += 1;
+        state.recent_restarts = recent.len() as u64;
+        state.state = "backoff".to_string();
+        state.save(&state_path);
+        event_file::record_now(
+            "supervisor_restart",
+            None,
+            &[
+                ("exit", Value::Str(&exit)),
+                ("pid", Value::U64(u64::from(child_pid))),
+                ("ran_ms", Value::U64(ran.as_millis() as u64)),
+                ("restarts", Value::U64(state.restarts)),
+                ("delay_ms", Value::U64(delay.as_millis() as u64)),
+            ],
+        );
+        if !wait_restart(delay) {
+            println!("[rendercore] supervisor: stop requested while waiting to restart");
+            let _ = std::fs::remove_file(&state_path);
+            return Ok(());
+        }
+    }
+}
+
+fn print_supervise_help() {
+    println!("Usage: kitsune-rendercore supervise [--replace] [--no-hooks]");
+    println!();
+    println!("Runs the renderer as a child process and starts it again when it crashes,");
+    println!("panics or is killed. The flags are passed to the renderer.");
+    println!();
+    println!("  A clean exit ('quit', quiet hours) ends the supervisor too. Restarts wait");
+    println!("  1s, doubling while the renderer keeps dying within a minute, up to 30s.");
+    println!(
+        "  More than KRC_SUPERVISE_MAX_RESTARTS (default {DEFAULT_MAX_RESTARTS}) restarts within"
+    );
+    println!(
+        "  KRC_SUPERVISE_WINDOW_MIN (default {DEFAULT_WINDOW_MIN}) minutes is a crash loop: it gives up,"
+    );
+    println!("  exits 1 and 'status' shows it until the next supervise.");
+    println!("  SIGTERM/SIGINT stop the renderer (which saves its runtime snapshot) and");
+    println!("  then the supervisor; SIGHUP is passed on as a reload. After a crash the");
+    println!("  next renderer restores the last snapshot, saved every 30s; one that dies");
+    println!("  soon after starting twice in a row sets it aside as *.json.crashed.");
+    println!();
+    println!("  Restarts and giving up are written to KRC_EVENT_LOG as supervisor_restart");
+    println!("  and supervisor_gave_up.");
+}
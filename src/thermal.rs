@@ -0,0 +1,221 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::journal::{self, Value};
+
+/// How often temperature and CPU pressure are read.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The renderer resumes once the temperature is this far below the pause
+/// threshold, so a package hovering at the limit does not flap.
+const RESUME_MARGIN_C: f64 = 5.0;
+
+/// CPU pressure must stay under its threshold this long before resuming.
+const PRESSURE_CALM_FOR: Duration = Duration::from_secs(30);
+
+/// Thermal zone types that measure the CPU package; other zones (Wi-Fi, SSD,
+/// battery) are only used when none of these exist.
+const CPU_ZONE_TYPES: [&str; 5] = ["x86_pkg_temp", "cpu", "soc", "k10temp", "tctl"];
+
+const PRESSURE_FILE: &str = "/proc/pressure/cpu";
+
+/// The last readings, for `status`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThermalReadings {
+    pub temp_c: Option<f64>,
+    /// PSI `some avg10` as a fraction of time (0.0-1.0).
+    pub cpu_pressure: Option<f64>,
+}
+
+/// Freezes the wallpaper while the CPU runs hot or starved
+/// (`KRC_THERMAL_PAUSE_C`, `KRC_CPU_PRESSURE_PAUSE`). Both rules are off by
+/// default; a rule whose source does not exist on this machine is reported
+/// once at startup and never read again.
+pub struct ThermalDetector {
+    pause_c: Option<f64>,
+    pressure_pause: Option<f64>,
+    zones: Vec<PathBuf>,
+    readings: ThermalReadings,
+    hot: bool,
+    starved: bool,
+    pressure_calm_since: Option<Instant>,
+}
+
+impl ThermalDetector {
+    pub fn from_env() -> Self {
+        let mut pause_c = threshold("KRC_THERMAL_PAUSE_C", 1.0..=150.0);
+        let mut pressure_pause = threshold("KRC_CPU_PRESSURE_PAUSE", 0.01..=1.0);
+        let zones = if pause_c.is_some() {
+            cpu_thermal_zones()
+        } else {
+            Vec::new()
+        };
+        if pause_c.is_some() && zones.is_empty() {
+            println!(
+                "[rendercore] KRC_THERMAL_PAUSE_C ignored: no readable /sys/class/thermal/thermal_zone*/temp"
+            );
+            pause_c = None;
+        }
+        if pressure_pause.is_some() && read_cpu_pressure().is_none() {
+            println!(
+                "[rendercore] KRC_CPU_PRESSURE_PAUSE ignored: {PRESSURE_FILE} is not readable (kernel without PSI?)"
+            );
+            pressure_pause = None;
+        }
+        Self {
+            pause_c,
+            pressure_pause,
+            zones,
+            readings: ThermalReadings::default(),
+            hot: false,
+            starved: false,
+            pressure_calm_since: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.pause_c.is_some() || self.pressure_pause.is_some()
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        POLL_INTERVAL
+    }
+
+    pub fn pause_c(&self) -> Option<f64> {
+        self.pause_c
+    }
+
+    pub fn pressure_pause(&self) -> Option<f64> {
+        self.pressure_pause
+    }
+
+    pub fn readings(&self) -> ThermalReadings {
+        self.readings
+    }
+
+    /// Whether the rule currently holds the renderer paused.
+    pub fn is_active(&self) -> bool {
+        self.hot || self.starved
+    }
+
+    /// Reads both sources and returns whether the renderer should stay
+    /// paused. A failed read keeps the previous verdict rather than resuming.
+    pub fn probe(&mut self, now: Instant) -> bool {
+        let was_active = self.is_active();
+        if let Some(pause_c) = self.pause_c {
+            self.readings.temp_c = self
+                .zones
+                .iter()
+                .filter_map(|zone| read_millidegrees(zone))
+                .reduce(f64::max);
+            if let Some(temp) = self.readings.temp_c {
+                self.hot = if self.hot {
+                    temp > pause_c - RESUME_MARGIN_C
+                } else {
+                    temp >= pause_c
+                };
+            }
+        }
+        if let Some(limit) = self.pressure_pause {
+            self.readings.cpu_pressure = read_cpu_pressure();
+            if let Some(pressure) = self.readings.cpu_pressure {
+                if pressure >= limit {
+                    self.starved = true;
+                    self.pressure_calm_since = None;
+                } else if self.starved {
+                    let calm_since = *self.pressure_calm_since.get_or_insert(now);
+                    self.starved = now.duration_since(calm_since) < PRESSURE_CALM_FOR;
+                }
+            }
+        }
+        let active = self.is_active();
+        if active != was_active {
+            journal::record(
+                "thermal",
+                &[
+                    ("active", Value::Bool(active)),
+                    (
+                        "temp_c",
+                        Value::U64(self.readings.temp_c.unwrap_or(0.0).max(0.0).round() as u64),
+                    ),
+                    (
+                        "cpu_pressure_pct",
+                        Value::U64(
+                            (self.readings.cpu_pressure.unwrap_or(0.0) * 100.0).round() as u64
+                        ),
+                    ),
+                ],
+            );
+        }
+        active
+    }
+}
+
+/// A numeric threshold from the environment; unset, `0` or out of `range`
+/// turns the rule off (the latter with a note).
+fn threshold(key: &str, range: std::ops::RangeInclusive<f64>) -> Option<f64> {
+    let raw = std::env::var(key).ok()?;
+    match raw.trim().parse::<f64>() {
+        Ok(0.0) => None,
+        Ok(value) if range.contains(&value) => Some(value),
+        _ => {
+            eprintln!(
+                "[rendercore] ignoring {key}={raw}: expected {}-{} or 0 for off",
+                range.start(),
+                range.end()
+            );
+            None
+        }
+    }
+}
+
+/// The `temp` files of the CPU package zones, else of every zone.
+fn cpu_thermal_zones() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/thermal") else {
+        return Vec::new();
+    };
+    let zones = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("thermal_zone"))
+        })
+        .filter(|path| read_millidegrees(&path.join("temp")).is_some())
+        .collect::<Vec<_>>();
+    let is_cpu = |zone: &PathBuf| {
+        std::fs::read_to_string(zone.join("type")).is_ok_and(|kind| {
+            let kind = kind.trim().to_ascii_lowercase();
+            CPU_ZONE_TYPES.iter().any(|cpu| kind.contains(cpu))
+        })
+    };
+    let cpu = zones.iter().filter(|zone| is_cpu(zone)).collect::<Vec<_>>();
+    let chosen = if cpu.is_empty() {
+        zones.iter().collect()
+    } else {
+        cpu
+    };
+    chosen.into_iter().map(|zone| zone.join("temp")).collect()
+}
+
+fn read_millidegrees(path: &Path) -> Option<f64> {
+    let raw = std::fs::read_to_string(path).ok()?;
+    raw.trim()
+        .parse::<i64>()
+        .ok()
+        .map(|milli| milli as f64 / 1000.0)
+}
+
+/// `some avg10=` of `/proc/pressure/cpu`, as a fraction.
+fn read_cpu_pressure() -> Option<f64> {
+    let raw = std::fs::read_to_string(PRESSURE_FILE).ok()?;
+    let some = raw.lines().find(|line| line.starts_with("some "))?;
+    let avg10 = some
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?;
+    avg10
+        .parse::<f64>()
+        .ok()
+        .map(|percent| (percent / 100.0).clamp(0.0, 1.0))
+}
@@ -0,0 +1,344 @@
+//! What the render clock counts (`KRC_TIMEBASE`, or an entry's `timebase=`).
+//! `monotonic`, the default, counts from the renderer's start. `utc` counts
+//! wall-clock time since the Unix epoch plus `KRC_TIME_OFFSET_MS`, so
+//! renderers on different machines, started at different times, agree on it:
+//! a video wall lines up across them.
+//!
+//! Under `utc`, with the same videos, map options, `KRC_VIDEO_FPS`,
+//! `KRC_VIDEO_SPEED` and `KRC_AMBIENT_SEED` everywhere and the clocks kept in
+//! step by NTP:
+//!
+//! - the shader's `time_sec` (wave, particles) and the ambient motion depend
+//!   on the wall clock alone, so the same instant draws the same frame;
+//! - a video plays the frame at `(t * speed) mod length` of its trim window
+//!   or file: its decoder opens at that position and is then paced against
+//!   the clock instead of a frame timer; one that gets more than
+//!   [`RESEEK_AFTER`] off it (a stall, a paused output resuming, a clock
+//!   step) reopens at the right position.
+//!
+//! Corrections of the system clock up to [`MAX_SLEW`] are absorbed by running
+//! the clock at most twice as fast, or holding it, until it has caught up: no
+//! frame moves by more than one frame's worth of time, and a one-second
+//! correction is gone within a second. A larger step is taken at once and
+//! logged.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Largest system clock correction smoothed rather than stepped.
+pub const MAX_SLEW: Duration = Duration::from_secs(1);
+
+/// How far a decoder may be off the clock before it reopens at the position
+/// the clock gives.
+pub const RESEEK_AFTER: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timebase {
+    #[default]
+    Monotonic,
+    Utc,
+}
+
+impl Timebase {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "monotonic" => Ok(Self::Monotonic),
+            "utc" => Ok(Self::Utc),
+            other => Err(format!(
+                "invalid timebase '{other}' (expected monotonic|utc)"
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Monotonic => "monotonic",
+            Self::Utc => "utc",
+        }
+    }
+
+    /// `KRC_TIMEBASE`, read once; unset or unreadable is monotonic.
+    pub fn default_from_env() -> Self {
+        static DEFAULT: OnceLock<Timebase> = OnceLock::new();
+        *DEFAULT.get_or_init(|| {
+            let Ok(raw) = std::env::var("KRC_TIMEBASE") else {
+                return Timebase::Monotonic;
+            };
+            Timebase::parse(&raw).unwrap_or_else(|err| {
+                eprintln!("[rendercore] ignoring KRC_TIMEBASE={raw}: {err}");
+                Timebase::Monotonic
+            })
+        })
+    }
+
+    /// An entry's `timebase=`, else `KRC_TIMEBASE`.
+    pub fn resolve(entry: Option<Self>) -> Self {
+        entry.unwrap_or_else(Self::default_from_env)
+    }
+}
+
+/// `KRC_TIME_OFFSET_MS`, read once: added to the `utc` clock, so a machine
+/// can be shifted against the others. May be negative.
+pub fn offset_ms() -> i64 {
+    static OFFSET: OnceLock<i64> = OnceLock::new();
+    *OFFSET.get_or_init(|| {
+        let Ok(raw) = std::env::var("KRC_TIME_OFFSET_MS") else {
+            return 0;
+        };
+        raw.trim().parse().unwrap_or_else(|_| {
+            eprintln!("[rendercore] ignoring KRC_TIME_OFFSET_MS={raw}: expected milliseconds");
+            0
+        })
+    })
+}
+
+/// The system clock in seconds since the epoch, plus the offset; unsmoothed.
+fn wall_secs() -> f64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64());
+    now + offset_ms() as f64 / 1000.0
+}
+
+/// The `utc` clock as a decoder opening off the render thread reads it:
+/// without the smoothing, which only ever differs by less than [`MAX_SLEW`]
+/// and is paced away once frames flow.
+#[cfg_attr(not(feature = "video-ffmpeg"), allow(dead_code))]
+pub fn wall_clock() -> Duration {
+    Duration::from_secs_f64(wall_secs().max(0.0))
+}
+
+/// Both clocks at one instant.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockReading {
+    pub monotonic: Duration,
+    /// Zero when no output asked for it.
+    pub utc: Duration,
+}
+
+impl ClockReading {
+    pub fn get(self, timebase: Timebase) -> Duration {
+        match timebase {
+            Timebase::Monotonic => self.monotonic,
+            Timebase::Utc => self.utc,
+        }
+    }
+}
+
+/// The render clock: time since the renderer started, and the smoothed
+/// `utc` clock.
+pub struct RenderClock {
+    started_at: Instant,
+    /// Wall-clock seconds minus monotonic seconds as applied; moves toward
+    /// the system clock's at most one second per second. `None` until the
+    /// `utc` clock is first read.
+    skew: Option<f64>,
+    read_at: Instant,
+}
+
+impl RenderClock {
+    pub fn from_env() -> Self {
+        let timebase = Timebase::default_from_env();
+        let offset = offset_ms();
+        if timebase != Timebase::Monotonic || offset != 0 {
+            println!(
+                "[rendercore] timebase: {} (KRC_TIME_OFFSET_MS={offset:+})",
+                timebase.as_str()
+            );
+        }
+        let now = Instant::now();
+        Self {
+            started_at: now,
+            skew: None,
+            read_at: now,
+        }
+    }
+
+    /// Reads the clocks; `utc` only when some output uses it, so the
+    /// smoothing (and its log line) starts fresh when one does again.
+    pub fn read(&mut self, utc: bool) -> ClockReading {
+        self.read_at(Instant::now(), utc.then(wall_secs))
+    }
+
+    /// [`RenderClock::read`] at `now`, with the system clock (plus the
+    /// offset) reading `wall` seconds; `None` when no output uses `utc`.
+    fn read_at(&mut self, now: Instant, wall: Option<f64>) -> ClockReading {
+        let monotonic = now.saturating_duration_since(self.started_at);
+        let Some(wall) = wall else {
+            self.skew = None;
+            return ClockReading {
+                monotonic,
+                utc: Duration::ZERO,
+            };
+        };
+        let mono = monotonic.as_secs_f64();
+        let target = wall - mono;
+        let skew = match self.skew {
+            Some(skew) if (target - skew).abs() <= MAX_SLEW.as_secs_f64() => {
+                let step = now.saturating_duration_since(self.read_at).as_secs_f64();
+                skew + (target - skew).clamp(-step, step)
+            }
+            Some(skew) => {
+                println!(
+                    "[rendercore] system clock stepped by {:+.3}s; utc timebase follows at once",
+                    target - skew
+                );
+                target
+            }
+            None => target,
+        };
+        self.skew = Some(skew);
+        self.read_at = now;
+        ClockReading {
+            monotonic,
+            utc: Duration::from_secs_f64((mono + skew).max(0.0)),
+        }
+    }
+}
+
+/// Where a video looping the `length_ms` window from `start_ms` is at
+/// `clock`, played at `speed`.
+#[cfg_attr(not(feature = "video-ffmpeg"), allow(dead_code))]
+pub fn video_position_ms(clock: Duration, speed: f32, start_ms: u64, length_ms: u64) -> u64 {
+    let played = (clock.as_secs_f64() * 1000.0 * f64::from(speed)) as u64;
+    start_ms + played % length_ms.max(1)
+}
+
+/// How far `position_ms` is ahead of `target_ms` (negative: behind) in a
+/// `length_ms` loop, the shorter way round.
+#[cfg_attr(not(feature = "video-ffmpeg"), allow(dead_code))]
+pub fn lead_ms(position_ms: u64, target_ms: u64, length_ms: u64) -> i64 {
+    let length = length_ms.max(1) as i64;
+    let diff = (position_ms as i64 - target_ms as i64).rem_euclid(length);
+    if diff > length / 2 {
+        diff - length
+    } else {
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-17 12:00:00 UTC.
+    const NOON: f64 = 1_792_238_400.0;
+
+    fn clock(started_at: Instant) -> RenderClock {
+        RenderClock {
+            started_at,
+            skew: None,
+            read_at: started_at,
+        }
+    }
+
+    /// The frame a `fps` video looping `length_ms` from `start_ms` shows.
+    fn frame_index(utc: Duration, fps: u64, start_ms: u64, length_ms: u64) -> u64 {
+        video_position_ms(utc, 1.0, start_ms, length_ms) * fps / 1000
+    }
+
+    #[test]
+    fn renderers_started_apart_agree_on_the_utc_window() {
+        let base = Instant::now();
+        // One machine has been up for an hour, the other starts now.
+        let mut early = clock(base);
+        let mut late = clock(base + Duration::from_secs(3600));
+        let mut frames = (Vec::new(), Vec::new());
+        for step in 0..600u64 {
+            let elapsed = Duration::from_millis(step * 16);
+            let wall = NOON + elapsed.as_secs_f64();
+            let a = early.read_at(base + Duration::from_secs(3600) + elapsed, Some(wall));
+            let b = late.read_at(base + Duration::from_secs(3600) + elapsed, Some(wall));
+            assert_ne!(a.monotonic, b.monotonic);
+            assert!((a.utc.as_secs_f64() - b.utc.as_secs_f64()).abs() < 1e-6);
+            assert_eq!(a.get(Timebase::Monotonic), a.monotonic);
+            frames.0.push(frame_index(a.utc, 30, 2_000, 61_234));
+            frames.1.push(frame_index(b.utc, 30, 2_000, 61_234));
+        }
+        assert_eq!(frames.0, frames.1);
+        // The window covers about 9.6s of a 30 fps video.
+        let distinct = frames.0.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!((280..=290).contains(&distinct), "{distinct}");
+    }
+
+    #[test]
+    fn monotonic_reads_leave_utc_at_zero() {
+        let base = Instant::now();
+        let mut clock = clock(base);
+        let reading = clock.read_at(base + Duration::from_secs(5), None);
+        assert_eq!(reading.monotonic, Duration::from_secs(5));
+        assert_eq!(reading.get(Timebase::Utc), Duration::ZERO);
+        assert_eq!(clock.skew, None);
+    }
+
+    #[test]
+    fn small_corrections_are_slewed_within_a_frame() {
+        let base = Instant::now();
+        let mut clock = clock(base);
+        let frame = Duration::from_millis(16);
+        let mut last = clock.read_at(base, Some(NOON)).utc;
+        // NTP pulls the clock back by 0.8s.
+        let mut caught_up = None;
+        for step in 1..=200u32 {
+            let elapsed = frame * step;
+            let wall = NOON - 0.8 + elapsed.as_secs_f64();
+            let utc = clock.read_at(base + elapsed, Some(wall)).utc;
+            let advance = utc.as_secs_f64() - last.as_secs_f64();
+            assert!(
+                (-1e-6..=2.0 * frame.as_secs_f64() + 1e-6).contains(&advance),
+                "step {step}: {advance}"
+            );
+            if caught_up.is_none() && (utc.as_secs_f64() - wall).abs() < 1e-6 {
+                caught_up = Some(elapsed);
+            }
+            last = utc;
+        }
+        let caught_up = caught_up.unwrap();
+        assert!(caught_up <= Duration::from_millis(850), "{caught_up:?}");
+    }
+
+    #[test]
+    fn large_steps_are_taken_at_once() {
+        let base = Instant::now();
+        let mut clock = clock(base);
+        clock.read_at(base, Some(NOON));
+        let later = base + Duration::from_millis(16);
+        let utc = clock.read_at(later, Some(NOON + 30.016)).utc;
+        assert!((utc.as_secs_f64() - (NOON + 30.016)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn positions_loop_inside_the_window() {
+        assert_eq!(
+            video_position_ms(Duration::from_millis(10_500), 1.0, 2_000, 4_000),
+            4_500
+        );
+        assert_eq!(
+            video_position_ms(Duration::from_millis(10_500), 2.0, 0, 4_000),
+            1_000
+        );
+        // An unknown length does not divide by zero.
+        assert_eq!(video_position_ms(Duration::from_secs(3), 1.0, 7, 0), 7);
+    }
+
+    #[test]
+    fn lead_takes_the_shorter_way_round_the_loop() {
+        assert_eq!(lead_ms(1_500, 1_000, 10_000), 500);
+        assert_eq!(lead_ms(1_000, 1_500, 10_000), -500);
+        assert_eq!(lead_ms(9_900, 100, 10_000), -200);
+        assert_eq!(lead_ms(100, 9_900, 10_000), 200);
+        assert_eq!(lead_ms(5, 5, 0), 0);
+    }
+
+    #[test]
+    fn timebases_parse() {
+        assert_eq!(Timebase::parse(" UTC "), Ok(Timebase::Utc));
+        assert_eq!(Timebase::parse("monotonic"), Ok(Timebase::Monotonic));
+        assert!(
+            Timebase::parse("tai")
+                .unwrap_err()
+                .contains("monotonic|utc")
+        );
+        assert_eq!(Timebase::resolve(Some(Timebase::Utc)), Timebase::Utc);
+    }
+}
@@ -0,0 +1,287 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config_file::ConfigFile;
+use crate::env_file;
+
+/// External programs the renderer and CLI shell out to. Each one resolves as
+/// `KRC_<TOOL>` (env or `KRC_ENV_FILE`), then `[tools] <name>` in config.toml,
+/// then a `$PATH` lookup, so a sandbox can point at wherever it mounts them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Ffmpeg,
+    Ffprobe,
+    Hyprctl,
+    Systemctl,
+    SystemdRun,
+    Journalctl,
+    Kitowall,
+    Tar,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 8] = [
+        Tool::Ffmpeg,
+        Tool::Ffprobe,
+        Tool::Hyprctl,
+        Tool::Systemctl,
+        Tool::SystemdRun,
+        Tool::Journalctl,
+        Tool::Kitowall,
+        Tool::Tar,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Ffmpeg => "ffmpeg",
+            Tool::Ffprobe => "ffprobe",
+            Tool::Hyprctl => "hyprctl",
+            Tool::Systemctl => "systemctl",
+            Tool::SystemdRun => "systemd-run",
+            Tool::Journalctl => "journalctl",
+            Tool::Kitowall => "kitowall",
+            Tool::Tar => "tar",
+        }
+    }
+
+    pub fn env_key(self) -> &'static str {
+        match self {
+            Tool::Ffmpeg => "KRC_FFMPEG",
+            Tool::Ffprobe => "KRC_FFPROBE",
+            Tool::Hyprctl => "KRC_HYPRCTL",
+            Tool::Systemctl => "KRC_SYSTEMCTL",
+            Tool::SystemdRun => "KRC_SYSTEMD_RUN",
+            Tool::Journalctl => "KRC_JOURNALCTL",
+            Tool::Kitowall => "KRC_KITOWALL",
+            Tool::Tar => "KRC_TAR",
+        }
+    }
+
+    /// What breaks without it, for `doctor`.
+    pub fn purpose(self) -> &'static str {
+        match self {
+            Tool::Ffmpeg => "video decoding",
+            Tool::Ffprobe => "checking set-video --trim against the video length",
+            Tool::Hyprctl => "monitor names from Hyprland (falls back to Wayland)",
+            Tool::Systemctl => "service commands and status service state",
+            Tool::SystemdRun => "restarting the service after KRC_QUIET_HOURS",
+            Tool::Journalctl => "service logs",
+            Tool::Kitowall => "install-deps, check-deps, install-service",
+            Tool::Tar => "export-config and import-config bundles (.tar.zst also needs zstd)",
+        }
+    }
+
+    /// Only ffmpeg is needed to render; the rest back optional CLI features.
+    pub fn required(self) -> bool {
+        self == Tool::Ffmpeg && self.used()
+    }
+
+    /// Whether this build ever runs it: a build without `video-ffmpeg`
+    /// spawns neither ffmpeg nor ffprobe.
+    pub fn used(self) -> bool {
+        cfg!(feature = "video-ffmpeg") || !matches!(self, Tool::Ffmpeg | Tool::Ffprobe)
+    }
+
+    /// Configured path or bare name, before any `$PATH` lookup.
+    fn configured(self) -> Option<(String, &'static str)> {
+        if let Some(value) = env_file::var(self.env_key()).filter(|v| !v.trim().is_empty()) {
+            return Some((value.trim().to_string(), self.env_key()));
+        }
+        ConfigFile::load_from_env()
+            .get("tools", self.name())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| (v.to_string(), "config.toml [tools]"))
+    }
+
+    /// The program to run: the configured value, else the `$PATH` match, else
+    /// the bare name (so spawning reports it as missing).
+    pub fn program(self) -> PathBuf {
+        match self.configured() {
+            Some((value, _)) => PathBuf::from(value),
+            None => find_in_path(self.name()).unwrap_or_else(|| PathBuf::from(self.name())),
+        }
+    }
+
+    /// The resolved executable and where it came from, or `None` when it
+    /// cannot be found.
+    pub fn locate(self) -> Option<(PathBuf, &'static str)> {
+        self.locate_configured(self.configured())
+    }
+
+    /// [`Tool::locate`] with `configured` as the configured value and where
+    /// it was set: a path must be an executable file, a bare name is looked
+    /// up in `$PATH`.
+    pub fn locate_configured(
+        self,
+        configured: Option<(String, &'static str)>,
+    ) -> Option<(PathBuf, &'static str)> {
+        match configured {
+            Some((value, source)) => find_in_path(&value).map(|path| (path, source)),
+            None => find_in_path(self.name()).map(|path| (path, "$PATH")),
+        }
+    }
+
+    pub fn command(self) -> Command {
+        Command::new(self.program())
+    }
+
+    /// Spawn error naming the tool and how to point the renderer at it.
+    pub fn spawn_error(self, err: std::io::Error) -> String {
+        let program = self.program();
+        if err.kind() == ErrorKind::NotFound {
+            format!(
+                "{} not found (tried '{}'); install it or set {} or [tools] {} in config.toml",
+                self.name(),
+                program.display(),
+                self.env_key(),
+                self.name()
+            )
+        } else {
+            format!(
+                "failed to execute {} ('{}'): {err}",
+                self.name(),
+                program.display()
+            )
+        }
+    }
+}
+
+/// The video's length in milliseconds, as ffprobe reports it for the container.
+#[cfg(feature = "video-ffmpeg")]
+pub fn probe_duration_ms(video: &str) -> Result<u32, String> {
+    let output = Tool::Ffprobe
+        .command()
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+            video,
+        ])
+        .output()
+        .map_err(|e| Tool::Ffprobe.spawn_error(e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let raw = String::from_utf8_lossy(&output.stdout);
+    raw.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs > 0.0)
+        .map(|secs| (secs * 1000.0).min(u32::MAX as f64) as u32)
+        .ok_or_else(|| format!("ffprobe reported no duration ('{}')", raw.trim()))
+}
+
+/// Width and height of the video's first video stream, as coded (rotation
+/// metadata is not applied).
+#[cfg(feature = "video-ffmpeg")]
+pub fn probe_video_size(video: &str) -> Result<(u32, u32), String> {
+    let output = Tool::Ffprobe
+        .command()
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=width,height",
+            "-of",
+            "csv=s=x:p=0",
+            video,
+        ])
+        .output()
+        .map_err(|e| Tool::Ffprobe.spawn_error(e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let raw = String::from_utf8_lossy(&output.stdout);
+    raw.trim()
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)))
+        .filter(|(w, h)| *w > 0 && *h > 0)
+        .ok_or_else(|| format!("ffprobe reported no video size ('{}')", raw.trim()))
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// Hyprland's IPC socket when it is actually reachable from here; sandboxes
+/// usually pass `HYPRLAND_INSTANCE_SIGNATURE` through without the socket.
+pub fn hyprland_ipc_socket() -> Option<PathBuf> {
+    let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")
+        .ok()
+        .filter(|v| !v.trim().is_empty())?;
+    let mut bases = Vec::new();
+    if let Ok(runtime) = std::env::var("XDG_RUNTIME_DIR") {
+        bases.push(PathBuf::from(runtime).join("hypr"));
+    }
+    bases.push(PathBuf::from("/tmp/hypr"));
+    bases
+        .into_iter()
+        .map(|base| base.join(&signature).join(".socket.sock"))
+        .find(|path| path.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_paths_must_be_executable_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("krc-tools-{}-locate", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let configured = |path: &Path| Some((path.to_string_lossy().into_owned(), "KRC_FFMPEG"));
+        let fake = dir.join("ffmpeg");
+        assert_eq!(Tool::Ffmpeg.locate_configured(configured(&fake)), None);
+        std::fs::write(&fake, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(Tool::Ffmpeg.locate_configured(configured(&fake)), None);
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            Tool::Ffmpeg.locate_configured(configured(&fake)),
+            Some((fake.clone(), "KRC_FFMPEG"))
+        );
+        // A directory is not a program.
+        assert_eq!(Tool::Ffmpeg.locate_configured(configured(&dir)), None);
+        // A bare name is looked up in $PATH.
+        assert_eq!(
+            Tool::Ffmpeg.locate_configured(Some((
+                "krc-no-such-tool".to_string(),
+                "config.toml [tools]"
+            ))),
+            None
+        );
+        let sh = Tool::Ffmpeg
+            .locate_configured(Some(("sh".to_string(), "config.toml [tools]")))
+            .unwrap();
+        assert!(sh.0.is_absolute() && sh.0.ends_with("sh"), "{sh:?}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
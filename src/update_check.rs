@@ -0,0 +1,407 @@
+//! `update-check` and `KRC_UPDATE_CHECK`: compares the built version with the
+//! latest GitHub release and says how to upgrade. Nothing is ever downloaded
+//! or run; the only request is one GET of the releases API.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::file_access::write_atomic;
+use crate::json::{escape_json, parse_json};
+use crate::paths::cache_dir;
+use crate::version::VERSION;
+
+const RELEASES_API: &str =
+    "https://api.github.com/repos/KitotsuMolina/Kitsune-RenderCore/releases/latest";
+const RELEASES_PAGE: &str = "https://github.com/KitotsuMolina/Kitsune-RenderCore/releases";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// The reply is a few KB; anything much larger is not the releases API.
+const MAX_REPLY_BYTES: u64 = 1 << 20;
+
+/// How often the render loop looks at the cache; the request itself is
+/// made at most once per [`Schedule`] period, across restarts.
+pub const TICK: Duration = Duration::from_secs(60 * 60);
+/// Delay before the first tick, so the check never competes with startup.
+pub const FIRST_TICK_AFTER: Duration = Duration::from_secs(120);
+
+/// `KRC_UPDATE_CHECK`: how often the running renderer may ask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    Daily,
+    Weekly,
+}
+
+impl Schedule {
+    /// `None` (the default) when unset or `off`.
+    pub fn from_env() -> Option<Self> {
+        let raw = std::env::var("KRC_UPDATE_CHECK").ok()?;
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "off" | "0" | "false" | "no" => None,
+            "daily" => Some(Self::Daily),
+            "weekly" | "1" | "true" | "yes" | "on" => Some(Self::Weekly),
+            _ => {
+                eprintln!(
+                    "[rendercore] warning: ignoring KRC_UPDATE_CHECK={raw}: expected off, daily or weekly"
+                );
+                None
+            }
+        }
+    }
+
+    fn period(self) -> Duration {
+        match self {
+            Self::Daily => Duration::from_secs(24 * 60 * 60),
+            Self::Weekly => Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+}
+
+/// Last check, kept in the cache dir: when it was made, and the tag it found
+/// (empty when it failed).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Cached {
+    checked: u64,
+    latest: String,
+}
+
+fn cache_file() -> Option<PathBuf> {
+    cache_dir().ok().map(|dir| dir.join("update-check"))
+}
+
+fn read_cache(path: &Path) -> Option<Cached> {
+    let raw = fs::read_to_string(path).ok()?;
+    let mut cached = Cached::default();
+    for line in raw.lines() {
+        match line.split_once('=') {
+            Some(("checked", value)) => cached.checked = value.trim().parse().ok()?,
+            Some(("latest", value)) => cached.latest = value.trim().to_string(),
+            _ => {}
+        }
+    }
+    Some(cached)
+}
+
+fn write_cache(path: &Path, cached: &Cached) -> Result<(), String> {
+    let contents = format!("checked={}\nlatest={}\n", cached.checked, cached.latest);
+    write_atomic(path, contents.as_bytes(), "update-check cache")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// The tag of the latest release (`v2.1.1`).
+fn fetch_latest() -> Result<String, String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .user_agent(format!("kitsune-rendercore/{VERSION}"))
+        .build()
+        .into();
+    let body = agent
+        .get(RELEASES_API)
+        .header("Accept", "application/vnd.github+json")
+        .call()
+        .map_err(|e| format!("GitHub releases API: {e}"))?
+        .body_mut()
+        .with_config()
+        .limit(MAX_REPLY_BYTES)
+        .read_to_string()
+        .map_err(|e| format!("GitHub releases API: {e}"))?;
+    let reply = parse_json(&body).map_err(|e| format!("unreadable releases reply: {e}"))?;
+    reply
+        .get("tag_name")
+        .and_then(|tag| tag.as_str())
+        .filter(|tag| parse_version(tag).is_some())
+        .map(str::to_string)
+        .ok_or_else(|| "releases reply has no version tag".to_string())
+}
+
+/// `v2.1.1` or `2.1.1` as numbers; a pre-release suffix (`-rc1`) is dropped.
+fn parse_version(raw: &str) -> Option<(u64, u64, u64)> {
+    let raw = raw.trim().trim_start_matches('v');
+    let core = raw.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (
+        parts.next()??,
+        parts.next()??,
+        parts.next().unwrap_or(Some(0))?,
+    );
+    parts.next().is_none().then_some(version)
+}
+
+fn is_newer(latest: &str) -> bool {
+    match (parse_version(latest), parse_version(VERSION)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// How this binary was installed, guessed from where it lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InstallMethod {
+    /// `cargo install` into `~/.cargo/bin`.
+    Cargo,
+    /// `scripts/install.sh`: `~/.local/bin`, or `/usr/local/bin` with `--system`.
+    Script,
+    /// A distribution package (the AUR one) under `/usr/bin`.
+    Package,
+    /// A `target/` directory of a checkout.
+    Source,
+    Unknown,
+}
+
+impl InstallMethod {
+    fn detect() -> Self {
+        std::env::current_exe()
+            .map(|exe| Self::from_path(&exe))
+            .unwrap_or(Self::Unknown)
+    }
+
+    fn from_path(exe: &Path) -> Self {
+        let path = exe.to_string_lossy();
+        let dir = exe.parent().map(|dir| dir.to_string_lossy().into_owned());
+        if path.contains("/.cargo/bin/") {
+            Self::Cargo
+        } else if path.contains("/target/release/") || path.contains("/target/debug/") {
+            Self::Source
+        } else if path.starts_with("/usr/local/bin/") || path.contains("/.local/bin/") {
+            Self::Script
+        } else if matches!(dir.as_deref(), Some("/usr/bin" | "/bin")) {
+            Self::Package
+        } else {
+            Self::Unknown
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Cargo => "cargo",
+            Self::Script => "script",
+            Self::Package => "package",
+            Self::Source => "source",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    fn hint(self, tag: &str) -> String {
+        match self {
+            Self::Cargo => format!(
+                "cargo install --git https://github.com/KitotsuMolina/Kitsune-RenderCore --tag {tag} --locked --features wayland-layer --force"
+            ),
+            Self::Script => format!(
+                "in your checkout: git fetch --tags && git checkout {tag} && ./scripts/install.sh (add --system if it lives in /usr/local/bin)"
+            ),
+            Self::Package => {
+                "update it with your package manager (e.g. paru -Syu kitsune-rendercore)"
+                    .to_string()
+            }
+            Self::Source => format!(
+                "git fetch --tags && git checkout {tag} && cargo build --release --features wayland-layer"
+            ),
+            Self::Unknown => format!("see {RELEASES_PAGE}/tag/{tag}"),
+        }
+    }
+}
+
+/// `update-check [--json]`: always asks, and reports network errors.
+pub fn run_update_check(args: &[String]) -> Result<(), String> {
+    let mut as_json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => as_json = true,
+            "--help" | "-h" => {
+                print_update_check_help();
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument for update-check: {other}")),
+        }
+    }
+    let latest = fetch_latest().map_err(|e| format!("update check failed: {e}"))?;
+    if let Some(path) = cache_file() {
+        let cached = Cached {
+            checked: unix_now(),
+            latest: latest.clone(),
+        };
+        if let Err(err) = write_cache(&path, &cached) {
+            eprintln!("[warn] {err}");
+        }
+    }
+    let method = InstallMethod::detect();
+    let newer = is_newer(&latest);
+    if as_json {
+        println!(
+            "{{\"current\":\"{}\",\"latest\":\"{}\",\"update_available\":{newer},\"install_method\":\"{}\",\"hint\":{}}}",
+            escape_json(VERSION),
+            escape_json(&latest),
+            method.as_str(),
+            if newer {
+                format!("\"{}\"", escape_json(&method.hint(&latest)))
+            } else {
+                "null".to_string()
+            }
+        );
+        return Ok(());
+    }
+    if !newer {
+        println!("[ok] up to date: {VERSION} (latest release {latest})");
+        return Ok(());
+    }
+    println!("[info] update available: {VERSION} -> {latest}");
+    println!("[info] release notes: {RELEASES_PAGE}/tag/{latest}");
+    println!(
+        "[info] installed via {}: {}",
+        method.as_str(),
+        method.hint(&latest)
+    );
+    Ok(())
+}
+
+/// The running renderer's side of `KRC_UPDATE_CHECK`: on each [`TICK`] it
+/// asks again once the cached answer is a period old, on a thread of its own
+/// so the render loop never waits on the network. Failures are silent and
+/// still count as a check.
+pub struct PassiveCheck {
+    schedule: Schedule,
+    cache: PathBuf,
+    running: Option<JoinHandle<()>>,
+    /// The tag last logged, so each release is announced once per run.
+    announced: Arc<Mutex<String>>,
+}
+
+impl PassiveCheck {
+    pub fn from_env() -> Option<Self> {
+        let schedule = Schedule::from_env()?;
+        let cache = cache_file()?;
+        println!(
+            "[rendercore] update check: {} (no downloads; cache {})",
+            schedule.as_str(),
+            cache.display()
+        );
+        Some(Self {
+            schedule,
+            cache,
+            running: None,
+            announced: Arc::default(),
+        })
+    }
+
+    pub fn tick(&mut self) {
+        if self.running.as_ref().is_some_and(|job| !job.is_finished()) {
+            return;
+        }
+        self.running = None;
+        let cached = read_cache(&self.cache).unwrap_or_default();
+        let age = unix_now().saturating_sub(cached.checked);
+        if age < self.schedule.period().as_secs() {
+            announce(&self.announced, &cached.latest);
+            return;
+        }
+        let cache = self.cache.clone();
+        let announced = Arc::clone(&self.announced);
+        let job = thread::Builder::new()
+            .name("krc-update-check".to_string())
+            .spawn(move || {
+                let latest = fetch_latest().unwrap_or_default();
+                let cached = Cached {
+                    checked: unix_now(),
+                    latest: latest.clone(),
+                };
+                // Unwritable cache: the next tick asks again, an hour later.
+                let _ = write_cache(&cache, &cached);
+                announce(&announced, &latest);
+            });
+        match job {
+            Ok(job) => self.running = Some(job),
+            Err(err) => eprintln!("[rendercore] warning: update check thread: {err}"),
+        }
+    }
+}
+
+/// Logs `latest` when it is newer than this build and was not logged yet.
+fn announce(announced: &Mutex<String>, latest: &str) {
+    if !is_newer(latest) {
+        return;
+    }
+    let mut announced = announced.lock().unwrap_or_else(|e| e.into_inner());
+    if *announced == latest {
+        return;
+    }
+    *announced = latest.to_string();
+    println!(
+        "[rendercore] update available: {VERSION} -> {latest} ({})",
+        InstallMethod::detect().hint(latest)
+    );
+}
+
+fn print_update_check_help() {
+    println!("kitsune-rendercore update-check");
+    println!("Usage:");
+    println!("  kitsune-rendercore update-check [--json]");
+    println!();
+    println!("Description:");
+    println!("  Asks the GitHub releases API for the latest release over HTTPS and");
+    println!("  says whether it is newer than this build, with an upgrade command that");
+    println!("  fits how this binary was installed (cargo, install script, package or");
+    println!("  source checkout). Nothing is downloaded or run.");
+    println!();
+    println!("  KRC_UPDATE_CHECK=weekly (or daily) makes the running renderer do the same");
+    println!("  in the background at most that often, logging only when a newer release");
+    println!("  exists; failures there are silent.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_round_trips_through_write_atomic() {
+        let dir =
+            std::env::temp_dir().join(format!("krc-update-check-{}-cache", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("kitsune-rendercore/update-check");
+        assert_eq!(read_cache(&path), None);
+        let cached = Cached {
+            checked: 1_760_000_000,
+            latest: "v1.4.0".to_string(),
+        };
+        write_cache(&path, &cached).unwrap();
+        assert_eq!(read_cache(&path), Some(cached));
+        // A failed check is cached with no tag.
+        let failed = Cached {
+            checked: 1_760_086_400,
+            latest: String::new(),
+        };
+        write_cache(&path, &failed).unwrap();
+        assert_eq!(read_cache(&path), Some(failed));
+        let names = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["update-check"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unreadable_cache_timestamps_are_ignored() {
+        let dir =
+            std::env::temp_dir().join(format!("krc-update-check-{}-garbled", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("update-check");
+        fs::write(&path, "checked=soon\nlatest=v1.4.0\n").unwrap();
+        assert_eq!(read_cache(&path), None);
+        fs::write(&path, "latest=v1.4.0\n").unwrap();
+        assert_eq!(read_cache(&path).unwrap().checked, 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
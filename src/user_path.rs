@@ -0,0 +1,411 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Turns a video path as a user typed or pasted it into the path the decoder
+/// opens: a `file://` URI loses its scheme and is percent-decoded, a leading
+/// `~`, `$HOME` or `${HOME}` becomes the home directory, and what is still
+/// relative is resolved against `base` (the map file's directory for map
+/// entries, the working directory for CLI and env input).
+///
+/// Only `~` on its own or followed by `/` is expanded, so a directory that is
+/// literally named `~something` keeps working; a directory named just `~` is
+/// reachable as `./~/...`.
+pub fn normalize_video_path(raw: &str, base: &Path) -> Result<String, String> {
+    let trimmed = raw.trim();
+    if let Some(uri_path) = strip_file_scheme(trimmed)
+        .map_err(|err| format!("cannot use '{raw}': {err} (tried reading a file:// URI)"))?
+    {
+        let decoded = percent_decode(uri_path)
+            .map_err(|err| format!("cannot use '{raw}': {err} (tried decoding a file:// URI)"))?;
+        return Ok(decoded);
+    }
+    let expanded = expand_home(trimmed).map_err(|err| format!("cannot use '{raw}': {err}"))?;
+    if Path::new(&expanded).is_absolute() {
+        return Ok(expanded);
+    }
+    Ok(base.join(expanded).to_string_lossy().into_owned())
+}
+
+/// A path as it goes into logs and terminal output. Control characters (an
+/// ESC or OSC sequence in a downloaded file's name) and bidi overrides come
+/// out as `\x1b` / `\u{202e}` instead of being sent to the terminal, and with
+/// `KRC_LOG_REDACT_HOME=1` the home directory prefix becomes `~`, so logs can
+/// be shared. Meant for reading, not for parsing back; status JSON and the
+/// journal keep the raw path.
+pub fn display_path(raw: &str) -> String {
+    escape_for_terminal(&redact_home(raw, redacted_home()))
+}
+
+/// `raw` with a leading `home` directory written as `~`.
+fn redact_home(raw: &str, home: Option<&str>) -> String {
+    match home.and_then(|home| raw.strip_prefix(home)) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{rest}"),
+        _ => raw.to_string(),
+    }
+}
+
+/// Paths longer than this are shortened by [`display_path_short`].
+const SHORT_PATH_CHARS: usize = 64;
+
+/// [`display_path`] for lines that repeat per output: a long path is cut to
+/// `…/<parent>/<file>`.
+pub fn display_path_short(raw: &str) -> String {
+    let full = display_path(raw);
+    if full.chars().count() <= SHORT_PATH_CHARS {
+        return full;
+    }
+    let mut tail = full.rsplitn(3, '/');
+    match (tail.next(), tail.next(), tail.next()) {
+        (Some(file), Some(parent), Some(_)) if !file.is_empty() => format!("…/{parent}/{file}"),
+        _ => full,
+    }
+}
+
+/// Longest free text (a map line issue, an error) [`display_text`] prints.
+const TEXT_CHARS: usize = 240;
+
+/// Text that may quote user input, such as map file contents, for logs:
+/// escaped like [`display_path`] and cut at a bounded length.
+pub fn display_text(raw: &str) -> String {
+    let escaped = escape_for_terminal(raw);
+    match escaped.char_indices().nth(TEXT_CHARS) {
+        Some((cut, _)) => format!("{}…", &escaped[..cut]),
+        None => escaped,
+    }
+}
+
+/// Home directory to replace with `~`, when `KRC_LOG_REDACT_HOME` asks for it.
+fn redacted_home() -> Option<&'static str> {
+    static HOME: OnceLock<Option<String>> = OnceLock::new();
+    HOME.get_or_init(|| {
+        let enabled = std::env::var("KRC_LOG_REDACT_HOME")
+            .ok()
+            .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+        home_dir()
+            .map(|home| home.to_string_lossy().trim_end_matches('/').to_string())
+            .filter(|home| !home.is_empty())
+    })
+    .as_deref()
+}
+
+fn escape_for_terminal(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+            // Bidi embeddings, overrides and isolates reorder the rest of the line.
+            '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' => {
+                out.push_str(&format!("\\u{{{:04x}}}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Directory that relative map entries are resolved against.
+pub fn map_base_dir(map_file: &Path) -> PathBuf {
+    match map_file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => cli_base_dir(),
+    }
+}
+
+/// Directory that relative CLI and env paths are resolved against.
+pub fn cli_base_dir() -> PathBuf {
+    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"))
+}
+
+/// The path part of a local `file:` URI (`file:///a`, `file://localhost/a`,
+/// `file:/a`), or `None` when `raw` is not one. A URI naming another host is
+/// an error rather than a path.
+fn strip_file_scheme(raw: &str) -> Result<Option<&str>, String> {
+    let Some(scheme) = raw.get(..5) else {
+        return Ok(None);
+    };
+    if !scheme.eq_ignore_ascii_case("file:") {
+        return Ok(None);
+    }
+    let rest = &raw[5..];
+    let Some(authority) = rest.strip_prefix("//") else {
+        return match rest.starts_with('/') {
+            true => Ok(Some(rest)),
+            false => Err("expected an absolute path after 'file:'".to_string()),
+        };
+    };
+    let host_end = authority.find('/').unwrap_or(authority.len());
+    let host = &authority[..host_end];
+    if !host.is_empty() && !host.eq_ignore_ascii_case("localhost") {
+        return Err(format!("host '{host}' is not local"));
+    }
+    match &authority[host_end..] {
+        "" => Err("no path after the host".to_string()),
+        path => Ok(Some(path)),
+    }
+}
+
+fn percent_decode(raw: &str) -> Result<String, String> {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let byte = raw
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| format!("invalid percent escape at byte {i}"))?;
+        out.push(byte);
+        i += 3;
+    }
+    let decoded = String::from_utf8(out).map_err(|_| "decoded path is not UTF-8".to_string())?;
+    if decoded.contains('\0') {
+        return Err("decoded path contains a NUL byte".to_string());
+    }
+    Ok(decoded)
+}
+
+fn expand_home(raw: &str) -> Result<String, String> {
+    let rest = ["~", "${HOME}", "$HOME"]
+        .iter()
+        .find_map(|prefix| raw.strip_prefix(prefix))
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'));
+    let Some(rest) = rest else {
+        return Ok(raw.to_string());
+    };
+    let home = home_dir().ok_or_else(|| {
+        "no home directory (HOME unset and no passwd entry; tried expanding the leading ~/$HOME)"
+            .to_string()
+    })?;
+    let expanded = format!("{}{rest}", home.to_string_lossy().trim_end_matches('/'));
+    Ok(if expanded.is_empty() {
+        "/".to_string()
+    } else {
+        expanded
+    })
+}
+
+/// `$HOME` when it is an absolute path, else the home directory of the passwd
+/// entry for this uid (systemd user services may run without `HOME`).
+pub fn home_dir() -> Option<PathBuf> {
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from)
+        && home.is_absolute()
+    {
+        return Some(home);
+    }
+    passwd_home_dir()
+}
+
+/// The real uid of this process.
+pub fn current_uid() -> u32 {
+    // SAFETY: getuid(2) cannot fail.
+    unsafe { libc::getuid() }
+}
+
+/// The login name of `uid` from the passwd database.
+pub fn user_name(uid: u32) -> Option<String> {
+    let name = passwd_field(uid, |entry| entry.pw_name)?;
+    Some(String::from_utf8_lossy(&name).into_owned())
+}
+
+fn passwd_home_dir() -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = passwd_field(current_uid(), |entry| entry.pw_dir)?;
+    let dir = PathBuf::from(std::ffi::OsStr::from_bytes(&dir));
+    dir.is_absolute().then_some(dir)
+}
+
+/// Largest buffer offered to `getpwuid_r` before giving up on an entry.
+const PASSWD_BUF_MAX: usize = 1 << 20;
+
+/// One string of the passwd entry for `uid`, copied out. Uses the reentrant
+/// `getpwuid_r`, so threads looking up users (logs, status, file checks)
+/// never share libc's static entry.
+fn passwd_field(
+    uid: u32,
+    field: impl Fn(&libc::passwd) -> *mut std::ffi::c_char,
+) -> Option<Vec<u8>> {
+    let mut buf = vec![0 as std::ffi::c_char; 1024];
+    loop {
+        // SAFETY: passwd is plain data; all-zero is a valid (empty) value.
+        let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        // SAFETY: entry, buf and found outlive the call and buf.len() is the
+        // buffer's size; on success found points at entry, whose strings point
+        // into buf.
+        let rc =
+            unsafe { libc::getpwuid_r(uid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut found) };
+        match rc {
+            0 if found.is_null() => return None,
+            0 => {
+                let ptr = field(&entry);
+                if ptr.is_null() {
+                    return None;
+                }
+                // SAFETY: a non-null field is a NUL-terminated string in buf,
+                // which is still alive.
+                return Some(unsafe { std::ffi::CStr::from_ptr(ptr) }.to_bytes().to_vec());
+            }
+            libc::EINTR => {}
+            libc::ERANGE if buf.len() < PASSWD_BUF_MAX => buf.resize(buf.len() * 4, 0),
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(raw: &str) -> Result<String, String> {
+        normalize_video_path(raw, Path::new("/maps"))
+    }
+
+    #[test]
+    fn file_uris_are_decoded() {
+        assert_eq!(normalize("file:///v/a.mp4").unwrap(), "/v/a.mp4");
+        assert_eq!(
+            normalize("file:///v/live%20wallpapers/%E5%8B%95%E7%94%BB%23%25.mp4").unwrap(),
+            "/v/live wallpapers/動画#%.mp4"
+        );
+        assert_eq!(normalize("FILE://localhost/v/a.mp4").unwrap(), "/v/a.mp4");
+        assert_eq!(normalize("file:/v/a.mp4").unwrap(), "/v/a.mp4");
+        assert_eq!(normalize("  file:///v/a.mp4  ").unwrap(), "/v/a.mp4");
+    }
+
+    #[test]
+    fn broken_file_uris_name_the_input() {
+        for (raw, want) in [
+            ("file://nas/v/a.mp4", "host 'nas' is not local"),
+            ("file://localhost", "no path after the host"),
+            ("file:v/a.mp4", "expected an absolute path"),
+            ("file:///v/a%2.mp4", "invalid percent escape"),
+            ("file:///v/a%zz.mp4", "invalid percent escape"),
+            ("file:///v/%ff.mp4", "not UTF-8"),
+            ("file:///v/a%00.mp4", "NUL"),
+        ] {
+            let err = normalize(raw).unwrap_err();
+            assert!(err.contains(raw) && err.contains(want), "{raw}: {err}");
+            assert!(err.contains("tried"), "{raw}: {err}");
+        }
+    }
+
+    #[test]
+    fn leading_home_forms_expand() {
+        let Some(home) = home_dir() else {
+            return;
+        };
+        let home = home.to_string_lossy().trim_end_matches('/').to_string();
+        for raw in ["~/v/a.mp4", "$HOME/v/a.mp4", "${HOME}/v/a.mp4"] {
+            assert_eq!(normalize(raw).unwrap(), format!("{home}/v/a.mp4"), "{raw}");
+        }
+    }
+
+    #[test]
+    fn literal_tilde_directories_are_relative_paths() {
+        assert_eq!(normalize("~videos/a.mp4").unwrap(), "/maps/~videos/a.mp4");
+        assert_eq!(normalize("./~/a.mp4").unwrap(), "/maps/./~/a.mp4");
+        assert_eq!(
+            normalize("$HOMEBREW/a.mp4").unwrap(),
+            "/maps/$HOMEBREW/a.mp4"
+        );
+        assert_eq!(normalize("/v/~/a.mp4").unwrap(), "/v/~/a.mp4");
+    }
+
+    #[test]
+    fn control_and_bidi_characters_are_escaped() {
+        assert_eq!(escape_for_terminal("/v/a.mp4"), "/v/a.mp4");
+        assert_eq!(
+            escape_for_terminal("/v/\x1b]0;pwned\x07x.mp4"),
+            "/v/\\x1b]0;pwned\\x07x.mp4"
+        );
+        assert_eq!(escape_for_terminal("a\nb\tc\rd"), "a\\x0ab\\x09c\\x0dd");
+        assert_eq!(escape_for_terminal("\u{9b}31m"), "\\x9b31m");
+        assert_eq!(
+            escape_for_terminal("evil\u{202e}4pm.exe"),
+            "evil\\u{202e}4pm.exe"
+        );
+        assert_eq!(
+            escape_for_terminal("\u{2066}x\u{2069}"),
+            "\\u{2066}x\\u{2069}"
+        );
+        // Ordinary non-ASCII text is left alone.
+        assert_eq!(escape_for_terminal("/v/動画 ünï.mp4"), "/v/動画 ünï.mp4");
+    }
+
+    #[test]
+    fn home_is_redacted_only_as_a_whole_directory() {
+        let home = Some("/home/kit");
+        assert_eq!(redact_home("/home/kit/v/a.mp4", home), "~/v/a.mp4");
+        assert_eq!(redact_home("/home/kit", home), "~");
+        assert_eq!(
+            redact_home("/home/kitten/a.mp4", home),
+            "/home/kitten/a.mp4"
+        );
+        assert_eq!(
+            redact_home("/srv/home/kit/a.mp4", home),
+            "/srv/home/kit/a.mp4"
+        );
+        assert_eq!(redact_home("/home/kit/a.mp4", None), "/home/kit/a.mp4");
+    }
+
+    #[test]
+    fn long_paths_shorten_to_parent_and_file() {
+        if redacted_home().is_some() {
+            return;
+        }
+        let short = "/srv/loops/rain.mp4";
+        assert_eq!(display_path_short(short), short);
+        let long = format!("/srv/{}/loops/rain\x1b.mp4", "deep/".repeat(20));
+        assert_eq!(display_path_short(&long), "…/loops/rain\\x1b.mp4");
+        let no_parent = format!("/{}", "x".repeat(80));
+        assert_eq!(display_path_short(&no_parent), no_parent);
+    }
+
+    #[test]
+    fn free_text_is_escaped_and_bounded() {
+        assert_eq!(display_text("line 3: bad\x1b[2J"), "line 3: bad\\x1b[2J");
+        let long = "é".repeat(TEXT_CHARS + 10);
+        let shown = display_text(&long);
+        assert_eq!(shown.chars().count(), TEXT_CHARS + 1);
+        assert!(shown.ends_with('…'));
+        assert_eq!(
+            display_text(&"é".repeat(TEXT_CHARS)),
+            "é".repeat(TEXT_CHARS)
+        );
+    }
+
+    #[test]
+    fn passwd_lookups_are_reentrant_and_agree() {
+        let uid = current_uid();
+        let name = user_name(uid);
+        let lookups = (0..8)
+            .map(|_| std::thread::spawn(move || user_name(uid)))
+            .collect::<Vec<_>>();
+        for lookup in lookups {
+            assert_eq!(lookup.join().unwrap(), name);
+        }
+        if name.is_some() {
+            assert!(passwd_home_dir().is_some_and(|dir| dir.is_absolute()));
+        }
+        assert_eq!(user_name(u32::MAX - 1), None);
+    }
+
+    #[test]
+    fn relative_paths_resolve_against_the_base() {
+        assert_eq!(normalize("loops/a.mp4").unwrap(), "/maps/loops/a.mp4");
+        assert_eq!(
+            map_base_dir(Path::new("/cfg/video-map.conf")),
+            Path::new("/cfg")
+        );
+        assert_eq!(map_base_dir(Path::new("video-map.conf")), cli_base_dir());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::json::escape_json;
+
+/// Embedded by build.rs; "unknown" when the source was not a git checkout.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_DESCRIBE: &str = env!("KRC_GIT_DESCRIBE");
+pub const BUILD_DATE: &str = env!("KRC_BUILD_DATE");
+/// Comma-separated cargo features, empty for a default build.
+pub const FEATURES: &str = env!("KRC_BUILD_FEATURES");
+/// Versions locked in Cargo.lock; linked only with `wayland-layer`.
+pub const WGPU_VERSION: &str = env!("KRC_WGPU_VERSION");
+pub const WAYLAND_CLIENT_VERSION: &str = env!("KRC_WAYLAND_CLIENT_VERSION");
+
+/// Why an output mapped to a video shows the fallback in a build without
+/// `video-ffmpeg`.
+pub const NO_VIDEO_SUPPORT: &str = "built without video support (video-ffmpeg feature off)";
+
+/// The video decoding compiled in: `ffmpeg`, or `none` without `video-ffmpeg`.
+pub fn video_support() -> &'static str {
+    if cfg!(feature = "video-ffmpeg") {
+        "ffmpeg"
+    } else {
+        "none"
+    }
+}
+
+fn features() -> Vec<&'static str> {
+    FEATURES.split(',').filter(|f| !f.is_empty()).collect()
+}
+
+fn linked(version: &'static str) -> &'static str {
+    if cfg!(feature = "wayland-layer") {
+        version
+    } else {
+        "not linked"
+    }
+}
+
+/// `2.1.1 (abc1234, built 2026-10-17) features=video-ffmpeg,wayland-layer video=ffmpeg`,
+/// for the bootstrap log and bug reports.
+pub fn summary() -> String {
+    let features = features();
+    format!(
+        "{VERSION} ({GIT_DESCRIBE}, built {BUILD_DATE}) features={} video={}",
+        if features.is_empty() {
+            "none".to_string()
+        } else {
+            features.join(",")
+        },
+        video_support()
+    )
+}
+
+pub fn run_version(args: &[String]) -> Result<(), String> {
+    let mut as_json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => as_json = true,
+            "--help" | "-h" => {
+                print_version_help();
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument for version: {other}")),
+        }
+    }
+    let features = features();
+    if as_json {
+        let features = features
+            .iter()
+            .map(|f| format!("\"{}\"", escape_json(f)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let optional = |version: &str| {
+            if cfg!(feature = "wayland-layer") {
+                format!("\"{}\"", escape_json(version))
+            } else {
+                "null".to_string()
+            }
+        };
+        println!(
+            "{{\"version\":\"{}\",\"git\":\"{}\",\"build_date\":\"{}\",\"features\":[{features}],\"video\":\"{}\",\"wgpu\":{},\"wayland_client\":{}}}",
+            escape_json(VERSION),
+            escape_json(GIT_DESCRIBE),
+            escape_json(BUILD_DATE),
+            video_support(),
+            optional(WGPU_VERSION),
+            optional(WAYLAND_CLIENT_VERSION)
+        );
+        return Ok(());
+    }
+    println!("kitsune-rendercore {VERSION}");
+    println!("git: {GIT_DESCRIBE}");
+    println!("built: {BUILD_DATE}");
+    println!(
+        "features: {}",
+        if features.is_empty() {
+            "none".to_string()
+        } else {
+            features.join(", ")
+        }
+    );
+    println!(
+        "backend: {}",
+        if cfg!(feature = "wayland-layer") {
+            "wayland-layer"
+        } else {
+            "stub"
+        }
+    );
+    println!("video: {}", video_support());
+    println!("wgpu: {}", linked(WGPU_VERSION));
+    println!("wayland-client: {}", linked(WAYLAND_CLIENT_VERSION));
+    Ok(())
+}
+
+fn print_version_help() {
+    println!("kitsune-rendercore version");
+    println!("Usage:");
+    println!("  kitsune-rendercore version [--json]");
+    println!("  kitsune-rendercore --version");
+    println!();
+    println!("Shows the release, the git revision and date it was built from, the cargo");
+    println!("features compiled in, the backend and video decoding they give, and the");
+    println!("wgpu and wayland-client versions linked.");
+    println!("Include it in bug reports.");
+}
@@ -1,69 +1,1539 @@
 use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-pub fn default_map_file_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    Path::new(&home)
-        .join(".config")
-        .join("kitsune-rendercore")
-        .join("video-map.conf")
+use crate::color::Gamut;
+use crate::env_file;
+use crate::file_access::write_atomic;
+use crate::log_limit;
+use crate::map_toml::{format_map_toml, parse_map_toml};
+use crate::paths::config_dir;
+use crate::timebase::Timebase;
+use crate::user_path::{
+    cli_base_dir, display_path, display_text, map_base_dir, normalize_video_path,
+};
+
+/// `video-map.toml` once it exists (e.g. after `migrate-map`), else the v1
+/// `video-map.conf`.
+pub fn default_map_file_path() -> Result<PathBuf, String> {
+    let dir = config_dir()?;
+    let v2 = dir.join("video-map.toml");
+    if v2.exists() {
+        return Ok(v2);
+    }
+    Ok(dir.join("video-map.conf"))
+}
+
+pub fn map_file_path_from_env() -> Result<PathBuf, String> {
+    match std::env::var("KRC_VIDEO_MAP_FILE") {
+        Ok(path) if !path.trim().is_empty() => Ok(PathBuf::from(path)),
+        _ => default_map_file_path(),
+    }
+}
+
+pub type VideoMap = BTreeMap<String, VideoMapEntry>;
+
+/// One map value: the video path plus optional trailing `key=value` options,
+/// e.g. `DP-1=/videos/a.mp4 rotate=90 flip=h`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VideoMapEntry {
+    /// For a `layout=` entry, its first cell's video, so code that knows only
+    /// single videos still sees a real path.
+    pub video: String,
+    pub options: EntryOptions,
+    pub layout: Option<Layout>,
+}
+
+/// Most cells a layout grid has along either axis.
+pub const MAX_LAYOUT_AXIS: u32 = 4;
+
+/// How often the renderer polls the map file (and the env file) for changes.
+pub const MAP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `layout=grid2x2:/a.mp4,/b.mp4,/c.mp4,/d.mp4`: the output split into a
+/// grid, each cell playing its own video with the entry's options. Cells fill
+/// row by row; an empty one (`/a.mp4,,/c.mp4`) or one past the last video
+/// stays black.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub columns: u32,
+    pub rows: u32,
+    pub cells: Vec<Option<String>>,
+}
+
+/// Per-entry options. Rotate and flip are applied on the render side (shader
+/// UV math), so changing them never involves the decoder; a quality preset
+/// changes the source size, which recreates that output's texture and decoder.
+/// The ambient effect and the particle overlay are render-side too; a trim
+/// window restarts the decoder.
+/// A crop window is render-side once the whole frame is decoded, as are the
+/// sampling filter and `fit=integer` (which decodes the whole frame too).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EntryOptions {
+    pub rotate: Rotation,
+    pub flip: Flip,
+    /// `None` follows `KRC_QUALITY` / `KRC_SOURCE_WIDTH/HEIGHT`.
+    pub quality: Option<QualityPreset>,
+    pub effect: Effect,
+    /// Parameters of `effect=ambient`; kept when the effect is off so toggling
+    /// it does not lose them.
+    pub ambient: AmbientParams,
+    /// Particles drawn over the picture (`overlay=`).
+    pub overlay: Overlay,
+    /// Parameters of `overlay=`; kept when it is off, like [`Self::ambient`].
+    pub overlay_params: OverlayParams,
+    /// `trim=IN:OUT`: only this window of the video plays, looping within it.
+    pub trim: Option<Trim>,
+    /// `still:PATH@TIME`: one frame of the video is shown instead of playing it.
+    pub still: Option<StillFrame>,
+    /// `crop=X,Y,W,H`: only this region of the video is shown, scaled to cover
+    /// the output.
+    pub crop: Option<Crop>,
+    /// `filter=nearest|linear`: how the video's pixels are sampled.
+    pub filter: SampleFilter,
+    /// `fit=cover|integer`: how the video fills the output.
+    pub fit: Fit,
+    /// `gamut=auto|srgb|native|matrix:...`: how the video's sRGB colors are
+    /// mapped to the panel.
+    pub gamut: Gamut,
+    /// `clock=on|off`: whether this output shows the clock widget; `None`
+    /// (`clock=auto`) follows `[widgets.clock] enabled`.
+    pub clock: Option<bool>,
+    /// `timebase=monotonic|utc`: what this output's effects and playback
+    /// follow; `None` (`timebase=auto`) follows `KRC_TIMEBASE`.
+    pub timebase: Option<Timebase>,
+}
+
+/// The frame a `still:` entry shows. It is written in front of the path
+/// (`still:/videos/a.mp4@00:01:23`) rather than as a `key=value` option, so
+/// the whole value names the picture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StillFrame {
+    /// `None` takes the frame 10% into the clip.
+    pub at_ms: Option<u32>,
+}
+
+/// A loop window in milliseconds of the source video. `end_ms` is `None` for
+/// `trim=IN:` (up to the end).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trim {
+    pub start_ms: u32,
+    pub end_ms: Option<u32>,
+}
+
+/// A source region. Integers are pixels of the video; with decimals all four
+/// are fractions of the frame (0-1), stored in millionths so options stay `Eq`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    unit: CropUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CropUnit {
+    Pixels,
+    Fraction,
+}
+
+/// Millionths in a whole frame, for fractional crops.
+const CROP_FRACTION_ONE: u32 = 1_000_000;
+
+/// Motion applied on top of the video (`effect=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Effect {
+    #[default]
+    None,
+    /// A slow zoom in and out, optionally drifting (`amplitude=`, `period=`,
+    /// `drift=`).
+    Ambient,
+}
+
+/// Particles composited over whatever the output shows (`overlay=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overlay {
+    #[default]
+    None,
+    Snow,
+    Rain,
+    Fireflies,
+}
+
+/// Texture sampling (`filter=`). `nearest` keeps pixel art crisp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleFilter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+/// How the video fills the output (`fit=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fit {
+    /// Scaled to cover the output, the overflow cut off.
+    #[default]
+    Cover,
+    /// Scaled by the largest whole multiple of its own pixels that fits (or
+    /// divided by the smallest whole number, when larger than the output),
+    /// centred with black borders.
+    Integer,
+}
+
+/// `effect=ambient` parameters. Fractions are stored in thousandths so options
+/// stay `Eq` and round-trip through the map file unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmbientParams {
+    /// Extra zoom at the peak, 0.001-0.25.
+    amplitude_milli: u32,
+    /// Seconds for one zoom in and out, 10-86400.
+    period_sec: u32,
+    /// How much of the zoom margin is used for panning, 0-1.
+    drift_milli: u32,
+}
+
+impl Default for AmbientParams {
+    fn default() -> Self {
+        Self {
+            amplitude_milli: 50,
+            period_sec: 300,
+            drift_milli: 0,
+        }
+    }
+}
+
+/// `overlay=` parameters, in thousandths like [`AmbientParams`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlayParams {
+    /// Share of the preset's particle budget drawn, 0.01-1.
+    density_milli: u32,
+    /// Multiplier on the preset's fall (or wander) speed, 0.1-4.
+    speed_milli: u32,
+    /// Sideways push, -1 (to the left) to 1 (to the right).
+    wind_milli: i32,
+}
+
+impl Default for OverlayParams {
+    fn default() -> Self {
+        Self {
+            density_milli: 500,
+            speed_milli: 1000,
+            wind_milli: 0,
+        }
+    }
+}
+
+/// `KRC_QUALITY` presets, also usable per map entry (`quality=high`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Flip {
+    #[default]
+    None,
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl Rotation {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim() {
+            "0" => Ok(Self::Deg0),
+            "90" => Ok(Self::Deg90),
+            "180" => Ok(Self::Deg180),
+            "270" => Ok(Self::Deg270),
+            other => Err(format!(
+                "invalid rotate value '{other}' (expected 0|90|180|270)"
+            )),
+        }
+    }
+
+    pub fn degrees(self) -> u32 {
+        match self {
+            Self::Deg0 => 0,
+            Self::Deg90 => 90,
+            Self::Deg180 => 180,
+            Self::Deg270 => 270,
+        }
+    }
+
+    /// Quarter turns clockwise, as consumed by the shader.
+    #[cfg(feature = "wayland-layer")]
+    pub fn quarter_turns(self) -> u32 {
+        self.degrees() / 90
+    }
+
+    #[cfg(feature = "wayland-layer")]
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Self::Deg90 | Self::Deg270)
+    }
+}
+
+impl Flip {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "none" | "" => Ok(Self::None),
+            "h" => Ok(Self::Horizontal),
+            "v" => Ok(Self::Vertical),
+            "hv" | "vh" => Ok(Self::Both),
+            other => Err(format!(
+                "invalid flip value '{other}' (expected h|v|hv|none)"
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Horizontal => "h",
+            Self::Vertical => "v",
+            Self::Both => "hv",
+        }
+    }
+
+    /// Bit 0 = horizontal, bit 1 = vertical, as consumed by the shader.
+    #[cfg(feature = "wayland-layer")]
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Horizontal => 1,
+            Self::Vertical => 2,
+            Self::Both => 3,
+        }
+    }
+}
+
+impl QualityPreset {
+    /// Accepts the `KRC_QUALITY` names and their resolution aliases.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "low" | "720p" => Ok(Self::Low),
+            "medium" | "1080p" => Ok(Self::Medium),
+            "high" | "1440p" => Ok(Self::High),
+            "ultra" | "4k" | "2160p" => Ok(Self::Ultra),
+            other => Err(format!(
+                "invalid quality value '{other}' (expected low|medium|high|ultra)"
+            )),
+        }
+    }
+
+    /// Like `parse`, with `default`/`none` clearing a per-entry override.
+    pub fn parse_override(raw: &str) -> Result<Option<Self>, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "default" | "none" | "" => Ok(None),
+            other => Self::parse(other).map(Some),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Ultra => "ultra",
+        }
+    }
+
+    /// Landscape source size; the backend orients and GPU-clamps it.
+    #[cfg(feature = "wayland-layer")]
+    pub fn source_size(self) -> (u32, u32) {
+        match self {
+            Self::Low => (1280, 720),
+            Self::Medium => (1920, 1080),
+            Self::High => (2560, 1440),
+            Self::Ultra => (3840, 2160),
+        }
+    }
+}
+
+impl Effect {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "none" | "" => Ok(Self::None),
+            "ambient" => Ok(Self::Ambient),
+            other => Err(format!(
+                "invalid effect value '{other}' (expected ambient|none)"
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Ambient => "ambient",
+        }
+    }
+}
+
+impl Overlay {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "none" | "" => Ok(Self::None),
+            "snow" => Ok(Self::Snow),
+            "rain" => Ok(Self::Rain),
+            "fireflies" => Ok(Self::Fireflies),
+            other => Err(format!(
+                "invalid overlay value '{other}' (expected snow|rain|fireflies|none)"
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Snow => "snow",
+            Self::Rain => "rain",
+            Self::Fireflies => "fireflies",
+        }
+    }
+}
+
+/// `clock=on|off|auto`.
+fn parse_clock(raw: &str) -> Result<Option<bool>, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "auto" | "" => Ok(None),
+        "on" => Ok(Some(true)),
+        "off" => Ok(Some(false)),
+        other => Err(format!(
+            "invalid clock value '{other}' (expected on|off|auto)"
+        )),
+    }
+}
+
+/// `timebase=monotonic|utc|auto`.
+fn parse_timebase(raw: &str) -> Result<Option<Timebase>, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "auto" | "" => Ok(None),
+        other => Timebase::parse(other).map(Some),
+    }
+}
+
+/// How `clock=` spells an explicit setting.
+pub fn clock_str(clock: bool) -> &'static str {
+    if clock { "on" } else { "off" }
+}
+
+impl SampleFilter {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "linear" | "" => Ok(Self::Linear),
+            "nearest" => Ok(Self::Nearest),
+            other => Err(format!(
+                "invalid filter value '{other}' (expected nearest|linear)"
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Linear => "linear",
+            Self::Nearest => "nearest",
+        }
+    }
+}
+
+impl Fit {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "cover" | "" => Ok(Self::Cover),
+            "integer" => Ok(Self::Integer),
+            other => Err(format!(
+                "invalid fit value '{other}' (expected cover|integer)"
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cover => "cover",
+            Self::Integer => "integer",
+        }
+    }
+}
+
+impl AmbientParams {
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn amplitude(self) -> f32 {
+        self.amplitude_milli as f32 / 1000.0
+    }
+
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn period_sec(self) -> u32 {
+        self.period_sec
+    }
+
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn drift(self) -> f32 {
+        self.drift_milli as f32 / 1000.0
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "amplitude" => self.amplitude_milli = parse_milli(key, value, 1..=250)?,
+            "drift" => self.drift_milli = parse_milli(key, value, 0..=1000)?,
+            _ => {
+                self.period_sec = value
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|p| (10..=86_400).contains(p))
+                    .ok_or_else(|| {
+                        format!("invalid period value '{value}' (expected 10-86400 seconds)")
+                    })?
+            }
+        }
+        Ok(())
+    }
+
+    /// Option keys and values as the map file spells them; `drift` only when
+    /// it is set.
+    pub fn pairs(self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![
+            ("amplitude", format_milli(self.amplitude_milli)),
+            ("period", self.period_sec.to_string()),
+        ];
+        if self.drift_milli != 0 {
+            pairs.push(("drift", format_milli(self.drift_milli)));
+        }
+        pairs
+    }
+}
+
+impl OverlayParams {
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn density(self) -> f32 {
+        self.density_milli as f32 / 1000.0
+    }
+
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn speed(self) -> f32 {
+        self.speed_milli as f32 / 1000.0
+    }
+
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn wind(self) -> f32 {
+        self.wind_milli as f32 / 1000.0
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "density" => self.density_milli = parse_milli(key, value, 10..=1000)?,
+            "speed" => self.speed_milli = parse_milli(key, value, 100..=4000)?,
+            _ => {
+                // The one signed option; `parse_milli` covers the magnitude.
+                let (sign, magnitude) = match value.trim().strip_prefix('-') {
+                    Some(rest) => (-1, rest),
+                    None => (1, value),
+                };
+                let milli = parse_milli(key, magnitude, 0..=1000)
+                    .map_err(|_| format!("invalid wind value '{value}' (expected -1 to 1)"))?;
+                self.wind_milli = sign * milli as i32;
+            }
+        }
+        Ok(())
+    }
+
+    /// Option keys and values as the map file spells them; only the ones
+    /// that differ from the default.
+    pub fn pairs(self) -> Vec<(&'static str, String)> {
+        let default = Self::default();
+        let mut pairs = Vec::new();
+        if self.density_milli != default.density_milli {
+            pairs.push(("density", format_milli(self.density_milli)));
+        }
+        if self.speed_milli != default.speed_milli {
+            pairs.push(("speed", format_milli(self.speed_milli)));
+        }
+        if self.wind_milli != default.wind_milli {
+            pairs.push(("wind", format!("{}", f64::from(self.wind_milli) / 1000.0)));
+        }
+        pairs
+    }
+}
+
+impl std::fmt::Display for OverlayParams {
+    /// `density=0.8 wind=-0.3`: the parameters that are not the default.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line = self
+            .pairs()
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        f.write_str(&line)
+    }
+}
+
+impl std::fmt::Display for AmbientParams {
+    /// `amplitude=0.05 period=300`, plus `drift=` when it is set.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line = self
+            .pairs()
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        f.write_str(&line)
+    }
+}
+
+impl Trim {
+    /// `IN:OUT` in seconds, either side may be empty (`:14.5`, `2:`);
+    /// `none` clears the trim.
+    pub fn parse_override(raw: &str) -> Result<Option<Self>, String> {
+        let raw = raw.trim();
+        if raw.is_empty() || raw.eq_ignore_ascii_case("none") {
+            return Ok(None);
+        }
+        let err =
+            || format!("invalid trim value '{raw}' (expected IN:OUT in seconds, e.g. 2.0:14.5)");
+        let (start, end) = raw.split_once(':').ok_or_else(err)?;
+        let seconds = |side: &str| -> Result<Option<u32>, String> {
+            if side.trim().is_empty() {
+                return Ok(None);
+            }
+            let secs = side.trim().parse::<f64>().map_err(|_| err())?;
+            if !secs.is_finite() || secs < 0.0 || secs * 1000.0 > u32::MAX as f64 {
+                return Err(err());
+            }
+            Ok(Some((secs * 1000.0).round() as u32))
+        };
+        let start_ms = seconds(start)?.unwrap_or(0);
+        let end_ms = seconds(end)?;
+        if let Some(end_ms) = end_ms
+            && start_ms >= end_ms
+        {
+            return Err(format!(
+                "invalid trim value '{raw}': in ({}s) must be before out ({}s)",
+                format_milli(start_ms),
+                format_milli(end_ms)
+            ));
+        }
+        if start_ms == 0 && end_ms.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self { start_ms, end_ms }))
+    }
+
+    #[cfg_attr(
+        not(all(feature = "wayland-layer", feature = "video-ffmpeg")),
+        allow(dead_code)
+    )]
+    pub fn duration_ms(self) -> Option<u32> {
+        self.end_ms.map(|end| end - self.start_ms)
+    }
+}
+
+impl std::fmt::Display for Trim {
+    /// `2:14.5`, or `2:` without an out point.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", format_milli(self.start_ms))?;
+        if let Some(end_ms) = self.end_ms {
+            write!(f, "{}", format_milli(end_ms))?;
+        }
+        Ok(())
+    }
+}
+
+impl Layout {
+    /// The text after `layout=`: `gridCxR:` then the cells' paths, comma
+    /// separated. A path holding a comma or a space is quoted like a map path.
+    pub fn parse(raw: &str, base: &Path) -> Result<Self, String> {
+        let (grid, cells) = raw
+            .split_once(':')
+            .ok_or_else(|| format!("invalid layout '{raw}' (expected gridCxR:PATH,PATH,...)"))?;
+        let (columns, rows) = grid
+            .strip_prefix("grid")
+            .and_then(|size| size.split_once('x'))
+            .and_then(|(c, r)| Some((c.parse::<u32>().ok()?, r.parse::<u32>().ok()?)))
+            .filter(|(c, r)| (1..=MAX_LAYOUT_AXIS).contains(c) && (1..=MAX_LAYOUT_AXIS).contains(r))
+            .ok_or_else(|| {
+                format!(
+                    "invalid layout grid '{grid}' (expected gridCxR, 1 to {MAX_LAYOUT_AXIS} each)"
+                )
+            })?;
+        let cells = split_unquoted(cells, ',')
+            .into_iter()
+            .map(|cell| {
+                let cell = cell.trim();
+                let path = if cell.starts_with('"') {
+                    let (path, rest) = parse_quoted_path(cell)?;
+                    if !rest.trim().is_empty() {
+                        return Err(format!("unexpected '{}' after quoted path", rest.trim()));
+                    }
+                    path
+                } else {
+                    cell.to_string()
+                };
+                if path.is_empty() {
+                    return Ok(None);
+                }
+                if path.starts_with("still:") {
+                    return Err("a layout cell cannot be a still: frame".to_string());
+                }
+                normalize_video_path(&path, base).map(Some)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if cells.len() > (columns * rows) as usize {
+            return Err(format!(
+                "layout {grid} has {} cells, {} videos given",
+                columns * rows,
+                cells.len()
+            ));
+        }
+        if cells.iter().all(Option::is_none) {
+            return Err(format!("layout {grid} names no video"));
+        }
+        Ok(Self {
+            columns,
+            rows,
+            cells,
+        })
+    }
+
+    /// `grid2x2`.
+    pub fn grid(&self) -> String {
+        format!("grid{}x{}", self.columns, self.rows)
+    }
+
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn cell_count(&self) -> usize {
+        (self.columns * self.rows) as usize
+    }
+
+    /// The video of cell `index` (row by row from 0); `None` for a black one.
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn cell(&self, index: usize) -> Option<&str> {
+        self.cells.get(index)?.as_deref()
+    }
+
+    /// `gridCxR:PATH,...` with each path passed through `path`.
+    fn format_with(&self, path: impl Fn(&str) -> String) -> String {
+        let cells = self
+            .cells
+            .iter()
+            .map(|cell| cell.as_deref().map(&path).unwrap_or_default())
+            .collect::<Vec<_>>();
+        format!("{}:{}", self.grid(), cells.join(","))
+    }
+}
+
+impl std::fmt::Display for Layout {
+    /// The form the map file holds, paths quoted where a comma or space
+    /// would split them.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let quoted =
+            self.format_with(|path| quote_path(path, needs_quoting(path) || path.contains(',')));
+        f.write_str(&quoted)
+    }
+}
+
+impl Crop {
+    /// `X,Y,W,H` in pixels (`1920,0,1920,1080`) or fractions of the frame
+    /// (`0.5,0,0.5,1`); `none` clears the crop.
+    pub fn parse_override(raw: &str) -> Result<Option<Self>, String> {
+        let raw = raw.trim();
+        if raw.is_empty() || raw.eq_ignore_ascii_case("none") {
+            return Ok(None);
+        }
+        let err = || {
+            format!(
+                "invalid crop value '{raw}' (expected X,Y,W,H in pixels, or 0-1 fractions such as 0.5,0,0.5,1)"
+            )
+        };
+        let parts = raw.split(',').map(str::trim).collect::<Vec<_>>();
+        let [x, y, w, h] = parts[..] else {
+            return Err(err());
+        };
+        let unit = if parts.iter().any(|part| part.contains('.')) {
+            CropUnit::Fraction
+        } else {
+            CropUnit::Pixels
+        };
+        let value = |part: &str| -> Result<u32, String> {
+            match unit {
+                CropUnit::Pixels => part.parse::<u32>().map_err(|_| err()),
+                CropUnit::Fraction => {
+                    let fraction = part.parse::<f64>().map_err(|_| err())?;
+                    if !(0.0..=1.0).contains(&fraction) {
+                        return Err(err());
+                    }
+                    Ok((fraction * CROP_FRACTION_ONE as f64).round() as u32)
+                }
+            }
+        };
+        let crop = Self {
+            x: value(x)?,
+            y: value(y)?,
+            w: value(w)?,
+            h: value(h)?,
+            unit,
+        };
+        if crop.w == 0 || crop.h == 0 {
+            return Err(format!(
+                "invalid crop value '{raw}': width and height must be above 0"
+            ));
+        }
+        Ok(Some(crop))
+    }
+
+    /// The region cut to the frame of a `width`x`height` video, in its own
+    /// unit; `None` when nothing of it is left inside.
+    #[cfg_attr(not(feature = "video-ffmpeg"), allow(dead_code))]
+    pub fn clamped(self, (width, height): (u32, u32)) -> Option<Self> {
+        let (full_w, full_h) = match self.unit {
+            CropUnit::Pixels => (width, height),
+            CropUnit::Fraction => (CROP_FRACTION_ONE, CROP_FRACTION_ONE),
+        };
+        let x = self.x.min(full_w);
+        let y = self.y.min(full_h);
+        let clamped = Self {
+            x,
+            y,
+            w: self.w.min(full_w - x),
+            h: self.h.min(full_h - y),
+            unit: self.unit,
+        };
+        clamped.pixels((width, height))?;
+        Some(clamped)
+    }
+
+    /// The region in pixels of a `width`x`height` video, clamped to the frame;
+    /// `None` when nothing of it is left inside.
+    #[cfg_attr(not(feature = "video-ffmpeg"), allow(dead_code))]
+    pub fn pixels(self, (width, height): (u32, u32)) -> Option<(u32, u32, u32, u32)> {
+        let scale = |value: u32, full: u32| match self.unit {
+            CropUnit::Pixels => value,
+            CropUnit::Fraction => {
+                (value as u64 * full as u64 / CROP_FRACTION_ONE as u64).min(full as u64) as u32
+            }
+        };
+        let x = scale(self.x, width).min(width);
+        let y = scale(self.y, height).min(height);
+        let w = scale(self.w, width).min(width - x);
+        let h = scale(self.h, height).min(height - y);
+        (w > 0 && h > 0).then_some((x, y, w, h))
+    }
+}
+
+impl std::fmt::Display for Crop {
+    /// `1920,0,1920,1080`, or `0.5,0.0,0.5,1.0` for fractions (always with a
+    /// decimal point, so it reads back as a fraction).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts = [self.x, self.y, self.w, self.h].map(|value| match self.unit {
+            CropUnit::Pixels => value.to_string(),
+            CropUnit::Fraction => {
+                let text = format!("{}", value as f64 / CROP_FRACTION_ONE as f64);
+                if text.contains('.') {
+                    text
+                } else {
+                    format!("{text}.0")
+                }
+            }
+        });
+        f.write_str(&parts.join(","))
+    }
+}
+
+impl StillFrame {
+    /// `HH:MM:SS(.fff)`, `MM:SS` or plain seconds.
+    fn parse_timestamp(raw: &str) -> Result<u32, String> {
+        let err =
+            || format!("invalid still timestamp '{raw}' (expected HH:MM:SS, MM:SS or seconds)");
+        let parts = raw.split(':').collect::<Vec<_>>();
+        if parts.len() > 3 {
+            return Err(err());
+        }
+        let (seconds, whole) = parts.split_last().ok_or_else(err)?;
+        let seconds = seconds.parse::<f64>().map_err(|_| err())?;
+        if !seconds.is_finite() || seconds < 0.0 || (!whole.is_empty() && seconds >= 60.0) {
+            return Err(err());
+        }
+        let mut total = 0u64;
+        for (idx, part) in whole.iter().enumerate() {
+            let value = part.parse::<u64>().map_err(|_| err())?;
+            if idx > 0 && value >= 60 {
+                return Err(err());
+            }
+            total = total * 60 + value;
+        }
+        let ms = (total as f64 * 60.0 + seconds) * 1000.0;
+        if ms > u32::MAX as f64 {
+            return Err(err());
+        }
+        Ok(ms.round() as u32)
+    }
+}
+
+impl std::fmt::Display for StillFrame {
+    /// `00:01:23`, with milliseconds only when they are set; empty for the
+    /// 10% default.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(at_ms) = self.at_ms else {
+            return Ok(());
+        };
+        let secs = at_ms / 1000;
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )?;
+        if at_ms % 1000 != 0 {
+            write!(f, ".{:03}", at_ms % 1000)?;
+        }
+        Ok(())
+    }
+}
+
+/// Peels `still:` and a trailing `@TIME` off a raw map path. A suffix that is
+/// not a timestamp stays part of the path (`still:/v/a@b.mp4`), and a bare
+/// `@` at the end asks for the default frame of a path that contains one.
+pub fn split_still(raw: &str) -> Result<(&str, Option<StillFrame>), String> {
+    let Some(rest) = raw.strip_prefix("still:") else {
+        return Ok((raw, None));
+    };
+    let (video, at_ms) = match rest.rsplit_once('@') {
+        Some((video, "")) => (video, None),
+        Some((video, time))
+            if time
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, ':' | '.')) =>
+        {
+            (video, Some(StillFrame::parse_timestamp(time)?))
+        }
+        _ => (rest, None),
+    };
+    Ok((video, Some(StillFrame { at_ms })))
+}
+
+/// A decimal fraction in thousandths, e.g. `0.05` -> 50.
+fn parse_milli(
+    key: &str,
+    value: &str,
+    range: std::ops::RangeInclusive<u32>,
+) -> Result<u32, String> {
+    let err = || {
+        format!(
+            "invalid {key} value '{value}' (expected {}-{})",
+            format_milli(*range.start()),
+            format_milli(*range.end())
+        )
+    };
+    let parsed = value.trim().parse::<f64>().map_err(|_| err())?;
+    if !parsed.is_finite() {
+        return Err(err());
+    }
+    let milli = (parsed * 1000.0).round();
+    if milli < *range.start() as f64 || milli > *range.end() as f64 {
+        return Err(err());
+    }
+    Ok(milli as u32)
+}
+
+fn format_milli(milli: u32) -> String {
+    format!("{}", milli as f64 / 1000.0)
+}
+
+const OPTION_KEYS: &[&str] = &[
+    "rotate",
+    "flip",
+    "quality",
+    "effect",
+    "amplitude",
+    "period",
+    "drift",
+    "overlay",
+    "density",
+    "speed",
+    "wind",
+    "trim",
+    "crop",
+    "filter",
+    "fit",
+    "gamut",
+    "clock",
+    "timebase",
+];
+
+impl EntryOptions {
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "rotate" => self.rotate = Rotation::parse(value)?,
+            "flip" => self.flip = Flip::parse(value)?,
+            "quality" => self.quality = QualityPreset::parse_override(value)?,
+            "effect" => self.effect = Effect::parse(value)?,
+            "amplitude" | "period" | "drift" => self.ambient.set(key, value)?,
+            "overlay" => self.overlay = Overlay::parse(value)?,
+            "density" | "speed" | "wind" => self.overlay_params.set(key, value)?,
+            "trim" => self.trim = Trim::parse_override(value)?,
+            "crop" => self.crop = Crop::parse_override(value)?,
+            "filter" => self.filter = SampleFilter::parse(value)?,
+            "fit" => self.fit = Fit::parse(value)?,
+            "gamut" => self.gamut = Gamut::parse(value)?,
+            "clock" => self.clock = parse_clock(value)?,
+            "timebase" => self.timebase = parse_timebase(value)?,
+            other => return Err(format!("unknown map option '{other}'")),
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for EntryOptions {
+    /// Writes only non-default options, each prefixed by a space.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.rotate != Rotation::Deg0 {
+            write!(f, " rotate={}", self.rotate.degrees())?;
+        }
+        if self.flip != Flip::None {
+            write!(f, " flip={}", self.flip.as_str())?;
+        }
+        if let Some(quality) = self.quality {
+            write!(f, " quality={}", quality.as_str())?;
+        }
+        if self.effect != Effect::None {
+            write!(f, " effect={}", self.effect.as_str())?;
+        }
+        if self.ambient != AmbientParams::default() {
+            write!(f, " {}", self.ambient)?;
+        }
+        if self.overlay != Overlay::None {
+            write!(f, " overlay={}", self.overlay.as_str())?;
+        }
+        if self.overlay_params != OverlayParams::default() {
+            write!(f, " {}", self.overlay_params)?;
+        }
+        if let Some(trim) = self.trim {
+            write!(f, " trim={trim}")?;
+        }
+        if let Some(crop) = self.crop {
+            write!(f, " crop={crop}")?;
+        }
+        if self.filter != SampleFilter::Linear {
+            write!(f, " filter={}", self.filter.as_str())?;
+        }
+        if self.fit != Fit::Cover {
+            write!(f, " fit={}", self.fit.as_str())?;
+        }
+        if self.gamut != Gamut::Auto {
+            write!(f, " gamut={}", self.gamut)?;
+        }
+        if let Some(clock) = self.clock {
+            write!(f, " clock={}", clock_str(clock))?;
+        }
+        if let Some(timebase) = self.timebase {
+            write!(f, " timebase={}", timebase.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl VideoMapEntry {
+    /// A `layout=` entry; `video` is its first cell's.
+    pub fn from_layout(layout: Layout, options: EntryOptions) -> Self {
+        Self {
+            video: layout
+                .cells
+                .iter()
+                .flatten()
+                .next()
+                .cloned()
+                .unwrap_or_default(),
+            options,
+            layout: Some(layout),
+        }
+    }
+
+    /// Every video the entry plays: each cell's for a layout.
+    pub fn videos(&self) -> Vec<&str> {
+        match &self.layout {
+            Some(layout) => layout.cells.iter().flatten().map(String::as_str).collect(),
+            None => vec![self.video.as_str()],
+        }
+    }
+
+    /// The entry with every video (each cell's for a layout) passed through `f`.
+    pub fn map_videos(&self, mut f: impl FnMut(&str) -> String) -> VideoMapEntry {
+        match &self.layout {
+            Some(layout) => VideoMapEntry::from_layout(
+                Layout {
+                    columns: layout.columns,
+                    rows: layout.rows,
+                    cells: layout
+                        .cells
+                        .iter()
+                        .map(|cell| cell.as_deref().map(&mut f))
+                        .collect(),
+                },
+                self.options,
+            ),
+            None => VideoMapEntry {
+                video: f(&self.video),
+                options: self.options,
+                layout: None,
+            },
+        }
+    }
+
+    /// The entry as `reduce-motion on` shows it: a `still:` of its first frame
+    /// (the start of its trim window) without effect or overlay. A `still:` entry keeps
+    /// its own frame; a layout's cells inherit the still through its options.
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn reduced_motion(mut self) -> Self {
+        if self.options.still.is_none() {
+            let at_ms = self.options.trim.map_or(0, |trim| trim.start_ms);
+            self.options.still = Some(StillFrame { at_ms: Some(at_ms) });
+        }
+        self.options.effect = Effect::None;
+        self.options.overlay = Overlay::None;
+        self
+    }
+
+    /// What cell `index` of a layout entry plays: its video with the entry's
+    /// options. `None` for a black cell or an entry without a layout.
+    #[cfg_attr(not(feature = "wayland-layer"), allow(dead_code))]
+    pub fn cell_entry(&self, index: usize) -> Option<VideoMapEntry> {
+        Some(VideoMapEntry {
+            video: self.layout.as_ref()?.cell(index)?.to_string(),
+            options: self.options,
+            layout: None,
+        })
+    }
+
+    /// The entry for log and terminal lines: path through [`display_path`],
+    /// then options. Use `to_string()` for what goes back into a map file.
+    pub fn display_label(&self) -> String {
+        format!("{}{}", self.display_path(), self.options)
+    }
+
+    /// [`Self::display_label`] for a `status` monitor line: `rotate` and
+    /// `flip` are always shown, the defaults too, right after the path.
+    pub fn status_label(&self) -> String {
+        let rest = EntryOptions {
+            rotate: Rotation::Deg0,
+            flip: Flip::None,
+            ..self.options
+        };
+        format!(
+            "{} rotate={} flip={}{rest}",
+            self.display_path(),
+            self.options.rotate.degrees(),
+            self.options.flip.as_str()
+        )
+    }
+
+    fn display_path(&self) -> String {
+        match &self.layout {
+            Some(layout) => format!("layout={}", layout.format_with(display_path)),
+            None => self.still_path(&display_path(&self.video)),
+        }
+    }
+
+    /// The path as the map spells it: `still:PATH@TIME` for a still entry,
+    /// `layout=gridCxR:PATH,...` for a layout.
+    pub fn map_path(&self) -> String {
+        match &self.layout {
+            Some(layout) => format!("layout={layout}"),
+            None => self.still_path(&self.video),
+        }
+    }
+
+    fn still_path(&self, video: &str) -> String {
+        match self.options.still {
+            Some(still @ StillFrame { at_ms: Some(_) }) => format!("still:{video}@{still}"),
+            // The trailing `@` keeps an `@` in the path from reading as the time.
+            Some(_) if video.contains('@') => format!("still:{video}@"),
+            Some(_) => format!("still:{video}"),
+            None => video.to_string(),
+        }
+    }
 }
 
-pub fn map_file_path_from_env() -> PathBuf {
-    std::env::var("KRC_VIDEO_MAP_FILE")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| default_map_file_path())
+impl std::fmt::Display for VideoMapEntry {
+    /// The map value as written to the map file: path (quoted when needed), then options.
+    /// A layout's cells are quoted one by one instead.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = match &self.layout {
+            Some(_) => self.map_path(),
+            None => quote_video_path(&self.map_path()),
+        };
+        write!(f, "{path}{}", self.options)
+    }
+}
+
+/// Whether a path must be quoted to survive a parse: anything that could be
+/// read as an option separator, a quote, a comment or a `KRC_VIDEO_MAP` separator.
+fn needs_quoting(video: &str) -> bool {
+    video
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '"' | '\\' | '=' | ';' | '#'))
 }
 
-pub fn parse_video_map_env(raw: &str) -> BTreeMap<String, String> {
+/// Writes `video` the way the map file expects it: verbatim when unambiguous,
+/// otherwise wrapped in double quotes with `"` and `\` backslash-escaped.
+pub fn quote_video_path(video: &str) -> String {
+    quote_path(video, needs_quoting(video))
+}
+
+fn quote_path(video: &str, quote: bool) -> String {
+    if !quote {
+        return video.to_string();
+    }
+    let mut out = String::with_capacity(video.len() + 2);
+    out.push('"');
+    for c in video.chars() {
+        if matches!(c, '"' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+/// Reads a quoted path from the start of `raw` (which begins with `"`) and
+/// returns it with the text after the closing quote.
+fn parse_quoted_path(raw: &str) -> Result<(String, &str), String> {
+    let mut video = String::new();
+    let mut chars = raw.char_indices().skip(1);
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Ok((video, &raw[idx + 1..])),
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\'))) => video.push(escaped),
+                Some((_, other)) => {
+                    return Err(format!(
+                        "unsupported escape '\\{other}' in quoted path (only \\\" and \\\\)"
+                    ));
+                }
+                None => break,
+            },
+            _ => video.push(c),
+        }
+    }
+    Err("unterminated quoted path".to_string())
+}
+
+/// Splits a map value into path and options.
+///
+/// A value starting with `"` is a quoted path (`\"` and `\\` escapes) and every
+/// token after it must be an option. Otherwise options are peeled off the end
+/// only while the trailing token is a known `key=value`, so unquoted paths
+/// containing spaces keep working as before. Newlines are never part of a path.
+/// A `still:` prefix is taken off (see [`split_still`]) and the path then
+/// goes through [`normalize_video_path`] against `base`. An unquoted
+/// `layout=gridCxR:...` path is a [`Layout`] instead.
+pub fn parse_entry_value(raw: &str, base: &Path) -> Result<VideoMapEntry, String> {
+    if raw.contains(['\n', '\r']) {
+        return Err("video path contains a newline".to_string());
+    }
+    let mut rest = raw.trim();
+    let mut options = EntryOptions::default();
+    let mut seen = Vec::new();
+    if rest.starts_with('"') {
+        let (video, tail) = parse_quoted_path(rest)?;
+        if !tail.is_empty() && !tail.starts_with(char::is_whitespace) {
+            return Err("expected a space after the closing quote".to_string());
+        }
+        for token in tail.split_whitespace() {
+            let Some((key, value)) = token.split_once('=') else {
+                return Err(format!("unexpected '{token}' after quoted path"));
+            };
+            if seen.contains(&key) {
+                return Err(format!("map option '{key}' given twice"));
+            }
+            options.set(key, value)?;
+            seen.push(key);
+        }
+        let (video, still) = split_still(&video)?;
+        if video.is_empty() {
+            return Err("video path is empty".to_string());
+        }
+        options.still = still;
+        let video = normalize_video_path(video, base)?;
+        return Ok(VideoMapEntry {
+            video,
+            options,
+            layout: None,
+        });
+    }
+    while let Some((head, token)) = rest.rsplit_once(char::is_whitespace) {
+        let Some((key, value)) = token.split_once('=') else {
+            break;
+        };
+        if !OPTION_KEYS.contains(&key) || seen.contains(&key) {
+            break;
+        }
+        options.set(key, value)?;
+        seen.push(key);
+        rest = head.trim_end();
+    }
+    if let Some(layout) = rest.strip_prefix("layout=") {
+        return Ok(VideoMapEntry::from_layout(
+            Layout::parse(layout, base)?,
+            options,
+        ));
+    }
+    let (rest, still) = split_still(rest)?;
+    if rest.is_empty() {
+        return Err("video path is empty".to_string());
+    }
+    options.still = still;
+    Ok(VideoMapEntry {
+        video: normalize_video_path(rest, base)?,
+        options,
+        layout: None,
+    })
+}
+
+/// Splits on `sep` outside double-quoted paths, so a quoted value may contain it.
+fn split_unquoted(raw: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (idx, c) in raw.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == sep && !in_quotes => {
+                parts.push(&raw[start..idx]);
+                start = idx + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&raw[start..]);
+    parts
+}
+
+/// Parses `MON:value;MON:value`. The monitor ends at the first `:`, so values may
+/// contain colons (`DP-1:file:///videos/a.mp4`); a `;` inside a value needs quotes.
+/// Relative paths resolve against the working directory.
+pub fn parse_video_map_env(raw: &str) -> VideoMap {
+    let base = cli_base_dir();
     let mut map = BTreeMap::new();
-    for entry in raw.split(';') {
+    for entry in split_unquoted(raw, ';') {
         let trimmed = entry.trim();
         if trimmed.is_empty() {
             continue;
         }
         let Some((monitor, path)) = trimmed.split_once(':') else {
+            eprintln!(
+                "[rendercore] KRC_VIDEO_MAP entry '{trimmed}' ignored: expected MONITOR:PATH"
+            );
             continue;
         };
         let monitor = monitor.trim();
-        let path = path.trim();
-        if monitor.is_empty() || path.is_empty() {
+        if monitor.is_empty() {
             continue;
         }
-        map.insert(monitor.to_string(), path.to_string());
+        match parse_entry_value(path, &base) {
+            Ok(entry) => {
+                map.insert(monitor.to_string(), entry);
+            }
+            Err(err) => eprintln!("[rendercore] KRC_VIDEO_MAP entry for {monitor} ignored: {err}"),
+        }
     }
     map
 }
 
-pub fn parse_video_map_file(path: &Path) -> BTreeMap<String, String> {
-    let Ok(contents) = fs::read_to_string(path) else {
-        return BTreeMap::new();
-    };
+/// On-disk map format. Each file is read and written back in its own format,
+/// so `set-video` never rewrites a v1 file as v2 or the other way around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapFormat {
+    /// `monitor=path [options]` lines (`video-map.conf`).
+    V1,
+    /// TOML tables (`video-map.toml`), see `map_toml`.
+    V2,
+}
+
+impl MapFormat {
+    /// A `.toml` extension means v2; otherwise the first non-comment line
+    /// decides (`[table]` or `version = ...` is v2). An empty or missing file
+    /// without the extension is v1.
+    pub fn detect(path: &Path, contents: Option<&str>) -> Self {
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+        {
+            return MapFormat::V2;
+        }
+        let first = contents.and_then(|c| {
+            c.lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty() && !line.starts_with('#'))
+        });
+        match first {
+            Some(line)
+                if line.starts_with('[')
+                    || line
+                        .split_once('=')
+                        .is_some_and(|(key, _)| key.trim() == "version") =>
+            {
+                MapFormat::V2
+            }
+            _ => MapFormat::V1,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MapFormat::V1 => "v1",
+            MapFormat::V2 => "v2",
+        }
+    }
+}
+
+/// A parsed map file. Only v2 can hold a default; for v1 files the default
+/// comes from `KRC_VIDEO_DEFAULT` alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapDocument {
+    pub format: MapFormat,
+    pub entries: VideoMap,
+    pub default: Option<VideoMapEntry>,
+}
+
+impl MapDocument {
+    /// The default for outputs without an entry: the file's `[default]`
+    /// overrides `KRC_VIDEO_DEFAULT`, as file entries override `KRC_VIDEO_MAP`.
+    pub fn default_video(&self) -> Option<VideoMapEntry> {
+        self.default.clone().or_else(default_video_from_env)
+    }
+}
+
+/// Loads a map file in whichever format it is in, logging skipped lines. A
+/// missing file is an empty map whose format follows the extension.
+pub fn load_map_document(path: &Path) -> MapDocument {
+    read_map_document(path).unwrap_or_else(|_| parse_map_document(path, "").0)
+}
+
+/// [`load_map_document`] that tells a missing or unreadable file (`Err`)
+/// apart from an empty one, for the renderer's reloads.
+pub fn read_map_document(path: &Path) -> Result<MapDocument, String> {
+    let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let (doc, issues) = parse_map_document(path, &contents);
+    for issue in issues {
+        // Every reload re-reads the file, so a broken line is logged once per
+        // window rather than on each reload.
+        log_limit::eprint_limited("map", &format!("{}: {}", issue.line, issue.message), || {
+            format!(
+                "[rendercore] map line {} ignored: {}",
+                issue.line,
+                display_text(&issue.message)
+            )
+        });
+    }
+    Ok(doc)
+}
+
+/// Parses map file contents without logging, for `watch-map` and
+/// `migrate-map`; relative paths resolve against the file's directory.
+pub fn parse_map_document(path: &Path, contents: &str) -> (MapDocument, Vec<MapLineIssue>) {
+    let base = map_base_dir(path);
+    match MapFormat::detect(path, Some(contents)) {
+        MapFormat::V1 => {
+            let (entries, issues) = parse_video_map_lines(contents, &base);
+            let doc = MapDocument {
+                format: MapFormat::V1,
+                entries,
+                default: None,
+            };
+            (doc, issues)
+        }
+        MapFormat::V2 => {
+            let (entries, default, issues) = parse_map_toml(contents, &base);
+            let doc = MapDocument {
+                format: MapFormat::V2,
+                entries,
+                default,
+            };
+            (doc, issues)
+        }
+    }
+}
+
+/// Parses v1 map lines (as `format_video_map` writes them); relative paths
+/// resolve against `base`.
+pub fn parse_video_map_str(contents: &str, base: &Path) -> VideoMap {
+    let (map, issues) = parse_video_map_lines(contents, base);
+    for issue in issues {
+        eprintln!(
+            "[rendercore] map line {} ignored: {}",
+            issue.line,
+            display_text(&issue.message)
+        );
+    }
+    map
+}
+
+/// A map file line the parser skipped.
+#[derive(Debug, Clone)]
+pub struct MapLineIssue {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+/// The v1 map file parser used by the renderer, returning skipped lines instead of
+/// logging them so `watch-map` reports exactly what the renderer ignores.
+pub fn parse_video_map_lines(contents: &str, base: &Path) -> (VideoMap, Vec<MapLineIssue>) {
     let mut map = BTreeMap::new();
-    for line in contents.lines() {
+    let mut issues = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
         let line = line.trim();
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
+        let mut skip = |message: String| {
+            issues.push(MapLineIssue {
+                line: idx + 1,
+                message,
+            })
+        };
         let Some((monitor, video)) = line.split_once('=') else {
+            skip("expected MONITOR=/path/video.mp4".to_string());
             continue;
         };
         let monitor = monitor.trim();
-        let video = video.trim();
-        if monitor.is_empty() || video.is_empty() {
+        if monitor.is_empty() {
+            skip("monitor name is empty".to_string());
             continue;
         }
-        map.insert(monitor.to_string(), video.to_string());
+        if video.trim().is_empty() {
+            skip(format!("({monitor}) video path is empty"));
+            continue;
+        }
+        match parse_entry_value(video, base) {
+            Ok(entry) => {
+                map.insert(monitor.to_string(), entry);
+            }
+            Err(err) => skip(format!("({monitor}) {err}")),
+        }
     }
-    map
+    (map, issues)
 }
 
-#[cfg(feature = "wayland-layer")]
-pub fn merge_maps(
-    env_map: BTreeMap<String, String>,
-    file_map: BTreeMap<String, String>,
-) -> BTreeMap<String, String> {
+pub fn merge_maps(env_map: VideoMap, file_map: VideoMap) -> VideoMap {
     let mut merged = env_map;
     for (k, v) in file_map {
         merged.insert(k, v);
@@ -71,50 +1541,510 @@ pub fn merge_maps(
     merged
 }
 
-pub fn set_monitor_video(path: &Path, monitor: &str, video: &str) -> Result<(), String> {
+/// Parses `KRC_VIDEO_MAP` (`MON:path;...`), honoring `KRC_ENV_FILE` overrides.
+pub fn env_video_map() -> VideoMap {
+    env_file::var("KRC_VIDEO_MAP")
+        .map(|v| parse_video_map_env(&v))
+        .unwrap_or_default()
+}
+
+/// Parses `KRC_VIDEO_DEFAULT` (or `KRC_VIDEO`), which accepts the same options and
+/// path forms (`file://`, `~`) as map values. Either name in the env file
+/// wins over both in the process environment.
+pub fn default_video_from_env() -> Option<VideoMapEntry> {
+    let raw = env_file::first_var(&["KRC_VIDEO_DEFAULT", "KRC_VIDEO"])?;
+    match parse_entry_value(&raw, &cli_base_dir()) {
+        Ok(entry) => Some(entry),
+        Err(err) => {
+            eprintln!("[rendercore] default video ignored: {err}");
+            None
+        }
+    }
+}
+
+/// Picks the entry for one output: explicit mapping first, then the global default.
+pub fn resolve_output_video(
+    merged_map: &VideoMap,
+    default_video: Option<&VideoMapEntry>,
+    output_name: &str,
+) -> Option<VideoMapEntry> {
+    merged_map.get(output_name).or(default_video).cloned()
+}
+
+/// Serializes a map in the same `monitor=path [options]` line format the map file uses.
+pub fn format_video_map(map: &VideoMap) -> String {
+    map.iter().map(|(k, v)| format!("{k}={v}\n")).collect()
+}
+
+pub fn set_monitor_video(path: &Path, monitor: &str, entry: &VideoMapEntry) -> Result<(), String> {
     if monitor.trim().is_empty() {
         return Err("monitor is empty".to_string());
     }
-    if video.trim().is_empty() {
-        return Err("video path is empty".to_string());
+    for video in entry.videos() {
+        if video.trim().is_empty() {
+            return Err("video path is empty".to_string());
+        }
+        if video.contains(['\n', '\r']) {
+            return Err(
+                "video path contains a newline, which the map file cannot hold".to_string(),
+            );
+        }
     }
 
-    let mut map = parse_video_map_file(path);
-    map.insert(monitor.to_string(), video.to_string());
-    write_map_file(path, &map)
+    let mut doc = load_map_document(path);
+    check_monitor_name(doc.format, monitor)?;
+    doc.entries.insert(monitor.to_string(), entry.clone());
+    write_map_document(path, &doc)
+}
+
+/// v1 cannot hold `=` or a leading `#` in a key; neither format holds a newline.
+pub fn check_monitor_name(format: MapFormat, monitor: &str) -> Result<(), String> {
+    let unwritable = match format {
+        MapFormat::V1 => {
+            monitor.contains(['=', '\n', '\r']) || monitor.trim_start().starts_with('#')
+        }
+        MapFormat::V2 => monitor.contains(['\n', '\r']),
+    };
+    if unwritable {
+        return Err(format!(
+            "monitor name '{monitor}' cannot be written to a {} map file",
+            format.label()
+        ));
+    }
+    Ok(())
 }
 
 pub fn unset_monitor_video(path: &Path, monitor: &str) -> Result<bool, String> {
     if monitor.trim().is_empty() {
         return Err("monitor is empty".to_string());
     }
-    let mut map = parse_video_map_file(path);
-    let removed = map.remove(monitor).is_some();
-    write_map_file(path, &map)?;
+    let mut doc = load_map_document(path);
+    let removed = doc.entries.remove(monitor).is_some();
+    write_map_document(path, &doc)?;
     Ok(removed)
 }
 
-pub fn unset_all_monitors(path: &Path, except: &[String]) -> Result<usize, String> {
-    let mut map = parse_video_map_file(path);
-    if map.is_empty() {
-        return Ok(0);
+/// What `unset_all_monitors` did, and what it left for `unset-video --all`
+/// to report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsetAllOutcome {
+    pub removed: usize,
+    /// Entries the filter left in place.
+    pub kept: usize,
+    pub default_removed: bool,
+    /// The file's `[default]` is still there (it was not asked to go).
+    pub default_kept: bool,
+}
+
+/// Removes every entry whose key is `selected`, and with `include_default`
+/// the file's `[default]` too. The file is only rewritten when something
+/// changed.
+pub fn unset_all_monitors(
+    path: &Path,
+    selected: impl Fn(&str) -> bool,
+    include_default: bool,
+) -> Result<UnsetAllOutcome, String> {
+    let mut doc = load_map_document(path);
+    let before = doc.entries.len();
+    doc.entries.retain(|k, _| !selected(k));
+    let removed = before.saturating_sub(doc.entries.len());
+    let default_removed = include_default && doc.default.take().is_some();
+    if removed > 0 || default_removed {
+        write_map_document(path, &doc)?;
     }
-    let before = map.len();
-    map.retain(|k, _| except.iter().any(|e| e == k));
-    let after = map.len();
-    write_map_file(path, &map)?;
-    Ok(before.saturating_sub(after))
+    Ok(UnsetAllOutcome {
+        removed,
+        kept: doc.entries.len(),
+        default_removed,
+        default_kept: doc.default.is_some(),
+    })
 }
 
-fn write_map_file(path: &Path, map: &BTreeMap<String, String>) -> Result<(), String> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("failed to create map directory {}: {e}", parent.display()))?;
+/// Sets (or with `None` clears) the quality preset of existing entries,
+/// keeping their video and other options. `monitors` of `None` means every
+/// entry in the file whose key is `selected`. Returns the monitors changed; a
+/// named monitor without an entry is an error, since an option cannot be
+/// stored without a video.
+pub fn set_entries_quality(
+    path: &Path,
+    monitors: Option<&[String]>,
+    quality: Option<QualityPreset>,
+    selected: impl Fn(&str) -> bool,
+) -> Result<Vec<String>, String> {
+    let mut doc = load_map_document(path);
+    if let Some(monitors) = monitors
+        && let Some(missing) = monitors.iter().find(|m| !doc.entries.contains_key(*m))
+    {
+        return Err(format!(
+            "{missing} has no entry in {}; set a video first with: kitsune-rendercore set-video --monitor {missing} --video <PATH> --quality <PRESET>",
+            path.display()
+        ));
     }
+    let mut changed = Vec::new();
+    for (monitor, entry) in doc.entries.iter_mut() {
+        let selected = match monitors {
+            Some(list) => list.contains(monitor),
+            None => selected(monitor),
+        };
+        if !selected || entry.options.quality == quality {
+            continue;
+        }
+        entry.options.quality = quality;
+        changed.push(monitor.clone());
+    }
+    if !changed.is_empty() {
+        write_map_document(path, &doc)?;
+    }
+    Ok(changed)
+}
+
+/// The file contents for `doc` in its own format.
+pub fn format_map_document(doc: &MapDocument) -> String {
+    match doc.format {
+        MapFormat::V1 => {
+            let mut out = String::from(
+                "# monitor=/absolute/path/video.mp4 [rotate=0|90|180|270] [flip=h|v|hv] [quality=low|medium|high|ultra]\n",
+            );
+            out.push_str(
+                "#   [effect=ambient amplitude=0.05 period=300 drift=0-1] [trim=IN:OUT]\n",
+            );
+            out.push_str("#   [overlay=snow|rain|fireflies density=0.5 speed=1 wind=-1..1]\n");
+            out.push_str("#   [crop=X,Y,W,H (pixels, or 0-1 fractions)] [filter=nearest|linear] [fit=cover|integer]\n");
+            out.push_str("#   [gamut=auto|srgb|native|matrix:M11,M12,...,M33] [clock=on|off]\n");
+            out.push_str("#   [timebase=monotonic|utc]\n");
+            out.push_str("# paths with spaces, '=', '#', ';' or quotes are written as \"...\" with \\\" and \\\\ escapes\n");
+            out.push_str("# monitor=still:/path@00:01:23 shows that one frame instead of playing the video\n");
+            out.push_str("# monitor=layout=grid2x2:/a,/b,/c,/d tiles several videos on the output, row by row\n");
+            out.push_str(&format_video_map(&doc.entries));
+            out
+        }
+        MapFormat::V2 => format_map_toml(&doc.entries, doc.default.as_ref()),
+    }
+}
+
+/// Writes through a temp file and a rename, so the renderer's mtime poll
+/// never reads a half-written map.
+pub fn write_map_document(path: &Path, doc: &MapDocument) -> Result<(), String> {
+    write_atomic(path, format_map_document(doc).as_bytes(), "map file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(raw: &str) -> VideoMapEntry {
+        parse_entry_value(raw, Path::new("/base")).unwrap()
+    }
+
+    /// The shared fixtures: one map written in each format.
+    const FIXTURES: [&str; 2] = ["video-map.v1.conf", "video-map.v2.toml"];
+
+    fn fixture(name: &str) -> (PathBuf, String) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        let contents = fs::read_to_string(&path).unwrap();
+        (path, contents)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("krc-map-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn both_formats_read_the_same_map() {
+        let [v1, v2] = FIXTURES.map(|name| {
+            let (path, contents) = fixture(name);
+            let (doc, issues) = parse_map_document(&path, &contents);
+            assert!(issues.is_empty(), "{name}: {issues:?}");
+            doc
+        });
+        assert_eq!((v1.format, v2.format), (MapFormat::V1, MapFormat::V2));
+        assert_eq!(v1.entries, v2.entries);
+        let map = v1.entries;
+        assert_eq!(map.len(), 8);
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        assert_eq!(
+            Path::new(&map["eDP-1"].video),
+            fixtures.join("loops/relative.mp4")
+        );
+        assert_eq!(map["DP-1"].options.rotate.degrees(), 90);
+        assert_eq!(
+            map["HDMI-A-1"].video,
+            "/srv/loops/live wallpapers/a=b #1.mp4"
+        );
+        assert!(map["DP-4"].options.still.is_some());
+        assert_eq!(map["DP-5"].videos().len(), 2);
+        assert!(map.contains_key("Dell Inc. U2720Q"));
+    }
+
+    #[test]
+    fn both_formats_round_trip_and_convert_into_each_other() {
+        for name in FIXTURES {
+            let (path, contents) = fixture(name);
+            let (doc, _) = parse_map_document(&path, &contents);
+            for format in [MapFormat::V1, MapFormat::V2] {
+                let converted = MapDocument {
+                    format,
+                    entries: doc.entries.clone(),
+                    default: None,
+                };
+                let written = format_map_document(&converted);
+                let target = path.with_extension(match format {
+                    MapFormat::V1 => "conf",
+                    MapFormat::V2 => "toml",
+                });
+                let (reread, issues) = parse_map_document(&target, &written);
+                assert!(issues.is_empty(), "{name} as {format:?}: {issues:?}");
+                assert_eq!(reread.format, format);
+                assert_eq!(reread.entries, doc.entries, "{name} as {format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn both_formats_skip_only_the_broken_entry() {
+        let broken = [
+            "DP-9=/srv/loops/bad.mp4 rotate=45\n",
+            "\n[monitors.DP-9]\nvideo = \"/srv/loops/bad.mp4\"\nrotate = 45\n",
+        ];
+        for (name, extra) in FIXTURES.into_iter().zip(broken) {
+            let (path, contents) = fixture(name);
+            let (doc, issues) = parse_map_document(&path, &format!("{contents}{extra}"));
+            assert_eq!(doc.entries.len(), 8, "{name}");
+            assert!(!doc.entries.contains_key("DP-9"), "{name}");
+            assert_eq!(issues.len(), 1, "{name}: {issues:?}");
+            assert_eq!(
+                issues[0].line,
+                contents.lines().count() + extra.lines().count(),
+                "{name}"
+            );
+            assert!(issues[0].message.contains("rotate"), "{name}: {issues:?}");
+        }
+    }
+
+    const POPULATED: &str = "version = 2\n\n[default]\nvideo = \"/v/default.mp4\"\n\n[monitors.DP-1]\nvideo = \"/v/a.mp4\"\n\n[monitors.DP-2]\nvideo = \"/v/b.mp4\"\n\n[monitors.HDMI-A-1]\nvideo = \"/v/c.mp4\"\n";
 
-    let mut out = String::from("# monitor=/absolute/path/video.mp4\n");
-    for (k, v) in map {
-        out.push_str(&format!("{k}={v}\n"));
+    #[test]
+    fn unset_all_covers_each_flag_combination() {
+        let dir = temp_dir("unset");
+        let path = dir.join("video-map.toml");
+        // (case, --except DP-1, --include-default, outcome, entries left)
+        let cases = [
+            ("all", false, false, (3, 0, false, true), vec![]),
+            ("all+default", false, true, (3, 0, true, false), vec![]),
+            ("except", true, false, (2, 1, false, true), vec!["DP-1"]),
+            (
+                "except+default",
+                true,
+                true,
+                (2, 1, true, false),
+                vec!["DP-1"],
+            ),
+        ];
+        for (name, except_dp1, include_default, want, left) in cases {
+            let selected = |key: &str| !(except_dp1 && key == "DP-1");
+            fs::write(&path, POPULATED).unwrap();
+            let outcome = unset_all_monitors(&path, selected, include_default).unwrap();
+            let got = (
+                outcome.removed,
+                outcome.kept,
+                outcome.default_removed,
+                outcome.default_kept,
+            );
+            assert_eq!(got, want, "{name}");
+            let written = fs::read_to_string(&path).unwrap();
+            let doc = load_map_document(&path);
+            assert_eq!(doc.format, MapFormat::V2, "{name}");
+            assert_eq!(doc.entries.keys().collect::<Vec<_>>(), left, "{name}");
+            assert_eq!(
+                written.contains("\n[default]\n"),
+                !include_default,
+                "{name}"
+            );
+            assert_eq!(
+                written.contains("/v/default.mp4"),
+                !include_default,
+                "{name}"
+            );
+        }
+
+        // Nothing to remove: the file is left byte for byte.
+        let untouched = "# hand-written\nversion = 2\n";
+        fs::write(&path, untouched).unwrap();
+        let outcome = unset_all_monitors(&path, |_| true, true).unwrap();
+        assert_eq!((outcome.removed, outcome.default_removed), (0, false));
+        assert_eq!(fs::read_to_string(&path).unwrap(), untouched);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn set_video_keeps_each_file_in_its_own_format() {
+        let dir = temp_dir("formats");
+        for name in FIXTURES {
+            let (_, contents) = fixture(name);
+            let path = dir.join(name);
+            fs::write(&path, &contents).unwrap();
+            let before = load_map_document(&path);
+            set_monitor_video(&path, "DP-1", &entry("/srv/loops/sun.mp4 flip=v")).unwrap();
+            let after = load_map_document(&path);
+            assert_eq!(after.format, before.format, "{name}");
+            assert_eq!(after.entries["DP-1"], entry("/srv/loops/sun.mp4 flip=v"));
+            assert_eq!(after.entries.len(), before.entries.len());
+            let written = fs::read_to_string(&path).unwrap();
+            assert_eq!(
+                written.contains("[monitors.DP-1]"),
+                before.format == MapFormat::V2,
+                "{name}"
+            );
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn status_label_always_shows_rotate_and_flip() {
+        assert_eq!(
+            entry("/srv/a.mp4").status_label(),
+            "/srv/a.mp4 rotate=0 flip=none"
+        );
+        assert_eq!(
+            entry("/srv/a.mp4 quality=low flip=hv rotate=270").status_label(),
+            "/srv/a.mp4 rotate=270 flip=hv quality=low"
+        );
+        // The map's own spelling keeps leaving the defaults out.
+        assert_eq!(entry("/srv/a.mp4").display_label(), "/srv/a.mp4");
+    }
+
+    /// Legal Unix paths that look like options, quotes, comments or
+    /// separators.
+    const AWKWARD_PATHS: &[&str] = &[
+        "/home/u/Videos/live wallpapers/a=b test.mp4",
+        "/v/  two  spaces  .mp4",
+        "/v/trailing space .mp4",
+        "/v/quality=low",
+        "/v/a.mp4 quality=low",
+        "/v/say \"hi\".mp4",
+        "/v/back\\slash.mp4",
+        "/v/ends with backslash\\",
+        "/v/#not-a-comment.mp4",
+        "/v/semi;colon.mp4",
+        "/v/tab\there.mp4",
+        "/v/colon:in:name.mp4",
+        "/v/ünïcödé 動画.mp4",
+        "/v/plain.mp4",
+    ];
+
+    #[test]
+    fn quoted_paths_round_trip() {
+        for path in AWKWARD_PATHS {
+            let written = quote_video_path(path);
+            assert_eq!(entry(&written).video, *path, "{written}");
+            let with_options = format!("{written} rotate=90 flip=h");
+            let parsed = entry(&with_options);
+            assert_eq!(parsed.video, *path, "{with_options}");
+            assert_eq!(parsed.options.rotate.degrees(), 90);
+        }
+        assert_eq!(quote_video_path("/v/plain.mp4"), "/v/plain.mp4");
+        assert_eq!(quote_video_path("/v/a b.mp4"), "\"/v/a b.mp4\"");
+        assert_eq!(quote_video_path("/v/q\"\\"), "\"/v/q\\\"\\\\\"");
+    }
+
+    #[test]
+    fn unquoted_values_keep_spaces_and_peel_known_options() {
+        assert_eq!(entry("/v/a b.mp4").video, "/v/a b.mp4");
+        assert_eq!(entry("  /v/a b.mp4  ").video, "/v/a b.mp4");
+        let parsed = entry("/v/a b.mp4 quality=low");
+        assert_eq!(
+            (
+                parsed.video.as_str(),
+                parsed.options.quality.map(QualityPreset::as_str)
+            ),
+            ("/v/a b.mp4", Some("low"))
+        );
+        // Only known keys are options; the rest stays part of the path.
+        assert_eq!(entry("/v/a b=c.mp4").video, "/v/a b=c.mp4");
+        assert_eq!(entry("/v/a.mp4 look=here").video, "/v/a.mp4 look=here");
+    }
+
+    #[test]
+    fn malformed_quoted_values_are_rejected() {
+        let base = Path::new("/base");
+        for (raw, want) in [
+            ("\"/v/a.mp4", "unterminated"),
+            ("\"/v/a.mp4\\\"", "unterminated"),
+            ("\"/v/a\\n.mp4\"", "unsupported escape"),
+            ("\"/v/a.mp4\"rotate=90", "space after the closing quote"),
+            ("\"/v/a.mp4\" extra", "unexpected 'extra'"),
+            ("\"/v/a.mp4\" rotate=90 rotate=180", "given twice"),
+            ("\"\"", "empty"),
+        ] {
+            let err = parse_entry_value(raw, base).unwrap_err();
+            assert!(err.contains(want), "{raw}: {err}");
+        }
+    }
+
+    #[test]
+    fn newlines_are_never_part_of_a_path() {
+        let base = Path::new("/base");
+        for raw in ["/v/a\nb.mp4", "\"/v/a\nb.mp4\"", "/v/a\rb.mp4"] {
+            assert!(
+                parse_entry_value(raw, base)
+                    .unwrap_err()
+                    .contains("newline")
+            );
+        }
+        let dir = temp_dir("newline");
+        let path = dir.join("video-map.conf");
+        let mut bad = entry("/v/a.mp4");
+        bad.video = "/v/a\nDP-2=/v/b.mp4".to_string();
+        let err = set_monitor_video(&path, "DP-1", &bad).unwrap_err();
+        assert!(err.contains("newline"), "{err}");
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn map_lines_split_on_the_first_equals_sign() {
+        let (map, issues) = parse_video_map_lines(
+            "# comment\nDP-1=/v/a=b test.mp4\nHDMI-A-1 = \"/v/x=y z.mp4\" flip=v\nnope\n=/v/a.mp4\nDP-3=\n",
+            Path::new("/base"),
+        );
+        assert_eq!(map["DP-1"].video, "/v/a=b test.mp4");
+        assert_eq!(map["HDMI-A-1"].video, "/v/x=y z.mp4");
+        assert_eq!(map["HDMI-A-1"].options.flip.as_str(), "v");
+        let lines = issues.iter().map(|issue| issue.line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn env_map_values_may_hold_colons_and_quoted_semicolons() {
+        let map = parse_video_map_env(
+            "DP-1:file:///v/a%20b.mp4; HDMI-A-1:\"/v/semi;colon.mp4\" rotate=180;broken;:/v/x.mp4",
+        );
+        assert_eq!(map["DP-1"].video, "/v/a b.mp4");
+        assert_eq!(map["HDMI-A-1"].video, "/v/semi;colon.mp4");
+        assert_eq!(map["HDMI-A-1"].options.rotate.degrees(), 180);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn set_video_round_trips_awkward_paths() {
+        let dir = temp_dir("roundtrip");
+        let path = dir.join("video-map.conf");
+        for (idx, video) in AWKWARD_PATHS.iter().enumerate() {
+            let mut wanted = entry("/v/a.mp4 rotate=270");
+            wanted.video = video.to_string();
+            let monitor = format!("DP-{idx}");
+            set_monitor_video(&path, &monitor, &wanted).unwrap();
+            let doc = load_map_document(&path);
+            assert_eq!(doc.entries[&monitor], wanted, "{video}");
+        }
+        let text = format_video_map(&load_map_document(&path).entries);
+        let reparsed = parse_video_map_str(&text, Path::new("/base"));
+        assert_eq!(reparsed.len(), AWKWARD_PATHS.len());
+        let _ = std::fs::remove_dir_all(&dir);
     }
-    fs::write(path, out).map_err(|e| format!("failed to write {}: {e}", path.display()))
 }
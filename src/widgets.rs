@@ -0,0 +1,297 @@
+//! Widgets drawn into the wallpaper itself, so they sit under every window:
+//! one line of text in the embedded [`bitmap_font`](crate::bitmap_font),
+//! placed by an anchor and a margin and scaled by whole texels. The clock is
+//! the only one so far; another text widget is a config section, a way to
+//! produce its text, and the same [`Placement`] and [`TextStyle`].
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config_file::ConfigFile;
+use crate::quiet_hours;
+
+pub const CLOCK_SECTION: &str = "widgets.clock";
+
+/// The corner, edge or center a widget is placed against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    const ALL: [Self; 9] = [
+        Self::TopLeft,
+        Self::Top,
+        Self::TopRight,
+        Self::Left,
+        Self::Center,
+        Self::Right,
+        Self::BottomLeft,
+        Self::Bottom,
+        Self::BottomRight,
+    ];
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim().to_ascii_lowercase();
+        Self::ALL.into_iter().find(|anchor| anchor.as_str() == raw)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TopLeft => "top-left",
+            Self::Top => "top",
+            Self::TopRight => "top-right",
+            Self::Left => "left",
+            Self::Center => "center",
+            Self::Right => "right",
+            Self::BottomLeft => "bottom-left",
+            Self::Bottom => "bottom",
+            Self::BottomRight => "bottom-right",
+        }
+    }
+
+    /// Column and row of the anchor: 0 start, 1 middle, 2 end.
+    fn slots(self) -> (u32, u32) {
+        let index = Self::ALL.iter().position(|a| *a == self).unwrap_or(0) as u32;
+        (index % 3, index / 3)
+    }
+}
+
+/// Where a widget sits on its output and how large it is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub anchor: Anchor,
+    /// Distance from the anchored edges, in logical pixels; the middle
+    /// anchors ignore it on their centered axis.
+    pub margin: u32,
+    /// Logical pixels per font texel.
+    pub scale: u32,
+}
+
+impl Placement {
+    /// The display-pixel rect `(x0, y0, x1, y1)` of `content` texels on an
+    /// `area` at `buffer_scale`. Texels stay whole pixels, so a widget that
+    /// does not fit is drawn at a smaller scale rather than stretched.
+    pub fn rect(
+        &self,
+        content: (u32, u32),
+        area: (u32, u32),
+        buffer_scale: u32,
+    ) -> (u32, u32, u32, u32) {
+        let buffer_scale = buffer_scale.max(1);
+        let texel = (self.scale * buffer_scale)
+            .min(area.0 / content.0.max(1))
+            .min(area.1 / content.1.max(1))
+            .max(1);
+        let width = (content.0 * texel).min(area.0);
+        let height = (content.1 * texel).min(area.1);
+        let margin = self.margin * buffer_scale;
+        let (column, row) = self.anchor.slots();
+        let x0 = align(column, width, area.0, margin);
+        let y0 = align(row, height, area.1, margin);
+        (x0, y0, x0 + width, y0 + height)
+    }
+}
+
+/// Start of a `size` span in `area`: `margin` in from the start or the end,
+/// or centered; never past either edge.
+fn align(slot: u32, size: u32, area: u32, margin: u32) -> u32 {
+    let free = area.saturating_sub(size);
+    match slot {
+        0 => margin.min(free),
+        1 => free / 2,
+        _ => free.saturating_sub(margin),
+    }
+}
+
+/// The color text is drawn in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// sRGB.
+    pub color: [u8; 3],
+    /// 0 (invisible) to 1.
+    pub opacity: f32,
+}
+
+impl TextStyle {
+    /// Linear RGB and opacity, as the shader blends them.
+    pub fn linear_rgba(&self) -> [f32; 4] {
+        let [r, g, b] = self.color.map(srgb_to_linear);
+        [r, g, b, self.opacity]
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// `#rrggbb` (the `#` optional).
+fn parse_color(raw: &str) -> Option<[u8; 3]> {
+    let hex = raw.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// `[widgets.clock]` from the config file. Bad values are warned about and
+/// fall back to their default, like the rest of the config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockConfig {
+    /// Shown on outputs whose entry has no `clock=on|off` of its own.
+    pub enabled: bool,
+    /// `strftime(3)` format, in local time.
+    pub format: String,
+    pub placement: Placement,
+    pub style: TextStyle,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: "%H:%M".to_string(),
+            placement: Placement {
+                anchor: Anchor::BottomRight,
+                margin: 48,
+                scale: 6,
+            },
+            style: TextStyle {
+                color: [255, 255, 255],
+                opacity: 0.85,
+            },
+        }
+    }
+}
+
+impl ClockConfig {
+    pub fn load(path: &Path) -> Self {
+        Self::from_config(&ConfigFile::load(path))
+    }
+
+    pub fn from_config(config: &ConfigFile) -> Self {
+        let mut clock = Self::default();
+        let get = |key: &str| config.get(CLOCK_SECTION, key).map(str::trim);
+        if let Some(raw) = get("enabled") {
+            match raw {
+                "true" => clock.enabled = true,
+                "false" => clock.enabled = false,
+                _ => ignored("enabled", raw, "true or false"),
+            }
+        }
+        if let Some(raw) = get("format") {
+            if quiet_hours::format_local(now_epoch(), raw).is_some() {
+                clock.format = raw.to_string();
+            } else {
+                ignored("format", raw, "a strftime format that gives some text");
+            }
+        }
+        if let Some(raw) = get("anchor") {
+            match Anchor::parse(raw) {
+                Some(anchor) => clock.placement.anchor = anchor,
+                None => ignored("anchor", raw, &Anchor::ALL.map(Anchor::as_str).join("|")),
+            }
+        }
+        if let Some(margin) = config.get_u64(CLOCK_SECTION, "margin") {
+            clock.placement.margin = margin.min(10_000) as u32;
+        }
+        if let Some(raw) = get("scale") {
+            match raw.parse::<u32>() {
+                Ok(scale @ 1..=32) => clock.placement.scale = scale,
+                _ => ignored("scale", raw, "1-32"),
+            }
+        }
+        if let Some(raw) = get("color") {
+            match parse_color(raw) {
+                Some(color) => clock.style.color = color,
+                None => ignored("color", raw, "\"#rrggbb\""),
+            }
+        }
+        if let Some(raw) = get("opacity") {
+            match raw.parse::<f32>() {
+                Ok(opacity) if (0.0..=1.0).contains(&opacity) => clock.style.opacity = opacity,
+                _ => ignored("opacity", raw, "0-1"),
+            }
+        }
+        clock
+    }
+
+    /// Whether an output shows the clock, given its entry's `clock=`.
+    pub fn shown(&self, entry: Option<bool>) -> bool {
+        entry.unwrap_or(self.enabled)
+    }
+}
+
+impl std::fmt::Display for ClockConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [r, g, b] = self.style.color;
+        write!(
+            f,
+            "{} format='{}' anchor={} margin={} scale={} color=#{r:02x}{g:02x}{b:02x} opacity={}",
+            if self.enabled { "on" } else { "off" },
+            self.format,
+            self.placement.anchor.as_str(),
+            self.placement.margin,
+            self.placement.scale,
+            self.style.opacity
+        )
+    }
+}
+
+fn ignored(key: &str, raw: &str, expected: &str) {
+    eprintln!("[rendercore] config [{CLOCK_SECTION}] {key}='{raw}' ignored: expected {expected}");
+}
+
+fn now_epoch() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// The clock's text, formatted again only when the wall-clock second
+/// changes: however often the renderer asks, the text (and so the pixels
+/// and damage that follow it) changes at most once a second.
+pub struct Clock {
+    pub config: ClockConfig,
+    second: Option<i64>,
+    text: String,
+}
+
+impl Clock {
+    pub fn new(config: ClockConfig) -> Self {
+        Self {
+            config,
+            second: None,
+            text: String::new(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: ClockConfig) {
+        self.config = config;
+        self.second = None;
+    }
+
+    pub fn text(&mut self) -> &str {
+        let now = now_epoch();
+        if self.second != Some(now) {
+            self.second = Some(now);
+            self.text = quiet_hours::format_local(now, &self.config.format).unwrap_or_default();
+        }
+        &self.text
+    }
+}
@@ -0,0 +1,266 @@
+//! The HTTP control endpoint against an embedded renderer with a backend
+//! that draws nothing, called the way `curl` would:
+//!
+//!     cargo test --features http-control --test http_control
+//!
+//! The same calls with curl, against a service with `[http] listen = "7878"`
+//! and `token = "secret"` in its config file:
+//!
+//!     curl -H 'Authorization: Bearer secret' http://127.0.0.1:7878/status
+//!     curl -X POST -H 'Authorization: Bearer secret' \
+//!          -H 'Content-Type: application/json' http://127.0.0.1:7878/pause \
+//!          -d '{"reason":"movie night"}'
+//!     curl -X POST -H 'Authorization: Bearer secret' \
+//!          -H 'Content-Type: application/json' http://127.0.0.1:7878/set-video \
+//!          -d '{"monitor":"DP-1","path":"/srv/loops/rain.mp4","transient":true}'
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use kitsune_rendercore::{
+    HttpConfig, LayerBackend, LayerRole, MonitorInfo, MonitorSurfaceSpec, OutputRuntimeStatus,
+    OutputTransform, RenderCoreBuilder, RenderCoreConfig,
+};
+
+const TOKEN: &str = "selftest";
+const JSON: &str = "Content-Type: application/json\r\n";
+
+/// One virtual output and no video map, so `set-video` is refused.
+struct HeadlessBackend;
+
+impl LayerBackend for HeadlessBackend {
+    fn name(&self) -> &'static str {
+        "http-test"
+    }
+
+    fn bootstrap(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn discover_monitors(&mut self) -> Result<Vec<MonitorInfo>, String> {
+        Ok(vec![MonitorInfo {
+            name: "HEADLESS-1".to_string(),
+            width: 1920,
+            height: 1080,
+            refresh_hz: 60,
+            transform: OutputTransform::Normal,
+        }])
+    }
+
+    fn build_surfaces(
+        &mut self,
+        monitors: &[MonitorInfo],
+    ) -> Result<Vec<MonitorSurfaceSpec>, String> {
+        Ok(monitors
+            .iter()
+            .map(|monitor| MonitorSurfaceSpec {
+                monitor: monitor.clone(),
+                layer: LayerRole::Background,
+            })
+            .collect())
+    }
+
+    fn render_frame(&mut self, _surfaces: &[MonitorSurfaceSpec]) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn output_status(&self) -> Vec<OutputRuntimeStatus> {
+        Vec::new()
+    }
+}
+
+/// Runs an embedded renderer serving HTTP on a free loopback port, calls
+/// `test` with the address once it answers, then shuts the renderer down.
+fn with_renderer(test: impl FnOnce(SocketAddr) + Send + 'static) {
+    let listen = TcpListener::bind("127.0.0.1:0")
+        .and_then(|probe| probe.local_addr())
+        .expect("no free port");
+    let config = RenderCoreConfig {
+        target_fps: 10,
+        quiet_hours: None,
+        report_interval: None,
+        ..RenderCoreConfig::default()
+    };
+    let (core, handle) = RenderCoreBuilder::new(config)
+        .backend(Box::new(HeadlessBackend))
+        .http_control(HttpConfig {
+            listen,
+            token: Some(TOKEN.to_string()),
+        })
+        .build();
+
+    let tester = thread::spawn(move || {
+        let up = Instant::now();
+        while TcpStream::connect(listen).is_err() {
+            assert!(
+                up.elapsed() < Duration::from_secs(5),
+                "listener never came up"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| test(listen)));
+        handle.shutdown().expect("shutdown");
+        if let Err(panic) = result {
+            std::panic::resume_unwind(panic);
+        }
+    });
+    core.run().expect("renderer failed");
+    tester.join().expect("test failed");
+}
+
+/// Sends `head` (request line and headers, without the blank line) and
+/// `body`, and returns the status code and body of the response.
+fn send(addr: SocketAddr, head: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).expect("connect");
+    let request = format!("{head}Content-Length: {}\r\n\r\n{body}", body.len());
+    stream.write_all(request.as_bytes()).expect("send");
+    read_response(&mut stream)
+}
+
+fn read_response(stream: &mut TcpStream) -> (u16, String) {
+    let mut response = String::new();
+    stream.read_to_string(&mut response).expect("read");
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or_else(|| panic!("no status line in {response:?}"));
+    let body = response
+        .split_once("\r\n\r\n")
+        .map_or("", |(_, body)| body.trim());
+    (status, body.to_string())
+}
+
+/// A request as curl sends it: the right Host, the token, and a JSON
+/// content type on POSTs.
+fn call(addr: SocketAddr, method: &str, path: &str, token: Option<&str>, body: &str) -> u16 {
+    let auth = token.map_or_else(String::new, |t| format!("Authorization: Bearer {t}\r\n"));
+    let kind = if method == "POST" { JSON } else { "" };
+    let head = format!("{method} {path} HTTP/1.1\r\nHost: {addr}\r\n{auth}{kind}");
+    send(addr, &head, body).0
+}
+
+#[test]
+fn endpoints_answer_with_their_status_codes() {
+    with_renderer(|addr| {
+        let oversized = format!("{{\"reason\":\"{}\"}}", "x".repeat(32 * 1024));
+        let checks: &[(&str, &str, Option<&str>, &str, u16)] = &[
+            ("GET", "/status", Some(TOKEN), "", 200),
+            ("GET", "/status", None, "", 401),
+            ("GET", "/status", Some("wrong"), "", 401),
+            ("POST", "/pause", Some(TOKEN), r#"{"reason":"test"}"#, 200),
+            ("POST", "/resume", Some(TOKEN), "", 200),
+            (
+                "POST",
+                "/pause",
+                Some(TOKEN),
+                r#"{"monitor":"NOPE-9"}"#,
+                400,
+            ),
+            ("POST", "/reload", Some(TOKEN), r#"{"hard":false}"#, 400),
+            (
+                "POST",
+                "/set-video",
+                Some(TOKEN),
+                r#"{"monitor":"HEADLESS-1","path":"/srv/loops/rain.mp4","transient":true}"#,
+                400,
+            ),
+            (
+                "POST",
+                "/set-video",
+                Some(TOKEN),
+                r#"{"path":"/a.mp4"}"#,
+                400,
+            ),
+            ("POST", "/pause", Some(TOKEN), "{not json", 400),
+            ("POST", "/pause", Some(TOKEN), &oversized, 413),
+            ("GET", "/pause", Some(TOKEN), "", 405),
+            ("GET", "/nowhere", Some(TOKEN), "", 404),
+        ];
+        for &(method, path, token, body, want) in checks {
+            assert_eq!(
+                call(addr, method, path, token, body),
+                want,
+                "{method} {path}"
+            );
+        }
+    });
+}
+
+#[test]
+fn foreign_host_headers_are_refused() {
+    with_renderer(|addr| {
+        let port = addr.port();
+        let auth = format!("Authorization: Bearer {TOKEN}\r\n");
+        for (host, want) in [
+            (format!("127.0.0.1:{port}"), 200),
+            (format!("localhost:{port}"), 200),
+            (format!("[::1]:{port}"), 200),
+            (format!("evil.example:{port}"), 403),
+            ("127.0.0.1".to_string(), 403),
+            (format!("localhost:{}", port.wrapping_add(1)), 403),
+        ] {
+            let head = format!("GET /status HTTP/1.1\r\nHost: {host}\r\n{auth}");
+            assert_eq!(send(addr, &head, "").0, want, "Host: {host}");
+        }
+        let head = format!("GET /status HTTP/1.1\r\n{auth}");
+        assert_eq!(send(addr, &head, "").0, 403, "no Host");
+    });
+}
+
+#[test]
+fn posts_need_a_json_content_type() {
+    with_renderer(|addr| {
+        let base =
+            format!("POST /resume HTTP/1.1\r\nHost: {addr}\r\nAuthorization: Bearer {TOKEN}\r\n");
+        for (kind, want) in [
+            ("", 415),
+            ("Content-Type: text/plain\r\n", 415),
+            ("Content-Type: application/x-www-form-urlencoded\r\n", 415),
+            ("Content-Type: application/json; charset=utf-8\r\n", 200),
+            ("Content-Type: Application/JSON\r\n", 200),
+        ] {
+            assert_eq!(
+                send(addr, &format!("{base}{kind}"), "{}").0,
+                want,
+                "{kind:?}"
+            );
+        }
+    });
+}
+
+#[test]
+fn a_slow_client_holds_up_nobody_and_is_cut_off() {
+    with_renderer(|addr| {
+        let mut slow = TcpStream::connect(addr).expect("connect");
+        slow.write_all(b"GET /status HTTP/1.1\r\n").expect("send");
+        let trickle = thread::spawn(move || {
+            let started = Instant::now();
+            // A byte now and then keeps any per-read timeout from firing.
+            for _ in 0..20 {
+                thread::sleep(Duration::from_millis(250));
+                if slow.write_all(b"X").is_err() {
+                    break;
+                }
+            }
+            let mut rest = Vec::new();
+            let _ = slow.read_to_end(&mut rest);
+            started.elapsed()
+        });
+
+        let started = Instant::now();
+        assert_eq!(call(addr, "GET", "/status", Some(TOKEN), ""), 200);
+        assert!(
+            started.elapsed() < Duration::from_secs(1),
+            "status waited on the slow client"
+        );
+
+        let cut_off = trickle.join().expect("slow client panicked");
+        assert!(
+            cut_off < Duration::from_secs(4),
+            "slow client kept for {cut_off:?}"
+        );
+    });
+}